@@ -1 +1,84 @@
 //! Core module
+//!
+//! Fault scenarios the engine knows how to inject.
+
+use serde::{Deserialize, Serialize};
+
+use crate::strategies::CorruptionType;
+
+/// A fault to inject into a target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FaultScenario {
+    /// Add latency to outgoing network traffic
+    NetworkLatency {
+        /// Added delay in milliseconds
+        delay_ms: u64,
+        /// Random jitter in milliseconds
+        jitter_ms: u64,
+    },
+    /// Drop traffic to the given destinations
+    NetworkPartition {
+        /// Blocked hosts or CIDR ranges
+        blocked_destinations: Vec<String>,
+    },
+    /// Send a signal to the target process
+    ProcessKill {
+        /// Signal number, e.g. 9 for `SIGKILL`
+        signal: i32,
+    },
+    /// Suspend the target process
+    ProcessPause {
+        /// How long to keep the process stopped, in milliseconds
+        duration_ms: u64,
+    },
+    /// Shift the target's clock
+    ClockSkew {
+        /// Offset in milliseconds (may be negative)
+        offset_ms: i64,
+    },
+    /// Consume a local resource
+    ResourceExhaustion {
+        /// Resource to consume
+        resource: ResourceKind,
+        /// Bytes to allocate; ignored for CPU exhaustion
+        #[serde(default)]
+        target_bytes: u64,
+    },
+    /// Force a plugin's executions to time out early
+    PluginTimeout {
+        /// Forced timeout in milliseconds
+        timeout_ms: u64,
+    },
+    /// Corrupt the outputs of a plugin
+    StateCorruption {
+        /// Damage done to each output
+        corruption: CorruptionType,
+    },
+}
+
+/// Resource consumed by [`FaultScenario::ResourceExhaustion`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    /// Hold allocated memory
+    Memory,
+    /// Keep a core busy
+    Cpu,
+}
+
+impl FaultScenario {
+    /// Short snake_case name of the scenario, e.g. `"network_latency"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NetworkLatency { .. } => "network_latency",
+            Self::NetworkPartition { .. } => "network_partition",
+            Self::ProcessKill { .. } => "process_kill",
+            Self::ProcessPause { .. } => "process_pause",
+            Self::ClockSkew { .. } => "clock_skew",
+            Self::ResourceExhaustion { .. } => "resource_exhaustion",
+            Self::PluginTimeout { .. } => "plugin_timeout",
+            Self::StateCorruption { .. } => "state_corruption",
+        }
+    }
+}
@@ -6,7 +6,20 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use shared_core::{Id, InterceptorGuard, PluginRegistry, Result, SystemError, Timestamp};
+
+use crate::core::FaultScenario;
+use crate::observers::{FaultClearedEvent, FaultEvent, Observer, PrometheusObserver};
+use crate::reporters::{ExperimentRecorder, ExperimentReport};
+use crate::strategies::{
+    FaultHandle, ResourceExhaustionStrategy, SlowPlugin, StateCorruptionStrategy, Suspend,
+};
 
 pub mod api;
 pub mod core;
@@ -14,6 +27,9 @@ pub mod observers;
 pub mod reporters;
 pub mod strategies;
 
+/// Target of faults on the process running the engine
+const LOCAL_PROCESS: &str = "local process";
+
 /// Chaos engine configuration
 #[derive(Debug, Clone)]
 pub struct ChaosEngineConfig {
@@ -21,6 +37,8 @@ pub struct ChaosEngineConfig {
     pub max_concurrent_faults: usize,
     /// Observer polling interval in milliseconds
     pub observer_poll_interval_ms: u64,
+    /// Register a [`PrometheusObserver`] exporting chaos metrics
+    pub enable_metrics: bool,
 }
 
 impl Default for ChaosEngineConfig {
@@ -28,37 +46,389 @@ impl Default for ChaosEngineConfig {
         Self {
             max_concurrent_faults: 10,
             observer_poll_interval_ms: 100,
+            enable_metrics: false,
+        }
+    }
+}
+
+/// Lifecycle state of the engine's experiment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExperimentState {
+    /// No experiment has been started
+    #[default]
+    Idle,
+    /// An experiment is running and faults may be injected
+    Running,
+    /// The experiment is running but its faults are suspended and no new
+    /// faults may be injected
+    Paused,
+    /// The last experiment was stopped
+    Completed,
+}
+
+impl fmt::Display for ExperimentState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Idle => "idle",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Completed => "completed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// State of the current or last experiment and when it ran
+#[derive(Default)]
+struct ExperimentWindow {
+    state: ExperimentState,
+    started_at: Option<Instant>,
+    stopped_at: Option<Instant>,
+    /// Faults injected during the experiment, dead once their guards drop
+    faults: Vec<Weak<dyn Suspend>>,
+}
+
+impl ExperimentWindow {
+    /// Fail with `InvalidState` unless the experiment is in one of `allowed`
+    /// states, `operation` naming what was attempted
+    fn require(&self, operation: &str, allowed: &[ExperimentState]) -> Result<()> {
+        if allowed.contains(&self.state) {
+            return Ok(());
+        }
+        let expected: Vec<String> = allowed.iter().map(ToString::to_string).collect();
+        Err(SystemError::InvalidState {
+            message: format!("cannot {operation} a {} experiment", self.state),
+            current_state: Some(self.state.to_string()),
+            expected_state: Some(expected.join(" or ")),
+        })
+    }
+
+    /// Suspend or restore every fault still active, forgetting cleared ones
+    fn suspend_faults(&mut self, suspended: bool) {
+        self.faults.retain(|fault| match fault.upgrade() {
+            Some(fault) => {
+                fault.set_suspended(suspended);
+                true
+            },
+            None => false,
+        });
+    }
+
+    /// Fail with `InvalidState` unless faults may be injected, otherwise
+    /// track the fault `activate` injects so that pausing suspends it
+    fn inject<T>(
+        &mut self,
+        activate: impl FnOnce() -> Result<(T, Weak<dyn Suspend>)>,
+    ) -> Result<T> {
+        self.require("inject faults into", &[ExperimentState::Running])?;
+        let (guard, fault) = activate()?;
+        self.faults.retain(|fault| fault.strong_count() > 0);
+        self.faults.push(fault);
+        Ok(guard)
+    }
+}
+
+/// Observers the engine notifies of its faults, shared with the faults so
+/// that dropping one reports it cleared
+struct Notifier {
+    recorder: Arc<ExperimentRecorder>,
+    observers: Vec<Arc<dyn Observer>>,
+}
+
+impl Notifier {
+    /// The experiment's recorder, then the observers from the config
+    fn all(&self) -> impl Iterator<Item = &dyn Observer> {
+        let recorder: &dyn Observer = self.recorder.as_ref();
+        std::iter::once(recorder).chain(self.observers.iter().map(|observer| observer.as_ref()))
+    }
+
+    /// Tell every observer that the fault of `event` was injected
+    ///
+    /// The engine's observers only record in memory, so their futures are
+    /// run to completion in place. Failures are logged.
+    fn injected(&self, event: &FaultEvent) {
+        for observer in self.all() {
+            if let Err(e) = futures::executor::block_on(observer.on_fault_injected(event)) {
+                let fault_id = &event.fault_id;
+                tracing::warn!("Observer {} failed on fault {}: {}", observer.name(), fault_id, e);
+            }
+        }
+    }
+
+    /// Tell every observer that a fault was cleared, as [`Self::injected`]
+    fn cleared(&self, event: &FaultClearedEvent) {
+        for observer in self.all() {
+            if let Err(e) = futures::executor::block_on(observer.on_fault_cleared(event)) {
+                let fault_id = &event.fault.fault_id;
+                tracing::warn!("Observer {} failed on fault {}: {}", observer.name(), fault_id, e);
+            }
         }
     }
 }
 
+/// A fault injected by the [`ChaosEngine`]
+///
+/// Dereferences to the guard of the fault. Dropping it clears the fault and
+/// then reports it cleared to the engine's observers.
+#[must_use = "the fault is cleared when the guard is dropped"]
+pub struct ActiveFault<G> {
+    /// Only taken on drop
+    guard: Option<G>,
+    event: FaultEvent,
+    injected: Instant,
+    notifier: Arc<Notifier>,
+}
+
+impl<G> ActiveFault<G> {
+    /// Injection the engine's observers were notified of
+    pub fn event(&self) -> &FaultEvent {
+        &self.event
+    }
+}
+
+impl<G> Deref for ActiveFault<G> {
+    type Target = G;
+
+    fn deref(&self) -> &G {
+        self.guard.as_ref().expect("the guard is only taken on drop")
+    }
+}
+
+impl<G> Drop for ActiveFault<G> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        self.notifier.cleared(&FaultClearedEvent {
+            fault: self.event.clone(),
+            actual_duration: self.injected.elapsed(),
+            impact_summary: String::new(),
+        });
+    }
+}
+
+impl<G: fmt::Debug> fmt::Debug for ActiveFault<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActiveFault")
+            .field("guard", &self.guard)
+            .field("event", &self.event)
+            .finish()
+    }
+}
+
 /// Main chaos engine struct (placeholder)
 pub struct ChaosEngine {
     config: ChaosEngineConfig,
+    notifier: Arc<Notifier>,
+    window: Mutex<ExperimentWindow>,
 }
 
 impl ChaosEngine {
     /// Create a new chaos engine
     pub fn new(config: ChaosEngineConfig) -> Result<Self> {
-        Ok(Self { config })
+        let mut observers: Vec<Arc<dyn Observer>> = Vec::new();
+        if config.enable_metrics {
+            observers.push(Arc::new(PrometheusObserver::new()));
+        }
+        Ok(Self {
+            config,
+            notifier: Arc::new(Notifier {
+                recorder: Arc::new(ExperimentRecorder::new()),
+                observers,
+            }),
+            window: Mutex::new(ExperimentWindow::default()),
+        })
     }
 
-    /// Start the chaos engine
+    /// Start the chaos engine, beginning a new experiment
+    ///
+    /// Fails with `InvalidState` unless the engine is idle or its last
+    /// experiment has completed.
     pub async fn start(&self) -> Result<()> {
+        let mut window = self.window.lock();
+        window.require("start", &[ExperimentState::Idle, ExperimentState::Completed])?;
         tracing::info!("Chaos Engine starting with config: {:?}", self.config);
+        self.notifier.recorder.reset();
+        *window = ExperimentWindow {
+            state: ExperimentState::Running,
+            started_at: Some(Instant::now()),
+            stopped_at: None,
+            faults: Vec::new(),
+        };
         Ok(())
     }
 
-    /// Stop the chaos engine
+    /// Stop the chaos engine, completing the running or paused experiment
+    ///
+    /// Faults injected during the experiment are suspended for good; their
+    /// guards only release what they hold. Fails with `InvalidState` if no
+    /// experiment is in progress.
     pub async fn stop(&self) -> Result<()> {
+        let mut window = self.window.lock();
+        window.require("stop", &[ExperimentState::Running, ExperimentState::Paused])?;
         tracing::info!("Chaos Engine stopping");
+        window.suspend_faults(true);
+        window.faults.clear();
+        window.state = ExperimentState::Completed;
+        window.stopped_at = Some(Instant::now());
         Ok(())
     }
+
+    /// Pause the running experiment, suspending its faults and refusing new
+    /// ones until [`resume_all_faults`](Self::resume_all_faults)
+    ///
+    /// Suspended plugin slowdowns and output corruptions stop taking effect,
+    /// and resource exhaustion releases its memory and idles its CPU worker.
+    /// Fails with `InvalidState` unless the experiment is running.
+    pub fn pause_all_faults(&self) -> Result<()> {
+        let mut window = self.window.lock();
+        window.require("pause", &[ExperimentState::Running])?;
+        tracing::info!("Chaos Engine pausing fault injection");
+        window.suspend_faults(true);
+        window.state = ExperimentState::Paused;
+        Ok(())
+    }
+
+    /// Resume the paused experiment, putting back the faults whose guards
+    /// are still held
+    ///
+    /// Fails with `InvalidState` unless the experiment is paused.
+    pub fn resume_all_faults(&self) -> Result<()> {
+        let mut window = self.window.lock();
+        window.require("resume", &[ExperimentState::Paused])?;
+        tracing::info!("Chaos Engine resuming fault injection");
+        window.suspend_faults(false);
+        window.state = ExperimentState::Running;
+        Ok(())
+    }
+
+    /// State of the current or last experiment
+    pub fn state(&self) -> ExperimentState {
+        self.window.lock().state
+    }
+
+    /// Recorder of the current experiment
+    ///
+    /// The engine notifies it of every fault it injects and clears. Report
+    /// golden signal violations to it so they appear in the experiment
+    /// report too.
+    pub fn recorder(&self) -> Arc<ExperimentRecorder> {
+        Arc::clone(&self.notifier.recorder)
+    }
+
+    /// Observers the engine registers itself, according to its config
+    ///
+    /// The engine notifies them of every fault it injects, and again when
+    /// the fault's [`ActiveFault`] is dropped.
+    pub fn observers(&self) -> &[Arc<dyn Observer>] {
+        &self.notifier.observers
+    }
+
+    /// Summarize the current or last experiment
+    ///
+    /// The report covers the faults injected through the engine and the
+    /// violations reported to its [`recorder`](Self::recorder). A running
+    /// experiment is reported up to now. Fails with
+    /// `InvalidState` if the engine was never started.
+    pub fn generate_experiment_report(&self) -> Result<ExperimentReport> {
+        let window = self.window.lock();
+        let Some(started_at) = window.started_at else {
+            return Err(SystemError::InvalidState {
+                message: "no experiment has been run".to_string(),
+                current_state: Some("never started".to_string()),
+                expected_state: Some("started".to_string()),
+            });
+        };
+        let duration = window
+            .stopped_at
+            .unwrap_or_else(Instant::now)
+            .duration_since(started_at);
+        Ok(self.notifier.recorder.report(duration))
+    }
+
+    /// Make a plugin appear slow by forcing its executions to time out after
+    /// `timeout`, whatever deadline callers pass
+    ///
+    /// The fault lasts until the returned guard is dropped. Fails with
+    /// `InvalidState` unless an experiment is running.
+    pub fn slow_plugin(
+        &self,
+        registry: &PluginRegistry,
+        plugin_id: &str,
+        timeout: Duration,
+    ) -> Result<ActiveFault<SlowPlugin>> {
+        let scenario = FaultScenario::PluginTimeout {
+            timeout_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+        };
+        self.inject(scenario, plugin_id, || {
+            Ok(SlowPlugin::activate(registry, plugin_id, timeout))
+        })
+    }
+
+    /// Consume the resource named by a `ResourceExhaustion` scenario with
+    /// `strategy`
+    ///
+    /// The fault lasts until the returned handle is dropped. Fails with
+    /// `InvalidState` unless an experiment is running, and as
+    /// [`ResourceExhaustionStrategy`] does for scenarios it cannot inject.
+    pub fn exhaust_resource(
+        &self,
+        strategy: &ResourceExhaustionStrategy,
+        scenario: &FaultScenario,
+    ) -> Result<ActiveFault<FaultHandle>> {
+        self.inject(scenario.clone(), LOCAL_PROCESS, || {
+            let handle = strategy.activate(scenario)?;
+            let fault = handle.suspender();
+            Ok((handle, fault))
+        })
+    }
+
+    /// Corrupt the outputs `registry` hands over from the target plugin of
+    /// `strategy`
+    ///
+    /// The fault lasts until the returned guard is dropped. Fails with
+    /// `InvalidState` unless an experiment is running.
+    pub fn corrupt_state(
+        &self,
+        registry: &PluginRegistry,
+        strategy: StateCorruptionStrategy,
+    ) -> Result<ActiveFault<InterceptorGuard>> {
+        let scenario = FaultScenario::StateCorruption {
+            corruption: strategy.corruption_type,
+        };
+        let target = strategy.target_plugin.clone();
+        self.inject(scenario, &target, || Ok(strategy.activate(registry)))
+    }
+
+    /// Inject the fault `activate` puts into effect on `target` if an
+    /// experiment is running, and notify the observers
+    fn inject<G>(
+        &self,
+        scenario: FaultScenario,
+        target: &str,
+        activate: impl FnOnce() -> Result<(G, Weak<dyn Suspend>)>,
+    ) -> Result<ActiveFault<G>> {
+        let guard = self.window.lock().inject(activate)?;
+        let event = FaultEvent {
+            fault_id: Id::generate().to_string(),
+            scenario,
+            target: target.to_string(),
+            planned_duration: Duration::ZERO,
+            injected_at: Timestamp::now(),
+        };
+        self.notifier.injected(&event);
+        Ok(ActiveFault {
+            guard: Some(guard),
+            event,
+            injected: Instant::now(),
+            notifier: Arc::clone(&self.notifier),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::ResourceKind;
+    use crate::strategies::CorruptionType;
 
     #[test]
     fn test_chaos_engine_creation() {
@@ -75,4 +445,244 @@ mod tests {
         assert!(engine.start().await.is_ok());
         assert!(engine.stop().await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_experiment_report_covers_last_run() {
+        use crate::reporters::GoldenSignal;
+
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        assert!(engine.generate_experiment_report().is_err());
+
+        engine.start().await.unwrap();
+        engine.recorder().record_violation("checkout", GoldenSignal::Errors, 0.3, 0.1);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        engine.stop().await.unwrap();
+
+        let report = engine.generate_experiment_report().unwrap();
+        assert!(report.duration >= Duration::from_millis(20));
+        assert_eq!(report.golden_signal_violations.len(), 1);
+        assert_eq!(engine.generate_experiment_report().unwrap().duration, report.duration);
+
+        // Starting again begins a fresh experiment
+        engine.start().await.unwrap();
+        assert!(engine.generate_experiment_report().unwrap().golden_signal_violations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_experiment_report_counts_injected_faults() {
+        use crate::reporters::GoldenSignal;
+
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        let registry = PluginRegistry::new();
+        engine.start().await.unwrap();
+
+        let timeout = Duration::from_millis(1);
+        let cleared = engine.slow_plugin(&registry, "resizer", timeout).unwrap();
+        drop(cleared);
+        let _slow = engine.slow_plugin(&registry, "resizer", timeout).unwrap();
+        let corruption = StateCorruptionStrategy::new("resizer", CorruptionType::Reorder);
+        let _corrupt = engine.corrupt_state(&registry, corruption).unwrap();
+        engine.recorder().record_violation("resizer", GoldenSignal::Errors, 0.3, 0.1);
+        engine.stop().await.unwrap();
+
+        let report = engine.generate_experiment_report().unwrap();
+        assert_eq!(report.total_faults, 3);
+        assert_eq!(report.fault_summary["plugin_timeout"], 2);
+        assert_eq!(report.fault_summary["state_corruption"], 1);
+        assert_eq!(report.golden_signal_violations[0].active_faults, 2);
+        assert_eq!(
+            report.recommendations,
+            ["Service resizer tolerated 1 concurrent fault but failed at 2\u{2014}recommend \
+              increasing replica count."]
+        );
+    }
+
+    #[test]
+    fn test_metrics_observer_is_opt_in() {
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        assert!(engine.observers().is_empty());
+
+        let engine = ChaosEngine::new(ChaosEngineConfig {
+            enable_metrics: true,
+            ..ChaosEngineConfig::default()
+        })
+        .unwrap();
+        let names: Vec<&str> = engine.observers().iter().map(|o| o.name()).collect();
+        assert_eq!(names, ["prometheus"]);
+    }
+
+    #[tokio::test]
+    async fn test_injected_faults_emit_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+        // Records the metrics of each thread apart, so tests do not mix
+        let _ = DebuggingRecorder::per_thread().install();
+        // Metrics recorded on this thread by name and first label value;
+        // histograms are drained
+        let recorded = || -> Vec<(String, Option<String>, DebugValue)> {
+            let snapshot = Snapshotter::current_thread_snapshot().unwrap().into_vec();
+            let mut metrics: Vec<_> = snapshot
+                .into_iter()
+                .map(|(key, .., value)| {
+                    let label = key.key().labels().next().map(|label| label.value().to_string());
+                    (key.key().name().to_string(), label, value)
+                })
+                .collect();
+            metrics.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+            metrics
+        };
+        let engine = ChaosEngine::new(ChaosEngineConfig {
+            enable_metrics: true,
+            ..ChaosEngineConfig::default()
+        })
+        .unwrap();
+        let registry = PluginRegistry::new();
+        engine.start().await.unwrap();
+
+        let slow = engine.slow_plugin(&registry, "resizer", Duration::from_millis(1)).unwrap();
+        let corruption = StateCorruptionStrategy::new("resizer", CorruptionType::Reorder);
+        let corrupt = engine.corrupt_state(&registry, corruption).unwrap();
+        let memory = FaultScenario::ResourceExhaustion {
+            resource: ResourceKind::Memory,
+            target_bytes: 1024,
+        };
+        let strategy = ResourceExhaustionStrategy::default();
+        let exhaustion = engine.exhaust_resource(&strategy, &memory).unwrap();
+        let injected = |scenario: &str| {
+            let scenario = Some(scenario.to_string());
+            ("chaos.faults_injected".to_string(), scenario, DebugValue::Counter(1))
+        };
+        let active = |count: f64| {
+            ("chaos.active_faults".to_string(), None, DebugValue::Gauge(count.into()))
+        };
+        assert_eq!(
+            recorded(),
+            [
+                active(3.0),
+                injected("plugin_timeout"),
+                injected("resource_exhaustion"),
+                injected("state_corruption"),
+            ]
+        );
+
+        // Dropping a guard reports its fault cleared
+        drop(slow);
+        let metrics = recorded();
+        assert_eq!(metrics[0], active(2.0));
+        assert!(matches!(&metrics[1], (name, None, DebugValue::Histogram(durations))
+            if name == "chaos.fault_duration_ms" && durations.len() == 1));
+        drop((corrupt, exhaustion));
+        assert_eq!(recorded()[0], active(0.0));
+    }
+
+    #[tokio::test]
+    async fn test_state_machine_rejects_invalid_transitions() {
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        assert_eq!(engine.state(), ExperimentState::Idle);
+
+        match engine.stop().await {
+            Err(SystemError::InvalidState {
+                current_state,
+                expected_state,
+                ..
+            }) => {
+                assert_eq!(current_state.as_deref(), Some("idle"));
+                assert_eq!(expected_state.as_deref(), Some("running or paused"));
+            },
+            other => panic!("expected InvalidState, got {other:?}"),
+        }
+        assert!(engine.pause_all_faults().is_err());
+        assert!(engine.resume_all_faults().is_err());
+
+        engine.start().await.unwrap();
+        assert_eq!(engine.state(), ExperimentState::Running);
+        assert!(engine.start().await.is_err());
+        assert!(engine.resume_all_faults().is_err());
+
+        engine.pause_all_faults().unwrap();
+        assert_eq!(engine.state(), ExperimentState::Paused);
+        assert!(engine.pause_all_faults().is_err());
+        assert!(engine.start().await.is_err());
+
+        engine.resume_all_faults().unwrap();
+        engine.pause_all_faults().unwrap();
+        engine.stop().await.unwrap();
+        assert_eq!(engine.state(), ExperimentState::Completed);
+        assert!(engine.stop().await.is_err());
+        assert!(engine.pause_all_faults().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_faults_require_running_experiment() {
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        let registry = PluginRegistry::new();
+        let timeout = Duration::from_millis(1);
+        assert!(engine.slow_plugin(&registry, "resizer", timeout).is_err());
+
+        engine.start().await.unwrap();
+        engine.pause_all_faults().unwrap();
+        assert!(engine.slow_plugin(&registry, "resizer", timeout).is_err());
+
+        engine.resume_all_faults().unwrap();
+        assert!(engine.slow_plugin(&registry, "resizer", timeout).is_ok());
+
+        engine.stop().await.unwrap();
+        assert!(engine.slow_plugin(&registry, "resizer", timeout).is_err());
+        assert_eq!(registry.timeout_override("resizer"), None);
+
+        let memory = FaultScenario::ResourceExhaustion {
+            resource: ResourceKind::Memory,
+            target_bytes: 1024,
+        };
+        let strategy = ResourceExhaustionStrategy::default();
+        assert!(engine.exhaust_resource(&strategy, &memory).is_err());
+        let corruption = StateCorruptionStrategy::new("resizer", CorruptionType::Reorder);
+        assert!(engine.corrupt_state(&registry, corruption).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pause_suspends_active_faults() {
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        let registry = PluginRegistry::new();
+        let timeout = Duration::from_millis(1);
+        engine.start().await.unwrap();
+
+        let slow = engine.slow_plugin(&registry, "resizer", timeout).unwrap();
+        let memory = FaultScenario::ResourceExhaustion {
+            resource: ResourceKind::Memory,
+            target_bytes: 1024,
+        };
+        let strategy = ResourceExhaustionStrategy::default();
+        let exhaustion = engine.exhaust_resource(&strategy, &memory).unwrap();
+        let cleared = engine.slow_plugin(&registry, "cropper", timeout).unwrap();
+        drop(cleared);
+
+        engine.pause_all_faults().unwrap();
+        assert_eq!(registry.timeout_override("resizer"), None);
+        assert_eq!(exhaustion.held_bytes(), 0);
+
+        // Only faults whose guards are still held come back
+        engine.resume_all_faults().unwrap();
+        assert_eq!(registry.timeout_override("resizer"), Some(timeout));
+        assert_eq!(registry.timeout_override("cropper"), None);
+        assert_eq!(exhaustion.held_bytes(), 1024);
+
+        engine.stop().await.unwrap();
+        assert_eq!(registry.timeout_override("resizer"), None);
+        assert_eq!(exhaustion.held_bytes(), 0);
+        drop(slow);
+    }
+
+    #[tokio::test]
+    async fn test_slow_plugin_override_is_scoped() {
+        let engine = ChaosEngine::new(ChaosEngineConfig::default()).unwrap();
+        let registry = PluginRegistry::new();
+        engine.start().await.unwrap();
+
+        let fault = engine.slow_plugin(&registry, "resizer", Duration::from_millis(1)).unwrap();
+        assert_eq!(registry.timeout_override("resizer"), Some(Duration::from_millis(1)));
+
+        drop(fault);
+        assert_eq!(registry.timeout_override("resizer"), None);
+    }
 }
@@ -1 +1,119 @@
 //! Observers module
+//!
+//! Observers are notified when the engine injects and clears faults, e.g. to
+//! alert operators or record metrics.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, Timestamp};
+
+use crate::core::FaultScenario;
+
+mod prometheus;
+mod slack;
+
+pub use prometheus::PrometheusObserver;
+pub use slack::{SlackNotificationObserver, SlackNotificationObserverBuilder};
+
+/// A fault that has been injected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultEvent {
+    /// Unique ID of this injection
+    pub fault_id: String,
+    /// The injected fault
+    pub scenario: FaultScenario,
+    /// Description of the affected target
+    pub target: String,
+    /// How long the fault is planned to stay active, zero for faults that
+    /// stay until their guard is dropped
+    pub planned_duration: Duration,
+    /// When the fault was injected
+    pub injected_at: Timestamp,
+}
+
+/// A fault that has been removed again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultClearedEvent {
+    /// The original injection
+    pub fault: FaultEvent,
+    /// How long the fault was actually active
+    pub actual_duration: Duration,
+    /// Human-readable summary of the observed impact, empty if none was
+    /// measured
+    pub impact_summary: String,
+}
+
+/// Receives fault lifecycle notifications
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait Observer: Send + Sync {
+    /// Observer name
+    fn name(&self) -> &str;
+
+    /// Called after a fault has been injected
+    async fn on_fault_injected(&self, event: &FaultEvent) -> Result<()>;
+
+    /// Called after a fault has been cleared
+    async fn on_fault_cleared(&self, event: &FaultClearedEvent) -> Result<()>;
+}
+
+/// Allows at most one notification per key within a fixed interval
+#[derive(Debug)]
+pub struct NotificationThrottle {
+    interval: Duration,
+    last_allowed: Mutex<HashMap<String, Instant>>,
+}
+
+impl NotificationThrottle {
+    /// Create a throttle allowing one notification per key every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_allowed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a notification for `key`, returning `false` if it must be
+    /// dropped
+    pub fn try_acquire(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut last_allowed = self.last_allowed.lock();
+
+        match last_allowed.get(key) {
+            Some(last) if now.duration_since(*last) < self.interval => false,
+            _ => {
+                last_allowed.insert(key.to_string(), now);
+                true
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_is_per_key() {
+        let throttle = NotificationThrottle::new(Duration::from_secs(60));
+
+        assert!(throttle.try_acquire("#chaos"));
+        assert!(!throttle.try_acquire("#chaos"));
+        assert!(throttle.try_acquire("#ops"));
+    }
+
+    #[test]
+    fn test_throttle_allows_after_interval() {
+        let throttle = NotificationThrottle::new(Duration::from_millis(10));
+
+        assert!(throttle.try_acquire("#chaos"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(throttle.try_acquire("#chaos"));
+    }
+}
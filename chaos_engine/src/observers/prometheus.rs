@@ -0,0 +1,153 @@
+//! Prometheus metrics for fault injection
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use shared_core::Result;
+
+use super::{FaultClearedEvent, FaultEvent, Observer};
+
+/// Records chaos metrics through the `metrics` facade
+///
+/// The metrics are exported by whatever recorder is installed, e.g. the
+/// Prometheus exporter set up by `shared_core::telemetry`:
+///
+/// - `chaos.faults_injected`: counter labelled by `scenario`
+/// - `chaos.fault_duration_ms`: histogram of how long faults were active
+/// - `chaos.active_faults`: gauge of the faults currently injected
+#[derive(Debug, Default)]
+pub struct PrometheusObserver {
+    active: Mutex<HashSet<String>>,
+}
+
+impl PrometheusObserver {
+    /// Create an observer with no active faults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of faults injected and not yet cleared
+    pub fn active_faults(&self) -> usize {
+        self.active.lock().len()
+    }
+}
+
+#[async_trait]
+impl Observer for PrometheusObserver {
+    fn name(&self) -> &str {
+        "prometheus"
+    }
+
+    async fn on_fault_injected(&self, event: &FaultEvent) -> Result<()> {
+        let mut active = self.active.lock();
+        active.insert(event.fault_id.clone());
+        shared_core::count!("chaos.faults_injected", 1, "scenario" => event.scenario.name());
+        shared_core::gauge!("chaos.active_faults", active.len() as f64);
+        Ok(())
+    }
+
+    async fn on_fault_cleared(&self, event: &FaultClearedEvent) -> Result<()> {
+        let mut active = self.active.lock();
+        active.remove(&event.fault.fault_id);
+        shared_core::histogram!(
+            "chaos.fault_duration_ms",
+            event.actual_duration.as_secs_f64() * 1000.0
+        );
+        shared_core::gauge!("chaos.active_faults", active.len() as f64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use shared_core::Timestamp;
+
+    use super::*;
+    use crate::core::FaultScenario;
+
+    fn event(fault_id: &str) -> FaultEvent {
+        FaultEvent {
+            fault_id: fault_id.to_string(),
+            scenario: FaultScenario::NetworkLatency {
+                delay_ms: 100,
+                jitter_ms: 10,
+            },
+            target: "payments-api".to_string(),
+            planned_duration: Duration::from_secs(30),
+            injected_at: Timestamp::now(),
+        }
+    }
+
+    fn cleared(fault: FaultEvent) -> FaultClearedEvent {
+        FaultClearedEvent {
+            fault,
+            actual_duration: Duration::from_secs(12),
+            impact_summary: "p99 latency +80ms".to_string(),
+        }
+    }
+
+    /// Metrics recorded on this thread, keyed by name and labels
+    fn recorded() -> BTreeMap<String, DebugValue> {
+        let snapshot = Snapshotter::current_thread_snapshot().expect("a recorder is installed");
+        snapshot
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<String> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                let name = if labels.is_empty() {
+                    key.name().to_string()
+                } else {
+                    format!("{}{{{}}}", key.name(), labels.join(","))
+                };
+                (name, value)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_active_faults_follow_injections() {
+        // Records the metrics of each thread apart, so tests do not mix
+        let _ = DebuggingRecorder::per_thread().install();
+        let observer = PrometheusObserver::new();
+        let injected = "chaos.faults_injected{scenario=network_latency}".to_string();
+
+        observer.on_fault_injected(&event("fault-1")).await.unwrap();
+        observer.on_fault_injected(&event("fault-2")).await.unwrap();
+        assert_eq!(observer.active_faults(), 2);
+        assert_eq!(
+            recorded(),
+            BTreeMap::from([
+                ("chaos.active_faults".to_string(), DebugValue::Gauge(2.0.into())),
+                (injected.clone(), DebugValue::Counter(2)),
+            ])
+        );
+
+        observer.on_fault_cleared(&cleared(event("fault-1"))).await.unwrap();
+        assert_eq!(observer.active_faults(), 1);
+        assert_eq!(
+            recorded(),
+            BTreeMap::from([
+                ("chaos.active_faults".to_string(), DebugValue::Gauge(1.0.into())),
+                (
+                    "chaos.fault_duration_ms".to_string(),
+                    DebugValue::Histogram(vec![12_000.0.into()])
+                ),
+                (injected.clone(), DebugValue::Counter(2)),
+            ])
+        );
+
+        // Clearing an unknown fault leaves the count alone
+        observer.on_fault_cleared(&cleared(event("fault-9"))).await.unwrap();
+        assert_eq!(observer.active_faults(), 1);
+        assert_eq!(recorded()["chaos.active_faults"], DebugValue::Gauge(1.0.into()));
+    }
+}
@@ -0,0 +1,321 @@
+//! Slack notifications for fault injection
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use shared_core::{Result, SystemError};
+
+use super::{FaultClearedEvent, FaultEvent, NotificationThrottle, Observer};
+
+/// Minimum time between two notifications of one kind to the same channel
+const DEFAULT_RATE_LIMIT: Duration = Duration::from_secs(5);
+
+/// Posts fault notifications to a Slack incoming webhook
+///
+/// Injections and clears are rate limited apart, so a fault cleared soon
+/// after its injection still gets its resolution message. Notifications
+/// over the per-channel rate limit are dropped.
+pub struct SlackNotificationObserver {
+    webhook_url: String,
+    channel: String,
+    http_client: reqwest::Client,
+    throttle: NotificationThrottle,
+}
+
+impl SlackNotificationObserver {
+    /// Start building an observer
+    pub fn builder() -> SlackNotificationObserverBuilder {
+        SlackNotificationObserverBuilder::default()
+    }
+
+    /// Channel notifications are posted to
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    fn injected_payload(&self, event: &FaultEvent) -> Value {
+        let text = format!(
+            ":rotating_light: Chaos fault `{}` injected into `{}`",
+            event.scenario.name(),
+            event.target
+        );
+        self.payload(
+            text,
+            &[
+                ("Fault", event.scenario.name().to_string()),
+                ("Target", event.target.clone()),
+                ("Planned duration", format_duration(event.planned_duration)),
+                ("Fault ID", event.fault_id.clone()),
+            ],
+        )
+    }
+
+    fn cleared_payload(&self, event: &FaultClearedEvent) -> Value {
+        let text = format!(
+            ":white_check_mark: Chaos fault `{}` cleared from `{}`",
+            event.fault.scenario.name(),
+            event.fault.target
+        );
+        self.payload(
+            text,
+            &[
+                ("Fault", event.fault.scenario.name().to_string()),
+                ("Target", event.fault.target.clone()),
+                ("Actual duration", format_duration(event.actual_duration)),
+                ("Impact", event.impact_summary.clone()),
+            ],
+        )
+    }
+
+    /// `chat.postMessage`-compatible message body
+    fn payload(&self, text: String, fields: &[(&str, String)]) -> Value {
+        let fields: Vec<Value> = fields
+            .iter()
+            .map(|(title, value)| json!({ "type": "mrkdwn", "text": format!("*{title}*\n{value}") }))
+            .collect();
+
+        json!({
+            "channel": self.channel,
+            "text": text,
+            "blocks": [
+                { "type": "section", "text": { "type": "mrkdwn", "text": text } },
+                { "type": "section", "fields": fields },
+            ],
+        })
+    }
+
+    /// Post a notification of `kind` unless one of that kind was posted
+    /// within the rate limit
+    async fn post(&self, kind: &str, payload: Value) -> Result<()> {
+        if !self.throttle.try_acquire(&format!("{} {kind}", self.channel)) {
+            let channel = &self.channel;
+            tracing::debug!("Dropping Slack {} notification to {}: rate limited", kind, channel);
+            return Ok(());
+        }
+
+        let response = self
+            .http_client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| SystemError::network("slack webhook", e.to_string(), None))?;
+
+        if !response.status().is_success() {
+            return Err(SystemError::network(
+                "slack webhook",
+                format!("unexpected status {}", response.status()),
+                None,
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+#[async_trait]
+impl Observer for SlackNotificationObserver {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn on_fault_injected(&self, event: &FaultEvent) -> Result<()> {
+        self.post("injected", self.injected_payload(event)).await
+    }
+
+    async fn on_fault_cleared(&self, event: &FaultClearedEvent) -> Result<()> {
+        self.post("cleared", self.cleared_payload(event)).await
+    }
+}
+
+/// Builder for [`SlackNotificationObserver`]
+#[derive(Default)]
+pub struct SlackNotificationObserverBuilder {
+    webhook_url: Option<String>,
+    channel: Option<String>,
+    http_client: Option<reqwest::Client>,
+    rate_limit: Option<Duration>,
+}
+
+impl SlackNotificationObserverBuilder {
+    /// Set the incoming webhook URL (required)
+    pub fn webhook_url(mut self, url: impl Into<String>) -> Self {
+        self.webhook_url = Some(url.into());
+        self
+    }
+
+    /// Set the channel to post to (required)
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// Use an existing HTTP client
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Override the minimum interval between notifications of one kind
+    pub fn rate_limit(mut self, interval: Duration) -> Self {
+        self.rate_limit = Some(interval);
+        self
+    }
+
+    /// Build the observer
+    pub fn build(self) -> Result<SlackNotificationObserver> {
+        let webhook_url = self
+            .webhook_url
+            .ok_or_else(|| SystemError::validation("webhook_url", "is required", None))?;
+        if !webhook_url.starts_with("https://") && !webhook_url.starts_with("http://") {
+            return Err(SystemError::validation(
+                "webhook_url",
+                "must be an http(s) URL",
+                Some(webhook_url),
+            ));
+        }
+
+        let channel = self
+            .channel
+            .filter(|channel| !channel.is_empty())
+            .ok_or_else(|| SystemError::validation("channel", "is required", None))?;
+
+        Ok(SlackNotificationObserver {
+            webhook_url,
+            channel,
+            http_client: self.http_client.unwrap_or_default(),
+            throttle: NotificationThrottle::new(self.rate_limit.unwrap_or(DEFAULT_RATE_LIMIT)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use parking_lot::Mutex;
+    use shared_core::Timestamp;
+
+    use super::*;
+    use crate::core::FaultScenario;
+
+    fn observer() -> SlackNotificationObserver {
+        SlackNotificationObserver::builder()
+            .webhook_url("https://hooks.slack.com/services/T000/B000/XXXX")
+            .channel("#chaos")
+            .build()
+            .unwrap()
+    }
+
+    fn event() -> FaultEvent {
+        FaultEvent {
+            fault_id: "fault-1".to_string(),
+            scenario: FaultScenario::NetworkLatency {
+                delay_ms: 100,
+                jitter_ms: 10,
+            },
+            target: "payments-api".to_string(),
+            planned_duration: Duration::from_secs(30),
+            injected_at: Timestamp::now(),
+        }
+    }
+
+    #[test]
+    fn test_builder_requires_webhook_and_channel() {
+        assert!(SlackNotificationObserver::builder()
+            .channel("#chaos")
+            .build()
+            .is_err());
+        assert!(SlackNotificationObserver::builder()
+            .webhook_url("https://hooks.slack.com/services/x")
+            .build()
+            .is_err());
+        assert!(SlackNotificationObserver::builder()
+            .webhook_url("ftp://example.com")
+            .channel("#chaos")
+            .build()
+            .is_err());
+        assert_eq!(observer().channel(), "#chaos");
+    }
+
+    #[test]
+    fn test_payloads() {
+        let observer = observer();
+
+        let injected = observer.injected_payload(&event());
+        assert_eq!(injected["channel"], "#chaos");
+        let text = injected.to_string();
+        assert!(text.contains("network_latency"));
+        assert!(text.contains("payments-api"));
+        assert!(text.contains("30.0s"));
+
+        let cleared = observer.cleared_payload(&FaultClearedEvent {
+            fault: event(),
+            actual_duration: Duration::from_millis(31_500),
+            impact_summary: "p99 latency +120ms".to_string(),
+        });
+        let text = cleared.to_string();
+        assert!(text.contains("cleared"));
+        assert!(text.contains("31.5s"));
+        assert!(text.contains("p99 latency +120ms"));
+    }
+
+    /// Start a webhook on a local port, returning its URL and the payloads
+    /// it receives
+    fn webhook() -> (String, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let payloads = Arc::clone(&received);
+        let make_service = make_service_fn(move |_| {
+            let payloads = Arc::clone(&payloads);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let payloads = Arc::clone(&payloads);
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await?;
+                        payloads.lock().push(serde_json::from_slice(&body).unwrap());
+                        Ok::<_, hyper::Error>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/webhook", server.local_addr());
+        tokio::spawn(server);
+        (url, received)
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_notifications_are_dropped() {
+        let (url, received) = webhook();
+        let observer = SlackNotificationObserver::builder()
+            .webhook_url(url)
+            .channel("#chaos")
+            .build()
+            .unwrap();
+        let cleared = FaultClearedEvent {
+            fault: event(),
+            actual_duration: Duration::from_secs(1),
+            impact_summary: String::new(),
+        };
+
+        observer.on_fault_injected(&event()).await.unwrap();
+        observer.on_fault_injected(&event()).await.unwrap();
+        // A clear is not held back by the injection just posted
+        observer.on_fault_cleared(&cleared).await.unwrap();
+        observer.on_fault_cleared(&cleared).await.unwrap();
+
+        let received = received.lock();
+        let texts: Vec<&str> =
+            received.iter().map(|payload| payload["text"].as_str().unwrap()).collect();
+        assert_eq!(texts.len(), 2, "{texts:?}");
+        assert!(texts[0].contains("injected"));
+        assert!(texts[1].contains("cleared"));
+    }
+}
@@ -1 +1,379 @@
 //! Reporters module
+//!
+//! [`ExperimentRecorder`] observes an experiment's faults and the golden
+//! signal violations seen while they were active; [`ExperimentReport`]
+//! summarizes them for operators once the experiment is over.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, Timestamp};
+
+use crate::observers::{FaultClearedEvent, FaultEvent, Observer};
+
+/// One of the four golden signals of a service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoldenSignal {
+    /// Time to serve requests
+    Latency,
+    /// Demand placed on the service
+    Traffic,
+    /// Rate of failed requests
+    Errors,
+    /// How full the service's most constrained resource is
+    Saturation,
+}
+
+impl fmt::Display for GoldenSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Latency => "latency",
+            Self::Traffic => "traffic",
+            Self::Errors => "errors",
+            Self::Saturation => "saturation",
+        })
+    }
+}
+
+/// A golden signal that crossed its threshold during an experiment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoldenSignalViolation {
+    /// Affected service, matching [`FaultEvent::target`]
+    pub service: String,
+    /// Violated signal
+    pub signal: GoldenSignal,
+    /// Observed value
+    pub observed: f64,
+    /// Threshold the value crossed
+    pub threshold: f64,
+    /// Faults active on the service when the violation was seen
+    pub active_faults: usize,
+    /// When the violation was seen
+    pub at: Timestamp,
+}
+
+/// Summary of a chaos experiment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentReport {
+    /// Time from engine start to stop
+    pub duration: Duration,
+    /// Faults injected
+    pub total_faults: u64,
+    /// Faults injected per scenario name
+    pub fault_summary: HashMap<String, u64>,
+    /// Violations, in the order they were seen
+    pub golden_signal_violations: Vec<GoldenSignalViolation>,
+    /// Suggested follow-ups derived from the results
+    pub recommendations: Vec<String>,
+}
+
+impl ExperimentReport {
+    /// Render the report as a Markdown document
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        // Writing to a String cannot fail
+        let _ = self.write_markdown(&mut out);
+        out
+    }
+
+    fn write_markdown(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "# Chaos Experiment Report")?;
+        writeln!(out)?;
+        writeln!(out, "- **Duration:** {:.1}s", self.duration.as_secs_f64())?;
+        writeln!(out, "- **Faults injected:** {}", self.total_faults)?;
+        writeln!(
+            out,
+            "- **Golden signal violations:** {}",
+            self.golden_signal_violations.len()
+        )?;
+
+        writeln!(out)?;
+        writeln!(out, "## Faults")?;
+        writeln!(out)?;
+        if self.fault_summary.is_empty() {
+            writeln!(out, "No faults were injected.")?;
+        } else {
+            writeln!(out, "| Scenario | Injections |")?;
+            writeln!(out, "|---|---|")?;
+            let sorted: BTreeMap<_, _> = self.fault_summary.iter().collect();
+            for (scenario, count) in sorted {
+                writeln!(out, "| {scenario} | {count} |")?;
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "## Golden Signal Violations")?;
+        writeln!(out)?;
+        if self.golden_signal_violations.is_empty() {
+            writeln!(out, "No golden signal crossed its threshold.")?;
+        } else {
+            writeln!(out, "| Service | Signal | Observed | Threshold | Active faults |")?;
+            writeln!(out, "|---|---|---|---|---|")?;
+            for v in &self.golden_signal_violations {
+                writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    v.service, v.signal, v.observed, v.threshold, v.active_faults
+                )?;
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "## Recommendations")?;
+        writeln!(out)?;
+        if self.recommendations.is_empty() {
+            writeln!(out, "None.")?;
+        }
+        for recommendation in &self.recommendations {
+            writeln!(out, "- {recommendation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Faults and violations seen for one service
+#[derive(Debug, Default)]
+struct ServiceRecord {
+    active_faults: usize,
+    /// Most faults ever active at once
+    peak_faults: usize,
+    /// Most faults active at once before the first violation
+    peak_before_violation: usize,
+    violated: bool,
+}
+
+#[derive(Debug, Default)]
+struct ExperimentLog {
+    fault_summary: HashMap<String, u64>,
+    services: BTreeMap<String, ServiceRecord>,
+    violations: Vec<GoldenSignalViolation>,
+}
+
+/// Records an experiment for [`ExperimentReport`]
+///
+/// The recorder is an [`Observer`] of fault injections; the
+/// [`ChaosEngine`](crate::ChaosEngine) notifies its own recorder of the
+/// faults it injects. Feed it golden signal violations with
+/// [`ExperimentRecorder::record_violation`].
+#[derive(Debug, Default)]
+pub struct ExperimentRecorder {
+    log: Mutex<ExperimentLog>,
+}
+
+impl ExperimentRecorder {
+    /// Create an empty recorder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget everything recorded so far
+    pub fn reset(&self) {
+        *self.log.lock() = ExperimentLog::default();
+    }
+
+    /// Record that a golden signal of `service` crossed `threshold`
+    pub fn record_violation(
+        &self,
+        service: &str,
+        signal: GoldenSignal,
+        observed: f64,
+        threshold: f64,
+    ) {
+        shared_core::count!(
+            "chaos.golden_signal_violations", 1, "signal" => signal.to_string()
+        );
+        let mut log = self.log.lock();
+        let record = log.services.entry(service.to_string()).or_default();
+        record.violated = true;
+        let active_faults = record.active_faults;
+        log.violations.push(GoldenSignalViolation {
+            service: service.to_string(),
+            signal,
+            observed,
+            threshold,
+            active_faults,
+            at: Timestamp::now(),
+        });
+    }
+
+    /// Summarize the recorded experiment, which lasted `duration`
+    pub fn report(&self, duration: Duration) -> ExperimentReport {
+        let log = self.log.lock();
+        ExperimentReport {
+            duration,
+            total_faults: log.fault_summary.values().sum(),
+            fault_summary: log.fault_summary.clone(),
+            golden_signal_violations: log.violations.clone(),
+            recommendations: recommendations(&log),
+        }
+    }
+}
+
+#[async_trait]
+impl Observer for ExperimentRecorder {
+    fn name(&self) -> &str {
+        "experiment_recorder"
+    }
+
+    async fn on_fault_injected(&self, event: &FaultEvent) -> Result<()> {
+        let mut log = self.log.lock();
+        *log.fault_summary.entry(event.scenario.name().to_string()).or_default() += 1;
+        let record = log.services.entry(event.target.clone()).or_default();
+        record.active_faults += 1;
+        record.peak_faults = record.peak_faults.max(record.active_faults);
+        if !record.violated {
+            record.peak_before_violation = record.peak_faults;
+        }
+        Ok(())
+    }
+
+    async fn on_fault_cleared(&self, event: &FaultClearedEvent) -> Result<()> {
+        let mut log = self.log.lock();
+        if let Some(record) = log.services.get_mut(&event.fault.target) {
+            record.active_faults = record.active_faults.saturating_sub(1);
+        }
+        Ok(())
+    }
+}
+
+fn recommendations(log: &ExperimentLog) -> Vec<String> {
+    let mut recommendations = Vec::new();
+    for (service, record) in &log.services {
+        let failed_at = log
+            .violations
+            .iter()
+            .filter(|v| &v.service == service)
+            .map(|v| v.active_faults)
+            .min();
+        match failed_at {
+            Some(0) => recommendations.push(format!(
+                "Service {service} violated its golden signals with no fault active\u{2014}\
+                 fix its baseline before running further experiments."
+            )),
+            Some(1) => recommendations.push(format!(
+                "Service {service} failed under a single fault\u{2014}recommend adding \
+                 redundancy, timeouts and retries."
+            )),
+            Some(failed_at) => {
+                let tolerated = record.peak_before_violation.min(failed_at - 1);
+                recommendations.push(format!(
+                    "Service {service} tolerated {} but failed at {failed_at}\u{2014}recommend \
+                     increasing replica count.",
+                    concurrent_faults(tolerated)
+                ));
+            },
+            None if record.peak_faults > 0 => recommendations.push(format!(
+                "Service {service} tolerated up to {} without golden signal \
+                 violations\u{2014}consider raising fault intensity.",
+                concurrent_faults(record.peak_faults)
+            )),
+            None => {},
+        }
+    }
+    recommendations
+}
+
+fn concurrent_faults(count: usize) -> String {
+    match count {
+        1 => "1 concurrent fault".to_string(),
+        n => format!("{n} concurrent faults"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::FaultScenario;
+
+    fn fault(id: &str, target: &str) -> FaultEvent {
+        FaultEvent {
+            fault_id: id.to_string(),
+            scenario: FaultScenario::NetworkLatency {
+                delay_ms: 200,
+                jitter_ms: 10,
+            },
+            target: target.to_string(),
+            planned_duration: Duration::from_secs(30),
+            injected_at: Timestamp::now(),
+        }
+    }
+
+    fn cleared(fault: FaultEvent) -> FaultClearedEvent {
+        FaultClearedEvent {
+            fault,
+            actual_duration: Duration::from_secs(30),
+            impact_summary: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_recommendations() {
+        let recorder = ExperimentRecorder::new();
+        recorder.record_violation("inventory", GoldenSignal::Errors, 0.2, 0.05);
+
+        // Faults are counted per service only while active
+        let transient = fault("t", "payments");
+        recorder.on_fault_injected(&transient).await.unwrap();
+        recorder.on_fault_cleared(&cleared(transient)).await.unwrap();
+
+        let kill = FaultEvent {
+            scenario: FaultScenario::ProcessKill { signal: 9 },
+            ..fault("k", "search")
+        };
+        recorder.on_fault_injected(&kill).await.unwrap();
+        recorder.on_fault_injected(&fault("p", "payments")).await.unwrap();
+        recorder.record_violation("payments", GoldenSignal::Saturation, 0.99, 0.9);
+
+        let report = recorder.report(Duration::from_secs(120));
+        assert_eq!(report.total_faults, 3);
+        assert_eq!(report.fault_summary["network_latency"], 2);
+        assert_eq!(report.fault_summary["process_kill"], 1);
+        assert_eq!(report.golden_signal_violations[1].active_faults, 1);
+        assert_eq!(
+            report.recommendations,
+            vec![
+                "Service inventory violated its golden signals with no fault active\u{2014}fix \
+                 its baseline before running further experiments.",
+                "Service payments failed under a single fault\u{2014}recommend adding \
+                 redundancy, timeouts and retries.",
+                "Service search tolerated up to 1 concurrent fault without golden signal \
+                 violations\u{2014}consider raising fault intensity.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_recommendation_and_markdown() {
+        let recorder = ExperimentRecorder::new();
+        for i in 0..3 {
+            recorder.on_fault_injected(&fault(&format!("f{i}"), "checkout")).await.unwrap();
+        }
+        recorder.on_fault_injected(&fault("f3", "checkout")).await.unwrap();
+        recorder.record_violation("checkout", GoldenSignal::Latency, 950.0, 500.0);
+
+        let report = recorder.report(Duration::from_millis(90_500));
+        assert_eq!(
+            report.recommendations,
+            vec![
+                "Service checkout tolerated 3 concurrent faults but failed at 4\u{2014}recommend \
+                 increasing replica count."
+            ]
+        );
+
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("# Chaos Experiment Report\n"));
+        assert!(markdown.contains("- **Duration:** 90.5s"));
+        assert!(markdown.contains("| network_latency | 4 |"));
+        assert!(markdown.contains("| checkout | latency | 950 | 500 | 4 |"));
+        assert!(markdown.contains("- Service checkout tolerated 3 concurrent faults"));
+
+        recorder.reset();
+        let empty = recorder.report(Duration::ZERO).to_markdown();
+        assert!(empty.contains("No faults were injected."));
+    }
+}
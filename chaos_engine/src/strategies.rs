@@ -1 +1,568 @@
 //! Strategies module
+//!
+//! Strategies put a [`FaultScenario`] into effect and return a
+//! [`FaultHandle`] that keeps the fault active until it is dropped.
+//! [`StateCorruptionStrategy`] instead hooks into a [`PluginRegistry`] and
+//! stays active until its [`InterceptorGuard`] is dropped.
+//!
+//! Faults are activated through the [`ChaosEngine`](crate::ChaosEngine),
+//! which only injects them while an experiment is running and suspends
+//! them while it is paused.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Weak,
+};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared_core::{
+    InterceptorGuard, OutputInterceptor, PluginOutput, PluginRegistry, Result, SystemError,
+    TimeoutOverride,
+};
+use tokio::task::JoinHandle;
+
+use crate::core::{FaultScenario, ResourceKind};
+
+/// Busy-loop iterations between yields of the CPU exhaustion worker
+const SPINS_PER_YIELD: u32 = 1 << 16;
+/// How often a suspended CPU exhaustion worker checks whether to go on
+const SUSPENDED_POLL: Duration = Duration::from_millis(1);
+
+/// Data key holding the bytes of a corrupted output that no longer parses
+pub const CORRUPTED_DATA_KEY: &str = "corrupted";
+
+/// An active fault the engine can suspend while its experiment is paused
+pub(crate) trait Suspend: Send + Sync {
+    /// Stop the fault taking effect, or start it again
+    fn set_suspended(&self, suspended: bool);
+}
+
+/// An active fault; dropping the handle clears it
+pub struct FaultHandle {
+    scenario: FaultScenario,
+    exhaustion: Arc<Exhaustion>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Resources held by a resource exhaustion fault, shared with its worker
+/// and the engine
+struct Exhaustion {
+    /// Memory held by a memory exhaustion fault, and its size when active
+    memory: Option<(Mutex<Vec<u8>>, usize)>,
+    /// Set on drop to stop background workers
+    cancel: AtomicBool,
+    /// Set while the experiment is paused
+    suspended: AtomicBool,
+}
+
+impl FaultHandle {
+    /// The scenario this handle keeps active
+    pub fn scenario(&self) -> &FaultScenario {
+        &self.scenario
+    }
+
+    /// Bytes of memory currently held by the fault, none while suspended
+    pub fn held_bytes(&self) -> usize {
+        self.exhaustion.memory.as_ref().map_or(0, |(memory, _)| memory.lock().len())
+    }
+
+    /// Suspension of the fault, for the engine
+    pub(crate) fn suspender(&self) -> Weak<dyn Suspend> {
+        let exhaustion: Arc<dyn Suspend> = self.exhaustion.clone();
+        Arc::downgrade(&exhaustion)
+    }
+}
+
+impl Drop for FaultHandle {
+    fn drop(&mut self) {
+        self.exhaustion.cancel.store(true, Ordering::Relaxed);
+        tracing::info!("Clearing {} fault", self.scenario.name());
+    }
+}
+
+impl Suspend for Exhaustion {
+    fn set_suspended(&self, suspended: bool) {
+        self.suspended.store(suspended, Ordering::Relaxed);
+        if let Some((memory, len)) = &self.memory {
+            let mut memory = memory.lock();
+            if suspended {
+                *memory = Vec::new();
+            } else if memory.is_empty() {
+                *memory = vec![0xA5; *len];
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for FaultHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaultHandle")
+            .field("scenario", &self.scenario)
+            .field("held_bytes", &self.held_bytes())
+            .field("worker", &self.worker.is_some())
+            .field("suspended", &self.exhaustion.suspended.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Consumes memory or CPU in the current process
+#[derive(Debug, Clone)]
+pub struct ResourceExhaustionStrategy {
+    max_bytes: u64,
+}
+
+impl ResourceExhaustionStrategy {
+    /// Create a strategy that refuses to allocate more than `max_bytes`
+    pub fn new(max_bytes: u64) -> Self {
+        Self { max_bytes }
+    }
+
+    /// Start consuming the resource named by a `ResourceExhaustion` scenario
+    ///
+    /// Memory exhaustion allocates and touches `target_bytes`. CPU exhaustion
+    /// runs a busy loop on the blocking pool, yielding regularly so other
+    /// threads still get scheduled, and needs a Tokio runtime. A suspended
+    /// fault frees its memory and idles its worker.
+    pub(crate) fn activate(&self, scenario: &FaultScenario) -> Result<FaultHandle> {
+        let FaultScenario::ResourceExhaustion {
+            resource,
+            target_bytes,
+        } = *scenario
+        else {
+            return Err(SystemError::validation(
+                "scenario",
+                "resource exhaustion strategy only handles resource_exhaustion",
+                Some(scenario.name().to_string()),
+            ));
+        };
+
+        let mut memory = None;
+        let mut runtime = None;
+        match resource {
+            ResourceKind::Memory => {
+                if target_bytes > self.max_bytes {
+                    return Err(SystemError::validation(
+                        "target_bytes",
+                        format!("exceeds the limit of {} bytes", self.max_bytes),
+                        Some(target_bytes.to_string()),
+                    ));
+                }
+                let len = usize::try_from(target_bytes).map_err(|_| {
+                    SystemError::validation(
+                        "target_bytes",
+                        "does not fit in memory",
+                        Some(target_bytes.to_string()),
+                    )
+                })?;
+                // Non-zero fill so every page is actually committed
+                memory = Some((Mutex::new(vec![0xA5; len]), len));
+                tracing::info!("Holding {} bytes of memory", len);
+            },
+            ResourceKind::Cpu => {
+                runtime = Some(tokio::runtime::Handle::try_current().map_err(|_| {
+                    SystemError::InvalidState {
+                        message: "CPU exhaustion needs a Tokio runtime".to_string(),
+                        current_state: None,
+                        expected_state: Some("inside a Tokio runtime".to_string()),
+                    }
+                })?);
+            },
+        }
+
+        let exhaustion = Arc::new(Exhaustion {
+            memory,
+            cancel: AtomicBool::new(false),
+            suspended: AtomicBool::new(false),
+        });
+        let worker = runtime.map(|runtime| {
+            let exhaustion = Arc::clone(&exhaustion);
+            tracing::info!("Spinning a CPU exhaustion worker");
+            runtime.spawn_blocking(move || spin(&exhaustion))
+        });
+        Ok(FaultHandle {
+            scenario: scenario.clone(),
+            exhaustion,
+            worker,
+        })
+    }
+}
+
+impl Default for ResourceExhaustionStrategy {
+    fn default() -> Self {
+        Self::new(1 << 30) // 1 GiB
+    }
+}
+
+/// Damage done by a [`StateCorruptionStrategy`] to serialized output data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CorruptionType {
+    /// Flip the lowest bit of one byte
+    BitFlip {
+        /// Offset of the byte; offsets past the end leave the data intact
+        byte_offset: usize,
+    },
+    /// Drop everything after the first bytes
+    Truncate {
+        /// Bytes to keep
+        to_bytes: usize,
+    },
+    /// Answer with stale data: each fresh output is repeated in place of the
+    /// outputs that follow it
+    Replay {
+        /// Outputs replaced by each fresh one
+        times: usize,
+    },
+    /// Move every value to the next key, in key order
+    Reorder,
+}
+
+/// Corrupts the outputs of one plugin to simulate a Byzantine peer
+///
+/// The output's `data` is serialized as JSON with sorted keys, so byte
+/// offsets are stable, and parsed back after the corruption. Data that no
+/// longer parses is handed over as a lossy string under
+/// [`CORRUPTED_DATA_KEY`]. The success flag is left alone: a Byzantine
+/// plugin reports success with wrong data.
+#[derive(Debug)]
+pub struct StateCorruptionStrategy {
+    /// Plugin whose outputs are corrupted
+    pub target_plugin: String,
+    /// Corruption applied to each output
+    pub corruption_type: CorruptionType,
+    /// Serialized data being replayed and the replays left
+    replay: Mutex<Option<(Vec<u8>, usize)>>,
+    /// Set while the experiment is paused
+    suspended: AtomicBool,
+}
+
+impl StateCorruptionStrategy {
+    /// Create a strategy corrupting the outputs of `target_plugin`
+    pub fn new(target_plugin: impl Into<String>, corruption_type: CorruptionType) -> Self {
+        Self {
+            target_plugin: target_plugin.into(),
+            corruption_type,
+            replay: Mutex::new(None),
+            suspended: AtomicBool::new(false),
+        }
+    }
+
+    /// Corrupt the outputs of the target plugin executed by `registry`
+    /// until the returned guard is dropped, except while suspended
+    pub(crate) fn activate(
+        self,
+        registry: &PluginRegistry,
+    ) -> (InterceptorGuard, Weak<dyn Suspend>) {
+        tracing::info!(
+            "Corrupting outputs of plugin {} with {:?}",
+            self.target_plugin,
+            self.corruption_type
+        );
+        let strategy = Arc::new(self);
+        let suspender: Arc<dyn Suspend> = strategy.clone();
+        (registry.add_interceptor(strategy), Arc::downgrade(&suspender))
+    }
+
+    /// Apply the corruption to the data of `output`
+    ///
+    /// `output` is taken to come from the target plugin; `registry` is the
+    /// registry that executed it.
+    pub fn corrupt_plugin_output(
+        &self,
+        _registry: &PluginRegistry,
+        mut output: PluginOutput,
+    ) -> PluginOutput {
+        let mut data: BTreeMap<_, _> = output.data.drain().collect();
+        if self.corruption_type == CorruptionType::Reorder {
+            let (keys, mut values): (Vec<_>, Vec<_>) = data.into_iter().unzip();
+            values.rotate_right(1);
+            data = keys.into_iter().zip(values).collect();
+        }
+        let mut bytes = serde_json::to_vec(&data).expect("JSON values always serialize");
+
+        match self.corruption_type {
+            CorruptionType::BitFlip { byte_offset } => {
+                if let Some(byte) = bytes.get_mut(byte_offset) {
+                    *byte ^= 1;
+                }
+            },
+            CorruptionType::Truncate { to_bytes } => bytes.truncate(to_bytes),
+            CorruptionType::Replay { times } => {
+                let mut replay = self.replay.lock();
+                match replay.as_mut() {
+                    Some((stale, left)) if *left > 0 => {
+                        *left -= 1;
+                        bytes.clone_from(stale);
+                    },
+                    _ => *replay = Some((bytes.clone(), times)),
+                }
+            },
+            CorruptionType::Reorder => {},
+        }
+
+        output.data = serde_json::from_slice(&bytes).unwrap_or_else(|_| {
+            let corrupted = String::from_utf8_lossy(&bytes).into_owned();
+            HashMap::from([(CORRUPTED_DATA_KEY.to_string(), corrupted.into())])
+        });
+        output
+    }
+}
+
+impl OutputInterceptor for StateCorruptionStrategy {
+    fn intercept(
+        &self,
+        registry: &PluginRegistry,
+        plugin_id: &str,
+        output: PluginOutput,
+    ) -> PluginOutput {
+        if plugin_id == self.target_plugin && !self.suspended.load(Ordering::Relaxed) {
+            self.corrupt_plugin_output(registry, output)
+        } else {
+            output
+        }
+    }
+}
+
+impl Suspend for StateCorruptionStrategy {
+    fn set_suspended(&self, suspended: bool) {
+        self.suspended.store(suspended, Ordering::Relaxed);
+    }
+}
+
+/// A plugin made slow by [`ChaosEngine::slow_plugin`](crate::ChaosEngine::slow_plugin)
+///
+/// Dropping it clears the fault.
+#[must_use = "the fault is cleared when the guard is dropped"]
+pub struct SlowPlugin {
+    fault: Arc<TimeoutFault>,
+}
+
+/// Timeout override of a [`SlowPlugin`], taken out while suspended
+struct TimeoutFault {
+    registry: PluginRegistry,
+    plugin_id: String,
+    timeout: Duration,
+    applied: Mutex<Option<TimeoutOverride>>,
+}
+
+impl SlowPlugin {
+    /// Force executions of `plugin_id` by `registry` to time out after
+    /// `timeout` until the returned guard is dropped, except while suspended
+    pub(crate) fn activate(
+        registry: &PluginRegistry,
+        plugin_id: &str,
+        timeout: Duration,
+    ) -> (Self, Weak<dyn Suspend>) {
+        tracing::info!("Overriding timeout of plugin {} to {:?}", plugin_id, timeout);
+        let fault = Arc::new(TimeoutFault {
+            registry: registry.clone(),
+            plugin_id: plugin_id.to_string(),
+            timeout,
+            applied: Mutex::new(Some(registry.override_timeout(plugin_id, timeout))),
+        });
+        let suspender: Arc<dyn Suspend> = fault.clone();
+        (Self { fault }, Arc::downgrade(&suspender))
+    }
+
+    /// Plugin made slow
+    pub fn plugin_id(&self) -> &str {
+        &self.fault.plugin_id
+    }
+}
+
+impl std::fmt::Debug for SlowPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlowPlugin")
+            .field("plugin_id", &self.fault.plugin_id)
+            .field("timeout", &self.fault.timeout)
+            .field("suspended", &self.fault.applied.lock().is_none())
+            .finish()
+    }
+}
+
+impl Suspend for TimeoutFault {
+    fn set_suspended(&self, suspended: bool) {
+        let mut applied = self.applied.lock();
+        if suspended {
+            *applied = None;
+        } else if applied.is_none() {
+            *applied = Some(self.registry.override_timeout(self.plugin_id.as_str(), self.timeout));
+        }
+    }
+}
+
+fn spin(exhaustion: &Exhaustion) {
+    while !exhaustion.cancel.load(Ordering::Relaxed) {
+        if exhaustion.suspended.load(Ordering::Relaxed) {
+            std::thread::sleep(SUSPENDED_POLL);
+            continue;
+        }
+        for _ in 0..SPINS_PER_YIELD {
+            std::hint::spin_loop();
+        }
+        std::thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use shared_core::{Plugin, PluginInput, PluginMetadata, PluginState};
+    use std::any::Any;
+    use std::time::Duration;
+
+    fn exhaust(resource: ResourceKind, target_bytes: u64) -> FaultScenario {
+        FaultScenario::ResourceExhaustion {
+            resource,
+            target_bytes,
+        }
+    }
+
+    #[test]
+    fn test_memory_released_on_drop() {
+        let strategy = ResourceExhaustionStrategy::default();
+        let handle = strategy.activate(&exhaust(ResourceKind::Memory, 4 << 20)).unwrap();
+        assert_eq!(handle.held_bytes(), 4 << 20);
+
+        let memory = Arc::downgrade(&handle.exhaustion);
+        drop(handle);
+        assert!(memory.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_rejects_invalid_scenarios() {
+        let strategy = ResourceExhaustionStrategy::new(1024);
+
+        let err = strategy.activate(&exhaust(ResourceKind::Memory, 2048)).unwrap_err();
+        assert!(matches!(
+            err,
+            SystemError::Validation { ref field, .. } if field == "target_bytes"
+        ));
+
+        let err = strategy.activate(&FaultScenario::ClockSkew { offset_ms: 5 }).unwrap_err();
+        assert!(matches!(err, SystemError::Validation { ref field, .. } if field == "scenario"));
+
+        let err = strategy.activate(&exhaust(ResourceKind::Cpu, 0)).unwrap_err();
+        assert!(matches!(err, SystemError::InvalidState { .. }));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cpu_worker_stops_on_drop() {
+        let strategy = ResourceExhaustionStrategy::default();
+        let mut handle = strategy.activate(&exhaust(ResourceKind::Cpu, 0)).unwrap();
+        assert_eq!(handle.held_bytes(), 0);
+
+        let worker = handle.worker.take().unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!worker.is_finished());
+
+        // A suspended worker idles until resumed or dropped
+        handle.suspender().upgrade().unwrap().set_suspended(true);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!worker.is_finished());
+
+        drop(handle);
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("worker did not stop")
+            .unwrap();
+    }
+
+    fn output() -> PluginOutput {
+        PluginOutput::success().with_data("a", json!(1)).with_data("b", json!([2, 3]))
+    }
+
+    fn corrupt(corruption_type: CorruptionType) -> PluginOutput {
+        StateCorruptionStrategy::new("peer", corruption_type)
+            .corrupt_plugin_output(&PluginRegistry::new(), output())
+    }
+
+    #[test]
+    fn test_corruptions() {
+        // Serialized data: {"a":1,"b":[2,3]}
+        let flipped = corrupt(CorruptionType::BitFlip { byte_offset: 5 });
+        assert!(flipped.success);
+        assert_eq!(flipped.data, output().with_data("a", json!(0)).data);
+
+        let flipped = corrupt(CorruptionType::BitFlip { byte_offset: 0 });
+        assert_eq!(flipped.data[CORRUPTED_DATA_KEY], "z\"a\":1,\"b\":[2,3]}");
+        let intact = corrupt(CorruptionType::BitFlip { byte_offset: 100 });
+        assert_eq!(intact.data, output().data);
+
+        let truncated = corrupt(CorruptionType::Truncate { to_bytes: 6 });
+        assert_eq!(truncated.data[CORRUPTED_DATA_KEY], "{\"a\":1");
+        assert_eq!(corrupt(CorruptionType::Truncate { to_bytes: 2 }).data.len(), 1);
+
+        let reordered = corrupt(CorruptionType::Reorder);
+        assert_eq!(reordered.data["a"], json!([2, 3]));
+        assert_eq!(reordered.data["b"], json!(1));
+    }
+
+    #[test]
+    fn test_replay_repeats_stale_data() {
+        let registry = PluginRegistry::new();
+        let strategy = StateCorruptionStrategy::new("peer", CorruptionType::Replay { times: 2 });
+        let seen: Vec<_> = (0..5)
+            .map(|n| {
+                let fresh = PluginOutput::success().with_data("n", json!(n));
+                strategy.corrupt_plugin_output(&registry, fresh).data["n"].clone()
+            })
+            .collect();
+        assert_eq!(seen, [json!(0), json!(0), json!(0), json!(3), json!(3)]);
+    }
+
+    struct Peer(PluginMetadata);
+
+    #[async_trait::async_trait]
+    impl Plugin for Peer {
+        fn metadata(&self) -> &PluginMetadata {
+            &self.0
+        }
+
+        async fn execute(&mut self, _input: PluginInput) -> Result<PluginOutput> {
+            Ok(output())
+        }
+
+        fn state(&self) -> PluginState {
+            PluginState::Active
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corruption_scoped_to_target() {
+        let registry = PluginRegistry::new();
+        for id in ["peer", "honest"] {
+            let plugin = Peer(PluginMetadata::new(id, id, "1.0.0"));
+            registry.register(Box::new(plugin)).await.unwrap();
+        }
+        let data = |id| {
+            let registry = registry.clone();
+            async move { registry.execute(id, PluginInput::new()).await.unwrap().data }
+        };
+
+        let strategy = StateCorruptionStrategy::new("peer", CorruptionType::Reorder);
+        let (fault, suspender) = strategy.activate(&registry);
+        assert_eq!(data("peer").await["a"], json!([2, 3]));
+        assert_eq!(data("honest").await, output().data);
+
+        suspender.upgrade().unwrap().set_suspended(true);
+        assert_eq!(data("peer").await, output().data);
+        suspender.upgrade().unwrap().set_suspended(false);
+        assert_eq!(data("peer").await["a"], json!([2, 3]));
+
+        drop(fault);
+        assert_eq!(data("peer").await, output().data);
+    }
+}
@@ -0,0 +1,231 @@
+//! ABI module
+//!
+//! The interface a contract offers embedders, derived from the typed IR so
+//! every target reports the same [`ContractAbi`]. Wasm modules carry it as
+//! JSON in the [`ABI_SECTION`] custom section, and generated Rust as the
+//! `ABI` constant of the generated module. Everything is listed in
+//! declaration order, so unchanged sources always produce the same ABI.
+
+use serde::{Deserialize, Serialize};
+use shared_core::Result;
+use wasmparser::{Parser, Payload};
+
+use crate::codegen::ABI_SECTION;
+use crate::hir::{self, Ty};
+
+/// Prefix of the line of generated Rust defining the ABI constant
+pub(crate) const RUST_ABI_CONST: &str = "pub const ABI: &str = r#\"";
+
+/// Interface of a compiled contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractAbi {
+    /// Contract name
+    pub name: String,
+    /// Contract functions callers can call; library functions are not
+    /// listed
+    pub functions: Vec<FunctionAbi>,
+    /// State variables
+    pub state: Vec<StateAbi>,
+    /// Events the contract can emit
+    pub events: Vec<EventAbi>,
+}
+
+/// Signature of a contract function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionAbi {
+    /// Function name
+    pub name: String,
+    /// Parameters, in order
+    pub params: Vec<ParamAbi>,
+    /// Return type, [`Ty::Unit`] for none
+    pub returns: Ty,
+}
+
+/// A function parameter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamAbi {
+    /// Parameter name
+    pub name: String,
+    /// Parameter type
+    pub ty: Ty,
+}
+
+/// A state variable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateAbi {
+    /// Variable name
+    pub name: String,
+    /// Variable type
+    pub ty: Ty,
+}
+
+/// An event and its fields, in the order the runtime receives them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventAbi {
+    /// Event name
+    pub name: String,
+    /// Fields, in declaration order
+    pub fields: Vec<ParamAbi>,
+}
+
+impl ContractAbi {
+    /// ABI of a type-checked contract
+    pub fn from_contract(contract: &hir::Contract) -> Self {
+        let functions = contract
+            .functions
+            .iter()
+            .filter(|function| function.exported)
+            .map(|function| FunctionAbi {
+                name: function.name.clone(),
+                params: function
+                    .params
+                    .iter()
+                    .map(|id| {
+                        let local = &function.locals[id.0];
+                        ParamAbi {
+                            name: local.name.clone(),
+                            ty: local.ty.clone(),
+                        }
+                    })
+                    .collect(),
+                returns: function.return_type.clone(),
+            })
+            .collect();
+        let state = contract
+            .state
+            .iter()
+            .map(|var| StateAbi {
+                name: var.name.clone(),
+                ty: var.ty.clone(),
+            })
+            .collect();
+        let events = contract
+            .events
+            .iter()
+            .map(|event| EventAbi {
+                name: event.name.clone(),
+                fields: event
+                    .fields
+                    .iter()
+                    .map(|field| ParamAbi {
+                        name: field.name.clone(),
+                        ty: field.ty.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            name: contract.name.clone(),
+            functions,
+            state,
+            events,
+        }
+    }
+
+    /// Serialize the ABI to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Deserialize an ABI from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// ABI of a Wasm module, `None` if it has none or it is malformed
+    pub(crate) fn from_wasm(bytes: &[u8]) -> Option<Self> {
+        Parser::new(0).parse_all(bytes).find_map(|payload| match payload {
+            Ok(Payload::CustomSection(section)) if section.name() == ABI_SECTION => {
+                serde_json::from_slice(section.data()).ok()
+            },
+            _ => None,
+        })
+    }
+
+    /// ABI of generated Rust, `None` if it has none or it is malformed
+    pub(crate) fn from_rust(source: &str) -> Option<Self> {
+        source.lines().find_map(|line| {
+            let json = line.trim_start().strip_prefix(RUST_ABI_CONST)?.strip_suffix("\"#;")?;
+            serde_json::from_str(json).ok()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{CompilationTarget, CompilerConfig, ContractCompiler, InMemoryResolver, OptLevel};
+
+    const FIXTURES: &str = "tests/fixtures/codegen";
+
+    fn fixtures() -> Vec<PathBuf> {
+        let mut paths: Vec<_> = fs::read_dir(FIXTURES)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "contract"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn abi(source: &str, target: CompilationTarget, opt_level: OptLevel) -> Option<ContractAbi> {
+        let compiler = ContractCompiler::new(CompilerConfig {
+            target,
+            opt_level,
+            ..CompilerConfig::default()
+        })
+        .unwrap();
+        compiler.compile(source).ok()?.abi()
+    }
+
+    /// Compare the ABI of every fixture with the JSON snapshot next to it;
+    /// set `UPDATE_SNAPSHOTS=1` to rewrite the snapshots instead
+    #[test]
+    fn test_abi_snapshots() {
+        for path in fixtures() {
+            let source = fs::read_to_string(&path).unwrap();
+            let json = abi(&source, CompilationTarget::Rust, OptLevel::Full)
+                .unwrap()
+                .to_json()
+                .unwrap();
+            let snapshot = path.with_extension("abi.json");
+            if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+                fs::write(&snapshot, format!("{json}\n")).unwrap();
+                continue;
+            }
+            let expected = fs::read_to_string(&snapshot)
+                .unwrap_or_else(|_| panic!("missing snapshot {}", snapshot.display()));
+            assert_eq!(format!("{json}\n"), expected, "snapshot {} differs", snapshot.display());
+            assert_eq!(ContractAbi::from_json(&json).unwrap().to_json().unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_abi_is_the_same_for_every_target() {
+        let source = fs::read_to_string(format!("{FIXTURES}/counter.contract")).unwrap();
+        let expected = abi(&source, CompilationTarget::Rust, OptLevel::None).unwrap();
+        assert_eq!(expected.functions.len(), 4);
+        assert_eq!(expected.functions[1].params[0].name, "amount");
+        for opt_level in [OptLevel::None, OptLevel::Full] {
+            for target in [CompilationTarget::Rust, CompilationTarget::Wasm] {
+                assert_eq!(abi(&source, target, opt_level).as_ref(), Some(&expected));
+            }
+        }
+
+        // Library functions are not part of the interface
+        let compiler = ContractCompiler::new(CompilerConfig::default())
+            .unwrap()
+            .with_resolver(
+                InMemoryResolver::new().with_module("math", "pub fn one() -> u64 { return 1; }"),
+            );
+        let output = compiler
+            .compile("import math; contract C { fn f() -> u64 { return math::one(); } }")
+            .unwrap();
+        let names: Vec<_> = output.abi().unwrap().functions.into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["f"]);
+        assert!(ContractAbi::from_rust("pub mod c {}").is_none());
+    }
+}
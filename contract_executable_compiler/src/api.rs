@@ -1 +1,882 @@
 //! Api module
+//!
+//! Check server for editors: JSON-RPC 2.0 over stdio, speaking the subset of
+//! the Language Server Protocol below.
+//!
+//! - `initialize` and `shutdown` requests, `initialized` and `exit`
+//!   notifications
+//! - `textDocument/didOpen`, `didChange` and `didClose`, each answered with a
+//!   `textDocument/publishDiagnostics` notification for the document
+//! - `textDocument/formatting`, through [`format_source`](crate::format_source)
+//! - `textDocument/hover`, showing the types the type checker inferred
+//! - `textDocument/documentSymbol` and `textDocument/definition`
+//!
+//! Documents are checked through the [compilation cache](crate::cache), so
+//! an edit only parses and type checks the edited document again, not the
+//! modules it imports. Diagnostics of imported modules are not published.
+//! Positions count UTF-16 code units, as the protocol requires.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use shared_core::{Result, SystemError};
+
+use crate::ast::Span;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::hir::{self, Callee, Ty};
+use crate::module::Program;
+use crate::{CacheStats, CompilerConfig, ContractCompiler};
+
+/// JSON-RPC error: the message is not valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error: the message is not a request or notification
+pub const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error: the server does not handle the method
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error: the parameters do not fit the method
+pub const INVALID_PARAMS: i64 = -32602;
+/// LSP error: the request is valid but could not be carried out
+pub const REQUEST_FAILED: i64 = -32803;
+
+/// Serve an editor on stdin and stdout until it sends `exit` or closes stdin
+pub fn serve_stdio(config: CompilerConfig) -> Result<()> {
+    let mut server = CheckServer::new(ContractCompiler::new(config)?);
+    server.run(io::stdin().lock(), io::stdout().lock())
+}
+
+/// Language server state: the compiler and the documents the editor opened
+pub struct CheckServer {
+    compiler: ContractCompiler,
+    documents: HashMap<String, Document>,
+    shutting_down: bool,
+    exited: bool,
+}
+
+/// An open document and the result of checking its latest text
+struct Document {
+    text: String,
+    /// Module id of the document, as in [`Diagnostic::file`]
+    file: String,
+    /// The loaded program, `None` if it failed to load
+    program: Option<Program>,
+    /// Unoptimized IR of the program, `None` unless it checks
+    contract: Option<hir::Contract>,
+    /// What the cache did for the latest check
+    stats: CacheStats,
+}
+
+/// Error answering a request
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl CheckServer {
+    /// Serve with `compiler`, whose resolver loads the imports of documents
+    pub fn new(compiler: ContractCompiler) -> Self {
+        Self {
+            compiler,
+            documents: HashMap::new(),
+            shutting_down: false,
+            exited: false,
+        }
+    }
+
+    /// Answer the messages read from `input` on `output` until `exit` or
+    /// the end of `input`
+    ///
+    /// Fails on I/O errors and on messages without a `Content-Length`
+    /// header; a body that is not JSON is answered with a parse error.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<()> {
+        while let Some(body) = read_message(&mut input)? {
+            let replies = match serde_json::from_slice(&body) {
+                Ok(message) => self.handle(message),
+                Err(err) => vec![error_response(
+                    Value::Null,
+                    RpcError::new(PARSE_ERROR, err.to_string()),
+                )],
+            };
+            for reply in &replies {
+                write_message(&mut output, reply)?;
+            }
+            if self.exited {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// What the compilation cache did when the open document `uri` was last
+    /// checked
+    pub fn cache_stats(&self, uri: &str) -> Option<&CacheStats> {
+        self.documents.get(uri).map(|document| &document.stats)
+    }
+
+    /// Handle one message, returning the responses and notifications to send
+    pub fn handle(&mut self, message: Value) -> Vec<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // Responses are never asked for, so only malformed messages land here
+            return match (id, message.get("result").or(message.get("error"))) {
+                (_, Some(_)) => Vec::new(),
+                (id, None) => vec![error_response(
+                    id.unwrap_or(Value::Null),
+                    RpcError::new(INVALID_REQUEST, "expected a request or notification"),
+                )],
+            };
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        match id {
+            Some(id) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(err) => error_response(id, err),
+                };
+                vec![response]
+            },
+            None => self.notify(method, params).unwrap_or_else(|err| {
+                tracing::warn!("Ignoring {} notification: {}", method, err.message);
+                Vec::new()
+            }),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> std::result::Result<Value, RpcError> {
+        if self.shutting_down {
+            return Err(RpcError::new(INVALID_REQUEST, "the server is shutting down"));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 1 },
+                    "documentFormattingProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            })),
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            },
+            "textDocument/formatting" => {
+                let params: DocumentParams = parse_params(params)?;
+                self.formatting(&params.text_document.uri)
+            },
+            "textDocument/hover" => {
+                let params: PositionParams = parse_params(params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let hover = document.reference_at(params.position).map(|reference| {
+                    json!({
+                        "contents": { "kind": "plaintext", "value": reference.hover },
+                        "range": range(&document.text, reference.span),
+                    })
+                });
+                Ok(hover.unwrap_or(Value::Null))
+            },
+            "textDocument/definition" => {
+                let params: PositionParams = parse_params(params)?;
+                let uri = &params.text_document.uri;
+                let document = self.document(uri)?;
+                let location = document.reference_at(params.position).and_then(|reference| {
+                    let (file, span) = reference.target?;
+                    let (uri, source) = match file {
+                        Some(file) if file == document.file => {
+                            (uri.clone(), document.text.as_str())
+                        },
+                        Some(file) if file.starts_with('/') => {
+                            let source = document.program.as_ref()?.source(Some(&file))?;
+                            (format!("file://{file}"), source)
+                        },
+                        // Built into the compiler, so not something editors can open
+                        _ => return None,
+                    };
+                    Some(json!({ "uri": uri, "range": range(source, span) }))
+                });
+                Ok(location.unwrap_or(Value::Null))
+            },
+            "textDocument/documentSymbol" => {
+                let params: DocumentParams = parse_params(params)?;
+                Ok(Value::Array(self.document(&params.text_document.uri)?.symbols()))
+            },
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {method}"))),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> std::result::Result<Vec<Value>, RpcError> {
+        match method {
+            "exit" => {
+                self.exited = true;
+                Ok(Vec::new())
+            },
+            "textDocument/didOpen" => {
+                let params: OpenParams = parse_params(params)?;
+                let item = params.text_document;
+                Ok(vec![self.check(item.uri, item.text, item.version)])
+            },
+            "textDocument/didChange" => {
+                let params: ChangeParams = parse_params(params)?;
+                let uri = params.text_document.uri;
+                let mut text = self.document(&uri)?.text.clone();
+                for change in params.content_changes {
+                    match change.range {
+                        Some(changed) => {
+                            let start = offset(&text, changed.start);
+                            let end = offset(&text, changed.end).max(start);
+                            text.replace_range(start..end, &change.text);
+                        },
+                        None => text = change.text,
+                    }
+                }
+                Ok(vec![self.check(uri, text, params.text_document.version)])
+            },
+            "textDocument/didClose" => {
+                let params: DocumentParams = parse_params(params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                Ok(vec![publish_diagnostics(&uri, None, Vec::new())])
+            },
+            // Other notifications, `initialized` and `$/` ones included, need
+            // no answer
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Check the new `text` of the document `uri`, returning its diagnostics
+    fn check(&mut self, uri: String, text: String, version: Option<i64>) -> Value {
+        let started = Instant::now();
+        let file = uri.strip_prefix("file://").unwrap_or(&uri).to_string();
+        let mut stats = CacheStats::default();
+        let (loaded, diagnostics) =
+            self.compiler.check_cached(Some(file.clone()), text.clone(), &mut stats);
+        tracing::debug!("Checked {} in {:?}: {:?}", uri, started.elapsed(), stats);
+
+        let diagnostics = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.file.as_deref() == Some(file.as_str()))
+            .map(|diagnostic| lsp_diagnostic(&uri, &text, diagnostic))
+            .collect();
+        let published = publish_diagnostics(&uri, version, diagnostics);
+        let (program, contract) = match loaded {
+            Some((program, contract)) => (Some(program), contract),
+            None => (None, None),
+        };
+        let document = Document {
+            text,
+            file,
+            program,
+            contract,
+            stats,
+        };
+        self.documents.insert(uri, document);
+        published
+    }
+
+    fn formatting(&self, uri: &str) -> std::result::Result<Value, RpcError> {
+        let document = self.document(uri)?;
+        let formatted = self
+            .compiler
+            .format(&document.text)
+            .map_err(|err| RpcError::new(REQUEST_FAILED, err.to_string()))?;
+        if formatted == document.text {
+            return Ok(json!([]));
+        }
+        let whole = json!({
+            "start": { "line": 0, "character": 0 },
+            "end": position(&document.text, document.text.len()),
+        });
+        Ok(json!([{ "range": whole, "newText": formatted }]))
+    }
+
+    fn document(&self, uri: &str) -> std::result::Result<&Document, RpcError> {
+        self.documents
+            .get(uri)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("{uri} is not open")))
+    }
+}
+
+/// A name or expression of a document the type checker resolved
+struct Reference {
+    span: Span,
+    /// Hover text: the type, or the signature of what is named
+    hover: String,
+    /// Module and span of the declaration of what is named
+    target: Option<(Option<String>, Span)>,
+}
+
+impl Document {
+    /// Innermost reference at an editor position, if the document checks
+    fn reference_at(&self, at: LspPosition) -> Option<Reference> {
+        let contract = self.contract.as_ref()?;
+        let at = offset(&self.text, at);
+        let mut references = Vec::new();
+        for function in &contract.functions {
+            if function.file.as_deref() != Some(self.file.as_str()) {
+                continue;
+            }
+            for local in &function.locals {
+                references.push(Reference {
+                    span: local.span,
+                    hover: format!("{}: {}", local.name, local.ty),
+                    target: Some((function.file.clone(), local.span)),
+                });
+            }
+            collect_block(contract, function, &function.body, &mut references);
+        }
+        references
+            .into_iter()
+            .filter(|reference| {
+                reference.span.start.offset <= at && at < reference.span.end.offset
+            })
+            .min_by_key(|reference| reference.span.end.offset - reference.span.start.offset)
+    }
+
+    /// Outline of the document, from its syntax tree
+    fn symbols(&self) -> Vec<Value> {
+        let Some(program) = &self.program else {
+            return Vec::new();
+        };
+        let ast = &program.modules()[0].ast;
+        let symbol = |name: &crate::ast::Ident, kind: u32, span: Span, children: Vec<Value>| {
+            json!({
+                "name": name.name,
+                "kind": kind,
+                "range": range(&self.text, span),
+                "selectionRange": range(&self.text, name.span),
+                "children": children,
+            })
+        };
+        // Symbol kinds of the protocol
+        const CLASS: u32 = 5;
+        const METHOD: u32 = 6;
+        const FIELD: u32 = 8;
+        const FUNCTION: u32 = 12;
+        const EVENT: u32 = 24;
+
+        let mut symbols: Vec<Value> = ast
+            .functions
+            .iter()
+            .map(|function| symbol(&function.name, FUNCTION, function.span, Vec::new()))
+            .collect();
+        if let Some(contract) = &ast.contract {
+            let fields = contract.state.iter().map(|f| symbol(&f.name, FIELD, f.span, Vec::new()));
+            let events = contract.events.iter().map(|e| symbol(&e.name, EVENT, e.span, Vec::new()));
+            let functions =
+                contract.functions.iter().map(|f| symbol(&f.name, METHOD, f.span, Vec::new()));
+            let children = fields.chain(events).chain(functions).collect();
+            symbols.push(symbol(&contract.name, CLASS, contract.span, children));
+        }
+        symbols
+    }
+}
+
+fn collect_block(
+    contract: &hir::Contract,
+    function: &hir::Function,
+    block: &hir::Block,
+    references: &mut Vec<Reference>,
+) {
+    let expr = |expr: &hir::Expr, references: &mut Vec<Reference>| {
+        collect_expr(contract, function, expr, references);
+    };
+    for stmt in &block.statements {
+        match &stmt.kind {
+            hir::StmtKind::Let { value, .. } | hir::StmtKind::Expr(value) => {
+                expr(value, references);
+            },
+            hir::StmtKind::Assign { place, value } => {
+                expr(place, references);
+                expr(value, references);
+            },
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                expr(condition, references);
+                collect_block(contract, function, then_branch, references);
+                if let Some(else_branch) = else_branch {
+                    collect_block(contract, function, else_branch, references);
+                }
+            },
+            hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. } => {
+                expr(condition, references);
+            },
+            hir::StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    expr(value, references);
+                }
+            },
+            hir::StmtKind::Emit { fields, .. } => {
+                for field in fields {
+                    expr(field, references);
+                }
+            },
+        }
+    }
+}
+
+fn collect_expr(
+    contract: &hir::Contract,
+    function: &hir::Function,
+    expr: &hir::Expr,
+    references: &mut Vec<Reference>,
+) {
+    let (hover, target) = match &expr.kind {
+        hir::ExprKind::Local(id) => {
+            let local = &function.locals[id.0];
+            (format!("{}: {}", local.name, local.ty), Some((function.file.clone(), local.span)))
+        },
+        hir::ExprKind::State(index) => {
+            let state = &contract.state[*index];
+            (format!("{}: {}", state.name, state.ty), Some((contract.file.clone(), state.span)))
+        },
+        hir::ExprKind::Call {
+            callee: Callee::Function(index),
+            ..
+        } => {
+            let callee = &contract.functions[*index];
+            let params = callee.params.iter().map(|param| &callee.locals[param.0]);
+            let params = params.map(|param| (param.name.as_str(), &param.ty));
+            let hover = signature(&callee.name, params, &callee.return_type);
+            (hover, Some((callee.file.clone(), callee.span)))
+        },
+        hir::ExprKind::Call {
+            callee: Callee::Builtin(builtin),
+            ..
+        } => {
+            let (params, return_type) = builtin.signature();
+            let params = params.iter().map(|ty| ("_", ty));
+            (signature(builtin.name(), params, &return_type), None)
+        },
+        _ => (expr.ty.to_string(), None),
+    };
+    references.push(Reference {
+        span: expr.span,
+        hover,
+        target,
+    });
+
+    match &expr.kind {
+        hir::ExprKind::Unary { operand, .. } => {
+            collect_expr(contract, function, operand, references);
+        },
+        hir::ExprKind::Binary { lhs, rhs, .. } => {
+            collect_expr(contract, function, lhs, references);
+            collect_expr(contract, function, rhs, references);
+        },
+        hir::ExprKind::Index { base, index } => {
+            collect_expr(contract, function, base, references);
+            collect_expr(contract, function, index, references);
+        },
+        hir::ExprKind::Call { args: elements, .. } | hir::ExprKind::Array(elements) => {
+            for element in elements {
+                collect_expr(contract, function, element, references);
+            }
+        },
+        hir::ExprKind::Int(_)
+        | hir::ExprKind::Bool(_)
+        | hir::ExprKind::Str(_)
+        | hir::ExprKind::Local(_)
+        | hir::ExprKind::State(_) => {},
+    }
+}
+
+/// `fn name(a: u64) -> bool`
+fn signature<'a>(
+    name: &str,
+    params: impl Iterator<Item = (&'a str, &'a Ty)>,
+    return_type: &Ty,
+) -> String {
+    let params: Vec<String> = params.map(|(name, ty)| format!("{name}: {ty}")).collect();
+    match return_type {
+        Ty::Unit => format!("fn {name}({})", params.join(", ")),
+        ty => format!("fn {name}({}) -> {ty}", params.join(", ")),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentId {
+    uri: String,
+    #[serde(default)]
+    version: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentItem {
+    uri: String,
+    text: String,
+    #[serde(default)]
+    version: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentParams {
+    text_document: DocumentId,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenParams {
+    text_document: DocumentItem,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeParams {
+    text_document: DocumentId,
+    content_changes: Vec<ContentChange>,
+}
+
+/// New text of the document, or of `range` of it
+#[derive(Deserialize)]
+struct ContentChange {
+    #[serde(default)]
+    range: Option<LspRange>,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PositionParams {
+    text_document: DocumentId,
+    position: LspPosition,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct LspRange {
+    start: LspPosition,
+    end: LspPosition,
+}
+
+/// 0-based line and UTF-16 offset in it
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct LspPosition {
+    line: usize,
+    character: usize,
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> std::result::Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+/// Byte offset of `position` in `source`, clamped to the end of its line
+fn offset(source: &str, position: LspPosition) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match source[line_start..].find('\n') {
+            Some(end) => line_start += end + 1,
+            None => return source.len(),
+        }
+    }
+    let line = &source[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= position.character {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Protocol position of the byte `offset` of `source`
+fn position(source: &str, offset: usize) -> Value {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+fn range(source: &str, span: Span) -> Value {
+    json!({
+        "start": position(source, span.start.offset),
+        "end": position(source, span.end.offset),
+    })
+}
+
+fn lsp_diagnostic(uri: &str, source: &str, diagnostic: &Diagnostic) -> Value {
+    let mut message = diagnostic.message.clone();
+    if let Some(help) = &diagnostic.help {
+        message.push_str("\nhelp: ");
+        message.push_str(help);
+    }
+    let related: Vec<Value> = diagnostic
+        .labels
+        .iter()
+        .map(|label| {
+            json!({
+                "location": { "uri": uri, "range": range(source, label.span) },
+                "message": label.message,
+            })
+        })
+        .collect();
+    json!({
+        "range": range(source, diagnostic.span),
+        "severity": match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "code": diagnostic.code.as_str(),
+        "source": "contract",
+        "message": message,
+        "relatedInformation": related,
+    })
+}
+
+fn publish_diagnostics(uri: &str, version: Option<i64>, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "version": version, "diagnostics": diagnostics },
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// Body of the next message, `None` at the end of `input`
+fn read_message(input: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line).map_err(|e| SystemError::io(e, "reading a header"))? == 0 {
+            return match length {
+                None => Ok(None),
+                Some(_) => Err(SystemError::io("unexpected end of input", "reading a header")),
+            };
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                let value = value.trim();
+                length = Some(value.parse::<usize>().map_err(|_| {
+                    SystemError::validation("Content-Length", "is not a length", Some(value.into()))
+                })?);
+            }
+        }
+    }
+    let length =
+        length.ok_or_else(|| SystemError::validation("Content-Length", "is missing", None))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(|e| SystemError::io(e, "reading a message"))?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|()| output.flush())
+        .map_err(|e| SystemError::io(e, "writing a message"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::cache::StageStats;
+    use crate::InMemoryResolver;
+
+    const URI: &str = "file:///work/vault.contract";
+    const UTIL: &str = "pub fn double(x: u64) -> u64 { return x + x; }";
+    const VAULT: &str = "import util;
+contract Vault {
+    state { total: u64; }
+    fn deposit(amount: u64) -> u64 {
+        let doubled = util::double(amount);
+        self.total = self.total + doubled;
+        return self.total;
+    }
+}
+";
+
+    fn frame(message: &Value) -> Vec<u8> {
+        let mut framed = Vec::new();
+        write_message(&mut framed, message).unwrap();
+        framed
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Vec<u8> {
+        frame(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+
+    fn notification(method: &str, params: Value) -> Vec<u8> {
+        frame(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn change(version: i64, text: &str) -> Vec<u8> {
+        notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": version },
+                "contentChanges": [{ "text": text }],
+            }),
+        )
+    }
+
+    fn at(line: usize, character: usize) -> Value {
+        let position = json!({ "line": line, "character": character });
+        json!({ "textDocument": { "uri": URI }, "position": position })
+    }
+
+    fn open(text: &str) -> Vec<u8> {
+        let document = json!({ "uri": URI, "languageId": "contract", "version": 1, "text": text });
+        notification("textDocument/didOpen", json!({ "textDocument": document }))
+    }
+
+    /// Run a scripted session, returning the server and what it sent
+    fn session(script: &[Vec<u8>]) -> (CheckServer, Vec<Value>) {
+        let resolver = InMemoryResolver::new().with_module("util", UTIL);
+        let compiler = ContractCompiler::new(CompilerConfig::default()).unwrap();
+        let mut server = CheckServer::new(compiler.with_resolver(resolver));
+        let mut output = Vec::new();
+        server.run(Cursor::new(script.concat()), &mut output).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut sent = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            sent.push(serde_json::from_slice(&body).unwrap());
+        }
+        (server, sent)
+    }
+
+    /// Codes and lines of published diagnostics
+    fn published(message: &Value, version: i64) -> Vec<(String, u64)> {
+        assert_eq!(message["method"], "textDocument/publishDiagnostics");
+        assert_eq!(message["params"]["uri"], URI);
+        assert_eq!(message["params"]["version"], version);
+        let diagnostics = message["params"]["diagnostics"].as_array().unwrap();
+        let line = |diagnostic: &Value| diagnostic["range"]["start"]["line"].as_u64().unwrap();
+        diagnostics.iter().map(|d| (d["code"].as_str().unwrap().to_string(), line(d))).collect()
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        let broken = VAULT.replace("self.total + doubled", "self.total + true");
+        let messy = VAULT.replace("    let doubled", "let   doubled");
+        let (server, sent) = session(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            open(VAULT),
+            change(2, &broken),
+            change(3, &messy),
+            request(2, "textDocument/formatting", json!({ "textDocument": { "uri": URI } })),
+            request(3, "textDocument/completion", at(0, 0)),
+            b"Content-Length: 1\r\n\r\n{".to_vec(),
+            request(4, "shutdown", Value::Null),
+            request(5, "textDocument/hover", at(0, 0)),
+            notification("exit", Value::Null),
+            request(6, "shutdown", Value::Null),
+        ]);
+
+        assert_eq!(sent.len(), 9, "{sent:#?}");
+        assert_eq!(sent[0]["id"], 1);
+        assert_eq!(sent[0]["result"]["capabilities"]["hoverProvider"], true);
+        // `deposit` updates state after calling `util`, adding unchecked
+        let lints = [("W0009".to_string(), 4), ("W0010".to_string(), 5)];
+        assert_eq!(published(&sent[1], 1), lints);
+        // The bad addition no longer uses `doubled`
+        let diagnostics = published(&sent[2], 2);
+        let expected = [("W0001", 4), ("W0009", 4), ("W0010", 5), ("E0004", 5)];
+        assert_eq!(diagnostics, expected.map(|(code, line)| (code.to_string(), line)));
+        assert_eq!(published(&sent[3], 3), lints);
+
+        assert_eq!(sent[4]["id"], 2);
+        let edits = sent[4]["result"].as_array().unwrap();
+        assert_eq!(edits[0]["newText"], crate::format_source(&messy).unwrap());
+        assert_eq!(edits[0]["range"]["end"], json!({ "line": 9, "character": 0 }));
+
+        assert_eq!(sent[5]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(sent[6]["error"]["code"], PARSE_ERROR);
+        assert_eq!(sent[6]["id"], Value::Null);
+        assert_eq!(sent[7]["result"], Value::Null);
+        assert_eq!(sent[8]["error"]["code"], INVALID_REQUEST);
+
+        // Edits recheck the document, never the module it imports
+        let stats = server.cache_stats(URI).unwrap();
+        assert_eq!(stats.check, StageStats { hits: 1, misses: 1 });
+        assert_eq!(stats.recompiled, ["contract"]);
+    }
+
+    #[test]
+    fn test_hover_definition_and_symbols() {
+        let mut closed = at(0, 0);
+        closed["textDocument"]["uri"] = json!("file:///closed.contract");
+        let (_, sent) = session(&[
+            open(VAULT),
+            request(1, "textDocument/hover", at(5, 22)),
+            request(2, "textDocument/hover", at(4, 24)),
+            request(3, "textDocument/definition", at(5, 35)),
+            request(4, "textDocument/definition", at(4, 24)),
+            request(5, "textDocument/documentSymbol", json!({ "textDocument": { "uri": URI } })),
+            request(6, "textDocument/hover", at(1, 0)),
+            request(7, "textDocument/hover", closed),
+        ]);
+        let hover = |index: usize| sent[index]["result"]["contents"]["value"].clone();
+        assert_eq!(hover(1), "total: u64");
+        assert_eq!(hover(2), "fn util::double(x: u64) -> u64");
+        // `doubled` is declared on line 4
+        let definition = &sent[3]["result"];
+        assert_eq!(definition["uri"], URI);
+        assert_eq!(definition["range"]["start"], json!({ "line": 4, "character": 12 }));
+        // The library is not a file editors can open
+        assert_eq!(sent[4]["result"], Value::Null);
+
+        let symbols = sent[5]["result"].as_array().unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0]["name"], "Vault");
+        let children = symbols[0]["children"].as_array().unwrap();
+        let children: Vec<&str> = children.iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!(children, ["total", "deposit"]);
+        assert_eq!(sent[6]["result"], Value::Null);
+        assert_eq!(sent[7]["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_positions_count_utf16() {
+        let source = "let s = \"é𝄞\"; x\nnext";
+        let x = source.find('x').unwrap();
+        assert_eq!(position(source, x), json!({ "line": 0, "character": 15 }));
+        assert_eq!(offset(source, LspPosition { line: 0, character: 15 }), x);
+        assert_eq!(offset(source, LspPosition { line: 0, character: 99 }), x + 1);
+        assert_eq!(offset(source, LspPosition { line: 1, character: 2 }), source.len() - 2);
+        assert_eq!(offset(source, LspPosition { line: 7, character: 0 }), source.len());
+
+        let mut input = Cursor::new(b"Content-Type: json\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut input).is_err());
+    }
+}
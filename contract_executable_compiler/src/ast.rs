@@ -0,0 +1,406 @@
+//! AST module
+//!
+//! Syntax tree of a contract, as produced by [`parse`](crate::parser::parse).
+//! Every node carries the [`Span`] of source text it was parsed from.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Location of a character in the source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Position {
+    /// Byte offset from the start of the source
+    pub offset: usize,
+    /// 1-based line
+    pub line: u32,
+    /// 1-based column, in characters
+    pub column: u32,
+}
+
+/// Source range of a node, end exclusive
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    /// First character of the node
+    pub start: Position,
+    /// Position just past the node
+    pub end: Position,
+}
+
+impl Span {
+    /// Span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}..{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+/// A name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ident {
+    /// The name
+    pub name: String,
+    /// Where it appears
+    pub span: Span,
+}
+
+/// A type annotation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Type {
+    /// What the annotation names
+    pub kind: TypeKind,
+    /// Where it appears
+    pub span: Span,
+}
+
+/// Kinds of type annotation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TypeKind {
+    /// Type name, resolved by [`typeck`](crate::typeck)
+    Named(String),
+    /// `[element; len]`
+    Array {
+        /// Element type
+        element: Box<Type>,
+        /// Number of elements
+        len: u64,
+    },
+}
+
+/// A source file: its imports, then either a contract or the functions of
+/// a library module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceFile {
+    /// `import` declarations, in order
+    pub imports: Vec<Import>,
+    /// The contract of a contract file
+    pub contract: Option<Contract>,
+    /// Functions of a library module
+    pub functions: Vec<Function>,
+}
+
+/// `import "path";` or `import a::b;`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Import {
+    /// The imported module
+    pub path: ImportPath,
+    /// The whole declaration
+    pub span: Span,
+}
+
+/// Kinds of module reference
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ImportPath {
+    /// File path, relative to the importing file or a search path; the
+    /// `.contract` extension may be left out
+    File(String),
+    /// `::`-separated module path, as in `std::math`
+    Module(Vec<String>),
+}
+
+impl ImportPath {
+    /// Name the imported module is referred to by: the last path segment,
+    /// without extension
+    pub fn alias(&self) -> &str {
+        match self {
+            Self::File(path) => {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                name.strip_suffix(".contract").unwrap_or(name)
+            },
+            Self::Module(segments) => segments.last().map_or("", String::as_str),
+        }
+    }
+}
+
+impl fmt::Display for ImportPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{path:?}"),
+            Self::Module(segments) => f.write_str(&segments.join("::")),
+        }
+    }
+}
+
+/// A whole contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contract {
+    /// Contract name
+    pub name: Ident,
+    /// Fields declared in `state` blocks
+    pub state: Vec<StateField>,
+    /// Declared events
+    pub events: Vec<Event>,
+    /// Contract functions
+    pub functions: Vec<Function>,
+    /// Lints allowed by `#[allow(...)]` attributes on the declaration
+    pub allow: Vec<Ident>,
+    /// The whole declaration
+    pub span: Span,
+}
+
+/// A persistent contract field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateField {
+    /// Field name
+    pub name: Ident,
+    /// Field type
+    pub ty: Type,
+    /// Lints allowed by `#[allow(...)]` attributes on the declaration
+    pub allow: Vec<Ident>,
+    /// The whole declaration
+    pub span: Span,
+}
+
+/// `event Name { field: type, ... }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// Event name
+    pub name: Ident,
+    /// Fields, in order
+    pub fields: Vec<Param>,
+    /// Lints allowed by `#[allow(...)]` attributes on the declaration
+    pub allow: Vec<Ident>,
+    /// The whole declaration
+    pub span: Span,
+}
+
+/// A contract or library function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Function {
+    /// Whether a library function is declared `pub`, and so callable from
+    /// modules importing it
+    pub public: bool,
+    /// Function name
+    pub name: Ident,
+    /// Parameters, in order
+    pub params: Vec<Param>,
+    /// Declared return type, `None` for functions returning nothing
+    pub return_type: Option<Type>,
+    /// Function body
+    pub body: Block,
+    /// Lints allowed by `#[allow(...)]` attributes on the declaration
+    pub allow: Vec<Ident>,
+    /// The whole declaration
+    pub span: Span,
+}
+
+/// A function parameter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Param {
+    /// Parameter name
+    pub name: Ident,
+    /// Parameter type
+    pub ty: Type,
+    /// The whole declaration
+    pub span: Span,
+}
+
+/// A braced list of statements
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    /// Statements, in order
+    pub statements: Vec<Stmt>,
+    /// The block including its braces
+    pub span: Span,
+}
+
+/// A statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stmt {
+    /// What the statement is
+    pub kind: StmtKind,
+    /// The whole statement
+    pub span: Span,
+}
+
+/// Kinds of statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StmtKind {
+    /// `let name: ty = value;`
+    Let {
+        /// Bound name
+        name: Ident,
+        /// Optional type annotation
+        ty: Option<Type>,
+        /// Bound value
+        value: Expr,
+    },
+    /// `target = value;`
+    Assign {
+        /// Assigned place, a name, field access or array element
+        target: Expr,
+        /// Assigned value
+        value: Expr,
+    },
+    /// `if condition { ... } else { ... }`
+    If {
+        /// Branch condition
+        condition: Expr,
+        /// Taken when the condition holds
+        then_branch: Block,
+        /// Taken otherwise; `else if` is an `If` statement alone in a block
+        else_branch: Option<Block>,
+    },
+    /// `require(condition, "message");`, reverting the call when false
+    Require {
+        /// Checked condition
+        condition: Expr,
+        /// Optional failure message
+        message: Option<String>,
+    },
+    /// `assert(condition, "message");`, an internal invariant
+    Assert {
+        /// Checked condition
+        condition: Expr,
+        /// Optional failure message
+        message: Option<String>,
+    },
+    /// `return value;`
+    Return(Option<Expr>),
+    /// `emit Name { field: value, ... };`
+    Emit {
+        /// Emitted event
+        event: Ident,
+        /// Field values, in source order
+        fields: Vec<FieldInit>,
+    },
+    /// An expression evaluated for its effects
+    Expr(Expr),
+}
+
+/// `field: value` in an `emit` statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldInit {
+    /// Field name
+    pub name: Ident,
+    /// Field value
+    pub value: Expr,
+}
+
+/// An expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expr {
+    /// What the expression is
+    pub kind: ExprKind,
+    /// The whole expression
+    pub span: Span,
+}
+
+/// Kinds of expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExprKind {
+    /// Unsigned integer literal
+    Int(u64),
+    /// `true` or `false`
+    Bool(bool),
+    /// String literal, escapes resolved
+    Str(String),
+    /// Reference to a name, qualified by a module alias as in `math::min`
+    /// when it contains `::`
+    Ident(String),
+    /// Prefix operator
+    Unary {
+        /// Operator
+        op: UnaryOp,
+        /// Operand
+        operand: Box<Expr>,
+    },
+    /// Infix operator
+    Binary {
+        /// Operator
+        op: BinaryOp,
+        /// Left operand
+        lhs: Box<Expr>,
+        /// Right operand
+        rhs: Box<Expr>,
+    },
+    /// Function call
+    Call {
+        /// Called expression
+        callee: Box<Expr>,
+        /// Arguments, in order
+        args: Vec<Expr>,
+    },
+    /// Field access `base.field`
+    Field {
+        /// Accessed expression
+        base: Box<Expr>,
+        /// Field name
+        field: Ident,
+    },
+    /// Array element `base[index]`
+    Index {
+        /// Indexed expression
+        base: Box<Expr>,
+        /// Element index
+        index: Box<Expr>,
+    },
+    /// Array literal `[a, b, c]`
+    Array(Vec<Expr>),
+}
+
+/// Prefix operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnaryOp {
+    /// `!`
+    Not,
+    /// `-`
+    Neg,
+}
+
+/// Infix operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BinaryOp {
+    /// `||`
+    Or,
+    /// `&&`
+    And,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Rem,
+}
+
+impl BinaryOp {
+    /// Binding power; higher binds tighter, all operators are left-associative
+    pub fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Eq | Self::Ne => 3,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 4,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Rem => 6,
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Bindings module
+//!
+//! Generates a typed Rust client for a contract from its [`ContractAbi`]. The
+//! client module defines `<Name>Client`, with one method per export of the
+//! contract's Wasm module: the contract functions, then a `get_` and `set_`
+//! accessor per state variable. Every method converts its arguments to JSON
+//! and calls
+//! [`ContractExecutor::call_typed`](crate::ContractExecutor::call_typed), so
+//! the client depends on this crate and `shared_core`.
+//!
+//! The code starts with a plain comment rather than an inner doc comment so
+//! it can be pulled in with `include!`, and is laid out the way `rustfmt`
+//! lays it out with default settings.
+
+use shared_core::{Result, SystemError};
+
+use crate::abi::ContractAbi;
+use crate::codegen::{exports, AbiFunction, INIT_EXPORT};
+use crate::hir::Ty;
+use crate::rust_codegen::{ident, raw, snake_case, Writer, MAX_WIDTH};
+
+/// Widest argument list `rustfmt` keeps on the line of its call
+const FN_CALL_WIDTH: usize = 60;
+/// Widest `vec!` contents `rustfmt` keeps on one line
+const ARRAY_WIDTH: usize = 60;
+/// Length of `ContractExecutor::call_typed()`
+const CALL_OVERHEAD: usize = 30;
+
+/// Generate the client module for the contract described by `abi`
+///
+/// The module is named `module_name`, by default `<contract>_client` in snake
+/// case. Contracts whose interface uses maps or arrays, which the client
+/// cannot pass, are `Validation` errors, as are invalid module names.
+pub fn generate_bindings(abi: &ContractAbi, module_name: Option<&str>) -> Result<String> {
+    let module = match module_name {
+        Some(name) => name.to_string(),
+        None => format!("{}_client", snake_case(&abi.name)),
+    };
+    let module = ident(&module).map_err(|reason| {
+        SystemError::validation("module_name", reason, Some(module.clone()))
+    })?;
+    let client = format!("{}Client", abi.name);
+
+    let mut out = Writer::default();
+    out.line(&format!(
+        "// Client for contract `{}`, generated by contract_executable_compiler; do not edit.",
+        abi.name
+    ));
+    out.blank();
+    out.open(&format!("pub mod {module} {{"));
+    out.line("use contract_executable_compiler::{ContractExecutor, ContractInstance};");
+    out.line("use shared_core::Result;");
+    out.blank();
+    out.line(&format!("/// Typed calls to an instance of contract `{}`", abi.name));
+    out.open(&format!("pub struct {client}<'a> {{"));
+    out.line("/// Executor making the calls");
+    out.line("pub executor: &'a ContractExecutor,");
+    out.line("/// Instance called");
+    out.line("pub instance: &'a ContractInstance,");
+    out.close("}");
+    out.blank();
+    out.open(&format!("impl {client}<'_> {{"));
+    let functions: Vec<_> =
+        exports(abi).into_iter().filter(|function| function.name != INIT_EXPORT).collect();
+    for (i, function) in functions.iter().enumerate() {
+        if i > 0 {
+            out.blank();
+        }
+        method(&mut out, abi, function)?;
+    }
+    out.close("}");
+    out.close("}");
+    Ok(out.finish())
+}
+
+fn method(out: &mut Writer, abi: &ContractAbi, function: &AbiFunction) -> Result<()> {
+    let names = param_names(abi, function);
+    let mut params = Vec::with_capacity(names.len());
+    for (name, ty) in names.iter().zip(&function.params) {
+        params.push(format!("{name}: {}", client_type(ty, &function.name)?));
+    }
+    let returns = client_type(&function.returns, &function.name)?;
+
+    out.line(&format!("/// {}", summary(&function.name)));
+    let name = raw(&function.name);
+    let head = format!("pub fn {name}(&self{}) -> Result<{returns}> {{", params_suffix(&params));
+    if out.indent() + head.len() <= MAX_WIDTH {
+        out.open(&head);
+    } else {
+        out.open(&format!("pub fn {name}("));
+        out.line("&self,");
+        for param in &params {
+            out.line(&format!("{param},"));
+        }
+        out.depth -= 1;
+        out.open(&format!(") -> Result<{returns}> {{"));
+    }
+    let values: Vec<_> = names.iter().map(|name| format!("{name}.into()")).collect();
+    let values = values.join(", ");
+    let args = [
+        "self.executor".to_string(),
+        "self.instance".to_string(),
+        format!("{:?}", function.name),
+    ];
+    let call = format!("ContractExecutor::call_typed({}, vec![{values}])", args.join(", "));
+    // Past `fn_call_width` arguments go on lines of their own
+    if out.indent() + call.len() <= MAX_WIDTH && call.len() - CALL_OVERHEAD <= FN_CALL_WIDTH {
+        out.line(&call);
+        out.close("}");
+        return Ok(());
+    }
+    out.open("ContractExecutor::call_typed(");
+    for arg in &args {
+        out.line(&format!("{arg},"));
+    }
+    let vec = format!("vec![{values}],");
+    if out.indent() + vec.len() <= MAX_WIDTH && values.len() <= ARRAY_WIDTH {
+        out.line(&vec);
+    } else {
+        out.open("vec![");
+        for name in &names {
+            out.line(&format!("{name}.into(),"));
+        }
+        out.close("],");
+    }
+    out.close(")");
+    out.close("}");
+    Ok(())
+}
+
+fn params_suffix(params: &[String]) -> String {
+    params.iter().map(|param| format!(", {param}")).collect()
+}
+
+/// Rust names of the parameters of `function`
+fn param_names(abi: &ContractAbi, function: &AbiFunction) -> Vec<String> {
+    if let Some(contract_fn) = abi.functions.iter().find(|f| f.name == function.name) {
+        return contract_fn.params.iter().map(|param| raw(&param.name)).collect();
+    }
+    // Accessors take one index per dimension, and setters the value last
+    let indices = function.params.len() - usize::from(function.name.starts_with("set_"));
+    let mut names: Vec<_> = match indices {
+        1 => vec!["index".to_string()],
+        n => (0..n).map(|i| format!("index{i}")).collect(),
+    };
+    if indices < function.params.len() {
+        names.push("value".to_string());
+    }
+    names
+}
+
+/// Doc line of the method calling `name`
+fn summary(name: &str) -> String {
+    if let Some(var) = name.strip_prefix("get_") {
+        format!("Read state variable `{var}`")
+    } else if let Some(var) = name.strip_prefix("set_") {
+        format!("Overwrite state variable `{var}`")
+    } else {
+        format!("Call contract function `{name}`")
+    }
+}
+
+/// Type the client uses for `ty` in the signature of `function`
+fn client_type(ty: &Ty, function: &str) -> Result<&'static str> {
+    match ty {
+        Ty::U64 | Ty::Address => Ok("u64"),
+        Ty::I64 => Ok("i64"),
+        Ty::Bool => Ok("bool"),
+        Ty::String => Ok("String"),
+        Ty::Unit => Ok("()"),
+        Ty::Map | Ty::Array { .. } => Err(SystemError::validation(
+            "abi",
+            format!("`{function}` passes a `{ty}` value, which the client does not support"),
+            Some(function.to_string()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::*;
+    use crate::{CompilerConfig, ContractCompiler};
+
+    const FIXTURES: &str = "tests/fixtures/bindings";
+
+    /// Check `code` is formatted the way `rustfmt` formats it by default,
+    /// unless `SKIP_RUSTC_TESTS` is set or `rustfmt` is missing
+    fn assert_formatted(code: &str) {
+        if std::env::var_os("SKIP_RUSTC_TESTS").is_some()
+            || Command::new("rustfmt").arg("--version").output().is_err()
+        {
+            return;
+        }
+        // Outside the repository, so its `rustfmt.toml` does not apply
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("client.rs");
+        fs::write(&file, code).unwrap();
+        let output = Command::new("rustfmt")
+            .args(["--edition", "2021", "--check"])
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    }
+
+    fn abi(source: &str) -> ContractAbi {
+        ContractCompiler::new(CompilerConfig::default())
+            .unwrap()
+            .compile(source)
+            .unwrap()
+            .abi()
+            .unwrap()
+    }
+
+    /// Compare the client generated for the counter fixture with the golden
+    /// file included by the `bindings` integration test; set
+    /// `UPDATE_SNAPSHOTS=1` to rewrite it instead
+    #[test]
+    fn test_golden_bindings() {
+        let source = fs::read_to_string("tests/fixtures/codegen/counter.contract").unwrap();
+        let bindings = generate_bindings(&abi(&source), None).unwrap();
+        let golden = Path::new(FIXTURES).join("counter.rs");
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&golden, &bindings).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|_| panic!("missing golden file {}", golden.display()));
+        assert_eq!(bindings, expected, "golden file {} differs", golden.display());
+        assert_formatted(&bindings);
+    }
+
+    #[test]
+    fn test_bindings_signatures() {
+        let abi = abi("contract Grid {
+            state { cells: [[bool; 2]; 3]; }
+            fn move(type: i64, a_parameter_with_a_long_name: u64, another_long_one: u64) {}
+        }");
+        let bindings = generate_bindings(&abi, Some("grid")).unwrap();
+        assert!(bindings.contains("pub mod grid {"));
+        let getter = "pub fn get_cells(&self, index0: u64, index1: u64) -> Result<bool>";
+        assert!(bindings.contains(getter));
+        assert!(bindings.contains("vec![index0.into(), index1.into(), value.into()]"));
+        // Keywords become raw identifiers, and long signatures wrap
+        assert!(bindings.contains("pub fn r#move(\n            &self,\n            r#type: i64,"));
+        assert_formatted(&bindings);
+
+        let abi = ContractAbi {
+            name: "Named".to_string(),
+            functions: Vec::new(),
+            state: vec![crate::abi::StateAbi {
+                name: "label".to_string(),
+                ty: Ty::String,
+            }],
+            events: Vec::new(),
+        };
+        let bindings = generate_bindings(&abi, None).unwrap();
+        assert!(bindings.contains("pub fn set_label(&self, value: String) -> Result<()>"));
+        let abi = ContractAbi {
+            state: vec![crate::abi::StateAbi {
+                name: "entries".to_string(),
+                ty: Ty::Map,
+            }],
+            ..abi
+        };
+        assert!(generate_bindings(&abi, None).is_err());
+        assert!(generate_bindings(&abi, Some("not a module")).is_err());
+    }
+}
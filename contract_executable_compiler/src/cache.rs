@@ -0,0 +1,891 @@
+//! Compilation cache
+//!
+//! Incremental compilation for
+//! [`ContractCompiler::compile_project`](crate::ContractCompiler::compile_project).
+//! The result of every [`Stage`] is stored under a BLAKE3 key of everything
+//! it depends on, so unchanged work is loaded instead of redone:
+//!
+//! - parsing, per module, depends on the module source;
+//! - type checking and optimization, per module, depend on the module's
+//!   name, id and source and on the keys of the modules it imports, so
+//!   editing a module rebuilds it and the modules importing it, directly or
+//!   not, only;
+//! - code generation depends on the key of the contract module, which covers
+//!   the whole program, as the backends emit one artifact per contract.
+//!
+//! Every key also covers the compiler version, [`CACHE_FORMAT`] and the
+//! parts of the [`CompilerConfig`] that change the output, so entries
+//! written by another version or configuration are never read. Entries carry
+//! a checksum; one that fails to verify or decode is rebuilt, never used.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use shared_core::{ErrorCollection, Result, SystemError};
+
+use crate::ast::SourceFile;
+use crate::diagnostic::{self, Diagnostic};
+use crate::hir::{self, Callee};
+use crate::module::{ModuleResolver, Program};
+use crate::optimize::{self, OptStats};
+use crate::parser::parse_file_with;
+use crate::{codegen, rust_codegen, typeck, CompilationTarget, CompileOutput, CompilerConfig};
+
+/// Version of the layout of cache entries, part of every key
+pub const CACHE_FORMAT: u32 = 4;
+
+/// Where a [`CompilationCache`] keeps its entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CacheBackend {
+    /// In memory, for the life of the compiler
+    #[default]
+    Memory,
+    /// One file per entry in a directory, created if missing
+    Directory(PathBuf),
+    /// A sled database at a path
+    #[cfg(feature = "sled-cache")]
+    Sled(PathBuf),
+}
+
+/// Storage of cache entries by key
+///
+/// Stores only keep bytes; checking them is up to the cache, so a store may
+/// lose or mangle entries without breaking compilation.
+pub trait CacheStore: Send + Sync {
+    /// Entry stored under `key`, if any
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`, replacing any entry
+    fn put(&self, key: &str, value: &[u8]) -> Result<()>;
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(entries.get(key).cloned())
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+}
+
+struct DirectoryStore {
+    dir: PathBuf,
+}
+
+impl CacheStore for DirectoryStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(SystemError::io(e, format!("reading cache entry {key}"))),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        // Readers see the old entry or the new one, never half of one
+        let temporary = self.dir.join(format!("{key}.{}.tmp", std::process::id()));
+        fs::write(&temporary, value)
+            .and_then(|()| fs::rename(&temporary, self.dir.join(key)))
+            .map_err(|e| SystemError::io(e, format!("writing cache entry {key}")))
+    }
+}
+
+#[cfg(feature = "sled-cache")]
+struct SledStore {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled-cache")]
+impl CacheStore for SledStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let value = self.db.get(key).map_err(|e| SystemError::io(e, "reading cache entry"))?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.db.insert(key, value).map_err(|e| SystemError::io(e, "writing cache entry"))?;
+        Ok(())
+    }
+}
+
+/// A step of compilation whose results are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Source to syntax tree, per module
+    Parse,
+    /// Syntax tree to typed IR, per module
+    Check,
+    /// Optimization passes over typed IR, per module
+    Optimize,
+    /// Typed IR to the target's output, per program
+    Codegen,
+}
+
+/// Hits and misses of one [`Stage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageStats {
+    /// Results loaded from the cache
+    pub hits: usize,
+    /// Results computed
+    pub misses: usize,
+}
+
+/// What the cache did during one compilation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Parsing
+    pub parse: StageStats,
+    /// Type checking
+    pub check: StageStats,
+    /// Optimization
+    pub optimize: StageStats,
+    /// Code generation
+    pub codegen: StageStats,
+    /// Entries found but rebuilt because they failed to verify or decode
+    pub corrupt: usize,
+    /// Modules that were type checked again, in program order
+    pub recompiled: Vec<String>,
+}
+
+impl CacheStats {
+    fn stage(&mut self, stage: Stage) -> &mut StageStats {
+        match stage {
+            Stage::Parse => &mut self.parse,
+            Stage::Check => &mut self.check,
+            Stage::Optimize => &mut self.optimize,
+            Stage::Codegen => &mut self.codegen,
+        }
+    }
+}
+
+/// Output of [`ContractCompiler::compile_project`](crate::ContractCompiler::compile_project)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectOutput {
+    /// Compiled contract
+    pub output: CompileOutput,
+    /// What the optimizer did, over every module
+    pub opt_stats: OptStats,
+    /// What the cache did
+    pub cache: CacheStats,
+}
+
+/// A function of another module called by cached IR, stored in place of its
+/// index in the program, which changes as other modules change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Link {
+    /// Id of the module, `None` for root source not read through a resolver
+    file: Option<String>,
+    /// Index of the function in its module
+    index: usize,
+}
+
+/// Typed IR of one module, calls referring to [`CheckedModule::links`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheckedModule {
+    contract: hir::Contract,
+    links: Vec<Link>,
+    warnings: Vec<Diagnostic>,
+}
+
+/// A checked module of a program, see [`CompilationCache::check_modules`]
+struct LinkedModule {
+    /// Index of the module in the program
+    index: usize,
+    /// Key of its [`CheckedModule`]
+    key: String,
+    /// Program-wide indices of its links
+    targets: Vec<usize>,
+    checked: CheckedModule,
+}
+
+/// Modules of a program that checked, with the problems of all of them
+struct CheckedProgram {
+    modules: Vec<LinkedModule>,
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OptimizedModule {
+    contract: hir::Contract,
+    stats: OptStats,
+}
+
+/// Caches the stages of compilation across calls
+pub struct CompilationCache {
+    store: Box<dyn CacheStore>,
+}
+
+impl CompilationCache {
+    /// Open the cache kept in `backend`
+    pub fn new(backend: &CacheBackend) -> Result<Self> {
+        let store: Box<dyn CacheStore> = match backend {
+            CacheBackend::Memory => Box::<MemoryStore>::default(),
+            CacheBackend::Directory(dir) => {
+                fs::create_dir_all(dir)
+                    .map_err(|e| SystemError::io(e, format!("creating {}", dir.display())))?;
+                Box::new(DirectoryStore { dir: dir.clone() })
+            },
+            #[cfg(feature = "sled-cache")]
+            CacheBackend::Sled(path) => Box::new(SledStore {
+                db: sled::open(path)
+                    .map_err(|e| SystemError::io(e, format!("opening {}", path.display())))?,
+            }),
+        };
+        Ok(Self { store })
+    }
+
+    /// A cache keeping its entries in `store`
+    pub fn with_store(store: impl CacheStore + 'static) -> Self {
+        Self {
+            store: Box::new(store),
+        }
+    }
+
+    /// Load the contract `source` and its imports like [`Program::load`],
+    /// reusing the syntax trees of unchanged modules
+    pub(crate) fn load(
+        &self,
+        file: Option<String>,
+        source: String,
+        resolver: &dyn ModuleResolver,
+        config: &CompilerConfig,
+        stats: &mut CacheStats,
+    ) -> std::result::Result<Program, ErrorCollection<Diagnostic>> {
+        let fingerprint = fingerprint(config);
+        let mut parse = |file: Option<&str>, source: &str| {
+            let key = entry_key(&[b"parse", &fingerprint, source.as_bytes()]);
+            if let Some(ast) = self.lookup::<SourceFile>(Stage::Parse, &key, stats) {
+                return Ok(ast);
+            }
+            ran(Stage::Parse, file.unwrap_or(""));
+            let ast = parse_file_with(source, &config.limits)?;
+            self.save(&key, &ast);
+            Ok(ast)
+        };
+        Program::load_with(file, source, resolver, &mut parse)
+    }
+
+    /// Type check `program` like
+    /// [`ContractCompiler::check`](crate::ContractCompiler::check), lints
+    /// aside, reusing the results of unchanged modules
+    ///
+    /// Returns the unoptimized IR of the program if it checks, and every
+    /// error and warning in order.
+    pub(crate) fn check(
+        &self,
+        program: &Program,
+        config: &CompilerConfig,
+        stats: &mut CacheStats,
+    ) -> (Option<hir::Contract>, Vec<Diagnostic>) {
+        let keys = module_keys(program, &fingerprint(config));
+        let checked = self.check_modules(program, &keys, stats);
+        let mut diagnostics = checked.errors;
+        let contract = diagnostics.is_empty().then(|| {
+            let functions = checked.modules.iter().flat_map(|module| {
+                let mut functions = module.checked.contract.functions.clone();
+                relink(&mut functions, &module.targets);
+                functions
+            });
+            let functions = functions.collect();
+            hir::Contract {
+                functions,
+                ..checked.modules[0].checked.contract.clone()
+            }
+        });
+        diagnostics.extend(checked.warnings);
+        diagnostic::sort(&mut diagnostics);
+        (contract, diagnostics)
+    }
+
+    /// Compile `program` like
+    /// [`ContractCompiler::compile`](crate::ContractCompiler::compile),
+    /// reusing the results of unchanged modules
+    pub(crate) fn compile(
+        &self,
+        program: &Program,
+        config: &CompilerConfig,
+        stats: &mut CacheStats,
+    ) -> Result<(CompileOutput, OptStats)> {
+        let fingerprint = fingerprint(config);
+        let keys = module_keys(program, &fingerprint);
+        let passes = match &config.passes {
+            Some(passes) => passes.as_slice(),
+            None => config.opt_level.passes(),
+        };
+
+        let CheckedProgram {
+            modules,
+            errors,
+            warnings,
+        } = self.check_modules(program, &keys, stats);
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+        errors.into_iter().collect::<ErrorCollection<_>>().into_result(())?;
+
+        let mut opt_stats = OptStats::default();
+        let mut contract: Option<hir::Contract> = None;
+        for LinkedModule {
+            index,
+            key,
+            targets,
+            checked,
+        } in modules
+        {
+            let module = &program.modules()[index];
+            let passes_key = format!("{passes:?}");
+            let key = entry_key(&[b"optimize", key.as_bytes(), passes_key.as_bytes()]);
+            let optimized = match self.lookup::<OptimizedModule>(Stage::Optimize, &key, stats) {
+                Some(optimized) => optimized,
+                None => {
+                    ran(Stage::Optimize, &module.name);
+                    let mut optimized = checked.contract;
+                    let stats = optimize::optimize(&mut optimized, passes);
+                    let optimized = OptimizedModule {
+                        contract: optimized,
+                        stats,
+                    };
+                    self.save(&key, &optimized);
+                    optimized
+                },
+            };
+            add_stats(&mut opt_stats, optimized.stats);
+
+            let mut functions = optimized.contract.functions;
+            relink(&mut functions, &targets);
+            match &mut contract {
+                Some(contract) => contract.functions.extend(functions),
+                None => {
+                    contract = Some(hir::Contract {
+                        functions,
+                        ..optimized.contract
+                    });
+                },
+            }
+        }
+        let contract = contract.expect("programs have a contract module");
+
+        let target_key = format!("{:?}", config.target);
+        let key = entry_key(&[b"codegen", keys[0].as_bytes(), target_key.as_bytes()]);
+        if let Some(output) = self.lookup::<CompileOutput>(Stage::Codegen, &key, stats) {
+            return Ok((output, opt_stats));
+        }
+        ran(Stage::Codegen, &program.modules()[0].name);
+        let output = match config.target {
+            CompilationTarget::Rust => CompileOutput::RustSource(rust_codegen::generate_rust(
+                &contract,
+                config.module_name.as_deref(),
+            )?),
+            CompilationTarget::Wasm => CompileOutput::WasmBytes(codegen::generate_wasm(&contract)?),
+        };
+        self.save(&key, &output);
+        Ok((output, opt_stats))
+    }
+
+    /// Type check every module of `program`, whose keys are `keys`
+    fn check_modules(
+        &self,
+        program: &Program,
+        keys: &[String],
+        stats: &mut CacheStats,
+    ) -> CheckedProgram {
+        let offsets = typeck::function_offsets(program);
+        let mut checked_program = CheckedProgram {
+            modules: Vec::new(),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        };
+        for (index, module) in program.modules().iter().enumerate() {
+            let key = entry_key(&[b"check", keys[index].as_bytes()]);
+            let cached = self.lookup::<CheckedModule>(Stage::Check, &key, stats);
+            let cached = cached.and_then(|checked| {
+                let targets = resolve_links(program, &offsets, &checked.links);
+                if targets.is_none() {
+                    // The entry decoded, but calls a function that is not there
+                    stats.corrupt += 1;
+                    stats.check.hits -= 1;
+                    stats.check.misses += 1;
+                }
+                Some((targets?, checked))
+            });
+            let (targets, checked) = match cached {
+                Some(cached) => cached,
+                None => {
+                    ran(Stage::Check, &module.name);
+                    stats.recompiled.push(module.name.clone());
+                    let (checked, module_warnings) = typeck::check_module(program, index);
+                    let mut checked = match checked {
+                        Ok(checked) => checked,
+                        Err(module_errors) => {
+                            checked_program.warnings.extend(module_warnings);
+                            checked_program.errors.extend(module_errors);
+                            continue;
+                        },
+                    };
+                    let (links, targets) = unlink(program, &offsets, &mut checked);
+                    let checked = CheckedModule {
+                        contract: checked,
+                        links,
+                        warnings: module_warnings,
+                    };
+                    self.save(&key, &checked);
+                    (targets, checked)
+                },
+            };
+            checked_program.warnings.extend(checked.warnings.iter().cloned());
+            checked_program.modules.push(LinkedModule {
+                index,
+                key,
+                targets,
+                checked,
+            });
+        }
+        diagnostic::sort(&mut checked_program.warnings);
+        diagnostic::sort(&mut checked_program.errors);
+        checked_program
+    }
+
+    /// Entry under `key`, counting a hit or miss of `stage`; entries that
+    /// fail to verify or decode are misses
+    fn lookup<T: DeserializeOwned>(
+        &self,
+        stage: Stage,
+        key: &str,
+        stats: &mut CacheStats,
+    ) -> Option<T> {
+        let entry = match self.store.get(key) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::warn!("Failed to read compilation cache: {}", err);
+                None
+            },
+        };
+        let value = entry.and_then(|bytes| {
+            let value = decode(&bytes);
+            if value.is_none() {
+                tracing::warn!("Rebuilding corrupt compilation cache entry {}", key);
+                stats.corrupt += 1;
+            }
+            value
+        });
+        let counts = stats.stage(stage);
+        match value {
+            Some(_) => counts.hits += 1,
+            None => counts.misses += 1,
+        }
+        value
+    }
+
+    /// Store `value` under `key`; failures only cost a rebuild later
+    fn save<T: Serialize>(&self, key: &str, value: &T) {
+        let result = serde_json::to_vec(value)
+            .map_err(SystemError::from)
+            .and_then(|payload| {
+                let mut entry = blake3::hash(&payload).as_bytes().to_vec();
+                entry.extend(payload);
+                self.store.put(key, &entry)
+            });
+        if let Err(err) = result {
+            tracing::warn!("Failed to write compilation cache: {}", err);
+        }
+    }
+}
+
+impl std::fmt::Debug for CompilationCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompilationCache").finish_non_exhaustive()
+    }
+}
+
+/// Value of an entry: a BLAKE3 checksum, then the JSON payload
+fn decode<T: DeserializeOwned>(entry: &[u8]) -> Option<T> {
+    let (checksum, payload) = entry.split_at_checked(blake3::OUT_LEN)?;
+    if blake3::hash(payload).as_bytes() != checksum {
+        return None;
+    }
+    serde_json::from_slice(payload).ok()
+}
+
+/// Hash of `parts` with the compiler version and cache format
+fn entry_key(parts: &[&[u8]]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(&CACHE_FORMAT.to_le_bytes());
+    for part in parts {
+        // Length prefixes keep `ab`, `c` apart from `a`, `bc`
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Hash of the configuration that changes compiler output
+fn fingerprint(config: &CompilerConfig) -> Vec<u8> {
+    let passes = match &config.passes {
+        Some(passes) => format!("{passes:?}"),
+        None => format!("{:?}", config.opt_level),
+    };
+    let description = format!(
+        "{:?}|{}|{:?}|{:?}",
+        config.target, passes, config.module_name, config.limits
+    );
+    blake3::hash(description.as_bytes()).as_bytes().to_vec()
+}
+
+/// Key of every module, covering the modules it imports
+fn module_keys(program: &Program, fingerprint: &[u8]) -> Vec<String> {
+    fn visit(program: &Program, fingerprint: &[u8], index: usize, keys: &mut [Option<String>]) {
+        if keys[index].is_some() {
+            return;
+        }
+        let module = &program.modules()[index];
+        let mut imports: Vec<(&String, &usize)> = module.imports.iter().collect();
+        imports.sort();
+        for &(_, &import) in &imports {
+            visit(program, fingerprint, import, keys);
+        }
+        let role: &[u8] = if index == 0 { b"contract" } else { b"library" };
+        let mut parts: Vec<&[u8]> = vec![
+            b"module",
+            fingerprint,
+            role,
+            module.name.as_bytes(),
+            module.file.as_deref().unwrap_or("").as_bytes(),
+            module.source.as_bytes(),
+        ];
+        for &(alias, &import) in &imports {
+            parts.push(alias.as_bytes());
+            parts.push(keys[import].as_deref().unwrap_or("").as_bytes());
+        }
+        keys[index] = Some(entry_key(&parts));
+    }
+
+    // Imports form no cycles, as loading rejects them
+    let mut keys = vec![None; program.modules().len()];
+    for index in 0..keys.len() {
+        visit(program, fingerprint, index, &mut keys);
+    }
+    keys.into_iter().map(Option::unwrap_or_default).collect()
+}
+
+/// Replace the program-wide indices of the functions `contract` calls with
+/// indices into the returned links, also returning the replaced indices
+fn unlink(
+    program: &Program,
+    offsets: &[usize],
+    contract: &mut hir::Contract,
+) -> (Vec<Link>, Vec<usize>) {
+    let mut targets: Vec<usize> = Vec::new();
+    for function in &mut contract.functions {
+        for_each_callee(&mut function.body, &mut |index| {
+            *index = match targets.iter().position(|target| target == index) {
+                Some(position) => position,
+                None => {
+                    targets.push(*index);
+                    targets.len() - 1
+                },
+            };
+        });
+    }
+    let links = targets
+        .iter()
+        .map(|&target| {
+            let module = offsets.partition_point(|&offset| offset <= target) - 1;
+            Link {
+                file: program.modules()[module].file.clone(),
+                index: target - offsets[module],
+            }
+        })
+        .collect();
+    (links, targets)
+}
+
+/// Program-wide indices of `links`, `None` if one names no function
+fn resolve_links(program: &Program, offsets: &[usize], links: &[Link]) -> Option<Vec<usize>> {
+    let modules = program.modules();
+    links
+        .iter()
+        .map(|link| {
+            let module = modules.iter().position(|module| module.file == link.file)?;
+            let end = offsets[module + 1];
+            let target = offsets[module] + link.index;
+            (target < end).then_some(target)
+        })
+        .collect()
+}
+
+/// Replace link indices in `functions` with the program-wide `targets`
+fn relink(functions: &mut [hir::Function], targets: &[usize]) {
+    for function in functions {
+        for_each_callee(&mut function.body, &mut |index| *index = targets[*index]);
+    }
+}
+
+fn for_each_callee(block: &mut hir::Block, f: &mut dyn FnMut(&mut usize)) {
+    for stmt in &mut block.statements {
+        match &mut stmt.kind {
+            hir::StmtKind::Let { value, .. } | hir::StmtKind::Expr(value) => {
+                expr_callees(value, f);
+            },
+            hir::StmtKind::Assign { place, value } => {
+                expr_callees(place, f);
+                expr_callees(value, f);
+            },
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                expr_callees(condition, f);
+                for_each_callee(then_branch, f);
+                if let Some(else_branch) = else_branch {
+                    for_each_callee(else_branch, f);
+                }
+            },
+            hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. } => {
+                expr_callees(condition, f);
+            },
+            hir::StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    expr_callees(value, f);
+                }
+            },
+            hir::StmtKind::Emit { fields, .. } => {
+                for field in fields {
+                    expr_callees(field, f);
+                }
+            },
+        }
+    }
+}
+
+fn expr_callees(expr: &mut hir::Expr, f: &mut dyn FnMut(&mut usize)) {
+    match &mut expr.kind {
+        hir::ExprKind::Int(_)
+        | hir::ExprKind::Bool(_)
+        | hir::ExprKind::Str(_)
+        | hir::ExprKind::Local(_)
+        | hir::ExprKind::State(_) => {},
+        hir::ExprKind::Unary { operand, .. } => expr_callees(operand, f),
+        hir::ExprKind::Binary { lhs, rhs, .. } => {
+            expr_callees(lhs, f);
+            expr_callees(rhs, f);
+        },
+        hir::ExprKind::Call { callee, args } => {
+            if let Callee::Function(index) = callee {
+                f(index);
+            }
+            for arg in args {
+                expr_callees(arg, f);
+            }
+        },
+        hir::ExprKind::Index { base, index } => {
+            expr_callees(base, f);
+            expr_callees(index, f);
+        },
+        hir::ExprKind::Array(elements) => {
+            for element in elements {
+                expr_callees(element, f);
+            }
+        },
+    }
+}
+
+fn add_stats(total: &mut OptStats, stats: OptStats) {
+    total.constants_folded += stats.constants_folded;
+    total.nodes_removed += stats.nodes_removed;
+    total.subexpressions_eliminated += stats.subexpressions_eliminated;
+    total.requires_merged += stats.requires_merged;
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Stages run on this thread, with the module they ran for
+    static RAN: std::cell::RefCell<Vec<(Stage, String)>> = const {
+        std::cell::RefCell::new(Vec::new())
+    };
+}
+
+/// Record that `stage` ran for `module`, for tests to spy on
+fn ran(stage: Stage, module: &str) {
+    tracing::debug!("Running {:?} for {}", stage, module);
+    #[cfg(test)]
+    RAN.with(|ran| ran.borrow_mut().push((stage, module.to_string())));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::ContractCompiler;
+
+    const UTIL: &str = "
+        pub fn double(x: u64) -> u64 { return add(x, x); }
+        fn add(a: u64, b: u64) -> u64 { return a + b; }
+    ";
+    const FEES: &str = "
+        import \"util\";
+        pub fn charge(amount: u64) -> u64 {
+            let doubled = util::double(amount);
+            return doubled + 1;
+        }
+    ";
+    const MAIN: &str = "
+        import \"fees\";
+        import \"util\";
+        contract Vault {
+            state { total: u64; }
+            fn quote(amount: u64) -> u64 {
+                let fee = fees::charge(amount);
+                return fee;
+            }
+            fn twice(x: u64) -> u64 { return util::double(x); }
+        }
+    ";
+
+    fn write_project(dir: &Path) {
+        fs::write(dir.join("util.contract"), UTIL).unwrap();
+        fs::write(dir.join("fees.contract"), FEES).unwrap();
+        fs::write(dir.join("main.contract"), MAIN).unwrap();
+    }
+
+    fn compiler(cache: CacheBackend) -> ContractCompiler {
+        let config = CompilerConfig {
+            target: CompilationTarget::Wasm,
+            cache,
+            ..CompilerConfig::default()
+        };
+        ContractCompiler::new(config).unwrap()
+    }
+
+    /// Stages run since the last call, parses named by file stem
+    fn take_ran() -> Vec<(Stage, String)> {
+        RAN.with(|ran| ran.take())
+            .into_iter()
+            .map(|(stage, module)| match stage {
+                Stage::Parse => {
+                    let stem = Path::new(&module).file_stem().unwrap().to_string_lossy();
+                    (stage, stem.into_owned())
+                },
+                _ => (stage, module),
+            })
+            .collect()
+    }
+
+    fn ran(stage: Stage, ran: &[(Stage, String)]) -> Vec<&str> {
+        ran.iter().filter(|r| r.0 == stage).map(|r| r.1.as_str()).collect()
+    }
+
+    /// Compile with the cache, checking the output matches an uncached build
+    fn compile(compiler: &ContractCompiler, root: &Path) -> ProjectOutput {
+        let project = compiler.compile_project(root).unwrap();
+        assert_eq!(project.output, compiler.compile_path(root).unwrap());
+        project
+    }
+
+    #[test]
+    fn test_only_changed_modules_and_dependents_recompile() {
+        let dir = tempfile::tempdir().unwrap();
+        write_project(dir.path());
+        let root = dir.path().join("main.contract");
+        let compiler = compiler(CacheBackend::Memory);
+        take_ran();
+
+        let project = compile(&compiler, &root);
+        let first = take_ran();
+        assert_eq!(ran(Stage::Check, &first), ["contract", "fees", "util"]);
+        assert_eq!(project.cache.check, StageStats { hits: 0, misses: 3 });
+        assert_eq!(project.cache.recompiled, ["contract", "fees", "util"]);
+
+        // Nothing changed, so nothing runs
+        let project = compile(&compiler, &root);
+        assert_eq!(take_ran(), []);
+        assert_eq!(project.cache.parse, StageStats { hits: 3, misses: 0 });
+        assert_eq!(project.cache.codegen, StageStats { hits: 1, misses: 0 });
+        assert!(project.cache.recompiled.is_empty());
+
+        // A new function in `fees` moves the functions of `util` in the
+        // program, so util's cached calls must be relinked
+        let fees = FEES.replace("pub fn charge", "fn base() -> u64 { return 1; }\npub fn charge");
+        fs::write(dir.path().join("fees.contract"), fees).unwrap();
+        let project = compile(&compiler, &root);
+        let ran_now = take_ran();
+        assert_eq!(ran(Stage::Parse, &ran_now), ["fees"]);
+        assert_eq!(ran(Stage::Check, &ran_now), ["contract", "fees"]);
+        assert_eq!(ran(Stage::Optimize, &ran_now), ["contract", "fees"]);
+        assert_eq!(ran(Stage::Codegen, &ran_now), ["contract"]);
+        assert_eq!(project.cache.check, StageStats { hits: 1, misses: 2 });
+
+        // The contract has no dependents
+        fs::write(&root, MAIN.replace("return fee;", "return fee + 1;")).unwrap();
+        compile(&compiler, &root);
+        assert_eq!(ran(Stage::Check, &take_ran()), ["contract"]);
+
+        // Everything depends on `util`
+        fs::write(dir.path().join("util.contract"), UTIL.replace("a + b", "b + a")).unwrap();
+        compile(&compiler, &root);
+        assert_eq!(ran(Stage::Check, &take_ran()), ["contract", "fees", "util"]);
+    }
+
+    #[test]
+    fn test_directory_cache_survives_restarts_and_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        write_project(dir.path());
+        let root = dir.path().join("main.contract");
+        let cache_dir = dir.path().join("cache");
+        compile(&compiler(CacheBackend::Directory(cache_dir.clone())), &root);
+
+        let project = compile(&compiler(CacheBackend::Directory(cache_dir.clone())), &root);
+        assert_eq!(project.cache.check, StageStats { hits: 3, misses: 0 });
+        assert_eq!(project.cache.corrupt, 0);
+
+        // Mangled entries are rebuilt, never used
+        for (i, entry) in fs::read_dir(&cache_dir).unwrap().enumerate() {
+            let path = entry.unwrap().path();
+            let mut bytes = fs::read(&path).unwrap();
+            match i % 3 {
+                0 => bytes.truncate(10),
+                1 => *bytes.last_mut().unwrap() ^= 1,
+                _ => bytes[0] ^= 1,
+            }
+            fs::write(&path, bytes).unwrap();
+        }
+        take_ran();
+        let project = compile(&compiler(CacheBackend::Directory(cache_dir.clone())), &root);
+        assert_eq!(project.cache.corrupt, 10);
+        assert_eq!(project.cache.check, StageStats { hits: 0, misses: 3 });
+        assert_eq!(ran(Stage::Codegen, &take_ran()), ["contract"]);
+
+        // Entries of another configuration are never read
+        let config = CompilerConfig {
+            target: CompilationTarget::Rust,
+            cache: CacheBackend::Directory(cache_dir),
+            ..CompilerConfig::default()
+        };
+        let project = ContractCompiler::new(config).unwrap().compile_project(&root).unwrap();
+        assert_eq!(project.cache.check, StageStats { hits: 0, misses: 3 });
+        assert!(matches!(project.output, CompileOutput::RustSource(_)));
+    }
+
+    #[test]
+    fn test_entry_keys() {
+        assert_ne!(entry_key(&[b"ab", b"c"]), entry_key(&[b"a", b"bc"]));
+        assert_eq!(entry_key(&[b"parse"]), entry_key(&[b"parse"]));
+        assert!(decode::<u64>(b"short").is_none());
+    }
+}
@@ -0,0 +1,987 @@
+//! Codegen module
+//!
+//! Lowers a type-checked [`hir::Contract`] to a WebAssembly module.
+//!
+//! - Every contract function is exported under its own name; library
+//!   functions are not exported.
+//! - State variables live in linear memory, exported as `memory`, from
+//!   offset 0 in declaration order; every scalar takes an 8-byte slot and
+//!   arrays are stored inline. Each variable gets `get_<name>` and
+//!   `set_<name>` exports, taking one `i64` index per array dimension.
+//! - `_init` zeroes all state.
+//! - `u64`, `i64` and `address` values are `i64`, `bool` is `i32`.
+//!   `string` and `map` values live in the host and are `i32` handles to
+//!   them, 0 being the empty value. Builtins, those of `std::map` and
+//!   `std::assert` included, are imported from the `env` module under
+//!   their [`symbol`](Builtin::symbol).
+//! - String literals are stored in a data segment after the state, and
+//!   turned into handles by the imported `env.`[`STRING_IMPORT`].
+//!   Comparing strings or maps calls the imported `env.`[`EQUALS_IMPORT`].
+//! - `emit` passes each field value, in declaration order, to the imported
+//!   `env.`[`EMIT_FIELD_IMPORT`] as an `i64` (`bool` as 0 or 1, handles
+//!   zero-extended), then calls
+//!   `env.`[`EMIT_IMPORT`] with the index of the event in the ABI's
+//!   `events`. Both are only imported by contracts that emit events.
+//! - A failed `require` or `assert`, or an out-of-bounds index, traps.
+//!   The library builtins revert with their message from the host instead.
+//!   Integer arithmetic wraps.
+//! - A custom section named [`ABI_SECTION`] holds the [`ContractAbi`] as
+//!   JSON; [`exports`] derives the signature of every export from it.
+//! - A last custom section named [`SOURCE_MAP_SECTION`] holds the
+//!   [`SourceMap`] of the module: each statement's instructions map to the
+//!   statement, and accessors map to their state variable.
+//!
+//! Array-typed values other than state variables are not supported by this
+//! backend yet.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, CustomSection, DataSection, EntityType, ExportKind,
+    ExportSection, Function, FunctionSection, ImportSection, Instruction, MemArg, MemorySection,
+    MemoryType, Module, TypeSection, ValType,
+};
+use wasmparser::{Parser, Payload};
+
+use crate::abi::ContractAbi;
+use crate::ast::{BinaryOp, Span, UnaryOp};
+use crate::hir::{self, Builtin, Callee, LocalId, Ty};
+use crate::optimize::visit_stmt_exprs;
+use crate::source_map::{Mapping, SourceLocation, SourceMap};
+
+/// Bytes per scalar state slot
+const SLOT_SIZE: u64 = 8;
+/// Bytes per linear memory page
+const PAGE_SIZE: u64 = 65_536;
+/// Export that zeroes all state
+pub const INIT_EXPORT: &str = "_init";
+/// Export of the linear memory
+pub const MEMORY_EXPORT: &str = "memory";
+/// Custom section holding the JSON [`ContractAbi`]
+pub const ABI_SECTION: &str = "contract_abi";
+/// Custom section holding the JSON [`SourceMap`] of the module
+pub const SOURCE_MAP_SECTION: &str = "contract_source_map";
+/// Import taking one field value of the event about to be emitted,
+/// `(i64) -> ()`
+pub const EMIT_FIELD_IMPORT: &str = "emit_field";
+/// Import emitting an event, by index, with the field values passed since
+/// the last one, `(i32) -> ()`
+pub const EMIT_IMPORT: &str = "emit";
+/// Import returning a handle to the string of `len` bytes at `ptr` in
+/// linear memory, `(i32, i32) -> i32`
+pub const STRING_IMPORT: &str = "string";
+/// Import comparing the `string` or `map` values of two handles,
+/// `(i32, i32) -> i32`
+pub const EQUALS_IMPORT: &str = "equals";
+
+/// Source-level signature of an exported function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbiFunction {
+    /// Export name
+    pub name: String,
+    /// Parameter types
+    pub params: Vec<Ty>,
+    /// Return type, `Ty::Unit` for none
+    pub returns: Ty,
+}
+
+/// Signatures of the functions exported by the module generated for a
+/// contract with `abi`, in export order
+pub fn exports(abi: &ContractAbi) -> Vec<AbiFunction> {
+    let mut exports: Vec<_> = abi
+        .functions
+        .iter()
+        .map(|function| AbiFunction {
+            name: function.name.clone(),
+            params: function.params.iter().map(|param| param.ty.clone()).collect(),
+            returns: function.returns.clone(),
+        })
+        .collect();
+    exports.push(AbiFunction {
+        name: INIT_EXPORT.to_string(),
+        params: Vec::new(),
+        returns: Ty::Unit,
+    });
+    for var in &abi.state {
+        // One index per array dimension
+        let mut indices = Vec::new();
+        let mut element = &var.ty;
+        while let Ty::Array { element: inner, .. } = element {
+            indices.push(Ty::U64);
+            element = inner;
+        }
+        exports.push(AbiFunction {
+            name: format!("get_{}", var.name),
+            params: indices.clone(),
+            returns: element.clone(),
+        });
+        indices.push(element.clone());
+        exports.push(AbiFunction {
+            name: format!("set_{}", var.name),
+            params: indices,
+            returns: Ty::Unit,
+        });
+    }
+    exports
+}
+
+/// Generate a WebAssembly module for `contract`
+///
+/// Constructs the backend cannot lower yet are `Validation` errors naming
+/// the offending source position.
+pub fn generate_wasm(contract: &hir::Contract) -> Result<Vec<u8>> {
+    Generator::new(contract)?.finish()
+}
+
+fn unsupported(span: Span, what: impl std::fmt::Display) -> SystemError {
+    SystemError::validation(
+        "source",
+        format!(
+            "{}:{}: {what} is not supported by the wasm backend",
+            span.start.line, span.start.column
+        ),
+        None,
+    )
+}
+
+/// Wasm type of a scalar value, `None` for unit
+fn val_type(ty: &Ty, span: Span) -> Result<Option<ValType>> {
+    match ty {
+        Ty::U64 | Ty::I64 | Ty::Address => Ok(Some(ValType::I64)),
+        Ty::Bool | Ty::String | Ty::Map => Ok(Some(ValType::I32)),
+        Ty::Unit => Ok(None),
+        Ty::Array { .. } => Err(unsupported(span, format!("a `{ty}` value"))),
+    }
+}
+
+/// Bytes a value of `ty` takes in linear memory
+fn size_of(ty: &Ty) -> u64 {
+    match ty {
+        Ty::Array { element, len } => size_of(element).saturating_mul(*len),
+        _ => SLOT_SIZE,
+    }
+}
+
+/// Bytes of linear memory the state variables of `abi` take, from offset 0
+pub(crate) fn state_size(abi: &ContractAbi) -> u64 {
+    abi.state.iter().map(|var| size_of(&var.ty)).fold(0, u64::saturating_add)
+}
+
+/// Offsets of the state slots holding `string` and `map` handles
+pub(crate) fn handle_slots(abi: &ContractAbi) -> Vec<u64> {
+    fn has_handles(ty: &Ty) -> bool {
+        match ty {
+            Ty::String | Ty::Map => true,
+            Ty::Array { element, .. } => has_handles(element),
+            _ => false,
+        }
+    }
+    fn collect(ty: &Ty, offset: u64, slots: &mut Vec<u64>) {
+        match ty {
+            Ty::String | Ty::Map => slots.push(offset),
+            Ty::Array { element, len } if has_handles(element) => {
+                for index in 0..*len {
+                    collect(element, offset + index * size_of(element), slots);
+                }
+            },
+            _ => {},
+        }
+    }
+    let mut slots = Vec::new();
+    let mut offset = 0;
+    for var in &abi.state {
+        collect(&var.ty, offset, &mut slots);
+        offset += size_of(&var.ty);
+    }
+    slots
+}
+
+fn memarg(offset: u64) -> MemArg {
+    MemArg {
+        offset,
+        align: 3,
+        memory_index: 0,
+    }
+}
+
+/// Module sections under construction
+struct Generator<'a> {
+    contract: &'a hir::Contract,
+    types: TypeSection,
+    type_index: HashMap<(Vec<ValType>, Vec<ValType>), u32>,
+    imports: ImportSection,
+    builtins: HashMap<Builtin, u32>,
+    /// Indices of the `emit_field` and `emit` imports, if imported
+    emit_imports: Option<(u32, u32)>,
+    /// Index of the `string` import, if imported
+    string_import: Option<u32>,
+    /// Index of the `equals` import, if imported
+    equals_import: Option<u32>,
+    /// Address of every string literal
+    strings: HashMap<String, u32>,
+    /// Bytes of the string literals, stored after the state
+    data: Vec<u8>,
+    functions: FunctionSection,
+    exports: ExportSection,
+    exported: HashMap<String, Span>,
+    code: CodeSection,
+    /// Mappings by index in the code section, with offsets relative to the
+    /// function body
+    mappings: Vec<(u32, Mapping)>,
+    /// Offset of every state variable
+    state_offsets: Vec<u64>,
+    state_size: u64,
+}
+
+impl<'a> Generator<'a> {
+    fn new(contract: &'a hir::Contract) -> Result<Self> {
+        let mut state_offsets = Vec::with_capacity(contract.state.len());
+        let mut state_size: u64 = 0;
+        for var in &contract.state {
+            state_offsets.push(state_size);
+            state_size = state_size
+                .checked_add(size_of(&var.ty))
+                .filter(|size| *size <= u64::from(u32::MAX))
+                .ok_or_else(|| unsupported(var.span, "more than 4 GiB of state"))?;
+        }
+
+        let mut generator = Self {
+            contract,
+            types: TypeSection::new(),
+            type_index: HashMap::new(),
+            imports: ImportSection::new(),
+            builtins: HashMap::new(),
+            emit_imports: None,
+            string_import: None,
+            equals_import: None,
+            strings: HashMap::new(),
+            data: Vec::new(),
+            functions: FunctionSection::new(),
+            exports: ExportSection::new(),
+            exported: HashMap::new(),
+            code: CodeSection::new(),
+            mappings: Vec::new(),
+            state_offsets,
+            state_size,
+        };
+        // Imported functions come first in the function index space
+        for builtin in Builtin::ALL {
+            if contract.functions.iter().any(|f| block_calls(&f.body, builtin)) {
+                let (params, result) = builtin.signature();
+                let ty = generator.signature(&params, &result, Span::default())?;
+                generator.imports.import("env", builtin.symbol(), EntityType::Function(ty));
+                generator.builtins.insert(builtin, generator.imports.len() - 1);
+            }
+        }
+        if contract.functions.iter().any(|f| block_emits(&f.body)) {
+            let field = generator.func_type(vec![ValType::I64], Vec::new());
+            generator.imports.import("env", EMIT_FIELD_IMPORT, EntityType::Function(field));
+            let emit = generator.func_type(vec![ValType::I32], Vec::new());
+            generator.imports.import("env", EMIT_IMPORT, EntityType::Function(emit));
+            let imports = generator.imports.len();
+            generator.emit_imports = Some((imports - 2, imports - 1));
+        }
+
+        let mut literals = Vec::new();
+        for function in &contract.functions {
+            for stmt in &function.body.statements {
+                visit_stmt_exprs(stmt, &mut |expr| {
+                    if let hir::ExprKind::Str(value) = &expr.kind {
+                        literals.push((value.clone(), expr.span));
+                    }
+                });
+            }
+        }
+        for (value, span) in literals {
+            if generator.strings.contains_key(&value) {
+                continue;
+            }
+            let address = u32::try_from(state_size + generator.data.len() as u64)
+                .map_err(|_| unsupported(span, "more than 4 GiB of state and strings"))?;
+            generator.data.extend_from_slice(value.as_bytes());
+            generator.strings.insert(value, address);
+        }
+        if !generator.strings.is_empty() {
+            let string = generator.func_type(vec![ValType::I32, ValType::I32], vec![ValType::I32]);
+            generator.imports.import("env", STRING_IMPORT, EntityType::Function(string));
+            generator.string_import = Some(generator.imports.len() - 1);
+        }
+        let compares_values = |expr: &hir::Expr| match &expr.kind {
+            hir::ExprKind::Binary { lhs, .. } => matches!(lhs.ty, Ty::String | Ty::Map),
+            _ => false,
+        };
+        if contract.functions.iter().any(|f| block_has(&f.body, compares_values)) {
+            let equals = generator.func_type(vec![ValType::I32, ValType::I32], vec![ValType::I32]);
+            generator.imports.import("env", EQUALS_IMPORT, EntityType::Function(equals));
+            generator.equals_import = Some(generator.imports.len() - 1);
+        }
+        Ok(generator)
+    }
+
+    fn signature(&mut self, params: &[Ty], result: &Ty, span: Span) -> Result<u32> {
+        let params = params
+            .iter()
+            .map(|ty| Ok(val_type(ty, span)?.unwrap_or(ValType::I32)))
+            .collect::<Result<Vec<_>>>()?;
+        let results: Vec<_> = val_type(result, span)?.into_iter().collect();
+        Ok(self.func_type(params, results))
+    }
+
+    fn func_type(&mut self, params: Vec<ValType>, results: Vec<ValType>) -> u32 {
+        let next = self.type_index.len() as u32;
+        *self.type_index.entry((params, results)).or_insert_with_key(|(params, results)| {
+            self.types.function(params.iter().copied(), results.iter().copied());
+            next
+        })
+    }
+
+    /// Index of the next function defined in the module
+    fn next_function(&self) -> u32 {
+        self.imports.len() + self.functions.len()
+    }
+
+    /// Export the next function as `name`
+    fn export(&mut self, name: &str, span: Span) -> Result<()> {
+        if self.exported.insert(name.to_string(), span).is_some() {
+            return Err(unsupported(span, format!("a second export named `{name}`")));
+        }
+        self.exports.export(name, ExportKind::Func, self.next_function());
+        Ok(())
+    }
+
+    /// Map `range` of the next function body to `location`, extending the
+    /// last mapping if it is for the same location
+    fn map(&mut self, range: Range<usize>, function: &str, location: SourceLocation) {
+        let body = self.code.len();
+        if let Some((last_body, last)) = self.mappings.last_mut() {
+            if *last_body == body && last.end == range.start && last.location == location {
+                last.end = range.end;
+                return;
+            }
+        }
+        let mapping = Mapping {
+            start: range.start,
+            end: range.end,
+            function: function.to_string(),
+            location,
+        };
+        self.mappings.push((body, mapping));
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        for function in &self.contract.functions {
+            self.function(function)?;
+        }
+        self.init()?;
+        for index in 0..self.contract.state.len() {
+            self.accessors(index)?;
+        }
+
+        let memory_size = self.state_size + self.data.len() as u64;
+        let pages = memory_size.div_ceil(PAGE_SIZE).max(1);
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: pages,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+        self.exports.export(MEMORY_EXPORT, ExportKind::Memory, 0);
+        let abi = CustomSection {
+            name: Cow::Borrowed(ABI_SECTION),
+            data: Cow::Owned(serde_json::to_vec(&ContractAbi::from_contract(self.contract))?),
+        };
+
+        let mut module = Module::new();
+        module
+            .section(&self.types)
+            .section(&self.imports)
+            .section(&self.functions)
+            .section(&memories)
+            .section(&self.exports)
+            .section(&self.code);
+        if !self.data.is_empty() {
+            let mut data = DataSection::new();
+            let offset = ConstExpr::i32_const(self.state_size as i32);
+            data.active(0, &offset, self.data.iter().copied());
+            module.section(&data);
+        }
+        module.section(&abi);
+
+        // Appended last, so the map does not move the code it maps
+        let mut bodies = Vec::new();
+        for payload in Parser::new(0).parse_all(module.as_slice()) {
+            if let Payload::CodeSectionEntry(body) = payload.map_err(|e| {
+                SystemError::internal(format!("generated an invalid module: {e}"), None)
+            })? {
+                bodies.push(body.range().start);
+            }
+        }
+        let mappings = self
+            .mappings
+            .into_iter()
+            .map(|(body, mapping)| {
+                let base = bodies[body as usize];
+                Mapping {
+                    start: base + mapping.start,
+                    end: base + mapping.end,
+                    ..mapping
+                }
+            })
+            .collect();
+        let source_map = CustomSection {
+            name: Cow::Borrowed(SOURCE_MAP_SECTION),
+            data: Cow::Owned(serde_json::to_vec(&SourceMap { mappings })?),
+        };
+        module.section(&source_map);
+        Ok(module.finish())
+    }
+
+    fn function(&mut self, function: &hir::Function) -> Result<()> {
+        let params: Vec<_> =
+            function.params.iter().map(|id| function.locals[id.0].ty.clone()).collect();
+        let ty = self.signature(&params, &function.return_type, function.span)?;
+        if function.exported {
+            self.export(&function.name, function.span)?;
+        }
+        self.functions.function(ty);
+
+        let mut locals = Vec::new();
+        for (index, local) in function.locals.iter().enumerate() {
+            if !function.params.contains(&LocalId(index)) {
+                let ty = val_type(&local.ty, local.span)?.unwrap_or(ValType::I32);
+                locals.push((1, ty));
+            }
+        }
+        // Scratch local for array indices
+        let scratch = function.locals.len() as u32;
+        locals.push((1, ValType::I64));
+
+        let mut body = FunctionBody {
+            generator: self,
+            locals: local_indices(function),
+            scratch,
+            span: function.span,
+            instructions: Vec::new(),
+            spans: Vec::new(),
+        };
+        body.block(&function.body)?;
+        if function.return_type != Ty::Unit {
+            // Type checking guarantees every path returned before this
+            body.emit(Instruction::Unreachable);
+        }
+        body.emit(Instruction::End);
+        let FunctionBody {
+            instructions,
+            spans,
+            ..
+        } = body;
+
+        let mut code = Function::new(locals);
+        for (instruction, span) in instructions.iter().zip(spans) {
+            let start = code.byte_len();
+            code.instruction(instruction);
+            let location = SourceLocation::new(function.file.clone(), span);
+            self.map(start..code.byte_len(), &function.name, location);
+        }
+        self.code.function(&code);
+        Ok(())
+    }
+
+    fn init(&mut self) -> Result<()> {
+        let ty = self.func_type(Vec::new(), Vec::new());
+        self.export(INIT_EXPORT, Span::default())?;
+        self.functions.function(ty);
+        let mut code = Function::new([]);
+        code.instruction(&Instruction::I32Const(0))
+            .instruction(&Instruction::I32Const(0))
+            .instruction(&Instruction::I32Const(self.state_size as i32))
+            .instruction(&Instruction::MemoryFill(0))
+            .instruction(&Instruction::End);
+        self.code.function(&code);
+        Ok(())
+    }
+
+    /// `get_<name>` and `set_<name>` of a state variable
+    fn accessors(&mut self, index: usize) -> Result<()> {
+        let var = &self.contract.state[index];
+        let mut dimensions = Vec::new();
+        let mut element = &var.ty;
+        while let Ty::Array { element: inner, len } = element {
+            dimensions.push((*len, size_of(inner)));
+            element = inner;
+        }
+        let value = val_type(element, var.span)?.unwrap_or(ValType::I32);
+        let indices = vec![ValType::I64; dimensions.len()];
+        let (element, name, span) = (element.clone(), var.name.clone(), var.span);
+        let location = SourceLocation::new(self.contract.file.clone(), span);
+
+        // Address of the element, from the index parameters
+        let mut address = vec![Instruction::I32Const(self.state_offsets[index] as i32)];
+        for (param, (len, stride)) in dimensions.iter().enumerate() {
+            address.extend(bounds_checked_offset(param as u32, *len, *stride));
+        }
+
+        let getter = self.func_type(indices.clone(), vec![value]);
+        let getter_name = format!("get_{name}");
+        self.export(&getter_name, span)?;
+        self.functions.function(getter);
+        let mut code = Function::new([]);
+        for instruction in address.iter().chain(&load(&element)) {
+            code.instruction(instruction);
+        }
+        code.instruction(&Instruction::End);
+        self.map(0..code.byte_len(), &getter_name, location.clone());
+        self.code.function(&code);
+
+        let mut params = indices;
+        params.push(value);
+        let value_param = dimensions.len() as u32;
+        let setter = self.func_type(params, Vec::new());
+        let setter_name = format!("set_{name}");
+        self.export(&setter_name, span)?;
+        self.functions.function(setter);
+        let mut code = Function::new([]);
+        for instruction in &address {
+            code.instruction(instruction);
+        }
+        code.instruction(&Instruction::LocalGet(value_param));
+        for instruction in store(&element) {
+            code.instruction(&instruction);
+        }
+        code.instruction(&Instruction::End);
+        self.map(0..code.byte_len(), &setter_name, location);
+        self.code.function(&code);
+        Ok(())
+    }
+}
+
+/// Add `index * stride` to the address on the stack, trapping unless
+/// `index < len`; `index` is the `i64` in local `local`
+fn bounds_checked_offset(local: u32, len: u64, stride: u64) -> Vec<Instruction<'static>> {
+    vec![
+        Instruction::LocalGet(local),
+        Instruction::I64Const(len as i64),
+        Instruction::I64GeU,
+        Instruction::If(BlockType::Empty),
+        Instruction::Unreachable,
+        Instruction::End,
+        Instruction::LocalGet(local),
+        Instruction::I32WrapI64,
+        Instruction::I32Const(stride as i32),
+        Instruction::I32Mul,
+        Instruction::I32Add,
+    ]
+}
+
+/// Load a scalar of type `ty` from the address on the stack
+fn load(ty: &Ty) -> Vec<Instruction<'static>> {
+    match ty {
+        Ty::Bool | Ty::String | Ty::Map => {
+            vec![Instruction::I64Load(memarg(0)), Instruction::I32WrapI64]
+        },
+        _ => vec![Instruction::I64Load(memarg(0))],
+    }
+}
+
+/// Store a scalar of type `ty`; the stack holds the address, then the value
+fn store(ty: &Ty) -> Vec<Instruction<'static>> {
+    match ty {
+        Ty::Bool | Ty::String | Ty::Map => {
+            vec![Instruction::I64ExtendI32U, Instruction::I64Store(memarg(0))]
+        },
+        _ => vec![Instruction::I64Store(memarg(0))],
+    }
+}
+
+/// Wasm local index of every HIR local; parameters come first in wasm
+fn local_indices(function: &hir::Function) -> Vec<u32> {
+    let mut indices = vec![0; function.locals.len()];
+    for (position, id) in function.params.iter().enumerate() {
+        indices[id.0] = position as u32;
+    }
+    let mut next = function.params.len() as u32;
+    for (index, slot) in indices.iter_mut().enumerate() {
+        if !function.params.contains(&LocalId(index)) {
+            *slot = next;
+            next += 1;
+        }
+    }
+    indices
+}
+
+/// Instructions of one function under construction
+struct FunctionBody<'g, 'a> {
+    generator: &'g Generator<'a>,
+    locals: Vec<u32>,
+    scratch: u32,
+    /// Statement being lowered, or the function outside statements
+    span: Span,
+    instructions: Vec<Instruction<'static>>,
+    /// Span of the statement each instruction was emitted for
+    spans: Vec<Span>,
+}
+
+impl FunctionBody<'_, '_> {
+    fn emit(&mut self, instruction: Instruction<'static>) {
+        self.instructions.push(instruction);
+        self.spans.push(self.span);
+    }
+
+    fn emit_all(&mut self, instructions: Vec<Instruction<'static>>) {
+        for instruction in instructions {
+            self.emit(instruction);
+        }
+    }
+
+    fn block(&mut self, block: &hir::Block) -> Result<()> {
+        for stmt in &block.statements {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &hir::Stmt) -> Result<()> {
+        let outer = std::mem::replace(&mut self.span, stmt.span);
+        match &stmt.kind {
+            hir::StmtKind::Let { local, value } => {
+                self.expr(value)?;
+                self.emit(Instruction::LocalSet(self.locals[local.0]));
+            },
+            hir::StmtKind::Assign { place, value } => match &place.kind {
+                hir::ExprKind::Local(id) => {
+                    self.expr(value)?;
+                    self.emit(Instruction::LocalSet(self.locals[id.0]));
+                },
+                _ => {
+                    val_type(&place.ty, place.span)?;
+                    self.address(place)?;
+                    self.expr(value)?;
+                    self.emit_all(store(&place.ty));
+                },
+            },
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition)?;
+                self.emit(Instruction::If(BlockType::Empty));
+                self.block(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.emit(Instruction::Else);
+                    self.block(else_branch)?;
+                }
+                self.emit(Instruction::End);
+            },
+            hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. } => {
+                self.expr(condition)?;
+                self.emit(Instruction::I32Eqz);
+                self.emit(Instruction::If(BlockType::Empty));
+                self.emit(Instruction::Unreachable);
+                self.emit(Instruction::End);
+            },
+            hir::StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value)?;
+                }
+                self.emit(Instruction::Return);
+            },
+            hir::StmtKind::Emit { event, fields } => {
+                let (field_import, emit_import) =
+                    self.generator.emit_imports.expect("contracts that emit import `emit`");
+                for field in fields {
+                    val_type(&field.ty, field.span)?;
+                    self.expr(field)?;
+                    if matches!(field.ty, Ty::Bool | Ty::String | Ty::Map) {
+                        self.emit(Instruction::I64ExtendI32U);
+                    }
+                    self.emit(Instruction::Call(field_import));
+                }
+                self.emit(Instruction::I32Const(*event as i32));
+                self.emit(Instruction::Call(emit_import));
+            },
+            hir::StmtKind::Expr(expr) => {
+                self.expr(expr)?;
+                if expr.ty != Ty::Unit {
+                    self.emit(Instruction::Drop);
+                }
+            },
+        }
+        self.span = outer;
+        Ok(())
+    }
+
+    /// Push the `i32` address of a state variable or an element of one
+    fn address(&mut self, place: &hir::Expr) -> Result<()> {
+        match &place.kind {
+            hir::ExprKind::State(index) => {
+                let offset = self.generator.state_offsets[*index];
+                self.emit(Instruction::I32Const(offset as i32));
+            },
+            hir::ExprKind::Index { base, index } => {
+                let Ty::Array { len, element } = &base.ty else {
+                    unreachable!("type checking only allows indexing arrays");
+                };
+                self.address(base)?;
+                self.expr(index)?;
+                self.emit(Instruction::LocalSet(self.scratch));
+                let offset = bounds_checked_offset(self.scratch, *len, size_of(element));
+                self.emit_all(offset);
+            },
+            _ => return Err(unsupported(place.span, "an array that is not a state variable")),
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &hir::Expr) -> Result<()> {
+        match &expr.kind {
+            hir::ExprKind::Int(value) => self.emit(Instruction::I64Const(*value as i64)),
+            hir::ExprKind::Bool(value) => self.emit(Instruction::I32Const(i32::from(*value))),
+            hir::ExprKind::Str(value) => {
+                let string =
+                    self.generator.string_import.expect("contracts with strings import `string`");
+                self.emit(Instruction::I32Const(self.generator.strings[value] as i32));
+                self.emit(Instruction::I32Const(value.len() as i32));
+                self.emit(Instruction::Call(string));
+            },
+            hir::ExprKind::Array(_) => return Err(unsupported(expr.span, "an array literal")),
+            hir::ExprKind::Local(id) => {
+                val_type(&expr.ty, expr.span)?;
+                self.emit(Instruction::LocalGet(self.locals[id.0]));
+            },
+            hir::ExprKind::State(_) | hir::ExprKind::Index { .. } => {
+                val_type(&expr.ty, expr.span)?;
+                self.address(expr)?;
+                self.emit_all(load(&expr.ty));
+            },
+            hir::ExprKind::Unary { op, operand } => match op {
+                UnaryOp::Not => {
+                    self.expr(operand)?;
+                    self.emit(Instruction::I32Eqz);
+                },
+                UnaryOp::Neg => {
+                    self.emit(Instruction::I64Const(0));
+                    self.expr(operand)?;
+                    self.emit(Instruction::I64Sub);
+                },
+            },
+            hir::ExprKind::Binary { op, lhs, rhs } => self.binary(*op, lhs, rhs)?,
+            hir::ExprKind::Call { callee, args } => {
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                let index = match callee {
+                    Callee::Builtin(builtin) => self.generator.builtins[builtin],
+                    Callee::Function(index) => self.generator.imports.len() + *index as u32,
+                };
+                self.emit(Instruction::Call(index));
+            },
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: &hir::Expr, rhs: &hir::Expr) -> Result<()> {
+        // Short-circuit the logical operators
+        if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            self.expr(lhs)?;
+            self.emit(Instruction::If(BlockType::Result(ValType::I32)));
+            if op == BinaryOp::And {
+                self.expr(rhs)?;
+                self.emit(Instruction::Else);
+                self.emit(Instruction::I32Const(0));
+            } else {
+                self.emit(Instruction::I32Const(1));
+                self.emit(Instruction::Else);
+                self.expr(rhs)?;
+            }
+            self.emit(Instruction::End);
+            return Ok(());
+        }
+
+        self.expr(lhs)?;
+        self.expr(rhs)?;
+        if matches!(lhs.ty, Ty::String | Ty::Map) {
+            // Type checking only allows `==` and `!=` on them
+            let equals =
+                self.generator.equals_import.expect("contracts comparing values import `equals`");
+            self.emit(Instruction::Call(equals));
+            if op == BinaryOp::Ne {
+                self.emit(Instruction::I32Eqz);
+            }
+            return Ok(());
+        }
+        let signed = lhs.ty == Ty::I64;
+        let instruction = match (op, &lhs.ty) {
+            (BinaryOp::Eq, Ty::Bool) => Instruction::I32Eq,
+            (BinaryOp::Ne, Ty::Bool) => Instruction::I32Ne,
+            (BinaryOp::Eq, _) => Instruction::I64Eq,
+            (BinaryOp::Ne, _) => Instruction::I64Ne,
+            (BinaryOp::Lt, _) if signed => Instruction::I64LtS,
+            (BinaryOp::Lt, _) => Instruction::I64LtU,
+            (BinaryOp::Le, _) if signed => Instruction::I64LeS,
+            (BinaryOp::Le, _) => Instruction::I64LeU,
+            (BinaryOp::Gt, _) if signed => Instruction::I64GtS,
+            (BinaryOp::Gt, _) => Instruction::I64GtU,
+            (BinaryOp::Ge, _) if signed => Instruction::I64GeS,
+            (BinaryOp::Ge, _) => Instruction::I64GeU,
+            (BinaryOp::Add, _) => Instruction::I64Add,
+            (BinaryOp::Sub, _) => Instruction::I64Sub,
+            (BinaryOp::Mul, _) => Instruction::I64Mul,
+            (BinaryOp::Div, _) if signed => Instruction::I64DivS,
+            (BinaryOp::Div, _) => Instruction::I64DivU,
+            (BinaryOp::Rem, _) if signed => Instruction::I64RemS,
+            (BinaryOp::Rem, _) => Instruction::I64RemU,
+            (BinaryOp::And | BinaryOp::Or, _) => unreachable!("handled above"),
+        };
+        self.emit(instruction);
+        Ok(())
+    }
+}
+
+/// Whether `block` calls `builtin` anywhere
+pub(crate) fn block_calls(block: &hir::Block, builtin: Builtin) -> bool {
+    let callee = Callee::Builtin(builtin);
+    block_has(block, |expr| {
+        matches!(&expr.kind, hir::ExprKind::Call { callee: called, .. } if *called == callee)
+    })
+}
+
+/// Whether any expression in `block`, subexpressions included, satisfies
+/// `predicate`
+fn block_has(block: &hir::Block, predicate: impl Fn(&hir::Expr) -> bool) -> bool {
+    let mut found = false;
+    for stmt in &block.statements {
+        visit_stmt_exprs(stmt, &mut |expr| found |= predicate(expr));
+    }
+    found
+}
+
+/// Whether `block` emits an event anywhere
+fn block_emits(block: &hir::Block) -> bool {
+    block.statements.iter().any(|stmt| match &stmt.kind {
+        hir::StmtKind::Emit { .. } => true,
+        hir::StmtKind::If {
+            then_branch,
+            else_branch,
+            ..
+        } => block_emits(then_branch) || else_branch.as_ref().is_some_and(block_emits),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmparser::Validator;
+
+    use super::*;
+    use crate::module::{InMemoryResolver, Program};
+    use crate::{parser::parse, typeck};
+
+    fn compile(source: &str) -> Result<Vec<u8>> {
+        generate_wasm(&typeck::check(&parse(source).unwrap()).unwrap())
+    }
+
+    fn exports(module: &[u8]) -> Vec<String> {
+        let mut names = Vec::new();
+        for payload in Parser::new(0).parse_all(module) {
+            if let Payload::ExportSection(reader) = payload.unwrap() {
+                names.extend(reader.into_iter().map(|e| e.unwrap().name.to_string()));
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn test_generated_module_validates() {
+        let module = compile(
+            "contract Vault {
+                state { owner: address; balances: [[u64; 2]; 4]; open: bool; }
+                fn deposit(slot: u64, amount: u64) {
+                    require(caller() == self.owner && self.open, \"closed\");
+                    let before = self.balances[slot][1];
+                    self.balances[slot][1] = before + amount;
+                }
+                fn spread(a: i64, b: i64) -> i64 {
+                    if a > b { return a - b; } else if a == b { return 0; }
+                    return -(b - a) % 7;
+                }
+                fn close() { self.open = !self.open || now() > 10; }
+            }",
+        )
+        .unwrap();
+
+        Validator::new().validate_all(&module).unwrap();
+        assert_eq!(
+            exports(&module),
+            vec![
+                "deposit",
+                "spread",
+                "close",
+                "_init",
+                "get_owner",
+                "set_owner",
+                "get_balances",
+                "set_balances",
+                "get_open",
+                "set_open",
+                "memory",
+            ]
+        );
+    }
+
+    fn imports(module: &[u8]) -> Vec<String> {
+        let mut names = Vec::new();
+        for payload in Parser::new(0).parse_all(module) {
+            if let Payload::ImportSection(reader) = payload.unwrap() {
+                names.extend(reader.into_iter().map(|i| i.unwrap().name.to_string()));
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn test_strings_and_maps() {
+        let contract = |source: &str| {
+            let program = Program::load(None, source.to_string(), &InMemoryResolver::new());
+            typeck::check_program(&program.unwrap()).0.unwrap()
+        };
+        let module = generate_wasm(&contract(
+            "import std::map;
+            contract Registry {
+                state { names: map; owners: [string; 2]; }
+                fn register(name: string) -> u64 {
+                    require(name != \"\", \"empty name\");
+                    self.owners[0] = \"admin\";
+                    self.names = map::insert(self.names, name, 1);
+                    return map::get(self.names, \"admin\");
+                }
+            }",
+        ))
+        .unwrap();
+
+        Validator::new().validate_all(&module).unwrap();
+        assert_eq!(imports(&module), ["map_insert", "map_get", STRING_IMPORT, EQUALS_IMPORT]);
+        // Literals are stored once, in a data segment after the state
+        let mut data = Vec::new();
+        for payload in Parser::new(0).parse_all(&module) {
+            if let Payload::DataSection(reader) = payload.unwrap() {
+                data.extend(reader.into_iter().map(|d| d.unwrap().data.to_vec()));
+            }
+        }
+        assert_eq!(data, [b"admin".to_vec()]);
+    }
+
+    #[test]
+    fn test_unsupported_constructs() {
+        let err = compile("contract C { fn f() { let a = [1, 2]; } }").unwrap_err();
+        assert!(err.to_string().contains("1:27: a `[u64; 2]` value is not supported"), "{err}");
+
+        let err = compile("contract C { state { x: u64; } fn get_x() {} }").unwrap_err();
+        assert!(err.to_string().contains("export named `get_x`"), "{err}");
+    }
+}
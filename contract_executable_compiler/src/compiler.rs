@@ -1 +1,54 @@
 //! Compiler module
+//!
+//! Output of the contract compiler.
+
+use serde::{Deserialize, Serialize};
+
+use crate::abi::ContractAbi;
+use crate::source_map::SourceMap;
+
+/// Result of compiling a contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompiledArtifact {
+    /// WebAssembly module bytes
+    Wasm(Vec<u8>),
+    /// Generated Rust source code
+    RustSource(String),
+}
+
+/// Output of [`ContractCompiler::compile`](crate::ContractCompiler::compile)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompileOutput {
+    /// Generated Rust source code, for [`CompilationTarget::Rust`](crate::CompilationTarget)
+    RustSource(String),
+    /// WebAssembly module bytes, for [`CompilationTarget::Wasm`](crate::CompilationTarget)
+    WasmBytes(Vec<u8>),
+}
+
+impl CompileOutput {
+    /// Interface of the compiled contract, the same for every target
+    pub fn abi(&self) -> Option<ContractAbi> {
+        match self {
+            Self::RustSource(source) => ContractAbi::from_rust(source),
+            Self::WasmBytes(module) => ContractAbi::from_wasm(module),
+        }
+    }
+
+    /// Map from the output back to the contract source, `None` for Wasm
+    /// modules without one
+    pub fn source_map(&self) -> Option<SourceMap> {
+        match self {
+            Self::RustSource(source) => Some(SourceMap::from_rust(source)),
+            Self::WasmBytes(module) => SourceMap::from_wasm(module),
+        }
+    }
+}
+
+impl From<CompileOutput> for CompiledArtifact {
+    fn from(output: CompileOutput) -> Self {
+        match output {
+            CompileOutput::RustSource(source) => Self::RustSource(source),
+            CompileOutput::WasmBytes(module) => Self::Wasm(module),
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! Diagnostic module
+//!
+//! Errors and warnings reported against contract source, with renderers for
+//! people (annotated source snippets) and tools (JSON).
+//!
+//! The JSON form is a stable schema, versioned by [`JSON_SCHEMA_VERSION`]:
+//! an object with `version` and `diagnostics`, each diagnostic holding
+//! `severity`, `code`, `message`, `span`, `labels` and `help`, plus `file`
+//! for diagnostics about an imported module. Spans have a
+//! `start` and exclusive `end` position, each with a byte `offset` and
+//! 1-based `line` and `column`.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use shared_core::Result;
+
+use crate::ast::Span;
+use crate::error::CompileError;
+
+/// Version of the JSON diagnostics schema
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The contract cannot be compiled
+    Error,
+    /// Suspicious code that still compiles
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// Category of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// Source that does not parse
+    SyntaxError,
+    /// A name that is not declared
+    UndefinedName,
+    /// A name declared twice in the same scope
+    DuplicateDeclaration,
+    /// A type name that does not exist
+    UnknownType,
+    /// A value of the wrong type
+    TypeMismatch,
+    /// A call with the wrong number of arguments
+    WrongArity,
+    /// A function with a return type that can end without returning
+    MissingReturn,
+    /// An operator, call, field access or index applied to the wrong kind
+    /// of operand
+    InvalidOperand,
+    /// An import no module was found for
+    UnresolvedImport,
+    /// Modules that import each other
+    ImportCycle,
+    /// A call to a library function not declared `pub`
+    PrivateItem,
+    /// A contract where a library module is expected, or the reverse
+    InvalidModule,
+    /// A local that is never read
+    UnusedVariable,
+    /// Statements after a `return`
+    UnreachableCode,
+    /// A state variable no function refers to
+    UnusedState,
+    /// A local or parameter named like one in an enclosing scope, a function
+    /// or a builtin
+    ShadowedName,
+    /// A function with more statements than the configured limit
+    LongFunction,
+    /// An integer literal other than 0 or 1 in a `require` condition
+    MagicNumber,
+    /// A library function called from another module but not declared `pub`
+    MissingPub,
+    /// An `#[allow(...)]` naming a lint that does not exist
+    UnknownLint,
+    /// A state update after a call into another module
+    Reentrancy,
+    /// Integer arithmetic that wraps on overflow
+    IntegerOverflow,
+    /// A transfer not guarded by a check of the caller
+    UnauthorizedTransfer,
+}
+
+impl ErrorCode {
+    /// Every code
+    pub const ALL: [ErrorCode; 23] = [
+        Self::SyntaxError,
+        Self::UndefinedName,
+        Self::DuplicateDeclaration,
+        Self::UnknownType,
+        Self::TypeMismatch,
+        Self::WrongArity,
+        Self::MissingReturn,
+        Self::InvalidOperand,
+        Self::UnresolvedImport,
+        Self::ImportCycle,
+        Self::PrivateItem,
+        Self::InvalidModule,
+        Self::UnusedVariable,
+        Self::UnreachableCode,
+        Self::UnusedState,
+        Self::ShadowedName,
+        Self::LongFunction,
+        Self::MagicNumber,
+        Self::MissingPub,
+        Self::UnknownLint,
+        Self::Reentrancy,
+        Self::IntegerOverflow,
+        Self::UnauthorizedTransfer,
+    ];
+
+    /// Stable code, as in `E0004`; warnings start with `W`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SyntaxError => "E0000",
+            Self::UndefinedName => "E0001",
+            Self::DuplicateDeclaration => "E0002",
+            Self::UnknownType => "E0003",
+            Self::TypeMismatch => "E0004",
+            Self::WrongArity => "E0005",
+            Self::MissingReturn => "E0006",
+            Self::InvalidOperand => "E0007",
+            Self::UnresolvedImport => "E0008",
+            Self::ImportCycle => "E0009",
+            Self::PrivateItem => "E0010",
+            Self::InvalidModule => "E0011",
+            Self::UnusedVariable => "W0001",
+            Self::UnreachableCode => "W0002",
+            Self::UnusedState => "W0003",
+            Self::ShadowedName => "W0004",
+            Self::LongFunction => "W0005",
+            Self::MagicNumber => "W0006",
+            Self::MissingPub => "W0007",
+            Self::UnknownLint => "W0008",
+            Self::Reentrancy => "W0009",
+            Self::IntegerOverflow => "W0010",
+            Self::UnauthorizedTransfer => "W0011",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_str() == code)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown error code `{code}`")))
+    }
+}
+
+/// Secondary source location explaining a diagnostic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+    /// Source the label points at
+    pub span: Span,
+    /// What the source has to do with the diagnostic
+    pub message: String,
+}
+
+/// An error or warning about contract source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// How serious it is
+    pub severity: Severity,
+    /// Category
+    pub code: ErrorCode,
+    /// What is wrong
+    pub message: String,
+    /// Offending source
+    pub span: Span,
+    /// Related source locations
+    pub labels: Vec<Label>,
+    /// How to fix it
+    pub help: Option<String>,
+    /// Id of the module the spans are in, `None` for the contract source
+    /// given as a string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    /// Create an error
+    pub fn error(code: ErrorCode, span: Span, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, span, message.into())
+    }
+
+    /// Create a warning
+    pub fn warning(code: ErrorCode, span: Span, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, span, message.into())
+    }
+
+    fn new(severity: Severity, code: ErrorCode, span: Span, message: String) -> Self {
+        Self {
+            severity,
+            code,
+            message,
+            span,
+            labels: Vec::new(),
+            help: None,
+            file: None,
+        }
+    }
+
+    /// Add a secondary label at `span`
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Set the help text
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Set the module the spans are in
+    pub fn in_file(mut self, file: Option<String>) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Render with the annotated lines of `source`, which was read from
+    /// `path`
+    ///
+    /// The primary span is underlined with `^`, labels with `-`. Spans over
+    /// several lines are underlined to the end of their first line.
+    pub fn render(&self, source: &str, path: &str) -> String {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut annotations: Vec<(Span, char, &str)> = vec![(self.span, '^', "")];
+        annotations.extend(self.labels.iter().map(|l| (l.span, '-', l.message.as_str())));
+        let mut line_numbers: Vec<u32> = annotations.iter().map(|a| a.0.start.line).collect();
+        line_numbers.sort_unstable();
+        line_numbers.dedup();
+        let width = line_numbers.last().map_or(1, |line| line.to_string().len());
+        let gutter = " ".repeat(width);
+
+        let start = self.span.start;
+        let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        out.push_str(&format!("{gutter}--> {path}:{}:{}\n", start.line, start.column));
+        out.push_str(&format!("{gutter} |\n"));
+        let mut previous: Option<u32> = None;
+        for line in line_numbers {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                out.push_str("...\n");
+            }
+            previous = Some(line);
+            let text = line
+                .checked_sub(1)
+                .and_then(|index| lines.get(index as usize))
+                .copied()
+                .unwrap_or("");
+            let row = format!("{line:>width$} | {text}");
+            out.push_str(row.trim_end());
+            out.push('\n');
+            for (span, marker, message) in annotations.iter().filter(|a| a.0.start.line == line) {
+                let column = span.start.column.max(1) as usize;
+                let line_len = text.chars().count() + 1;
+                let end = if span.end.line == line {
+                    span.end.column as usize
+                } else {
+                    line_len
+                };
+                let len = end.saturating_sub(column).max(1);
+                let markers = marker.to_string().repeat(len);
+                let padding = " ".repeat(column - 1);
+                let row = format!("{gutter} | {padding}{markers} {message}");
+                out.push_str(row.trim_end());
+                out.push('\n');
+            }
+        }
+        if let Some(help) = &self.help {
+            out.push_str(&format!("{gutter} |\n{gutter} = help: {help}\n"));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file}:")?;
+        }
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.span.start.line, self.span.start.column, self.severity, self.code, self.message
+        )
+    }
+}
+
+impl From<CompileError> for Diagnostic {
+    fn from(err: CompileError) -> Self {
+        Diagnostic::error(ErrorCode::SyntaxError, err.span, err.message())
+    }
+}
+
+/// Order `diagnostics` by file, then by position, the contract source first
+pub fn sort(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| {
+        (&a.file, a.span.start.offset).cmp(&(&b.file, b.span.start.offset))
+    });
+}
+
+/// Output format of [`render_all`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// Annotated source snippets, separated by blank lines
+    #[default]
+    Text,
+    /// The versioned JSON schema described in the [module docs](self)
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    version: u32,
+    diagnostics: &'a [Diagnostic],
+}
+
+/// Render `diagnostics` about `source`, which was read from `path`
+pub fn render_all(
+    diagnostics: &[Diagnostic],
+    source: &str,
+    path: &str,
+    format: DiagnosticFormat,
+) -> Result<String> {
+    match format {
+        DiagnosticFormat::Text => {
+            let rendered: Vec<String> =
+                diagnostics.iter().map(|d| d.render(source, path)).collect();
+            Ok(rendered.join("\n"))
+        },
+        DiagnosticFormat::Json => {
+            let report = JsonReport {
+                version: JSON_SCHEMA_VERSION,
+                diagnostics,
+            };
+            Ok(serde_json::to_string_pretty(&report)? + "\n")
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::{CompilerConfig, ContractCompiler};
+
+    const FIXTURES: &str = "tests/fixtures/diagnostics";
+
+    /// Compare `actual` with the snapshot next to `source`; set
+    /// `UPDATE_SNAPSHOTS=1` to rewrite snapshots instead
+    fn assert_snapshot(source: &Path, extension: &str, actual: &str) {
+        let snapshot = source.with_extension(extension);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&snapshot, actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&snapshot)
+            .unwrap_or_else(|_| panic!("missing snapshot {}", snapshot.display()));
+        assert_eq!(actual, expected, "snapshot {} differs", snapshot.display());
+    }
+
+    fn check_fixture(name: &str) -> (String, Vec<Diagnostic>) {
+        let path = Path::new(FIXTURES).join(name);
+        let source = fs::read_to_string(&path).unwrap();
+        let compiler = ContractCompiler::new(CompilerConfig::default()).unwrap();
+        let diagnostics = compiler.check(&source);
+        for format in [DiagnosticFormat::Text, DiagnosticFormat::Json] {
+            let rendered = render_all(&diagnostics, &source, name, format).unwrap();
+            let extension = match format {
+                DiagnosticFormat::Text => "txt",
+                DiagnosticFormat::Json => "json",
+            };
+            assert_snapshot(&path, extension, &rendered);
+        }
+        (source, diagnostics)
+    }
+
+    #[test]
+    fn test_multi_error_snapshots() {
+        let (source, diagnostics) = check_fixture("multi_error.contract");
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+        assert_eq!(codes, ["E0002", "W0003", "E0003", "W0001", "E0004", "E0001", "W0002", "E0006"]);
+
+        // JSON offsets index the source bytes the span covers
+        let json: serde_json::Value = serde_json::from_str(
+            &render_all(&diagnostics, &source, "", DiagnosticFormat::Json).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["version"], JSON_SCHEMA_VERSION);
+        let span = &json["diagnostics"][2]["span"];
+        let (start, end) = (span["start"]["offset"].as_u64(), span["end"]["offset"].as_u64());
+        assert_eq!(&source[start.unwrap() as usize..end.unwrap() as usize], "u256");
+        let parsed: Vec<Diagnostic> = serde_json::from_value(json["diagnostics"].clone()).unwrap();
+        assert_eq!(parsed, diagnostics);
+    }
+
+    #[test]
+    fn test_syntax_error_snapshot() {
+        let (_, diagnostics) = check_fixture("syntax_error.contract");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::SyntaxError);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+}
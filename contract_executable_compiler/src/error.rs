@@ -0,0 +1,56 @@
+//! Error module
+//!
+//! Errors reported against contract source.
+
+use std::fmt;
+
+use shared_core::SystemError;
+
+use crate::ast::Span;
+
+/// A syntax error in contract source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// Where the offending token starts and ends
+    pub span: Span,
+    /// Description of the offending token
+    pub found: String,
+    /// Descriptions of the tokens that would have been accepted
+    pub expected: Vec<String>,
+}
+
+impl CompileError {
+    /// 1-based line of the offending token
+    pub fn line(&self) -> u32 {
+        self.span.start.line
+    }
+
+    /// 1-based column of the offending token
+    pub fn column(&self) -> u32 {
+        self.span.start.column
+    }
+
+    /// What was found and expected, without the position
+    pub fn message(&self) -> String {
+        let found = format!("unexpected {}", self.found);
+        match self.expected.as_slice() {
+            [] => found,
+            [only] => format!("{found}, expected {only}"),
+            many => format!("{found}, expected one of {}", many.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line(), self.column(), self.message())
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<CompileError> for SystemError {
+    fn from(err: CompileError) -> Self {
+        SystemError::validation("source", err.to_string(), Some(err.found))
+    }
+}
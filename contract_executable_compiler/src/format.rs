@@ -0,0 +1,608 @@
+//! Formatter module
+//!
+//! Prints a source file back in canonical style: four-space indentation, one
+//! statement per line, a single `state` block for each run of state
+//! variables, and blank lines between items. Expressions get the fewest
+//! parentheses that keep their meaning. Comments stay where they were
+//! relative to the declarations and statements around them, and a blank
+//! line in the source is kept as one. Formatting formatted source changes
+//! nothing.
+//!
+//! Comments inside an expression, or between the fields of a one-line
+//! declaration, move to the line after it.
+
+use crate::ast::{
+    BinaryOp, Block, Contract, Event, Expr, ExprKind, Function, Ident, Import, ImportPath,
+    Param, Position, SourceFile, Span, StateField, Stmt, StmtKind, Type, TypeKind, UnaryOp,
+};
+use crate::error::CompileError;
+use crate::lexer::{tokenize_with_comments, Comment, Token, TokenKind};
+use crate::parser::parse_file;
+
+/// Widest line an event declaration is kept on
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+/// Format `source`, a contract or library module
+///
+/// Fails with the syntax error if `source` does not parse.
+pub fn format_source(source: &str) -> Result<String, CompileError> {
+    let file = parse_file(source)?;
+    let (tokens, comments) = tokenize_with_comments(source)?;
+    let mut formatter = Formatter {
+        tokens,
+        comments,
+        next_comment: 0,
+        lines: Vec::new(),
+        depth: 0,
+        last_line: 0,
+        at_block_start: true,
+    };
+    formatter.file(&file);
+    formatter.comments_before(source.len());
+    let mut out = formatter.lines.join("\n");
+    out.push('\n');
+    Ok(out)
+}
+
+/// An item of a contract, in source order
+enum Item<'a> {
+    State(&'a [StateField]),
+    Event(&'a Event),
+    Function(&'a Function),
+}
+
+struct Formatter {
+    tokens: Vec<Token>,
+    comments: Vec<Comment>,
+    /// First comment not yet printed
+    next_comment: usize,
+    lines: Vec<String>,
+    depth: usize,
+    /// Source line the last printed node or comment ended on
+    last_line: u32,
+    /// Whether nothing was printed since the last `{`, or the start
+    at_block_start: bool,
+}
+
+impl Formatter {
+    fn line(&mut self, text: &str) {
+        self.lines.push(format!("{}{text}", INDENT.repeat(self.depth)));
+        self.at_block_start = false;
+    }
+
+    fn blank(&mut self) {
+        if !self.at_block_start && self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// Span of the first `kind` token at or after byte `offset`
+    fn token_after(&self, offset: usize, kind: &TokenKind) -> Span {
+        let first = self.tokens.partition_point(|token| token.span.start.offset < offset);
+        self.tokens[first..]
+            .iter()
+            .find(|token| token.kind == *kind)
+            .map_or_else(Span::default, |token| token.span)
+    }
+
+    /// Span of the last `kind` token before byte `offset`
+    fn token_before(&self, offset: usize, kind: &TokenKind) -> Span {
+        let end = self.tokens.partition_point(|token| token.span.start.offset < offset);
+        self.tokens[..end]
+            .iter()
+            .rev()
+            .find(|token| token.kind == *kind)
+            .map_or_else(Span::default, |token| token.span)
+    }
+
+    /// Print the comments before byte `offset` on lines of their own
+    fn comments_before(&mut self, offset: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start.offset >= offset {
+                return;
+            }
+            let (text, line) = (comment.text.clone(), comment.span.start.line);
+            self.next_comment += 1;
+            if line > self.last_line + 1 {
+                self.blank();
+            }
+            self.line(&text);
+            self.last_line = line;
+        }
+    }
+
+    /// Note that the last printed node ended at `end`, and let a comment
+    /// after it on the same line, with only separators between, trail it
+    fn end(&mut self, end: Position) {
+        self.last_line = end.line;
+        let Some(comment) = self.comments.get(self.next_comment) else {
+            return;
+        };
+        let first = self.tokens.partition_point(|token| token.span.start.offset < end.offset);
+        let separators_only = self.tokens[first..]
+            .iter()
+            .take_while(|token| token.span.start.offset < comment.span.start.offset)
+            .all(|token| matches!(token.kind, TokenKind::Comma | TokenKind::Semi));
+        if comment.span.start.line == end.line && separators_only {
+            let last = self.lines.last_mut().expect("a node was printed");
+            last.push(' ');
+            last.push_str(&comment.text);
+            self.next_comment += 1;
+        }
+    }
+
+    /// Print the comments before `span`, and a blank line if the source had
+    /// one before it
+    fn start(&mut self, span: Span) {
+        self.comments_before(span.start.offset);
+        if span.start.line > self.last_line + 1 {
+            self.blank();
+        }
+    }
+
+    /// Print `text {` for the block opened by the brace at `brace`
+    fn open(&mut self, text: &str, brace: Span) {
+        self.line(&format!("{text} {{"));
+        self.depth += 1;
+        self.at_block_start = true;
+        self.end(brace.end);
+    }
+
+    /// Print the comments left in the block closed by the brace at `brace`,
+    /// then `text`
+    fn close(&mut self, brace: Span, text: &str) {
+        self.comments_before(brace.start.offset);
+        self.depth -= 1;
+        self.line(text);
+        self.end(brace.end);
+    }
+
+    /// Whether comments are left before byte `offset`
+    fn has_comments_before(&self, offset: usize) -> bool {
+        self.comments.get(self.next_comment).is_some_and(|c| c.span.start.offset < offset)
+    }
+
+    fn file(&mut self, file: &SourceFile) {
+        for import in &file.imports {
+            self.import(import);
+        }
+        if let Some(contract) = &file.contract {
+            self.blank();
+            self.contract(contract);
+        }
+        for function in &file.functions {
+            self.blank();
+            self.function(function);
+        }
+    }
+
+    fn import(&mut self, import: &Import) {
+        self.start(import.span);
+        let path = match &import.path {
+            ImportPath::File(path) => string_literal(path),
+            ImportPath::Module(segments) => segments.join("::"),
+        };
+        self.line(&format!("import {path};"));
+        self.end(import.span.end);
+    }
+
+    /// Print `#[allow(...)]` before a declaration
+    fn attributes(&mut self, allow: &[Ident]) {
+        let (Some(first), Some(last)) = (allow.first(), allow.last()) else {
+            return;
+        };
+        self.start(self.token_before(first.span.start.offset, &TokenKind::HashBracket));
+        let names: Vec<_> = allow.iter().map(|lint| lint.name.as_str()).collect();
+        self.line(&format!("#[allow({})]", names.join(", ")));
+        self.end(self.token_after(last.span.end.offset, &TokenKind::RBracket).end);
+    }
+
+    fn contract(&mut self, contract: &Contract) {
+        self.attributes(&contract.allow);
+        self.start(contract.span);
+        let head = format!("contract {}", contract.name.name);
+        let mut items: Vec<(usize, Item)> = Vec::new();
+        let mut rest = contract.state.as_slice();
+        while let Some(first) = rest.first() {
+            // A run of state variables with no other item between them
+            let next_item = contract
+                .events
+                .iter()
+                .map(|event| event.span.start.offset)
+                .chain(contract.functions.iter().map(|function| function.span.start.offset))
+                .filter(|&offset| offset > first.span.start.offset)
+                .min()
+                .unwrap_or(usize::MAX);
+            let len = rest.iter().take_while(|field| field.span.start.offset < next_item).count();
+            items.push((first.span.start.offset, Item::State(&rest[..len])));
+            rest = &rest[len..];
+        }
+        items.extend(contract.events.iter().map(|e| (e.span.start.offset, Item::Event(e))));
+        items.extend(contract.functions.iter().map(|f| (f.span.start.offset, Item::Function(f))));
+        items.sort_by_key(|(offset, _)| *offset);
+
+        let close = self.token_before(contract.span.end.offset, &TokenKind::RBrace);
+        if items.is_empty() && !self.has_comments_before(close.start.offset) {
+            self.line(&format!("{head} {{}}"));
+            self.end(contract.span.end);
+            return;
+        }
+        self.open(&head, self.token_after(contract.name.span.end.offset, &TokenKind::LBrace));
+        let mut previous_event = false;
+        for (_, item) in items {
+            let is_event = matches!(item, Item::Event(_));
+            if !(is_event && previous_event) {
+                self.blank();
+            }
+            previous_event = is_event;
+            match item {
+                Item::State(fields) => self.state(fields),
+                Item::Event(event) => self.event(event),
+                Item::Function(function) => self.function(function),
+            }
+        }
+        self.close(close, "}");
+    }
+
+    fn state(&mut self, fields: &[StateField]) {
+        let (Some(first), Some(last)) = (fields.first(), fields.last()) else {
+            return;
+        };
+        let keyword = self.token_before(first.span.start.offset, &TokenKind::State);
+        self.start(keyword);
+        self.open("state", self.token_after(keyword.end.offset, &TokenKind::LBrace));
+        for field in fields {
+            self.attributes(&field.allow);
+            self.start(field.span);
+            self.line(&format!("{}: {};", field.name.name, ty(&field.ty)));
+            self.end(field.span.end);
+        }
+        self.close(self.token_after(last.span.end.offset, &TokenKind::RBrace), "}");
+    }
+
+    fn event(&mut self, event: &Event) {
+        self.attributes(&event.allow);
+        self.start(event.span);
+        let head = format!("event {}", event.name.name);
+        let fields: Vec<_> = event.fields.iter().map(param).collect();
+        let one_line = match fields.as_slice() {
+            [] => format!("{head} {{}}"),
+            fields => format!("{head} {{ {} }}", fields.join(", ")),
+        };
+        let close = self.token_before(event.span.end.offset, &TokenKind::RBrace);
+        let fits = INDENT.len() * self.depth + one_line.len() <= MAX_WIDTH;
+        if fits && !self.has_comments_before(close.start.offset) {
+            self.line(&one_line);
+            self.end(event.span.end);
+            return;
+        }
+        self.open(&head, self.token_after(event.name.span.end.offset, &TokenKind::LBrace));
+        for (field, text) in event.fields.iter().zip(&fields) {
+            self.start(field.span);
+            self.line(&format!("{text},"));
+            self.end(field.span.end);
+        }
+        self.close(close, "}");
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.attributes(&function.allow);
+        self.start(function.span);
+        let params: Vec<_> = function.params.iter().map(param).collect();
+        let mut head = format!("fn {}({})", function.name.name, params.join(", "));
+        if function.public {
+            head = format!("pub {head}");
+        }
+        if let Some(return_type) = &function.return_type {
+            head = format!("{head} -> {}", ty(return_type));
+        }
+        self.block(&head, &function.body);
+    }
+
+    /// Print `head { ... }`, on one line if the block is empty
+    fn block(&mut self, head: &str, block: &Block) {
+        let (open, close) = braces(block);
+        if block.statements.is_empty() && !self.has_comments_before(close.start.offset) {
+            self.line(&format!("{head} {{}}"));
+            self.end(block.span.end);
+            return;
+        }
+        self.open(head, open);
+        self.statements(block);
+        self.close(close, "}");
+    }
+
+    fn statements(&mut self, block: &Block) {
+        for stmt in &block.statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        self.start(stmt.span);
+        let text = match &stmt.kind {
+            StmtKind::Let { name, ty: None, value } => {
+                format!("let {} = {};", name.name, expr(value))
+            },
+            StmtKind::Let {
+                name,
+                ty: Some(annotation),
+                value,
+            } => format!("let {}: {} = {};", name.name, ty(annotation), expr(value)),
+            StmtKind::Assign { target, value } => format!("{} = {};", expr(target), expr(value)),
+            StmtKind::If { .. } => {
+                self.if_statement("if", stmt);
+                return;
+            },
+            StmtKind::Require { condition, message } => check("require", condition, message),
+            StmtKind::Assert { condition, message } => check("assert", condition, message),
+            StmtKind::Return(None) => "return;".to_string(),
+            StmtKind::Return(Some(value)) => format!("return {};", expr(value)),
+            StmtKind::Emit { event, fields } => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.name.name, expr(&field.value)))
+                    .collect();
+                match fields.as_slice() {
+                    [] => format!("emit {} {{}};", event.name),
+                    fields => format!("emit {} {{ {} }};", event.name, fields.join(", ")),
+                }
+            },
+            StmtKind::Expr(value) => format!("{};", expr(value)),
+        };
+        self.line(&text);
+        self.end(stmt.span.end);
+    }
+
+    /// Print an `if` statement, its first line starting with `head`
+    fn if_statement(&mut self, head: &str, stmt: &Stmt) {
+        let StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = &stmt.kind
+        else {
+            return;
+        };
+        let head = format!("{head} {}", expr(condition));
+        let Some(else_branch) = else_branch else {
+            self.block(&head, then_branch);
+            return;
+        };
+        let (open, close) = braces(then_branch);
+        self.open(&head, open);
+        self.statements(then_branch);
+        match else_branch.statements.as_slice() {
+            // `else if`, whose block is just the nested statement
+            [nested] if nested.span == else_branch.span => {
+                self.comments_before(close.start.offset);
+                self.depth -= 1;
+                self.if_statement("} else if", nested);
+            },
+            _ => {
+                self.close(close, "} else {");
+                let (open, close) = braces(else_branch);
+                self.depth += 1;
+                self.at_block_start = true;
+                self.end(open.end);
+                self.statements(else_branch);
+                self.close(close, "}");
+            },
+        }
+    }
+}
+
+/// Spans of the braces around `block`
+fn braces(block: &Block) -> (Span, Span) {
+    let brace = |at: Position| {
+        let mut end = at;
+        end.offset += 1;
+        end.column += 1;
+        Span { start: at, end }
+    };
+    let mut close = block.span.end;
+    close.offset -= 1;
+    close.column -= 1;
+    (brace(block.span.start), brace(close))
+}
+
+fn param(param: &Param) -> String {
+    format!("{}: {}", param.name.name, ty(&param.ty))
+}
+
+fn ty(ty: &Type) -> String {
+    match &ty.kind {
+        TypeKind::Named(name) => name.clone(),
+        TypeKind::Array { element, len } => format!("[{}; {len}]", self::ty(element)),
+    }
+}
+
+fn check(keyword: &str, condition: &Expr, message: &Option<String>) -> String {
+    match message {
+        Some(message) => format!("{keyword}({}, {});", expr(condition), string_literal(message)),
+        None => format!("{keyword}({});", expr(condition)),
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut out = String::from('"');
+    for c in value.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn expr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Int(value) => value.to_string(),
+        ExprKind::Bool(value) => value.to_string(),
+        ExprKind::Str(value) => string_literal(value),
+        ExprKind::Ident(name) => name.clone(),
+        ExprKind::Unary { op, operand } => {
+            let op = match op {
+                UnaryOp::Not => "!",
+                UnaryOp::Neg => "-",
+            };
+            format!("{op}{}", operand_of(operand, matches!(operand.kind, ExprKind::Binary { .. })))
+        },
+        ExprKind::Binary { op, lhs, rhs } => {
+            // Left-associative: a right operand of equal precedence needs
+            // parentheses, a left one does not
+            let lhs = operand_of(lhs, binds_looser(lhs, op.precedence()));
+            let rhs = operand_of(rhs, binds_looser(rhs, op.precedence() + 1));
+            format!("{lhs} {} {rhs}", binary_op(*op))
+        },
+        ExprKind::Call { callee, args } => {
+            let args: Vec<_> = args.iter().map(self::expr).collect();
+            format!("{}({})", postfix_base(callee), args.join(", "))
+        },
+        ExprKind::Field { base, field } => format!("{}.{}", postfix_base(base), field.name),
+        ExprKind::Index { base, index } => {
+            format!("{}[{}]", postfix_base(base), self::expr(index))
+        },
+        ExprKind::Array(elements) => {
+            let elements: Vec<_> = elements.iter().map(self::expr).collect();
+            format!("[{}]", elements.join(", "))
+        },
+    }
+}
+
+/// `expr`, parenthesized if `parenthesize`
+fn operand_of(operand: &Expr, parenthesize: bool) -> String {
+    if parenthesize {
+        format!("({})", expr(operand))
+    } else {
+        expr(operand)
+    }
+}
+
+/// Whether `operand` is an operator binding looser than `precedence`
+fn binds_looser(operand: &Expr, precedence: u8) -> bool {
+    matches!(&operand.kind, ExprKind::Binary { op, .. } if op.precedence() < precedence)
+}
+
+/// The base of a call, field access or index, parenthesized if an operator
+fn postfix_base(base: &Expr) -> String {
+    operand_of(base, matches!(base.kind, ExprKind::Unary { .. } | ExprKind::Binary { .. }))
+}
+
+fn binary_op(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Or => "||",
+        BinaryOp::And => "&&",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+    }
+}
+
+/// `file` as JSON with every span left out, to compare syntax trees of
+/// differently laid out source
+#[cfg(test)]
+pub(crate) fn without_spans(file: &SourceFile) -> serde_json::Value {
+    fn strip(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.remove("span");
+                map.values_mut().for_each(strip);
+            },
+            serde_json::Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => {},
+        }
+    }
+    let mut value = serde_json::to_value(file).unwrap();
+    strip(&mut value);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    /// Every parseable contract source under `tests/fixtures`
+    fn corpus() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        let mut dirs = vec![PathBuf::from("tests/fixtures")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "contract") {
+                    paths.push(path);
+                }
+            }
+        }
+        paths.sort();
+        paths.retain(|path| parse_file(&fs::read_to_string(path).unwrap()).is_ok());
+        assert!(paths.len() > 5, "corpus too small");
+        paths
+    }
+
+    fn comments(source: &str) -> Vec<String> {
+        let (_, comments) = tokenize_with_comments(source).unwrap();
+        comments.into_iter().map(|comment| comment.text).collect()
+    }
+
+    #[test]
+    fn test_corpus_idempotent() {
+        for path in corpus() {
+            let source = fs::read_to_string(&path).unwrap();
+            let formatted = format_source(&source)
+                .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            let again = format_source(&formatted).unwrap();
+            assert_eq!(formatted, again, "{} is not stable", path.display());
+            assert_eq!(
+                without_spans(&parse_file(&formatted).unwrap()),
+                without_spans(&parse_file(&source).unwrap()),
+                "formatting changed the meaning of {}",
+                path.display()
+            );
+            assert_eq!(comments(&formatted), comments(&source), "{}", path.display());
+            assert!(formatted.lines().all(|line| line.len() <= MAX_WIDTH && !line.ends_with(' ')));
+        }
+    }
+
+    /// Compare the formatted messy fixture with its snapshot; set
+    /// `UPDATE_SNAPSHOTS=1` to rewrite it instead
+    #[test]
+    fn test_format_snapshot() {
+        let source = Path::new("tests/fixtures/format/messy.contract");
+        let formatted = format_source(&fs::read_to_string(source).unwrap()).unwrap();
+        let snapshot = source.with_extension("formatted");
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&snapshot, &formatted).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&snapshot)
+            .unwrap_or_else(|_| panic!("missing snapshot {}", snapshot.display()));
+        assert_eq!(formatted, expected, "snapshot {} differs", snapshot.display());
+    }
+
+    #[test]
+    fn test_parentheses() {
+        let source = "fn f() { x = (a - (b - c)) - d * (e + f) + -(g + h) + (!k).y[(1)]; }";
+        let formatted = format_source(source).unwrap();
+        assert!(formatted.contains("x = a - (b - c) - d * (e + f) + -(g + h) + (!k).y[1];"));
+        assert!(format_source("fn f( {}").is_err());
+    }
+}
@@ -0,0 +1,371 @@
+//! Fuzz module
+//!
+//! Random contract sources for testing the compiler against untrusted input.
+//! [`ProgramGenerator`] follows the grammar of the [parser](crate::parser),
+//! so its programs parse; names, types and operators are picked at random,
+//! so many of them do not type check.
+//!
+//! Generation draws from any [`RngCore`], such as the one a deterministic
+//! [`ResourceGovernor`](shared_core::ResourceGovernor) hands out, and
+//! [`generate_program`] seeds the same kind of generator with a given seed,
+//! so a seed always gives the same program.
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+/// Default of the node budget of [`generate_program`]
+pub const DEFAULT_MAX_NODES: usize = 200;
+/// Deepest nesting of generated blocks and expressions, well within
+/// [`DEFAULT_MAX_DEPTH`](crate::parser::DEFAULT_MAX_DEPTH)
+const MAX_DEPTH: usize = 12;
+
+const NAMES: [&str; 8] = ["a", "b", "total", "owner", "x", "limit", "count", "flag"];
+const TYPES: [&str; 6] = ["u64", "i64", "bool", "address", "string", "[u64; 3]"];
+const BINARY_OPS: [&str; 13] =
+    ["||", "&&", "==", "!=", "<", "<=", ">", ">=", "+", "-", "*", "/", "%"];
+
+/// The contract generated from `seed`, with at most [`DEFAULT_MAX_NODES`]
+/// statements and expressions
+pub fn generate_program(seed: u64) -> String {
+    ProgramGenerator::new(StdRng::seed_from_u64(seed), DEFAULT_MAX_NODES).contract()
+}
+
+/// Generates random contract sources
+pub struct ProgramGenerator<R> {
+    rng: R,
+    /// Statements and expressions left to generate
+    budget: usize,
+    depth: usize,
+    out: String,
+    state: Vec<String>,
+    /// Events and their field names
+    events: Vec<(String, Vec<String>)>,
+    functions: Vec<String>,
+    /// Parameters and locals in scope
+    locals: Vec<String>,
+}
+
+impl<R: RngCore> ProgramGenerator<R> {
+    /// Create a generator drawing from `rng`, whose programs have at most
+    /// `max_nodes` statements and expressions
+    pub fn new(rng: R, max_nodes: usize) -> Self {
+        Self {
+            rng,
+            budget: max_nodes,
+            depth: 0,
+            out: String::new(),
+            state: Vec::new(),
+            events: Vec::new(),
+            functions: Vec::new(),
+            locals: Vec::new(),
+        }
+    }
+
+    /// Generate a contract, spending the node budget left
+    pub fn contract(&mut self) -> String {
+        self.out.clear();
+        self.state = (0..self.rng.gen_range(0..4)).map(|i| format!("s{i}")).collect();
+        self.events = (0..self.rng.gen_range(0..3))
+            .map(|i| {
+                let fields = (0..self.rng.gen_range(0..3)).map(|j| format!("f{j}")).collect();
+                (format!("E{i}"), fields)
+            })
+            .collect();
+        self.functions = (0..self.rng.gen_range(1..4)).map(|i| format!("g{i}")).collect();
+
+        self.out.push_str("contract Fuzz {\n");
+        if !self.state.is_empty() {
+            self.out.push_str("    state {\n");
+            for field in self.state.clone() {
+                let ty = self.ty();
+                self.out.push_str(&format!("        {field}: {ty};\n"));
+            }
+            self.out.push_str("    }\n");
+        }
+        for (event, fields) in self.events.clone() {
+            let fields: Vec<_> =
+                fields.iter().map(|field| format!("{field}: {}", self.ty())).collect();
+            self.out.push_str(&format!("    event {event} {{ {} }}\n", fields.join(", ")));
+        }
+        for function in self.functions.clone() {
+            self.function(&function);
+        }
+        self.out.push_str("}\n");
+        std::mem::take(&mut self.out)
+    }
+
+    fn function(&mut self, name: &str) {
+        let params: Vec<_> = (0..self.rng.gen_range(0..3))
+            .map(|_| (self.pick(&NAMES).to_string(), self.ty()))
+            .collect();
+        self.locals = params.iter().map(|(name, _)| name.clone()).collect();
+        let params: Vec<_> = params.iter().map(|(name, ty)| format!("{name}: {ty}")).collect();
+        let returns = if self.rng.gen_bool(0.5) {
+            format!(" -> {}", self.ty())
+        } else {
+            String::new()
+        };
+        self.out.push_str(&format!("    fn {name}({}){returns} ", params.join(", ")));
+        self.block(1);
+        self.out.push('\n');
+    }
+
+    /// A block at indentation level `indent`, without a trailing newline
+    fn block(&mut self, indent: usize) {
+        self.out.push_str("{\n");
+        self.depth += 1;
+        let scope = self.locals.len();
+        for _ in 0..self.rng.gen_range(0..5) {
+            if self.budget == 0 {
+                break;
+            }
+            self.statement(indent + 1);
+        }
+        self.locals.truncate(scope);
+        self.depth -= 1;
+        self.out.push_str(&format!("{}}}", "    ".repeat(indent)));
+    }
+
+    fn statement(&mut self, indent: usize) {
+        self.budget = self.budget.saturating_sub(1);
+        self.out.push_str(&"    ".repeat(indent));
+        let nested = self.depth < MAX_DEPTH;
+        match self.rng.gen_range(0..9) {
+            0 | 1 => {
+                let name = self.pick(&NAMES).to_string();
+                let value = self.expr();
+                let annotation = if self.rng.gen_bool(0.5) {
+                    format!(": {}", self.ty())
+                } else {
+                    String::new()
+                };
+                self.out.push_str(&format!("let {name}{annotation} = {value};"));
+                self.locals.push(name);
+            },
+            2 => {
+                let target = self.place();
+                let value = self.expr();
+                self.out.push_str(&format!("{target} = {value};"));
+            },
+            3 if nested => {
+                let condition = self.expr();
+                self.out.push_str(&format!("if {condition} "));
+                self.block(indent);
+                while self.rng.gen_bool(0.3) && self.depth < MAX_DEPTH {
+                    let condition = self.expr();
+                    self.out.push_str(&format!(" else if {condition} "));
+                    self.block(indent);
+                }
+                if self.rng.gen_bool(0.5) {
+                    self.out.push_str(" else ");
+                    self.block(indent);
+                }
+            },
+            4 => {
+                let condition = self.expr();
+                let keyword = *self.pick(&["require", "assert"]);
+                let message = if self.rng.gen_bool(0.5) { ", \"failed\"" } else { "" };
+                self.out.push_str(&format!("{keyword}({condition}{message});"));
+            },
+            5 if self.rng.gen_bool(0.3) => self.out.push_str("return;"),
+            5 => {
+                let value = self.expr();
+                self.out.push_str(&format!("return {value};"));
+            },
+            6 if !self.events.is_empty() => {
+                let (event, fields) = self.events[self.rng.gen_range(0..self.events.len())].clone();
+                let fields: Vec<_> =
+                    fields.iter().map(|field| format!("{field}: {}", self.expr())).collect();
+                self.out.push_str(&format!("emit {event} {{ {} }};", fields.join(", ")));
+            },
+            _ => {
+                let call = self.call();
+                self.out.push_str(&format!("{call};"));
+            },
+        }
+        self.out.push('\n');
+    }
+
+    /// An assignable expression
+    fn place(&mut self) -> String {
+        match self.rng.gen_range(0..3) {
+            0 if !self.state.is_empty() => format!("self.{}", self.state_field()),
+            1 if !self.state.is_empty() => {
+                let field = self.state_field();
+                format!("self.{field}[{}]", self.expr())
+            },
+            _ => self.variable(),
+        }
+    }
+
+    fn expr(&mut self) -> String {
+        self.budget = self.budget.saturating_sub(1);
+        if self.budget == 0 || self.depth >= MAX_DEPTH {
+            return self.leaf();
+        }
+        self.depth += 1;
+        let expr = match self.rng.gen_range(0..10) {
+            0..=3 => self.leaf(),
+            4 | 5 => {
+                let (lhs, rhs) = (self.expr(), self.expr());
+                format!("{lhs} {} {rhs}", self.pick(&BINARY_OPS))
+            },
+            6 => format!("{}{}", self.pick(&["!", "-"]), self.leaf()),
+            7 => format!("({})", self.expr()),
+            8 => self.call(),
+            _ if self.rng.gen_bool(0.5) => format!("{}[{}]", self.place(), self.expr()),
+            _ => {
+                let len = self.rng.gen_range(1..4);
+                let elements: Vec<_> = (0..len).map(|_| self.expr()).collect();
+                format!("[{}]", elements.join(", "))
+            },
+        };
+        self.depth -= 1;
+        expr
+    }
+
+    fn call(&mut self) -> String {
+        let callee = match self.rng.gen_range(0..4) {
+            0 => "caller".to_string(),
+            1 => "now".to_string(),
+            _ => self.pick_owned(&self.functions.clone()),
+        };
+        let args: Vec<_> = match callee.as_str() {
+            "caller" | "now" => Vec::new(),
+            _ => (0..self.rng.gen_range(0..3)).map(|_| self.expr()).collect(),
+        };
+        format!("{callee}({})", args.join(", "))
+    }
+
+    fn leaf(&mut self) -> String {
+        match self.rng.gen_range(0..6) {
+            0 => self.rng.gen_range(0..4u64).to_string(),
+            1 => self.rng.gen::<u64>().to_string(),
+            2 => self.pick(&["true", "false"]).to_string(),
+            3 if !self.state.is_empty() => format!("self.{}", self.state_field()),
+            _ => self.variable(),
+        }
+    }
+
+    /// A local in scope, or any name
+    fn variable(&mut self) -> String {
+        if self.locals.is_empty() || self.rng.gen_bool(0.1) {
+            self.pick(&NAMES).to_string()
+        } else {
+            self.pick_owned(&self.locals.clone())
+        }
+    }
+
+    fn state_field(&mut self) -> String {
+        self.pick_owned(&self.state.clone())
+    }
+
+    fn ty(&mut self) -> String {
+        self.pick(&TYPES).to_string()
+    }
+
+    fn pick<'a, T>(&mut self, options: &'a [T]) -> &'a T {
+        &options[self.rng.gen_range(0..options.len())]
+    }
+
+    fn pick_owned(&mut self, options: &[String]) -> String {
+        self.pick(options).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use proptest::prelude::*;
+    use shared_core::{ResourceGovernor, ResourceGovernorConfig};
+
+    use super::*;
+    use crate::format::{format_source, without_spans};
+    use crate::parser::parse_file;
+    use crate::{CompilerConfig, ContractCompiler, Severity};
+
+    /// Seeds of the generated programs each test goes through
+    const SEEDS: u64 = 2000;
+
+    /// Check that formatting `source`, which parses, keeps its syntax tree
+    /// and is stable
+    fn assert_round_trip(source: &str) {
+        let file = parse_file(source).unwrap_or_else(|err| panic!("{err}\n{source}"));
+        let formatted = format_source(source).unwrap();
+        let reparsed = parse_file(&formatted).unwrap_or_else(|err| panic!("{err}\n{formatted}"));
+        assert_eq!(without_spans(&reparsed), without_spans(&file), "{source}");
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        assert_eq!(generate_program(7), generate_program(7));
+        assert_ne!(generate_program(7), generate_program(8));
+        let governed = || {
+            let governor = ResourceGovernor::new(ResourceGovernorConfig::testing()).unwrap();
+            ProgramGenerator::new(governor.get_rng(), DEFAULT_MAX_NODES).contract()
+        };
+        assert_eq!(governed(), governed());
+    }
+
+    #[test]
+    fn test_generated_programs_round_trip() {
+        for seed in 0..SEEDS {
+            assert_round_trip(&generate_program(seed));
+        }
+    }
+
+    /// Checking and compiling generated programs finishes, and does not
+    /// panic, for programs within the node budget
+    #[test]
+    fn test_generated_programs_check() {
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            let compiler = ContractCompiler::new(CompilerConfig::default()).unwrap();
+            let mut compiled = 0;
+            for seed in 0..SEEDS {
+                let source = generate_program(seed);
+                if compiler.check(&source).iter().all(|d| d.severity != Severity::Error) {
+                    compiler.compile(&source).unwrap_or_else(|err| panic!("{err}\n{source}"));
+                    compiled += 1;
+                }
+            }
+            done.send(compiled).unwrap();
+        });
+        let compiled = finished.recv_timeout(Duration::from_secs(120)).expect("checking hung");
+        assert!(compiled > 0, "no generated program type checks");
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn parse_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let source = String::from_utf8_lossy(&bytes);
+            let _ = parse_file(&source);
+        }
+
+        /// Generated programs with a span of bytes replaced
+        #[test]
+        fn mutated_programs_never_panic(
+            seed in 0..SEEDS,
+            at in 0.0..1.0f64,
+            removed in 0..8usize,
+            inserted in "[ -~]{0,8}",
+        ) {
+            let mut source = generate_program(seed);
+            let mut start = (source.len() as f64 * at) as usize;
+            while !source.is_char_boundary(start) {
+                start -= 1;
+            }
+            let end = (start + removed).min(source.len());
+            source.replace_range(start..end, &inserted);
+            if parse_file(&source).is_ok() {
+                assert_round_trip(&source);
+            }
+        }
+    }
+}
@@ -0,0 +1,707 @@
+//! Gas module
+//!
+//! Static gas estimation for compiled contracts.
+//!
+//! Instructions are charged by category: arithmetic and control flow 1,
+//! memory access 3, calls 10, and host (I/O) calls 100. Branches contribute
+//! their cheapest arm to `min` and their most expensive arm to `max`. Loops
+//! must have a recognisable counter; they count zero iterations (`for` and
+//! `while`) or one iteration (`loop`) towards `min` and
+//! [`LOOP_ITERATION_ESTIMATE`] iterations towards `max`.
+
+use std::collections::{HashMap, HashSet};
+
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+use syn::visit::{self, Visit};
+use wasmparser::{ExternalKind, Operator, Parser, Payload, TypeRef};
+
+use crate::compiler::CompiledArtifact;
+
+/// Gas charged for arithmetic, local access and control flow
+pub const ARITHMETIC_GAS: u64 = 1;
+/// Gas charged for memory access
+pub const MEMORY_GAS: u64 = 3;
+/// Gas charged for a call into contract code
+pub const CALL_GAS: u64 = 10;
+/// Gas charged for a call into the host
+pub const IO_GAS: u64 = 100;
+/// Iterations assumed per loop when computing `max_gas`
+pub const LOOP_ITERATION_ESTIMATE: u64 = 100;
+
+/// Estimated execution budget of a compiled contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasEstimate {
+    /// Cheapest call into the contract
+    pub min_gas: u64,
+    /// Most expensive call into the contract
+    pub max_gas: u64,
+    /// Maximum gas per function
+    pub per_call_estimates: HashMap<String, u64>,
+}
+
+/// Estimate the gas needed to execute a compiled artifact
+///
+/// Returns a `Validation` error naming the function if a loop has no
+/// detectable counter.
+pub fn estimate_gas(artifact: &CompiledArtifact) -> Result<GasEstimate> {
+    let costs = match artifact {
+        CompiledArtifact::Wasm(module) => wasm_costs(module)?,
+        CompiledArtifact::RustSource(code) => rust_costs(code)?,
+    };
+
+    Ok(GasEstimate {
+        min_gas: costs.iter().map(|(_, cost)| cost.min).min().unwrap_or(0),
+        max_gas: costs.iter().map(|(_, cost)| cost.max).max().unwrap_or(0),
+        per_call_estimates: costs
+            .into_iter()
+            .map(|(name, cost)| (name, cost.max))
+            .collect(),
+    })
+}
+
+/// Gas bounds of a code fragment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Cost {
+    min: u64,
+    max: u64,
+}
+
+impl Cost {
+    fn fixed(gas: u64) -> Self {
+        Self { min: gas, max: gas }
+    }
+
+    fn add(&mut self, other: Cost) {
+        self.min = self.min.saturating_add(other.min);
+        self.max = self.max.saturating_add(other.max);
+    }
+
+    /// Exactly one of the two alternatives runs
+    fn either(a: Cost, b: Cost) -> Cost {
+        Cost {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    /// Loop body executed `min_iterations` up to the estimate
+    fn repeated(body: Cost, min_iterations: u64) -> Cost {
+        Cost {
+            min: body.min.saturating_mul(min_iterations),
+            max: body.max.saturating_mul(LOOP_ITERATION_ESTIMATE),
+        }
+    }
+}
+
+fn unbounded_loop(function: &str) -> SystemError {
+    SystemError::validation(function, "unbounded loop: no loop counter detected", None)
+}
+
+fn wasm_error(err: impl std::fmt::Display) -> SystemError {
+    SystemError::validation("artifact", format!("invalid Wasm module: {err}"), None)
+}
+
+/// Cost of every exported function, or of every function if none is exported
+fn wasm_costs(module: &[u8]) -> Result<Vec<(String, Cost)>> {
+    let mut imported_functions = 0u32;
+    let mut exports = HashMap::new();
+    let mut bodies = Vec::new();
+
+    for payload in Parser::new(0).parse_all(module) {
+        match payload.map_err(wasm_error)? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if matches!(import.map_err(wasm_error)?.ty, TypeRef::Func(_)) {
+                        imported_functions += 1;
+                    }
+                }
+            },
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export.map_err(wasm_error)?;
+                    if export.kind == ExternalKind::Func {
+                        exports.insert(export.index, export.name.to_string());
+                    }
+                }
+            },
+            Payload::CodeSectionEntry(body) => bodies.push(body),
+            _ => {},
+        }
+    }
+
+    let mut costs = Vec::new();
+    for (position, body) in bodies.into_iter().enumerate() {
+        let index = imported_functions + u32::try_from(position).map_err(wasm_error)?;
+        let exported = exports.get(&index);
+        let name = exported
+            .cloned()
+            .unwrap_or_else(|| format!("$func{index}"));
+
+        let operators = body.get_operators_reader().map_err(wasm_error)?;
+        let cost = wasm_function_cost(&name, operators, imported_functions)?;
+        if exports.is_empty() || exported.is_some() {
+            costs.push((name, cost));
+        }
+    }
+    Ok(costs)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Block,
+    Loop,
+    If,
+}
+
+struct Frame {
+    kind: FrameKind,
+    cost: Cost,
+    /// Cost of the `then` arm once `else` has been seen
+    then_cost: Option<Cost>,
+    counter_update: bool,
+    conditional_branch: bool,
+}
+
+impl Frame {
+    fn new(kind: FrameKind) -> Self {
+        Self {
+            kind,
+            cost: Cost::default(),
+            then_cost: None,
+            counter_update: false,
+            conditional_branch: false,
+        }
+    }
+}
+
+fn innermost_loop(stack: &mut [Frame]) -> Option<&mut Frame> {
+    stack.iter_mut().rev().find(|frame| frame.kind == FrameKind::Loop)
+}
+
+fn wasm_function_cost(
+    name: &str,
+    operators: wasmparser::OperatorsReader<'_>,
+    imported_functions: u32,
+) -> Result<Cost> {
+    // The function body is an implicit block closed by the final `end`
+    let mut stack = vec![Frame::new(FrameKind::Block)];
+    let mut previous_was_step = false;
+
+    for operator in operators {
+        let operator = operator.map_err(wasm_error)?;
+        let gas = match &operator {
+            Operator::I32Load { .. }
+            | Operator::I64Load { .. }
+            | Operator::F32Load { .. }
+            | Operator::F64Load { .. }
+            | Operator::I32Load8S { .. }
+            | Operator::I32Load8U { .. }
+            | Operator::I32Load16S { .. }
+            | Operator::I32Load16U { .. }
+            | Operator::I64Load8S { .. }
+            | Operator::I64Load8U { .. }
+            | Operator::I64Load16S { .. }
+            | Operator::I64Load16U { .. }
+            | Operator::I64Load32S { .. }
+            | Operator::I64Load32U { .. }
+            | Operator::I32Store { .. }
+            | Operator::I64Store { .. }
+            | Operator::F32Store { .. }
+            | Operator::F64Store { .. }
+            | Operator::I32Store8 { .. }
+            | Operator::I32Store16 { .. }
+            | Operator::I64Store8 { .. }
+            | Operator::I64Store16 { .. }
+            | Operator::I64Store32 { .. }
+            | Operator::MemorySize { .. }
+            | Operator::MemoryGrow { .. }
+            | Operator::MemoryInit { .. }
+            | Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. }
+            | Operator::DataDrop { .. } => MEMORY_GAS,
+            Operator::Call { function_index } if *function_index < imported_functions => IO_GAS,
+            Operator::Call { .. } | Operator::CallIndirect { .. } => CALL_GAS,
+            Operator::End | Operator::Else => 0,
+            _ => ARITHMETIC_GAS,
+        };
+        if let Some(frame) = stack.last_mut() {
+            frame.cost.add(Cost::fixed(gas));
+        }
+
+        match &operator {
+            Operator::Block { .. } => stack.push(Frame::new(FrameKind::Block)),
+            Operator::Loop { .. } => stack.push(Frame::new(FrameKind::Loop)),
+            Operator::If { .. } => stack.push(Frame::new(FrameKind::If)),
+            Operator::Else => {
+                if let Some(frame) = stack.last_mut() {
+                    frame.then_cost = Some(std::mem::take(&mut frame.cost));
+                }
+            },
+            Operator::BrIf { .. } | Operator::BrTable { .. } => {
+                if let Some(frame) = innermost_loop(&mut stack) {
+                    frame.conditional_branch = true;
+                }
+            },
+            Operator::LocalSet { .. } | Operator::LocalTee { .. } if previous_was_step => {
+                if let Some(frame) = innermost_loop(&mut stack) {
+                    frame.counter_update = true;
+                }
+            },
+            Operator::End => {
+                let Some(frame) = stack.pop() else {
+                    return Err(wasm_error(format!("unbalanced `end` in {name}")));
+                };
+                let cost = match frame.kind {
+                    FrameKind::Block => frame.cost,
+                    FrameKind::If => match frame.then_cost {
+                        Some(then_cost) => Cost::either(then_cost, frame.cost),
+                        None => Cost::either(frame.cost, Cost::default()),
+                    },
+                    FrameKind::Loop => {
+                        if !(frame.counter_update && frame.conditional_branch) {
+                            return Err(unbounded_loop(name));
+                        }
+                        Cost::repeated(frame.cost, 1)
+                    },
+                };
+
+                match stack.last_mut() {
+                    Some(parent) => parent.cost.add(cost),
+                    None => return Ok(cost),
+                }
+            },
+            _ => {},
+        }
+
+        previous_was_step = matches!(
+            operator,
+            Operator::I32Add | Operator::I32Sub | Operator::I64Add | Operator::I64Sub
+        );
+    }
+
+    Err(wasm_error(format!("function {name} is missing its final `end`")))
+}
+
+/// Cost of every function and method in a Rust source file
+fn rust_costs(code: &str) -> Result<Vec<(String, Cost)>> {
+    let file = syn::parse_file(code).map_err(|e| {
+        SystemError::validation("artifact", format!("invalid Rust source: {e}"), None)
+    })?;
+
+    let mut functions: Vec<(String, &syn::Block)> = Vec::new();
+    for item in &file.items {
+        match item {
+            syn::Item::Fn(function) => functions.push((function.sig.ident.to_string(), &function.block)),
+            syn::Item::Impl(implementation) => {
+                let self_ty = implementation.self_ty.to_token_stream().to_string();
+                for impl_item in &implementation.items {
+                    if let syn::ImplItem::Fn(method) = impl_item {
+                        functions.push((format!("{self_ty}::{}", method.sig.ident), &method.block));
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    functions
+        .into_iter()
+        .map(|(name, block)| {
+            let mut visitor = CostVisitor::new(&name);
+            visitor.visit_block(block);
+            visitor.finish().map(|cost| (name, cost))
+        })
+        .collect()
+}
+
+/// Accumulates the cost of a Rust syntax tree
+struct CostVisitor<'a> {
+    function: &'a str,
+    cost: Cost,
+    unbounded: bool,
+}
+
+impl<'a> CostVisitor<'a> {
+    fn new(function: &'a str) -> Self {
+        Self {
+            function,
+            cost: Cost::default(),
+            unbounded: false,
+        }
+    }
+
+    fn finish(self) -> Result<Cost> {
+        if self.unbounded {
+            return Err(unbounded_loop(self.function));
+        }
+        Ok(self.cost)
+    }
+
+    /// Cost of a sub-tree, kept separate from the running total
+    fn measure(&mut self, visit: impl FnOnce(&mut CostVisitor<'a>)) -> Cost {
+        let mut nested = CostVisitor::new(self.function);
+        visit(&mut nested);
+        self.unbounded |= nested.unbounded;
+        nested.cost
+    }
+
+    fn charge(&mut self, gas: u64) {
+        self.cost.add(Cost::fixed(gas));
+    }
+}
+
+impl<'ast> Visit<'ast> for CostVisitor<'_> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        self.charge(ARITHMETIC_GAS);
+        visit::visit_local(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+        self.charge(ARITHMETIC_GAS);
+        visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_unary(&mut self, node: &'ast syn::ExprUnary) {
+        self.charge(ARITHMETIC_GAS);
+        visit::visit_expr_unary(self, node);
+    }
+
+    fn visit_expr_assign(&mut self, node: &'ast syn::ExprAssign) {
+        self.charge(ARITHMETIC_GAS);
+        visit::visit_expr_assign(self, node);
+    }
+
+    fn visit_expr_field(&mut self, node: &'ast syn::ExprField) {
+        self.charge(MEMORY_GAS);
+        visit::visit_expr_field(self, node);
+    }
+
+    fn visit_expr_index(&mut self, node: &'ast syn::ExprIndex) {
+        self.charge(MEMORY_GAS);
+        visit::visit_expr_index(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        self.charge(CALL_GAS);
+        visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        self.charge(CALL_GAS);
+        visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_macro(&mut self, _node: &'ast syn::Macro) {
+        // Macros such as `println!` or `emit!` talk to the host
+        self.charge(IO_GAS);
+    }
+
+    fn visit_expr_closure(&mut self, _node: &'ast syn::ExprClosure) {
+        // Charged where the closure is called, not where it is defined
+    }
+
+    fn visit_item(&mut self, _node: &'ast syn::Item) {
+        // Nested items are not executed in place
+    }
+
+    fn visit_expr_if(&mut self, node: &'ast syn::ExprIf) {
+        self.charge(ARITHMETIC_GAS);
+        self.visit_expr(&node.cond);
+        let then_cost = self.measure(|v| v.visit_block(&node.then_branch));
+        let else_cost = match &node.else_branch {
+            Some((_, else_branch)) => self.measure(|v| v.visit_expr(else_branch)),
+            None => Cost::default(),
+        };
+        self.cost.add(Cost::either(then_cost, else_cost));
+    }
+
+    fn visit_expr_match(&mut self, node: &'ast syn::ExprMatch) {
+        self.charge(ARITHMETIC_GAS);
+        self.visit_expr(&node.expr);
+        let arms = node
+            .arms
+            .iter()
+            .map(|arm| self.measure(|v| v.visit_arm(arm)))
+            .collect::<Vec<_>>();
+        if let Some(cost) = arms.into_iter().reduce(Cost::either) {
+            self.cost.add(cost);
+        }
+    }
+
+    fn visit_expr_for_loop(&mut self, node: &'ast syn::ExprForLoop) {
+        // The iterator acts as the loop counter
+        self.visit_expr(&node.expr);
+        let body = self.measure(|v| v.visit_block(&node.body));
+        self.cost.add(Cost::repeated(body, 0));
+    }
+
+    fn visit_expr_while(&mut self, node: &'ast syn::ExprWhile) {
+        let condition_vars = identifiers(|v| v.visit_expr(&node.cond));
+        let assigned_vars = assigned_identifiers(&node.body);
+        if condition_vars.is_disjoint(&assigned_vars) {
+            self.unbounded = true;
+        }
+
+        let condition = self.measure(|v| v.visit_expr(&node.cond));
+        let body = self.measure(|v| v.visit_block(&node.body));
+        self.cost.add(condition);
+        let mut iteration = condition;
+        iteration.add(body);
+        self.cost.add(Cost::repeated(iteration, 0));
+    }
+
+    fn visit_expr_loop(&mut self, node: &'ast syn::ExprLoop) {
+        let mut finder = BreakFinder::default();
+        finder.visit_block(&node.body);
+        if !finder.found || assigned_identifiers(&node.body).is_empty() {
+            self.unbounded = true;
+        }
+
+        let body = self.measure(|v| v.visit_block(&node.body));
+        self.cost.add(Cost::repeated(body, 1));
+    }
+}
+
+/// Single-segment paths (variable names) used in an expression
+fn identifiers(visit: impl FnOnce(&mut IdentCollector)) -> HashSet<String> {
+    let mut collector = IdentCollector::default();
+    visit(&mut collector);
+    collector.idents
+}
+
+#[derive(Default)]
+struct IdentCollector {
+    idents: HashSet<String>,
+}
+
+impl<'ast> Visit<'ast> for IdentCollector {
+    fn visit_expr_path(&mut self, node: &'ast syn::ExprPath) {
+        if let Some(ident) = node.path.get_ident() {
+            self.idents.insert(ident.to_string());
+        }
+    }
+}
+
+/// Variables assigned with `=` or a compound assignment operator
+fn assigned_identifiers(block: &syn::Block) -> HashSet<String> {
+    #[derive(Default)]
+    struct AssignmentCollector {
+        idents: HashSet<String>,
+    }
+
+    impl<'ast> Visit<'ast> for AssignmentCollector {
+        fn visit_expr_assign(&mut self, node: &'ast syn::ExprAssign) {
+            self.idents.extend(identifiers(|v| v.visit_expr(&node.left)));
+            visit::visit_expr_assign(self, node);
+        }
+
+        fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+            use syn::BinOp::*;
+            if matches!(
+                node.op,
+                AddAssign(_) | SubAssign(_) | MulAssign(_) | DivAssign(_) | ShlAssign(_) | ShrAssign(_)
+            ) {
+                self.idents.extend(identifiers(|v| v.visit_expr(&node.left)));
+            }
+            visit::visit_expr_binary(self, node);
+        }
+    }
+
+    let mut collector = AssignmentCollector::default();
+    collector.visit_block(block);
+    collector.idents
+}
+
+#[derive(Default)]
+struct BreakFinder {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for BreakFinder {
+    fn visit_expr_break(&mut self, _node: &'ast syn::ExprBreak) {
+        self.found = true;
+    }
+
+    fn visit_expr_return(&mut self, _node: &'ast syn::ExprReturn) {
+        self.found = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_encoder::{
+        BlockType, CodeSection, EntityType, ExportKind, ExportSection, Function, FunctionSection,
+        ImportSection, Instruction, MemArg, MemorySection, MemoryType, Module, TypeSection, ValType,
+    };
+
+    use super::*;
+
+    fn module(functions: &[(&str, Vec<Instruction<'static>>)]) -> Vec<u8> {
+        let mut types = TypeSection::new();
+        types.function([], []);
+        types.function([ValType::I32], [ValType::I32]);
+
+        let mut imports = ImportSection::new();
+        imports.import("env", "log", EntityType::Function(0));
+
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+
+        let mut function_section = FunctionSection::new();
+        let mut exports = ExportSection::new();
+        let mut code = CodeSection::new();
+        for (i, (name, body)) in functions.iter().enumerate() {
+            function_section.function(1);
+            exports.export(name, ExportKind::Func, u32::try_from(i).unwrap() + 1);
+
+            let mut function = Function::new([(1, ValType::I32)]);
+            for instruction in body {
+                function.instruction(instruction);
+            }
+            function.instruction(&Instruction::End);
+            code.function(&function);
+        }
+
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&imports)
+            .section(&function_section)
+            .section(&memories)
+            .section(&exports)
+            .section(&code);
+        module.finish()
+    }
+
+    fn counted_loop() -> Vec<Instruction<'static>> {
+        vec![
+            Instruction::Loop(BlockType::Empty),
+            Instruction::LocalGet(1),
+            Instruction::I32Const(1),
+            Instruction::I32Add,
+            Instruction::LocalTee(1),
+            Instruction::LocalGet(0),
+            Instruction::I32LtU,
+            Instruction::BrIf(0),
+            Instruction::End,
+            Instruction::LocalGet(1),
+        ]
+    }
+
+    #[test]
+    fn test_wasm_instruction_costs() {
+        let memarg = MemArg {
+            offset: 0,
+            align: 2,
+            memory_index: 0,
+        };
+        let artifact = CompiledArtifact::Wasm(module(&[
+            (
+                "load",
+                vec![Instruction::LocalGet(0), Instruction::I32Load(memarg)],
+            ),
+            (
+                "log",
+                vec![Instruction::Call(0), Instruction::LocalGet(0)],
+            ),
+            (
+                "branch",
+                vec![
+                    Instruction::LocalGet(0),
+                    Instruction::If(BlockType::Result(ValType::I32)),
+                    Instruction::Call(1),
+                    Instruction::Else,
+                    Instruction::I32Const(0),
+                    Instruction::End,
+                ],
+            ),
+        ]));
+
+        let estimate = estimate_gas(&artifact).unwrap();
+        assert_eq!(estimate.per_call_estimates["load"], ARITHMETIC_GAS + MEMORY_GAS);
+        assert_eq!(estimate.per_call_estimates["log"], IO_GAS + ARITHMETIC_GAS);
+        // local.get + if + max(call + local.get, i32.const)
+        assert_eq!(estimate.per_call_estimates["branch"], 2 + CALL_GAS);
+        assert_eq!(estimate.min_gas, 3);
+        assert_eq!(estimate.max_gas, IO_GAS + ARITHMETIC_GAS);
+    }
+
+    #[test]
+    fn test_wasm_loops() {
+        let artifact = CompiledArtifact::Wasm(module(&[("sum", counted_loop())]));
+        let estimate = estimate_gas(&artifact).unwrap();
+        // loop + 7 body instructions per iteration, then local.get
+        assert_eq!(estimate.min_gas, 1 + 7 + 1);
+        assert_eq!(estimate.max_gas, 1 + 7 * LOOP_ITERATION_ESTIMATE + 1);
+
+        let spin = CompiledArtifact::Wasm(module(&[(
+            "spin",
+            vec![
+                Instruction::Loop(BlockType::Empty),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::I32Const(0),
+            ],
+        )]));
+        assert!(matches!(
+            estimate_gas(&spin),
+            Err(SystemError::Validation { ref field, .. }) if field == "spin"
+        ));
+    }
+
+    #[test]
+    fn test_rust_source_costs() {
+        let source = r#"
+            fn transfer(balances: &mut Vec<u64>, amount: u64) {
+                let fee = amount / 100;
+                if fee > 0 {
+                    emit!("fee", fee);
+                }
+                balances[0] -= amount + fee;
+            }
+
+            fn sum(n: u64) -> u64 {
+                let mut total = 0;
+                let mut i = 0;
+                while i < n {
+                    total += i;
+                    i += 1;
+                }
+                for value in 0..n {
+                    total += value;
+                }
+                total
+            }
+        "#;
+
+        let estimate = estimate_gas(&CompiledArtifact::RustSource(source.to_string())).unwrap();
+        let transfer = estimate.per_call_estimates["transfer"];
+        assert!(transfer > IO_GAS, "macro call should be charged as I/O");
+        assert!(estimate.min_gas < transfer);
+        assert!(estimate.per_call_estimates["sum"] > LOOP_ITERATION_ESTIMATE);
+    }
+
+    #[test]
+    fn test_rust_unbounded_loops() {
+        for source in [
+            "fn spin() { loop { work(); } }",
+            "fn wait(ready: bool) { while !ready { poll(); } }",
+        ] {
+            let result = estimate_gas(&CompiledArtifact::RustSource(source.to_string()));
+            assert!(
+                matches!(result, Err(SystemError::Validation { .. })),
+                "{source} should be rejected"
+            );
+        }
+
+        let bounded = "fn retry() { let mut n = 0; loop { n += 1; if n > 3 { break; } } }";
+        assert!(estimate_gas(&CompiledArtifact::RustSource(bounded.to_string())).is_ok());
+    }
+}
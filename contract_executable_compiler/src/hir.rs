@@ -0,0 +1,405 @@
+//! HIR module
+//!
+//! Typed intermediate representation produced by [`typeck`](crate::typeck)
+//! for code generation. Every expression carries its [`Ty`], and names are
+//! resolved: locals, state variables and functions are referred to by index.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{BinaryOp, Span, UnaryOp};
+
+/// Type of a value
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Ty {
+    /// Unsigned 64-bit integer
+    U64,
+    /// Signed 64-bit integer
+    I64,
+    /// Boolean
+    Bool,
+    /// UTF-8 string
+    String,
+    /// Map from `string` keys to `u64` values, see `std::map`
+    Map,
+    /// Account address
+    Address,
+    /// Fixed-size array
+    Array {
+        /// Element type
+        element: Box<Ty>,
+        /// Number of elements
+        len: u64,
+    },
+    /// No value, the result of functions without a return type
+    Unit,
+}
+
+impl Ty {
+    /// Whether the type is `u64` or `i64`
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::U64 | Self::I64)
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U64 => f.write_str("u64"),
+            Self::I64 => f.write_str("i64"),
+            Self::Bool => f.write_str("bool"),
+            Self::String => f.write_str("string"),
+            Self::Map => f.write_str("map"),
+            Self::Address => f.write_str("address"),
+            Self::Array { element, len } => write!(f, "[{element}; {len}]"),
+            Self::Unit => f.write_str("()"),
+        }
+    }
+}
+
+/// Index of a local in [`Function::locals`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocalId(pub usize);
+
+/// A type-checked contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contract {
+    /// Contract name
+    pub name: String,
+    /// State variables, indexed by [`ExprKind::State`]
+    pub state: Vec<StateVar>,
+    /// Events, indexed by [`StmtKind::Emit`]
+    pub events: Vec<Event>,
+    /// Functions, indexed by [`Callee::Function`]
+    pub functions: Vec<Function>,
+    /// Id of the contract module, as in [`Diagnostic::file`](crate::Diagnostic::file)
+    pub file: Option<String>,
+}
+
+/// A persistent contract variable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateVar {
+    /// Variable name
+    pub name: String,
+    /// Variable type
+    pub ty: Ty,
+    /// Declaration in the source
+    pub span: Span,
+}
+
+/// An event the contract can emit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// Event name
+    pub name: String,
+    /// Fields, in declaration order
+    pub fields: Vec<EventField>,
+    /// Declaration in the source
+    pub span: Span,
+}
+
+/// A field of an event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventField {
+    /// Field name
+    pub name: String,
+    /// Field type
+    pub ty: Ty,
+    /// Declaration in the source
+    pub span: Span,
+}
+
+/// A type-checked function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Function {
+    /// Function name; library functions are qualified by their module, as
+    /// in `math::min`
+    pub name: String,
+    /// Whether the function is a contract entry point rather than a library
+    /// function only other functions call
+    pub exported: bool,
+    /// Parameters, in order
+    pub params: Vec<LocalId>,
+    /// Every local of the function, parameters included
+    pub locals: Vec<Local>,
+    /// Return type, [`Ty::Unit`] if none is declared
+    pub return_type: Ty,
+    /// Function body
+    pub body: Block,
+    /// Declaration in the source
+    pub span: Span,
+    /// Id of the module declaring the function
+    pub file: Option<String>,
+}
+
+/// A parameter or `let` binding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Local {
+    /// Bound name
+    pub name: String,
+    /// Bound type
+    pub ty: Ty,
+    /// Declaration in the source
+    pub span: Span,
+}
+
+/// A list of statements with its own scope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    /// Statements, in order
+    pub statements: Vec<Stmt>,
+    /// Source of the block
+    pub span: Span,
+}
+
+/// A statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stmt {
+    /// What the statement is
+    pub kind: StmtKind,
+    /// Source of the statement
+    pub span: Span,
+}
+
+/// Kinds of statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StmtKind {
+    /// Initialize a local
+    Let {
+        /// Initialized local
+        local: LocalId,
+        /// Its value
+        value: Expr,
+    },
+    /// Store into a place: a local, state variable or array element of one
+    Assign {
+        /// Assigned place
+        place: Expr,
+        /// Stored value
+        value: Expr,
+    },
+    /// Conditional
+    If {
+        /// Boolean condition
+        condition: Expr,
+        /// Taken when the condition holds
+        then_branch: Block,
+        /// Taken otherwise
+        else_branch: Option<Block>,
+    },
+    /// Revert the call unless the condition holds
+    Require {
+        /// Boolean condition
+        condition: Expr,
+        /// Revert message
+        message: Option<String>,
+    },
+    /// Abort on a broken invariant unless the condition holds
+    Assert {
+        /// Boolean condition
+        condition: Expr,
+        /// Abort message
+        message: Option<String>,
+    },
+    /// Return from the function
+    Return(Option<Expr>),
+    /// Emit an event
+    Emit {
+        /// Emitted event, by index in [`Contract::events`]
+        event: usize,
+        /// Field values in the event's declaration order, which is also the
+        /// order they are evaluated in
+        fields: Vec<Expr>,
+    },
+    /// Evaluate an expression for its effects
+    Expr(Expr),
+}
+
+/// A typed expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expr {
+    /// What the expression is
+    pub kind: ExprKind,
+    /// Type of its value
+    pub ty: Ty,
+    /// Source of the expression
+    pub span: Span,
+}
+
+/// Kinds of expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExprKind {
+    /// Integer literal of type `ty`
+    Int(u64),
+    /// Boolean literal
+    Bool(bool),
+    /// String literal
+    Str(String),
+    /// Read a local
+    Local(LocalId),
+    /// Read a state variable, by index in [`Contract::state`]
+    State(usize),
+    /// Prefix operator
+    Unary {
+        /// Operator
+        op: UnaryOp,
+        /// Operand
+        operand: Box<Expr>,
+    },
+    /// Infix operator; both operands have the same type
+    Binary {
+        /// Operator
+        op: BinaryOp,
+        /// Left operand
+        lhs: Box<Expr>,
+        /// Right operand
+        rhs: Box<Expr>,
+    },
+    /// Function call
+    Call {
+        /// Called function
+        callee: Callee,
+        /// Arguments, matching the callee's parameters
+        args: Vec<Expr>,
+    },
+    /// Array element, the index is a `u64`
+    Index {
+        /// Indexed array
+        base: Box<Expr>,
+        /// Element index
+        index: Box<Expr>,
+    },
+    /// Array literal
+    Array(Vec<Expr>),
+}
+
+/// Target of a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Callee {
+    /// Contract function, by index in [`Contract::functions`]
+    Function(usize),
+    /// Function provided by the runtime
+    Builtin(Builtin),
+}
+
+/// Functions provided by the runtime
+///
+/// `caller` and `now` can be called from anywhere; the others are the
+/// functions of standard library modules that every target implements
+/// natively, called through their module as in `map::insert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Builtin {
+    /// `caller() -> address`, the account calling the contract
+    Caller,
+    /// `now() -> u64`, block time in seconds
+    Now,
+    /// `map::insert(entries, key, value) -> map`, `entries` with `key` set
+    /// to `value`
+    MapInsert,
+    /// `map::get(entries, key) -> u64`, the value of `key`, reverting if
+    /// there is none
+    MapGet,
+    /// `map::remove(entries, key) -> map`, `entries` without `key`
+    MapRemove,
+    /// `map::contains(entries, key) -> bool`, whether `key` has a value
+    MapContains,
+    /// `assert::require_eq(a, b)`, reverting unless `a == b`
+    RequireEq,
+    /// `assert::require_ne(a, b)`, reverting unless `a != b`
+    RequireNe,
+    /// `assert::require_gt(a, b)`, reverting unless `a > b`
+    RequireGt,
+    /// `assert::require_ge(a, b)`, reverting unless `a >= b`
+    RequireGe,
+    /// `assert::require_lt(a, b)`, reverting unless `a < b`
+    RequireLt,
+    /// `assert::require_le(a, b)`, reverting unless `a <= b`
+    RequireLe,
+}
+
+impl Builtin {
+    /// Every builtin
+    pub const ALL: [Builtin; 12] = [
+        Builtin::Caller,
+        Builtin::Now,
+        Builtin::MapInsert,
+        Builtin::MapGet,
+        Builtin::MapRemove,
+        Builtin::MapContains,
+        Builtin::RequireEq,
+        Builtin::RequireNe,
+        Builtin::RequireGt,
+        Builtin::RequireGe,
+        Builtin::RequireLt,
+        Builtin::RequireLe,
+    ];
+
+    /// Name the builtin is called by, within its module
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Caller => "caller",
+            Self::Now => "now",
+            Self::MapInsert => "insert",
+            Self::MapGet => "get",
+            Self::MapRemove => "remove",
+            Self::MapContains => "contains",
+            Self::RequireEq => "require_eq",
+            Self::RequireNe => "require_ne",
+            Self::RequireGt => "require_gt",
+            Self::RequireGe => "require_ge",
+            Self::RequireLt => "require_lt",
+            Self::RequireLe => "require_le",
+        }
+    }
+
+    /// Standard module the builtin belongs to, `None` for those callable
+    /// from anywhere
+    pub fn module(self) -> Option<&'static str> {
+        match self {
+            Self::Caller | Self::Now => None,
+            Self::MapInsert | Self::MapGet | Self::MapRemove | Self::MapContains => {
+                Some("std::map")
+            },
+            _ => Some("std::assert"),
+        }
+    }
+
+    /// Name unique among builtins, which targets implement it under, as in
+    /// `map_insert`
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::MapInsert => "map_insert",
+            Self::MapGet => "map_get",
+            Self::MapRemove => "map_remove",
+            Self::MapContains => "map_contains",
+            _ => self.name(),
+        }
+    }
+
+    /// Operator of the comparison a `std::assert` builtin requires to hold
+    pub fn comparison(self) -> Option<&'static str> {
+        match self {
+            Self::RequireEq => Some("=="),
+            Self::RequireNe => Some("!="),
+            Self::RequireGt => Some(">"),
+            Self::RequireGe => Some(">="),
+            Self::RequireLt => Some("<"),
+            Self::RequireLe => Some("<="),
+            _ => None,
+        }
+    }
+
+    /// Parameter types and return type
+    pub fn signature(self) -> (Vec<Ty>, Ty) {
+        match self {
+            Self::Caller => (Vec::new(), Ty::Address),
+            Self::Now => (Vec::new(), Ty::U64),
+            Self::MapInsert => (vec![Ty::Map, Ty::String, Ty::U64], Ty::Map),
+            Self::MapGet => (vec![Ty::Map, Ty::String], Ty::U64),
+            Self::MapRemove => (vec![Ty::Map, Ty::String], Ty::Map),
+            Self::MapContains => (vec![Ty::Map, Ty::String], Ty::Bool),
+            _ => (vec![Ty::U64, Ty::U64], Ty::Unit),
+        }
+    }
+}
@@ -0,0 +1,609 @@
+//! Interpreter module
+//!
+//! Runs a type-checked [`hir::Contract`] directly, without generating code,
+//! with the semantics of the code generators: integer arithmetic wraps,
+//! while division by zero, signed division overflow and out-of-bounds
+//! indices fail the call, as does a failed `require` or `assert`. Failures
+//! are reported at their source location with the message the generated
+//! Rust's `ContractError` displays, as in `reverted: std::math: addition
+//! overflow`.
+//!
+//! Arguments and results are JSON values like those of the Wasm
+//! [`runtime`](crate::runtime): `u64`, `i64` and `address` are numbers and
+//! `bool` is a boolean, with strings and arrays as JSON strings and arrays
+//! and maps as objects. The builtins of standard modules are run by the
+//! host, see [`stdlib`](crate::stdlib).
+//! Calls are transactional: a failed call leaves the state and the events
+//! as they were. Calls nest at most [`MAX_CALL_DEPTH`] deep, so unbounded
+//! recursion fails instead of overflowing the host's stack.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value as Json;
+use shared_core::{Result, SystemError};
+
+use crate::ast::{BinaryOp, Span, UnaryOp};
+use crate::hir::{self, Builtin, Callee, LocalId, Ty};
+use crate::source_map::SourceLocation;
+use crate::stdlib;
+
+/// Deepest nesting of contract function calls
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// Values the host supplies to the `env` builtins of a call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallContext {
+    /// Returned by `caller()`
+    pub caller: u64,
+    /// Returned by `now()`
+    pub now: u64,
+}
+
+/// An event emitted by a contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContractEvent {
+    /// Event name
+    pub name: String,
+    /// Field names and JSON values, in declaration order
+    pub fields: Vec<(String, Json)>,
+}
+
+impl ContractEvent {
+    /// Value of field `name`
+    pub fn field(&self, name: &str) -> Option<&Json> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value)
+    }
+}
+
+/// A contract with its own state, run by interpreting its IR
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    contract: Arc<hir::Contract>,
+    state: Vec<Value>,
+    context: CallContext,
+    events: Vec<ContractEvent>,
+}
+
+/// Runtime value; integers of every type are held as their `u64` bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Int(u64),
+    Bool(bool),
+    Str(String),
+    Map(BTreeMap<String, u64>),
+    Array(Vec<Value>),
+    Unit,
+}
+
+/// Why a call failed, and where
+struct Failure {
+    reason: String,
+    span: Span,
+    file: Option<String>,
+}
+
+/// Where execution goes after a statement
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+/// Locals of a function call
+struct Frame<'a> {
+    function: &'a hir::Function,
+    locals: Vec<Value>,
+    depth: usize,
+}
+
+impl Frame<'_> {
+    fn fail(&self, span: Span, reason: impl Into<String>) -> Failure {
+        Failure {
+            reason: reason.into(),
+            span,
+            file: self.function.file.clone(),
+        }
+    }
+}
+
+type Exec<T> = std::result::Result<T, Failure>;
+
+impl Interpreter {
+    /// Interpret `contract`, with its state variables zeroed
+    pub fn new(contract: hir::Contract) -> Self {
+        let state = contract.state.iter().map(|var| zero(&var.ty)).collect();
+        Self {
+            contract: Arc::new(contract),
+            state,
+            context: CallContext::default(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Set the values the builtins return during later calls
+    pub fn set_context(&mut self, context: CallContext) {
+        self.context = context;
+    }
+
+    /// Exported functions, in declaration order
+    pub fn methods(&self) -> impl Iterator<Item = &hir::Function> {
+        self.contract.functions.iter().filter(|function| function.exported)
+    }
+
+    /// Call the exported function `method` with the JSON array `args`,
+    /// returning its result, `null` for functions without a return type
+    ///
+    /// Unknown methods are `NotFound` errors and arguments not matching the
+    /// parameters `Validation` errors. A call that fails while running is a
+    /// `SystemSpecific` error naming the method, the source location and the
+    /// reason.
+    pub fn call(&mut self, method: &str, args: Json) -> Result<Json> {
+        let contract = &self.contract;
+        let (index, function) = contract
+            .functions
+            .iter()
+            .enumerate()
+            .find(|(_, function)| function.exported && function.name == method)
+            .ok_or_else(|| SystemError::not_found("contract method", method))?;
+        let args = match args {
+            Json::Null => Vec::new(),
+            Json::Array(args) => args,
+            other => {
+                return Err(SystemError::validation(
+                    "args",
+                    "expected an array of arguments",
+                    Some(other.to_string()),
+                ));
+            },
+        };
+        if args.len() != function.params.len() {
+            return Err(SystemError::validation(
+                "args",
+                format!("`{method}` takes {} argument(s)", function.params.len()),
+                Some(args.len().to_string()),
+            ));
+        }
+        let args = args
+            .iter()
+            .zip(&function.params)
+            .map(|(arg, param)| from_json(arg, &function.locals[param.0].ty))
+            .collect::<Result<Vec<_>>>()?;
+        let return_type = function.return_type.clone();
+
+        let (state, events) = (self.state.clone(), self.events.len());
+        match self.invoke(index, args, 0) {
+            Ok(value) => Ok(to_json(&value, &return_type)),
+            Err(failure) => {
+                self.state = state;
+                self.events.truncate(events);
+                let location = SourceLocation::new(failure.file, failure.span);
+                Err(SystemError::SystemSpecific {
+                    system: "contract_interpreter".to_string(),
+                    message: format!("`{method}` failed at {location}: {}", failure.reason),
+                    context: None,
+                })
+            },
+        }
+    }
+
+    /// Events emitted by successful calls since the last call of this
+    /// method, in emission order
+    pub fn take_events(&mut self) -> Vec<ContractEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Run function `index` with `args` bound to its parameters
+    fn invoke(&mut self, index: usize, args: Vec<Value>, depth: usize) -> Exec<Value> {
+        // Shared so the frame does not borrow the interpreter
+        let contract = Arc::clone(&self.contract);
+        let function = &contract.functions[index];
+        let mut frame = Frame {
+            function,
+            locals: function.locals.iter().map(|local| zero(&local.ty)).collect(),
+            depth,
+        };
+        if depth >= MAX_CALL_DEPTH {
+            let reason = format!("calls nest deeper than {MAX_CALL_DEPTH}");
+            return Err(frame.fail(function.span, reason));
+        }
+        for (param, arg) in function.params.iter().zip(args) {
+            frame.locals[param.0] = arg;
+        }
+        match self.block(&mut frame, &function.body)? {
+            Flow::Return(value) => Ok(value),
+            Flow::Next => Ok(Value::Unit),
+        }
+    }
+
+    fn block(&mut self, frame: &mut Frame<'_>, block: &hir::Block) -> Exec<Flow> {
+        for stmt in &block.statements {
+            if let Flow::Return(value) = self.stmt(frame, stmt)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn stmt(&mut self, frame: &mut Frame<'_>, stmt: &hir::Stmt) -> Exec<Flow> {
+        match &stmt.kind {
+            hir::StmtKind::Let { local, value } => {
+                frame.locals[local.0] = self.expr(frame, value)?;
+            },
+            hir::StmtKind::Assign { place, value } => {
+                // The value first, as in the generated Rust
+                let value = self.expr(frame, value)?;
+                *self.place(frame, place)? = value;
+            },
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.condition(frame, condition)? {
+                    return self.block(frame, then_branch);
+                }
+                if let Some(else_branch) = else_branch {
+                    return self.block(frame, else_branch);
+                }
+            },
+            hir::StmtKind::Require { condition, message } => {
+                if !self.condition(frame, condition)? {
+                    let message = message.as_deref().unwrap_or("requirement failed");
+                    return Err(frame.fail(stmt.span, format!("reverted: {message}")));
+                }
+            },
+            hir::StmtKind::Assert { condition, message } => {
+                if !self.condition(frame, condition)? {
+                    let message = message.as_deref().unwrap_or("assertion failed");
+                    return Err(frame.fail(stmt.span, format!("assertion failed: {message}")));
+                }
+            },
+            hir::StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(frame, value)?,
+                    None => Value::Unit,
+                };
+                return Ok(Flow::Return(value));
+            },
+            hir::StmtKind::Emit { event, fields } => {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    values.push(self.expr(frame, field)?);
+                }
+                let event = &self.contract.events[*event];
+                let fields = event
+                    .fields
+                    .iter()
+                    .zip(&values)
+                    .map(|(field, value)| (field.name.clone(), to_json(value, &field.ty)))
+                    .collect();
+                self.events.push(ContractEvent {
+                    name: event.name.clone(),
+                    fields,
+                });
+            },
+            hir::StmtKind::Expr(expr) => {
+                self.expr(frame, expr)?;
+            },
+        }
+        Ok(Flow::Next)
+    }
+
+    fn condition(&mut self, frame: &mut Frame<'_>, condition: &hir::Expr) -> Exec<bool> {
+        match self.expr(frame, condition)? {
+            Value::Bool(value) => Ok(value),
+            _ => unreachable!("type checking only allows boolean conditions"),
+        }
+    }
+
+    fn expr(&mut self, frame: &mut Frame<'_>, expr: &hir::Expr) -> Exec<Value> {
+        let value = match &expr.kind {
+            hir::ExprKind::Int(value) => Value::Int(*value),
+            hir::ExprKind::Bool(value) => Value::Bool(*value),
+            hir::ExprKind::Str(value) => Value::Str(value.clone()),
+            hir::ExprKind::Local(id) => frame.locals[id.0].clone(),
+            hir::ExprKind::State(index) => self.state[*index].clone(),
+            hir::ExprKind::Unary { op, operand } => match (op, self.expr(frame, operand)?) {
+                (UnaryOp::Not, Value::Bool(value)) => Value::Bool(!value),
+                (UnaryOp::Neg, Value::Int(value)) => Value::Int(value.wrapping_neg()),
+                _ => unreachable!("type checking only allows `!` on bools and `-` on integers"),
+            },
+            hir::ExprKind::Binary { op, lhs, rhs } => self.binary(frame, *op, lhs, rhs)?,
+            hir::ExprKind::Call { callee, args } => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.expr(frame, arg)?);
+                }
+                match callee {
+                    Callee::Builtin(builtin) => self
+                        .builtin(*builtin, values)
+                        .map_err(|reason| frame.fail(expr.span, format!("reverted: {reason}")))?,
+                    Callee::Function(index) => self.invoke(*index, values, frame.depth + 1)?,
+                }
+            },
+            hir::ExprKind::Index { base, index } => {
+                let Value::Array(elements) = self.expr(frame, base)? else {
+                    unreachable!("type checking only allows indexing arrays");
+                };
+                let index = self.index(frame, index, elements.len())?;
+                elements[index].clone()
+            },
+            hir::ExprKind::Array(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.expr(frame, element)?);
+                }
+                Value::Array(values)
+            },
+        };
+        Ok(value)
+    }
+
+    /// Run `builtin` on `args`, failing with the revert message
+    fn builtin(&self, builtin: Builtin, args: Vec<Value>) -> std::result::Result<Value, String> {
+        let value = match (builtin, args.as_slice()) {
+            (Builtin::Caller, []) => Value::Int(self.context.caller),
+            (Builtin::Now, []) => Value::Int(self.context.now),
+            (Builtin::MapInsert, [Value::Map(entries), Value::Str(key), Value::Int(value)]) => {
+                let mut entries = entries.clone();
+                entries.insert(key.clone(), *value);
+                Value::Map(entries)
+            },
+            (Builtin::MapGet, [Value::Map(entries), Value::Str(key)]) => {
+                Value::Int(*entries.get(key).ok_or_else(|| stdlib::missing_key(key))?)
+            },
+            (Builtin::MapRemove, [Value::Map(entries), Value::Str(key)]) => {
+                let mut entries = entries.clone();
+                entries.remove(key);
+                Value::Map(entries)
+            },
+            (Builtin::MapContains, [Value::Map(entries), Value::Str(key)]) => {
+                Value::Bool(entries.contains_key(key))
+            },
+            (_, [Value::Int(a), Value::Int(b)]) => match stdlib::assert_failure(builtin, *a, *b) {
+                Some(message) => return Err(message),
+                None => Value::Unit,
+            },
+            _ => unreachable!("type checking only passes builtins their parameter types"),
+        };
+        Ok(value)
+    }
+
+    fn binary(
+        &mut self,
+        frame: &mut Frame<'_>,
+        op: BinaryOp,
+        lhs: &hir::Expr,
+        rhs: &hir::Expr,
+    ) -> Exec<Value> {
+        let left = self.expr(frame, lhs)?;
+        // `&&` and `||` only evaluate the right operand when it decides
+        match (op, &left) {
+            (BinaryOp::And, Value::Bool(false)) => return Ok(left),
+            (BinaryOp::Or, Value::Bool(true)) => return Ok(left),
+            (BinaryOp::And | BinaryOp::Or, _) => return self.expr(frame, rhs),
+            _ => {},
+        }
+        let right = self.expr(frame, rhs)?;
+        let ordering = || compare(&lhs.ty, &left, &right);
+        let value = match op {
+            BinaryOp::Eq => Value::Bool(left == right),
+            BinaryOp::Ne => Value::Bool(left != right),
+            BinaryOp::Lt => Value::Bool(ordering() == Ordering::Less),
+            BinaryOp::Le => Value::Bool(ordering() != Ordering::Greater),
+            BinaryOp::Gt => Value::Bool(ordering() == Ordering::Greater),
+            BinaryOp::Ge => Value::Bool(ordering() != Ordering::Less),
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                let (Value::Int(a), Value::Int(b)) = (left, right) else {
+                    unreachable!("type checking only allows arithmetic on integers");
+                };
+                let result = arithmetic(op, &lhs.ty, a, b);
+                Value::Int(result.ok_or_else(|| {
+                    frame.fail(lhs.span, "division by zero or overflow")
+                })?)
+            },
+            BinaryOp::And | BinaryOp::Or => unreachable!("logical operators are handled above"),
+        };
+        Ok(value)
+    }
+
+    /// Value of the array index `index` into `len` elements, failing when
+    /// out of bounds
+    fn index(&mut self, frame: &mut Frame<'_>, index: &hir::Expr, len: usize) -> Exec<usize> {
+        let Value::Int(value) = self.expr(frame, index)? else {
+            unreachable!("type checking only allows `u64` indices");
+        };
+        match usize::try_from(value) {
+            Ok(i) if i < len => Ok(i),
+            _ => {
+                let reason = format!("index {value} out of bounds for length {len}");
+                Err(frame.fail(index.span, reason))
+            },
+        }
+    }
+
+    /// The value a place refers to, evaluating its indices
+    fn place<'s>(&'s mut self, frame: &'s mut Frame<'_>, place: &hir::Expr) -> Exec<&'s mut Value> {
+        let mut indices = Vec::new();
+        let mut root = place;
+        while let hir::ExprKind::Index { base, index } = &root.kind {
+            indices.push(index);
+            root = base;
+        }
+        // Indices are evaluated outermost array first, as they are written
+        let mut positions = Vec::with_capacity(indices.len());
+        let mut ty = &root.ty;
+        for index in indices.into_iter().rev() {
+            let Ty::Array { element, len } = ty else {
+                unreachable!("type checking only allows indexing arrays");
+            };
+            let len = usize::try_from(*len).unwrap_or(usize::MAX);
+            positions.push(self.index(frame, index, len)?);
+            ty = element;
+        }
+        let mut value = match &root.kind {
+            hir::ExprKind::Local(LocalId(id)) => &mut frame.locals[*id],
+            hir::ExprKind::State(index) => &mut self.state[*index],
+            _ => unreachable!("type checking only allows assigning to places"),
+        };
+        for position in positions {
+            let Value::Array(elements) = value else {
+                unreachable!("type checking only allows indexing arrays");
+            };
+            value = &mut elements[position];
+        }
+        Ok(value)
+    }
+}
+
+/// Zero value of `ty`, the initial value of state variables
+fn zero(ty: &Ty) -> Value {
+    match ty {
+        Ty::U64 | Ty::I64 | Ty::Address => Value::Int(0),
+        Ty::Bool => Value::Bool(false),
+        Ty::String => Value::Str(String::new()),
+        Ty::Map => Value::Map(BTreeMap::new()),
+        Ty::Array { element, len } => Value::Array(vec![zero(element); *len as usize]),
+        Ty::Unit => Value::Unit,
+    }
+}
+
+/// Order of two values of type `ty`
+fn compare(ty: &Ty, left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Int(a), Value::Int(b)) if *ty == Ty::I64 => (*a as i64).cmp(&(*b as i64)),
+        (Value::Int(a), Value::Int(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Str(a), Value::Str(b)) => a.cmp(b),
+        _ => unreachable!("type checking only allows ordering scalars"),
+    }
+}
+
+/// `a op b` over integers of type `ty`, `None` on division by zero or
+/// signed division overflow
+fn arithmetic(op: BinaryOp, ty: &Ty, a: u64, b: u64) -> Option<u64> {
+    match op {
+        BinaryOp::Add => Some(a.wrapping_add(b)),
+        BinaryOp::Sub => Some(a.wrapping_sub(b)),
+        BinaryOp::Mul => Some(a.wrapping_mul(b)),
+        BinaryOp::Div if *ty == Ty::I64 => (a as i64).checked_div(b as i64).map(|v| v as u64),
+        BinaryOp::Rem if *ty == Ty::I64 => (a as i64).checked_rem(b as i64).map(|v| v as u64),
+        BinaryOp::Div => a.checked_div(b),
+        BinaryOp::Rem => a.checked_rem(b),
+        _ => unreachable!("only arithmetic operators are evaluated here"),
+    }
+}
+
+fn from_json(arg: &Json, ty: &Ty) -> Result<Value> {
+    let value = match ty {
+        Ty::U64 | Ty::Address => arg.as_u64().map(Value::Int),
+        Ty::I64 => arg.as_i64().map(|v| Value::Int(v as u64)),
+        Ty::Bool => arg.as_bool().map(Value::Bool),
+        Ty::String => arg.as_str().map(|v| Value::Str(v.to_string())),
+        Ty::Map => arg.as_object().and_then(|entries| {
+            let entries = entries.iter().map(|(key, value)| Some((key.clone(), value.as_u64()?)));
+            entries.collect::<Option<_>>().map(Value::Map)
+        }),
+        Ty::Array { element, len } => match arg.as_array() {
+            Some(elements) if elements.len() as u64 == *len => Some(Value::Array(
+                elements.iter().map(|e| from_json(e, element)).collect::<Result<_>>()?,
+            )),
+            _ => None,
+        },
+        Ty::Unit => None,
+    };
+    value.ok_or_else(|| {
+        SystemError::validation("args", format!("expected a `{ty}` value"), Some(arg.to_string()))
+    })
+}
+
+fn to_json(value: &Value, ty: &Ty) -> Json {
+    match (value, ty) {
+        (Value::Int(v), Ty::I64) => Json::from(*v as i64),
+        (Value::Int(v), _) => Json::from(*v),
+        (Value::Bool(v), _) => Json::Bool(*v),
+        (Value::Str(v), _) => Json::String(v.clone()),
+        (Value::Map(entries), _) => {
+            entries.iter().map(|(key, value)| (key.clone(), Json::from(*value))).collect()
+        },
+        (Value::Array(elements), Ty::Array { element, .. }) => {
+            elements.iter().map(|e| to_json(e, element)).collect()
+        },
+        _ => Json::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{CompilerConfig, ContractCompiler};
+
+    fn load(source: &str) -> Interpreter {
+        ContractCompiler::new(CompilerConfig::default()).unwrap().interpret(source).unwrap()
+    }
+
+    #[test]
+    fn test_call_semantics() {
+        let mut contract = load(
+            r#"
+            contract Counter {
+                state { total: u64; slots: [[i64; 2]; 2]; }
+                event Added { by: address, amount: u64 }
+
+                fn add(amount: u64) -> u64 {
+                    self.total = self.total + amount;
+                    require(self.total < 100, "too much");
+                    emit Added { by: caller(), amount: amount };
+                    return self.total;
+                }
+
+                fn put(i: u64, j: u64, value: i64) -> i64 {
+                    self.slots[i][j] = value;
+                    return self.slots[i][j] / -2;
+                }
+
+                fn ratio(a: u64, b: u64) -> u64 { return a / b; }
+                fn lazy(a: u64) -> bool { return a == 0 || 10 / a > 1; }
+            }
+            "#,
+        );
+        contract.set_context(CallContext { caller: 7, now: 0 });
+        assert_eq!(contract.call("add", json!([40])).unwrap(), json!(40));
+        assert_eq!(contract.call("add", json!([2])).unwrap(), json!(42));
+        let events = contract.take_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].field("by"), Some(&json!(7)));
+
+        // A failed call rolls back its state and events
+        let err = contract.call("add", json!([60])).unwrap_err().to_string();
+        assert!(err.contains("`add` failed at 8:21: reverted: too much"), "{err}");
+        assert!(contract.take_events().is_empty());
+        assert_eq!(contract.call("add", json!([0])).unwrap(), json!(42));
+
+        assert_eq!(contract.call("put", json!([1, 0, -9])).unwrap(), json!(4));
+        let err = contract.call("put", json!([0, 2, 1])).unwrap_err().to_string();
+        assert!(err.contains("index 2 out of bounds for length 2"), "{err}");
+        let err = contract.call("ratio", json!([1, 0])).unwrap_err().to_string();
+        assert!(err.contains("division by zero or overflow"), "{err}");
+        assert_eq!(contract.call("lazy", json!([0])).unwrap(), json!(true));
+
+        assert!(contract.call("missing", Json::Null).is_err());
+        assert!(contract.call("add", json!([-1])).is_err());
+        assert!(contract.call("add", json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_call_depth() {
+        let mut contract = load(
+            "contract Loop { fn down(n: u64) -> u64 { if n == 0 { return 0; } return down(n - 1); } }",
+        );
+        let depth = MAX_CALL_DEPTH as u64 - 1;
+        assert_eq!(contract.call("down", json!([depth])).unwrap(), json!(0));
+        let err = contract.call("down", json!([depth + 1])).unwrap_err().to_string();
+        assert!(err.contains("calls nest deeper than 256"), "{err}");
+    }
+}
@@ -0,0 +1,486 @@
+//! Lexer module
+//!
+//! Splits contract source into [`Token`]s. Whitespace and `//` comments
+//! separate tokens and are dropped; [`tokenize_with_comments`] keeps the
+//! comments for tools that print source back.
+
+use std::fmt;
+
+use crate::ast::{Position, Span};
+use crate::error::CompileError;
+
+/// Kinds of token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Name that is not a keyword
+    Ident(String),
+    /// Unsigned integer literal
+    Int(u64),
+    /// String literal, escapes resolved
+    Str(String),
+    /// `contract`
+    Contract,
+    /// `state`
+    State,
+    /// `event`
+    Event,
+    /// `emit`
+    Emit,
+    /// `fn`
+    Fn,
+    /// `let`
+    Let,
+    /// `if`
+    If,
+    /// `else`
+    Else,
+    /// `require`
+    Require,
+    /// `assert`
+    Assert,
+    /// `return`
+    Return,
+    /// `import`
+    Import,
+    /// `pub`
+    Pub,
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// `{`
+    LBrace,
+    /// `}`
+    RBrace,
+    /// `[`
+    LBracket,
+    /// `]`
+    RBracket,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `:`
+    Colon,
+    /// `::`
+    ColonColon,
+    /// `;`
+    Semi,
+    /// `,`
+    Comma,
+    /// `.`
+    Dot,
+    /// `->`
+    Arrow,
+    /// `=`
+    Assign,
+    /// `==`
+    EqEq,
+    /// `!=`
+    NotEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Star,
+    /// `/`
+    Slash,
+    /// `%`
+    Percent,
+    /// `!`
+    Bang,
+    /// `&&`
+    AndAnd,
+    /// `||`
+    OrOr,
+    /// `#[`, opening an attribute
+    HashBracket,
+    /// End of the source
+    Eof,
+}
+
+impl TokenKind {
+    fn keyword(word: &str) -> Option<Self> {
+        Some(match word {
+            "contract" => Self::Contract,
+            "state" => Self::State,
+            "event" => Self::Event,
+            "emit" => Self::Emit,
+            "fn" => Self::Fn,
+            "let" => Self::Let,
+            "if" => Self::If,
+            "else" => Self::Else,
+            "require" => Self::Require,
+            "assert" => Self::Assert,
+            "return" => Self::Return,
+            "import" => Self::Import,
+            "pub" => Self::Pub,
+            "true" => Self::True,
+            "false" => Self::False,
+            _ => return None,
+        })
+    }
+
+    /// Source text of keywords and punctuation
+    pub fn text(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::Ident(_) | Self::Int(_) | Self::Str(_) | Self::Eof => return None,
+            Self::Contract => "contract",
+            Self::State => "state",
+            Self::Event => "event",
+            Self::Emit => "emit",
+            Self::Fn => "fn",
+            Self::Let => "let",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::Require => "require",
+            Self::Assert => "assert",
+            Self::Return => "return",
+            Self::Import => "import",
+            Self::Pub => "pub",
+            Self::True => "true",
+            Self::False => "false",
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::LBracket => "[",
+            Self::RBracket => "]",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::Colon => ":",
+            Self::ColonColon => "::",
+            Self::Semi => ";",
+            Self::Comma => ",",
+            Self::Dot => ".",
+            Self::Arrow => "->",
+            Self::Assign => "=",
+            Self::EqEq => "==",
+            Self::NotEq => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::Bang => "!",
+            Self::AndAnd => "&&",
+            Self::OrOr => "||",
+            Self::HashBracket => "#[",
+        })
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ident(name) => write!(f, "identifier `{name}`"),
+            Self::Int(value) => write!(f, "integer `{value}`"),
+            Self::Str(value) => write!(f, "string {value:?}"),
+            Self::Eof => f.write_str("end of input"),
+            other => write!(f, "`{}`", other.text().unwrap_or_default()),
+        }
+    }
+}
+
+/// A token and where it appears
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// What the token is
+    pub kind: TokenKind,
+    /// Where it appears
+    pub span: Span,
+}
+
+/// A `//` comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// Comment text from the `//`, without trailing whitespace
+    pub text: String,
+    /// Where it appears
+    pub span: Span,
+}
+
+/// Tokenize `source`; the result always ends with an `Eof` token
+pub fn tokenize(source: &str) -> Result<Vec<Token>, CompileError> {
+    Ok(tokenize_with_comments(source)?.0)
+}
+
+/// Tokenize `source`, also returning its comments in source order
+pub fn tokenize_with_comments(source: &str) -> Result<(Vec<Token>, Vec<Comment>), CompileError> {
+    lex(source, usize::MAX)
+}
+
+/// Tokenize `source` like [`tokenize`], failing once it has more than
+/// `max_tokens` tokens besides `Eof`
+pub fn tokenize_limited(source: &str, max_tokens: usize) -> Result<Vec<Token>, CompileError> {
+    Ok(lex(source, max_tokens)?.0)
+}
+
+fn lex(source: &str, max_tokens: usize) -> Result<(Vec<Token>, Vec<Comment>), CompileError> {
+    let mut lexer = Lexer {
+        source,
+        position: Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        },
+        comments: Vec::new(),
+    };
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token()?;
+        let done = token.kind == TokenKind::Eof;
+        if !done && tokens.len() == max_tokens {
+            return Err(CompileError {
+                span: token.span,
+                found: format!("token past the limit of {max_tokens}"),
+                expected: Vec::new(),
+            });
+        }
+        tokens.push(token);
+        if done {
+            return Ok((tokens, lexer.comments));
+        }
+    }
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    position: Position,
+    comments: Vec<Comment>,
+}
+
+impl Lexer<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position.offset..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.source[self.position.offset..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position.offset += c.len_utf8();
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn bump_while(&mut self, accept: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&accept) {
+            self.bump();
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            self.bump_while(char::is_whitespace);
+            if self.peek() == Some('/') && self.peek_second() == Some('/') {
+                let start = self.position;
+                self.bump_while(|c| c != '\n');
+                let text = self.source[start.offset..self.position.offset].trim_end();
+                self.comments.push(Comment {
+                    text: text.to_string(),
+                    span: self.span_from(start),
+                });
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn span_from(&self, start: Position) -> Span {
+        Span {
+            start,
+            end: self.position,
+        }
+    }
+
+    fn error(&self, start: Position, found: String, expected: &[&str]) -> CompileError {
+        CompileError {
+            span: self.span_from(start),
+            found,
+            expected: expected.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, CompileError> {
+        self.skip_trivia();
+        let start = self.position;
+        let Some(c) = self.bump() else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                span: self.span_from(start),
+            });
+        };
+
+        let kind = match c {
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let word = &self.source[start.offset..self.position.offset];
+                TokenKind::keyword(word).unwrap_or_else(|| TokenKind::Ident(word.to_string()))
+            },
+            c if c.is_ascii_digit() => {
+                self.bump_while(|c| c.is_ascii_digit());
+                let digits = &self.source[start.offset..self.position.offset];
+                let value = digits.parse().map_err(|_| {
+                    self.error(
+                        start,
+                        format!("integer `{digits}`"),
+                        &["integer up to 18446744073709551615"],
+                    )
+                })?;
+                TokenKind::Int(value)
+            },
+            '"' => TokenKind::Str(self.string_literal(start)?),
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ':' => self.pair(':', TokenKind::ColonColon, TokenKind::Colon),
+            ';' => TokenKind::Semi,
+            ',' => TokenKind::Comma,
+            '.' => TokenKind::Dot,
+            '+' => TokenKind::Plus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '-' => self.pair('>', TokenKind::Arrow, TokenKind::Minus),
+            '=' => self.pair('=', TokenKind::EqEq, TokenKind::Assign),
+            '!' => self.pair('=', TokenKind::NotEq, TokenKind::Bang),
+            '<' => self.pair('=', TokenKind::Le, TokenKind::Lt),
+            '>' => self.pair('=', TokenKind::Ge, TokenKind::Gt),
+            '&' if self.peek() == Some('&') => {
+                self.bump();
+                TokenKind::AndAnd
+            },
+            '|' if self.peek() == Some('|') => {
+                self.bump();
+                TokenKind::OrOr
+            },
+            '#' if self.peek() == Some('[') => {
+                self.bump();
+                TokenKind::HashBracket
+            },
+            '&' => return Err(self.error(start, "character `&`".to_string(), &["`&&`"])),
+            '|' => return Err(self.error(start, "character `|`".to_string(), &["`||`"])),
+            other => return Err(self.error(start, format!("character {other:?}"), &[])),
+        };
+        Ok(Token {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Two-character operator if `second` follows, else the one-character one
+    fn pair(&mut self, second: char, double: TokenKind, single: TokenKind) -> TokenKind {
+        if self.peek() == Some(second) {
+            self.bump();
+            double
+        } else {
+            single
+        }
+    }
+
+    fn string_literal(&mut self, start: Position) -> Result<String, CompileError> {
+        let mut value = String::new();
+        loop {
+            let escape_start = self.position;
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.bump() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('\\') => value.push('\\'),
+                    Some('"') => value.push('"'),
+                    other => {
+                        let found = other.map_or("end of input".to_string(), |c| {
+                            format!("escape `\\{c}`")
+                        });
+                        return Err(self.error(
+                            escape_start,
+                            found,
+                            &["`\\n`", "`\\t`", "`\\\\`", "`\\\"`"],
+                        ));
+                    },
+                },
+                Some('\n') | None => {
+                    return Err(self.error(
+                        start,
+                        "unterminated string".to_string(),
+                        &["closing `\"`"],
+                    ));
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source).unwrap().into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn test_tokenize_operators_and_positions() {
+        assert_eq!(
+            kinds("a->b >= 1 // done\n!= \"x\\n\""),
+            vec![
+                TokenKind::Ident("a".to_string()),
+                TokenKind::Arrow,
+                TokenKind::Ident("b".to_string()),
+                TokenKind::Ge,
+                TokenKind::Int(1),
+                TokenKind::NotEq,
+                TokenKind::Str("x\n".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+
+        assert_eq!(
+            kinds("import std::math;"),
+            vec![
+                TokenKind::Import,
+                TokenKind::Ident("std".to_string()),
+                TokenKind::ColonColon,
+                TokenKind::Ident("math".to_string()),
+                TokenKind::Semi,
+                TokenKind::Eof,
+            ]
+        );
+
+        let (tokens, comments) = tokenize_with_comments("#[x] // one\n// two  \n").unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::HashBracket);
+        let comments: Vec<_> =
+            comments.iter().map(|c| (c.text.as_str(), c.span.start.line)).collect();
+        assert_eq!(comments, [("// one", 1), ("// two", 2)]);
+
+        let tokens = tokenize("fn\n  let").unwrap();
+        assert_eq!(format!("{:?}", tokens[1].span), "2:3..2:6");
+
+        let err = tokenize("let x = 1 # 2").unwrap_err();
+        assert_eq!(err.to_string(), "1:11: unexpected character '#'");
+    }
+}
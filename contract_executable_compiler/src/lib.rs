@@ -5,22 +5,80 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
+use std::fs;
+use std::path::{Path, PathBuf};
 
+use shared_core::{ErrorCollection, Result};
+
+pub mod abi;
 pub mod api;
+pub mod ast;
+pub mod bindings;
+pub mod cache;
+pub mod codegen;
 pub mod compiler;
 pub mod config;
 pub mod core;
+pub mod diagnostic;
+pub mod error;
+pub mod format;
+pub mod fuzz;
+pub mod gas;
+pub mod hir;
+pub mod interpreter;
+pub mod lexer;
+pub mod lint;
+pub mod module;
+pub mod optimize;
 pub mod parser;
+#[cfg(feature = "wasm-backend")]
 pub mod runtime;
+pub mod rust_codegen;
+pub mod source_map;
+pub mod stdlib;
+pub mod trace;
+pub mod typeck;
+
+pub use abi::ContractAbi;
+pub use api::CheckServer;
+pub use bindings::generate_bindings;
+pub use cache::{CacheBackend, CacheStats, CompilationCache, ProjectOutput};
+pub use compiler::{CompileOutput, CompiledArtifact};
+pub use diagnostic::{Diagnostic, DiagnosticFormat, ErrorCode, Severity};
+pub use error::CompileError;
+pub use format::format_source;
+pub use gas::GasEstimate;
+pub use interpreter::{CallContext, ContractEvent, Interpreter};
+pub use lint::{Lint, LintConfig, LintLevel};
+pub use module::{FileSystemResolver, InMemoryResolver, ModuleResolver, ModuleSource, Program};
+pub use optimize::{OptLevel, OptStats, Pass};
+pub use parser::ParseLimits;
+pub use source_map::{SourceLocation, SourceMap, TrapSite};
+pub use trace::{ExecutionTrace, TraceNode, TraceSample};
+#[cfg(feature = "wasm-backend")]
+pub use runtime::{ContractExecutor, ContractInstance, ContractState, ExecutionOutcome};
 
 /// Compiler configuration
 #[derive(Debug, Clone)]
 pub struct CompilerConfig {
     /// Target compilation backend
     pub target: CompilationTarget,
-    /// Enable optimizations
-    pub optimize: bool,
+    /// Optimization passes to run
+    pub opt_level: OptLevel,
+    /// Passes to run instead of those of `opt_level`
+    pub passes: Option<Vec<Pass>>,
+    /// Module wrapping generated Rust code, by default the contract name
+    /// in snake case
+    pub module_name: Option<String>,
+    /// Directories imports are looked up in, after the directory of the
+    /// importing file
+    pub search_paths: Vec<PathBuf>,
+    /// Where [`ContractCompiler::compile_project`] caches its work
+    pub cache: CacheBackend,
+    /// Lint levels; a denied lint fails compilation
+    pub lints: LintConfig,
+    /// Bounds on the size and nesting of every source file compiled
+    pub limits: ParseLimits,
 }
 
 /// Compilation target
@@ -36,7 +94,13 @@ impl Default for CompilerConfig {
     fn default() -> Self {
         Self {
             target: CompilationTarget::Rust,
-            optimize: true,
+            opt_level: OptLevel::default(),
+            passes: None,
+            module_name: None,
+            search_paths: Vec::new(),
+            cache: CacheBackend::default(),
+            lints: LintConfig::default(),
+            limits: ParseLimits::default(),
         }
     }
 }
@@ -44,18 +108,201 @@ impl Default for CompilerConfig {
 /// Contract compiler (placeholder)
 pub struct ContractCompiler {
     config: CompilerConfig,
+    resolver: Box<dyn ModuleResolver + Send + Sync>,
+    cache: CompilationCache,
 }
 
 impl ContractCompiler {
     /// Create a new compiler
+    ///
+    /// Imports are resolved by a [`FileSystemResolver`] over the configured
+    /// search paths. Fails if the configured cache cannot be opened.
     pub fn new(config: CompilerConfig) -> Result<Self> {
-        Ok(Self { config })
+        let resolver = Box::new(FileSystemResolver::new(config.search_paths.clone()));
+        let cache = CompilationCache::new(&config.cache)?;
+        Ok(Self {
+            config,
+            resolver,
+            cache,
+        })
+    }
+
+    /// Resolve imports with `resolver` instead of from the file system
+    pub fn with_resolver(mut self, resolver: impl ModuleResolver + Send + Sync + 'static) -> Self {
+        self.resolver = Box::new(resolver);
+        self
     }
 
     /// Compile contract from source
-    pub fn compile(&self, _source: &str) -> Result<String> {
-        tracing::info!("Compiling contract with target: {:?}", self.config.target);
-        Ok("// Compiled contract placeholder".to_string())
+    ///
+    /// Syntax, import and type errors, source past the configured
+    /// [`ParseLimits`] included, are `Validation` errors listing every
+    /// [`Diagnostic`]. Imports of files are looked up in the search paths.
+    pub fn compile(&self, source: &str) -> Result<CompileOutput> {
+        Ok(self.compile_with_stats(source)?.0)
+    }
+
+    /// Compile the contract in the file `root`, whose imports are looked up
+    /// next to it first
+    pub fn compile_path(&self, root: &Path) -> Result<CompileOutput> {
+        let source = fs::read_to_string(root)?;
+        let id = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let program = self.load(Some(id.display().to_string()), source)?;
+        Ok(self.compile_program(&program)?.0)
+    }
+
+    /// Compile the contract in the file `root` like
+    /// [`compile_path`](Self::compile_path), reusing the cached work of
+    /// modules unchanged since an earlier call
+    ///
+    /// Only modules whose source changed, and the modules importing them,
+    /// are checked again; see the [`cache`] module for what is reused.
+    pub fn compile_project(&self, root: &Path) -> Result<ProjectOutput> {
+        let source = fs::read_to_string(root)?;
+        let id = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let mut cache = CacheStats::default();
+        let file = Some(id.display().to_string());
+        let program = self.cache.load(file, source, &*self.resolver, &self.config, &mut cache)?;
+        self.lint_program(&program)?;
+        let (output, opt_stats) = self.cache.compile(&program, &self.config, &mut cache)?;
+        tracing::info!("Compiled {} with cache: {:?}", root.display(), cache);
+        Ok(ProjectOutput {
+            output,
+            opt_stats,
+            cache,
+        })
+    }
+
+    /// Parse, type check and lint contract source without generating code
+    ///
+    /// Returns every error and warning, ordered by file and position; a
+    /// syntax or import error stops checking, so it is reported without
+    /// type errors or lints.
+    pub fn check(&self, source: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = match self.load(None, source.to_string()) {
+            Ok(program) => {
+                let (checked, mut diagnostics) = typeck::check_program(&program);
+                if let Err(errors) = checked {
+                    diagnostics.extend(errors);
+                }
+                diagnostics.extend(lint::lint_program(&program, &self.config.lints));
+                diagnostics
+            },
+            Err(errors) => errors.into_iter().collect(),
+        };
+        diagnostic::sort(&mut diagnostics);
+        diagnostics
+    }
+
+    /// [`check`](Self::check) the contract `source` of the module `file`
+    /// through the compilation cache, as editors do on every change
+    ///
+    /// Also returns the loaded program, unless it failed to load, and its
+    /// unoptimized IR if it checks.
+    pub(crate) fn check_cached(
+        &self,
+        file: Option<String>,
+        source: String,
+        stats: &mut CacheStats,
+    ) -> (Option<(Program, Option<hir::Contract>)>, Vec<Diagnostic>) {
+        let program = match self.cache.load(file, source, &*self.resolver, &self.config, stats) {
+            Ok(program) => program,
+            Err(errors) => return (None, errors.into_iter().collect()),
+        };
+        let (contract, mut diagnostics) = self.cache.check(&program, &self.config, stats);
+        diagnostics.extend(lint::lint_program(&program, &self.config.lints));
+        diagnostic::sort(&mut diagnostics);
+        (Some((program, contract)), diagnostics)
+    }
+
+    /// Compile contract from source, also reporting what the optimizer did
+    pub fn compile_with_stats(&self, source: &str) -> Result<(CompileOutput, OptStats)> {
+        let program = self.load(None, source.to_string())?;
+        self.compile_program(&program)
+    }
+
+    /// Format contract or library source
+    ///
+    /// The result is the source in canonical layout, comments kept; see the
+    /// [`format`] module. Syntax errors are `Validation` errors.
+    pub fn format(&self, source: &str) -> Result<String> {
+        parser::parse_file_with(source, &self.config.limits)?;
+        Ok(format::format_source(source)?)
+    }
+
+    /// Parse `source` and load its imports within the configured limits
+    fn load(
+        &self,
+        file: Option<String>,
+        source: String,
+    ) -> std::result::Result<Program, ErrorCollection<Diagnostic>> {
+        let limits = self.config.limits;
+        let mut parse = |_: Option<&str>, source: &str| parser::parse_file_with(source, &limits);
+        Program::load_with(file, source, &*self.resolver, &mut parse)
+    }
+
+    /// Fail with the denied lints of `program`, tracing the others
+    fn lint_program(&self, program: &Program) -> Result<()> {
+        let (errors, warnings): (Vec<_>, _) = lint::lint_program(program, &self.config.lints)
+            .into_iter()
+            .partition(|diagnostic| diagnostic.severity == Severity::Error);
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(errors.into_iter().collect::<ErrorCollection<_>>().into())
+    }
+
+    /// Type check and optimize contract source for the
+    /// [`Interpreter`], which runs it without generating code
+    ///
+    /// Fails like [`compile`](Self::compile).
+    pub fn interpret(&self, source: &str) -> Result<Interpreter> {
+        let program = self.load(None, source.to_string())?;
+        Ok(Interpreter::new(self.lower_program(&program)?.0))
+    }
+
+    /// Lint, type check and optimize `program`
+    fn lower_program(&self, program: &Program) -> Result<(hir::Contract, OptStats)> {
+        self.lint_program(program)?;
+        let (checked, warnings) = typeck::check_program(program);
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+        let mut contract = checked?;
+        let passes = match &self.config.passes {
+            Some(passes) => passes.as_slice(),
+            None => self.config.opt_level.passes(),
+        };
+        let stats = optimize::optimize(&mut contract, passes);
+        tracing::debug!("Optimized contract {}: {:?}", contract.name, stats);
+        Ok((contract, stats))
+    }
+
+    fn compile_program(&self, program: &Program) -> Result<(CompileOutput, OptStats)> {
+        let (contract, stats) = self.lower_program(program)?;
+        tracing::info!(
+            "Compiling contract {} with target: {:?}",
+            contract.name,
+            self.config.target
+        );
+        let output = match self.config.target {
+            CompilationTarget::Rust => CompileOutput::RustSource(rust_codegen::generate_rust(
+                &contract,
+                self.config.module_name.as_deref(),
+            )?),
+            CompilationTarget::Wasm => {
+                CompileOutput::WasmBytes(codegen::generate_wasm(&contract)?)
+            },
+        };
+        Ok((output, stats))
+    }
+
+    /// Estimate the gas budget needed to execute a compiled artifact
+    pub fn estimate_gas(&self, artifact: &CompiledArtifact) -> Result<GasEstimate> {
+        gas::estimate_gas(artifact)
     }
 }
 
@@ -75,6 +322,59 @@ mod tests {
         let config = CompilerConfig::default();
         let compiler = ContractCompiler::new(config).unwrap();
         let result = compiler.compile("contract Test {}");
-        assert!(result.is_ok());
+        let Ok(CompileOutput::RustSource(source)) = result else {
+            panic!("expected Rust source, got {result:?}");
+        };
+        assert!(source.contains("pub mod test {"));
+        assert!(source.contains("pub struct Test {}"));
+
+        let err = compiler.compile("contract Test {").unwrap_err();
+        assert!(matches!(err, shared_core::SystemError::Validation { .. }));
+    }
+
+    #[test]
+    fn test_wasm_compilation() {
+        let config = CompilerConfig {
+            target: CompilationTarget::Wasm,
+            ..CompilerConfig::default()
+        };
+        let compiler = ContractCompiler::new(config).unwrap();
+        let output = compiler.compile("contract Test { fn ping() -> bool { return true; } }");
+        let Ok(CompileOutput::WasmBytes(module)) = output else {
+            panic!("expected a wasm module, got {output:?}");
+        };
+        let estimate = compiler.estimate_gas(&CompileOutput::WasmBytes(module).into()).unwrap();
+        assert!(estimate.per_call_estimates.contains_key("ping"));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        // As deep as the default limit allows, which every pass handles
+        let parens = parser::DEFAULT_MAX_DEPTH - 2;
+        let source = format!(
+            "contract Deep {{ fn f() -> u64 {{ return {}1{}; }} }}",
+            "(".repeat(parens),
+            ")".repeat(parens)
+        );
+        for target in [CompilationTarget::Rust, CompilationTarget::Wasm] {
+            let config = CompilerConfig {
+                target,
+                ..CompilerConfig::default()
+            };
+            ContractCompiler::new(config).unwrap().compile(&source).unwrap();
+        }
+
+        let compiler = ContractCompiler::new(CompilerConfig {
+            limits: ParseLimits {
+                max_source_bytes: 64,
+                ..ParseLimits::default()
+            },
+            ..CompilerConfig::default()
+        })
+        .unwrap();
+        let err = compiler.compile(&source).unwrap_err();
+        assert!(matches!(err, shared_core::SystemError::Validation { .. }), "{err}");
+        assert_eq!(compiler.check(&source)[0].code, ErrorCode::SyntaxError);
+        assert!(compiler.format(&source).is_err());
     }
 }
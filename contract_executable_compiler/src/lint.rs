@@ -0,0 +1,924 @@
+//! Lint module
+//!
+//! Style checks on a loaded [`Program`], reported as [`Diagnostic`]s with
+//! their own codes:
+//!
+//! - `unused_state`: a state variable no function refers to
+//! - `shadowed_name`: a `let` or parameter named like a local of an
+//!   enclosing scope, a function of its module or a builtin
+//! - `long_function`: a function with more statements, nested ones
+//!   included, than [`LintConfig::max_statements`]
+//! - `magic_number`: an integer literal other than 0 or 1 in a `require`
+//!   condition
+//! - `missing_pub`: a library function another module calls without it
+//!   being `pub`, reported at its declaration
+//! - `reentrancy`: a call into an imported module, other than the standard
+//!   library, followed in source order by an update of contract state
+//! - `integer_overflow`: `+`, `-` or `*` on values that are not both
+//!   literals, which wraps on overflow, instead of a `std::math` checked
+//!   operation
+//! - `unauthorized_transfer`: a call to a function named `transfer` or
+//!   `transfer_*` not preceded by a `require` or `assert` comparing
+//!   `caller()`, nor inside an `if` comparing it
+//!
+//! Lints warn unless [`LintConfig`] allows or denies them; a denied lint is
+//! an error. `#[allow(name, ...)]` on a contract, state variable, event or
+//! function allows lints within it. Library modules of the standard library
+//! are not linted.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{BinaryOp, Block, Expr, ExprKind, Function, Ident, Span, Stmt, StmtKind};
+use crate::diagnostic::{self, Diagnostic, ErrorCode, Severity};
+use crate::hir::Builtin;
+use crate::module::{Module, Program};
+
+/// Default of [`LintConfig::max_statements`]
+pub const DEFAULT_MAX_STATEMENTS: usize = 40;
+
+/// A style check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A state variable no function refers to
+    UnusedState,
+    /// A name hiding another one
+    ShadowedName,
+    /// A function over the statement limit
+    LongFunction,
+    /// An unnamed constant in a `require` condition
+    MagicNumber,
+    /// A library function called from another module but not `pub`
+    MissingPub,
+    /// A state update after a call into another module
+    Reentrancy,
+    /// Arithmetic that wraps on overflow
+    IntegerOverflow,
+    /// A transfer anyone may trigger
+    UnauthorizedTransfer,
+}
+
+impl Lint {
+    /// Every lint
+    pub const ALL: [Lint; 8] = [
+        Self::UnusedState,
+        Self::ShadowedName,
+        Self::LongFunction,
+        Self::MagicNumber,
+        Self::MissingPub,
+        Self::Reentrancy,
+        Self::IntegerOverflow,
+        Self::UnauthorizedTransfer,
+    ];
+
+    /// Name used in `#[allow(...)]`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnusedState => "unused_state",
+            Self::ShadowedName => "shadowed_name",
+            Self::LongFunction => "long_function",
+            Self::MagicNumber => "magic_number",
+            Self::MissingPub => "missing_pub",
+            Self::Reentrancy => "reentrancy",
+            Self::IntegerOverflow => "integer_overflow",
+            Self::UnauthorizedTransfer => "unauthorized_transfer",
+        }
+    }
+
+    /// The lint named `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+
+    /// Code of the diagnostics it reports
+    pub fn code(self) -> ErrorCode {
+        match self {
+            Self::UnusedState => ErrorCode::UnusedState,
+            Self::ShadowedName => ErrorCode::ShadowedName,
+            Self::LongFunction => ErrorCode::LongFunction,
+            Self::MagicNumber => ErrorCode::MagicNumber,
+            Self::MissingPub => ErrorCode::MissingPub,
+            Self::Reentrancy => ErrorCode::Reentrancy,
+            Self::IntegerOverflow => ErrorCode::IntegerOverflow,
+            Self::UnauthorizedTransfer => ErrorCode::UnauthorizedTransfer,
+        }
+    }
+}
+
+/// What a lint finding becomes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Nothing
+    Allow,
+    /// A warning
+    #[default]
+    Warn,
+    /// An error, failing compilation
+    Deny,
+}
+
+/// Lint configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    /// Level of each lint set explicitly; the others warn
+    pub levels: HashMap<Lint, LintLevel>,
+    /// Most statements a function may have before `long_function` reports it
+    pub max_statements: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            levels: HashMap::new(),
+            max_statements: DEFAULT_MAX_STATEMENTS,
+        }
+    }
+}
+
+impl LintConfig {
+    /// Set the level of `lint`
+    pub fn with_level(mut self, lint: Lint, level: LintLevel) -> Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    /// Level of `lint`
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels.get(&lint).copied().unwrap_or_default()
+    }
+}
+
+/// Lint every module of `program` but those of the standard library
+///
+/// The diagnostics are ordered by file and position.
+pub fn lint_program(program: &Program, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        config,
+        findings: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    let modules = program.modules();
+    let linted = |module: &Module| !module.file.as_deref().is_some_and(is_std);
+    let mut called = HashSet::new();
+    for (index, module) in modules.iter().enumerate().filter(|(_, module)| linted(module)) {
+        linter.module(index, module, modules);
+        // Calls from this module to private functions of the ones it imports
+        for (alias, &imported) in &module.imports {
+            if !linted(&modules[imported]) {
+                continue;
+            }
+            for function in &modules[imported].ast.functions {
+                let name = &function.name.name;
+                if !function.public
+                    && module_calls(module, &format!("{alias}::{name}"))
+                    && called.insert((imported, name))
+                {
+                    linter.find(
+                        Lint::MissingPub,
+                        imported,
+                        Diagnostic::warning(
+                            Lint::MissingPub.code(),
+                            function.name.span,
+                            format!("function `{name}` is called from another module"),
+                        )
+                        .with_help(format!("declare it `pub fn {name}`")),
+                    );
+                }
+            }
+        }
+    }
+    linter.finish(modules)
+}
+
+/// A lint finding in module `module`, before levels and attributes apply
+struct Finding {
+    lint: Lint,
+    module: usize,
+    diagnostic: Diagnostic,
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    findings: Vec<Finding>,
+    /// Diagnostics about the attributes themselves
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn find(&mut self, lint: Lint, module: usize, diagnostic: Diagnostic) {
+        if self.config.level(lint) != LintLevel::Allow {
+            self.findings.push(Finding {
+                lint,
+                module,
+                diagnostic,
+            });
+        }
+    }
+
+    fn module(&mut self, index: usize, module: &Module, modules: &[Module]) {
+        let functions: Vec<&Function> = match &module.ast.contract {
+            Some(contract) => contract.functions.iter().collect(),
+            None => module.ast.functions.iter().collect(),
+        };
+        if let Some(contract) = &module.ast.contract {
+            for field in &contract.state {
+                let name = &field.name.name;
+                if !functions.iter().any(|function| refers_to_state(&function.body, name)) {
+                    self.find(
+                        Lint::UnusedState,
+                        index,
+                        Diagnostic::warning(
+                            Lint::UnusedState.code(),
+                            field.name.span,
+                            format!("state variable `{name}` is never used"),
+                        )
+                        .with_help("remove it, or use it in a function"),
+                    );
+                }
+            }
+        }
+        let names: HashMap<&str, Span> =
+            functions.iter().map(|f| (f.name.name.as_str(), f.name.span)).collect();
+        let external: HashSet<&str> = module
+            .imports
+            .iter()
+            .filter(|(_, &imported)| !modules[imported].file.as_deref().is_some_and(is_std))
+            .map(|(alias, _)| alias.as_str())
+            .collect();
+        for function in &functions {
+            self.function(index, function, &names);
+            self.reentrancy(index, function, &external);
+            self.integer_overflow(index, function);
+            self.unauthorized_transfer(index, function);
+        }
+    }
+
+    /// Report the first call into an `external` module followed by a state
+    /// update
+    fn reentrancy(&mut self, module: usize, function: &Function, external: &HashSet<&str>) {
+        let mut call = None;
+        let mut found = None;
+        each_statement(&function.body, &mut |stmt| {
+            if found.is_some() {
+                return;
+            }
+            if call.is_none() {
+                each_statement_expr(stmt, &mut |expr| {
+                    if call.is_none() && calls_module(expr, external) {
+                        call = Some(expr.span);
+                    }
+                });
+            }
+            if let (Some(call), StmtKind::Assign { target, .. }) = (call, &stmt.kind) {
+                if assigns_state(target) {
+                    found = Some((call, stmt.span));
+                }
+            }
+        });
+        let Some((call, update)) = found else {
+            return;
+        };
+        self.find(
+            Lint::Reentrancy,
+            module,
+            Diagnostic::warning(
+                Lint::Reentrancy.code(),
+                call,
+                format!(
+                    "function `{}` updates state after calling another module",
+                    function.name.name
+                ),
+            )
+            .with_label(update, "state updated here")
+            .with_help("update state before calling other modules"),
+        );
+    }
+
+    fn integer_overflow(&mut self, module: usize, function: &Function) {
+        let mut unchecked = Vec::new();
+        each_statement(&function.body, &mut |stmt| {
+            each_statement_expr(stmt, &mut |expr| {
+                let ExprKind::Binary { op, lhs, rhs } = &expr.kind else {
+                    return;
+                };
+                let (symbol, checked) = match op {
+                    BinaryOp::Add => ("+", "checked_add"),
+                    BinaryOp::Sub => ("-", "checked_sub"),
+                    BinaryOp::Mul => ("*", "checked_mul"),
+                    _ => return,
+                };
+                let literal = |expr: &Expr| matches!(expr.kind, ExprKind::Int(_));
+                if !(literal(lhs) && literal(rhs)) {
+                    unchecked.push((expr.span, symbol, checked));
+                }
+            });
+        });
+        for (span, op, checked) in unchecked {
+            self.find(
+                Lint::IntegerOverflow,
+                module,
+                Diagnostic::warning(
+                    Lint::IntegerOverflow.code(),
+                    span,
+                    format!("`{op}` wraps on overflow"),
+                )
+                .with_help(format!("import `std::math` and call `math::{checked}`")),
+            );
+        }
+    }
+
+    fn unauthorized_transfer(&mut self, module: usize, function: &Function) {
+        let mut transfers = Vec::new();
+        unguarded_transfers(&function.body, false, &mut transfers);
+        for span in transfers {
+            self.find(
+                Lint::UnauthorizedTransfer,
+                module,
+                Diagnostic::warning(
+                    Lint::UnauthorizedTransfer.code(),
+                    span,
+                    "transfer is not guarded by a check of the caller",
+                )
+                .with_help("check the caller first, as in `require(caller() == self.owner);`"),
+            );
+        }
+    }
+
+    fn function(&mut self, module: usize, function: &Function, functions: &HashMap<&str, Span>) {
+        let name = &function.name.name;
+        let statements = count_statements(&function.body);
+        if statements > self.config.max_statements {
+            self.find(
+                Lint::LongFunction,
+                module,
+                Diagnostic::warning(
+                    Lint::LongFunction.code(),
+                    function.name.span,
+                    format!(
+                        "function `{name}` has {statements} statements, more than {}",
+                        self.config.max_statements
+                    ),
+                )
+                .with_help("split it into smaller functions"),
+            );
+        }
+
+        let mut scopes = Scopes {
+            functions,
+            scopes: vec![HashMap::new()],
+            found: Vec::new(),
+        };
+        for param in &function.params {
+            scopes.declare(&param.name);
+        }
+        scopes.block(&function.body);
+        for diagnostic in scopes.found {
+            self.find(Lint::ShadowedName, module, diagnostic);
+        }
+
+        let mut literals = Vec::new();
+        each_statement(&function.body, &mut |stmt| {
+            if let StmtKind::Require { condition, .. } = &stmt.kind {
+                each_expr(condition, &mut |expr| {
+                    if matches!(expr.kind, ExprKind::Int(value) if value > 1) {
+                        literals.push(expr.clone());
+                    }
+                });
+            }
+        });
+        for literal in literals {
+            let ExprKind::Int(value) = literal.kind else {
+                continue;
+            };
+            self.find(
+                Lint::MagicNumber,
+                module,
+                Diagnostic::warning(
+                    Lint::MagicNumber.code(),
+                    literal.span,
+                    format!("magic number `{value}` in a `require` condition"),
+                )
+                .with_help("name it with a `let` binding or a state variable"),
+            );
+        }
+    }
+
+    /// Apply attributes and levels to the findings
+    fn finish(mut self, modules: &[Module]) -> Vec<Diagnostic> {
+        let allowed: Vec<Vec<(Lint, Span)>> =
+            modules.iter().map(|module| self.allowed(module)).collect();
+        for finding in self.findings {
+            let Finding {
+                lint,
+                module,
+                diagnostic,
+            } = finding;
+            let start = diagnostic.span.start.offset;
+            if allowed[module].iter().any(|&(allowed, span)| {
+                allowed == lint && span.start.offset <= start && start < span.end.offset
+            }) {
+                continue;
+            }
+            let diagnostic = match self.config.level(lint) {
+                LintLevel::Deny => Diagnostic {
+                    severity: Severity::Error,
+                    ..diagnostic
+                },
+                _ => diagnostic,
+            };
+            self.diagnostics.push(diagnostic.in_file(modules[module].file.clone()));
+        }
+        diagnostic::sort(&mut self.diagnostics);
+        self.diagnostics
+    }
+
+    /// Lints allowed by the attributes of `module`, with the spans they
+    /// apply to, warning about unknown names
+    fn allowed(&mut self, module: &Module) -> Vec<(Lint, Span)> {
+        let mut attributed: Vec<(&[Ident], Span)> = Vec::new();
+        if let Some(contract) = &module.ast.contract {
+            attributed.push((&contract.allow, contract.span));
+            attributed.extend(contract.state.iter().map(|field| (&*field.allow, field.span)));
+            attributed.extend(contract.events.iter().map(|event| (&*event.allow, event.span)));
+            attributed.extend(contract.functions.iter().map(|f| (&*f.allow, f.span)));
+        }
+        attributed.extend(module.ast.functions.iter().map(|f| (&*f.allow, f.span)));
+
+        let mut allowed = Vec::new();
+        for (names, span) in attributed {
+            for name in names {
+                match Lint::from_name(&name.name) {
+                    Some(lint) => allowed.push((lint, span)),
+                    None => self.diagnostics.push(
+                        Diagnostic::warning(
+                            ErrorCode::UnknownLint,
+                            name.span,
+                            format!("unknown lint `{}`", name.name),
+                        )
+                        .with_help(format!(
+                            "the lints are {}",
+                            Lint::ALL.map(Lint::name).join(", ")
+                        ))
+                        .in_file(module.file.clone()),
+                    ),
+                }
+            }
+        }
+        allowed
+    }
+}
+
+/// Names declared in the scopes of a function being walked
+struct Scopes<'a> {
+    /// Functions of the module, by name
+    functions: &'a HashMap<&'a str, Span>,
+    /// Locals of each enclosing block, innermost last
+    scopes: Vec<HashMap<String, Span>>,
+    found: Vec<Diagnostic>,
+}
+
+impl Scopes<'_> {
+    fn declare(&mut self, name: &Ident) {
+        let text = &name.name;
+        let shadowed = self.scopes[..self.scopes.len() - 1]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(text))
+            .map(|&span| (span, "declared here"))
+            .or_else(|| self.functions.get(text.as_str()).map(|&span| (span, "function here")));
+        let message = format!("`{text}` shadows an earlier declaration");
+        match shadowed {
+            Some((span, label)) => self.found.push(
+                Diagnostic::warning(ErrorCode::ShadowedName, name.span, message)
+                    .with_label(span, label),
+            ),
+            None if Builtin::ALL.iter().any(|b| b.module().is_none() && b.name() == text) => {
+                self.found.push(Diagnostic::warning(
+                    ErrorCode::ShadowedName,
+                    name.span,
+                    format!("`{text}` shadows a builtin function"),
+                ));
+            },
+            None => {},
+        }
+        let innermost = self.scopes.last_mut().expect("function scope is never empty");
+        innermost.entry(text.clone()).or_insert(name.span);
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { name, .. } => self.declare(name),
+                StmtKind::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    self.block(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.block(else_branch);
+                    }
+                },
+                _ => {},
+            }
+        }
+        self.scopes.pop();
+    }
+}
+
+/// Whether `id` is the id of a standard library module
+fn is_std(id: &str) -> bool {
+    id.starts_with("std::")
+}
+
+/// Whether `block` refers to the state variable `name`
+fn refers_to_state(block: &Block, name: &str) -> bool {
+    let mut found = false;
+    each_statement(block, &mut |stmt| {
+        each_statement_expr(stmt, &mut |expr| {
+            if let ExprKind::Field { base, field } = &expr.kind {
+                let on_self = matches!(&base.kind, ExprKind::Ident(base) if base == "self");
+                found |= on_self && field.name == name;
+            }
+        });
+    });
+    found
+}
+
+/// Whether `expr` calls a function of one of the modules `aliases`
+fn calls_module(expr: &Expr, aliases: &HashSet<&str>) -> bool {
+    let ExprKind::Call { callee, .. } = &expr.kind else {
+        return false;
+    };
+    matches!(&callee.kind, ExprKind::Ident(name)
+        if name.split_once("::").is_some_and(|(alias, _)| aliases.contains(alias)))
+}
+
+/// Whether assigning to `target` updates a state variable
+fn assigns_state(target: &Expr) -> bool {
+    match &target.kind {
+        ExprKind::Field { base, .. } => {
+            matches!(&base.kind, ExprKind::Ident(base) if base == "self")
+        },
+        ExprKind::Index { base, .. } => assigns_state(base),
+        _ => false,
+    }
+}
+
+/// Whether `expr` calls a function named `transfer` or `transfer_*`
+fn is_transfer(expr: &Expr) -> bool {
+    let ExprKind::Call { callee, .. } = &expr.kind else {
+        return false;
+    };
+    let ExprKind::Ident(path) = &callee.kind else {
+        return false;
+    };
+    let name = path.rsplit("::").next().unwrap_or(path);
+    name == "transfer" || name.starts_with("transfer_")
+}
+
+/// Whether `condition` compares `caller()` with something
+fn checks_caller(condition: &Expr) -> bool {
+    let is_caller = |expr: &Expr| {
+        matches!(&expr.kind, ExprKind::Call { callee, args }
+            if args.is_empty() && matches!(&callee.kind, ExprKind::Ident(name) if name == "caller"))
+    };
+    let mut found = false;
+    each_expr(condition, &mut |expr| {
+        if let ExprKind::Binary {
+            op: BinaryOp::Eq | BinaryOp::Ne,
+            lhs,
+            rhs,
+        } = &expr.kind
+        {
+            found |= is_caller(lhs) || is_caller(rhs);
+        }
+    });
+    found
+}
+
+/// Add the spans of the transfers in `block` not guarded by a check of the
+/// caller to `found`, the block being `guarded` already or not
+fn unguarded_transfers(block: &Block, mut guarded: bool, found: &mut Vec<Span>) {
+    for stmt in &block.statements {
+        if !guarded {
+            each_statement_expr(stmt, &mut |expr| {
+                if is_transfer(expr) {
+                    found.push(expr.span);
+                }
+            });
+        }
+        match &stmt.kind {
+            StmtKind::Require { condition, .. } | StmtKind::Assert { condition, .. } => {
+                guarded |= checks_caller(condition);
+            },
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let checked = checks_caller(condition);
+                unguarded_transfers(then_branch, guarded || checked, found);
+                if let Some(else_branch) = else_branch {
+                    unguarded_transfers(else_branch, guarded, found);
+                }
+                // `if caller() != self.owner { return; }` guards the rest
+                let returns = matches!(
+                    then_branch.statements.last(),
+                    Some(Stmt { kind: StmtKind::Return(_), .. })
+                );
+                guarded |= checked && returns;
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Whether a function of `module` calls `path`
+fn module_calls(module: &Module, path: &str) -> bool {
+    let functions = match &module.ast.contract {
+        Some(contract) => &contract.functions,
+        None => &module.ast.functions,
+    };
+    let mut found = false;
+    for function in functions {
+        each_statement(&function.body, &mut |stmt| {
+            each_statement_expr(stmt, &mut |expr| {
+                if let ExprKind::Call { callee, .. } = &expr.kind {
+                    found |= matches!(&callee.kind, ExprKind::Ident(name) if name == path);
+                }
+            });
+        });
+    }
+    found
+}
+
+/// Statements of `block`, nested ones included
+fn count_statements(block: &Block) -> usize {
+    let mut count = 0;
+    each_statement(block, &mut |_| count += 1);
+    count
+}
+
+/// Call `f` on every statement of `block`, nested ones after the statement
+/// holding them
+fn each_statement(block: &Block, f: &mut dyn FnMut(&Stmt)) {
+    for stmt in &block.statements {
+        f(stmt);
+        if let StmtKind::If {
+            then_branch,
+            else_branch,
+            ..
+        } = &stmt.kind
+        {
+            each_statement(then_branch, f);
+            if let Some(else_branch) = else_branch {
+                each_statement(else_branch, f);
+            }
+        }
+    }
+}
+
+/// Call `f` on every expression of `stmt` itself, not of nested statements
+fn each_statement_expr(stmt: &Stmt, f: &mut dyn FnMut(&Expr)) {
+    match &stmt.kind {
+        StmtKind::Let { value, .. } | StmtKind::Return(Some(value)) | StmtKind::Expr(value) => {
+            each_expr(value, f);
+        },
+        StmtKind::Assign { target, value } => {
+            each_expr(target, f);
+            each_expr(value, f);
+        },
+        StmtKind::If { condition, .. }
+        | StmtKind::Require { condition, .. }
+        | StmtKind::Assert { condition, .. } => each_expr(condition, f),
+        StmtKind::Emit { fields, .. } => {
+            for field in fields {
+                each_expr(&field.value, f);
+            }
+        },
+        StmtKind::Return(None) => {},
+    }
+}
+
+/// Call `f` on `expr` and each of its subexpressions
+fn each_expr(expr: &Expr, f: &mut dyn FnMut(&Expr)) {
+    f(expr);
+    match &expr.kind {
+        ExprKind::Unary { operand, .. } => each_expr(operand, f),
+        ExprKind::Binary { lhs, rhs, .. } => {
+            each_expr(lhs, f);
+            each_expr(rhs, f);
+        },
+        ExprKind::Call { callee, args } => {
+            each_expr(callee, f);
+            args.iter().for_each(|arg| each_expr(arg, f));
+        },
+        ExprKind::Field { base, .. } => each_expr(base, f),
+        ExprKind::Index { base, index } => {
+            each_expr(base, f);
+            each_expr(index, f);
+        },
+        ExprKind::Array(elements) => elements.iter().for_each(|element| each_expr(element, f)),
+        ExprKind::Int(_) | ExprKind::Bool(_) | ExprKind::Str(_) | ExprKind::Ident(_) => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompilerConfig, ContractCompiler, InMemoryResolver};
+
+    /// Codes and lines of the lints of `source`, whose imports are
+    /// `modules`
+    fn lints(config: &LintConfig, modules: &[(&str, &str)], source: &str) -> Vec<(String, u32)> {
+        let resolver = modules
+            .iter()
+            .fold(InMemoryResolver::new(), |resolver, (name, source)| {
+                resolver.with_module(*name, *source)
+            });
+        let program = Program::load(None, source.to_string(), &resolver).unwrap();
+        lint_program(&program, config)
+            .into_iter()
+            .map(|d| (d.code.to_string(), d.span.start.line))
+            .collect()
+    }
+
+    fn warnings(source: &str) -> Vec<(String, u32)> {
+        lints(&LintConfig::default(), &[], source)
+    }
+
+    #[test]
+    fn test_unused_state() {
+        let source = "contract C {
+            state { used: u64; written: u64; unused: u64; }
+            fn f() -> u64 { self.written = 1; return self.used; }
+        }";
+        assert_eq!(warnings(source), [("W0003".to_string(), 2)]);
+        assert!(warnings("contract C { state { a: u64; } fn f() { self.a = 2; } }").is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_name() {
+        let source = "contract C {
+            fn f(a: u64, now: u64) -> u64 {
+                let a: u64 = 1;
+                if a > 0 {
+                    let b = now;
+                    let g = b;
+                    return g;
+                }
+                let b = 2;
+                return b;
+            }
+            fn g() {}
+        }";
+        let found = warnings(source);
+        let expected = [("W0004", 2), ("W0004", 3), ("W0004", 6)];
+        assert_eq!(found, expected.map(|(code, line)| (code.to_string(), line)));
+        // Locals of sibling blocks do not shadow each other
+        let source = "contract C {
+            fn f(c: bool) { if c { let x = 1; } else { let x = 2; } }
+        }";
+        assert!(warnings(source).is_empty());
+    }
+
+    #[test]
+    fn test_long_function() {
+        let config = LintConfig {
+            max_statements: 3,
+            ..LintConfig::default()
+        };
+        let short = "contract C { fn f(c: bool) { if c { return; } else { return; } } }";
+        assert!(lints(&config, &[], short).is_empty());
+        let long = "contract C { fn f(c: bool) { if c { return; } if c { return; } } }";
+        assert_eq!(lints(&config, &[], long), [("W0005".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_magic_number() {
+        let source = "contract C {
+            fn f(a: u64) {
+                require(a > 0 && a != 1);
+                require(a < 100, \"too big\");
+                assert(a < 50);
+                let limit: u64 = 100;
+                require(a < limit);
+            }
+        }";
+        assert_eq!(warnings(source), [("W0006".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_missing_pub() {
+        let fees = "fn rate() -> u64 { return 1; }\npub fn fee() -> u64 { return 2; }";
+        let modules = [("fees", fees)];
+        let config = LintConfig::default();
+        let source = "import fees; contract C { fn f() -> u64 { return fees::rate(); } }";
+        let diagnostics = lints(&config, &modules, source);
+        assert_eq!(diagnostics, [("W0007".to_string(), 1)]);
+        let source = "import fees; contract C { fn f() -> u64 { return fees::fee(); } }";
+        assert!(lints(&config, &modules, source).is_empty());
+        // The standard library is not linted
+        let source = "import std::math; contract C { fn f() -> u64 { return math::min(1, 2); } }";
+        assert!(lints(&config, &modules, source).is_empty());
+    }
+
+    #[test]
+    fn test_reentrancy() {
+        let bank = "pub fn pay(to: address, amount: u64) -> bool { return true; }";
+        let modules = [("bank", bank)];
+        let config = LintConfig::default().with_level(Lint::IntegerOverflow, LintLevel::Allow);
+        let source = "import bank;
+        contract C {
+            state { paid: u64; }
+            fn late(to: address) {
+                let ok = bank::pay(to, 5);
+                if ok { self.paid = 5; }
+            }
+            fn early(to: address) {
+                self.paid = 5;
+                let ok = bank::pay(to, 5);
+            }
+        }";
+        assert_eq!(lints(&config, &modules, source), [("W0009".to_string(), 5)]);
+        // Standard library calls are not calls into other contracts
+        let source = "import std::math;
+        contract C { state { a: u64; } fn f() { self.a = math::checked_add(self.a, 1); } }";
+        assert!(lints(&config, &modules, source).is_empty());
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let source = "contract C {
+            state { total: u64; }
+            fn f(a: u64) -> u64 {
+                let b = 2 * 3;
+                self.total = self.total + a;
+                return a - 1 / a;
+            }
+        }";
+        assert_eq!(warnings(source), [("W0010".to_string(), 5), ("W0010".to_string(), 6)]);
+    }
+
+    #[test]
+    fn test_unauthorized_transfer() {
+        let source = "contract C {
+            state { owner: address; }
+            fn transfer_to(to: address) {}
+            fn open(to: address) { transfer_to(to); }
+            fn required(to: address) { require(caller() == self.owner); transfer_to(to); }
+            fn branch(to: address) { if self.owner == caller() { transfer_to(to); } }
+            fn early(to: address) {
+                if caller() != self.owner { return; }
+                transfer_to(to);
+            }
+            fn late(to: address) { transfer_to(to); require(caller() == self.owner); }
+            fn other(to: address) {
+                if caller() == self.owner { return; } else { transfer_to(to); }
+            }
+        }";
+        let expected = [("W0011", 4), ("W0011", 11), ("W0011", 13)];
+        assert_eq!(warnings(source), expected.map(|(code, line)| (code.to_string(), line)));
+    }
+
+    #[test]
+    fn test_allow_attributes() {
+        let source = "#[allow(unused_state)]
+        contract C {
+            state { a: u64; #[allow(magic_number, shadowed_name)] b: u64; }
+            #[allow(magic_number)]
+            fn f(x: u64) { require(x > 5); }
+            fn g(x: u64) { require(x > 5); }
+            #[allow(shadowed_name, no_such_lint)]
+            fn h(x: u64) { let x = 1; }
+        }";
+        let expected = [("W0006", 6), ("W0008", 7)];
+        assert_eq!(warnings(source), expected.map(|(code, line)| (code.to_string(), line)));
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let source = "contract C { state { a: u64; } fn f(x: u64) { require(x > 5); } }";
+        let config = LintConfig::default()
+            .with_level(Lint::UnusedState, LintLevel::Allow)
+            .with_level(Lint::MagicNumber, LintLevel::Deny);
+        let program = Program::load(None, source.to_string(), &InMemoryResolver::new()).unwrap();
+        let diagnostics = lint_program(&program, &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(Lint::from_name("magic_number"), Some(Lint::MagicNumber));
+
+        // Denied lints fail compilation; warnings do not
+        let compile = |lints: LintConfig| {
+            let config = CompilerConfig {
+                lints,
+                ..CompilerConfig::default()
+            };
+            ContractCompiler::new(config).unwrap().compile(source)
+        };
+        assert!(compile(LintConfig::default()).is_ok());
+        let err = compile(config).unwrap_err();
+        assert!(err.to_string().contains("W0006"), "{err}");
+    }
+}
@@ -0,0 +1,543 @@
+//! Module system
+//!
+//! Loads a contract and everything it imports into a [`Program`]. The root
+//! file holds the contract; every imported file is a library of functions.
+//! Library functions declared `pub` can be called from importing modules as
+//! `alias::name`, where the alias is the last segment of the import path:
+//!
+//! ```text
+//! import "lib/fees";
+//! import std::math;
+//!
+//! contract Vault {
+//!     fn quote(amount: u64) -> u64 { return math::clamp(fees::charge(amount), 1, 100); }
+//! }
+//! ```
+//!
+//! Imports are found by a [`ModuleResolver`], except `std::` modules, which
+//! are built into the compiler as the [standard library](crate::stdlib). An
+//! import cycle is an error naming every module on it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use shared_core::ErrorCollection;
+
+use crate::ast::{Contract, Import, ImportPath, SourceFile, Span};
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::error::CompileError;
+use crate::parser::parse_file;
+use crate::stdlib;
+
+/// Source of a resolved module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSource {
+    /// Identifies the module; a module imported twice under the same id is
+    /// loaded once
+    pub id: String,
+    /// Module source
+    pub source: String,
+}
+
+/// Parses a module, given its id and source, for [`Program::load_with`]
+pub type ParseFn<'p> =
+    dyn FnMut(Option<&str>, &str) -> std::result::Result<SourceFile, CompileError> + 'p;
+
+/// Finds the modules imports refer to
+pub trait ModuleResolver {
+    /// Source of the module `path` imported by the module `importer`, `None`
+    /// for source not read through a resolver
+    ///
+    /// Fails with every location tried, for the error message.
+    fn resolve(
+        &self,
+        path: &ImportPath,
+        importer: Option<&str>,
+    ) -> std::result::Result<ModuleSource, Vec<String>>;
+}
+
+/// Resolves imports to `.contract` files
+///
+/// File paths are tried relative to the directory of the importing file,
+/// then under each search path; module paths `a::b` are tried as
+/// `a/b.contract` under each search path. Module ids are canonical paths.
+#[derive(Debug, Clone, Default)]
+pub struct FileSystemResolver {
+    search_paths: Vec<PathBuf>,
+}
+
+impl FileSystemResolver {
+    /// Create a resolver looking under `search_paths`, in order
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Self { search_paths }
+    }
+}
+
+impl ModuleResolver for FileSystemResolver {
+    fn resolve(
+        &self,
+        path: &ImportPath,
+        importer: Option<&str>,
+    ) -> std::result::Result<ModuleSource, Vec<String>> {
+        let (relative, importer_dir) = match path {
+            ImportPath::File(file) => {
+                let mut relative = PathBuf::from(file);
+                if relative.extension().is_none() {
+                    relative.set_extension("contract");
+                }
+                (relative, importer.and_then(|id| Path::new(id).parent()))
+            },
+            ImportPath::Module(segments) => {
+                (segments.iter().collect::<PathBuf>().with_extension("contract"), None)
+            },
+        };
+        let mut attempted = Vec::new();
+        for base in importer_dir.into_iter().chain(self.search_paths.iter().map(PathBuf::as_path)) {
+            let candidate = base.join(&relative);
+            if let Ok(source) = fs::read_to_string(&candidate) {
+                let id = fs::canonicalize(&candidate).unwrap_or(candidate);
+                return Ok(ModuleSource {
+                    id: id.display().to_string(),
+                    source,
+                });
+            }
+            let candidate = candidate.display().to_string();
+            if !attempted.contains(&candidate) {
+                attempted.push(candidate);
+            }
+        }
+        Err(attempted)
+    }
+}
+
+/// Resolves imports to sources registered by name
+///
+/// `import "lib/fees"` and `import lib::fees` both refer to the module
+/// registered as `lib/fees`.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryResolver {
+    modules: HashMap<String, String>,
+}
+
+impl InMemoryResolver {
+    /// Create a resolver without modules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `source` as the module `name`
+    pub fn with_module(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.modules.insert(name.into(), source.into());
+        self
+    }
+}
+
+impl ModuleResolver for InMemoryResolver {
+    fn resolve(
+        &self,
+        path: &ImportPath,
+        _importer: Option<&str>,
+    ) -> std::result::Result<ModuleSource, Vec<String>> {
+        let name = match path {
+            ImportPath::File(file) => file.strip_suffix(".contract").unwrap_or(file).to_string(),
+            ImportPath::Module(segments) => segments.join("/"),
+        };
+        match self.modules.get(&name) {
+            Some(source) => Ok(ModuleSource {
+                id: name,
+                source: source.clone(),
+            }),
+            None => Err(vec![name]),
+        }
+    }
+}
+
+/// A parsed module of a [`Program`]
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// Name qualifying the module's functions in HIR, unique in the program
+    pub name: String,
+    /// Id of the module, `None` for root source not read through a resolver
+    pub file: Option<String>,
+    /// Module source
+    pub source: String,
+    /// Syntax tree
+    pub ast: SourceFile,
+    /// Imported modules by alias, as indices into [`Program::modules`]
+    pub imports: HashMap<String, usize>,
+}
+
+/// A contract and the library modules it imports, directly or not
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// The contract module first
+    modules: Vec<Module>,
+}
+
+impl Program {
+    /// Parse the contract `source` and load its imports through `resolver`
+    ///
+    /// `file` is the resolver id of the contract, if it was read through
+    /// one. Syntax errors, unresolved imports, import cycles and imported
+    /// contracts are all reported.
+    pub fn load(
+        file: Option<String>,
+        source: String,
+        resolver: &dyn ModuleResolver,
+    ) -> std::result::Result<Self, ErrorCollection<Diagnostic>> {
+        Self::load_with(file, source, resolver, &mut |_, source| parse_file(source))
+    }
+
+    /// [`load`](Self::load), parsing every module with `parse`
+    pub fn load_with(
+        file: Option<String>,
+        source: String,
+        resolver: &dyn ModuleResolver,
+        parse: &mut ParseFn<'_>,
+    ) -> std::result::Result<Self, ErrorCollection<Diagnostic>> {
+        let mut loader = Loader {
+            resolver,
+            parse,
+            modules: Vec::new(),
+            by_id: HashMap::new(),
+            stack: Vec::new(),
+            diagnostics: ErrorCollection::new(),
+        };
+        if let Some(root) = loader.load(file.clone(), source, "contract".to_string()) {
+            if loader.modules[root].ast.contract.is_none() {
+                let functions = &loader.modules[root].ast.functions;
+                let span = functions.first().map_or(Span::default(), |f| f.name.span);
+                loader.diagnostics.push(
+                    Diagnostic::error(ErrorCode::InvalidModule, span, "expected a contract")
+                        .with_help("library modules can only be imported")
+                        .in_file(file),
+                );
+            }
+        }
+        let program = Program {
+            modules: loader.modules,
+        };
+        loader.diagnostics.into_result(program)
+    }
+
+    /// A program of `contract` alone
+    pub fn single(contract: Contract) -> Self {
+        let ast = SourceFile {
+            imports: Vec::new(),
+            contract: Some(contract),
+            functions: Vec::new(),
+        };
+        Program {
+            modules: vec![Module {
+                name: "contract".to_string(),
+                file: None,
+                source: String::new(),
+                ast,
+                imports: HashMap::new(),
+            }],
+        }
+    }
+
+    /// Every module, the contract module first
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// The contract of the root module
+    pub fn contract(&self) -> &Contract {
+        self.modules[0].ast.contract.as_ref().expect("loaded programs have a contract")
+    }
+
+    /// Source of the module `file`, as named by [`Diagnostic::file`]
+    pub fn source(&self, file: Option<&str>) -> Option<&str> {
+        self.modules.iter().find(|m| m.file.as_deref() == file).map(|m| m.source.as_str())
+    }
+}
+
+struct Loader<'r> {
+    resolver: &'r dyn ModuleResolver,
+    parse: &'r mut ParseFn<'r>,
+    modules: Vec<Module>,
+    /// Modules by id; `None` for modules that failed to parse
+    by_id: HashMap<String, Option<usize>>,
+    /// Modules whose imports are being loaded, outermost first
+    stack: Vec<usize>,
+    diagnostics: ErrorCollection<Diagnostic>,
+}
+
+impl Loader<'_> {
+    /// Parse a module and load its imports; `None` if it does not parse
+    fn load(&mut self, file: Option<String>, source: String, name: String) -> Option<usize> {
+        let parsed = (self.parse)(file.as_deref(), &source);
+        let index = parsed.as_ref().ok().map(|_| self.modules.len());
+        if let Some(id) = &file {
+            self.by_id.insert(id.clone(), index);
+        }
+        let ast = match parsed {
+            Ok(ast) => ast,
+            Err(err) => {
+                self.diagnostics.push(Diagnostic::from(err).in_file(file));
+                return None;
+            },
+        };
+        let index = self.modules.len();
+        let imports = ast.imports.clone();
+        self.modules.push(Module {
+            name,
+            file,
+            source,
+            ast,
+            imports: HashMap::new(),
+        });
+
+        self.stack.push(index);
+        let mut spans: HashMap<String, Span> = HashMap::new();
+        for import in &imports {
+            let alias = import.path.alias().to_string();
+            if let Some(&first) = spans.get(&alias) {
+                let message = format!("a module named `{alias}` is already imported");
+                let duplicate =
+                    Diagnostic::error(ErrorCode::DuplicateDeclaration, import.span, message)
+                        .with_label(first, "first imported here")
+                        .in_file(self.modules[index].file.clone());
+                self.diagnostics.push(duplicate);
+                continue;
+            }
+            spans.insert(alias.clone(), import.span);
+            if let Some(target) = self.import(index, import) {
+                self.modules[index].imports.insert(alias, target);
+            }
+        }
+        self.stack.pop();
+        Some(index)
+    }
+
+    fn import(&mut self, importer: usize, import: &Import) -> Option<usize> {
+        let file = self.modules[importer].file.clone();
+        let resolved = std_module(&import.path)
+            .unwrap_or_else(|| self.resolver.resolve(&import.path, file.as_deref()));
+        let module = match resolved {
+            Ok(module) => module,
+            Err(attempted) => {
+                let message = format!("cannot find module {}", import.path);
+                let mut diagnostic =
+                    Diagnostic::error(ErrorCode::UnresolvedImport, import.span, message);
+                if !attempted.is_empty() {
+                    diagnostic = diagnostic.with_help(format!("tried {}", attempted.join(", ")));
+                }
+                self.diagnostics.push(diagnostic.in_file(file));
+                return None;
+            },
+        };
+
+        if let Some(&existing) = self.by_id.get(&module.id) {
+            let existing = existing?;
+            if let Some(start) = self.stack.iter().position(|&m| m == existing) {
+                let mut cycle: Vec<&str> =
+                    self.stack[start..].iter().map(|&m| self.display_name(m)).collect();
+                cycle.push(self.display_name(existing));
+                let message = format!("import cycle: {}", cycle.join(" -> "));
+                self.diagnostics.push(
+                    Diagnostic::error(ErrorCode::ImportCycle, import.span, message).in_file(file),
+                );
+                return None;
+            }
+            return Some(existing);
+        }
+
+        let alias = import.path.alias();
+        let name = if self.modules.iter().any(|m| m.name == alias) {
+            format!("{alias}_{}", self.modules.len())
+        } else {
+            alias.to_string()
+        };
+        let index = self.load(Some(module.id), module.source, name)?;
+        if self.modules[index].ast.contract.is_some() {
+            let message = format!("module {} declares a contract", import.path);
+            self.diagnostics.push(
+                Diagnostic::error(ErrorCode::InvalidModule, import.span, message)
+                    .with_help("only library modules of functions can be imported")
+                    .in_file(file),
+            );
+            return None;
+        }
+        Some(index)
+    }
+
+    fn display_name(&self, module: usize) -> &str {
+        let module = &self.modules[module];
+        module.file.as_deref().unwrap_or(&module.name)
+    }
+}
+
+/// Source of a built-in `std::` module; `None` for other paths
+fn std_module(path: &ImportPath) -> Option<std::result::Result<ModuleSource, Vec<String>>> {
+    let ImportPath::Module(segments) = path else {
+        return None;
+    };
+    if segments.first().map(String::as_str) != Some("std") {
+        return None;
+    }
+    let id = segments.join("::");
+    Some(match stdlib::source(&id) {
+        Some(source) => Ok(ModuleSource {
+            id,
+            source: source.to_string(),
+        }),
+        None => Err(vec![format!("built-in module `{id}`")]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{CompileOutput, CompilerConfig, ContractCompiler, ErrorCode};
+
+    const FEES: &str = "
+        import std::math;
+        pub fn charge(amount: u64) -> u64 {
+            let fee = rate(amount);
+            return math::max(fee, 1);
+        }
+        fn rate(amount: u64) -> u64 { return amount / 100; }
+    ";
+
+    fn compiler(resolver: InMemoryResolver) -> ContractCompiler {
+        ContractCompiler::new(CompilerConfig::default()).unwrap().with_resolver(resolver)
+    }
+
+    /// `(code, file, line)` of every diagnostic for `source`
+    fn diagnostics(
+        resolver: InMemoryResolver,
+        source: &str,
+    ) -> Vec<(ErrorCode, Option<String>, u32)> {
+        compiler(resolver)
+            .check(source)
+            .into_iter()
+            .map(|d| (d.code, d.file, d.span.start.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_multi_file_compilation() {
+        let resolver = InMemoryResolver::new().with_module("lib/fees", FEES);
+        let source = "import \"lib/fees\";
+            import std::math;
+            contract Vault {
+                state { cap: u64; }
+                fn quote(amount: u64) -> u64 {
+                    let fee = fees::charge(amount);
+                    return math::min(amount + fee, self.cap);
+                }
+            }";
+        let program = Program::load(None, source.to_string(), &resolver).unwrap();
+        let names: Vec<_> = program.modules().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["contract", "fees", "math"]);
+        assert_eq!(program.source(Some("lib/fees")), Some(FEES));
+
+        let output = compiler(resolver).compile(source);
+        let Ok(CompileOutput::RustSource(rust)) = output else {
+            panic!("expected Rust source, got {output:?}");
+        };
+        assert!(rust.contains("pub fn quote("));
+        assert!(rust.contains("\n        fn fees_charge(") && rust.contains("self.fees_rate("));
+        assert!(rust.contains("self.math_min(") && !rust.contains("fn test_fees_charge"));
+    }
+
+    #[test]
+    fn test_compile_path_reports_attempted_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        fs::create_dir_all(dir.path().join("contracts")).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("fees.contract"), FEES).unwrap();
+        let root = dir.path().join("contracts/vault.contract");
+        let contract = "import \"fees\"; contract V { fn f() -> u64 { return fees::charge(5); } }";
+        fs::write(&root, contract).unwrap();
+
+        let config = CompilerConfig {
+            search_paths: vec![shared.clone()],
+            ..CompilerConfig::default()
+        };
+        assert!(ContractCompiler::new(config).unwrap().compile_path(&root).is_ok());
+
+        let err = ContractCompiler::new(CompilerConfig::default())
+            .unwrap()
+            .compile_path(&root)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("error[E0008]: cannot find module \"fees\""), "{err}");
+        let importer = root.canonicalize().unwrap().display().to_string();
+        let resolver = FileSystemResolver::new(vec![dir.path().join("missing")]);
+        let attempted = resolver
+            .resolve(&ImportPath::File("fees".to_string()), Some(&importer))
+            .unwrap_err();
+        let tried = [
+            dir.path().canonicalize().unwrap().join("contracts/fees.contract"),
+            dir.path().join("missing/fees.contract"),
+        ];
+        assert_eq!(attempted, tried.map(|path| path.display().to_string()));
+
+        let missing = diagnostics(InMemoryResolver::new(), "import std::text; contract C {}");
+        assert_eq!(missing, [(ErrorCode::UnresolvedImport, None, 1)]);
+        let errors = compiler(InMemoryResolver::new()).check("import a::b; contract C {}");
+        assert_eq!(errors[0].help.as_deref(), Some("tried a/b"));
+    }
+
+    #[test]
+    fn test_import_cycles() {
+        let resolver = InMemoryResolver::new()
+            .with_module("a", "import b; pub fn f() {}")
+            .with_module("b", "import c;")
+            .with_module("c", "import a;");
+        let errors = compiler(resolver).check("import a; contract C {}");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::ImportCycle);
+        assert_eq!(errors[0].message, "import cycle: a -> b -> c -> a");
+        assert_eq!(errors[0].file.as_deref(), Some("c"));
+
+        // Importing a module twice is not a cycle
+        let resolver = InMemoryResolver::new()
+            .with_module("a", "import std::math; import b;")
+            .with_module("b", "import std::math;");
+        let source = "import a; import b; contract C {}".to_string();
+        let program = Program::load(None, source, &resolver).unwrap();
+        assert_eq!(program.modules().len(), 4);
+    }
+
+    #[test]
+    fn test_privacy_and_module_rules() {
+        let resolver = || InMemoryResolver::new().with_module("fees", FEES);
+        // The private function is also linted where it is declared
+        let source = "import fees;\ncontract C { fn f() -> u64 { return fees::rate(1); } }";
+        let expected = [
+            (ErrorCode::PrivateItem, None, 2),
+            (ErrorCode::MissingPub, Some("fees".to_string()), 7),
+        ];
+        assert_eq!(diagnostics(resolver(), source), expected);
+        let cases: &[(&str, ErrorCode)] = &[
+            ("contract C { fn f() -> u64 { return fees::nope(1); } }", ErrorCode::UndefinedName),
+            ("contract C { fn f() -> u64 { return rate(1); } }", ErrorCode::UndefinedName),
+            ("contract C { fn f() -> u64 { return other::charge(1); } }", ErrorCode::UndefinedName),
+        ];
+        for (contract, code) in cases {
+            let source = format!("import fees;\n{contract}");
+            assert_eq!(diagnostics(resolver(), &source), [(*code, None, 2)], "{contract}");
+        }
+
+        let resolver = InMemoryResolver::new()
+            .with_module("ledger", "pub fn f() -> u64 { return self.total; }")
+            .with_module("token", "contract Token {}");
+        let errors = diagnostics(resolver.clone(), "import ledger; contract C {}");
+        assert_eq!(errors, [(ErrorCode::InvalidOperand, Some("ledger".to_string()), 1)]);
+        let errors = diagnostics(resolver.clone(), "import token; contract C {}");
+        assert_eq!(errors, [(ErrorCode::InvalidModule, None, 1)]);
+        let errors = diagnostics(resolver, "import std::math; import math; contract C {}");
+        assert_eq!(errors, [(ErrorCode::DuplicateDeclaration, None, 1)]);
+        let errors = diagnostics(InMemoryResolver::new(), "pub fn f() {}");
+        assert_eq!(errors, [(ErrorCode::InvalidModule, None, 1)]);
+    }
+}
@@ -0,0 +1,1029 @@
+//! Optimize module
+//!
+//! Semantics-preserving passes over the typed IR, run between type
+//! checking and code generation:
+//!
+//! - [`Pass::ConstantFolding`] evaluates operators on literals, wrapping
+//!   like the generated code, and simplifies `&&` and `||` with a literal
+//!   operand.
+//! - [`Pass::DeadCodeElimination`] drops branches that can never run,
+//!   statements after a `return`, always-true checks and expression
+//!   statements without effects.
+//! - [`Pass::CommonSubexpressionElimination`] reuses the local bound by
+//!   `let x = e;` for later occurrences of `e` in the same block, until a
+//!   store or call may change its value.
+//! - [`Pass::RequireMerging`] joins adjacent `require`s with the same
+//!   message into one `&&` condition.
+//!
+//! Nothing that can trap, such as division or indexing, is removed or
+//! evaluated earlier than in the source.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{BinaryOp, Span, UnaryOp};
+use crate::hir::{self, Callee, LocalId, Ty};
+
+/// An optimization pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pass {
+    /// Evaluate operators on literals
+    ConstantFolding,
+    /// Remove code that never runs or has no effect
+    DeadCodeElimination,
+    /// Reuse `let`-bound values instead of recomputing them
+    CommonSubexpressionElimination,
+    /// Join adjacent `require` statements
+    RequireMerging,
+}
+
+impl Pass {
+    /// Every pass, in the order they run
+    pub const ALL: [Pass; 4] = [
+        Pass::ConstantFolding,
+        Pass::DeadCodeElimination,
+        Pass::CommonSubexpressionElimination,
+        Pass::RequireMerging,
+    ];
+}
+
+/// Preset selection of passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptLevel {
+    /// No optimization
+    None,
+    /// Constant folding and dead code elimination
+    Basic,
+    /// Every pass
+    #[default]
+    Full,
+}
+
+impl OptLevel {
+    /// Passes the level enables
+    pub fn passes(self) -> &'static [Pass] {
+        match self {
+            Self::None => &[],
+            Self::Basic => &[Pass::ConstantFolding, Pass::DeadCodeElimination],
+            Self::Full => &Pass::ALL,
+        }
+    }
+}
+
+/// What the optimizer changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptStats {
+    /// Operators evaluated at compile time
+    pub constants_folded: usize,
+    /// Statements and expressions removed by any pass
+    pub nodes_removed: usize,
+    /// Expressions replaced by a read of an existing local
+    pub subexpressions_eliminated: usize,
+    /// `require` statements merged into their predecessor
+    pub requires_merged: usize,
+}
+
+/// Run the enabled `passes` over every function of `contract`
+///
+/// Passes always run in [`Pass::ALL`] order, whatever the order of
+/// `passes`.
+pub fn optimize(contract: &mut hir::Contract, passes: &[Pass]) -> OptStats {
+    let mut stats = OptStats::default();
+    for function in &mut contract.functions {
+        let unique = unique_names(function);
+        for pass in Pass::ALL.into_iter().filter(|pass| passes.contains(pass)) {
+            let body = &mut function.body;
+            let before = count_block(body);
+            match pass {
+                Pass::ConstantFolding => fold_block(body, &mut stats),
+                Pass::DeadCodeElimination => eliminate_dead_code(body, &unique),
+                Pass::CommonSubexpressionElimination => {
+                    eliminate_common_subexpressions(body, &mut Vec::new(), &unique, &mut stats);
+                },
+                Pass::RequireMerging => merge_requires(body, &mut stats),
+            }
+            stats.nodes_removed += before.saturating_sub(count_block(body));
+        }
+    }
+    stats
+}
+
+/// Whether each local's name is declared only once in its function
+///
+/// Generated Rust refers to locals by name, so only such locals may be read
+/// outside the place they were in the source.
+fn unique_names(function: &hir::Function) -> Vec<bool> {
+    let locals = &function.locals;
+    locals
+        .iter()
+        .map(|local| locals.iter().filter(|other| other.name == local.name).count() == 1)
+        .collect()
+}
+
+// Constant folding
+
+fn fold_block(block: &mut hir::Block, stats: &mut OptStats) {
+    for stmt in &mut block.statements {
+        match &mut stmt.kind {
+            hir::StmtKind::Let { value, .. } | hir::StmtKind::Expr(value) => {
+                fold_expr(value, stats);
+            },
+            hir::StmtKind::Assign { place, value } => {
+                fold_expr(place, stats);
+                fold_expr(value, stats);
+            },
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                fold_expr(condition, stats);
+                fold_block(then_branch, stats);
+                if let Some(else_branch) = else_branch {
+                    fold_block(else_branch, stats);
+                }
+            },
+            hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. } => {
+                fold_expr(condition, stats);
+            },
+            hir::StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    fold_expr(value, stats);
+                }
+            },
+            hir::StmtKind::Emit { fields, .. } => {
+                for field in fields {
+                    fold_expr(field, stats);
+                }
+            },
+        }
+    }
+}
+
+fn fold_expr(expr: &mut hir::Expr, stats: &mut OptStats) {
+    match &mut expr.kind {
+        hir::ExprKind::Unary { operand, .. } => fold_expr(operand, stats),
+        hir::ExprKind::Binary { lhs, rhs, .. } => {
+            fold_expr(lhs, stats);
+            fold_expr(rhs, stats);
+        },
+        hir::ExprKind::Call { args, .. } | hir::ExprKind::Array(args) => {
+            for arg in args {
+                fold_expr(arg, stats);
+            }
+        },
+        hir::ExprKind::Index { base, index } => {
+            fold_expr(base, stats);
+            fold_expr(index, stats);
+        },
+        _ => return,
+    }
+    if let Some(folded) = fold_operator(expr) {
+        stats.constants_folded += 1;
+        *expr = folded;
+    }
+}
+
+/// Simplified form of an operator whose operands are already folded
+fn fold_operator(expr: &hir::Expr) -> Option<hir::Expr> {
+    let span = expr.span;
+    match &expr.kind {
+        hir::ExprKind::Unary { op, operand } => match (op, &operand.kind) {
+            (UnaryOp::Not, hir::ExprKind::Bool(value)) => Some(bool_literal(!value, span)),
+            (UnaryOp::Neg, hir::ExprKind::Int(0)) => Some(int_literal(0, Ty::I64, span)),
+            (
+                _,
+                hir::ExprKind::Unary {
+                    op: inner,
+                    operand: inner_operand,
+                },
+            ) if inner == op => Some((**inner_operand).clone()),
+            _ => None,
+        },
+        hir::ExprKind::Binary { op, lhs, rhs } => fold_binary(*op, lhs, rhs, span),
+        _ => None,
+    }
+}
+
+fn fold_binary(op: BinaryOp, lhs: &hir::Expr, rhs: &hir::Expr, span: Span) -> Option<hir::Expr> {
+    match (op, &lhs.kind, &rhs.kind) {
+        (BinaryOp::And, hir::ExprKind::Bool(true), _)
+        | (BinaryOp::Or, hir::ExprKind::Bool(false), _) => return Some(rhs.clone()),
+        (BinaryOp::And, _, hir::ExprKind::Bool(true))
+        | (BinaryOp::Or, _, hir::ExprKind::Bool(false)) => return Some(lhs.clone()),
+        // The right operand is never evaluated
+        (BinaryOp::And, hir::ExprKind::Bool(false), _) => return Some(bool_literal(false, span)),
+        (BinaryOp::Or, hir::ExprKind::Bool(true), _) => return Some(bool_literal(true, span)),
+        // The left operand is evaluated, so it may only go if that is unobservable
+        (BinaryOp::And, _, hir::ExprKind::Bool(false)) if is_pure(lhs) => {
+            return Some(bool_literal(false, span));
+        },
+        (BinaryOp::Or, _, hir::ExprKind::Bool(true)) if is_pure(lhs) => {
+            return Some(bool_literal(true, span));
+        },
+        (BinaryOp::Eq, hir::ExprKind::Bool(a), hir::ExprKind::Bool(b)) => {
+            return Some(bool_literal(a == b, span));
+        },
+        (BinaryOp::Ne, hir::ExprKind::Bool(a), hir::ExprKind::Bool(b)) => {
+            return Some(bool_literal(a != b, span));
+        },
+        _ => {},
+    }
+
+    let (hir::ExprKind::Int(a), hir::ExprKind::Int(b)) = (&lhs.kind, &rhs.kind) else {
+        return None;
+    };
+    let ty = lhs.ty.clone();
+    let compare = |result: bool| Some(bool_literal(result, span));
+    if ty == Ty::I64 {
+        // Literals are non-negative, so i64 operands compare like u64
+        let (a, b) = (i64::try_from(*a).ok()?, i64::try_from(*b).ok()?);
+        let value = match op {
+            BinaryOp::Add => a.wrapping_add(b),
+            BinaryOp::Sub => a.wrapping_sub(b),
+            BinaryOp::Mul => a.wrapping_mul(b),
+            BinaryOp::Div => a.checked_div(b)?,
+            BinaryOp::Rem => a.checked_rem(b)?,
+            _ => return fold_comparison(op, a, b).and_then(compare),
+        };
+        // Negative results have no literal form
+        return Some(int_literal(u64::try_from(value).ok()?, ty, span));
+    }
+    let value = match op {
+        BinaryOp::Add => a.wrapping_add(*b),
+        BinaryOp::Sub => a.wrapping_sub(*b),
+        BinaryOp::Mul => a.wrapping_mul(*b),
+        BinaryOp::Div => a.checked_div(*b)?,
+        BinaryOp::Rem => a.checked_rem(*b)?,
+        _ => return fold_comparison(op, a, b).and_then(compare),
+    };
+    Some(int_literal(value, ty, span))
+}
+
+fn fold_comparison<T: Ord>(op: BinaryOp, a: T, b: T) -> Option<bool> {
+    Some(match op {
+        BinaryOp::Eq => a == b,
+        BinaryOp::Ne => a != b,
+        BinaryOp::Lt => a < b,
+        BinaryOp::Le => a <= b,
+        BinaryOp::Gt => a > b,
+        BinaryOp::Ge => a >= b,
+        _ => return None,
+    })
+}
+
+fn bool_literal(value: bool, span: Span) -> hir::Expr {
+    hir::Expr {
+        kind: hir::ExprKind::Bool(value),
+        ty: Ty::Bool,
+        span,
+    }
+}
+
+fn int_literal(value: u64, ty: Ty, span: Span) -> hir::Expr {
+    hir::Expr {
+        kind: hir::ExprKind::Int(value),
+        ty,
+        span,
+    }
+}
+
+// Dead code elimination
+
+fn eliminate_dead_code(block: &mut hir::Block, unique: &[bool]) {
+    for mut stmt in std::mem::take(&mut block.statements) {
+        match &mut stmt.kind {
+            hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. }
+                if matches!(condition.kind, hir::ExprKind::Bool(true)) => {},
+            hir::StmtKind::Expr(expr) if is_pure(expr) => {},
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                eliminate_dead_code(then_branch, unique);
+                if let Some(else_branch) = else_branch {
+                    eliminate_dead_code(else_branch, unique);
+                }
+                let is_empty = |block: &hir::Block| block.statements.is_empty();
+                if let hir::ExprKind::Bool(taken) = condition.kind {
+                    let branch = if taken {
+                        Some(std::mem::replace(then_branch, empty_block(then_branch.span)))
+                    } else {
+                        else_branch.take()
+                    };
+                    match branch {
+                        Some(branch) if can_splice(&branch, unique) => {
+                            block.statements.extend(branch.statements);
+                        },
+                        Some(branch) => {
+                            // Keep the block as the scope of its locals
+                            condition.kind = hir::ExprKind::Bool(true);
+                            *then_branch = branch;
+                            *else_branch = None;
+                            block.statements.push(stmt);
+                        },
+                        None => {},
+                    }
+                } else if !(is_empty(then_branch)
+                    && else_branch.as_ref().map_or(true, is_empty)
+                    && is_pure(condition))
+                {
+                    block.statements.push(stmt);
+                }
+            },
+            _ => block.statements.push(stmt),
+        }
+        if block.statements.last().is_some_and(always_returns) {
+            break;
+        }
+    }
+}
+
+fn empty_block(span: Span) -> hir::Block {
+    hir::Block {
+        statements: Vec::new(),
+        span,
+    }
+}
+
+/// Whether the statements of `block` can move to the enclosing block
+/// without a local they declare shadowing another
+fn can_splice(block: &hir::Block, unique: &[bool]) -> bool {
+    block.statements.iter().all(|stmt| match &stmt.kind {
+        hir::StmtKind::Let { local, .. } => unique[local.0],
+        _ => true,
+    })
+}
+
+/// Whether every path through `stmt` ends in `return`
+fn always_returns(stmt: &hir::Stmt) -> bool {
+    match &stmt.kind {
+        hir::StmtKind::Return(_) => true,
+        hir::StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => {
+            then_branch.statements.iter().any(always_returns)
+                && else_branch.statements.iter().any(always_returns)
+        },
+        _ => false,
+    }
+}
+
+// Common subexpression elimination
+
+/// A value bound by `let` that later statements can read instead
+#[derive(Clone)]
+struct Available {
+    local: LocalId,
+    value: hir::Expr,
+    reads: Reads,
+}
+
+/// What an expression reads or a statement writes
+#[derive(Clone, Default)]
+struct Reads {
+    locals: Vec<LocalId>,
+    state: Vec<usize>,
+    /// Calls a contract function, which may write any state
+    calls: bool,
+}
+
+impl Reads {
+    fn of_expr(expr: &hir::Expr) -> Self {
+        let mut reads = Self::default();
+        reads.expr(expr);
+        reads
+    }
+
+    fn expr(&mut self, expr: &hir::Expr) {
+        visit_expr(expr, &mut |e| match &e.kind {
+            hir::ExprKind::Local(id) => self.locals.push(*id),
+            hir::ExprKind::State(index) => self.state.push(*index),
+            hir::ExprKind::Call {
+                callee: Callee::Function(_),
+                ..
+            } => self.calls = true,
+            _ => {},
+        });
+    }
+
+    /// Places `stmt` may write, and whether it calls a contract function
+    fn writes_of(stmt: &hir::Stmt) -> Self {
+        let mut writes = Self::default();
+        writes.stmt_writes(stmt);
+        writes
+    }
+
+    fn stmt_writes(&mut self, stmt: &hir::Stmt) {
+        visit_stmt_exprs(stmt, &mut |e| {
+            if matches!(
+                e.kind,
+                hir::ExprKind::Call {
+                    callee: Callee::Function(_),
+                    ..
+                }
+            ) {
+                self.calls = true;
+            }
+        });
+        match &stmt.kind {
+            hir::StmtKind::Let { local, .. } => self.locals.push(*local),
+            hir::StmtKind::Assign { place, .. } => {
+                let mut root = place;
+                while let hir::ExprKind::Index { base, .. } = &root.kind {
+                    root = base;
+                }
+                match root.kind {
+                    hir::ExprKind::Local(id) => self.locals.push(id),
+                    hir::ExprKind::State(index) => self.state.push(index),
+                    _ => {},
+                }
+            },
+            hir::StmtKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                for stmt in nested(then_branch, else_branch.as_ref()) {
+                    self.stmt_writes(stmt);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Whether a value reading `self` may change when `writes` happen
+    fn invalidated_by(&self, writes: &Reads) -> bool {
+        writes.locals.iter().any(|id| self.locals.contains(id))
+            || writes.state.iter().any(|index| self.state.contains(index))
+            || (writes.calls && !self.state.is_empty())
+    }
+}
+
+fn eliminate_common_subexpressions(
+    block: &mut hir::Block,
+    available: &mut Vec<Available>,
+    unique: &[bool],
+    stats: &mut OptStats,
+) {
+    for stmt in &mut block.statements {
+        let writes = Reads::writes_of(stmt);
+        if writes.calls {
+            // A call in the statement may run before any replaced read
+            available.retain(|a| a.reads.state.is_empty());
+        }
+        match &mut stmt.kind {
+            hir::StmtKind::Let { value, .. } | hir::StmtKind::Expr(value) => {
+                replace_available(value, available, stats);
+            },
+            hir::StmtKind::Assign { place, value } => {
+                replace_available(value, available, stats);
+                if let hir::ExprKind::Index { index, .. } = &mut place.kind {
+                    replace_available(index, available, stats);
+                }
+            },
+            hir::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                replace_available(condition, available, stats);
+                eliminate_common_subexpressions(then_branch, &mut available.clone(), unique, stats);
+                if let Some(else_branch) = else_branch {
+                    eliminate_common_subexpressions(
+                        else_branch,
+                        &mut available.clone(),
+                        unique,
+                        stats,
+                    );
+                }
+            },
+            hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. } => {
+                replace_available(condition, available, stats);
+            },
+            hir::StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    replace_available(value, available, stats);
+                }
+            },
+            hir::StmtKind::Emit { fields, .. } => {
+                for field in fields {
+                    replace_available(field, available, stats);
+                }
+            },
+        }
+        available
+            .retain(|a| !a.reads.invalidated_by(&writes) && !writes.locals.contains(&a.local));
+
+        if let hir::StmtKind::Let { local, value } = &stmt.kind {
+            let reads = Reads::of_expr(value);
+            let worth_reusing = matches!(
+                value.kind,
+                hir::ExprKind::Unary { .. }
+                    | hir::ExprKind::Binary { .. }
+                    | hir::ExprKind::Index { .. }
+            );
+            if worth_reusing && !reads.calls && unique[local.0] && is_copy(&value.ty) {
+                available.push(Available {
+                    local: *local,
+                    value: value.clone(),
+                    reads,
+                });
+            }
+        }
+    }
+}
+
+/// Replace the outermost subexpressions of `expr` that are available
+fn replace_available(expr: &mut hir::Expr, available: &[Available], stats: &mut OptStats) {
+    if let Some(found) = available.iter().find(|a| same_expr(&a.value, expr)) {
+        expr.kind = hir::ExprKind::Local(found.local);
+        stats.subexpressions_eliminated += 1;
+        return;
+    }
+    match &mut expr.kind {
+        hir::ExprKind::Unary { operand, .. } => replace_available(operand, available, stats),
+        hir::ExprKind::Binary { lhs, rhs, .. } => {
+            replace_available(lhs, available, stats);
+            replace_available(rhs, available, stats);
+        },
+        hir::ExprKind::Call { args, .. } | hir::ExprKind::Array(args) => {
+            for arg in args {
+                replace_available(arg, available, stats);
+            }
+        },
+        hir::ExprKind::Index { base, index } => {
+            replace_available(base, available, stats);
+            replace_available(index, available, stats);
+        },
+        _ => {},
+    }
+}
+
+fn is_copy(ty: &Ty) -> bool {
+    match ty {
+        Ty::String | Ty::Map => false,
+        Ty::Array { element, .. } => is_copy(element),
+        _ => true,
+    }
+}
+
+// Require merging
+
+fn merge_requires(block: &mut hir::Block, stats: &mut OptStats) {
+    let mut merged: Vec<hir::Stmt> = Vec::with_capacity(block.statements.len());
+    for mut stmt in std::mem::take(&mut block.statements) {
+        if let hir::StmtKind::If {
+            then_branch,
+            else_branch,
+            ..
+        } = &mut stmt.kind
+        {
+            merge_requires(then_branch, stats);
+            if let Some(else_branch) = else_branch {
+                merge_requires(else_branch, stats);
+            }
+        }
+        if let (
+            Some(hir::Stmt {
+                kind:
+                    hir::StmtKind::Require {
+                        condition: first,
+                        message: first_message,
+                    },
+                span,
+            }),
+            hir::StmtKind::Require { condition, message },
+        ) = (merged.last_mut(), &stmt.kind)
+        {
+            if first_message == message {
+                // `&&` skips the second check when the first fails, like the
+                // early revert did
+                let lhs = std::mem::replace(first, bool_literal(true, Span::default()));
+                *first = hir::Expr {
+                    span: lhs.span.to(condition.span),
+                    kind: hir::ExprKind::Binary {
+                        op: BinaryOp::And,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(condition.clone()),
+                    },
+                    ty: Ty::Bool,
+                };
+                *span = span.to(stmt.span);
+                stats.requires_merged += 1;
+                continue;
+            }
+        }
+        merged.push(stmt);
+    }
+    block.statements = merged;
+}
+
+// Helpers
+
+/// Whether evaluating `expr` can neither trap nor change state
+fn is_pure(expr: &hir::Expr) -> bool {
+    let mut pure = true;
+    visit_expr(expr, &mut |e| match &e.kind {
+        hir::ExprKind::Call {
+            callee: Callee::Function(_),
+            ..
+        }
+        | hir::ExprKind::Index { .. }
+        | hir::ExprKind::Binary {
+            op: BinaryOp::Div | BinaryOp::Rem,
+            ..
+        } => pure = false,
+        // Standard library builtins may revert
+        hir::ExprKind::Call {
+            callee: Callee::Builtin(builtin),
+            ..
+        } if builtin.module().is_some() => pure = false,
+        _ => {},
+    });
+    pure
+}
+
+/// Structural equality, ignoring spans
+fn same_expr(a: &hir::Expr, b: &hir::Expr) -> bool {
+    if a.ty != b.ty {
+        return false;
+    }
+    match (&a.kind, &b.kind) {
+        (hir::ExprKind::Int(x), hir::ExprKind::Int(y)) => x == y,
+        (hir::ExprKind::Bool(x), hir::ExprKind::Bool(y)) => x == y,
+        (hir::ExprKind::Str(x), hir::ExprKind::Str(y)) => x == y,
+        (hir::ExprKind::Local(x), hir::ExprKind::Local(y)) => x == y,
+        (hir::ExprKind::State(x), hir::ExprKind::State(y)) => x == y,
+        (
+            hir::ExprKind::Unary { op, operand },
+            hir::ExprKind::Unary {
+                op: other_op,
+                operand: other,
+            },
+        ) => op == other_op && same_expr(operand, other),
+        (
+            hir::ExprKind::Binary { op, lhs, rhs },
+            hir::ExprKind::Binary {
+                op: other_op,
+                lhs: other_lhs,
+                rhs: other_rhs,
+            },
+        ) => op == other_op && same_expr(lhs, other_lhs) && same_expr(rhs, other_rhs),
+        (
+            hir::ExprKind::Call { callee, args },
+            hir::ExprKind::Call {
+                callee: other_callee,
+                args: other_args,
+            },
+        ) => callee == other_callee && same_exprs(args, other_args),
+        (
+            hir::ExprKind::Index { base, index },
+            hir::ExprKind::Index {
+                base: other_base,
+                index: other_index,
+            },
+        ) => same_expr(base, other_base) && same_expr(index, other_index),
+        (hir::ExprKind::Array(x), hir::ExprKind::Array(y)) => same_exprs(x, y),
+        _ => false,
+    }
+}
+
+fn same_exprs(a: &[hir::Expr], b: &[hir::Expr]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_expr(a, b))
+}
+
+/// Call `f` on `expr` and every subexpression
+pub(crate) fn visit_expr(expr: &hir::Expr, f: &mut impl FnMut(&hir::Expr)) {
+    f(expr);
+    match &expr.kind {
+        hir::ExprKind::Unary { operand, .. } => visit_expr(operand, f),
+        hir::ExprKind::Binary { lhs, rhs, .. } => {
+            visit_expr(lhs, f);
+            visit_expr(rhs, f);
+        },
+        hir::ExprKind::Call { args, .. } | hir::ExprKind::Array(args) => {
+            for arg in args {
+                visit_expr(arg, f);
+            }
+        },
+        hir::ExprKind::Index { base, index } => {
+            visit_expr(base, f);
+            visit_expr(index, f);
+        },
+        _ => {},
+    }
+}
+
+/// Call `f` on every expression of `stmt`, nested statements included
+pub(crate) fn visit_stmt_exprs(stmt: &hir::Stmt, f: &mut impl FnMut(&hir::Expr)) {
+    match &stmt.kind {
+        hir::StmtKind::Let { value, .. } | hir::StmtKind::Expr(value) => visit_expr(value, f),
+        hir::StmtKind::Assign { place, value } => {
+            visit_expr(place, f);
+            visit_expr(value, f);
+        },
+        hir::StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visit_expr(condition, f);
+            for stmt in nested(then_branch, else_branch.as_ref()) {
+                visit_stmt_exprs(stmt, f);
+            }
+        },
+        hir::StmtKind::Require { condition, .. } | hir::StmtKind::Assert { condition, .. } => {
+            visit_expr(condition, f);
+        },
+        hir::StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visit_expr(value, f);
+            }
+        },
+        hir::StmtKind::Emit { fields, .. } => {
+            for field in fields {
+                visit_expr(field, f);
+            }
+        },
+    }
+}
+
+/// Statements and expressions in `block`
+fn count_block(block: &hir::Block) -> usize {
+    block.statements.iter().map(count_stmt).sum()
+}
+
+fn count_stmt(stmt: &hir::Stmt) -> usize {
+    let mut count = 1;
+    visit_stmt_exprs(stmt, &mut |_| count += 1);
+    if let hir::StmtKind::If {
+        then_branch,
+        else_branch,
+        ..
+    } = &stmt.kind
+    {
+        // Nested statements themselves, their expressions are counted above
+        count += nested(then_branch, else_branch.as_ref()).count();
+    }
+    count
+}
+
+/// Statements directly inside the branches of an `if`
+fn nested<'a>(
+    then_branch: &'a hir::Block,
+    else_branch: Option<&'a hir::Block>,
+) -> impl Iterator<Item = &'a hir::Stmt> {
+    then_branch.statements.iter().chain(else_branch.into_iter().flat_map(|b| &b.statements))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(kind: hir::ExprKind, ty: Ty) -> hir::Expr {
+        hir::Expr {
+            kind,
+            ty,
+            span: Span::default(),
+        }
+    }
+
+    fn int(value: u64) -> hir::Expr {
+        int_literal(value, Ty::U64, Span::default())
+    }
+
+    fn boolean(value: bool) -> hir::Expr {
+        bool_literal(value, Span::default())
+    }
+
+    fn local(id: usize, ty: Ty) -> hir::Expr {
+        expr(hir::ExprKind::Local(LocalId(id)), ty)
+    }
+
+    fn bin(op: BinaryOp, lhs: hir::Expr, rhs: hir::Expr) -> hir::Expr {
+        let ty = match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                lhs.ty.clone()
+            },
+            _ => Ty::Bool,
+        };
+        let kind = hir::ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        expr(kind, ty)
+    }
+
+    fn call(ty: Ty) -> hir::Expr {
+        let kind = hir::ExprKind::Call {
+            callee: Callee::Function(0),
+            args: Vec::new(),
+        };
+        expr(kind, ty)
+    }
+
+    fn stmt(kind: hir::StmtKind) -> hir::Stmt {
+        hir::Stmt {
+            kind,
+            span: Span::default(),
+        }
+    }
+
+    fn let_(id: usize, value: hir::Expr) -> hir::Stmt {
+        stmt(hir::StmtKind::Let {
+            local: LocalId(id),
+            value,
+        })
+    }
+
+    fn require(condition: hir::Expr, message: &str) -> hir::Stmt {
+        stmt(hir::StmtKind::Require {
+            condition,
+            message: Some(message.to_string()),
+        })
+    }
+
+    fn block(statements: Vec<hir::Stmt>) -> hir::Block {
+        hir::Block {
+            statements,
+            span: Span::default(),
+        }
+    }
+
+    /// Contract with one function over the named locals of type `u64`
+    fn contract(locals: &[&str], statements: Vec<hir::Stmt>) -> hir::Contract {
+        let locals = locals
+            .iter()
+            .map(|name| hir::Local {
+                name: (*name).to_string(),
+                ty: Ty::U64,
+                span: Span::default(),
+            })
+            .collect();
+        hir::Contract {
+            name: "Test".to_string(),
+            state: Vec::new(),
+            events: Vec::new(),
+            functions: vec![hir::Function {
+                name: "run".to_string(),
+                exported: true,
+                params: Vec::new(),
+                locals,
+                return_type: Ty::Unit,
+                body: block(statements),
+                span: Span::default(),
+                file: None,
+            }],
+            file: None,
+        }
+    }
+
+    fn body(contract: &hir::Contract) -> &[hir::Stmt] {
+        &contract.functions[0].body.statements
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let flag = local(0, Ty::Bool);
+        let sum = bin(BinaryOp::Add, int(2), int(3));
+        let product = bin(BinaryOp::Mul, sum, int(4));
+        let condition = bin(BinaryOp::And, bin(BinaryOp::Eq, product, int(20)), flag.clone());
+        let signed = |value| int_literal(value, Ty::I64, Span::default());
+        let negative = bin(BinaryOp::Sub, signed(1), signed(2));
+        let by_zero = bin(BinaryOp::Div, int(1), int(0));
+        let effectful =
+            bin(BinaryOp::And, bin(BinaryOp::Eq, call(Ty::U64), int(1)), boolean(false));
+        let mut contract = contract(
+            &["flag"],
+            vec![
+                require(condition, "a"),
+                require(bin(BinaryOp::Eq, negative.clone(), negative.clone()), "b"),
+                require(bin(BinaryOp::Eq, by_zero.clone(), int(1)), "c"),
+                require(effectful.clone(), "d"),
+            ],
+        );
+
+        let stats = optimize(&mut contract, &[Pass::ConstantFolding]);
+        assert_eq!(stats.constants_folded, 4);
+        let conditions: Vec<_> = body(&contract)
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                hir::StmtKind::Require { condition, .. } => condition.clone(),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(conditions[0], flag);
+        // A negative i64 has no literal, and division by zero must still trap
+        assert_eq!(conditions[1], bin(BinaryOp::Eq, negative.clone(), negative));
+        assert_eq!(conditions[2], bin(BinaryOp::Eq, by_zero, int(1)));
+        // The call still runs even though the result is known
+        assert_eq!(conditions[3], effectful);
+    }
+
+    #[test]
+    fn test_dead_code_elimination() {
+        let ret = stmt(hir::StmtKind::Return(None));
+        let assign = stmt(hir::StmtKind::Assign {
+            place: local(0, Ty::U64),
+            value: int(1),
+        });
+        let branch = |statements| {
+            stmt(hir::StmtKind::If {
+                condition: boolean(true),
+                then_branch: block(statements),
+                else_branch: Some(block(vec![assign.clone()])),
+            })
+        };
+        let mut contract = contract(
+            &["x", "y", "x"],
+            vec![
+                require(boolean(true), "never"),
+                stmt(hir::StmtKind::Expr(bin(BinaryOp::Add, local(0, Ty::U64), int(1)))),
+                branch(vec![let_(1, int(2))]),
+                // Splicing would let the second `x` shadow the first
+                branch(vec![let_(2, int(3))]),
+                ret.clone(),
+                assign.clone(),
+            ],
+        );
+
+        let stats = optimize(&mut contract, &[Pass::DeadCodeElimination]);
+        let kept = vec![
+            let_(1, int(2)),
+            stmt(hir::StmtKind::If {
+                condition: boolean(true),
+                then_branch: block(vec![let_(2, int(3))]),
+                else_branch: None,
+            }),
+            ret,
+        ];
+        assert_eq!(body(&contract), kept.as_slice());
+        assert_eq!(stats.nodes_removed, 17);
+        assert_eq!(stats.constants_folded, 0);
+    }
+
+    #[test]
+    fn test_common_subexpression_elimination() {
+        let sum = || bin(BinaryOp::Add, local(0, Ty::U64), local(1, Ty::U64));
+        let mut contract = contract(
+            &["x", "y", "a", "b", "c"],
+            vec![
+                let_(2, sum()),
+                let_(3, bin(BinaryOp::Mul, sum(), int(2))),
+                stmt(hir::StmtKind::Assign {
+                    place: local(0, Ty::U64),
+                    value: int(7),
+                }),
+                let_(4, sum()),
+            ],
+        );
+
+        let stats = optimize(&mut contract, &[Pass::CommonSubexpressionElimination]);
+        assert_eq!(stats.subexpressions_eliminated, 1);
+        assert_eq!(body(&contract)[1], let_(3, bin(BinaryOp::Mul, local(2, Ty::U64), int(2))));
+        // `x` changed, so the sum is computed again
+        assert_eq!(body(&contract)[3], let_(4, sum()));
+    }
+
+    #[test]
+    fn test_require_merging() {
+        let check = |id| bin(BinaryOp::Gt, local(id, Ty::U64), int(0));
+        let mut contract = contract(
+            &["x", "y"],
+            vec![
+                require(check(0), "empty"),
+                require(check(1), "empty"),
+                require(check(0), "zero"),
+            ],
+        );
+
+        let stats = optimize(&mut contract, &Pass::ALL);
+        assert_eq!(stats.requires_merged, 1);
+        let merged = vec![
+            require(bin(BinaryOp::And, check(0), check(1)), "empty"),
+            require(check(0), "zero"),
+        ];
+        assert_eq!(body(&contract), merged.as_slice());
+    }
+
+    #[test]
+    fn test_pass_selection() {
+        let statements = vec![
+            require(bin(BinaryOp::Lt, int(1), int(2)), "always"),
+            require(local(0, Ty::Bool), "flag"),
+        ];
+        let mut unoptimized = contract(&["flag"], statements.clone());
+        assert_eq!(optimize(&mut unoptimized, OptLevel::None.passes()), OptStats::default());
+        assert_eq!(body(&unoptimized), statements.as_slice());
+
+        let mut basic = contract(&["flag"], statements);
+        let stats = optimize(&mut basic, OptLevel::Basic.passes());
+        assert_eq!(stats.constants_folded, 1);
+        assert_eq!(stats.requires_merged, 0);
+        assert_eq!(body(&basic), [require(local(0, Ty::Bool), "flag")].as_slice());
+    }
+}
@@ -1 +1,927 @@
 //! Parser module
+//!
+//! Recursive-descent parser for the contract DSL:
+//!
+//! ```text
+//! file      = import* ( attrs contract | ( attrs "pub"? function )* )
+//! import    = "import" ( STRING | path ) ";"
+//! attrs     = ( "#[" "allow" "(" IDENT ( "," IDENT )* ","? ")" "]" )*
+//! contract  = "contract" IDENT "{" ( state | attrs event | attrs function )* "}"
+//! state     = "state" "{" ( attrs IDENT ":" type ";" )* "}"
+//! event     = "event" IDENT "{" ( param ( "," param )* ","? )? "}"
+//! function  = "fn" IDENT "(" ( param ( "," param )* ","? )? ")" ( "->" type )? block
+//! param     = IDENT ":" type
+//! type      = IDENT | "[" type ";" INT "]"
+//! block     = "{" stmt* "}"
+//! stmt      = "let" IDENT ( ":" type )? "=" expr ";"
+//!           | "if" expr block ( "else" ( stmt_if | block ) )?
+//!           | ( "require" | "assert" ) "(" expr ( "," STRING )? ")" ";"
+//!           | "return" expr? ";"
+//!           | "emit" IDENT "{" ( IDENT ":" expr ( "," IDENT ":" expr )* ","? )? "}" ";"
+//!           | expr ( "=" expr )? ";"
+//! primary   = INT | STRING | "true" | "false" | path | "(" expr ")"
+//!           | "[" ( expr ( "," expr )* ","? )? "]"
+//! path      = IDENT ( "::" IDENT )*
+//! ```
+//!
+//! Keywords are accepted as the segments of a `::` path, so that modules
+//! such as `std::assert` can be imported and called.
+//!
+//! [`parse`] reads a lone contract, [`parse_file`] a whole source file.
+//!
+//! Binary operators bind, loosest first: `||`, `&&`, `==` `!=`,
+//! `<` `<=` `>` `>=`, `+` `-`, `*` `/` `%`; all are left-associative. Prefix
+//! `!` and `-` bind tighter, and calls, field access and indexing tightest.
+//!
+//! Source may come from untrusted users, so [`ParseLimits`] bound its size,
+//! its number of tokens and how deeply blocks, expressions, types and
+//! `else if` chains nest; the later passes recurse over the syntax tree, so
+//! the depth bound keeps them within the stack too.
+
+use crate::ast::{
+    BinaryOp, Block, Contract, Event, Expr, ExprKind, FieldInit, Function, Ident, Import,
+    ImportPath, Param, Position, SourceFile, Span, StateField, Stmt, StmtKind, Type, TypeKind,
+    UnaryOp,
+};
+use crate::error::CompileError;
+use crate::lexer::{tokenize_limited, Token, TokenKind};
+
+/// Default of [`ParseLimits::max_source_bytes`], 1 MiB
+pub const DEFAULT_MAX_SOURCE_BYTES: usize = 1 << 20;
+/// Default of [`ParseLimits::max_tokens`]
+pub const DEFAULT_MAX_TOKENS: usize = 100_000;
+/// Default of [`ParseLimits::max_depth`]
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Bounds on the source the parser accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Longest source, in bytes
+    pub max_source_bytes: usize,
+    /// Most tokens, not counting the end of input
+    pub max_tokens: usize,
+    /// Deepest nesting of blocks, expressions, array types and `else if`
+    pub max_depth: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_source_bytes: DEFAULT_MAX_SOURCE_BYTES,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+/// Parse contract source into its syntax tree
+///
+/// The error names the first offending token and every token that would
+/// have been accepted in its place.
+pub fn parse(source: &str) -> Result<Contract, CompileError> {
+    let mut parser = Parser::new(source, &ParseLimits::default())?;
+    let allow = parser.attributes()?;
+    let contract = parser.contract(allow)?;
+    parser.expect(&TokenKind::Eof)?;
+    Ok(contract)
+}
+
+/// Parse a source file with its imports, holding either a contract or
+/// library functions
+pub fn parse_file(source: &str) -> Result<SourceFile, CompileError> {
+    parse_file_with(source, &ParseLimits::default())
+}
+
+/// [`parse_file`] within `limits`
+pub fn parse_file_with(source: &str, limits: &ParseLimits) -> Result<SourceFile, CompileError> {
+    let mut parser = Parser::new(source, limits)?;
+    let mut imports = Vec::new();
+    while parser.at(&TokenKind::Import) {
+        imports.push(parser.import()?);
+    }
+    let mut file = SourceFile {
+        imports,
+        contract: None,
+        functions: Vec::new(),
+    };
+    let mut allow = parser.attributes()?;
+    if parser.at(&TokenKind::Contract) {
+        file.contract = Some(parser.contract(allow)?);
+    } else {
+        loop {
+            let public = parser.eat(&TokenKind::Pub);
+            if public.is_none() && !parser.at(&TokenKind::Fn) {
+                if !allow.is_empty() {
+                    return Err(parser.unexpected());
+                }
+                break;
+            }
+            let mut function = parser.function(allow)?;
+            if let Some(start) = public {
+                function.public = true;
+                function.span = start.to(function.span);
+            }
+            file.functions.push(function);
+            allow = parser.attributes()?;
+        }
+    }
+    parser.expect(&TokenKind::Eof)?;
+    Ok(file)
+}
+
+type ParseResult<T> = Result<T, CompileError>;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// What was tried and rejected at the current token
+    expected: Vec<String>,
+    /// Nesting depth at the current token
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Parser {
+    fn new(source: &str, limits: &ParseLimits) -> ParseResult<Self> {
+        if source.len() > limits.max_source_bytes {
+            let start = Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            };
+            return Err(CompileError {
+                span: Span { start, end: start },
+                found: format!("source longer than {} bytes", limits.max_source_bytes),
+                expected: Vec::new(),
+            });
+        }
+        Ok(Self {
+            tokens: tokenize_limited(source, limits.max_tokens)?,
+            pos: 0,
+            expected: Vec::new(),
+            depth: 0,
+            max_depth: limits.max_depth,
+        })
+    }
+
+    /// Run `parse` one nesting level deeper, failing past the depth limit
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth == self.max_depth {
+            return Err(CompileError {
+                span: self.peek().span,
+                found: format!("nesting deeper than {} levels", self.max_depth),
+                expected: Vec::new(),
+            });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        self.expected.clear();
+        token
+    }
+
+    fn expect_here(&mut self, what: String) {
+        if !self.expected.contains(&what) {
+            self.expected.push(what);
+        }
+    }
+
+    /// Whether the current token is `kind`, noting it as expected if not
+    fn at(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            return true;
+        }
+        self.expect_here(kind.to_string());
+        false
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> Option<Span> {
+        self.at(kind).then(|| self.advance().span)
+    }
+
+    fn expect(&mut self, kind: &TokenKind) -> ParseResult<Span> {
+        self.eat(kind).ok_or_else(|| self.unexpected())
+    }
+
+    /// Error at the current token listing everything tried there
+    fn unexpected(&mut self) -> CompileError {
+        CompileError {
+            span: self.peek().span,
+            found: self.peek().kind.to_string(),
+            expected: std::mem::take(&mut self.expected),
+        }
+    }
+
+    /// Identifier, described as `what` when missing
+    fn name(&mut self, what: &str) -> ParseResult<Ident> {
+        if let TokenKind::Ident(name) = &self.peek().kind {
+            let name = name.clone();
+            let span = self.advance().span;
+            return Ok(Ident { name, span });
+        }
+        self.expect_here(what.to_string());
+        Err(self.unexpected())
+    }
+
+    /// Segment of a `::` path; keywords such as `assert` can name modules
+    fn path_segment(&mut self, what: &str) -> ParseResult<Ident> {
+        if is_keyword(&self.peek().kind) {
+            let token = self.advance();
+            let name = token.kind.text().unwrap_or_default().to_string();
+            return Ok(Ident {
+                name,
+                span: token.span,
+            });
+        }
+        self.name(what)
+    }
+
+    /// Whether a keyword starts a path here, as in `assert::require_eq`
+    fn at_keyword_path(&self) -> bool {
+        is_keyword(&self.peek().kind)
+            && self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::ColonColon)
+    }
+
+    /// `IDENT ( "::" IDENT )*` as one name
+    fn path(&mut self) -> ParseResult<Expr> {
+        let first = self.path_segment("identifier")?;
+        let mut path = first.name;
+        let mut span = first.span;
+        while self.eat(&TokenKind::ColonColon).is_some() {
+            let segment = self.path_segment("identifier")?;
+            path = format!("{path}::{}", segment.name);
+            span = span.to(segment.span);
+        }
+        Ok(Expr {
+            kind: ExprKind::Ident(path),
+            span,
+        })
+    }
+
+    fn ty(&mut self) -> ParseResult<Type> {
+        let Some(start) = self.eat(&TokenKind::LBracket) else {
+            let Ident { name, span } = self.name("type")?;
+            return Ok(Type {
+                kind: TypeKind::Named(name),
+                span,
+            });
+        };
+        let element = self.nested(Self::ty)?;
+        self.expect(&TokenKind::Semi)?;
+        let TokenKind::Int(len) = self.peek().kind else {
+            self.expect_here("array length".to_string());
+            return Err(self.unexpected());
+        };
+        self.advance();
+        let end = self.expect(&TokenKind::RBracket)?;
+        Ok(Type {
+            kind: TypeKind::Array {
+                element: Box::new(element),
+                len,
+            },
+            span: start.to(end),
+        })
+    }
+
+    /// Comma-separated expressions up to `close`, which is consumed
+    fn expr_list(&mut self, close: &TokenKind) -> ParseResult<Vec<Expr>> {
+        let mut exprs = Vec::new();
+        while self.eat(close).is_none() {
+            exprs.push(self.expr()?);
+            if self.eat(&TokenKind::Comma).is_none() {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(exprs)
+    }
+
+    /// Lints named by the `#[allow(...)]` attributes here, if any
+    ///
+    /// Attributes are optional, so `#[` is not listed as expected when
+    /// something else follows.
+    fn attributes(&mut self) -> ParseResult<Vec<Ident>> {
+        let mut allow = Vec::new();
+        while self.peek().kind == TokenKind::HashBracket {
+            self.advance();
+            let attribute = self.name("attribute")?;
+            if attribute.name != "allow" {
+                return Err(CompileError {
+                    span: attribute.span,
+                    found: format!("attribute `{}`", attribute.name),
+                    expected: vec!["`allow`".to_string()],
+                });
+            }
+            self.expect(&TokenKind::LParen)?;
+            loop {
+                allow.push(self.name("lint name")?);
+                if self.eat(&TokenKind::Comma).is_none() || self.at(&TokenKind::RParen) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RParen)?;
+            self.expect(&TokenKind::RBracket)?;
+        }
+        Ok(allow)
+    }
+
+    fn import(&mut self) -> ParseResult<Import> {
+        let start = self.expect(&TokenKind::Import)?;
+        let path = if let TokenKind::Str(path) = &self.peek().kind {
+            let path = ImportPath::File(path.clone());
+            self.advance();
+            path
+        } else {
+            self.expect_here("string".to_string());
+            let mut segments = vec![self.path_segment("module path")?.name];
+            while self.eat(&TokenKind::ColonColon).is_some() {
+                segments.push(self.path_segment("identifier")?.name);
+            }
+            ImportPath::Module(segments)
+        };
+        let end = self.expect(&TokenKind::Semi)?;
+        Ok(Import {
+            path,
+            span: start.to(end),
+        })
+    }
+
+    fn contract(&mut self, allow: Vec<Ident>) -> ParseResult<Contract> {
+        let start = self.expect(&TokenKind::Contract)?;
+        let name = self.name("identifier")?;
+        self.expect(&TokenKind::LBrace)?;
+
+        let mut state = Vec::new();
+        let mut events = Vec::new();
+        let mut functions = Vec::new();
+        let end = loop {
+            let item_allow = self.attributes()?;
+            if item_allow.is_empty() {
+                if let Some(end) = self.eat(&TokenKind::RBrace) {
+                    break end;
+                } else if self.at(&TokenKind::State) {
+                    state.extend(self.state_block()?);
+                    continue;
+                }
+            }
+            if self.at(&TokenKind::Event) {
+                events.push(self.event(item_allow)?);
+            } else if self.at(&TokenKind::Fn) {
+                functions.push(self.function(item_allow)?);
+            } else {
+                return Err(self.unexpected());
+            }
+        };
+
+        Ok(Contract {
+            name,
+            state,
+            events,
+            functions,
+            allow,
+            span: start.to(end),
+        })
+    }
+
+    fn state_block(&mut self) -> ParseResult<Vec<StateField>> {
+        self.expect(&TokenKind::State)?;
+        self.expect(&TokenKind::LBrace)?;
+        let mut fields = Vec::new();
+        loop {
+            let allow = self.attributes()?;
+            if allow.is_empty() && self.eat(&TokenKind::RBrace).is_some() {
+                break;
+            }
+            let name = self.name("identifier")?;
+            self.expect(&TokenKind::Colon)?;
+            let ty = self.ty()?;
+            let end = self.expect(&TokenKind::Semi)?;
+            fields.push(StateField {
+                span: name.span.to(end),
+                name,
+                ty,
+                allow,
+            });
+        }
+        Ok(fields)
+    }
+
+    fn event(&mut self, allow: Vec<Ident>) -> ParseResult<Event> {
+        let start = self.expect(&TokenKind::Event)?;
+        let name = self.name("identifier")?;
+        self.expect(&TokenKind::LBrace)?;
+        let fields = self.params(&TokenKind::RBrace)?;
+        Ok(Event {
+            name,
+            fields,
+            allow,
+            span: start.to(self.prev_span()),
+        })
+    }
+
+    /// Comma-separated `IDENT ":" type` up to `close`, which is consumed
+    fn params(&mut self, close: &TokenKind) -> ParseResult<Vec<Param>> {
+        let mut params = Vec::new();
+        while self.eat(close).is_none() {
+            let name = self.name("identifier")?;
+            self.expect(&TokenKind::Colon)?;
+            let ty = self.ty()?;
+            params.push(Param {
+                span: name.span.to(ty.span),
+                name,
+                ty,
+            });
+            if self.eat(&TokenKind::Comma).is_none() {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    fn function(&mut self, allow: Vec<Ident>) -> ParseResult<Function> {
+        let start = self.expect(&TokenKind::Fn)?;
+        let name = self.name("identifier")?;
+        self.expect(&TokenKind::LParen)?;
+        let params = self.params(&TokenKind::RParen)?;
+
+        let return_type = match self.eat(&TokenKind::Arrow) {
+            Some(_) => Some(self.ty()?),
+            None => None,
+        };
+        let body = self.block()?;
+
+        Ok(Function {
+            public: false,
+            span: start.to(body.span),
+            name,
+            params,
+            return_type,
+            body,
+            allow,
+        })
+    }
+
+    fn block(&mut self) -> ParseResult<Block> {
+        self.nested(Self::block_contents)
+    }
+
+    fn block_contents(&mut self) -> ParseResult<Block> {
+        let start = self.expect(&TokenKind::LBrace)?;
+        let mut statements = Vec::new();
+        let end = loop {
+            if let Some(end) = self.eat(&TokenKind::RBrace) {
+                break end;
+            }
+            statements.push(self.statement()?);
+        };
+        Ok(Block {
+            statements,
+            span: start.to(end),
+        })
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        let start = self.peek().span;
+        let kind = if self.eat(&TokenKind::Let).is_some() {
+            let name = self.name("identifier")?;
+            let ty = match self.eat(&TokenKind::Colon) {
+                Some(_) => Some(self.ty()?),
+                None => None,
+            };
+            self.expect(&TokenKind::Assign)?;
+            let value = self.expr()?;
+            self.expect(&TokenKind::Semi)?;
+            StmtKind::Let { name, ty, value }
+        } else if self.at(&TokenKind::If) {
+            return self.if_statement();
+        } else if !self.at_keyword_path() && self.eat(&TokenKind::Require).is_some() {
+            let (condition, message) = self.check_arguments()?;
+            StmtKind::Require { condition, message }
+        } else if !self.at_keyword_path() && self.eat(&TokenKind::Assert).is_some() {
+            let (condition, message) = self.check_arguments()?;
+            StmtKind::Assert { condition, message }
+        } else if self.eat(&TokenKind::Return).is_some() {
+            let value = match self.eat(&TokenKind::Semi) {
+                Some(_) => None,
+                None => {
+                    let value = self.expr()?;
+                    self.expect(&TokenKind::Semi)?;
+                    Some(value)
+                },
+            };
+            StmtKind::Return(value)
+        } else if self.eat(&TokenKind::Emit).is_some() {
+            let event = self.name("event name")?;
+            self.expect(&TokenKind::LBrace)?;
+            let mut fields = Vec::new();
+            while self.eat(&TokenKind::RBrace).is_none() {
+                let name = self.name("field name")?;
+                self.expect(&TokenKind::Colon)?;
+                fields.push(FieldInit {
+                    name,
+                    value: self.expr()?,
+                });
+                if self.eat(&TokenKind::Comma).is_none() {
+                    self.expect(&TokenKind::RBrace)?;
+                    break;
+                }
+            }
+            self.expect(&TokenKind::Semi)?;
+            StmtKind::Emit { event, fields }
+        } else {
+            let expr = self.expr()?;
+            let kind = if self.eat(&TokenKind::Assign).is_some() {
+                if !is_place(&expr) {
+                    return Err(CompileError {
+                        span: expr.span,
+                        found: "non-assignable expression".to_string(),
+                        expected: vec![
+                            "identifier".to_string(),
+                            "field access".to_string(),
+                            "array element".to_string(),
+                        ],
+                    });
+                }
+                StmtKind::Assign {
+                    target: expr,
+                    value: self.expr()?,
+                }
+            } else {
+                StmtKind::Expr(expr)
+            };
+            self.expect(&TokenKind::Semi)?;
+            kind
+        };
+        Ok(Stmt {
+            kind,
+            span: start.to(self.prev_span()),
+        })
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        let start = self.expect(&TokenKind::If)?;
+        let condition = self.expr()?;
+        let then_branch = self.block()?;
+        let else_branch = match self.eat(&TokenKind::Else) {
+            Some(_) if self.at(&TokenKind::If) => {
+                let nested = self.nested(Self::if_statement)?;
+                Some(Block {
+                    span: nested.span,
+                    statements: vec![nested],
+                })
+            },
+            Some(_) => Some(self.block()?),
+            None => None,
+        };
+        Ok(Stmt {
+            kind: StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            },
+            span: start.to(self.prev_span()),
+        })
+    }
+
+    /// `( expr ( "," STRING )? ) ;` of `require` and `assert`
+    fn check_arguments(&mut self) -> ParseResult<(Expr, Option<String>)> {
+        self.expect(&TokenKind::LParen)?;
+        let condition = self.expr()?;
+        let mut message = None;
+        if self.eat(&TokenKind::Comma).is_some() {
+            if let TokenKind::Str(text) = &self.peek().kind {
+                message = Some(text.clone());
+                self.advance();
+            } else {
+                self.expect_here("string".to_string());
+                return Err(self.unexpected());
+            }
+        }
+        self.expect(&TokenKind::RParen)?;
+        self.expect(&TokenKind::Semi)?;
+        Ok((condition, message))
+    }
+
+    fn expr(&mut self) -> ParseResult<Expr> {
+        self.nested(|parser| parser.binary(1))
+    }
+
+    /// Precedence climbing over operators binding at least `min_precedence`
+    fn binary(&mut self, min_precedence: u8) -> ParseResult<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = binary_op(&self.peek().kind) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.advance();
+            let rhs = self.binary(op.precedence() + 1)?;
+            lhs = Expr {
+                span: lhs.span.to(rhs.span),
+                kind: ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        let op = match self.peek().kind {
+            TokenKind::Bang => UnaryOp::Not,
+            TokenKind::Minus => UnaryOp::Neg,
+            _ => return self.postfix(),
+        };
+        let start = self.advance().span;
+        let operand = self.nested(Self::unary)?;
+        Ok(Expr {
+            span: start.to(operand.span),
+            kind: ExprKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+        })
+    }
+
+    fn postfix(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            expr = match self.peek().kind {
+                TokenKind::LParen => {
+                    self.advance();
+                    let args = self.expr_list(&TokenKind::RParen)?;
+                    Expr {
+                        span: expr.span.to(self.prev_span()),
+                        kind: ExprKind::Call {
+                            callee: Box::new(expr),
+                            args,
+                        },
+                    }
+                },
+                TokenKind::LBracket => {
+                    self.advance();
+                    let index = self.expr()?;
+                    let end = self.expect(&TokenKind::RBracket)?;
+                    Expr {
+                        span: expr.span.to(end),
+                        kind: ExprKind::Index {
+                            base: Box::new(expr),
+                            index: Box::new(index),
+                        },
+                    }
+                },
+                TokenKind::Dot => {
+                    self.advance();
+                    let field = self.name("field name")?;
+                    Expr {
+                        span: expr.span.to(field.span),
+                        kind: ExprKind::Field {
+                            base: Box::new(expr),
+                            field,
+                        },
+                    }
+                },
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        let kind = match &self.peek().kind {
+            TokenKind::Int(value) => ExprKind::Int(*value),
+            TokenKind::Str(value) => ExprKind::Str(value.clone()),
+            TokenKind::True => ExprKind::Bool(true),
+            TokenKind::False => ExprKind::Bool(false),
+            TokenKind::Ident(_) => return self.path(),
+            _ if self.at_keyword_path() => return self.path(),
+            TokenKind::LParen => {
+                let start = self.advance().span;
+                let inner = self.expr()?;
+                let end = self.expect(&TokenKind::RParen)?;
+                return Ok(Expr {
+                    kind: inner.kind,
+                    span: start.to(end),
+                });
+            },
+            TokenKind::LBracket => {
+                let start = self.advance().span;
+                let elements = self.expr_list(&TokenKind::RBracket)?;
+                return Ok(Expr {
+                    kind: ExprKind::Array(elements),
+                    span: start.to(self.prev_span()),
+                });
+            },
+            _ => {
+                self.expect_here("expression".to_string());
+                return Err(self.unexpected());
+            },
+        };
+        let span = self.advance().span;
+        Ok(Expr { kind, span })
+    }
+}
+
+/// Whether `kind` is a keyword, as opposed to punctuation or a literal
+fn is_keyword(kind: &TokenKind) -> bool {
+    kind.text().is_some_and(|text| text.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Whether `expr` can be assigned to
+fn is_place(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Ident(_) | ExprKind::Field { .. } => true,
+        ExprKind::Index { base, .. } => is_place(base),
+        _ => false,
+    }
+}
+
+fn binary_op(kind: &TokenKind) -> Option<BinaryOp> {
+    Some(match kind {
+        TokenKind::OrOr => BinaryOp::Or,
+        TokenKind::AndAnd => BinaryOp::And,
+        TokenKind::EqEq => BinaryOp::Eq,
+        TokenKind::NotEq => BinaryOp::Ne,
+        TokenKind::Lt => BinaryOp::Lt,
+        TokenKind::Le => BinaryOp::Le,
+        TokenKind::Gt => BinaryOp::Gt,
+        TokenKind::Ge => BinaryOp::Ge,
+        TokenKind::Plus => BinaryOp::Add,
+        TokenKind::Minus => BinaryOp::Sub,
+        TokenKind::Star => BinaryOp::Mul,
+        TokenKind::Slash => BinaryOp::Div,
+        TokenKind::Percent => BinaryOp::Rem,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    const FIXTURES: &str = "tests/fixtures/parser";
+
+    /// Contract sources under `FIXTURES/dir`, sorted
+    fn fixtures(dir: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = fs::read_dir(Path::new(FIXTURES).join(dir))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "contract"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no fixtures in {dir}");
+        paths
+    }
+
+    /// Compare `actual` with the snapshot next to `source`; set
+    /// `UPDATE_SNAPSHOTS=1` to rewrite snapshots instead
+    fn assert_snapshot(source: &Path, extension: &str, actual: &str) {
+        let snapshot = source.with_extension(extension);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&snapshot, actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&snapshot)
+            .unwrap_or_else(|_| panic!("missing snapshot {}", snapshot.display()));
+        assert_eq!(actual, expected, "snapshot {} differs", snapshot.display());
+    }
+
+    #[test]
+    fn test_valid_fixtures() {
+        for path in fixtures("valid") {
+            let source = fs::read_to_string(&path).unwrap();
+            let contract = parse(&source)
+                .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            assert_snapshot(&path, "ast", &format!("{contract:#?}\n"));
+        }
+    }
+
+    #[test]
+    fn test_invalid_fixtures() {
+        for path in fixtures("invalid") {
+            let source = fs::read_to_string(&path).unwrap();
+            let err = parse(&source).expect_err(&path.display().to_string());
+            assert_snapshot(&path, "err", &format!("{err}\n"));
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let contract = parse("contract C { fn f() { x = !a || b && c == 1 + 2 * -d; } }").unwrap();
+        let StmtKind::Assign { value, .. } = &contract.functions[0].body.statements[0].kind
+        else {
+            panic!("expected an assignment");
+        };
+        let ExprKind::Binary { op, rhs, .. } = &value.kind else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(*op, BinaryOp::Or);
+        let ExprKind::Binary { op, rhs, .. } = &rhs.kind else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(*op, BinaryOp::And);
+        assert!(matches!(rhs.kind, ExprKind::Binary { op: BinaryOp::Eq, .. }));
+        assert_eq!(format!("{:?}", value.span), "1:27..1:53");
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let file = parse_file(
+            "import \"lib/fees.contract\";
+            import std::math;
+            pub fn cap(a: u64) -> u64 { return math::min(a, fees::limit()); }
+            fn helper() {}",
+        )
+        .unwrap();
+        let aliases: Vec<_> = file.imports.iter().map(|i| i.path.alias()).collect();
+        assert_eq!(aliases, ["fees", "math"]);
+        assert_eq!(file.imports[1].path.to_string(), "std::math");
+        assert!(file.contract.is_none());
+        assert_eq!(file.functions.len(), 2);
+        assert!(file.functions[0].public && !file.functions[1].public);
+        assert_eq!(format!("{:?}", file.functions[0].span), "3:13..3:78");
+        let StmtKind::Return(Some(value)) = &file.functions[0].body.statements[0].kind else {
+            panic!("expected a return");
+        };
+        let ExprKind::Call { callee, .. } = &value.kind else {
+            panic!("expected a call");
+        };
+        assert_eq!(callee.kind, ExprKind::Ident("math::min".to_string()));
+
+        let contract = parse_file("import std::math; contract C {}").unwrap();
+        assert_eq!(contract.contract.unwrap().name.name, "C");
+        let err = parse_file("contract C {} fn f() {}").unwrap_err();
+        assert_eq!(err.to_string(), "1:15: unexpected `fn`, expected end of input");
+        let err = parse_file("import 1;").unwrap_err();
+        let expected = "1:8: unexpected integer `1`, expected one of string, module path";
+        assert_eq!(err.to_string(), expected);
+    }
+
+    fn limited(max_source_bytes: usize, max_tokens: usize, max_depth: usize) -> ParseLimits {
+        ParseLimits {
+            max_source_bytes,
+            max_tokens,
+            max_depth,
+        }
+    }
+
+    #[test]
+    fn test_source_and_token_limits() {
+        // Four tokens besides the end of input
+        let source = "contract C {}";
+        assert!(parse_file_with(source, &limited(source.len(), 4, 1)).is_ok());
+        let err = parse_file_with(source, &limited(source.len() - 1, 4, 1)).unwrap_err();
+        assert_eq!(err.to_string(), "1:1: unexpected source longer than 12 bytes");
+        let err = parse_file_with(source, &limited(source.len(), 3, 1)).unwrap_err();
+        assert_eq!(err.to_string(), "1:13: unexpected token past the limit of 3");
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        // The body, the statement's expression, then one level per
+        // parenthesis
+        let parens = 10;
+        let source = format!(
+            "contract C {{ fn f() {{ x = {}1{}; }} }}",
+            "(".repeat(parens),
+            ")".repeat(parens)
+        );
+        assert!(parse_file_with(&source, &limited(usize::MAX, usize::MAX, parens + 2)).is_ok());
+        let err = parse_file_with(&source, &limited(usize::MAX, usize::MAX, parens + 1));
+        assert_eq!(err.unwrap_err().found, "nesting deeper than 11 levels");
+
+        // Each kind of nesting counts, and none overflows the stack
+        let deep = 10_000;
+        let sources = [
+            format!("contract C {{ fn f() {{ x = {}1; }} }}", "(".repeat(deep)),
+            format!("contract C {{ fn f() {{ x = {}1; }} }}", "-".repeat(deep)),
+            format!("contract C {{ fn f() {{ {} }} }}", "if a { ".repeat(deep)),
+            format!("contract C {{ fn f() {{ if a {{}}{} }} }}", " else if a {}".repeat(deep)),
+            format!("contract C {{ state {{ a: {}u64; }} }}", "[".repeat(deep)),
+        ];
+        for source in sources {
+            let err = parse_file(&source).unwrap_err();
+            assert_eq!(err.found, "nesting deeper than 64 levels");
+        }
+    }
+}
@@ -1 +1,1136 @@
 //! Runtime module
+//!
+//! Runs contracts compiled to WebAssembly in process under wasmtime.
+//! Arguments and results are JSON values converted through the types the
+//! module's [`ABI_SECTION`] gives its [`exports`]: `u64`, `i64` and `address`
+//! are numbers and `bool` is a boolean. Every call runs with a fresh fuel budget, so a
+//! runaway contract fails instead of hanging the host. Traps are reported at
+//! the contract source location the module's [`SourceMap`] gives for them.
+//!
+//! `string` values are JSON strings and `map` values objects of numbers.
+//! The instance holds them in a table the contract refers to by handle;
+//! after each call, only those the state refers to are kept. The builtins
+//! of `std::map` and `std::assert` are host functions over that table, and
+//! a failed one fails the call with its revert message.
+//!
+//! Events a call emits through the [`EMIT_IMPORT`] host functions are
+//! returned in its [`ExecutionOutcome`] and broadcast to the instance's
+//! subscribers. An executor created [`with_trace`](ContractExecutor::with_trace)
+//! also returns where the fuel went, read at every host call; see the
+//! [`trace`](crate::trace) module.
+//!
+//! Calls are transactional: the contract state is saved before each call
+//! and put back if the call fails, whether by a failed `require`, another
+//! trap or running out of fuel. Wasm writes its memory in place, so the
+//! savepoint is a copy of the state variables rather than an overlay; the
+//! value table only grows during a call, so it is cut back to its length.
+//! Contract code cannot catch the failure of a function it calls, so a
+//! call has a single savepoint; embedders nest their own with
+//! [`ContractInstance::snapshot`] and [`ContractInstance::restore`].
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, PoisonError};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use shared_core::{Result, SystemError};
+use wasmparser::{Parser, Payload};
+use tokio::sync::broadcast;
+use wasmtime::{
+    Config, Engine, Extern, Instance, Linker, Memory, Module, Store, Trap, Val, WasmBacktrace,
+};
+
+use crate::abi::{ContractAbi, EventAbi};
+use crate::codegen::{
+    exports, handle_slots, state_size, AbiFunction, ABI_SECTION, EMIT_FIELD_IMPORT, EMIT_IMPORT,
+    EQUALS_IMPORT, MEMORY_EXPORT, STRING_IMPORT,
+};
+use crate::compiler::CompiledArtifact;
+use crate::hir::{Builtin, Ty};
+pub use crate::interpreter::{CallContext, ContractEvent};
+use crate::source_map::{SourceLocation, SourceMap};
+use crate::stdlib;
+use crate::trace::{ExecutionTrace, TraceSample};
+
+/// Fuel available to each call by default
+pub const DEFAULT_FUEL_LIMIT: u64 = 10_000_000;
+/// Events a subscriber may fall behind by before it lags
+const EVENT_CAPACITY: usize = 1024;
+
+/// Result of a successful call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExecutionOutcome {
+    /// Value returned, `null` for functions without a return type
+    pub return_value: Value,
+    /// Fuel the call consumed
+    pub gas_used: u64,
+    /// Events the call emitted, in emission order
+    pub events: Vec<ContractEvent>,
+    /// Where the fuel went, if the executor traces calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<ExecutionTrace>,
+}
+
+/// Loads and calls compiled contracts
+pub struct ContractExecutor {
+    engine: Engine,
+    linker: Linker<HostState>,
+    fuel_limit: u64,
+    trace: bool,
+}
+
+/// A loaded contract with its own state
+pub struct ContractInstance {
+    store: Mutex<Store<HostState>>,
+    instance: Instance,
+    memory: Memory,
+    /// Bytes of memory, from offset 0, holding the state variables
+    state_size: usize,
+    /// Offsets of the state slots holding value handles
+    handle_slots: Vec<usize>,
+    abi: HashMap<String, AbiFunction>,
+    source_map: Option<SourceMap>,
+    events: broadcast::Sender<ContractEvent>,
+}
+
+/// The state variables of a [`ContractInstance`] at some point, see
+/// [`ContractInstance::snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractState {
+    bytes: Vec<u8>,
+    values: Vec<HostValue>,
+}
+
+impl ContractState {
+    /// The state variables as laid out in the contract's memory
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// A `string` or `map` value a contract refers to by handle
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostValue {
+    Str(String),
+    Map(BTreeMap<String, u64>),
+}
+
+impl HostValue {
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Str(value) => value.is_empty(),
+            Self::Map(entries) => entries.is_empty(),
+        }
+    }
+}
+
+/// Failure of a library builtin, reverting the call with its message
+#[derive(Debug)]
+struct Revert(String);
+
+impl std::fmt::Display for Revert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "reverted: {}", self.0)
+    }
+}
+
+impl std::error::Error for Revert {}
+
+/// Data of an instance's store the host functions use
+#[derive(Debug, Default)]
+struct HostState {
+    context: CallContext,
+    /// Values handle `n` refers to, at `n - 1`; handle 0 is the empty value
+    values: Vec<HostValue>,
+    /// Declared events, indexed the way `emit` numbers them
+    events: Vec<EventAbi>,
+    /// Values passed to `emit_field` since the last `emit`
+    fields: Vec<i64>,
+    /// Events emitted by the current call
+    emitted: Vec<ContractEvent>,
+    /// Host calls of the current call, if it is traced
+    host_calls: Option<Vec<HostCall>>,
+}
+
+/// A host call made by a traced call
+#[derive(Debug)]
+struct HostCall {
+    /// Imported function called
+    name: &'static str,
+    /// Fuel left when it was called
+    fuel: u64,
+    /// Function index and module offset of each frame, innermost first
+    frames: Vec<(u32, Option<usize>)>,
+}
+
+/// Record a host call if the current call is traced
+fn record_host_call(caller: &mut wasmtime::Caller<'_, HostState>, name: &'static str) {
+    if caller.data().host_calls.is_none() {
+        return;
+    }
+    let fuel = caller.get_fuel().unwrap_or(0);
+    let backtrace = WasmBacktrace::capture(&*caller);
+    let frames = backtrace.frames().iter().map(|f| (f.func_index(), f.module_offset()));
+    let call = HostCall {
+        name,
+        fuel,
+        frames: frames.collect(),
+    };
+    if let Some(host_calls) = &mut caller.data_mut().host_calls {
+        host_calls.push(call);
+    }
+}
+
+impl HostState {
+    /// Handle of `value`
+    fn push(&mut self, value: HostValue) -> i32 {
+        if value.is_empty() {
+            return 0;
+        }
+        self.values.push(value);
+        self.values.len() as i32
+    }
+
+    /// Value of `handle`, `None` for the empty value
+    fn value(&self, handle: i32) -> wasmtime::Result<Option<&HostValue>> {
+        if handle == 0 {
+            return Ok(None);
+        }
+        usize::try_from(handle - 1)
+            .ok()
+            .and_then(|index| self.values.get(index))
+            .map(Some)
+            .ok_or_else(|| wasmtime::Error::msg(format!("invalid value handle {handle}")))
+    }
+
+    fn string(&self, handle: i32) -> wasmtime::Result<Cow<'_, str>> {
+        match self.value(handle)? {
+            None => Ok(Cow::Borrowed("")),
+            Some(HostValue::Str(value)) => Ok(Cow::Borrowed(value)),
+            Some(HostValue::Map(_)) => {
+                Err(wasmtime::Error::msg(format!("handle {handle} is not a string")))
+            },
+        }
+    }
+
+    fn map(&self, handle: i32) -> wasmtime::Result<Cow<'_, BTreeMap<String, u64>>> {
+        match self.value(handle)? {
+            None => Ok(Cow::Owned(BTreeMap::new())),
+            Some(HostValue::Map(entries)) => Ok(Cow::Borrowed(entries)),
+            Some(HostValue::Str(_)) => {
+                Err(wasmtime::Error::msg(format!("handle {handle} is not a map")))
+            },
+        }
+    }
+
+    /// Whether two handles refer to equal values; no empty value has a
+    /// handle other than 0
+    fn equals(&self, a: i32, b: i32) -> wasmtime::Result<i32> {
+        Ok(i32::from(self.value(a)? == self.value(b)?))
+    }
+
+    /// Record event `index` with the pending field values
+    fn emit(&mut self, index: i32) -> wasmtime::Result<()> {
+        let values = std::mem::take(&mut self.fields);
+        let event = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.events.get(index))
+            .ok_or_else(|| wasmtime::Error::msg(format!("emitted undeclared event {index}")))?;
+        if values.len() != event.fields.len() {
+            return Err(wasmtime::Error::msg(format!(
+                "event `{}` has {} field(s) but {} were passed",
+                event.name,
+                event.fields.len(),
+                values.len()
+            )));
+        }
+        let fields = event
+            .fields
+            .iter()
+            .zip(values)
+            .map(|(field, value)| {
+                (field.name.clone(), to_json(&Val::I64(value), &field.ty, &self.values))
+            })
+            .collect();
+        self.emitted.push(ContractEvent {
+            name: event.name.clone(),
+            fields,
+        });
+        Ok(())
+    }
+}
+
+impl ContractExecutor {
+    /// Create an executor giving each call `fuel_limit` units of fuel
+    pub fn new(fuel_limit: u64) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| runtime_error("creating engine", e))?;
+        let mut linker = Linker::new(&engine);
+        for builtin in Builtin::ALL {
+            link_builtin(&mut linker, builtin).map_err(|e| runtime_error("linking builtins", e))?;
+        }
+        linker
+            .func_wrap(
+                "env",
+                STRING_IMPORT,
+                |mut caller: wasmtime::Caller<'_, HostState>, ptr: i32, len: i32| {
+                    record_host_call(&mut caller, STRING_IMPORT);
+                    let memory = caller
+                        .get_export(MEMORY_EXPORT)
+                        .and_then(Extern::into_memory)
+                        .ok_or_else(|| wasmtime::Error::msg("module exports no memory"))?;
+                    let (start, len) = (ptr as u32 as usize, len as u32 as usize);
+                    let bytes = memory
+                        .data(&caller)
+                        .get(start..start.saturating_add(len))
+                        .ok_or_else(|| wasmtime::Error::msg("string out of bounds"))?;
+                    let value = String::from_utf8(bytes.to_vec())?;
+                    Ok(caller.data_mut().push(HostValue::Str(value)))
+                },
+            )
+            .and_then(|linker| {
+                linker.func_wrap(
+                    "env",
+                    EQUALS_IMPORT,
+                    |mut caller: wasmtime::Caller<'_, HostState>, a: i32, b: i32| {
+                        record_host_call(&mut caller, EQUALS_IMPORT);
+                        caller.data().equals(a, b)
+                    },
+                )
+            })
+            .map_err(|e| runtime_error("linking value imports", e))?;
+        linker
+            .func_wrap(
+                "env",
+                EMIT_FIELD_IMPORT,
+                |mut caller: wasmtime::Caller<'_, HostState>, value: i64| {
+                    record_host_call(&mut caller, EMIT_FIELD_IMPORT);
+                    caller.data_mut().fields.push(value);
+                },
+            )
+            .and_then(|linker| {
+                linker.func_wrap(
+                    "env",
+                    EMIT_IMPORT,
+                    |mut caller: wasmtime::Caller<'_, HostState>, index: i32| {
+                        record_host_call(&mut caller, EMIT_IMPORT);
+                        caller.data_mut().emit(index)
+                    },
+                )
+            })
+            .map_err(|e| runtime_error("linking event imports", e))?;
+        Ok(Self {
+            engine,
+            linker,
+            fuel_limit,
+            trace: false,
+        })
+    }
+
+    /// Whether to return an [`ExecutionTrace`] of every call
+    ///
+    /// Untraced calls only pay for a check at each host call.
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Fuel each call may consume
+    pub fn fuel_limit(&self) -> u64 {
+        self.fuel_limit
+    }
+
+    /// Instantiate a compiled Wasm contract with zeroed state
+    pub fn load(&self, artifact: &CompiledArtifact) -> Result<ContractInstance> {
+        let CompiledArtifact::Wasm(bytes) = artifact else {
+            return Err(SystemError::validation(
+                "artifact",
+                "only Wasm artifacts can be executed",
+                Some("rust source".to_string()),
+            ));
+        };
+        let abi = read_abi(bytes)?;
+        let handle_slots = handle_slots(&abi).into_iter().map(|slot| slot as usize).collect();
+        let module = Module::new(&self.engine, bytes)
+            .map_err(|e| SystemError::validation("artifact", e.to_string(), None))?;
+        let host = HostState {
+            events: abi.events.clone(),
+            ..HostState::default()
+        };
+        let mut store = Store::new(&self.engine, host);
+        store.set_fuel(self.fuel_limit).map_err(|e| runtime_error("setting fuel", e))?;
+        let instance = self
+            .linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| runtime_error("instantiating module", e))?;
+        let memory = instance.get_memory(&mut store, MEMORY_EXPORT).ok_or_else(|| {
+            SystemError::validation("artifact", "module exports no memory", None)
+        })?;
+        let state_size = usize::try_from(state_size(&abi))
+            .ok()
+            .filter(|&size| size <= memory.data_size(&store))
+            .ok_or_else(|| {
+                SystemError::validation("artifact", "memory is smaller than the state", None)
+            })?;
+        Ok(ContractInstance {
+            store: Mutex::new(store),
+            instance,
+            memory,
+            state_size,
+            handle_slots,
+            abi: exports(&abi).into_iter().map(|f| (f.name.clone(), f)).collect(),
+            source_map: SourceMap::from_wasm(bytes),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        })
+    }
+
+    /// Call the exported `method` of `instance`
+    ///
+    /// `args` is a JSON array of the arguments, or `null` for none. The
+    /// result is `null` for functions without a return type. A trap, such as
+    /// a failed `require`, a failed library builtin or running out of fuel is
+    /// a `SystemSpecific` error; the state changes made before it are rolled
+    /// back.
+    pub fn call(&self, instance: &ContractInstance, method: &str, args: Value) -> Result<Value> {
+        Ok(self.execute(instance, method, args)?.return_value)
+    }
+
+    /// [`call`](Self::call), also reporting the fuel used and the events
+    /// emitted
+    ///
+    /// The events are broadcast to the instance's subscribers once the call
+    /// succeeds; a failed call emits none.
+    pub fn execute(
+        &self,
+        instance: &ContractInstance,
+        method: &str,
+        args: Value,
+    ) -> Result<ExecutionOutcome> {
+        let abi = instance
+            .abi
+            .get(method)
+            .ok_or_else(|| SystemError::not_found("contract method", method))?;
+        let args = match args {
+            Value::Null => Vec::new(),
+            Value::Array(args) => args,
+            other => {
+                return Err(SystemError::validation(
+                    "args",
+                    "expected an array of arguments",
+                    Some(other.to_string()),
+                ));
+            },
+        };
+        if args.len() != abi.params.len() {
+            return Err(SystemError::validation(
+                "args",
+                format!("`{method}` takes {} argument(s)", abi.params.len()),
+                Some(args.len().to_string()),
+            ));
+        }
+
+        let mut store = instance.store.lock().unwrap_or_else(PoisonError::into_inner);
+        let values = store.data().values.len();
+        let params = args
+            .iter()
+            .zip(&abi.params)
+            .map(|(arg, ty)| to_wasm(arg, ty, store.data_mut()))
+            .collect::<Result<Vec<_>>>()
+            .inspect_err(|_| store.data_mut().values.truncate(values))?;
+        let func = instance
+            .instance
+            .get_func(&mut *store, method)
+            .ok_or_else(|| SystemError::not_found("contract export", method))?;
+        let mut results = vec![Val::I64(0); usize::from(abi.returns != Ty::Unit)];
+        store.set_fuel(self.fuel_limit).map_err(|e| runtime_error("setting fuel", e))?;
+        store.data_mut().fields.clear();
+        store.data_mut().emitted.clear();
+        store.data_mut().host_calls = self.trace.then(Vec::new);
+        let savepoint = instance.read_state(&store);
+        let outcome = func.call(&mut *store, &params, &mut results);
+        let used = self.fuel_limit - store.get_fuel().unwrap_or(0);
+        let host_calls = store.data_mut().host_calls.take();
+        tracing::debug!("Contract call {} used {} fuel", method, used);
+        if let Err(err) = outcome {
+            instance.write_state(&mut store, &savepoint);
+            store.data_mut().values.truncate(values);
+            let at = instance
+                .trap_location(&err)
+                .map_or_else(String::new, |location| format!(" at {location}"));
+            let message = match (err.downcast_ref::<Trap>(), err.downcast_ref::<Revert>()) {
+                (Some(Trap::OutOfFuel), _) => format!("`{method}` ran out of fuel{at}"),
+                (Some(trap), _) => format!("`{method}` trapped{at}: {trap}"),
+                (None, Some(revert)) => format!("`{method}` failed{at}: {revert}"),
+                (None, None) => format!("`{method}` failed{at}: {err}"),
+            };
+            return Err(SystemError::SystemSpecific {
+                system: "contract_runtime".to_string(),
+                message,
+                context: Some(format!("fuel used: {used}")),
+            });
+        }
+        let return_value = results
+            .first()
+            .map_or(Value::Null, |result| to_json(result, &abi.returns, &store.data().values));
+        instance.compact_values(&mut store);
+        let events = std::mem::take(&mut store.data_mut().emitted);
+        for event in &events {
+            // No subscribers is not an error
+            let _ = instance.events.send(event.clone());
+        }
+        Ok(ExecutionOutcome {
+            return_value,
+            gas_used: used,
+            events,
+            trace: host_calls.map(|calls| instance.trace(method, calls, self.fuel_limit, used)),
+        })
+    }
+
+    /// [`call`](Self::call) with the arguments in `args`, deserializing the
+    /// result as `T`
+    ///
+    /// This is what generated client bindings call.
+    pub fn call_typed<T: DeserializeOwned>(
+        &self,
+        instance: &ContractInstance,
+        method: &str,
+        args: Vec<Value>,
+    ) -> Result<T> {
+        let result = self.call(instance, method, Value::Array(args))?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+impl ContractInstance {
+    /// Set the values the builtins return during later calls
+    pub fn set_context(&self, context: CallContext) {
+        self.store.lock().unwrap_or_else(PoisonError::into_inner).data_mut().context = context;
+    }
+
+    /// Receive the events of successful calls made from now on
+    ///
+    /// A subscriber that falls more than 1024 events behind gets
+    /// `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<ContractEvent> {
+        self.events.subscribe()
+    }
+
+    /// Exported functions, in no particular order
+    pub fn methods(&self) -> impl Iterator<Item = &AbiFunction> {
+        self.abi.values()
+    }
+
+    /// Copy of the current state variables
+    ///
+    /// Waits for a call in progress to finish.
+    pub fn snapshot(&self) -> ContractState {
+        let store = self.store.lock().unwrap_or_else(PoisonError::into_inner);
+        ContractState {
+            bytes: self.read_state(&store),
+            values: store.data().values.clone(),
+        }
+    }
+
+    /// Put back the state variables of an earlier [`snapshot`](Self::snapshot)
+    ///
+    /// Fails with a `Validation` error if `state` is not the size of this
+    /// contract's state, e.g. because it was taken of another contract.
+    pub fn restore(&self, state: &ContractState) -> Result<()> {
+        if state.bytes.len() != self.state_size {
+            return Err(SystemError::validation(
+                "state",
+                format!("expected {} bytes of state", self.state_size),
+                Some(state.bytes.len().to_string()),
+            ));
+        }
+        let mut store = self.store.lock().unwrap_or_else(PoisonError::into_inner);
+        self.write_state(&mut store, &state.bytes);
+        store.data_mut().values.clone_from(&state.values);
+        Ok(())
+    }
+
+    /// Drop the values the state does not refer to, renumbering handles
+    fn compact_values(&self, store: &mut Store<HostState>) {
+        let old = std::mem::take(&mut store.data_mut().values);
+        let mut values = Vec::with_capacity(self.handle_slots.len());
+        for &slot in &self.handle_slots {
+            let memory = self.memory.data_mut(&mut *store);
+            let bytes = &mut memory[slot..slot + 8];
+            let handle = u64::from_le_bytes(bytes.try_into().expect("slots are 8 bytes"));
+            let value = usize::try_from(handle).ok().and_then(|h| old.get(h.checked_sub(1)?));
+            let handle = match value {
+                Some(value) => {
+                    values.push(value.clone());
+                    values.len() as u64
+                },
+                None => 0,
+            };
+            bytes.copy_from_slice(&handle.to_le_bytes());
+        }
+        store.data_mut().values = values;
+    }
+
+    fn read_state(&self, store: &Store<HostState>) -> Vec<u8> {
+        self.memory.data(store)[..self.state_size].to_vec()
+    }
+
+    fn write_state(&self, store: &mut Store<HostState>, state: &[u8]) {
+        self.memory.data_mut(store)[..self.state_size].copy_from_slice(state);
+    }
+
+    /// Map from the module back to the contract source, if it has one
+    pub fn source_map(&self) -> Option<&SourceMap> {
+        self.source_map.as_ref()
+    }
+
+    /// Trace of a call of `method` that used `used` fuel, cut at `host_calls`
+    fn trace(
+        &self,
+        method: &str,
+        host_calls: Vec<HostCall>,
+        fuel_limit: u64,
+        used: u64,
+    ) -> ExecutionTrace {
+        let mut samples = Vec::with_capacity(host_calls.len() + 1);
+        let mut spent = 0;
+        for call in host_calls {
+            let at = fuel_limit.saturating_sub(call.fuel);
+            let stack = call.frames.iter().rev();
+            let stack = stack.map(|&(index, offset)| self.function_name(index, offset)).collect();
+            let offset = call.frames.first().and_then(|&(_, offset)| offset);
+            let source_map = self.source_map.as_ref();
+            samples.push(TraceSample {
+                stack,
+                gas: at.saturating_sub(spent),
+                host_call: Some(call.name.to_string()),
+                location: offset.and_then(|offset| source_map?.symbolicate(offset)),
+            });
+            spent = at;
+        }
+        // After the last host call, the rest is attributed to the method
+        samples.push(TraceSample {
+            stack: vec![method.to_string()],
+            gas: used.saturating_sub(spent),
+            host_call: None,
+            location: None,
+        });
+        ExecutionTrace {
+            method: method.to_string(),
+            total_gas: used,
+            samples,
+        }
+    }
+
+    /// Name of the function of a backtrace frame, from the source map
+    fn function_name(&self, index: u32, offset: Option<usize>) -> String {
+        offset
+            .and_then(|offset| self.source_map.as_ref()?.mapping_at(offset))
+            .map_or_else(|| format!("func[{index}]"), |mapping| mapping.function.clone())
+    }
+
+    /// Source location of the innermost contract frame of a failed call
+    fn trap_location(&self, err: &wasmtime::Error) -> Option<SourceLocation> {
+        let source_map = self.source_map.as_ref()?;
+        let backtrace = err.downcast_ref::<WasmBacktrace>()?;
+        backtrace
+            .frames()
+            .iter()
+            .filter_map(|frame| frame.module_offset())
+            .find_map(|offset| source_map.symbolicate(offset))
+    }
+}
+
+impl Default for ContractExecutor {
+    fn default() -> Self {
+        Self::new(DEFAULT_FUEL_LIMIT).expect("the default engine configuration is valid")
+    }
+}
+
+impl std::fmt::Debug for ContractExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContractExecutor")
+            .field("fuel_limit", &self.fuel_limit)
+            .field("trace", &self.trace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for ContractInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContractInstance")
+            .field("methods", &self.abi.len())
+            .finish_non_exhaustive()
+    }
+}
+
+fn runtime_error(operation: &str, err: impl std::fmt::Display) -> SystemError {
+    SystemError::internal(format!("{operation}: {err}"), None)
+}
+
+/// The ABI custom section of a module
+fn read_abi(bytes: &[u8]) -> Result<ContractAbi> {
+    for payload in Parser::new(0).parse_all(bytes) {
+        let payload =
+            payload.map_err(|e| SystemError::validation("artifact", e.to_string(), None))?;
+        if let Payload::CustomSection(section) = payload {
+            if section.name() == ABI_SECTION {
+                return Ok(serde_json::from_slice(section.data())?);
+            }
+        }
+    }
+    Err(SystemError::validation(
+        "artifact",
+        format!("module has no `{ABI_SECTION}` section"),
+        None,
+    ))
+}
+
+fn to_wasm(arg: &Value, ty: &Ty, host: &mut HostState) -> Result<Val> {
+    let value = match ty {
+        Ty::U64 | Ty::Address => arg.as_u64().map(|v| Val::I64(v as i64)),
+        Ty::I64 => arg.as_i64().map(Val::I64),
+        Ty::Bool => arg.as_bool().map(|v| Val::I32(i32::from(v))),
+        Ty::String => arg.as_str().map(|v| Val::I32(host.push(HostValue::Str(v.to_string())))),
+        Ty::Map => arg
+            .as_object()
+            .and_then(|entries| {
+                let entries = entries.iter().map(|(key, v)| Some((key.clone(), v.as_u64()?)));
+                entries.collect::<Option<BTreeMap<_, _>>>()
+            })
+            .map(|entries| Val::I32(host.push(HostValue::Map(entries)))),
+        Ty::Array { .. } | Ty::Unit => None,
+    };
+    value.ok_or_else(|| {
+        SystemError::validation("args", format!("expected a `{ty}` value"), Some(arg.to_string()))
+    })
+}
+
+/// JSON of a Wasm value of type `ty`, resolving handles in `values`
+fn to_json(result: &Val, ty: &Ty, values: &[HostValue]) -> Value {
+    let handle = match (result, ty) {
+        (Val::I64(v), Ty::String | Ty::Map) => *v as usize,
+        (Val::I32(v), Ty::String | Ty::Map) => *v as u32 as usize,
+        (Val::I64(v), Ty::I64) => return Value::from(*v),
+        (Val::I64(v), Ty::Bool) => return Value::Bool(*v != 0),
+        (Val::I64(v), _) => return Value::from(*v as u64),
+        (Val::I32(v), _) => return Value::Bool(*v != 0),
+        _ => return Value::Null,
+    };
+    match (handle.checked_sub(1).and_then(|index| values.get(index)), ty) {
+        (Some(HostValue::Str(value)), _) => Value::from(value.as_str()),
+        (Some(HostValue::Map(entries)), _) => {
+            entries.iter().map(|(key, value)| (key.clone(), Value::from(*value))).collect()
+        },
+        (None, Ty::String) => Value::from(""),
+        (None, _) => Value::Object(serde_json::Map::new()),
+    }
+}
+
+/// Define the host function of `builtin`
+fn link_builtin(linker: &mut Linker<HostState>, builtin: Builtin) -> wasmtime::Result<()> {
+    type Caller<'a> = wasmtime::Caller<'a, HostState>;
+    let name = builtin.symbol();
+    match builtin {
+        Builtin::Caller | Builtin::Now => {
+            let read: fn(&CallContext) -> u64 = match builtin {
+                Builtin::Caller => |context| context.caller,
+                _ => |context| context.now,
+            };
+            linker.func_wrap("env", name, move |mut caller: Caller<'_>| {
+                record_host_call(&mut caller, name);
+                read(&caller.data().context) as i64
+            })?
+        },
+        Builtin::MapInsert => linker.func_wrap(
+            "env",
+            name,
+            |mut caller: Caller<'_>, map: i32, key: i32, value: i64| {
+                record_host_call(&mut caller, name);
+                let host = caller.data_mut();
+                let mut entries = host.map(map)?.into_owned();
+                entries.insert(host.string(key)?.into_owned(), value as u64);
+                Ok(host.push(HostValue::Map(entries)))
+            },
+        )?,
+        Builtin::MapGet => {
+            linker.func_wrap("env", name, |mut caller: Caller<'_>, map: i32, key: i32| {
+                record_host_call(&mut caller, name);
+                let host = caller.data();
+                let key = host.string(key)?;
+                match host.map(map)?.get(key.as_ref()) {
+                    Some(value) => Ok(*value as i64),
+                    None => Err(Revert(stdlib::missing_key(&key)).into()),
+                }
+            })?
+        },
+        Builtin::MapRemove => {
+            linker.func_wrap("env", name, |mut caller: Caller<'_>, map: i32, key: i32| {
+                record_host_call(&mut caller, name);
+                let host = caller.data_mut();
+                let mut entries = host.map(map)?.into_owned();
+                entries.remove(host.string(key)?.as_ref());
+                Ok(host.push(HostValue::Map(entries)))
+            })?
+        },
+        Builtin::MapContains => {
+            linker.func_wrap("env", name, |mut caller: Caller<'_>, map: i32, key: i32| {
+                record_host_call(&mut caller, name);
+                let host = caller.data();
+                Ok(i32::from(host.map(map)?.contains_key(host.string(key)?.as_ref())))
+            })?
+        },
+        _ => linker.func_wrap("env", name, move |mut caller: Caller<'_>, a: i64, b: i64| {
+            record_host_call(&mut caller, name);
+            match stdlib::assert_failure(builtin, a as u64, b as u64) {
+                Some(message) => Err(Revert(message).into()),
+                None => Ok(()),
+            }
+        })?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{CompilationTarget, CompilerConfig, ContractCompiler};
+
+    fn load(executor: &ContractExecutor, source: &str) -> ContractInstance {
+        let compiler = ContractCompiler::new(CompilerConfig {
+            target: CompilationTarget::Wasm,
+            ..CompilerConfig::default()
+        })
+        .unwrap();
+        executor.load(&compiler.compile(source).unwrap().into()).unwrap()
+    }
+
+    const WALLET: &str = "contract Wallet {
+        state { owner: address; balances: [u64; 4]; offset: i64; }
+        fn deposit(slot: u64, amount: u64) -> u64 {
+            require(caller() == self.owner, \"owner only\");
+            self.balances[slot] = self.balances[slot] + amount;
+            return self.balances[slot];
+        }
+        fn shift(by: i64) -> i64 {
+            self.offset = self.offset + by;
+            return self.offset;
+        }
+        fn is_empty(slot: u64) -> bool { return self.balances[slot] == 0; }
+        fn spin(times: u64) {
+            if times > 0 { spin(times - 1); }
+        }
+    }";
+
+    #[test]
+    fn test_call_converts_json() {
+        let executor = ContractExecutor::default();
+        let wallet = load(&executor, WALLET);
+        assert_eq!(wallet.methods().count(), 11);
+
+        executor.call(&wallet, "set_owner", json!([7])).unwrap();
+        wallet.set_context(CallContext { caller: 7, now: 0 });
+        let big = u64::MAX - 1;
+        assert_eq!(executor.call(&wallet, "deposit", json!([2, big])).unwrap(), json!(big));
+        assert_eq!(executor.call(&wallet, "get_balances", json!([2])).unwrap(), json!(big));
+        assert_eq!(executor.call(&wallet, "shift", json!([-5])).unwrap(), json!(-5));
+        assert_eq!(executor.call(&wallet, "is_empty", json!([1])).unwrap(), json!(true));
+        assert_eq!(executor.call(&wallet, "_init", Value::Null).unwrap(), Value::Null);
+        assert_eq!(executor.call(&wallet, "is_empty", json!([2])).unwrap(), json!(true));
+
+        // Bad calls are rejected before running anything
+        assert!(matches!(
+            executor.call(&wallet, "withdraw", Value::Null),
+            Err(SystemError::NotFound { .. })
+        ));
+        for args in [json!([1]), json!([1, -1]), json!([true, 1]), json!({"slot": 1})] {
+            let result = executor.call(&wallet, "deposit", args.clone());
+            assert!(matches!(result, Err(SystemError::Validation { .. })), "{args}");
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let executor = ContractExecutor::default();
+        let token = load(
+            &executor,
+            "contract Token {
+                event Minted { to: address, amount: u64 }
+                event Moved { delta: i64, burned: bool }
+                fn mint(to: address, amount: u64) -> u64 {
+                    emit Minted { amount: amount, to: to };
+                    emit Moved { delta: -1, burned: amount == 0 };
+                    require(amount < 100, \"too much\");
+                    return amount;
+                }
+            }",
+        );
+        let mut subscriber = token.subscribe();
+
+        let outcome = executor.execute(&token, "mint", json!([7, 5])).unwrap();
+        assert_eq!(outcome.return_value, json!(5));
+        assert!(outcome.gas_used > 0);
+        let names: Vec<_> = outcome.events.iter().map(|event| event.name.as_str()).collect();
+        assert_eq!(names, ["Minted", "Moved"]);
+        // Fields are in declaration order whatever order `emit` sets them in
+        assert_eq!(
+            outcome.events[0].fields,
+            [("to".to_string(), json!(7)), ("amount".to_string(), json!(5))]
+        );
+        assert_eq!(outcome.events[1].field("delta"), Some(&json!(-1)));
+        assert_eq!(outcome.events[1].field("burned"), Some(&json!(false)));
+        assert_eq!(subscriber.try_recv().unwrap(), outcome.events[0]);
+        assert_eq!(subscriber.try_recv().unwrap(), outcome.events[1]);
+
+        // A failed call emits nothing
+        assert!(executor.execute(&token, "mint", json!([7, 100])).is_err());
+        assert!(subscriber.try_recv().is_err());
+        let outcome = executor.execute(&token, "mint", json!([8, 0])).unwrap();
+        assert_eq!(outcome.events.len(), 2);
+        assert_eq!(outcome.events[1].field("burned"), Some(&json!(true)));
+    }
+
+    #[test]
+    fn test_strings_and_maps() {
+        let executor = ContractExecutor::default();
+        let registry = load(
+            &executor,
+            "import std::map;
+            import std::assert;
+            contract Registry {
+                state { balances: map; label: string; }
+                event Labeled { label: string, balances: map }
+                fn set(name: string, amount: u64) -> u64 {
+                    self.balances = map::insert(self.balances, name, amount);
+                    return map::get(self.balances, name);
+                }
+                fn take(name: string) -> u64 {
+                    let amount = map::get(self.balances, name);
+                    self.balances = map::remove(self.balances, name);
+                    return amount;
+                }
+                fn relabel(label: string) -> bool {
+                    let changed = self.label != label;
+                    self.label = label;
+                    emit Labeled { label: label, balances: self.balances };
+                    return changed && label != \"\";
+                }
+                fn check(a: u64, b: u64) { assert::require_gt(a, b); }
+            }",
+        );
+        assert_eq!(executor.call(&registry, "set", json!(["alice", 5])).unwrap(), json!(5));
+        assert_eq!(executor.call(&registry, "set", json!(["bob", 7])).unwrap(), json!(7));
+        let balances = executor.call(&registry, "get_balances", Value::Null).unwrap();
+        assert_eq!(balances, json!({"alice": 5, "bob": 7}));
+        let outcome = executor.execute(&registry, "relabel", json!(["x"])).unwrap();
+        assert_eq!(outcome.return_value, json!(true));
+        assert_eq!(outcome.events[0].field("label"), Some(&json!("x")));
+        assert_eq!(outcome.events[0].field("balances"), Some(&balances));
+        assert_eq!(executor.call(&registry, "relabel", json!(["x"])).unwrap(), json!(false));
+        assert_eq!(executor.call(&registry, "get_label", Value::Null).unwrap(), json!("x"));
+        // Only the values the state refers to are kept between calls
+        let before = registry.snapshot();
+        assert_eq!(before.values.len(), 2);
+
+        // Library builtins revert with their message and roll back
+        let err = executor.call(&registry, "take", json!(["carol"])).unwrap_err().to_string();
+        assert!(err.contains("`take` failed at 11:"), "{err}");
+        assert!(err.ends_with("reverted: std::map: no value for key \"carol\""), "{err}");
+        let err = executor.call(&registry, "check", json!([1, 2])).unwrap_err().to_string();
+        assert!(err.ends_with(": reverted: std::assert: require_gt failed: 1 > 2"), "{err}");
+        assert_eq!(registry.snapshot(), before);
+        assert_eq!(executor.call(&registry, "take", json!(["alice"])).unwrap(), json!(5));
+        let balances = executor.call(&registry, "get_balances", Value::Null).unwrap();
+        assert_eq!(balances, json!({"bob": 7}));
+
+        executor.call(&registry, "set_balances", json!([{"z": 1}])).unwrap();
+        registry.restore(&before).unwrap();
+        let balances = executor.call(&registry, "get_balances", Value::Null).unwrap();
+        assert_eq!(balances, json!({"alice": 5, "bob": 7}));
+        for (method, args) in [("set_balances", json!([{"z": -1}])), ("set", json!([5, 1]))] {
+            let result = executor.call(&registry, method, args.clone());
+            assert!(matches!(result, Err(SystemError::Validation { .. })), "{args}");
+        }
+        assert_eq!(registry.snapshot(), before);
+    }
+
+    #[test]
+    fn test_traps_and_fuel() {
+        let executor = ContractExecutor::new(10_000).unwrap();
+        let wallet = load(&executor, WALLET);
+
+        // `require` fails unless the caller owns the wallet
+        executor.call(&wallet, "set_owner", json!([7])).unwrap();
+        let err = executor.call(&wallet, "deposit", json!([0, 1])).unwrap_err();
+        assert!(err.to_string().contains("trapped"), "{err}");
+        let err = executor.call(&wallet, "get_balances", json!([4])).unwrap_err();
+        assert!(err.to_string().contains("trapped"), "{err}");
+
+        assert!(executor.call(&wallet, "spin", json!([10])).is_ok());
+        let err = executor.call(&wallet, "spin", json!([1_000_000])).unwrap_err();
+        assert!(err.to_string().contains("ran out of fuel"), "{err}");
+        // Each call gets a fresh budget
+        assert!(executor.call(&wallet, "spin", json!([10])).is_ok());
+
+        let rust = CompiledArtifact::RustSource(String::new());
+        assert!(executor.load(&rust).is_err());
+    }
+
+    #[test]
+    fn test_failed_calls_roll_back_state() {
+        let executor = ContractExecutor::new(10_000).unwrap();
+        let vault = load(
+            &executor,
+            "contract Vault {
+                state { total: u64; slots: [u64; 2]; }
+                fn store(slot: u64, amount: u64) {
+                    self.total = self.total + amount;
+                    self.slots[slot] = amount;
+                    require(amount < 100, \"too much\");
+                }
+                fn burn(amount: u64) {
+                    self.total = amount;
+                    if amount > 0 { burn(amount + 1); }
+                }
+            }",
+        );
+        executor.call(&vault, "store", json!([0, 5])).unwrap();
+        let before = vault.snapshot();
+
+        assert!(executor.call(&vault, "store", json!([1, 100])).is_err());
+        assert_eq!(executor.call(&vault, "get_total", Value::Null).unwrap(), json!(5));
+        assert_eq!(executor.call(&vault, "get_slots", json!([1])).unwrap(), json!(0));
+        // Running out of fuel rolls back too
+        let err = executor.call(&vault, "burn", json!([1])).unwrap_err();
+        assert!(err.to_string().contains("ran out of fuel"), "{err}");
+        assert_eq!(vault.snapshot(), before);
+        assert_eq!(before.as_bytes().len(), 24);
+    }
+
+    #[test]
+    fn test_nested_savepoints() {
+        let executor = ContractExecutor::default();
+        let wallet = load(&executor, WALLET);
+        let balance = |slot: u64| executor.call(&wallet, "get_balances", json!([slot])).unwrap();
+        executor.call(&wallet, "set_owner", json!([7])).unwrap();
+        wallet.set_context(CallContext { caller: 7, now: 0 });
+
+        let outer = wallet.snapshot();
+        executor.call(&wallet, "deposit", json!([0, 10])).unwrap();
+        let inner = wallet.snapshot();
+        executor.call(&wallet, "deposit", json!([1, 20])).unwrap();
+        executor.call(&wallet, "shift", json!([-3])).unwrap();
+
+        // Restoring the inner savepoint only undoes what came after it
+        wallet.restore(&inner).unwrap();
+        assert_eq!(balance(0), json!(10));
+        assert_eq!(balance(1), json!(0));
+        assert_eq!(executor.call(&wallet, "shift", json!([0])).unwrap(), json!(0));
+        wallet.restore(&outer).unwrap();
+        assert_eq!(balance(0), json!(0));
+        assert_eq!(executor.call(&wallet, "get_owner", Value::Null).unwrap(), json!(7));
+
+        let other = load(&executor, "contract Empty { fn ping() {} }");
+        let err = other.restore(&outer).unwrap_err();
+        assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+    }
+
+    #[test]
+    fn test_trace_attributes_fuel_to_functions() {
+        const NESTED: &str = "contract Nested {
+            state { total: u64; }
+            fn leaf(x: u64) -> u64 {
+                let t = now();
+                return x * 3 + t;
+            }
+            fn middle(x: u64) -> u64 {
+                let a = leaf(x);
+                let b = leaf(a + 1);
+                return a + b + now();
+            }
+            fn run(x: u64) -> u64 {
+                let m = middle(x);
+                self.total = self.total + m + now();
+                return m;
+            }
+        }";
+        let executor = ContractExecutor::default().with_trace(true);
+        let nested = load(&executor, NESTED);
+        let outcome = executor.execute(&nested, "run", json!([2])).unwrap();
+        let trace = outcome.trace.unwrap();
+        assert_eq!(trace.total_gas, outcome.gas_used);
+
+        let calls: Vec<_> = trace.samples.iter().map(|s| s.host_call.as_deref()).collect();
+        assert_eq!(calls, [Some("now"), Some("now"), Some("now"), Some("now"), None]);
+        assert_eq!(trace.samples[0].stack, ["run", "middle", "leaf"]);
+        assert_eq!(trace.samples[0].depth(), 3);
+        assert_eq!(trace.samples[0].location.as_ref().map(|l| l.line), Some(4));
+        assert_eq!(trace.samples[2].stack, ["run", "middle"]);
+        assert_eq!(trace.samples[3].stack, ["run"]);
+
+        let by_function = trace.function_gas();
+        assert_eq!(by_function.keys().collect::<Vec<_>>(), ["leaf", "middle", "run"]);
+        assert_eq!(by_function.values().sum::<u64>(), outcome.gas_used);
+        let tree = trace.tree();
+        assert_eq!(tree.function, "run");
+        assert_eq!(tree.total_gas, outcome.gas_used);
+        let middle = &tree.children[0];
+        assert_eq!(middle.children[0].function, "leaf");
+        assert_eq!(middle.total_gas, middle.self_gas + middle.children[0].total_gas);
+        assert_eq!(trace.hotspots(1).len(), 1);
+
+        let mut folded = 0;
+        for line in trace.folded().lines() {
+            let (stack, gas) = line.rsplit_once(' ').unwrap();
+            assert!(stack.starts_with("run"), "{line}");
+            folded += gas.parse::<u64>().unwrap();
+        }
+        assert_eq!(folded, outcome.gas_used);
+
+        // Tracing is off by default
+        let executor = ContractExecutor::default();
+        let nested = load(&executor, NESTED);
+        let untraced = executor.execute(&nested, "run", json!([2])).unwrap();
+        assert_eq!(untraced.trace, None);
+        assert_eq!(untraced.gas_used, outcome.gas_used);
+    }
+
+    #[test]
+    fn test_traps_are_symbolicated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.contract");
+        std::fs::write(&path, WALLET).unwrap();
+        let compiler = ContractCompiler::new(CompilerConfig {
+            target: CompilationTarget::Wasm,
+            ..CompilerConfig::default()
+        })
+        .unwrap();
+        let output = compiler.compile_path(&path).unwrap();
+        let file = std::fs::canonicalize(&path).unwrap().display().to_string();
+
+        let source_map = output.source_map().unwrap();
+        let require = source_map.mappings.iter().find(|m| m.function == "deposit").unwrap();
+        assert_eq!(
+            source_map.symbolicate(require.start),
+            Some(SourceLocation {
+                file: Some(file.clone()),
+                line: 4,
+                column: 13,
+            })
+        );
+
+        let executor = ContractExecutor::default();
+        let wallet = executor.load(&output.into()).unwrap();
+        assert!(wallet.source_map().is_some());
+        executor.call(&wallet, "set_owner", json!([7])).unwrap();
+        let err = executor.call(&wallet, "deposit", json!([0, 1])).unwrap_err();
+        assert!(err.to_string().contains(&format!("trapped at {file}:4:13")), "{err}");
+        // Accessors map to the state variable
+        let err = executor.call(&wallet, "get_balances", json!([4])).unwrap_err();
+        assert!(err.to_string().contains(&format!("trapped at {file}:2:")), "{err}");
+    }
+}
@@ -0,0 +1,1119 @@
+//! Rust code generation module
+//!
+//! Lowers a type-checked [`hir::Contract`] to a self-contained Rust module:
+//!
+//! - The contract becomes a struct with one public field per state
+//!   variable, zeroed by `new()`.
+//! - Every contract function becomes a method taking the runtime as
+//!   `env: &dyn Env` and returning `Result<_, ContractError>`; a failed
+//!   `require` or `assert` returns early with an error.
+//! - Integer arithmetic wraps, while division by zero and out-of-bounds
+//!   indices are errors.
+//! - Library functions become private methods named after their module,
+//!   as in `math_min` for `math::min`.
+//! - `std::map` and `std::assert` builtins become free functions named
+//!   after their symbol, as in `std_map_insert`, and `map` is a
+//!   `BTreeMap<String, u64>`. Only the ones the contract uses are emitted.
+//! - Events become the variants of an `Event` enum, and `emit` hands one
+//!   to `Env::emit`.
+//! - A `#[cfg(test)]` module stubs the runtime and calls every contract
+//!   function once, as a starting point for contract tests.
+//! - The [`ContractAbi`] is embedded as JSON in the `ABI` constant.
+//! - A `// dsl:<location>` comment precedes the first statement of every
+//!   source line, for [`SourceMap`](crate::source_map::SourceMap).
+//!
+//! Output is laid out the way `rustfmt` lays it out with default settings,
+//! so it can be checked in and formatted without churn. Expressions long
+//! enough for `rustfmt` to wrap are emitted on one line regardless.
+
+use std::collections::HashSet;
+
+use shared_core::{Result, SystemError};
+
+use crate::abi::{ContractAbi, RUST_ABI_CONST};
+use crate::codegen::block_calls;
+use crate::ast::{BinaryOp, Span, UnaryOp};
+use crate::hir::{self, Builtin, Callee, LocalId, Ty};
+use crate::source_map::{SourceLocation, RUST_MARKER};
+
+/// Width `rustfmt` fits items into
+pub(crate) const MAX_WIDTH: usize = 100;
+/// Widest struct literal body `rustfmt` keeps on one line
+const STRUCT_LIT_WIDTH: usize = 18;
+/// Widest struct-like enum variant body `rustfmt` keeps on one line
+const STRUCT_VARIANT_WIDTH: usize = 35;
+/// Indentation of one nesting level
+const INDENT: &str = "    ";
+
+/// Names the generated module defines or relies on at type level
+const RESERVED_TYPES: &[&str] = &[
+    "Address",
+    "ContractError",
+    "Default",
+    "Env",
+    "Err",
+    "Event",
+    "Map",
+    "None",
+    "Ok",
+    "Option",
+    "Result",
+    "Self",
+    "Some",
+    "String",
+    "TestEnv",
+];
+/// Names the generated methods use for their own bindings
+const RESERVED_LOCALS: &[&str] = &[
+    "Err",
+    "None",
+    "Ok",
+    "Some",
+    "checked_index",
+    "crate",
+    "env",
+    "self",
+    "super",
+];
+/// Rust keywords that are valid contract names, emitted as raw identifiers
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+    "dyn", "enum", "extern", "final", "for", "impl", "in", "loop", "macro", "match", "mod",
+    "move", "mut", "override", "priv", "pub", "ref", "static", "struct", "trait", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Generate Rust source for `contract`
+///
+/// The code is wrapped in `pub mod <module_name>`, by default the contract
+/// name in snake case. Names that would clash with the generated code are
+/// `Validation` errors.
+pub fn generate_rust(contract: &hir::Contract, module_name: Option<&str>) -> Result<String> {
+    let module = match module_name {
+        Some(name) => name.to_string(),
+        None => snake_case(&contract.name),
+    };
+    let module = ident(&module).map_err(|reason| {
+        SystemError::config(format!("invalid module name `{module}`: {reason}"), None)
+    })?;
+    check_names(contract)?;
+
+    let mut out = Writer::default();
+    out.line(&format!(
+        "//! Generated from contract `{}` by contract_executable_compiler; do not edit.",
+        contract.name
+    ));
+    out.blank();
+    out.line("#[allow(dead_code, unreachable_code, unused_must_use, unused_variables)]");
+    out.open(&format!("pub mod {module} {{"));
+    out.raw(PRELUDE);
+    out.blank();
+    if !contract.events.is_empty() {
+        event_enum(&mut out, contract);
+        out.blank();
+    }
+    env_trait(&mut out, contract);
+    out.blank();
+    out.raw(CHECKED_INDEX);
+    out.blank();
+    std_items(&mut out, contract);
+    let abi = serde_json::to_string(&ContractAbi::from_contract(contract))?;
+    out.line("/// Interface of the contract, as JSON");
+    out.line(&format!("{RUST_ABI_CONST}{abi}\"#;"));
+    out.blank();
+    contract_struct(&mut out, contract);
+    out.blank();
+    contract_impl(&mut out, contract)?;
+    out.blank();
+    test_scaffold(&mut out, contract);
+    out.close("}");
+    Ok(out.finish())
+}
+
+/// Definitions every generated module starts with
+const PRELUDE: &str = r#"/// Account address
+pub type Address = u64;
+
+/// Reason a contract call failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractError {
+    /// A `require` condition or a standard library check did not hold
+    Reverted(String),
+    /// An `assert` condition did not hold
+    AssertionFailed(&'static str),
+    /// An array index was out of bounds
+    IndexOutOfBounds { index: u64, len: u64 },
+    /// Division by zero or overflow
+    Arithmetic,
+}
+
+impl std::fmt::Display for ContractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reverted(message) => write!(f, "reverted: {message}"),
+            Self::AssertionFailed(message) => write!(f, "assertion failed: {message}"),
+            Self::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for length {len}")
+            }
+            Self::Arithmetic => f.write_str("division by zero or overflow"),
+        }
+    }
+}
+
+impl std::error::Error for ContractError {}"#;
+
+/// Bounds check every indexing goes through
+const CHECKED_INDEX: &str =
+    r#"fn checked_index(index: u64, len: u64) -> Result<usize, ContractError> {
+    if index < len {
+        Ok(index as usize)
+    } else {
+        Err(ContractError::IndexOutOfBounds { index, len })
+    }
+}"#;
+
+/// `map` type, emitted for contracts that use it
+const MAP_TYPE: &str = r#"/// `map` values, from `string` keys to `u64` values
+pub type Map = std::collections::BTreeMap<String, u64>;"#;
+
+/// Helper function implementing a standard library builtin
+fn std_helper(builtin: Builtin) -> String {
+    let name = format!("std_{}", builtin.symbol());
+    let module = builtin.module().expect("only library builtins have helpers");
+    let doc = format!("/// `{}::{}`", module.trim_start_matches("std::"), builtin.name());
+    let body = match builtin {
+        Builtin::MapInsert => format!(
+            "fn {name}(mut entries: Map, key: String, value: u64) -> Map {{
+    entries.insert(key, value);
+    entries
+}}"
+        ),
+        Builtin::MapGet => format!(
+            "fn {name}(entries: Map, key: String) -> Result<u64, ContractError> {{
+    match entries.get(&key) {{
+        Some(value) => Ok(*value),
+        None => Err(ContractError::Reverted(format!(
+            \"std::map: no value for key {{key:?}}\"
+        ))),
+    }}
+}}"
+        ),
+        Builtin::MapRemove => format!(
+            "fn {name}(mut entries: Map, key: String) -> Map {{
+    entries.remove(&key);
+    entries
+}}"
+        ),
+        Builtin::MapContains => format!(
+            "fn {name}(entries: Map, key: String) -> bool {{
+    entries.contains_key(&key)
+}}"
+        ),
+        _ => {
+            let op = builtin.comparison().expect("`std::assert` builtins are comparisons");
+            let message = format!("std::assert: {} failed: {{a}} {op} {{b}}", builtin.name());
+            format!(
+                "fn {name}(a: u64, b: u64) -> Result<(), ContractError> {{
+    if a {op} b {{
+        Ok(())
+    }} else {{
+        Err(ContractError::Reverted(format!(
+            {message:?}
+        )))
+    }}
+}}"
+            )
+        },
+    };
+    format!("{doc}\n{body}")
+}
+
+/// The `map` type and the helpers of the library builtins `contract` calls
+fn std_items(out: &mut Writer, contract: &hir::Contract) {
+    if uses_map(contract) {
+        out.raw(MAP_TYPE);
+        out.blank();
+    }
+    for builtin in Builtin::ALL {
+        let called = contract.functions.iter().any(|f| block_calls(&f.body, builtin));
+        if builtin.module().is_some() && called {
+            out.raw(&std_helper(builtin));
+            out.blank();
+        }
+    }
+}
+
+/// Indented line buffer
+#[derive(Default)]
+pub(crate) struct Writer {
+    buf: String,
+    pub(crate) depth: usize,
+}
+
+impl Writer {
+    pub(crate) fn indent(&self) -> usize {
+        self.depth * INDENT.len()
+    }
+
+    pub(crate) fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.buf.push_str(INDENT);
+        }
+        self.buf.push_str(line);
+        self.buf.push('\n');
+    }
+
+    pub(crate) fn blank(&mut self) {
+        self.buf.push('\n');
+    }
+
+    /// Write `line` and indent what follows
+    pub(crate) fn open(&mut self, line: &str) {
+        self.line(line);
+        self.depth += 1;
+    }
+
+    /// Dedent and write `line`
+    pub(crate) fn close(&mut self, line: &str) {
+        self.depth -= 1;
+        self.line(line);
+    }
+
+    /// Write a multi-line block at the current indentation
+    pub(crate) fn raw(&mut self, block: &str) {
+        for line in block.lines() {
+            if line.is_empty() {
+                self.blank();
+            } else {
+                self.line(line);
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.buf
+    }
+}
+
+fn event_enum(out: &mut Writer, contract: &hir::Contract) {
+    out.line("/// Events the contract emits");
+    out.line("#[derive(Debug, Clone, PartialEq, Eq)]");
+    out.open("pub enum Event {");
+    for event in &contract.events {
+        let fields: Vec<_> = event
+            .fields
+            .iter()
+            .map(|field| format!("{}: {}", raw(&field.name), rust_type(&field.ty)))
+            .collect();
+        let body = fields.join(", ");
+        let name = raw(&event.name);
+        out.line(&format!("/// Event `{}`", event.name));
+        if fields.is_empty() {
+            out.line(&format!("{name} {{}},"));
+        } else if body.len() <= STRUCT_VARIANT_WIDTH {
+            out.line(&format!("{name} {{ {body} }},"));
+        } else {
+            out.open(&format!("{name} {{"));
+            for field in &fields {
+                out.line(&format!("{field},"));
+            }
+            out.close("},");
+        }
+    }
+    out.close("}");
+}
+
+fn env_trait(out: &mut Writer, contract: &hir::Contract) {
+    out.line("/// Services the runtime provides to the contract");
+    out.open("pub trait Env {");
+    out.line("/// Account calling the contract");
+    out.line("fn caller(&self) -> Address;");
+    out.line("/// Block time in seconds");
+    out.line("fn now(&self) -> u64;");
+    if !contract.events.is_empty() {
+        out.line("/// Record an event the contract emitted");
+        out.line("fn emit(&self, event: Event);");
+    }
+    out.close("}");
+}
+
+fn contract_struct(out: &mut Writer, contract: &hir::Contract) {
+    let name = &contract.name;
+    out.line(&format!("/// State of contract `{name}`"));
+    out.line("#[derive(Debug, Clone, PartialEq, Eq)]");
+    if contract.state.is_empty() {
+        out.line(&format!("pub struct {name} {{}}"));
+        return;
+    }
+    out.open(&format!("pub struct {name} {{"));
+    for var in &contract.state {
+        out.line(&format!("pub {}: {},", raw(&var.name), rust_type(&var.ty)));
+    }
+    out.close("}");
+}
+
+fn contract_impl(out: &mut Writer, contract: &hir::Contract) -> Result<()> {
+    let name = &contract.name;
+    out.open(&format!("impl {name} {{"));
+    out.line("/// Zeroed state");
+    out.open("pub fn new() -> Self {");
+    let fields: Vec<_> = contract
+        .state
+        .iter()
+        .map(|var| format!("{}: {}", raw(&var.name), zero_value(&var.ty)))
+        .collect();
+    struct_literal(out, "Self", &fields, "");
+    out.close("}");
+    for function in &contract.functions {
+        out.blank();
+        method(out, contract, function)?;
+    }
+    out.close("}");
+    out.blank();
+    out.open(&format!("impl Default for {name} {{"));
+    out.open("fn default() -> Self {");
+    out.line("Self::new()");
+    out.close("}");
+    out.close("}");
+    Ok(())
+}
+
+/// Write `path { fields }`, on one line if `rustfmt` would keep it there
+fn struct_literal(out: &mut Writer, path: &str, fields: &[String], suffix: &str) {
+    let body = fields.join(", ");
+    if fields.is_empty() {
+        out.line(&format!("{path} {{}}{suffix}"));
+    } else if body.len() <= STRUCT_LIT_WIDTH {
+        out.line(&format!("{path} {{ {body} }}{suffix}"));
+    } else {
+        out.open(&format!("{path} {{"));
+        for field in fields {
+            out.line(&format!("{field},"));
+        }
+        out.close(&format!("}}{suffix}"));
+    }
+}
+
+fn method(out: &mut Writer, contract: &hir::Contract, function: &hir::Function) -> Result<()> {
+    let assigned = assigned_locals(&function.body);
+    let mut params = vec!["&mut self".to_string(), "env: &dyn Env".to_string()];
+    for id in &function.params {
+        let local = &function.locals[id.0];
+        let binding = if assigned.contains(id) { "mut " } else { "" };
+        params.push(format!("{binding}{}: {}", raw(&local.name), rust_type(&local.ty)));
+    }
+    let visibility = if function.exported { "pub " } else { "" };
+    let head = format!("{visibility}fn {}(", method_name(function));
+    let tail = format!(") -> Result<{}, ContractError> {{", rust_type(&function.return_type));
+    if out.indent() + head.len() + params.join(", ").len() + tail.len() <= MAX_WIDTH {
+        out.open(&format!("{head}{}{tail}", params.join(", ")));
+    } else {
+        out.open(&head);
+        for param in &params {
+            out.line(&format!("{param},"));
+        }
+        out.depth -= 1;
+        out.open(&tail);
+    }
+
+    let mut body = Body {
+        contract,
+        function,
+        assigned,
+        marked_line: None,
+        out,
+    };
+    body.block(&function.body)?;
+    if function.return_type == Ty::Unit {
+        body.out.line("Ok(())");
+    }
+    out.close("}");
+    Ok(())
+}
+
+fn test_scaffold(out: &mut Writer, contract: &hir::Contract) {
+    let name = &contract.name;
+    out.line("#[cfg(test)]");
+    out.open("mod tests {");
+    out.line("use super::*;");
+    out.blank();
+    out.line("/// Runtime stub; set its fields to control `caller()` and `now()`");
+    out.line("#[derive(Debug, Default)]");
+    out.open("struct TestEnv {");
+    out.line("caller: Address,");
+    out.line("now: u64,");
+    if !contract.events.is_empty() {
+        out.line("/// Events emitted so far");
+        out.line("events: std::cell::RefCell<Vec<Event>>,");
+    }
+    out.close("}");
+    out.blank();
+    out.raw(
+        "impl Env for TestEnv {
+    fn caller(&self) -> Address {
+        self.caller
+    }
+
+    fn now(&self) -> u64 {
+        self.now
+    }",
+    );
+    if !contract.events.is_empty() {
+        out.blank();
+        out.raw(
+            "    fn emit(&self, event: Event) {
+        self.events.borrow_mut().push(event);
+    }",
+        );
+    }
+    out.line("}");
+    out.blank();
+    out.line("#[test]");
+    out.open("fn test_new() {");
+    out.line(&format!("assert_eq!({name}::new(), {name}::default());"));
+    out.close("}");
+    for function in contract.functions.iter().filter(|f| f.exported) {
+        let mut args = vec!["&env".to_string()];
+        args.extend(function.params.iter().map(|id| zero_value(&function.locals[id.0].ty)));
+        out.blank();
+        out.line("#[test]");
+        out.open(&format!("fn test_{}() {{", function.name));
+        out.line(&format!("let mut contract = {name}::new();"));
+        out.line("let env = TestEnv::default();");
+        out.line(&format!("let result = contract.{}({});", raw(&function.name), args.join(", ")));
+        out.line("// Replace with assertions on `result` and `contract`");
+        out.line("let _ = result;");
+        out.close("}");
+    }
+    out.close("}");
+}
+
+/// Statements of one method under construction
+struct Body<'a, 'w> {
+    contract: &'a hir::Contract,
+    function: &'a hir::Function,
+    /// Locals assigned after their declaration, declared `mut`
+    assigned: HashSet<LocalId>,
+    /// Source line of the last location marker written
+    marked_line: Option<u32>,
+    out: &'w mut Writer,
+}
+
+impl Body<'_, '_> {
+    fn local(&self, id: LocalId) -> String {
+        raw(&self.function.locals[id.0].name)
+    }
+
+    fn block(&mut self, block: &hir::Block) -> Result<()> {
+        for stmt in &block.statements {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &hir::Stmt) -> Result<()> {
+        if self.marked_line != Some(stmt.span.start.line) {
+            self.marked_line = Some(stmt.span.start.line);
+            let location = SourceLocation::new(self.function.file.clone(), stmt.span);
+            self.out.line(&format!("{RUST_MARKER}{location}"));
+        }
+        match &stmt.kind {
+            hir::StmtKind::Let { local, value } => {
+                let binding = if self.assigned.contains(local) { "mut " } else { "" };
+                let ty = rust_type(&self.function.locals[local.0].ty);
+                let value = self.expr(value, true)?.text;
+                self.out.line(&format!("let {binding}{}: {ty} = {value};", self.local(*local)));
+            },
+            hir::StmtKind::Assign { place, value } => {
+                let place = self.expr(place, false)?.text;
+                let value = self.expr(value, true)?.text;
+                self.out.line(&format!("{place} = {value};"));
+            },
+            hir::StmtKind::If { .. } => self.if_chain(stmt, "")?,
+            hir::StmtKind::Require { condition, message } => {
+                self.guard(condition, "Reverted", message.as_deref(), "requirement failed")?;
+            },
+            hir::StmtKind::Assert { condition, message } => {
+                self.guard(condition, "AssertionFailed", message.as_deref(), "assertion failed")?;
+            },
+            hir::StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(value, true)?.text,
+                    None => "()".to_string(),
+                };
+                self.out.line(&format!("return Ok({value});"));
+            },
+            hir::StmtKind::Emit { event, fields } => {
+                let event = &self.contract.events[*event];
+                let values = event
+                    .fields
+                    .iter()
+                    .zip(fields)
+                    .map(|(field, value)| {
+                        Ok(format!("{}: {}", raw(&field.name), self.expr(value, true)?.text))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let path = format!("env.emit(Event::{}", raw(&event.name));
+                struct_literal(self.out, &path, &values, ");");
+            },
+            hir::StmtKind::Expr(expr) => {
+                let expr = self.expr(expr, false)?.text;
+                self.out.line(&format!("{expr};"));
+            },
+        }
+        Ok(())
+    }
+
+    /// Write an `if` statement, `else if` chains included; `prefix` is
+    /// `"} else "` when continuing a chain
+    fn if_chain(&mut self, stmt: &hir::Stmt, prefix: &str) -> Result<()> {
+        let hir::StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = &stmt.kind
+        else {
+            unreachable!("if_chain is only called on `if` statements");
+        };
+        let condition = self.expr(condition, false)?.text;
+        if prefix.is_empty() {
+            self.out.open(&format!("if {condition} {{"));
+        } else {
+            self.out.depth -= 1;
+            self.out.open(&format!("{prefix}if {condition} {{"));
+        }
+        self.block(then_branch)?;
+        match else_branch {
+            Some(block) if is_else_if(block) => self.if_chain(&block.statements[0], "} else ")?,
+            Some(block) => {
+                self.out.depth -= 1;
+                self.out.open("} else {");
+                self.block(block)?;
+                self.out.close("}");
+            },
+            None => self.out.close("}"),
+        }
+        Ok(())
+    }
+
+    fn guard(
+        &mut self,
+        condition: &hir::Expr,
+        variant: &str,
+        message: Option<&str>,
+        default: &str,
+    ) -> Result<()> {
+        let condition = self.expr(condition, false)?.wrap(Prec::Unary);
+        let message = message.unwrap_or(default);
+        self.out.open(&format!("if !{condition} {{"));
+        // `Reverted` also carries the formatted messages of `std::assert`
+        let message = match variant {
+            "Reverted" => format!("{message:?}.into()"),
+            _ => format!("{message:?}"),
+        };
+        self.out.line(&format!("return Err(ContractError::{variant}({message}));"));
+        self.out.close("}");
+        Ok(())
+    }
+
+    /// Render `expr`; `owned` clones places whose type is not `Copy`
+    fn expr(&self, expr: &hir::Expr, owned: bool) -> Result<Rendered> {
+        let place = match &expr.kind {
+            hir::ExprKind::Local(id) => self.local(*id),
+            hir::ExprKind::State(index) => {
+                format!("self.{}", raw(&self.contract.state[*index].name))
+            },
+            hir::ExprKind::Index { base, index } => {
+                let Ty::Array { len, .. } = &base.ty else {
+                    unreachable!("type checking only allows indexing arrays");
+                };
+                let base = self.expr(base, false)?.wrap(Prec::Postfix);
+                let index = self.expr(index, true)?.text;
+                format!("{base}[checked_index({index}, {len})?]")
+            },
+            _ => return self.value(expr),
+        };
+        let text = if owned && !is_copy(&expr.ty) {
+            format!("{place}.clone()")
+        } else {
+            place
+        };
+        Ok(Rendered::new(text, Prec::Postfix))
+    }
+
+    /// Render an expression that is not a place
+    fn value(&self, expr: &hir::Expr) -> Result<Rendered> {
+        let rendered = match &expr.kind {
+            hir::ExprKind::Int(value) => Rendered::new(value.to_string(), Prec::Literal),
+            hir::ExprKind::Bool(value) => Rendered::new(value.to_string(), Prec::Postfix),
+            hir::ExprKind::Str(value) => {
+                Rendered::new(format!("String::from({value:?})"), Prec::Postfix)
+            },
+            hir::ExprKind::Array(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| Ok(self.expr(e, true)?.text))
+                    .collect::<Result<Vec<_>>>()?;
+                Rendered::new(format!("[{}]", elements.join(", ")), Prec::Postfix)
+            },
+            hir::ExprKind::Unary { op, operand } => {
+                let rendered = self.expr(operand, false)?;
+                match op {
+                    UnaryOp::Not => Rendered::new(
+                        format!("!{}", rendered.wrap(Prec::Unary)),
+                        Prec::Unary,
+                    ),
+                    UnaryOp::Neg => Rendered::new(
+                        format!("{}.wrapping_neg()", receiver(rendered, &operand.ty)),
+                        Prec::Postfix,
+                    ),
+                }
+            },
+            hir::ExprKind::Binary { op, lhs, rhs } => self.binary(*op, lhs, rhs)?,
+            hir::ExprKind::Call { callee, args } => {
+                let mut rendered = Vec::with_capacity(args.len() + 1);
+                let text = match callee {
+                    Callee::Builtin(Builtin::Caller) => "env.caller()".to_string(),
+                    Callee::Builtin(Builtin::Now) => "env.now()".to_string(),
+                    Callee::Builtin(builtin) => {
+                        for arg in args {
+                            rendered.push(self.expr(arg, true)?.text);
+                        }
+                        let fallible = matches!(builtin, Builtin::MapGet)
+                            || builtin.comparison().is_some();
+                        let suffix = if fallible { "?" } else { "" };
+                        format!("std_{}({}){suffix}", builtin.symbol(), rendered.join(", "))
+                    },
+                    Callee::Function(index) => {
+                        rendered.push("env".to_string());
+                        for arg in args {
+                            if calls_function(arg) {
+                                return Err(unsupported(
+                                    arg.span,
+                                    "a contract call as an argument of another",
+                                ));
+                            }
+                            rendered.push(self.expr(arg, true)?.text);
+                        }
+                        let name = method_name(&self.contract.functions[*index]);
+                        format!("self.{name}({})?", rendered.join(", "))
+                    },
+                };
+                Rendered::new(text, Prec::Postfix)
+            },
+            hir::ExprKind::Local(_) | hir::ExprKind::State(_) | hir::ExprKind::Index { .. } => {
+                unreachable!("places are rendered by `expr`")
+            },
+        };
+        Ok(rendered)
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: &hir::Expr, rhs: &hir::Expr) -> Result<Rendered> {
+        let method = match op {
+            BinaryOp::Add => Some(("wrapping_add", false)),
+            BinaryOp::Sub => Some(("wrapping_sub", false)),
+            BinaryOp::Mul => Some(("wrapping_mul", false)),
+            BinaryOp::Div => Some(("checked_div", true)),
+            BinaryOp::Rem => Some(("checked_rem", true)),
+            _ => None,
+        };
+        if let Some((method, checked)) = method {
+            let lhs_text = receiver(self.expr(lhs, true)?, &lhs.ty);
+            let rhs_text = self.expr(rhs, true)?.text;
+            let check = if checked { ".ok_or(ContractError::Arithmetic)?" } else { "" };
+            return Ok(Rendered::new(
+                format!("{lhs_text}.{method}({rhs_text}){check}"),
+                Prec::Postfix,
+            ));
+        }
+
+        let (symbol, prec) = match op {
+            BinaryOp::Or => ("||", Prec::Or),
+            BinaryOp::And => ("&&", Prec::And),
+            BinaryOp::Eq => ("==", Prec::Compare),
+            BinaryOp::Ne => ("!=", Prec::Compare),
+            BinaryOp::Lt => ("<", Prec::Compare),
+            BinaryOp::Le => ("<=", Prec::Compare),
+            BinaryOp::Gt => (">", Prec::Compare),
+            BinaryOp::Ge => (">=", Prec::Compare),
+            _ => unreachable!("arithmetic is handled above"),
+        };
+        // Comparisons do not chain in Rust, and the right operand of a
+        // logical operator keeps the grouping of the source
+        let lhs = self.expr(lhs, false)?.wrap(prec.tighter_for_compare());
+        let rhs = self.expr(rhs, false)?.wrap(prec.tighter());
+        Ok(Rendered::new(format!("{lhs} {symbol} {rhs}"), prec))
+    }
+}
+
+/// Binding strength of rendered Rust expressions, loosest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Or,
+    And,
+    Compare,
+    Unary,
+    /// Unsuffixed integer literal, a method receiver only once suffixed
+    Literal,
+    Postfix,
+}
+
+impl Prec {
+    /// Weakest operand precedence that needs no parentheses on the right
+    fn tighter(self) -> Prec {
+        match self {
+            Self::Or => Self::And,
+            Self::And => Self::Compare,
+            _ => Self::Unary,
+        }
+    }
+
+    /// Weakest operand precedence that needs no parentheses on the left
+    fn tighter_for_compare(self) -> Prec {
+        match self {
+            Self::Compare => Self::Unary,
+            other => other,
+        }
+    }
+}
+
+/// Rendered expression and how tightly it binds
+struct Rendered {
+    text: String,
+    prec: Prec,
+}
+
+impl Rendered {
+    fn new(text: String, prec: Prec) -> Self {
+        Self { text, prec }
+    }
+
+    /// Text usable where at least `needed` binding strength is required
+    fn wrap(self, needed: Prec) -> String {
+        if self.prec >= needed {
+            self.text
+        } else {
+            format!("({})", self.text)
+        }
+    }
+}
+
+/// Text of `rendered` as the receiver of an integer method
+fn receiver(rendered: Rendered, ty: &Ty) -> String {
+    if rendered.prec == Prec::Literal {
+        format!("{}_{}", rendered.text, rust_type(ty))
+    } else {
+        rendered.wrap(Prec::Postfix)
+    }
+}
+
+fn rust_type(ty: &Ty) -> String {
+    match ty {
+        Ty::U64 => "u64".to_string(),
+        Ty::I64 => "i64".to_string(),
+        Ty::Bool => "bool".to_string(),
+        Ty::String => "String".to_string(),
+        Ty::Map => "Map".to_string(),
+        Ty::Address => "Address".to_string(),
+        Ty::Array { element, len } => format!("[{}; {len}]", rust_type(element)),
+        Ty::Unit => "()".to_string(),
+    }
+}
+
+fn zero_value(ty: &Ty) -> String {
+    match ty {
+        Ty::U64 | Ty::I64 | Ty::Address => "0".to_string(),
+        Ty::Bool => "false".to_string(),
+        Ty::String => "String::new()".to_string(),
+        Ty::Map => "Map::new()".to_string(),
+        Ty::Array { element, len } if is_copy(element) => {
+            format!("[{}; {len}]", zero_value(element))
+        },
+        Ty::Array { element, .. } => {
+            format!("std::array::from_fn(|_| {})", zero_value(element))
+        },
+        Ty::Unit => "()".to_string(),
+    }
+}
+
+fn is_copy(ty: &Ty) -> bool {
+    match ty {
+        Ty::String | Ty::Map => false,
+        Ty::Array { element, .. } => is_copy(element),
+        _ => true,
+    }
+}
+
+fn is_else_if(block: &hir::Block) -> bool {
+    matches!(block.statements.as_slice(), [stmt] if matches!(stmt.kind, hir::StmtKind::If { .. }))
+}
+
+fn unsupported(span: Span, what: &str) -> SystemError {
+    SystemError::validation(
+        "source",
+        format!(
+            "{}:{}: {what} is not supported by the Rust backend",
+            span.start.line, span.start.column
+        ),
+        None,
+    )
+}
+
+/// `name` as a Rust identifier, raw if it is a keyword
+pub(crate) fn ident(name: &str) -> std::result::Result<String, &'static str> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || name == "_" {
+        return Err("not an identifier");
+    }
+    if matches!(name, "crate" | "self" | "Self" | "super") {
+        return Err("reserved by Rust");
+    }
+    Ok(raw(name))
+}
+
+pub(crate) fn raw(name: &str) -> String {
+    if RUST_KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Name of the method generated for `function`
+fn method_name(function: &hir::Function) -> String {
+    if function.exported {
+        raw(&function.name)
+    } else {
+        function.name.replace("::", "_")
+    }
+}
+
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if previous_lower {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+            previous_lower = false;
+        } else {
+            out.push(c);
+            previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        }
+    }
+    out
+}
+
+/// Reject names that would not compile or would clash in generated code
+fn check_names(contract: &hir::Contract) -> Result<()> {
+    let clash =
+        |span: Span, kind: &str, name: &str| unsupported(span, &format!("a {kind} named `{name}`"));
+    if RESERVED_TYPES.contains(&contract.name.as_str()) {
+        return Err(clash(Span::default(), "contract", &contract.name));
+    }
+    for var in &contract.state {
+        if matches!(var.name.as_str(), "crate" | "self" | "Self" | "super") {
+            return Err(clash(var.span, "state variable", &var.name));
+        }
+    }
+    for event in &contract.events {
+        if matches!(event.name.as_str(), "crate" | "self" | "Self" | "super") {
+            return Err(clash(event.span, "event", &event.name));
+        }
+        for field in &event.fields {
+            if matches!(field.name.as_str(), "crate" | "self" | "Self" | "super") {
+                return Err(clash(field.span, "event field", &field.name));
+            }
+        }
+    }
+    let mut methods = HashSet::new();
+    for function in &contract.functions {
+        if matches!(function.name.as_str(), "new" | "crate" | "self" | "Self" | "super")
+            || !methods.insert(method_name(function))
+        {
+            return Err(clash(function.span, "function", &function.name));
+        }
+        for local in &function.locals {
+            if RESERVED_LOCALS.contains(&local.name.as_str()) {
+                return Err(clash(local.span, "local", &local.name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Locals assigned anywhere in `block`
+fn assigned_locals(block: &hir::Block) -> HashSet<LocalId> {
+    let mut assigned = HashSet::new();
+    let mut blocks = vec![block];
+    while let Some(block) = blocks.pop() {
+        for stmt in &block.statements {
+            match &stmt.kind {
+                hir::StmtKind::Assign { place, .. } => {
+                    let mut place = place;
+                    while let hir::ExprKind::Index { base, .. } = &place.kind {
+                        place = base;
+                    }
+                    if let hir::ExprKind::Local(id) = place.kind {
+                        assigned.insert(id);
+                    }
+                },
+                hir::StmtKind::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    blocks.push(then_branch);
+                    blocks.extend(else_branch);
+                },
+                _ => {},
+            }
+        }
+    }
+    assigned
+}
+
+/// Whether `contract` has `map` values anywhere
+fn uses_map(contract: &hir::Contract) -> bool {
+    fn has_map(ty: &Ty) -> bool {
+        match ty {
+            Ty::Map => true,
+            Ty::Array { element, .. } => has_map(element),
+            _ => false,
+        }
+    }
+    let state = contract.state.iter().map(|var| &var.ty);
+    let events = contract.events.iter().flat_map(|event| event.fields.iter().map(|f| &f.ty));
+    let functions = contract.functions.iter().flat_map(|function| {
+        function.locals.iter().map(|local| &local.ty).chain([&function.return_type])
+    });
+    state.chain(events).chain(functions).any(has_map)
+}
+
+/// Whether `expr` calls a contract function, which borrows `self` mutably
+fn calls_function(expr: &hir::Expr) -> bool {
+    match &expr.kind {
+        hir::ExprKind::Call { callee, args } => {
+            matches!(callee, Callee::Function(_)) || args.iter().any(calls_function)
+        },
+        hir::ExprKind::Unary { operand, .. } => calls_function(operand),
+        hir::ExprKind::Binary { lhs, rhs, .. } => calls_function(lhs) || calls_function(rhs),
+        hir::ExprKind::Index { base, index } => calls_function(base) || calls_function(index),
+        hir::ExprKind::Array(elements) => elements.iter().any(calls_function),
+        hir::ExprKind::Int(_)
+        | hir::ExprKind::Bool(_)
+        | hir::ExprKind::Str(_)
+        | hir::ExprKind::Local(_)
+        | hir::ExprKind::State(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use super::*;
+    use crate::{parser::parse, typeck};
+
+    const FIXTURES: &str = "tests/fixtures/codegen";
+    /// Set to skip invoking `rustc` and `rustfmt` on generated code
+    const SKIP_ENV: &str = "SKIP_RUSTC_TESTS";
+
+    fn generate(source: &str) -> Result<String> {
+        generate_rust(&typeck::check(&parse(source).unwrap()).unwrap(), None)
+    }
+
+    fn fixtures() -> Vec<PathBuf> {
+        let mut paths: Vec<_> = fs::read_dir(FIXTURES)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "contract"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no fixtures in {FIXTURES}");
+        paths
+    }
+
+    /// Compare `actual` with the golden file next to `source`; set
+    /// `UPDATE_SNAPSHOTS=1` to rewrite golden files instead
+    fn assert_golden(source: &Path, actual: &str) {
+        let golden = source.with_extension("rs");
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&golden, actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|_| panic!("missing golden file {}", golden.display()));
+        assert_eq!(actual, expected, "golden file {} differs", golden.display());
+    }
+
+    fn run(command: &mut Command) {
+        let output = command.output().unwrap_or_else(|err| panic!("{command:?}: {err}"));
+        assert!(
+            output.status.success(),
+            "{command:?} failed:\n{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn test_golden_fixtures() {
+        for path in fixtures() {
+            let source = fs::read_to_string(&path).unwrap();
+            let generated = generate(&source).unwrap();
+            assert_golden(&path, &generated);
+        }
+    }
+
+    #[test]
+    fn test_generated_code_compiles() {
+        if std::env::var_os(SKIP_ENV).is_some() {
+            return;
+        }
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let dir = tempfile::tempdir().unwrap();
+        for path in fixtures() {
+            let source = fs::read_to_string(&path).unwrap();
+            let file = dir.path().join(path.with_extension("rs").file_name().unwrap());
+            fs::write(&file, generate(&source).unwrap()).unwrap();
+
+            run(Command::new(&rustc)
+                .args(["--edition", "2021", "--crate-type", "lib", "--out-dir"])
+                .arg(dir.path())
+                .arg(&file));
+            // The generated test scaffold builds and passes
+            let tests = dir.path().join("scaffold");
+            run(Command::new(&rustc)
+                .args(["--edition", "2021", "--test", "-o"])
+                .arg(&tests)
+                .arg(&file));
+            run(&mut Command::new(&tests));
+            // Formatting is already what rustfmt produces
+            if Command::new("rustfmt").arg("--version").output().is_ok() {
+                run(Command::new("rustfmt").args(["--edition", "2021", "--check"]).arg(&file));
+            }
+        }
+    }
+
+    #[test]
+    fn test_module_name_and_clashes() {
+        let contract = typeck::check(&parse("contract MyToken {}").unwrap()).unwrap();
+        assert!(generate_rust(&contract, None).unwrap().contains("pub mod my_token {"));
+        assert!(generate_rust(&contract, Some("type")).unwrap().contains("pub mod r#type {"));
+        let err = generate_rust(&contract, Some("my-token")).unwrap_err();
+        assert!(matches!(err, SystemError::Config { .. }), "{err}");
+
+        let err = generate("contract C { fn f(env: u64) {} }").unwrap_err();
+        assert!(err.to_string().contains("1:19: a local named `env`"), "{err}");
+        let err = generate("contract C { fn f(a: u64) {} fn g() -> u64 { f(g()); return 1; } }")
+            .unwrap_err();
+        assert!(err.to_string().contains("1:48: a contract call as an argument"), "{err}");
+    }
+}
@@ -0,0 +1,272 @@
+//! Source map module
+//!
+//! Maps positions in compiled output back to the contract source, so a trap
+//! or panic can be reported at the statement that caused it.
+//!
+//! - Wasm modules carry their [`SourceMap`] as JSON in the
+//!   [`SOURCE_MAP_SECTION`](crate::codegen::SOURCE_MAP_SECTION) custom
+//!   section, mapping byte offsets in the module, as wasmtime reports them in
+//!   backtraces. [`SourceMap::to_json`] gives the same JSON for a sidecar
+//!   file.
+//! - Generated Rust carries a `// dsl:<location>` comment before the first
+//!   statement of every source line, mapping 1-based lines of the generated
+//!   file.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use shared_core::Result;
+use wasmparser::{Parser, Payload};
+
+use crate::ast::Span;
+use crate::codegen::SOURCE_MAP_SECTION;
+
+/// Prefix of the comments marking source locations in generated Rust
+pub const RUST_MARKER: &str = "// dsl:";
+
+/// A position in contract source
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// Id of the module, as in [`Diagnostic::file`](crate::Diagnostic::file)
+    pub file: Option<String>,
+    /// 1-based line
+    pub line: u32,
+    /// 1-based column, in characters
+    pub column: u32,
+}
+
+impl SourceLocation {
+    /// Start of `span` in the module `file`
+    pub fn new(file: Option<String>, span: Span) -> Self {
+        Self {
+            file,
+            line: span.start.line,
+            column: span.start.column,
+        }
+    }
+
+    /// Parse `file:line:column`, or `line:column` without a file
+    fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.rsplitn(3, ':');
+        let column = parts.next()?.parse().ok()?;
+        let line = parts.next()?.parse().ok()?;
+        Some(Self {
+            file: parts.next().map(str::to_string),
+            line,
+            column,
+        })
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file}:")?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A range of compiled output produced from one source location
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    /// First byte offset in the Wasm module, or first line of generated Rust
+    pub start: usize,
+    /// Offset or line just past the range
+    pub end: usize,
+    /// Function the range belongs to, by its name in the compiled output
+    pub function: String,
+    /// Statement or declaration the range was generated from
+    pub location: SourceLocation,
+}
+
+/// Mappings from compiled output to contract source
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// Mappings ordered by `start`, without overlaps
+    pub mappings: Vec<Mapping>,
+}
+
+/// Where compiled code failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapSite<'a> {
+    /// Byte offset in a Wasm module, or line of generated Rust
+    Offset(usize),
+    /// Message of a panic in generated Rust, naming the panicking line as in
+    /// `panicked at src/token.rs:42:9`
+    Panic(&'a str),
+}
+
+impl From<usize> for TrapSite<'_> {
+    fn from(offset: usize) -> Self {
+        Self::Offset(offset)
+    }
+}
+
+impl<'a> From<&'a str> for TrapSite<'a> {
+    fn from(message: &'a str) -> Self {
+        Self::Panic(message)
+    }
+}
+
+impl SourceMap {
+    /// Source location of the code at `site`, `None` for code not generated
+    /// from a statement, such as `_init`
+    ///
+    /// Panic lines are only meaningful if the generated Rust was written to a
+    /// file of its own unchanged.
+    pub fn symbolicate<'a>(&self, site: impl Into<TrapSite<'a>>) -> Option<SourceLocation> {
+        let offset = match site.into() {
+            TrapSite::Offset(offset) => offset,
+            TrapSite::Panic(message) => panic_line(message)?,
+        };
+        Some(self.mapping_at(offset)?.location.clone())
+    }
+
+    /// Mapping of the code at a Wasm offset or generated Rust line, if any
+    pub fn mapping_at(&self, offset: usize) -> Option<&Mapping> {
+        let index = self.mappings.partition_point(|mapping| mapping.start <= offset);
+        let mapping = &self.mappings[index.checked_sub(1)?];
+        (offset < mapping.end).then_some(mapping)
+    }
+
+    /// Serialize the map to JSON, as stored in Wasm modules
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Deserialize a map from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Map of a Wasm module, `None` if it has none or the map is malformed
+    pub(crate) fn from_wasm(bytes: &[u8]) -> Option<Self> {
+        Parser::new(0).parse_all(bytes).find_map(|payload| match payload {
+            Ok(Payload::CustomSection(section)) if section.name() == SOURCE_MAP_SECTION => {
+                serde_json::from_slice(section.data()).ok()
+            },
+            _ => None,
+        })
+    }
+
+    /// Map of generated Rust, read from its location markers
+    ///
+    /// A marker's mapping runs until the next marker or the end of the
+    /// method it is in.
+    pub(crate) fn from_rust(source: &str) -> Self {
+        let mut mappings = Vec::new();
+        // Indentation and name of the last method started
+        let mut method = (0, "");
+        // Open mapping, with the indentation of its method
+        let mut open: Option<(usize, Mapping)> = None;
+        for (line, text) in (1..).zip(source.lines()) {
+            let code = text.trim_start();
+            let indent = text.len() - code.len();
+            let location = code.strip_prefix(RUST_MARKER).and_then(SourceLocation::parse);
+            let closes_method = open.as_ref().is_some_and(|(depth, _)| {
+                *depth == indent && code == "}"
+            });
+            if location.is_some() || closes_method {
+                if let Some((_, mut mapping)) = open.take() {
+                    mapping.end = line;
+                    mappings.push(mapping);
+                }
+            }
+            if let Some(location) = location {
+                let mapping = Mapping {
+                    start: line,
+                    end: line,
+                    function: method.1.to_string(),
+                    location,
+                };
+                open = Some((method.0, mapping));
+            } else if let Some(name) = code.strip_prefix("pub fn ").or(code.strip_prefix("fn ")) {
+                method = (indent, name.split('(').next().unwrap_or(name));
+            }
+        }
+        Self { mappings }
+    }
+}
+
+/// Line named by a panic message
+fn panic_line(message: &str) -> Option<usize> {
+    let site = message.split_once("panicked at ").map_or(message, |(_, site)| site);
+    site.split_whitespace().find_map(|word| {
+        let location = SourceLocation::parse(word.trim_end_matches([':', ',']))?;
+        usize::try_from(location.line).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse, rust_codegen, typeck};
+
+    #[test]
+    fn test_symbolicate_offsets() {
+        let location = |line| SourceLocation {
+            file: Some("token.contract".to_string()),
+            line,
+            column: 9,
+        };
+        let map = SourceMap {
+            mappings: vec![
+                Mapping {
+                    start: 10,
+                    end: 14,
+                    function: "mint".to_string(),
+                    location: location(3),
+                },
+                Mapping {
+                    start: 20,
+                    end: 21,
+                    function: "mint".to_string(),
+                    location: location(4),
+                },
+            ],
+        };
+        assert_eq!(map.symbolicate(9), None);
+        assert_eq!(map.symbolicate(10), Some(location(3)));
+        assert_eq!(map.symbolicate(13), Some(location(3)));
+        assert_eq!(map.symbolicate(14), None);
+        assert_eq!(map.symbolicate(20), Some(location(4)));
+        assert_eq!(map.symbolicate(21), None);
+        assert_eq!(SourceMap::from_json(&map.to_json().unwrap()).unwrap(), map);
+        assert_eq!(location(3).to_string(), "token.contract:3:9");
+    }
+
+    #[test]
+    fn test_symbolicate_rust_panics() {
+        let source = "contract Vault {
+    state { total: u64; }
+    fn add(amount: u64) -> u64 {
+        require(amount > 0, \"empty\");
+        let total = self.total + amount;
+        if total > 100 { return 100; }
+        self.total = total;
+        return total;
+    }
+}";
+        let contract = typeck::check(&parse(source).unwrap()).unwrap();
+        let rust = rust_codegen::generate_rust(&contract, None).unwrap();
+        let map = SourceMap::from_rust(&rust);
+        let lines: Vec<_> = map.mappings.iter().map(|m| m.location.line).collect();
+        assert_eq!(lines, [4, 5, 6, 7, 8]);
+        assert!(map.mappings.iter().all(|m| m.function == "add"));
+
+        // Lines of the generated code, 1-based
+        let line_of = |needle: &str| rust.lines().position(|l| l.contains(needle)).unwrap() + 1;
+        let revert = line_of("ContractError::Reverted(\"empty\"");
+        let message = format!("thread 'main' panicked at src/vault.rs:{revert}:17:\nboom");
+        assert_eq!(map.symbolicate(message.as_str()).map(|l| l.line), Some(4));
+        let early_return = line_of("return Ok(100)");
+        let message = format!("panicked at 'overflow', src/vault.rs:{early_return}:5");
+        assert_eq!(map.symbolicate(message.as_str()).map(|l| l.line), Some(6));
+        assert_eq!(map.symbolicate(line_of("Ok(total)")).map(|l| l.column), Some(9));
+
+        assert_eq!(map.symbolicate(line_of("pub fn new()")), None);
+        assert_eq!(map.symbolicate(line_of("fn test_add()")), None);
+        assert_eq!(map.symbolicate("no location here"), None);
+    }
+}
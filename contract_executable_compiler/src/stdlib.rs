@@ -0,0 +1,215 @@
+//! Standard library
+//!
+//! Library modules built into the compiler, imported as `std::<name>`.
+//! `std::math` is written in the contract language itself, so the code
+//! generators and the [`interpreter`](crate::interpreter) run it like
+//! contract code. `std::map` and `std::assert` are [builtins](Builtin) of
+//! their module, which the interpreter and the Wasm
+//! [`runtime`](crate::runtime) implement as host functions and generated
+//! Rust as helper functions, all with the messages below. Either way they
+//! behave identically on all targets. Failures revert the call like a
+//! failed `require`.
+//!
+//! `std::math`, over `u64`:
+//!
+//! | Function | Result | Gas |
+//! |---|---|---|
+//! | `checked_add(a, b)` | `a + b`, reverting on overflow | 13 |
+//! | `checked_sub(a, b)` | `a - b`, reverting on underflow | 11 |
+//! | `checked_mul(a, b)` | `a * b`, reverting on overflow | 21 |
+//! | `pow(base, exp)` | `base` to the power `exp`, reverting on overflow | 56 |
+//! | `min(a, b)` / `max(a, b)` | The smaller / larger value | 9 |
+//! | `clamp(value, low, high)` | `value` limited to `low..=high`, reverting if `low > high` | 26 |
+//!
+//! `std::map`, over the `map` type from `string` keys to `u64` values.
+//! Maps are values like any other, so updates return the updated map:
+//!
+//! | Function | Result | Gas |
+//! |---|---|---|
+//! | `insert(entries, key, value)` | `entries` with `key` set to `value` | 100 |
+//! | `get(entries, key)` | The value of `key`, reverting if there is none | 100 |
+//! | `remove(entries, key)` | `entries` without `key` | 100 |
+//! | `contains(entries, key)` | Whether `key` has a value | 100 |
+//!
+//! `std::assert`, over `u64`, reverting unless the condition holds:
+//!
+//! | Function | Condition | Gas |
+//! |---|---|---|
+//! | `require_eq(a, b)` / `require_ne(a, b)` | `a == b` / `a != b` | 100 |
+//! | `require_gt(a, b)` / `require_ge(a, b)` | `a > b` / `a >= b` | 100 |
+//! | `require_lt(a, b)` / `require_le(a, b)` | `a < b` / `a <= b` | 100 |
+//!
+//! Gas is the [static estimate](crate::gas) of one call's own Wasm code,
+//! on its most expensive path; calls it makes cost
+//! [`CALL_GAS`](crate::gas::CALL_GAS) each plus their own gas. `pow` calls
+//! itself once per bit of `exp`. Builtins are host calls, costing
+//! [`IO_GAS`](crate::gas::IO_GAS) whatever their arguments.
+//!
+//! Revert messages name the module and the failed check. Those of
+//! `std::math` are constant, as in `std::math: addition overflow`, as the
+//! language has no string formatting; builtins format theirs with the
+//! values involved, as in `std::assert: require_eq failed: 3 == 4` and
+//! `std::map: no value for key "alice"`.
+
+use crate::hir::Builtin;
+
+/// `std::math`
+const MATH: &str = r#"
+pub fn checked_add(a: u64, b: u64) -> u64 {
+    let sum = a + b;
+    require(sum >= a, "std::math: addition overflow");
+    return sum;
+}
+
+pub fn checked_sub(a: u64, b: u64) -> u64 {
+    require(b <= a, "std::math: subtraction underflow");
+    return a - b;
+}
+
+pub fn checked_mul(a: u64, b: u64) -> u64 {
+    if a == 0 { return 0; }
+    let product = a * b;
+    require(product / a == b, "std::math: multiplication overflow");
+    return product;
+}
+
+pub fn pow(base: u64, exp: u64) -> u64 {
+    if exp == 0 { return 1; }
+    let half = pow(base, exp / 2);
+    let square = checked_mul(half, half);
+    if exp % 2 == 1 { return checked_mul(square, base); }
+    return square;
+}
+
+pub fn min(a: u64, b: u64) -> u64 {
+    if a < b { return a; }
+    return b;
+}
+
+pub fn max(a: u64, b: u64) -> u64 {
+    if a > b { return a; }
+    return b;
+}
+
+pub fn clamp(value: u64, low: u64, high: u64) -> u64 {
+    require(low <= high, "std::math: clamp bounds are inverted");
+    if value < low { return low; }
+    return min(value, high);
+}
+"#;
+
+/// `std::map`, made of builtins only
+const MAP: &str = "";
+
+/// `std::assert`, made of builtins only
+const ASSERT: &str = "";
+
+/// Standard modules by path
+pub const MODULES: &[(&str, &str)] =
+    &[("std::math", MATH), ("std::map", MAP), ("std::assert", ASSERT)];
+
+/// Source of the standard module `path`, as in `std::math`
+pub fn source(path: &str) -> Option<&'static str> {
+    MODULES.iter().find(|(name, _)| *name == path).map(|(_, source)| *source)
+}
+
+/// Revert message of the `std::assert` builtin `builtin` called with `a`
+/// and `b`, `None` if its condition holds
+pub(crate) fn assert_failure(builtin: Builtin, a: u64, b: u64) -> Option<String> {
+    let holds = match builtin {
+        Builtin::RequireEq => a == b,
+        Builtin::RequireNe => a != b,
+        Builtin::RequireGt => a > b,
+        Builtin::RequireGe => a >= b,
+        Builtin::RequireLt => a < b,
+        Builtin::RequireLe => a <= b,
+        _ => return None,
+    };
+    let op = builtin.comparison()?;
+    (!holds).then(|| format!("std::assert: {} failed: {a} {op} {b}", builtin.name()))
+}
+
+/// Revert message of `map::get` for a key without a value
+pub(crate) fn missing_key(key: &str) -> String {
+    format!("std::map: no value for key {key:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::compiler::CompiledArtifact;
+    use crate::module::{InMemoryResolver, Program};
+    use crate::{codegen, gas, typeck};
+
+    /// Gas of every function of `source`, library functions included
+    fn gas_costs(source: &str) -> HashMap<String, u64> {
+        let program = Program::load(None, source.to_string(), &InMemoryResolver::new()).unwrap();
+        let mut contract = typeck::check_program(&program).0.unwrap();
+        // Export library functions so the estimate covers them
+        for function in &mut contract.functions {
+            function.exported = true;
+        }
+        let wasm = codegen::generate_wasm(&contract).unwrap();
+        gas::estimate_gas(&CompiledArtifact::Wasm(wasm)).unwrap().per_call_estimates
+    }
+
+    #[test]
+    fn test_documented_gas() {
+        let math = gas_costs("import std::math; contract C {}");
+        for (name, gas) in [
+            ("checked_add", 13),
+            ("checked_sub", 11),
+            ("checked_mul", 21),
+            ("pow", 56),
+            ("min", 9),
+            ("max", 9),
+            ("clamp", 26),
+        ] {
+            assert_eq!(math[&format!("math::{name}")], gas, "{name}");
+        }
+
+        // Calling a builtin costs the host call, whatever its arguments; the
+        // wrappers also pay 1 per argument they pass and 2 to return a value
+        let builtins = gas_costs(
+            "import std::map; import std::assert;
+            contract C {
+                fn insert(m: map, k: string, v: u64) -> map { return map::insert(m, k, v); }
+                fn get(m: map, k: string) -> u64 { return map::get(m, k); }
+                fn remove(m: map, k: string) -> map { return map::remove(m, k); }
+                fn contains(m: map, k: string) -> bool { return map::contains(m, k); }
+                fn eq(a: u64, b: u64) { assert::require_eq(a, b); }
+                fn ne(a: u64, b: u64) { assert::require_ne(a, b); }
+                fn gt(a: u64, b: u64) { assert::require_gt(a, b); }
+                fn ge(a: u64, b: u64) { assert::require_ge(a, b); }
+                fn lt(a: u64, b: u64) { assert::require_lt(a, b); }
+                fn le(a: u64, b: u64) { assert::require_le(a, b); }
+            }",
+        );
+        for (name, wrapper) in [
+            ("insert", 5),
+            ("get", 4),
+            ("remove", 4),
+            ("contains", 4),
+            ("eq", 2),
+            ("ne", 2),
+            ("gt", 2),
+            ("ge", 2),
+            ("lt", 2),
+            ("le", 2),
+        ] {
+            assert_eq!(builtins[name], gas::IO_GAS + wrapper, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_modules_type_check() {
+        for (path, _) in MODULES {
+            let source = format!("import {path}; contract C {{}}");
+            let program = Program::load(None, source, &InMemoryResolver::new()).unwrap();
+            let (checked, warnings) = typeck::check_program(&program);
+            assert!(checked.is_ok() && warnings.is_empty(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Trace module
+//!
+//! Where the gas of a contract call went, as recorded by a tracing
+//! [`ContractExecutor`](crate::ContractExecutor): the call is cut into
+//! [`TraceSample`]s at every host call, each holding the gas used since the
+//! previous one and the call stack it ended in. Gas is attributed to the
+//! innermost function of that stack, so the trace is exact per host-call
+//! interval and approximate within one.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::source_map::SourceLocation;
+
+/// Gas used by a call, cut at its host calls
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutionTrace {
+    /// Exported function that was called
+    pub method: String,
+    /// Gas the call used, the sum of the samples' gas
+    pub total_gas: u64,
+    /// Intervals between host calls, in execution order
+    pub samples: Vec<TraceSample>,
+}
+
+/// Gas used between two host calls, or the last one and the return
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceSample {
+    /// Call stack the interval ended in, outermost function first
+    pub stack: Vec<String>,
+    /// Gas used during the interval
+    pub gas: u64,
+    /// Host function called at the end of the interval, `None` for the
+    /// return from the call
+    pub host_call: Option<String>,
+    /// Statement making the host call, if the module has a source map
+    pub location: Option<SourceLocation>,
+}
+
+/// Gas of a function, with the functions it called, in a [`ExecutionTrace::tree`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TraceNode {
+    /// Function name
+    pub function: String,
+    /// Gas used in the function itself
+    pub self_gas: u64,
+    /// Gas used in the function and everything it called
+    pub total_gas: u64,
+    /// Functions called, by name
+    pub children: Vec<TraceNode>,
+}
+
+impl TraceSample {
+    /// Call depth the interval ended at, 1 for the called method itself
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+}
+
+impl ExecutionTrace {
+    /// Gas by call path, rooted at the called method
+    pub fn tree(&self) -> TraceNode {
+        let mut root = TraceNode {
+            function: self.method.clone(),
+            ..TraceNode::default()
+        };
+        for sample in &self.samples {
+            // Stacks start at the method; anything else is attributed to it
+            let path = sample.stack.get(1..).unwrap_or_default();
+            let mut node = &mut root;
+            node.total_gas += sample.gas;
+            for function in path {
+                let index = match node.children.iter().position(|c| &c.function == function) {
+                    Some(index) => index,
+                    None => {
+                        node.children.push(TraceNode {
+                            function: function.clone(),
+                            ..TraceNode::default()
+                        });
+                        node.children.len() - 1
+                    },
+                };
+                node = &mut node.children[index];
+                node.total_gas += sample.gas;
+            }
+            node.self_gas += sample.gas;
+        }
+        root
+    }
+
+    /// Gas used in each function itself, by name; the values sum to
+    /// `total_gas`
+    pub fn function_gas(&self) -> BTreeMap<String, u64> {
+        let mut gas = BTreeMap::new();
+        for sample in &self.samples {
+            let function = sample.stack.last().unwrap_or(&self.method);
+            *gas.entry(function.clone()).or_default() += sample.gas;
+        }
+        gas
+    }
+
+    /// The `n` functions using the most gas themselves, most first
+    pub fn hotspots(&self, n: usize) -> Vec<(String, u64)> {
+        let mut hotspots: Vec<(String, u64)> = self.function_gas().into_iter().collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hotspots.truncate(n);
+        hotspots
+    }
+
+    /// Folded stacks, one `outer;inner gas` line per call path, as flame
+    /// graph tools read them
+    pub fn folded(&self) -> String {
+        let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+        for sample in &self.samples {
+            let stack = if sample.stack.is_empty() {
+                self.method.clone()
+            } else {
+                sample.stack.join(";")
+            };
+            *stacks.entry(stack).or_default() += sample.gas;
+        }
+        stacks.into_iter().map(|(stack, gas)| format!("{stack} {gas}\n")).collect()
+    }
+}
@@ -0,0 +1,1212 @@
+//! Type checking
+//!
+//! Resolves names and types of a parsed [`ast::Contract`] and lowers it to
+//! [`hir::Contract`]. State variables are read and written through `self`,
+//! as in `self.supply`; bare names refer to parameters and locals. `emit`
+//! must set every field its event declares, once, in any order.
+//!
+//! Functions of imported library modules are lowered alongside the
+//! contract's, after them, named `module::function`. They are called as
+//! `alias::function`, must be declared `pub` to be callable from other
+//! modules, and cannot access contract state. The [builtins](Builtin) of a
+//! standard module are called the same way, as in `map::insert`.
+//!
+//! Every problem found is reported as a [`Diagnostic`]; checking goes on
+//! after an error, skipping only what depends on the broken part, so one run
+//! reports as many independent errors as possible. Unused locals and
+//! unreachable statements are reported as warnings.
+
+use std::collections::HashMap;
+
+use shared_core::ErrorCollection;
+
+use crate::ast::{self, BinaryOp, Span, UnaryOp};
+pub use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::hir::{self, Builtin, Callee, LocalId, Ty};
+use crate::module::Program;
+
+/// Type check `contract` and lower it to HIR
+pub fn check(contract: &ast::Contract) -> Result<hir::Contract, ErrorCollection<Diagnostic>> {
+    check_with_warnings(contract).0
+}
+
+/// Type check `contract` and lower it to HIR, also returning warnings in
+/// source order
+pub fn check_with_warnings(
+    contract: &ast::Contract,
+) -> (Result<hir::Contract, ErrorCollection<Diagnostic>>, Vec<Diagnostic>) {
+    check_program(&Program::single(contract.clone()))
+}
+
+/// Type check the contract of `program` with the library modules it imports
+/// and lower them to HIR, also returning warnings in source order
+pub fn check_program(
+    program: &Program,
+) -> (Result<hir::Contract, ErrorCollection<Diagnostic>>, Vec<Diagnostic>) {
+    let mut checker = Checker::new(program, None);
+    let functions = (0..program.modules().len())
+        .flat_map(|module| checker.check_bodies(program, module))
+        .collect();
+    checker.finish(program, functions)
+}
+
+/// Type check the function bodies of the module `module` of `program` alone
+///
+/// The result is a contract holding only that module's functions, with
+/// calls referring to functions by their index in the whole program, and the
+/// state for the contract module only. Diagnostics are reported for that
+/// module only, so checking every module this way reports the same problems
+/// as [`check_program`].
+pub fn check_module(
+    program: &Program,
+    module: usize,
+) -> (Result<hir::Contract, ErrorCollection<Diagnostic>>, Vec<Diagnostic>) {
+    let mut checker = Checker::new(program, Some(module));
+    let functions = checker.check_bodies(program, module);
+    if module != 0 {
+        checker.state.clear();
+        checker.events.clear();
+    }
+    checker.finish(program, functions)
+}
+
+/// Index in the whole program of the first function of each module, then
+/// the number of functions
+pub fn function_offsets(program: &Program) -> Vec<usize> {
+    let mut offsets = vec![0];
+    for module in 0..program.modules().len() {
+        offsets.push(offsets[module] + module_functions(program, module).len());
+    }
+    offsets
+}
+
+/// Functions of the module `module`, in declaration order
+fn module_functions(program: &Program, module: usize) -> &[ast::Function] {
+    match module {
+        0 => &program.contract().functions,
+        _ => &program.modules()[module].ast.functions,
+    }
+}
+
+/// Signature of a function; `None` types failed to resolve
+struct Signature {
+    params: Vec<Option<Ty>>,
+    return_type: Option<Ty>,
+    /// Span of the function name
+    span: Span,
+    /// Declared `pub`
+    public: bool,
+}
+
+/// Names visible in one module
+struct ModuleScope {
+    name: String,
+    file: Option<String>,
+    /// Imported modules by alias
+    imports: HashMap<String, usize>,
+    /// Functions declared in the module, as indices into the signatures
+    functions: HashMap<String, usize>,
+}
+
+struct Checker {
+    diagnostics: ErrorCollection<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+    /// State variables, with `Ty::Unit` and `None` for unresolved types
+    state: Vec<(hir::StateVar, Option<Ty>)>,
+    state_index: HashMap<String, usize>,
+    /// Events, with `None` for field types that failed to resolve
+    events: Vec<(hir::Event, Vec<Option<Ty>>)>,
+    event_index: HashMap<String, usize>,
+    signatures: Vec<Signature>,
+    modules: Vec<ModuleScope>,
+    /// Module being checked; the contract module is 0
+    current: usize,
+    /// Module diagnostics are reported for, `None` for every module
+    reporting: Option<usize>,
+}
+
+/// Locals of the function being checked
+struct FunctionScope {
+    locals: Vec<hir::Local>,
+    /// Locals whose type failed to resolve; their uses are not reported again
+    poisoned: Vec<bool>,
+    /// Locals that are referred to after their declaration
+    used: Vec<bool>,
+    scopes: Vec<HashMap<String, LocalId>>,
+    return_type: Option<Ty>,
+}
+
+impl FunctionScope {
+    fn lookup(&self, name: &str) -> Option<LocalId> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+}
+
+impl Checker {
+    /// Declare the state and the functions of every module of `program`
+    fn new(program: &Program, reporting: Option<usize>) -> Self {
+        let mut checker = Checker {
+            diagnostics: ErrorCollection::new(),
+            warnings: Vec::new(),
+            state: Vec::new(),
+            state_index: HashMap::new(),
+            events: Vec::new(),
+            event_index: HashMap::new(),
+            signatures: Vec::new(),
+            modules: program
+                .modules()
+                .iter()
+                .map(|module| ModuleScope {
+                    name: module.name.clone(),
+                    file: module.file.clone(),
+                    imports: module.imports.clone(),
+                    functions: HashMap::new(),
+                })
+                .collect(),
+            current: 0,
+            reporting,
+        };
+        checker.declare_state(&program.contract().state);
+        checker.declare_events(&program.contract().events);
+        // The contract's functions come first, so their indices are unchanged
+        for module in 0..program.modules().len() {
+            checker.current = module;
+            for function in module_functions(program, module) {
+                checker.declare_function(function);
+            }
+        }
+        checker
+    }
+
+    /// Check and lower the functions of `module`
+    fn check_bodies(&mut self, program: &Program, module: usize) -> Vec<hir::Function> {
+        self.current = module;
+        let first = function_offsets(program)[module];
+        module_functions(program, module)
+            .iter()
+            .zip(first..)
+            .filter_map(|(function, index)| self.function(function, index))
+            .collect()
+    }
+
+    fn finish(
+        self,
+        program: &Program,
+        functions: Vec<hir::Function>,
+    ) -> (Result<hir::Contract, ErrorCollection<Diagnostic>>, Vec<Diagnostic>) {
+        // Unresolved state types were reported, so the placeholder is never seen
+        let state = self
+            .state
+            .into_iter()
+            .map(|(var, ty)| hir::StateVar {
+                ty: ty.unwrap_or(Ty::Unit),
+                ..var
+            })
+            .collect();
+        let events = self.events.into_iter().map(|(event, _)| event).collect();
+        let contract = hir::Contract {
+            name: program.contract().name.name.clone(),
+            state,
+            events,
+            functions,
+            file: program.modules()[0].file.clone(),
+        };
+        let mut warnings = self.warnings;
+        crate::diagnostic::sort(&mut warnings);
+        (self.diagnostics.into_result(contract), warnings)
+    }
+
+    /// Whether diagnostics about the current module are reported
+    fn reporting(&self) -> bool {
+        self.reporting.map_or(true, |module| module == self.current)
+    }
+
+    fn push(&mut self, diagnostic: Diagnostic) {
+        if self.reporting() {
+            let file = self.modules[self.current].file.clone();
+            self.diagnostics.push(diagnostic.in_file(file));
+        }
+    }
+
+    fn warn(&mut self, warning: Diagnostic) {
+        if self.reporting() {
+            let file = self.modules[self.current].file.clone();
+            self.warnings.push(warning.in_file(file));
+        }
+    }
+
+    fn error(&mut self, code: ErrorCode, span: Span, message: impl Into<String>) {
+        self.push(Diagnostic::error(code, span, message));
+    }
+
+    fn mismatch(&mut self, expected: &Ty, found: &Ty, span: Span) {
+        self.error(
+            ErrorCode::TypeMismatch,
+            span,
+            format!("expected `{expected}`, found `{found}`"),
+        );
+    }
+
+    fn resolve_type(&mut self, ty: &ast::Type) -> Option<Ty> {
+        match &ty.kind {
+            ast::TypeKind::Named(name) => {
+                let resolved = match name.as_str() {
+                    "u64" => Ty::U64,
+                    "i64" => Ty::I64,
+                    "bool" => Ty::Bool,
+                    "string" => Ty::String,
+                    "address" => Ty::Address,
+                    "map" => Ty::Map,
+                    _ => {
+                        let message = format!("unknown type `{name}`");
+                        self.push(
+                            Diagnostic::error(ErrorCode::UnknownType, ty.span, message).with_help(
+                                "the types are `u64`, `i64`, `bool`, `string`, `address`, \
+                                 `map` and arrays of them",
+                            ),
+                        );
+                        return None;
+                    },
+                };
+                Some(resolved)
+            },
+            ast::TypeKind::Array { element, len } => Some(Ty::Array {
+                element: Box::new(self.resolve_type(element)?),
+                len: *len,
+            }),
+        }
+    }
+
+    fn declare_state(&mut self, fields: &[ast::StateField]) {
+        for field in fields {
+            let ty = self.resolve_type(&field.ty);
+            if let Some(&first) = self.state_index.get(&field.name.name) {
+                let message = format!("state variable `{}` is already declared", field.name.name);
+                self.push(
+                    Diagnostic::error(ErrorCode::DuplicateDeclaration, field.name.span, message)
+                        .with_label(self.state[first].0.span, "first declared here"),
+                );
+                continue;
+            }
+            self.state_index.insert(field.name.name.clone(), self.state.len());
+            let var = hir::StateVar {
+                name: field.name.name.clone(),
+                ty: Ty::Unit,
+                span: field.span,
+            };
+            self.state.push((var, ty));
+        }
+    }
+
+    fn declare_events(&mut self, events: &[ast::Event]) {
+        for event in events {
+            let mut fields: Vec<hir::EventField> = Vec::new();
+            let mut types = Vec::new();
+            for field in &event.fields {
+                let ty = self.resolve_type(&field.ty);
+                if let Some(first) = fields.iter().find(|f| f.name == field.name.name) {
+                    let message = format!("field `{}` is already declared", field.name.name);
+                    self.push(
+                        Diagnostic::error(ErrorCode::DuplicateDeclaration, field.name.span, message)
+                            .with_label(first.span, "first declared here"),
+                    );
+                    continue;
+                }
+                fields.push(hir::EventField {
+                    name: field.name.name.clone(),
+                    ty: ty.clone().unwrap_or(Ty::Unit),
+                    span: field.span,
+                });
+                types.push(ty);
+            }
+            if let Some(&first) = self.event_index.get(&event.name.name) {
+                let message = format!("event `{}` is already declared", event.name.name);
+                self.push(
+                    Diagnostic::error(ErrorCode::DuplicateDeclaration, event.name.span, message)
+                        .with_label(self.events[first].0.span, "first declared here"),
+                );
+                continue;
+            }
+            self.event_index.insert(event.name.name.clone(), self.events.len());
+            let event = hir::Event {
+                name: event.name.name.clone(),
+                fields,
+                span: event.span,
+            };
+            self.events.push((event, types));
+        }
+    }
+
+    /// Declare a function of the current module
+    fn declare_function(&mut self, function: &ast::Function) {
+        let name = &function.name.name;
+        let signature = Signature {
+            params: function.params.iter().map(|p| self.resolve_type(&p.ty)).collect(),
+            return_type: match &function.return_type {
+                Some(ty) => self.resolve_type(ty),
+                None => Some(Ty::Unit),
+            },
+            span: function.name.span,
+            public: function.public,
+        };
+        if Builtin::ALL.iter().any(|b| b.module().is_none() && b.name() == name) {
+            self.error(
+                ErrorCode::DuplicateDeclaration,
+                function.name.span,
+                format!("`{name}` is a builtin function"),
+            );
+        } else if let Some(&first) = self.modules[self.current].functions.get(name) {
+            let message = format!("function `{name}` is already declared");
+            self.push(
+                Diagnostic::error(ErrorCode::DuplicateDeclaration, function.name.span, message)
+                    .with_label(self.signatures[first].span, "first declared here"),
+            );
+        } else {
+            let index = self.signatures.len();
+            self.modules[self.current].functions.insert(name.clone(), index);
+        }
+        // Duplicates keep a signature so indices follow declaration order
+        self.signatures.push(signature);
+    }
+
+    fn function(&mut self, function: &ast::Function, index: usize) -> Option<hir::Function> {
+        let signature = &self.signatures[index];
+        let param_types = signature.params.clone();
+        let mut scope = FunctionScope {
+            locals: Vec::new(),
+            poisoned: Vec::new(),
+            used: Vec::new(),
+            scopes: vec![HashMap::new()],
+            return_type: signature.return_type.clone(),
+        };
+
+        let params = function
+            .params
+            .iter()
+            .zip(param_types)
+            .map(|(param, ty)| self.declare_local(&mut scope, &param.name, ty))
+            .collect();
+        let body = self.block(&mut scope, &function.body);
+        self.unused_locals(&scope, function.params.len());
+
+        if scope.return_type.as_ref().is_some_and(|ty| *ty != Ty::Unit)
+            && !block_returns(&function.body)
+        {
+            self.error(
+                ErrorCode::MissingReturn,
+                function.name.span,
+                format!("function `{}` does not return a value on every path", function.name.name),
+            );
+        }
+
+        let name = match self.current {
+            0 => function.name.name.clone(),
+            module => format!("{}::{}", self.modules[module].name, function.name.name),
+        };
+        Some(hir::Function {
+            name,
+            exported: self.current == 0,
+            params,
+            return_type: scope.return_type?,
+            locals: scope.locals,
+            body: body?,
+            span: function.span,
+            file: self.modules[self.current].file.clone(),
+        })
+    }
+
+    fn declare_local(
+        &mut self,
+        scope: &mut FunctionScope,
+        name: &ast::Ident,
+        ty: Option<Ty>,
+    ) -> LocalId {
+        let id = LocalId(scope.locals.len());
+        scope.poisoned.push(ty.is_none());
+        scope.used.push(false);
+        scope.locals.push(hir::Local {
+            name: name.name.clone(),
+            ty: ty.unwrap_or(Ty::Unit),
+            span: name.span,
+        });
+        let innermost = scope.scopes.last_mut().expect("function scope is never empty");
+        if let Some(first) = innermost.insert(name.name.clone(), id) {
+            let message = format!("`{}` is already declared in this scope", name.name);
+            self.push(
+                Diagnostic::error(ErrorCode::DuplicateDeclaration, name.span, message)
+                    .with_label(scope.locals[first.0].span, "first declared here"),
+            );
+        }
+        id
+    }
+
+    /// Warn about `let` locals, declared after the `params` parameters, that
+    /// are never referred to
+    fn unused_locals(&mut self, scope: &FunctionScope, params: usize) {
+        for (local, used) in scope.locals.iter().zip(&scope.used).skip(params) {
+            if *used || local.name.starts_with('_') {
+                continue;
+            }
+            self.warn(
+                Diagnostic::warning(
+                    ErrorCode::UnusedVariable,
+                    local.span,
+                    format!("unused variable `{}`", local.name),
+                )
+                .with_help(format!(
+                    "if this is intentional, prefix it with an underscore: `_{}`",
+                    local.name
+                )),
+            );
+        }
+    }
+
+    /// Check a block in a new scope; `None` if any statement failed
+    fn block(&mut self, scope: &mut FunctionScope, block: &ast::Block) -> Option<hir::Block> {
+        scope.scopes.push(HashMap::new());
+        let statements: Vec<_> =
+            block.statements.iter().map(|s| self.statement(scope, s)).collect();
+        scope.scopes.pop();
+
+        let returning = block.statements.iter().position(stmt_returns);
+        if let Some((last, unreachable)) =
+            returning.and_then(|i| Some((&block.statements[i], block.statements.get(i + 1)?)))
+        {
+            let end = block.statements.last().unwrap_or(unreachable);
+            self.warn(
+                Diagnostic::warning(
+                    ErrorCode::UnreachableCode,
+                    unreachable.span.to(end.span),
+                    "unreachable statement",
+                )
+                .with_label(last.span, "any code following this is unreachable"),
+            );
+        }
+        Some(hir::Block {
+            statements: statements.into_iter().collect::<Option<_>>()?,
+            span: block.span,
+        })
+    }
+
+    fn statement(&mut self, scope: &mut FunctionScope, stmt: &ast::Stmt) -> Option<hir::Stmt> {
+        let kind = match &stmt.kind {
+            ast::StmtKind::Let { name, ty, value } => {
+                let declared = match ty {
+                    Some(ty) => self.resolve_type(ty).map(Some),
+                    None => Some(None),
+                };
+                let value = match &declared {
+                    Some(Some(ty)) => self.expr_as(scope, value, ty),
+                    Some(None) => self.expr(scope, value, None),
+                    None => {
+                        self.expr(scope, value, None);
+                        None
+                    },
+                };
+                // An annotated local keeps its type even if the value is broken
+                let ty = match declared {
+                    Some(Some(ty)) => Some(ty),
+                    _ => value.as_ref().map(|value| value.ty.clone()),
+                };
+                let local = self.declare_local(scope, name, ty);
+                hir::StmtKind::Let { local, value: value? }
+            },
+            ast::StmtKind::Assign { target, value } => {
+                let place = self.expr(scope, target, None);
+                let value = match &place {
+                    Some(place) => self.expr_as(scope, value, &place.ty.clone()),
+                    None => self.expr(scope, value, None),
+                };
+                hir::StmtKind::Assign {
+                    place: place?,
+                    value: value?,
+                }
+            },
+            ast::StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.expr_as(scope, condition, &Ty::Bool);
+                let then_branch = self.block(scope, then_branch);
+                let else_branch = else_branch.as_ref().map(|block| self.block(scope, block));
+                hir::StmtKind::If {
+                    condition: condition?,
+                    then_branch: then_branch?,
+                    else_branch: else_branch.map_or(Some(None), |block| block.map(Some))?,
+                }
+            },
+            ast::StmtKind::Require { condition, message } => hir::StmtKind::Require {
+                condition: self.expr_as(scope, condition, &Ty::Bool)?,
+                message: message.clone(),
+            },
+            ast::StmtKind::Assert { condition, message } => hir::StmtKind::Assert {
+                condition: self.expr_as(scope, condition, &Ty::Bool)?,
+                message: message.clone(),
+            },
+            ast::StmtKind::Return(value) => {
+                let return_type = scope.return_type.clone()?;
+                match value {
+                    Some(value) if return_type == Ty::Unit => {
+                        self.error(
+                            ErrorCode::TypeMismatch,
+                            value.span,
+                            "function without a return type cannot return a value",
+                        );
+                        return None;
+                    },
+                    Some(value) => {
+                        hir::StmtKind::Return(Some(self.expr_as(scope, value, &return_type)?))
+                    },
+                    None if return_type != Ty::Unit => {
+                        self.mismatch(&return_type, &Ty::Unit, stmt.span);
+                        return None;
+                    },
+                    None => hir::StmtKind::Return(None),
+                }
+            },
+            ast::StmtKind::Emit { event, fields } => self.emit(scope, event, fields)?,
+            ast::StmtKind::Expr(expr) => hir::StmtKind::Expr(self.expr(scope, expr, None)?),
+        };
+        Some(hir::Stmt {
+            kind,
+            span: stmt.span,
+        })
+    }
+
+    /// Check `emit event { fields }`, ordering the values the way the event
+    /// declares its fields
+    fn emit(
+        &mut self,
+        scope: &mut FunctionScope,
+        event: &ast::Ident,
+        fields: &[ast::FieldInit],
+    ) -> Option<hir::StmtKind> {
+        let index = if self.current != 0 {
+            self.error(
+                ErrorCode::InvalidOperand,
+                event.span,
+                "library functions cannot emit events",
+            );
+            None
+        } else if let Some(&index) = self.event_index.get(&event.name) {
+            Some(index)
+        } else {
+            let message = format!("no event `{}`", event.name);
+            self.push(
+                Diagnostic::error(ErrorCode::UndefinedName, event.span, message)
+                    .with_help("declare it in the contract, as in `event Name { field: u64 }`"),
+            );
+            None
+        };
+        let Some(index) = index else {
+            // The values are still checked for errors of their own
+            for field in fields {
+                self.expr(scope, &field.value, None);
+            }
+            return None;
+        };
+
+        let (declared, types) = self.events[index].clone();
+        // `None` for fields not set yet, `Some(None)` for broken values
+        let mut values: Vec<Option<Option<hir::Expr>>> = vec![None; types.len()];
+        let mut valid = true;
+        for field in fields {
+            let position = declared.fields.iter().position(|f| f.name == field.name.name);
+            let ty = position.and_then(|position| types[position].as_ref());
+            let value = match ty {
+                Some(ty) => self.expr_as(scope, &field.value, ty),
+                None => self.expr(scope, &field.value, None),
+            };
+            let Some(position) = position else {
+                self.error(
+                    ErrorCode::UndefinedName,
+                    field.name.span,
+                    format!("event `{}` has no field `{}`", declared.name, field.name.name),
+                );
+                valid = false;
+                continue;
+            };
+            if values[position].is_some() {
+                self.error(
+                    ErrorCode::DuplicateDeclaration,
+                    field.name.span,
+                    format!("field `{}` is already set", field.name.name),
+                );
+                valid = false;
+                continue;
+            }
+            values[position] = Some(value);
+        }
+
+        let missing: Vec<_> = declared
+            .fields
+            .iter()
+            .zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|(field, _)| format!("`{}`", field.name))
+            .collect();
+        if !missing.is_empty() {
+            self.error(
+                ErrorCode::WrongArity,
+                event.span,
+                format!("missing field(s) {} of event `{}`", missing.join(", "), declared.name),
+            );
+            return None;
+        }
+        let fields = values.into_iter().map(Option::flatten).collect::<Option<Vec<_>>>();
+        Some(hir::StmtKind::Emit {
+            event: index,
+            fields: fields.filter(|_| valid)?,
+        })
+    }
+
+    /// Check `expr` and require its type to be `expected`
+    fn expr_as(
+        &mut self,
+        scope: &mut FunctionScope,
+        expr: &ast::Expr,
+        expected: &Ty,
+    ) -> Option<hir::Expr> {
+        let checked = self.expr(scope, expr, Some(expected))?;
+        if checked.ty != *expected {
+            self.mismatch(expected, &checked.ty, expr.span);
+            return None;
+        }
+        Some(checked)
+    }
+
+    /// Check `expr`; `hint` types integer literals and empty arrays
+    fn expr(
+        &mut self,
+        scope: &mut FunctionScope,
+        expr: &ast::Expr,
+        hint: Option<&Ty>,
+    ) -> Option<hir::Expr> {
+        let (kind, ty) = match &expr.kind {
+            ast::ExprKind::Int(value) => {
+                let ty = hint.filter(|ty| ty.is_integer()).cloned().unwrap_or(Ty::U64);
+                if ty == Ty::I64 && i64::try_from(*value).is_err() {
+                    self.error(
+                        ErrorCode::TypeMismatch,
+                        expr.span,
+                        format!("literal `{value}` does not fit in `i64`"),
+                    );
+                    return None;
+                }
+                (hir::ExprKind::Int(*value), ty)
+            },
+            ast::ExprKind::Bool(value) => (hir::ExprKind::Bool(*value), Ty::Bool),
+            ast::ExprKind::Str(value) => (hir::ExprKind::Str(value.clone()), Ty::String),
+            ast::ExprKind::Ident(name) => return self.name(scope, name, expr.span),
+            ast::ExprKind::Field { base, field } => {
+                if !matches!(&base.kind, ast::ExprKind::Ident(name) if name == "self") {
+                    self.expr(scope, base, None)?;
+                    self.error(
+                        ErrorCode::InvalidOperand,
+                        field.span,
+                        "only `self` has fields, as in `self.balance`",
+                    );
+                    return None;
+                }
+                if self.current != 0 {
+                    self.error(
+                        ErrorCode::InvalidOperand,
+                        expr.span,
+                        "library functions cannot access contract state",
+                    );
+                    return None;
+                }
+                let Some(&index) = self.state_index.get(&field.name) else {
+                    self.error(
+                        ErrorCode::UndefinedName,
+                        field.span,
+                        format!("no state variable `{}`", field.name),
+                    );
+                    return None;
+                };
+                let ty = self.state[index].1.clone()?;
+                (hir::ExprKind::State(index), ty)
+            },
+            ast::ExprKind::Index { base, index } => {
+                let base = self.expr(scope, base, None);
+                let index = self.expr_as(scope, index, &Ty::U64);
+                let base = base?;
+                let Ty::Array { element, .. } = &base.ty else {
+                    self.error(
+                        ErrorCode::InvalidOperand,
+                        base.span,
+                        format!("cannot index into a value of type `{}`", base.ty),
+                    );
+                    return None;
+                };
+                let ty = (**element).clone();
+                (
+                    hir::ExprKind::Index {
+                        base: Box::new(base),
+                        index: Box::new(index?),
+                    },
+                    ty,
+                )
+            },
+            ast::ExprKind::Array(elements) => return self.array(scope, elements, expr.span, hint),
+            ast::ExprKind::Unary { op, operand } => {
+                let ty = match op {
+                    UnaryOp::Not => Ty::Bool,
+                    UnaryOp::Neg => Ty::I64,
+                };
+                let operand = self.expr_as(scope, operand, &ty)?;
+                let kind = hir::ExprKind::Unary {
+                    op: *op,
+                    operand: Box::new(operand),
+                };
+                (kind, ty)
+            },
+            ast::ExprKind::Binary { op, lhs, rhs } => {
+                return self.binary(scope, *op, lhs, rhs, hint);
+            },
+            ast::ExprKind::Call { callee, args } => {
+                return self.call(scope, callee, args, expr.span);
+            },
+        };
+        Some(hir::Expr {
+            kind,
+            ty,
+            span: expr.span,
+        })
+    }
+
+    fn name(&mut self, scope: &mut FunctionScope, name: &str, span: Span) -> Option<hir::Expr> {
+        if let Some(id) = scope.lookup(name) {
+            scope.used[id.0] = true;
+            if scope.poisoned[id.0] {
+                return None;
+            }
+            return Some(hir::Expr {
+                kind: hir::ExprKind::Local(id),
+                ty: scope.locals[id.0].ty.clone(),
+                span,
+            });
+        }
+
+        let (code, message) = if name == "self" {
+            (ErrorCode::InvalidOperand, "`self` can only be used to access state".to_string())
+        } else if self.modules[self.current].functions.contains_key(name) {
+            (ErrorCode::InvalidOperand, format!("function `{name}` must be called"))
+        } else if self.current == 0 && self.state_index.contains_key(name) {
+            (ErrorCode::UndefinedName, format!("cannot find `{name}`, use `self.{name}`"))
+        } else {
+            (ErrorCode::UndefinedName, format!("cannot find `{name}` in this scope"))
+        };
+        self.error(code, span, message);
+        None
+    }
+
+    fn array(
+        &mut self,
+        scope: &mut FunctionScope,
+        elements: &[ast::Expr],
+        span: Span,
+        hint: Option<&Ty>,
+    ) -> Option<hir::Expr> {
+        let element_hint = match hint {
+            Some(Ty::Array { element, .. }) => Some((**element).clone()),
+            _ => None,
+        };
+        let Some((first, rest)) = elements.split_first() else {
+            let Some(ty) = hint.filter(|ty| matches!(ty, Ty::Array { .. })) else {
+                self.error(
+                    ErrorCode::TypeMismatch,
+                    span,
+                    "cannot infer the type of an empty array",
+                );
+                return None;
+            };
+            return Some(hir::Expr {
+                kind: hir::ExprKind::Array(Vec::new()),
+                ty: ty.clone(),
+                span,
+            });
+        };
+
+        let first = self.expr(scope, first, element_hint.as_ref())?;
+        let element = first.ty.clone();
+        let rest: Vec<_> = rest.iter().map(|e| self.expr_as(scope, e, &element)).collect();
+        let mut checked = vec![first];
+        checked.extend(rest.into_iter().collect::<Option<Vec<_>>>()?);
+        Some(hir::Expr {
+            ty: Ty::Array {
+                element: Box::new(element),
+                len: checked.len() as u64,
+            },
+            kind: hir::ExprKind::Array(checked),
+            span,
+        })
+    }
+
+    fn binary(
+        &mut self,
+        scope: &mut FunctionScope,
+        op: BinaryOp,
+        lhs: &ast::Expr,
+        rhs: &ast::Expr,
+        hint: Option<&Ty>,
+    ) -> Option<hir::Expr> {
+        let span = lhs.span.to(rhs.span);
+        let (lhs, rhs) = if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            let lhs = self.expr_as(scope, lhs, &Ty::Bool);
+            let rhs = self.expr_as(scope, rhs, &Ty::Bool);
+            (lhs?, rhs?)
+        } else {
+            let hint = match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                    hint.filter(|ty| ty.is_integer())
+                },
+                _ => None,
+            };
+            // A literal takes the type of the other operand
+            if is_int_literal(lhs) && !is_int_literal(rhs) {
+                let rhs = self.expr(scope, rhs, hint)?;
+                let lhs = self.expr_as(scope, lhs, &rhs.ty.clone())?;
+                (lhs, rhs)
+            } else {
+                let lhs = self.expr(scope, lhs, hint)?;
+                let rhs = self.expr_as(scope, rhs, &lhs.ty.clone())?;
+                (lhs, rhs)
+            }
+        };
+
+        let ty = match op {
+            BinaryOp::And | BinaryOp::Or => Ty::Bool,
+            BinaryOp::Eq | BinaryOp::Ne => Ty::Bool,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge if lhs.ty.is_integer() => {
+                Ty::Bool
+            },
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem
+                if lhs.ty.is_integer() =>
+            {
+                lhs.ty.clone()
+            },
+            _ => {
+                self.error(
+                    ErrorCode::InvalidOperand,
+                    span,
+                    format!("operator `{}` cannot be applied to `{}`", op_text(op), lhs.ty),
+                );
+                return None;
+            },
+        };
+        Some(hir::Expr {
+            kind: hir::ExprKind::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            ty,
+            span,
+        })
+    }
+
+    fn call(
+        &mut self,
+        scope: &mut FunctionScope,
+        callee: &ast::Expr,
+        args: &[ast::Expr],
+        span: Span,
+    ) -> Option<hir::Expr> {
+        let ast::ExprKind::Ident(name) = &callee.kind else {
+            let message = "only named functions can be called";
+            self.error(ErrorCode::InvalidOperand, callee.span, message);
+            return None;
+        };
+        let (callee_id, params, return_type) =
+            if let Some(builtin) = self.builtin(name) {
+                let (params, return_type) = builtin.signature();
+                let params = params.into_iter().map(Some).collect();
+                (Callee::Builtin(builtin), params, Some(return_type))
+            } else if let Some(index) = self.resolve_function(name, callee.span) {
+                let signature = &self.signatures[index];
+                (Callee::Function(index), signature.params.clone(), signature.return_type.clone())
+            } else {
+                for arg in args {
+                    self.expr(scope, arg, None);
+                }
+                return None;
+            };
+
+        let arity_ok = params.len() == args.len();
+        if !arity_ok {
+            self.error(
+                ErrorCode::WrongArity,
+                span,
+                format!(
+                    "function `{name}` takes {} argument(s) but {} were supplied",
+                    params.len(),
+                    args.len()
+                ),
+            );
+        }
+        let checked: Vec<_> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match params.get(i) {
+                Some(Some(ty)) => self.expr_as(scope, arg, ty),
+                _ => self.expr(scope, arg, None),
+            })
+            .collect();
+        if !arity_ok {
+            return None;
+        }
+
+        Some(hir::Expr {
+            kind: hir::ExprKind::Call {
+                callee: callee_id,
+                args: checked.into_iter().collect::<Option<_>>()?,
+            },
+            ty: return_type?,
+            span,
+        })
+    }
+
+    /// Builtin `name` refers to in the current module: a global one by its
+    /// name, or one of an imported standard module as `alias::name`
+    fn builtin(&self, name: &str) -> Option<Builtin> {
+        let (module, item) = match name.rsplit_once("::") {
+            None => (None, name),
+            Some((alias, item)) => {
+                let module = *self.modules[self.current].imports.get(alias)?;
+                (Some(self.modules[module].file.as_deref()?), item)
+            },
+        };
+        Builtin::ALL.into_iter().find(|b| b.module() == module && b.name() == item)
+    }
+
+    /// Index of the function `name` refers to in the current module,
+    /// reporting why if there is none
+    fn resolve_function(&mut self, name: &str, span: Span) -> Option<usize> {
+        let Some((alias, item)) = name.rsplit_once("::") else {
+            let found = self.modules[self.current].functions.get(name).copied();
+            if found.is_none() {
+                let message = format!("cannot find function `{name}`");
+                self.error(ErrorCode::UndefinedName, span, message);
+            }
+            return found;
+        };
+        let Some(&module) = self.modules[self.current].imports.get(alias) else {
+            self.push(
+                Diagnostic::error(
+                    ErrorCode::UndefinedName,
+                    span,
+                    format!("cannot find module `{alias}`"),
+                )
+                .with_help(format!("import it first, as in `import \"{alias}\";`")),
+            );
+            return None;
+        };
+        let Some(&index) = self.modules[module].functions.get(item) else {
+            let message = format!("cannot find function `{item}` in module `{alias}`");
+            self.error(ErrorCode::UndefinedName, span, message);
+            return None;
+        };
+        if !self.signatures[index].public {
+            self.push(
+                Diagnostic::error(
+                    ErrorCode::PrivateItem,
+                    span,
+                    format!("function `{item}` of module `{alias}` is private"),
+                )
+                .with_help(format!("declare it `pub fn {item}` to call it from other modules")),
+            );
+            return None;
+        }
+        Some(index)
+    }
+}
+
+fn is_int_literal(expr: &ast::Expr) -> bool {
+    matches!(expr.kind, ast::ExprKind::Int(_))
+}
+
+fn op_text(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Or => "||",
+        BinaryOp::And => "&&",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+    }
+}
+
+/// Whether every path through `block` ends in `return`
+fn block_returns(block: &ast::Block) -> bool {
+    block.statements.iter().any(stmt_returns)
+}
+
+/// Whether every path through `stmt` ends in `return`
+fn stmt_returns(stmt: &ast::Stmt) -> bool {
+    match &stmt.kind {
+        ast::StmtKind::Return(_) => true,
+        ast::StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => block_returns(then_branch) && block_returns(else_branch),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn check_source(source: &str) -> Result<hir::Contract, ErrorCollection<Diagnostic>> {
+        check(&parse(source).unwrap())
+    }
+
+    /// `(code, line, column)` of every diagnostic for `source`
+    fn diagnostics(source: &str) -> Vec<(&'static str, u32, u32)> {
+        check_source(source)
+            .unwrap_err()
+            .iter()
+            .map(|d| (d.code.as_str(), d.span.start.line, d.span.start.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_well_typed_contract() {
+        let contract = check_source(
+            "contract Vault {
+                state { owner: address; balances: [u64; 4]; total: i64; }
+                fn deposit(slot: u64, amount: u64) {
+                    require(caller() == self.owner, \"owner only\");
+                    let before = self.balances[slot];
+                    self.balances[slot] = before + amount * 2;
+                    self.total = -1 + self.total;
+                }
+                fn largest() -> u64 {
+                    let values: [u64; 2] = [self.balances[0], 7];
+                    if values[0] > values[1] { return values[0]; } else { return values[1]; }
+                }
+                fn check(now: u64) -> bool { return now < now() && largest() != 0; }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(contract.state[1].ty.to_string(), "[u64; 4]");
+        let deposit = &contract.functions[0];
+        assert_eq!(deposit.params, vec![LocalId(0), LocalId(1)]);
+        assert_eq!(deposit.locals[2].name, "before");
+        assert_eq!(deposit.locals[2].ty, Ty::U64);
+        assert_eq!(contract.functions[1].return_type, Ty::U64);
+        let hir::StmtKind::Return(Some(value)) = &contract.functions[2].body.statements[0].kind
+        else {
+            panic!("expected a return");
+        };
+        assert_eq!(value.ty, Ty::Bool);
+    }
+
+    #[test]
+    fn test_bad_programs() {
+        let cases: &[(&str, (&str, u32, u32))] = &[
+            ("contract C { fn f() { x = 1; } }", ("E0001", 1, 23)),
+            ("contract C { fn f() { g(); } }", ("E0001", 1, 23)),
+            ("contract C { state { a: u64; } fn f() -> u64 { return a; } }", ("E0001", 1, 55)),
+            ("contract C { fn f() -> u64 { return self.b; } }", ("E0001", 1, 42)),
+            ("contract C { state { a: u64; a: bool; } }", ("E0002", 1, 30)),
+            ("contract C { fn f() {} fn f() {} }", ("E0002", 1, 27)),
+            ("contract C { fn f(a: u64, a: u64) {} }", ("E0002", 1, 27)),
+            ("contract C { fn now() {} }", ("E0002", 1, 17)),
+            ("contract C { fn f() { let a = 1; let a = 2; } }", ("E0002", 1, 38)),
+            ("contract C { state { a: u256; } }", ("E0003", 1, 25)),
+            ("contract C { fn f(a: [foo; 2]) {} }", ("E0003", 1, 23)),
+            ("contract C { fn f() { let a: bool = 1; } }", ("E0004", 1, 37)),
+            ("contract C { fn f() -> u64 { return true; } }", ("E0004", 1, 37)),
+            ("contract C { fn f(a: u64) { require(a, \"x\"); } }", ("E0004", 1, 37)),
+            ("contract C { fn f(a: u64, b: i64) { let c = a + b; } }", ("E0004", 1, 49)),
+            ("contract C { fn f(a: u64) -> i64 { return -a; } }", ("E0004", 1, 44)),
+            ("contract C { fn f() { return 1; } }", ("E0004", 1, 30)),
+            ("contract C { fn f() -> u64 { return; } }", ("E0004", 1, 30)),
+            ("contract C { fn f() { let a: [u64; 2] = [1, true]; } }", ("E0004", 1, 45)),
+            ("contract C { fn f() { let a = []; } }", ("E0004", 1, 31)),
+            ("contract C { fn f(a: u64) {} fn g() { f(); } }", ("E0005", 1, 39)),
+            ("contract C { fn g() { let a = caller(1); } }", ("E0005", 1, 31)),
+            ("contract C { fn f(a: u64) -> u64 { if a > 1 { return 1; } } }", ("E0006", 1, 17)),
+            ("contract C { fn f(a: bool) -> bool { return a + a; } }", ("E0007", 1, 45)),
+            ("contract C { fn f(a: u64) -> u64 { return a[0]; } }", ("E0007", 1, 43)),
+            ("contract C { fn f(a: u64) -> u64 { return a.b; } }", ("E0007", 1, 45)),
+            ("contract C { fn f() {} fn g() { let x = f; } }", ("E0007", 1, 41)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(diagnostics(source), vec![*expected], "{source}");
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let contract = check_source(
+            "contract Token {
+                event Transfer { to: address, amount: u64 }
+                fn send(to: address) { emit Transfer { amount: 1, to: to }; }
+            }",
+        )
+        .unwrap();
+        assert_eq!(contract.events[0].fields[1].ty, Ty::U64);
+        // Field values are stored in declaration order
+        let statement = &contract.functions[0].body.statements[0];
+        let hir::StmtKind::Emit { event: 0, fields } = &statement.kind else {
+            panic!("expected an emit statement");
+        };
+        assert_eq!(fields[1].kind, hir::ExprKind::Int(1));
+
+        let event = "contract C { event E { a: u64 } ";
+        let cases: &[(&str, (&str, u32, u32))] = &[
+            ("fn f() { emit Missing {}; } }", ("E0001", 1, 47)),
+            ("fn f() { emit E { a: 1, b: 2 }; } }", ("E0001", 1, 57)),
+            ("fn f() { emit E { a: 1, a: 2 }; } }", ("E0002", 1, 57)),
+            ("fn f() { emit E {}; } }", ("E0005", 1, 47)),
+            ("fn f() { emit E { a: true }; } }", ("E0004", 1, 54)),
+            ("event E {} }", ("E0002", 1, 39)),
+        ];
+        for (rest, expected) in cases {
+            let source = format!("{event}{rest}");
+            assert_eq!(diagnostics(&source), vec![*expected], "{source}");
+        }
+        assert_eq!(diagnostics("contract C { event E { a: u64, a: bool } }"), [("E0002", 1, 32)]);
+    }
+
+    #[test]
+    fn test_errors_are_collected() {
+        let source = "contract C {
+            state { a: u256; }
+            fn f(x: u64) -> bool {
+                let y: bool = x;
+                missing(y);
+                return self.a == 1;
+            }
+            fn g() -> u64 { f(1, 2); }
+        }";
+        assert_eq!(
+            diagnostics(source),
+            vec![
+                ("E0003", 2, 24),
+                ("E0004", 4, 31),
+                ("E0001", 5, 17),
+                ("E0005", 8, 29),
+                ("E0006", 8, 16),
+            ]
+        );
+
+        // Diagnostics render with their code and position
+        let err = check_source("contract C { fn f() { x = 1; } }").unwrap_err();
+        assert_eq!(err.to_string(), "1:23: error[E0001]: cannot find `x` in this scope");
+    }
+}
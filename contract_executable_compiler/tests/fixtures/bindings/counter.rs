@@ -0,0 +1,66 @@
+// Client for contract `Counter`, generated by contract_executable_compiler; do not edit.
+
+pub mod counter_client {
+    use contract_executable_compiler::{ContractExecutor, ContractInstance};
+    use shared_core::Result;
+
+    /// Typed calls to an instance of contract `Counter`
+    pub struct CounterClient<'a> {
+        /// Executor making the calls
+        pub executor: &'a ContractExecutor,
+        /// Instance called
+        pub instance: &'a ContractInstance,
+    }
+
+    impl CounterClient<'_> {
+        /// Call contract function `increment`
+        pub fn increment(&self) -> Result<()> {
+            ContractExecutor::call_typed(self.executor, self.instance, "increment", vec![])
+        }
+
+        /// Call contract function `add`
+        pub fn add(&self, amount: u64) -> Result<u64> {
+            ContractExecutor::call_typed(self.executor, self.instance, "add", vec![amount.into()])
+        }
+
+        /// Call contract function `reset`
+        pub fn reset(&self) -> Result<()> {
+            ContractExecutor::call_typed(self.executor, self.instance, "reset", vec![])
+        }
+
+        /// Call contract function `get`
+        pub fn get(&self) -> Result<u64> {
+            ContractExecutor::call_typed(self.executor, self.instance, "get", vec![])
+        }
+
+        /// Read state variable `count`
+        pub fn get_count(&self) -> Result<u64> {
+            ContractExecutor::call_typed(self.executor, self.instance, "get_count", vec![])
+        }
+
+        /// Overwrite state variable `count`
+        pub fn set_count(&self, value: u64) -> Result<()> {
+            ContractExecutor::call_typed(
+                self.executor,
+                self.instance,
+                "set_count",
+                vec![value.into()],
+            )
+        }
+
+        /// Read state variable `owner`
+        pub fn get_owner(&self) -> Result<u64> {
+            ContractExecutor::call_typed(self.executor, self.instance, "get_owner", vec![])
+        }
+
+        /// Overwrite state variable `owner`
+        pub fn set_owner(&self, value: u64) -> Result<()> {
+            ContractExecutor::call_typed(
+                self.executor,
+                self.instance,
+                "set_owner",
+                vec![value.into()],
+            )
+        }
+    }
+}
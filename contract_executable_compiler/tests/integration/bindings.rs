@@ -0,0 +1,41 @@
+//! Calls a contract through the client generated from its ABI, checked in
+//! as `tests/fixtures/bindings/counter.rs`
+
+use contract_executable_compiler::runtime::DEFAULT_FUEL_LIMIT;
+use contract_executable_compiler::{
+    CallContext, CompilationTarget, CompilerConfig, ContractCompiler, ContractExecutor,
+};
+
+include!("../fixtures/bindings/counter.rs");
+
+use counter_client::CounterClient;
+
+#[test]
+fn test_generated_client_calls_contract() {
+    let compiler = ContractCompiler::new(CompilerConfig {
+        target: CompilationTarget::Wasm,
+        ..CompilerConfig::default()
+    })
+    .unwrap();
+    let source = std::fs::read_to_string("tests/fixtures/codegen/counter.contract").unwrap();
+    let executor = ContractExecutor::new(DEFAULT_FUEL_LIMIT).unwrap();
+    let instance = executor.load(&compiler.compile(&source).unwrap().into()).unwrap();
+    let client = CounterClient {
+        executor: &executor,
+        instance: &instance,
+    };
+
+    instance.set_context(CallContext { caller: 7, now: 0 });
+    client.reset().unwrap();
+    assert_eq!(client.get_owner().unwrap(), 7);
+    assert_eq!(client.add(5).unwrap(), 5);
+    client.increment().unwrap();
+    assert_eq!(client.get().unwrap(), 6);
+
+    client.set_count(40).unwrap();
+    assert_eq!(client.get_count().unwrap(), 40);
+    client.set_owner(9).unwrap();
+    let err = client.add(1).unwrap_err();
+    assert!(err.to_string().contains("trapped"), "{err}");
+    assert_eq!(client.get().unwrap(), 40);
+}
@@ -0,0 +1,73 @@
+//! Runs a contract compiled to WebAssembly under wasmtime
+
+use contract_executable_compiler::{
+    CompilationTarget, CompileOutput, CompilerConfig, ContractCompiler,
+};
+use wasmtime::{Engine, Linker, Module, Store};
+
+const COUNTER: &str = r#"
+contract Counter {
+    state {
+        count: u64;
+        owner: address;
+    }
+
+    fn increment() {
+        self.count = self.count + 1;
+    }
+
+    fn add(amount: u64) {
+        require(caller() == self.owner, "not the owner");
+        self.count = self.count + amount;
+    }
+
+    fn get() -> u64 {
+        return self.count;
+    }
+}
+"#;
+
+fn compile(source: &str) -> Vec<u8> {
+    let compiler = ContractCompiler::new(CompilerConfig {
+        target: CompilationTarget::Wasm,
+        ..CompilerConfig::default()
+    })
+    .unwrap();
+    match compiler.compile(source).unwrap() {
+        CompileOutput::WasmBytes(module) => module,
+        output => panic!("expected a wasm module, got {output:?}"),
+    }
+}
+
+#[test]
+fn test_counter_increment_and_get() {
+    let engine = Engine::default();
+    let module = Module::new(&engine, compile(COUNTER)).unwrap();
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("env", "caller", |_: wasmtime::Caller<'_, ()>| 7_i64).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+
+    let init = instance.get_typed_func::<(), ()>(&mut store, "_init").unwrap();
+    let increment = instance.get_typed_func::<(), ()>(&mut store, "increment").unwrap();
+    let add = instance.get_typed_func::<i64, ()>(&mut store, "add").unwrap();
+    let get = instance.get_typed_func::<(), i64>(&mut store, "get").unwrap();
+    let set_owner = instance.get_typed_func::<i64, ()>(&mut store, "set_owner").unwrap();
+    let get_count = instance.get_typed_func::<(), i64>(&mut store, "get_count").unwrap();
+
+    init.call(&mut store, ()).unwrap();
+    assert_eq!(get.call(&mut store, ()).unwrap(), 0);
+    for _ in 0..3 {
+        increment.call(&mut store, ()).unwrap();
+    }
+    assert_eq!(get.call(&mut store, ()).unwrap(), 3);
+
+    // `require` traps until the caller owns the counter
+    assert!(add.call(&mut store, 10).is_err());
+    set_owner.call(&mut store, 7).unwrap();
+    add.call(&mut store, 10).unwrap();
+    assert_eq!(get_count.call(&mut store, ()).unwrap(), 13);
+
+    init.call(&mut store, ()).unwrap();
+    assert_eq!(get.call(&mut store, ()).unwrap(), 0);
+}
@@ -0,0 +1,151 @@
+//! Differential tests of the optimizer: optimized and unoptimized Wasm
+//! builds of the same contract must behave identically
+
+use std::fs;
+use std::path::PathBuf;
+
+use contract_executable_compiler::{
+    CompilationTarget, CompileOutput, CompilerConfig, ContractCompiler, OptLevel, OptStats,
+};
+use wasmtime::{Engine, Instance, Linker, Memory, Module, Store, Val, ValType};
+
+const FIXTURES: &str = "tests/fixtures/optimizer";
+/// Calls made into each build
+const STEPS: u64 = 400;
+/// Integers the inputs are drawn from, as raw `i64` bits
+const INTERESTING: [i64; 10] = [0, 1, 2, 3, 5, 7, 11, 100, -1, i64::MIN];
+
+fn compile(source: &str, opt_level: OptLevel) -> (Vec<u8>, OptStats) {
+    let compiler = ContractCompiler::new(CompilerConfig {
+        target: CompilationTarget::Wasm,
+        opt_level,
+        ..CompilerConfig::default()
+    })
+    .unwrap();
+    match compiler.compile_with_stats(source).unwrap() {
+        (CompileOutput::WasmBytes(module), stats) => (module, stats),
+        (output, _) => panic!("expected a wasm module, got {output:?}"),
+    }
+}
+
+/// Step counter the `env` imports derive their values from
+struct Host {
+    step: u64,
+}
+
+struct Build {
+    store: Store<Host>,
+    instance: Instance,
+    memory: Memory,
+}
+
+impl Build {
+    fn new(engine: &Engine, module: &[u8]) -> Self {
+        let module = Module::new(engine, module).unwrap();
+        let mut linker = Linker::new(engine);
+        linker
+            .func_wrap("env", "caller", |caller: wasmtime::Caller<'_, Host>| {
+                (caller.data().step % 3) as i64
+            })
+            .unwrap();
+        linker
+            .func_wrap("env", "now", |caller: wasmtime::Caller<'_, Host>| caller.data().step as i64)
+            .unwrap();
+        let mut store = Store::new(engine, Host { step: 0 });
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        Self {
+            store,
+            instance,
+            memory,
+        }
+    }
+
+    /// Call `name`, returning its results or `None` if it trapped
+    fn call(&mut self, step: u64, name: &str, args: &[Val]) -> Option<Vec<i64>> {
+        self.store.data_mut().step = step;
+        let func = self.instance.get_func(&mut self.store, name).unwrap();
+        let mut results = vec![Val::I64(0); func.ty(&self.store).results().len()];
+        func.call(&mut self.store, args, &mut results).ok()?;
+        Some(
+            results
+                .iter()
+                .map(|val| match val {
+                    Val::I32(v) => i64::from(*v),
+                    Val::I64(v) => *v,
+                    other => panic!("unexpected result {other:?}"),
+                })
+                .collect(),
+        )
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.memory.data(&self.store)[..256].to_vec()
+    }
+}
+
+/// Deterministic pseudo-random numbers
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 33
+    }
+
+    fn arg(&mut self, ty: &ValType) -> Val {
+        let value = INTERESTING[self.next() as usize % INTERESTING.len()];
+        match ty {
+            ValType::I32 => Val::I32((value & 1) as i32),
+            _ => Val::I64(value),
+        }
+    }
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "contract"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures in {FIXTURES}");
+    paths
+}
+
+#[test]
+fn test_optimized_builds_match_unoptimized() {
+    let engine = Engine::default();
+    for path in fixtures() {
+        let source = fs::read_to_string(&path).unwrap();
+        let (plain, plain_stats) = compile(&source, OptLevel::None);
+        let (optimized, stats) = compile(&source, OptLevel::Full);
+        assert_eq!(plain_stats, OptStats::default());
+        assert!(stats.constants_folded > 0, "{}: {stats:?}", path.display());
+        assert!(stats.nodes_removed > 0, "{}: {stats:?}", path.display());
+        assert!(optimized.len() < plain.len(), "{}", path.display());
+
+        let mut plain = Build::new(&engine, &plain);
+        let mut optimized = Build::new(&engine, &optimized);
+        let module = plain.instance.exports(&mut plain.store).map(|e| e.name().to_string());
+        let mut functions: Vec<_> = module.filter(|name| name != "memory").collect();
+        functions.sort();
+
+        let mut rng = Lcg(7);
+        for step in 0..STEPS {
+            let name = &functions[rng.next() as usize % functions.len()];
+            let func = plain.instance.get_func(&mut plain.store, name).unwrap();
+            let params: Vec<ValType> = func.ty(&plain.store).params().collect();
+            let args: Vec<Val> = params.iter().map(|ty| rng.arg(ty)).collect();
+
+            let expected = plain.call(step, name, &args);
+            let actual = optimized.call(step, name, &args);
+            let context = format!("{} step {step}: {name}({args:?})", path.display());
+            assert_eq!(actual, expected, "{context}");
+            assert_eq!(optimized.state(), plain.state(), "{context}");
+        }
+    }
+}
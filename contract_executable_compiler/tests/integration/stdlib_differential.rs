@@ -0,0 +1,339 @@
+//! Differential tests of the standard library: a contract using it must
+//! give the same results as a native reference when interpreted, when
+//! compiled to Wasm and when compiled to Rust, overflow errors included.
+//! Where it fails, the interpreter and the generated Rust must also give
+//! the same reason, and so must Wasm where a builtin of `std::map` or
+//! `std::assert` fails. A second contract keeps a `std::map` in its state
+//! through a script of calls, which all three must run identically.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::process::Command;
+
+use contract_executable_compiler::{
+    CompilationTarget, CompileOutput, CompilerConfig, ContractCompiler, ContractExecutor,
+};
+use serde_json::{json, Value};
+
+/// Set to skip the half of the test that needs `rustc`
+const SKIP_ENV: &str = "SKIP_RUSTC_TESTS";
+
+const CONTRACT: &str = r#"
+import std::math;
+import std::assert;
+
+contract Calc {
+    fn add(a: u64, b: u64) -> u64 { return math::checked_add(a, b); }
+    fn sub(a: u64, b: u64) -> u64 { return math::checked_sub(a, b); }
+    fn mul(a: u64, b: u64) -> u64 { return math::checked_mul(a, b); }
+    fn pow(a: u64, b: u64) -> u64 { return math::pow(a, b); }
+    fn low(a: u64, b: u64) -> u64 { return math::min(a, b); }
+    fn high(a: u64, b: u64) -> u64 { return math::max(a, b); }
+    fn clamp(a: u64, b: u64) -> u64 { return math::clamp(a, b, 1000); }
+    fn equal(a: u64, b: u64) -> u64 {
+        assert::require_eq(a, b);
+        return a;
+    }
+    fn above(a: u64, b: u64) -> u64 {
+        assert::require_gt(a, b);
+        return a - b;
+    }
+}
+"#;
+
+const METHODS: [&str; 9] =
+    ["add", "sub", "mul", "pow", "low", "high", "clamp", "equal", "above"];
+
+const VALUES: [u64; 11] =
+    [0, 1, 2, 3, 10, 63, 64, u32::MAX as u64, u64::MAX / 2, u64::MAX - 1, u64::MAX];
+
+/// Native result of `method`, `None` where the contract reverts
+fn reference(method: &str, a: u64, b: u64) -> Option<u64> {
+    match method {
+        "add" => a.checked_add(b),
+        "sub" => a.checked_sub(b),
+        "mul" => a.checked_mul(b),
+        "pow" => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)).or_else(|| {
+            // Exponents beyond `u32` only fit for bases 0 and 1
+            (a <= 1).then_some(a)
+        }),
+        "low" => Some(a.min(b)),
+        "high" => Some(a.max(b)),
+        "clamp" => (b <= 1000).then(|| a.max(b).min(1000)),
+        "equal" => (a == b).then_some(a),
+        "above" => (a > b).then(|| a - b),
+        _ => unreachable!("unknown method {method}"),
+    }
+}
+
+/// Keeps balances by name
+const REGISTRY: &str = r#"
+import std::map;
+import std::assert;
+
+contract Registry {
+    state { balances: map; }
+    fn set(name: string, amount: u64) -> u64 {
+        self.balances = map::insert(self.balances, name, amount);
+        return map::get(self.balances, name);
+    }
+    fn lookup(name: string) -> u64 { return map::get(self.balances, name); }
+    fn remove(name: string) -> bool {
+        let had = map::contains(self.balances, name);
+        self.balances = map::remove(self.balances, name);
+        return had;
+    }
+    fn at_least(name: string, low: u64) -> u64 {
+        let amount = map::get(self.balances, name);
+        assert::require_ge(amount, low);
+        return amount;
+    }
+}
+"#;
+
+/// Calls made to `REGISTRY` in order, with the amount argument if any
+const SCRIPT: [(&str, &str, Option<u64>); 14] = [
+    ("lookup", "alice", None),
+    ("set", "alice", Some(5)),
+    ("set", "", Some(3)),
+    ("set", "bob", Some(7)),
+    ("lookup", "alice", None),
+    ("at_least", "alice", Some(6)),
+    ("at_least", "bob", Some(7)),
+    ("remove", "alice", None),
+    ("remove", "alice", None),
+    ("lookup", "alice", None),
+    ("set", "bob", Some(u64::MAX)),
+    ("at_least", "bob", Some(u64::MAX)),
+    ("lookup", "", None),
+    ("lookup", "\"zoë\"", None),
+];
+
+fn script_args(name: &str, amount: Option<u64>) -> Value {
+    match amount {
+        Some(amount) => json!([name, amount]),
+        None => json!([name]),
+    }
+}
+
+/// The revert reason at the end of a failed call's message
+fn reason(message: &str) -> &str {
+    let start = message.find("reverted: ").unwrap_or_else(|| panic!("not a revert: {message}"));
+    &message[start..]
+}
+
+fn inputs() -> impl Iterator<Item = (&'static str, u64, u64)> {
+    METHODS.into_iter().flat_map(|method| {
+        VALUES.into_iter().flat_map(move |a| VALUES.into_iter().map(move |b| (method, a, b)))
+    })
+}
+
+/// Result of every input on the interpreter, failures as their message
+fn interpret() -> Vec<Result<u64, String>> {
+    let compiler = ContractCompiler::new(CompilerConfig::default()).unwrap();
+    let mut contract = compiler.interpret(CONTRACT).unwrap();
+    inputs()
+        .map(|(method, a, b)| {
+            let result = contract.call(method, json!([a, b])).map_err(|err| err.to_string());
+            result.map(|value| value.as_u64().unwrap())
+        })
+        .collect()
+}
+
+/// Result of every call of `SCRIPT` on the interpreter
+fn interpret_script() -> Vec<Result<Value, String>> {
+    let compiler = ContractCompiler::new(CompilerConfig::default()).unwrap();
+    let mut contract = compiler.interpret(REGISTRY).unwrap();
+    SCRIPT
+        .iter()
+        .map(|&(method, name, amount)| {
+            contract.call(method, script_args(name, amount)).map_err(|err| err.to_string())
+        })
+        .collect()
+}
+
+fn compile(source: &str, target: CompilationTarget) -> CompileOutput {
+    let compiler = ContractCompiler::new(CompilerConfig {
+        target,
+        ..CompilerConfig::default()
+    })
+    .unwrap();
+    compiler.compile(source).unwrap()
+}
+
+/// Lines printed by the Rust generated for `source`, run with a `main`
+/// making `calls` on a new `module::contract`, one line per call
+fn run_generated_rust(
+    source: &str,
+    module: &str,
+    contract: &str,
+    calls: &[String],
+) -> Vec<String> {
+    let CompileOutput::RustSource(mut source) = compile(source, CompilationTarget::Rust) else {
+        panic!("expected Rust source");
+    };
+    write!(
+        source,
+        "
+struct Host;
+
+impl {module}::Env for Host {{
+    fn caller(&self) -> {module}::Address {{
+        0
+    }}
+
+    fn now(&self) -> u64 {{
+        0
+    }}
+}}
+
+fn main() {{
+    let mut contract = {module}::{contract}::new();
+"
+    )
+    .unwrap();
+    for call in calls {
+        writeln!(
+            source,
+            "    match contract.{call} {{ \
+             Ok(value) => println!(\"{{value}}\"), Err(err) => println!(\"error: {{err}}\") }}"
+        )
+        .unwrap();
+    }
+    source.push_str("}\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(format!("{module}.rs"));
+    let binary = dir.path().join(module);
+    fs::write(&file, source).unwrap();
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc)
+        .args(["--edition", "2021", "-o"])
+        .arg(&binary)
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = Command::new(&binary).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    stdout.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn test_interpreter_matches_reference() {
+    for ((method, a, b), result) in inputs().zip(interpret()) {
+        assert_eq!(result.as_ref().ok(), reference(method, a, b).as_ref(), "{method}({a}, {b})");
+    }
+}
+
+#[test]
+fn test_wasm_matches_reference() {
+    let executor = ContractExecutor::default();
+    let instance = executor.load(&compile(CONTRACT, CompilationTarget::Wasm).into()).unwrap();
+    for ((method, a, b), interpreted) in inputs().zip(interpret()) {
+        let result = executor.call(&instance, method, json!([a, b]));
+        let call = format!("{method}({a}, {b})");
+        match (result, interpreted) {
+            (Ok(value), _) => assert_eq!(value.as_u64(), reference(method, a, b), "{call}"),
+            // `std::assert` reverts with its message, `std::math` traps
+            (Err(err), Err(message)) if matches!(method, "equal" | "above") => {
+                let err = err.to_string();
+                assert!(err.ends_with(reason(&message)), "{call}: {message} vs {err}");
+            },
+            (Err(_), _) => assert_eq!(reference(method, a, b), None, "{call}"),
+        }
+    }
+}
+
+#[test]
+fn test_map_script_matches_across_targets() {
+    let interpreted = interpret_script();
+    let expected = [
+        None,
+        Some(json!(5)),
+        Some(json!(3)),
+        Some(json!(7)),
+        Some(json!(5)),
+        None,
+        Some(json!(7)),
+        Some(json!(true)),
+        Some(json!(false)),
+        None,
+        Some(json!(u64::MAX)),
+        Some(json!(u64::MAX)),
+        Some(json!(3)),
+        None,
+    ];
+    let results: Vec<_> = interpreted.iter().map(|r| r.as_ref().ok().cloned()).collect();
+    assert_eq!(results, expected);
+    let reasons: Vec<_> = interpreted.iter().filter_map(|r| r.as_ref().err()).collect();
+    let reasons: Vec<_> = reasons.into_iter().map(|message| reason(message)).collect();
+    assert_eq!(
+        reasons,
+        [
+            r#"reverted: std::map: no value for key "alice""#,
+            "reverted: std::assert: require_ge failed: 5 >= 6",
+            r#"reverted: std::map: no value for key "alice""#,
+            r#"reverted: std::map: no value for key "\"zoë\"""#,
+        ]
+    );
+
+    let executor = ContractExecutor::default();
+    let instance = executor.load(&compile(REGISTRY, CompilationTarget::Wasm).into()).unwrap();
+    for (&(method, name, amount), interpreted) in SCRIPT.iter().zip(&interpreted) {
+        let call = format!("{method}({name:?}, {amount:?})");
+        match (executor.call(&instance, method, script_args(name, amount)), interpreted) {
+            (Ok(value), Ok(expected)) => assert_eq!(&value, expected, "{call}"),
+            (Err(err), Err(message)) => {
+                let err = err.to_string();
+                assert!(err.ends_with(reason(message)), "{call}: {message} vs {err}");
+            },
+            (actual, _) => panic!("{call}: {actual:?} vs {interpreted:?}"),
+        }
+    }
+    let balances = executor.call(&instance, "get_balances", Value::Null).unwrap();
+    assert_eq!(balances, json!({"": 3, "bob": u64::MAX}));
+
+    if std::env::var_os(SKIP_ENV).is_some() {
+        return;
+    }
+    let calls: Vec<_> = SCRIPT
+        .iter()
+        .map(|(method, name, amount)| match amount {
+            Some(amount) => format!("{method}(&Host, String::from({name:?}), {amount})"),
+            None => format!("{method}(&Host, String::from({name:?}))"),
+        })
+        .collect();
+    let lines = run_generated_rust(REGISTRY, "registry", "Registry", &calls);
+    assert_eq!(lines.len(), SCRIPT.len());
+    for ((line, call), interpreted) in lines.iter().zip(&calls).zip(&interpreted) {
+        match (line.strip_prefix("error: "), interpreted) {
+            (None, Ok(expected)) => assert_eq!(*line, expected.to_string(), "{call}"),
+            (Some(actual), Err(message)) => assert_eq!(actual, reason(message), "{call}"),
+            _ => panic!("{call}: {line} vs {interpreted:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_generated_rust_matches_reference() {
+    if std::env::var_os(SKIP_ENV).is_some() {
+        return;
+    }
+    let calls: Vec<_> =
+        inputs().map(|(method, a, b)| format!("{method}(&Host, {a}, {b})")).collect();
+    let actual = run_generated_rust(CONTRACT, "calc", "Calc", &calls);
+    assert_eq!(actual.len(), inputs().count());
+    for ((line, (method, a, b)), interpreted) in actual.iter().zip(inputs()).zip(interpret()) {
+        let call = format!("{method}({a}, {b})");
+        match (reference(method, a, b), line.strip_prefix("error: ")) {
+            (Some(expected), _) => assert_eq!(*line, expected.to_string(), "{call}"),
+            (None, Some(reason)) => {
+                let message = interpreted.unwrap_err();
+                assert!(message.ends_with(reason), "{call}: {message} vs {reason}");
+            },
+            (None, None) => panic!("{call} returned {line}, expected an error"),
+        }
+    }
+}
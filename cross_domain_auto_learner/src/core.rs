@@ -1 +1,259 @@
 //! Core module
+//!
+//! [`AutoLearner`] keeps a model up to date as labelled samples arrive, and
+//! explains the predictions of that model.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use shared_core::{Result, SystemError};
+
+use crate::drift::{DriftDetector, DriftStatus};
+use crate::inference::{self, Explanation};
+use crate::models::NaiveBayesModel;
+use crate::training::TrainingSample;
+use crate::AutoLearnerConfig;
+
+/// Learner that refits its model on every batch of new samples
+#[derive(Debug, Clone)]
+pub struct AutoLearner {
+    config: AutoLearnerConfig,
+    samples: Vec<TrainingSample>,
+    model: Option<NaiveBayesModel>,
+    drift_detector: Option<DriftDetector>,
+}
+
+impl AutoLearner {
+    /// Create a learner that has not seen any samples
+    pub fn new(config: AutoLearnerConfig) -> Self {
+        Self {
+            config,
+            samples: Vec::new(),
+            model: None,
+            drift_detector: None,
+        }
+    }
+
+    /// Monitor the configured drift feature of incoming samples with `detector`
+    pub fn set_drift_detector(&mut self, detector: DriftDetector) {
+        self.drift_detector = Some(detector);
+    }
+
+    /// Detector monitoring incoming samples, if any
+    pub fn drift_detector(&self) -> Option<&DriftDetector> {
+        self.drift_detector.as_ref()
+    }
+
+    /// Model fitted on every sample so far, if any
+    pub fn model(&self) -> Option<&NaiveBayesModel> {
+        self.model.as_ref()
+    }
+
+    /// Refit the model with `samples` added to those seen before
+    ///
+    /// Returns the drift status of the batch, or `None` without a drift
+    /// detector. The learner is unchanged if fitting fails.
+    pub fn train_incremental(
+        &mut self,
+        samples: &[TrainingSample],
+    ) -> Result<Option<DriftStatus>> {
+        let feature = self.config.drift_feature;
+        if self.drift_detector.is_some() {
+            if let Some(sample) = samples.iter().find(|s| s.features.len() <= feature) {
+                return Err(SystemError::validation(
+                    "features",
+                    format!("drift feature {feature} is out of range"),
+                    Some(sample.features.len().to_string()),
+                ));
+            }
+        }
+
+        let seen = self.samples.len();
+        self.samples.extend_from_slice(samples);
+        match NaiveBayesModel::fit(&self.samples) {
+            Ok(model) => self.model = Some(model),
+            Err(err) => {
+                self.samples.truncate(seen);
+                return Err(err);
+            },
+        }
+
+        let Some(detector) = &mut self.drift_detector else {
+            return Ok(None);
+        };
+        let values: Vec<f64> = samples.iter().map(|s| s.features[feature]).collect();
+        let status = detector.update(&values);
+        if let DriftStatus::Drift { ks_statistic } = status {
+            tracing::warn!(
+                "Concept drift detected on feature {} (KS statistic {:.3})",
+                feature,
+                ks_statistic
+            );
+        }
+        Ok(Some(status))
+    }
+
+    /// Explain the model's prediction for `sample` from the configured
+    /// number of perturbed samples,
+    /// [`ExplanationConfig::n_perturbations`](crate::ExplanationConfig::n_perturbations)
+    ///
+    /// Fails like [`explain`](Self::explain).
+    pub fn explain_default(&self, sample: &TrainingSample) -> Result<Explanation> {
+        self.explain(sample, self.config.explanation.n_perturbations)
+    }
+
+    /// Explain the model's prediction for `sample` from `n_perturbations`
+    /// samples drawn around it, as configured by
+    /// [`AutoLearnerConfig::explanation`]
+    ///
+    /// The label of `sample` is ignored. Fails with an `InvalidState` error
+    /// before the learner has been trained, and a `Validation` error if
+    /// `sample` does not fit the model or the perturbations are invalid.
+    pub fn explain(&self, sample: &TrainingSample, n_perturbations: usize) -> Result<Explanation> {
+        let model = self.model.as_ref().ok_or_else(|| SystemError::InvalidState {
+            message: "learner has not been trained".into(),
+            current_state: Some("untrained".into()),
+            expected_state: Some("trained".into()),
+        })?;
+        let config = &self.config.explanation;
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        inference::explain(
+            model,
+            &sample.features,
+            n_perturbations,
+            config.perturbation_std,
+            &mut rng,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExplanationConfig;
+
+    fn batch(offset: f64) -> Vec<TrainingSample> {
+        (0..10)
+            .map(|i| {
+                let x = offset + f64::from(i);
+                TrainingSample::new(vec![x, 1.0], if i % 2 == 0 { "even" } else { "odd" })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_train_incremental_detects_drift() {
+        let mut learner = AutoLearner::new(AutoLearnerConfig::default());
+        assert_eq!(learner.train_incremental(&batch(0.0)).unwrap(), None);
+
+        let reference = batch(0.0).iter().map(|s| s.features[0]).collect();
+        learner.set_drift_detector(DriftDetector::new(reference, 10, 0.5).unwrap());
+        let status = learner.train_incremental(&batch(0.0)).unwrap();
+        assert_eq!(status, Some(DriftStatus::Stable));
+        let status = learner.train_incremental(&batch(100.0)).unwrap();
+        assert_eq!(status, Some(DriftStatus::Drift { ks_statistic: 1.0 }));
+        assert_eq!(learner.model().unwrap().class_priors.len(), 2);
+    }
+
+    /// The first feature separates the classes around 2, the second is
+    /// distributed alike in both
+    fn separable() -> Vec<TrainingSample> {
+        [(0.0, 1.0), (0.5, 2.0), (1.0, 3.0)]
+            .into_iter()
+            .map(|(x, y)| TrainingSample::new(vec![x, y], "low"))
+            .chain(
+                [(3.0, 1.0), (3.5, 2.0), (4.0, 3.0)]
+                    .into_iter()
+                    .map(|(x, y)| TrainingSample::new(vec![x, y], "high")),
+            )
+            .collect()
+    }
+
+    fn explaining(perturbation_std: f64, seed: u64) -> AutoLearner {
+        let mut learner = AutoLearner::new(AutoLearnerConfig {
+            explanation: ExplanationConfig {
+                perturbation_std,
+                seed: Some(seed),
+                ..ExplanationConfig::default()
+            },
+            ..AutoLearnerConfig::default()
+        });
+        learner.train_incremental(&separable()).unwrap();
+        learner
+    }
+
+    #[test]
+    fn test_explain_ranks_the_separating_feature_first() {
+        let learner = explaining(0.05, 7);
+        let sample = TrainingSample::new(vec![2.05, 2.0], "unknown");
+        let explanation = learner.explain(&sample, 500).unwrap();
+
+        let model = learner.model().unwrap();
+        let predicted = model.predict(&sample.features).unwrap();
+        assert_eq!(explanation.prediction, model.posterior(&sample.features, &predicted));
+        assert!(explanation.prediction > 0.5 && explanation.prediction < 1.0);
+
+        let importances = &explanation.feature_importances;
+        assert_eq!(importances.len(), 2);
+        assert_eq!(importances[0].0, 0);
+        assert!(importances[0].1 > 1.0, "{importances:?}");
+        assert!(importances[1].1.abs() < importances[0].1 / 10.0, "{importances:?}");
+        assert!(explanation.local_fidelity > 0.9, "{explanation:?}");
+
+        // Seeded explanations are reproducible
+        assert_eq!(explaining(0.05, 7).explain(&sample, 500).unwrap(), explanation);
+        assert_eq!(explaining(0.05, 7).explain_default(&sample).unwrap(), explanation);
+    }
+
+    #[test]
+    fn test_explain_at_small_perturbation_scales() {
+        let sample = TrainingSample::new(vec![2.05, 2.0], "unknown");
+        let importance = |std| explaining(std, 7).explain(&sample, 500).unwrap();
+        let reference = importance(1e-3).feature_importances[0].1;
+        for std in [1e-5, 1e-6] {
+            let explanation = importance(std);
+            let (feature, coefficient) = explanation.feature_importances[0];
+            assert_eq!(feature, 0);
+            assert!((coefficient / reference - 1.0).abs() < 0.01, "{std}: {explanation:?}");
+        }
+    }
+
+    #[test]
+    fn test_explain_rejects_invalid_requests() {
+        let sample = TrainingSample::new(vec![2.0, 2.0], "unknown");
+        let untrained = AutoLearner::new(AutoLearnerConfig::default());
+        let err = untrained.explain(&sample, 10).unwrap_err();
+        assert!(matches!(err, SystemError::InvalidState { .. }), "{err}");
+
+        let learner = explaining(0.5, 1);
+        let invalid = [
+            learner.explain(&sample, 0),
+            learner.explain(&TrainingSample::new(vec![2.0], "unknown"), 10),
+            explaining(0.0, 1).explain(&sample, 10),
+            explaining(f64::NAN, 1).explain(&sample, 10),
+        ];
+        for result in invalid {
+            let err = result.unwrap_err();
+            assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_failed_training_keeps_learner() {
+        let mut learner = AutoLearner::new(AutoLearnerConfig::default());
+        learner.train_incremental(&batch(0.0)).unwrap();
+        let ragged = [TrainingSample::new(vec![1.0], "even")];
+        assert!(learner.train_incremental(&ragged).is_err());
+        assert_eq!(learner.samples.len(), 10);
+
+        learner.set_drift_detector(DriftDetector::new(vec![0.0], 1, 0.5).unwrap());
+        learner.config = AutoLearnerConfig {
+            drift_feature: 2,
+            ..AutoLearnerConfig::default()
+        };
+        assert!(learner.train_incremental(&batch(0.0)).is_err());
+        assert_eq!(learner.samples.len(), 10);
+    }
+}
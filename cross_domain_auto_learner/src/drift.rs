@@ -0,0 +1,174 @@
+//! Drift module
+//!
+//! Detects concept drift by comparing recent values of a feature against a
+//! reference sample with the two-sample Kolmogorov-Smirnov test.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+
+/// Fraction of the drift threshold at which [`DriftStatus::Warning`] starts
+const WARNING_RATIO: f64 = 0.75;
+
+/// Outcome of comparing the recent window against the reference
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DriftStatus {
+    /// Distributions agree, or the window is not full yet
+    Stable,
+    /// Distributions diverge but have not crossed the threshold
+    Warning {
+        /// Kolmogorov-Smirnov statistic of the window
+        ks_statistic: f64,
+    },
+    /// Distributions differ by more than the threshold
+    Drift {
+        /// Kolmogorov-Smirnov statistic of the window
+        ks_statistic: f64,
+    },
+}
+
+/// Monitors a stream of values for a shift away from a reference distribution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftDetector {
+    /// Sorted values the stream is expected to follow
+    pub reference_distribution: Vec<f64>,
+    /// Number of recent values compared against the reference
+    pub window_size: usize,
+    /// Kolmogorov-Smirnov statistic above which the stream has drifted
+    pub drift_threshold: f64,
+    window: VecDeque<f64>,
+}
+
+impl DriftDetector {
+    /// Create a detector for values expected to follow `reference_distribution`
+    ///
+    /// Non-finite reference values are ignored.
+    pub fn new(
+        mut reference_distribution: Vec<f64>,
+        window_size: usize,
+        drift_threshold: f64,
+    ) -> Result<Self> {
+        reference_distribution.retain(|value| value.is_finite());
+        if reference_distribution.is_empty() {
+            return Err(SystemError::validation(
+                "reference_distribution",
+                "at least one finite value is required",
+                None,
+            ));
+        }
+        if window_size == 0 {
+            return Err(SystemError::validation(
+                "window_size",
+                "must be positive",
+                Some(window_size.to_string()),
+            ));
+        }
+        if !(drift_threshold > 0.0 && drift_threshold <= 1.0) {
+            return Err(SystemError::validation(
+                "drift_threshold",
+                "must be in (0, 1]",
+                Some(drift_threshold.to_string()),
+            ));
+        }
+        reference_distribution.sort_by(f64::total_cmp);
+        Ok(Self {
+            reference_distribution,
+            window_size,
+            drift_threshold,
+            window: VecDeque::with_capacity(window_size),
+        })
+    }
+
+    /// Add `new_samples` to the window and test it against the reference
+    ///
+    /// Only the latest `window_size` values are kept; non-finite values are
+    /// skipped. The status is [`DriftStatus::Stable`] until the window is
+    /// full.
+    pub fn update(&mut self, new_samples: &[f64]) -> DriftStatus {
+        for &value in new_samples.iter().filter(|value| value.is_finite()) {
+            if self.window.len() == self.window_size {
+                self.window.pop_front();
+            }
+            self.window.push_back(value);
+        }
+        if self.window.len() < self.window_size {
+            return DriftStatus::Stable;
+        }
+
+        let mut window: Vec<f64> = self.window.iter().copied().collect();
+        window.sort_by(f64::total_cmp);
+        let ks_statistic = ks_statistic(&self.reference_distribution, &window);
+        if ks_statistic > self.drift_threshold {
+            DriftStatus::Drift { ks_statistic }
+        } else if ks_statistic > self.drift_threshold * WARNING_RATIO {
+            DriftStatus::Warning { ks_statistic }
+        } else {
+            DriftStatus::Stable
+        }
+    }
+
+    /// Values currently in the window, oldest first
+    pub fn window(&self) -> impl Iterator<Item = f64> + '_ {
+        self.window.iter().copied()
+    }
+}
+
+/// Largest distance between the empirical distribution functions of two
+/// sorted, non-empty samples
+pub fn ks_statistic(a: &[f64], b: &[f64]) -> f64 {
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut statistic: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        statistic = statistic.max((i as f64 / n - j as f64 / m).abs());
+    }
+    statistic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ks_statistic() {
+        let a = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(ks_statistic(&a, &a), 0.0);
+        assert_eq!(ks_statistic(&a, &[5.0, 6.0]), 1.0);
+        // The distribution functions differ most just after 2: 1/2 against 0
+        assert_eq!(ks_statistic(&a, &[2.5, 3.5, 4.5, 5.5]), 0.5);
+        // Ties advance both samples together
+        assert_eq!(ks_statistic(&[1.0, 1.0, 2.0], &[1.0, 2.0, 2.0]), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_update_reports_drift() {
+        let reference: Vec<f64> = (0..100).map(f64::from).collect();
+        let mut detector = DriftDetector::new(reference, 20, 0.6).unwrap();
+
+        let same: Vec<f64> = (0..20).map(|i| f64::from(i * 5)).collect();
+        assert_eq!(detector.update(&same[..10]), DriftStatus::Stable);
+        assert_eq!(detector.update(&same[10..]), DriftStatus::Stable);
+
+        // Half the window from far outside the reference
+        let shifted: Vec<f64> = (0..10).map(|i| f64::from(200 + i)).collect();
+        assert!(matches!(detector.update(&shifted), DriftStatus::Warning { .. }));
+        assert_eq!(detector.update(&shifted), DriftStatus::Drift { ks_statistic: 1.0 });
+        assert_eq!(detector.window().count(), 20);
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_parameters() {
+        assert!(DriftDetector::new(vec![f64::NAN], 10, 0.5).is_err());
+        assert!(DriftDetector::new(vec![1.0], 0, 0.5).is_err());
+        assert!(DriftDetector::new(vec![1.0], 10, 0.0).is_err());
+        assert!(DriftDetector::new(vec![1.0], 10, f64::NAN).is_err());
+    }
+}
@@ -1 +1,179 @@
 //! Inference module
+//!
+//! Explains individual predictions with a simplified LIME: the model is
+//! queried around a sample, and a linear model weighted towards the sample
+//! is fitted to its answers. Each feature's coefficient is how much it
+//! moves the prediction near that sample.
+
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+
+use crate::models::NaiveBayesModel;
+
+/// Regularization keeping the local least-squares system solvable when the
+/// perturbations do not span every feature, relative to each coefficient's
+/// diagonal entry so that it is negligible at any perturbation scale
+const RIDGE: f64 = 1e-9;
+
+/// Local explanation of a prediction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    /// Coefficient of each feature in the local linear model, by feature
+    /// index, largest in absolute value first
+    pub feature_importances: Vec<(usize, f64)>,
+    /// Probability the model gives the class it predicts for the sample
+    pub prediction: f64,
+    /// Weighted R² of the local model over the perturbed samples, 1 when
+    /// it reproduces the model exactly
+    pub local_fidelity: f64,
+}
+
+/// Explain the prediction of `model` for `features` from `n_perturbations`
+/// samples drawn around it, each feature moved by Gaussian noise of
+/// standard deviation `perturbation_std`
+///
+/// Perturbed samples are weighted by an exponential kernel of their
+/// distance to `features`. Fails with a `Validation` error if there are no
+/// perturbations, the deviation is not positive, or `features` does not
+/// have as many features as the model.
+pub fn explain<R: Rng + ?Sized>(
+    model: &NaiveBayesModel,
+    features: &[f64],
+    n_perturbations: usize,
+    perturbation_std: f64,
+    rng: &mut R,
+) -> Result<Explanation> {
+    if n_perturbations == 0 {
+        return Err(SystemError::validation(
+            "n_perturbations",
+            "at least one perturbation is required",
+            None,
+        ));
+    }
+    let noise = Normal::new(0.0, perturbation_std)
+        .ok()
+        .filter(|_| perturbation_std > 0.0)
+        .ok_or_else(|| {
+            SystemError::validation(
+                "perturbation_std",
+                "must be positive and finite",
+                Some(perturbation_std.to_string()),
+            )
+        })?;
+    let class = model.predict(features)?;
+    let prediction = model.posterior(features, &class);
+
+    let dimensions = features.len();
+    let width = 0.75 * (dimensions as f64).sqrt() * perturbation_std;
+    let mut offsets = Vec::with_capacity(n_perturbations);
+    let mut targets = Vec::with_capacity(n_perturbations);
+    let mut weights = Vec::with_capacity(n_perturbations);
+    for _ in 0..n_perturbations {
+        let offset: Vec<f64> = (0..dimensions).map(|_| noise.sample(rng)).collect();
+        let perturbed: Vec<f64> = features.iter().zip(&offset).map(|(x, d)| x + d).collect();
+        let distance_sq: f64 = offset.iter().map(|d| d * d).sum();
+        targets.push(model.posterior(&perturbed, &class));
+        weights.push((-distance_sq / (width * width)).exp());
+        offsets.push(offset);
+    }
+
+    // Weighted least squares over an intercept and the feature offsets
+    let row = |offset: &[f64]| std::iter::once(1.0).chain(offset.iter().copied()).collect();
+    let rows: Vec<Vec<f64>> = offsets.iter().map(|offset| row(offset)).collect();
+    let size = dimensions + 1;
+    let mut normal = vec![vec![0.0; size]; size];
+    let mut rhs = vec![0.0; size];
+    for ((x, &y), &w) in rows.iter().zip(&targets).zip(&weights) {
+        for ((&xi, target), normal_row) in x.iter().zip(&mut rhs).zip(&mut normal) {
+            *target += w * xi * y;
+            for (&xj, cell) in x.iter().zip(normal_row.iter_mut()) {
+                *cell += w * xi * xj;
+            }
+        }
+    }
+    for (i, coefficients) in normal.iter_mut().enumerate() {
+        coefficients[i] *= 1.0 + RIDGE;
+    }
+    let coefficients = solve(normal, rhs).ok_or_else(|| {
+        SystemError::internal("local model could not be fitted", Some(class.clone()))
+    })?;
+
+    let total_weight: f64 = weights.iter().sum();
+    let mean = targets.iter().zip(&weights).map(|(y, w)| w * y).sum::<f64>() / total_weight;
+    let (mut residual, mut spread) = (0.0, 0.0);
+    for ((x, &y), &w) in rows.iter().zip(&targets).zip(&weights) {
+        let fitted: f64 = x.iter().zip(&coefficients).map(|(x, c)| x * c).sum();
+        residual += w * (y - fitted).powi(2);
+        spread += w * (y - mean).powi(2);
+    }
+    // A model that does not change around the sample is fitted exactly
+    let local_fidelity = if spread > 0.0 { 1.0 - residual / spread } else { 1.0 };
+
+    let mut feature_importances: Vec<(usize, f64)> =
+        coefficients.into_iter().skip(1).enumerate().collect();
+    feature_importances.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+    Ok(Explanation {
+        feature_importances,
+        prediction,
+        local_fidelity,
+    })
+}
+
+/// Solve `a · x = b` by Gaussian elimination with partial pivoting, or
+/// `None` if `a` is singular
+///
+/// A pivot counts as zero when it is within rounding error of the largest
+/// row sum of `a`, so the test does not depend on the scale of `a`.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    let norm = a.iter().map(|row| row.iter().map(|x| x.abs()).sum::<f64>()).fold(0.0, f64::max);
+    let tolerance = n as f64 * f64::EPSILON * norm;
+    for column in 0..n {
+        let pivot =
+            (column..n).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() <= tolerance {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let (upper, lower) = a.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        let pivot_b = b[column];
+        for (row, target) in lower.iter_mut().zip(&mut b[column + 1..]) {
+            let factor = row[column] / pivot_row[column];
+            for (cell, &above) in row.iter_mut().zip(pivot_row).skip(column) {
+                *cell -= factor * above;
+            }
+            *target -= factor * pivot_b;
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - known) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        let a = vec![vec![0.0, 2.0], vec![1.0, 1.0]];
+        let x = solve(a, vec![4.0, 3.0]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-12 && (x[1] - 2.0).abs() < 1e-12);
+        assert!(solve(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0]).is_none());
+
+        // Singularity is judged relative to the scale of the matrix
+        let tiny = vec![vec![0.0, 2e-20], vec![1e-20, 1e-20]];
+        let x = solve(tiny, vec![4e-20, 3e-20]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-12 && (x[1] - 2.0).abs() < 1e-12);
+        let nearly = vec![vec![1e20, 2e20], vec![2e20, 4e20 + 1.0]];
+        assert!(solve(nearly, vec![1.0, 2.0]).is_none());
+        assert!(solve(vec![vec![0.0; 2]; 2], vec![0.0; 2]).is_none());
+    }
+}
@@ -5,26 +5,58 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
-
 pub mod api;
 pub mod config;
 pub mod core;
+pub mod drift;
 pub mod inference;
 pub mod models;
 pub mod training;
 
+pub use core::AutoLearner;
+pub use drift::{DriftDetector, DriftStatus};
+pub use inference::Explanation;
+
 /// Auto learner configuration
 #[derive(Debug, Clone)]
 pub struct AutoLearnerConfig {
     /// Model type
     pub model_type: String,
+    /// Index of the feature an [`AutoLearner`]'s drift detector monitors
+    pub drift_feature: usize,
+    /// How [`AutoLearner::explain`] perturbs samples
+    pub explanation: ExplanationConfig,
 }
 
 impl Default for AutoLearnerConfig {
     fn default() -> Self {
         Self {
             model_type: "default".to_string(),
+            drift_feature: 0,
+            explanation: ExplanationConfig::default(),
+        }
+    }
+}
+
+/// How predictions are explained
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplanationConfig {
+    /// Perturbed samples [`AutoLearner::explain_default`] explains a
+    /// prediction with
+    pub n_perturbations: usize,
+    /// Standard deviation of the Gaussian noise added to each feature
+    pub perturbation_std: f64,
+    /// Seed of the perturbations, making explanations reproducible; `None`
+    /// draws them from entropy
+    pub seed: Option<u64>,
+}
+
+impl Default for ExplanationConfig {
+    fn default() -> Self {
+        Self {
+            n_perturbations: 500,
+            perturbation_std: 1.0,
+            seed: None,
         }
     }
 }
@@ -1 +1,239 @@
 //! Models module
+//!
+//! Classification models that can be fitted from labelled samples.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+
+use crate::training::TrainingSample;
+
+/// Variance floor so constant features don't produce a zero-width Gaussian
+const MIN_VARIANCE: f64 = 1e-9;
+
+/// Parameters of a univariate Gaussian distribution
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GaussianParams {
+    /// Mean
+    pub mean: f64,
+    /// Variance
+    pub variance: f64,
+}
+
+impl GaussianParams {
+    /// Log of the probability density at `x`
+    pub fn log_pdf(&self, x: f64) -> f64 {
+        let diff = x - self.mean;
+        -0.5 * ((2.0 * std::f64::consts::PI * self.variance).ln() + diff * diff / self.variance)
+    }
+}
+
+/// Gaussian naive Bayes classifier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NaiveBayesModel {
+    /// Prior probability of each class
+    pub class_priors: HashMap<String, f64>,
+    /// Per-class Gaussian parameters, one entry per feature
+    pub feature_likelihoods: HashMap<String, Vec<GaussianParams>>,
+}
+
+impl NaiveBayesModel {
+    /// Fit the model using maximum likelihood estimation
+    pub fn fit(samples: &[TrainingSample]) -> Result<Self> {
+        let first = samples.first().ok_or_else(|| {
+            SystemError::validation("samples", "at least one training sample is required", None)
+        })?;
+        let feature_count = first.features.len();
+        if feature_count == 0 {
+            return Err(SystemError::validation(
+                "features",
+                "samples must have at least one feature",
+                None,
+            ));
+        }
+
+        let mut by_class: HashMap<&str, Vec<&[f64]>> = HashMap::new();
+        for sample in samples {
+            if sample.features.len() != feature_count {
+                return Err(SystemError::validation(
+                    "features",
+                    format!("expected {feature_count} features"),
+                    Some(sample.features.len().to_string()),
+                ));
+            }
+            by_class
+                .entry(sample.label.as_str())
+                .or_default()
+                .push(&sample.features);
+        }
+
+        let total = samples.len() as f64;
+        let mut class_priors = HashMap::new();
+        let mut feature_likelihoods = HashMap::new();
+
+        for (label, rows) in by_class {
+            let n = rows.len() as f64;
+            let params = (0..feature_count)
+                .map(|i| {
+                    let mean = rows.iter().map(|row| row[i]).sum::<f64>() / n;
+                    let variance =
+                        rows.iter().map(|row| (row[i] - mean).powi(2)).sum::<f64>() / n;
+                    GaussianParams {
+                        mean,
+                        variance: variance.max(MIN_VARIANCE),
+                    }
+                })
+                .collect();
+
+            class_priors.insert(label.to_string(), n / total);
+            feature_likelihoods.insert(label.to_string(), params);
+        }
+
+        Ok(Self {
+            class_priors,
+            feature_likelihoods,
+        })
+    }
+
+    /// Predict the maximum a posteriori class label
+    pub fn predict(&self, features: &[f64]) -> Result<String> {
+        let expected = self
+            .feature_likelihoods
+            .values()
+            .next()
+            .map(Vec::len)
+            .ok_or_else(|| SystemError::InvalidState {
+                message: "model has no classes".into(),
+                current_state: None,
+                expected_state: Some("fitted".into()),
+            })?;
+
+        if features.len() != expected {
+            return Err(SystemError::validation(
+                "features",
+                format!("expected {expected} features"),
+                Some(features.len().to_string()),
+            ));
+        }
+
+        self.class_priors
+            .keys()
+            .map(|class| (class, self.log_likelihood(features, class)))
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(class, _)| class.clone())
+            .ok_or_else(|| SystemError::internal("no class scored", None))
+    }
+
+    /// Posterior probability of `class` given `features`
+    ///
+    /// Returns zero for unknown classes or mismatched feature counts.
+    pub fn posterior(&self, features: &[f64], class: &str) -> f64 {
+        let scores: Vec<f64> = self
+            .class_priors
+            .keys()
+            .map(|other| self.log_likelihood(features, other))
+            .collect();
+        let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let score = self.log_likelihood(features, class);
+        if !max.is_finite() || !score.is_finite() {
+            return 0.0;
+        }
+        // Shifted by the largest score so that the exponentials don't underflow
+        let total: f64 = scores.iter().map(|s| (s - max).exp()).sum();
+        (score - max).exp() / total
+    }
+
+    /// Joint log-likelihood of `features` and `class` (log prior plus feature
+    /// log-densities)
+    ///
+    /// Returns negative infinity for unknown classes or mismatched feature counts.
+    pub fn log_likelihood(&self, features: &[f64], class: &str) -> f64 {
+        let (Some(prior), Some(params)) = (
+            self.class_priors.get(class),
+            self.feature_likelihoods.get(class),
+        ) else {
+            return f64::NEG_INFINITY;
+        };
+
+        if features.len() != params.len() {
+            return f64::NEG_INFINITY;
+        }
+
+        prior.ln()
+            + features
+                .iter()
+                .zip(params)
+                .map(|(&x, p)| p.log_pdf(x))
+                .sum::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<TrainingSample> {
+        vec![
+            TrainingSample::new(vec![1.0, 2.0], "low"),
+            TrainingSample::new(vec![1.2, 1.8], "low"),
+            TrainingSample::new(vec![0.8, 2.2], "low"),
+            TrainingSample::new(vec![8.0, 9.0], "high"),
+            TrainingSample::new(vec![8.5, 9.5], "high"),
+        ]
+    }
+
+    #[test]
+    fn test_fit_computes_mle_parameters() {
+        let model = NaiveBayesModel::fit(&samples()).unwrap();
+
+        assert!((model.class_priors["low"] - 0.6).abs() < 1e-12);
+        assert!((model.class_priors["high"] - 0.4).abs() < 1e-12);
+
+        let high = &model.feature_likelihoods["high"];
+        assert!((high[0].mean - 8.25).abs() < 1e-12);
+        assert!((high[0].variance - 0.0625).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_predict() {
+        let model = NaiveBayesModel::fit(&samples()).unwrap();
+
+        assert_eq!(model.predict(&[1.1, 2.1]).unwrap(), "low");
+        assert_eq!(model.predict(&[7.9, 9.2]).unwrap(), "high");
+        assert!(model.predict(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn test_log_likelihood() {
+        let model = NaiveBayesModel::fit(&samples()).unwrap();
+
+        let low = model.log_likelihood(&[1.0, 2.0], "low");
+        let high = model.log_likelihood(&[1.0, 2.0], "high");
+        assert!(low > high);
+        assert_eq!(model.log_likelihood(&[1.0, 2.0], "unknown"), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_posterior() {
+        let model = NaiveBayesModel::fit(&samples()).unwrap();
+
+        let low = model.posterior(&[1.0, 2.0], "low");
+        let high = model.posterior(&[1.0, 2.0], "high");
+        assert!(low > 0.99);
+        assert!((low + high - 1.0).abs() < 1e-12);
+        assert_eq!(model.posterior(&[1.0, 2.0], "unknown"), 0.0);
+        assert_eq!(model.posterior(&[1.0], "low"), 0.0);
+    }
+
+    #[test]
+    fn test_fit_rejects_invalid_samples() {
+        assert!(NaiveBayesModel::fit(&[]).is_err());
+
+        let ragged = vec![
+            TrainingSample::new(vec![1.0, 2.0], "a"),
+            TrainingSample::new(vec![1.0], "b"),
+        ];
+        assert!(NaiveBayesModel::fit(&ragged).is_err());
+    }
+}
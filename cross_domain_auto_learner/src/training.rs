@@ -1 +1,24 @@
 //! Training module
+//!
+//! Labelled samples used to fit models.
+
+use serde::{Deserialize, Serialize};
+
+/// A labelled feature vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainingSample {
+    /// Feature values
+    pub features: Vec<f64>,
+    /// Class label
+    pub label: String,
+}
+
+impl TrainingSample {
+    /// Create a new training sample
+    pub fn new(features: Vec<f64>, label: impl Into<String>) -> Self {
+        Self {
+            features,
+            label: label.into(),
+        }
+    }
+}
@@ -1 +1,419 @@
 //! Blocking module
+//!
+//! Normalization of the security events domains report, in whatever format
+//! they use, into the canonical [`SecurityEvent`] blocking decisions are
+//! made on.
+//!
+//! An [`EventNormalizer`] holds operator-defined [`NormalizationMapping`]s.
+//! Each mapping recognizes a format by a JSON Schema and says where in it,
+//! as a [`JsonPath`], every event field is found. Schemas support the
+//! `type`, `required`, `properties`, `items`, `const` and `enum` keywords;
+//! other keywords are ignored.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shared_core::{Result, SystemError, Timestamp};
+
+/// Event field holding the reporting domain
+pub const DOMAIN_FIELD: &str = "domain";
+/// Event field holding the kind of event, required
+pub const EVENT_TYPE_FIELD: &str = "event_type";
+/// Event field holding what to block, e.g. an address, required
+pub const SUBJECT_FIELD: &str = "subject";
+/// Event field holding the severity, a number from 0 to 255
+pub const SEVERITY_FIELD: &str = "severity";
+/// Event field holding when the event happened, in milliseconds since the
+/// Unix epoch
+pub const TIMESTAMP_FIELD: &str = "timestamp";
+
+/// A security event in canonical form
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecurityEvent {
+    /// Domain that reported the event
+    pub domain: Option<String>,
+    /// Kind of event, e.g. `"brute_force"`
+    pub event_type: String,
+    /// What the event is about and may get blocked, e.g. an address
+    pub subject: String,
+    /// Severity, higher is worse; 0 if the event does not say
+    pub severity: u8,
+    /// When the event happened, if the event says
+    pub timestamp: Option<Timestamp>,
+    /// Mapped fields other than the canonical ones, by target name
+    pub attributes: BTreeMap<String, Value>,
+}
+
+/// Location of a value in a JSON document, such as `$.alert.src[0]`
+///
+/// Paths start at the root `$`, followed by `.name` or `['name']` for object
+/// members and `[index]` for array elements.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct JsonPath {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Member(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    /// Value at the path in `value`, if there is one
+    pub fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments.iter().try_fold(value, |value, segment| match segment {
+            Segment::Member(name) => value.get(name),
+            Segment::Index(index) => value.get(index),
+        })
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = SystemError;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| SystemError::validation("json_path", reason, Some(path.to_string()));
+        let mut rest = path.strip_prefix('$').ok_or_else(|| invalid("must start with `$`"))?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid("empty member name"));
+                }
+                segments.push(Segment::Member(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix("['") {
+                let end = after.find("']").ok_or_else(|| invalid("unclosed `['`"))?;
+                segments.push(Segment::Member(after[..end].to_string()));
+                rest = &after[end + 2..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("unclosed `[`"))?;
+                let index = after[..end].parse().map_err(|_| invalid("bad array index"))?;
+                segments.push(Segment::Index(index));
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("expected `.` or `[`"));
+            }
+        }
+        Ok(Self {
+            source: path.to_string(),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for JsonPath {
+    type Error = SystemError;
+
+    fn try_from(path: String) -> Result<Self> {
+        path.parse()
+    }
+}
+
+impl From<JsonPath> for String {
+    fn from(path: JsonPath) -> Self {
+        path.source
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// How to normalize the events of one format
+///
+/// In TOML, the schema is a table and every target field a path string:
+///
+/// ```toml
+/// [source_schema]
+/// type = "object"
+/// required = ["src_ip", "rule"]
+///
+/// [target_fields]
+/// subject = "$.src_ip"
+/// event_type = "$.rule"
+/// severity = "$.level"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NormalizationMapping {
+    /// JSON Schema the raw events of the format match
+    pub source_schema: Value,
+    /// Where each [`SecurityEvent`] field is in a raw event, by field name;
+    /// names other than the canonical fields become attributes
+    pub target_fields: HashMap<String, JsonPath>,
+}
+
+impl NormalizationMapping {
+    /// Parse an operator-defined mapping from TOML
+    pub fn from_toml(s: &str) -> Result<Self> {
+        let mapping: Self = toml::from_str(s)?;
+        if !mapping.source_schema.is_object() && !mapping.source_schema.is_boolean() {
+            return Err(SystemError::validation(
+                "source_schema",
+                "must be a table",
+                Some(mapping.source_schema.to_string()),
+            ));
+        }
+        Ok(mapping)
+    }
+
+    /// Whether `raw_event` is in this mapping's format
+    pub fn matches(&self, raw_event: &Value) -> bool {
+        schema_matches(&self.source_schema, raw_event)
+    }
+
+    /// Canonical event for `raw_event`, which must be in this mapping's
+    /// format
+    fn apply(&self, raw_event: &Value) -> Result<SecurityEvent> {
+        let mut fields: BTreeMap<String, Value> = self
+            .target_fields
+            .iter()
+            .filter_map(|(name, path)| Some((name.clone(), path.select(raw_event)?.clone())))
+            .collect();
+        let event_type = fields.remove(EVENT_TYPE_FIELD);
+        let subject = fields.remove(SUBJECT_FIELD);
+        let severity = fields.remove(SEVERITY_FIELD);
+        let timestamp = fields.remove(TIMESTAMP_FIELD);
+        let domain = fields.remove(DOMAIN_FIELD);
+        let severity = severity
+            .map(|severity| {
+                severity.as_u64().and_then(|severity| u8::try_from(severity).ok()).ok_or_else(
+                    || invalid_field(SEVERITY_FIELD, "must be a number from 0 to 255", &severity),
+                )
+            })
+            .transpose()?;
+        let timestamp = timestamp
+            .map(|timestamp| {
+                timestamp.as_u64().map(Timestamp::from_millis).ok_or_else(|| {
+                    invalid_field(TIMESTAMP_FIELD, "must be epoch milliseconds", &timestamp)
+                })
+            })
+            .transpose()?;
+        Ok(SecurityEvent {
+            domain: domain.map(|domain| text(DOMAIN_FIELD, domain)).transpose()?,
+            event_type: text(EVENT_TYPE_FIELD, required(EVENT_TYPE_FIELD, event_type)?)?,
+            subject: text(SUBJECT_FIELD, required(SUBJECT_FIELD, subject)?)?,
+            severity: severity.unwrap_or(0),
+            timestamp,
+            attributes: fields,
+        })
+    }
+}
+
+/// Normalizes raw events with the first mapping whose schema they match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventNormalizer {
+    /// Mappings, tried in order
+    pub mappings: Vec<NormalizationMapping>,
+}
+
+impl EventNormalizer {
+    /// Create a normalizer trying `mappings` in order
+    pub fn new(mappings: Vec<NormalizationMapping>) -> Self {
+        Self { mappings }
+    }
+
+    /// Canonical form of `raw_event`
+    ///
+    /// Fails with a `Validation` error if no mapping's schema matches, or if
+    /// the matching mapping does not find the event type and subject or
+    /// finds a field of the wrong type.
+    pub fn normalize(&self, raw_event: Value) -> Result<SecurityEvent> {
+        let mapping = self
+            .mappings
+            .iter()
+            .find(|mapping| mapping.matches(&raw_event))
+            .ok_or_else(|| {
+                SystemError::validation(
+                    "raw_event",
+                    "matches no normalization mapping",
+                    Some(raw_event.to_string()),
+                )
+            })?;
+        mapping.apply(&raw_event)
+    }
+}
+
+/// Whether `value` matches `schema`, as far as the supported keywords go
+fn schema_matches(schema: &Value, value: &Value) -> bool {
+    let schema = match schema {
+        Value::Bool(matches) => return *matches,
+        Value::Object(schema) => schema,
+        _ => return false,
+    };
+    if let Some(types) = schema.get("type") {
+        let matches_type = |ty: &Value| ty.as_str().is_some_and(|ty| has_type(value, ty));
+        let matches = match types {
+            Value::Array(types) => types.iter().any(matches_type),
+            ty => matches_type(ty),
+        };
+        if !matches {
+            return false;
+        }
+    }
+    if schema.get("const").is_some_and(|expected| expected != value) {
+        return false;
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            return false;
+        }
+    }
+    if let Value::Object(object) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            let present = |name: &Value| name.as_str().is_some_and(|n| object.contains_key(n));
+            if !required.iter().all(present) {
+                return false;
+            }
+        }
+        if let Some(Value::Object(properties)) = schema.get("properties") {
+            let property_matches = |(name, schema): (&String, &Value)| {
+                object.get(name).map_or(true, |value| schema_matches(schema, value))
+            };
+            if !properties.iter().all(property_matches) {
+                return false;
+            }
+        }
+    }
+    if let (Value::Array(items), Some(schema)) = (value, schema.get("items")) {
+        if !items.iter().all(|item| schema_matches(schema, item)) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether `value` is of the JSON Schema type `ty`
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => false,
+    }
+}
+
+fn required(field: &str, value: Option<Value>) -> Result<Value> {
+    value.ok_or_else(|| SystemError::validation(field, "not found in the raw event", None))
+}
+
+/// A text field, from a string, number or boolean
+fn text(field: &str, value: Value) -> Result<String> {
+    match value {
+        Value::String(text) => Ok(text),
+        Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+        _ => Err(invalid_field(field, "must be a string, number or boolean", &value)),
+    }
+}
+
+fn invalid_field(field: &str, reason: &str, value: &Value) -> SystemError {
+    SystemError::validation(field, reason, Some(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const FIREWALL: &str = r#"
+        [source_schema]
+        type = "object"
+        required = ["src", "rule"]
+        properties.src = { type = "object", required = ["ip"] }
+
+        [target_fields]
+        subject = "$.src.ip"
+        event_type = "$.rule"
+        severity = "$.level"
+        timestamp = "$.ts"
+        port = "$.src['dst port']"
+    "#;
+
+    const AUTH: &str = r#"
+        [source_schema]
+        type = "object"
+        properties.kind = { const = "login_failure" }
+        required = ["kind"]
+
+        [target_fields]
+        domain = "$.tenant"
+        event_type = "$.kind"
+        subject = "$.user.ids[0]"
+    "#;
+
+    #[test]
+    fn test_json_path() {
+        let path: JsonPath = "$.alert['src ip'][1].x".parse().unwrap();
+        let event = json!({"alert": {"src ip": [{}, {"x": 3}]}});
+        assert_eq!(path.select(&event), Some(&json!(3)));
+        assert_eq!(path.to_string(), "$.alert['src ip'][1].x");
+        assert_eq!("$".parse::<JsonPath>().unwrap().select(&event), Some(&event));
+        assert_eq!("$.missing".parse::<JsonPath>().unwrap().select(&event), None);
+        for bad in ["alert", "$.", "$[x]", "$['a'", "$a"] {
+            assert!(bad.parse::<JsonPath>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_picks_the_first_matching_mapping() {
+        let normalizer = EventNormalizer::new(vec![
+            NormalizationMapping::from_toml(FIREWALL).unwrap(),
+            NormalizationMapping::from_toml(AUTH).unwrap(),
+        ]);
+
+        let event = normalizer
+            .normalize(json!({
+                "src": {"ip": "10.0.0.1", "dst port": 22},
+                "rule": "ssh_scan",
+                "level": 7,
+                "ts": 1_700_000_000_000u64,
+            }))
+            .unwrap();
+        assert_eq!(event.subject, "10.0.0.1");
+        assert_eq!(event.event_type, "ssh_scan");
+        assert_eq!(event.severity, 7);
+        assert_eq!(event.timestamp, Some(Timestamp::from_millis(1_700_000_000_000)));
+        assert_eq!(event.domain, None);
+        assert_eq!(event.attributes, BTreeMap::from([("port".to_string(), json!(22))]));
+
+        let event = normalizer
+            .normalize(json!({"kind": "login_failure", "tenant": "eu", "user": {"ids": [42]}}))
+            .unwrap();
+        assert_eq!(event.domain.as_deref(), Some("eu"));
+        assert_eq!(event.subject, "42");
+        assert_eq!(event.severity, 0);
+
+        // No schema matches a login success, nor a firewall event without an IP
+        for raw in [json!({"kind": "login_success"}), json!({"src": {}, "rule": "x"})] {
+            let err = normalizer.normalize(raw).unwrap_err();
+            assert!(matches!(err, SystemError::Validation { field, .. } if field == "raw_event"));
+        }
+        let err = normalizer.normalize(json!({"kind": "login_failure"})).unwrap_err();
+        assert!(matches!(err, SystemError::Validation { field, .. } if field == "subject"));
+        let raw = json!({"src": {"ip": "10.0.0.1"}, "rule": "x", "level": 300});
+        assert!(normalizer.normalize(raw).is_err());
+    }
+
+    #[test]
+    fn test_from_toml_rejects_bad_mappings() {
+        assert!(NormalizationMapping::from_toml("source_schema = 1\n[target_fields]").is_err());
+        let bad_path = "[source_schema]\n[target_fields]\nsubject = \"src\"";
+        assert!(NormalizationMapping::from_toml(bad_path).is_err());
+        let mapping = NormalizationMapping::from_toml("source_schema = true\n[target_fields]");
+        assert!(mapping.unwrap().matches(&json!(null)));
+    }
+}
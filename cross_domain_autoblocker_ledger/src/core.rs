@@ -1 +1,130 @@
 //! Core module
+//!
+//! [`LedgerNode`] owns a [`Ledger`] and the background tasks that maintain
+//! it while the node runs.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use shared_core::{Result, SystemError};
+use tokio::task::JoinHandle;
+
+use crate::ledger::Ledger;
+use crate::LedgerConfig;
+
+/// A running ledger replica
+#[derive(Debug)]
+pub struct LedgerNode {
+    config: LedgerConfig,
+    ledger: Arc<Ledger>,
+    /// Background tasks, `Some` while the node runs
+    tasks: Mutex<Option<Vec<JoinHandle<()>>>>,
+}
+
+impl LedgerNode {
+    /// Create a stopped node with an empty ledger
+    pub fn new(config: LedgerConfig) -> Result<Self> {
+        let ledger = Arc::new(Ledger::new(config.ttl_policy)?);
+        Ok(Self {
+            config,
+            ledger,
+            tasks: Mutex::new(None),
+        })
+    }
+
+    /// Node configuration
+    pub fn config(&self) -> &LedgerConfig {
+        &self.config
+    }
+
+    /// Ledger served by the node
+    pub fn ledger(&self) -> &Arc<Ledger> {
+        &self.ledger
+    }
+
+    /// Start the node's background tasks
+    ///
+    /// With a TTL policy, compaction runs right away and then every
+    /// `compaction_interval_secs`.
+    pub async fn start(&self) -> Result<()> {
+        let mut tasks = self.tasks.lock();
+        if tasks.is_some() {
+            return Err(SystemError::InvalidState {
+                message: "ledger node is already running".to_string(),
+                current_state: Some("running".to_string()),
+                expected_state: Some("stopped".to_string()),
+            });
+        }
+        let mut handles = Vec::new();
+        if let Some(policy) = self.config.ttl_policy {
+            let ledger = Arc::clone(&self.ledger);
+            handles.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(policy.compaction_interval());
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    match ledger.compact() {
+                        Ok(result) if result.entries_removed > 0 => tracing::info!(
+                            entries_removed = result.entries_removed,
+                            bytes_freed = result.bytes_freed,
+                            "Compacted ledger"
+                        ),
+                        Ok(_) => {},
+                        Err(e) => tracing::warn!(error = %e, "Ledger compaction failed"),
+                    }
+                }
+            }));
+        }
+        *tasks = Some(handles);
+        Ok(())
+    }
+
+    /// Stop the node's background tasks
+    pub fn stop(&self) {
+        for handle in self.tasks.lock().take().into_iter().flatten() {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for LedgerNode {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::ledger::TtlPolicy;
+
+    #[tokio::test]
+    async fn test_start_runs_compaction() {
+        let config = LedgerConfig {
+            ttl_policy: Some(TtlPolicy {
+                entry_ttl_secs: 0,
+                compaction_interval_secs: 3600,
+            }),
+            ..Default::default()
+        };
+        let node = LedgerNode::new(config).unwrap();
+        node.ledger().append(json!({ "block": "10.0.0.1" })).unwrap();
+
+        node.start().await.unwrap();
+        assert!(node.start().await.is_err());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !node.ledger().is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        node.stop();
+        node.start().await.unwrap();
+    }
+}
@@ -1 +1,400 @@
 //! Ledger module
+//!
+//! Append-only log of block events committed under a Merkle root. Every
+//! entry gets a [`MembershipProof`] against the current root; with a
+//! [`TtlPolicy`] expired entries are dropped by [`Ledger::compact`], which
+//! recomputes the root over the entries that remain.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use shared_core::{crypto::hash_blake3, Result, SystemError, Timestamp};
+
+/// Root of a ledger without entries
+pub const EMPTY_ROOT: [u8; 32] = [0; 32];
+
+/// Automatic expiry of ledger entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TtlPolicy {
+    /// Age in seconds at which an entry expires
+    pub entry_ttl_secs: u64,
+    /// Seconds between background compactions
+    pub compaction_interval_secs: u64,
+}
+
+impl TtlPolicy {
+    /// Reject policies the background compaction cannot run with
+    pub fn validate(&self) -> Result<()> {
+        if self.compaction_interval_secs == 0 {
+            return Err(SystemError::config(
+                "compaction_interval_secs must be > 0",
+                Some("ttl_policy".to_string()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Entry lifetime
+    pub fn entry_ttl(&self) -> Duration {
+        Duration::from_secs(self.entry_ttl_secs)
+    }
+
+    /// Period of the background compaction
+    pub fn compaction_interval(&self) -> Duration {
+        Duration::from_secs(self.compaction_interval_secs)
+    }
+}
+
+/// One committed ledger entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Position in the ledger, never reused
+    pub sequence: u64,
+    /// When the entry was appended
+    pub timestamp: Timestamp,
+    /// Event payload
+    pub payload: serde_json::Value,
+    /// Merkle leaf hash of the entry
+    pub hash: [u8; 32],
+}
+
+impl LedgerEntry {
+    fn new(sequence: u64, timestamp: Timestamp, payload: serde_json::Value) -> Result<Self> {
+        let bytes = serde_json::to_vec(&(sequence, timestamp, &payload))?;
+        Ok(Self {
+            sequence,
+            timestamp,
+            payload,
+            hash: leaf_hash(&bytes),
+        })
+    }
+
+    /// Whether the entry has outlived `ttl` as of `now`
+    pub fn is_expired(&self, ttl: Duration, now: Timestamp) -> bool {
+        now.elapsed_since(self.timestamp).is_some_and(|age| age >= ttl)
+    }
+}
+
+/// Outcome of [`Ledger::compact`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CompactionResult {
+    /// Expired entries dropped
+    pub entries_removed: usize,
+    /// Encoded size of the dropped payloads
+    pub bytes_freed: u64,
+    /// Merkle root over the remaining entries
+    pub new_root: [u8; 32],
+}
+
+/// Side of a sibling hash in a Merkle path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    /// Sibling is hashed before the running node
+    Left,
+    /// Sibling is hashed after the running node
+    Right,
+}
+
+/// Merkle path from an entry's leaf to the ledger root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembershipProof {
+    /// Sequence of the proven entry
+    pub sequence: u64,
+    /// Leaf hash of the proven entry
+    pub leaf: [u8; 32],
+    /// Sibling hashes, from the leaf upwards
+    pub path: Vec<(Side, [u8; 32])>,
+}
+
+impl MembershipProof {
+    /// Whether the proof leads to `root`
+    pub fn verify(&self, root: &[u8; 32]) -> bool {
+        let computed = self.path.iter().fold(self.leaf, |node, (side, sibling)| match side {
+            Side::Left => node_hash(sibling, &node),
+            Side::Right => node_hash(&node, sibling),
+        });
+        &computed == root
+    }
+}
+
+struct StoredEntry {
+    entry: LedgerEntry,
+    encoded_len: u64,
+}
+
+#[derive(Default)]
+struct LedgerState {
+    entries: VecDeque<StoredEntry>,
+    next_sequence: u64,
+    root: Option<[u8; 32]>,
+}
+
+impl LedgerState {
+    fn leaves(&self) -> Vec<[u8; 32]> {
+        self.entries.iter().map(|stored| stored.entry.hash).collect()
+    }
+
+    fn root(&mut self) -> [u8; 32] {
+        if let Some(root) = self.root {
+            return root;
+        }
+        let root = merkle_root(self.leaves());
+        self.root = Some(root);
+        root
+    }
+}
+
+/// Append-only ledger of block events
+#[derive(Default)]
+pub struct Ledger {
+    ttl_policy: Option<TtlPolicy>,
+    state: RwLock<LedgerState>,
+}
+
+impl std::fmt::Debug for Ledger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ledger")
+            .field("ttl_policy", &self.ttl_policy)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl Ledger {
+    /// Create an empty ledger whose entries expire under `ttl_policy`
+    pub fn new(ttl_policy: Option<TtlPolicy>) -> Result<Self> {
+        if let Some(policy) = &ttl_policy {
+            policy.validate()?;
+        }
+        Ok(Self {
+            ttl_policy,
+            state: RwLock::default(),
+        })
+    }
+
+    /// Expiry policy of the ledger
+    pub fn ttl_policy(&self) -> Option<TtlPolicy> {
+        self.ttl_policy
+    }
+
+    /// Append an event and return the committed entry
+    pub fn append(&self, payload: serde_json::Value) -> Result<LedgerEntry> {
+        self.append_at(payload, Timestamp::now())
+    }
+
+    /// Append an event recorded at `timestamp`
+    pub fn append_at(
+        &self,
+        payload: serde_json::Value,
+        timestamp: Timestamp,
+    ) -> Result<LedgerEntry> {
+        let encoded_len = serde_json::to_vec(&payload)?.len() as u64;
+        let mut state = self.state.write();
+        let entry = LedgerEntry::new(state.next_sequence, timestamp, payload)?;
+        state.next_sequence += 1;
+        state.entries.push_back(StoredEntry {
+            entry: entry.clone(),
+            encoded_len,
+        });
+        state.root = None;
+        Ok(entry)
+    }
+
+    /// Entry with the given sequence, if it has not been compacted away
+    pub fn get(&self, sequence: u64) -> Option<LedgerEntry> {
+        let state = self.state.read();
+        position(&state, sequence).map(|index| state.entries[index].entry.clone())
+    }
+
+    /// Number of entries held
+    pub fn len(&self) -> usize {
+        self.state.read().entries.len()
+    }
+
+    /// Whether the ledger holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Current Merkle root
+    pub fn root(&self) -> [u8; 32] {
+        self.state.write().root()
+    }
+
+    /// Membership proof of an entry against the current root
+    pub fn prove(&self, sequence: u64) -> Result<MembershipProof> {
+        let state = self.state.read();
+        let index = position(&state, sequence)
+            .ok_or_else(|| SystemError::not_found("ledger entry", sequence.to_string()))?;
+        Ok(MembershipProof {
+            sequence,
+            leaf: state.entries[index].entry.hash,
+            path: merkle_path(state.leaves(), index),
+        })
+    }
+
+    /// Drop entries that have outlived the TTL policy
+    ///
+    /// Without a policy nothing is removed. Remaining entries keep their
+    /// sequence and leaf hash, so they stay provable against `new_root`.
+    pub fn compact(&self) -> Result<CompactionResult> {
+        self.compact_at(Timestamp::now())
+    }
+
+    /// Compact as of `now`
+    pub fn compact_at(&self, now: Timestamp) -> Result<CompactionResult> {
+        let mut state = self.state.write();
+        let mut entries_removed = 0;
+        let mut bytes_freed = 0;
+        if let Some(policy) = &self.ttl_policy {
+            let ttl = policy.entry_ttl();
+            state.entries.retain(|stored| {
+                let expired = stored.entry.is_expired(ttl, now);
+                if expired {
+                    entries_removed += 1;
+                    bytes_freed += stored.encoded_len;
+                }
+                !expired
+            });
+        }
+        if entries_removed > 0 {
+            state.root = None;
+        }
+
+        Ok(CompactionResult {
+            entries_removed,
+            bytes_freed,
+            new_root: state.root(),
+        })
+    }
+}
+
+fn position(state: &LedgerState, sequence: u64) -> Option<usize> {
+    state
+        .entries
+        .binary_search_by_key(&sequence, |stored| stored.entry.sequence)
+        .ok()
+}
+
+fn leaf_hash(bytes: &[u8]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(bytes.len() + 1);
+    data.push(0x00);
+    data.extend_from_slice(bytes);
+    hash_blake3(&data)
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [0u8; 65];
+    data[0] = 0x01;
+    data[1..33].copy_from_slice(left);
+    data[33..].copy_from_slice(right);
+    hash_blake3(&data)
+}
+
+/// Hash one tree level into the next; an odd last node is carried up as is
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+fn merkle_root(mut level: Vec<[u8; 32]>) -> [u8; 32] {
+    if level.is_empty() {
+        return EMPTY_ROOT;
+    }
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+fn merkle_path(mut level: Vec<[u8; 32]>, mut index: usize) -> Vec<(Side, [u8; 32])> {
+    let mut path = Vec::new();
+    while level.len() > 1 {
+        if index % 2 == 1 {
+            path.push((Side::Left, level[index - 1]));
+        } else if let Some(sibling) = level.get(index + 1) {
+            path.push((Side::Right, *sibling));
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    #[test]
+    fn test_proofs_cover_every_entry() {
+        let ledger = Ledger::new(None).unwrap();
+        assert_eq!(ledger.root(), EMPTY_ROOT);
+        for i in 0..7 {
+            ledger.append(json!({ "block": format!("10.0.0.{i}") })).unwrap();
+        }
+
+        let root = ledger.root();
+        for sequence in 0..7 {
+            assert!(ledger.prove(sequence).unwrap().verify(&root));
+        }
+        let mut forged = ledger.prove(3).unwrap();
+        forged.leaf = ledger.get(4).unwrap().hash;
+        assert!(!forged.verify(&root));
+        assert!(ledger.prove(7).is_err());
+
+        // Without a TTL policy nothing expires
+        let result = ledger.compact().unwrap();
+        assert_eq!(result.entries_removed, 0);
+        assert_eq!(result.new_root, root);
+    }
+
+    #[test]
+    fn test_compaction_drops_expired_entries() {
+        let policy = TtlPolicy {
+            entry_ttl_secs: 10 * 60,
+            compaction_interval_secs: 60,
+        };
+        let ledger = Ledger::new(Some(policy)).unwrap();
+        let start = 1_000 * MINUTE_MS;
+        for i in 0..6 {
+            let at = Timestamp::from_millis(start + i * MINUTE_MS);
+            ledger.append_at(json!({ "block": i }), at).unwrap();
+        }
+        let old_root = ledger.root();
+
+        let now = Timestamp::from_millis(start + 12 * MINUTE_MS);
+        let result = ledger.compact_at(now).unwrap();
+        assert_eq!(result.entries_removed, 3);
+        assert_eq!(result.bytes_freed, 3 * br#"{"block":0}"#.len() as u64);
+        assert_ne!(result.new_root, old_root);
+        assert_eq!(result.new_root, ledger.root());
+
+        assert!(ledger.get(2).is_none());
+        assert!(ledger.prove(2).is_err());
+        for sequence in 3..6 {
+            assert!(ledger.prove(sequence).unwrap().verify(&result.new_root));
+        }
+
+        // Sequences keep growing past compacted entries
+        let entry = ledger.append_at(json!({ "block": 6 }), now).unwrap();
+        assert_eq!(entry.sequence, 6);
+        assert_eq!(ledger.compact_at(now).unwrap().entries_removed, 0);
+
+        let invalid = TtlPolicy {
+            compaction_interval_secs: 0,
+            ..policy
+        };
+        assert!(Ledger::new(Some(invalid)).is_err());
+    }
+}
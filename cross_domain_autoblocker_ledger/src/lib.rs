@@ -5,8 +5,6 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
-
 pub mod api;
 pub mod blocking;
 pub mod config;
@@ -14,17 +12,24 @@ pub mod consensus;
 pub mod core;
 pub mod ledger;
 
+pub use blocking::{EventNormalizer, JsonPath, NormalizationMapping, SecurityEvent};
+pub use crate::core::LedgerNode;
+pub use ledger::{CompactionResult, Ledger, LedgerEntry, MembershipProof, TtlPolicy};
+
 /// Ledger configuration
 #[derive(Debug, Clone)]
 pub struct LedgerConfig {
     /// Consensus timeout in milliseconds
     pub consensus_timeout_ms: u64,
+    /// Expiry of ledger entries, `None` keeps entries forever
+    pub ttl_policy: Option<TtlPolicy>,
 }
 
 impl Default for LedgerConfig {
     fn default() -> Self {
         Self {
             consensus_timeout_ms: 5000,
+            ttl_policy: None,
         }
     }
 }
@@ -1 +1,840 @@
 //! Core module
+//!
+//! The lattice engine, which owns the node set behind a single lock and
+//! broadcasts each change to its subscribers.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use futures::stream::{self, Stream, StreamExt};
+use parking_lot::RwLock;
+use shared_core::{Result, SystemError};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    diff::{LatticeDiff, LatticeSnapshot, NodePatch},
+    lattice::{LatticeNode, NodeId},
+    DuplicatePolicy, LatticeConfig,
+};
+
+/// Change batches a subscriber may fall behind by before it misses some
+const CHANGE_BATCH_CAPACITY: usize = 1024;
+
+/// A change to the lattice, as seen by subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub enum LatticeChangeEvent {
+    /// A node was inserted
+    NodeAdded(LatticeNode),
+    /// A node was removed
+    NodeRemoved(NodeId),
+    /// The label or attributes of a node changed
+    NodeModified {
+        /// Node
+        id: NodeId,
+        /// Old and new values
+        patch: NodePatch,
+    },
+    /// An edge from a parent to its child was added
+    EdgeAdded {
+        /// Parent
+        from: NodeId,
+        /// Child
+        to: NodeId,
+    },
+    /// An edge from a parent to its child was removed
+    EdgeRemoved {
+        /// Parent
+        from: NodeId,
+        /// Child
+        to: NodeId,
+    },
+}
+
+/// Outcome of [`LatticeEngine::batch_insert`]
+#[derive(Debug, Default)]
+pub struct BatchInsertReport {
+    /// Number of nodes inserted
+    pub inserted: usize,
+    /// IDs that already existed (or repeated within the batch) and were skipped
+    pub duplicates: Vec<NodeId>,
+    /// Nodes rejected by validation, with the reason
+    pub errors: Vec<(NodeId, SystemError)>,
+}
+
+/// Semantic lattice engine
+pub struct LatticeEngine {
+    config: LatticeConfig,
+    nodes: RwLock<HashMap<NodeId, LatticeNode>>,
+    /// Every change made under one write lock is sent as one batch
+    changes: broadcast::Sender<Arc<[LatticeChangeEvent]>>,
+}
+
+impl LatticeEngine {
+    /// Create an empty engine
+    pub fn new(config: LatticeConfig) -> Self {
+        Self {
+            config,
+            nodes: RwLock::new(HashMap::new()),
+            changes: broadcast::channel(CHANGE_BATCH_CAPACITY).0,
+        }
+    }
+
+    /// Get the engine configuration
+    pub fn config(&self) -> &LatticeConfig {
+        &self.config
+    }
+
+    /// Insert a single node
+    ///
+    /// Fails if the ID already exists or a parent is unknown.
+    pub async fn insert(&self, node: LatticeNode) -> Result<()> {
+        let id = node.id.clone();
+        let mut report = self.batch_insert(vec![node]).await?;
+
+        if !report.duplicates.is_empty() {
+            return Err(already_exists(&id));
+        }
+        match report.errors.pop() {
+            Some((_, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Insert many nodes under a single write lock
+    ///
+    /// All nodes are validated before any is inserted. Parents may refer to
+    /// existing nodes or to other nodes in the same batch; a node whose parent
+    /// is rejected is rejected too. Duplicates are handled according to
+    /// [`LatticeConfig::duplicate_policy`]: with [`DuplicatePolicy::Fail`] the
+    /// whole batch is rolled back.
+    ///
+    /// Subscribers receive the inserted nodes, each followed by the edges
+    /// from its parents, in one batch.
+    pub async fn batch_insert(&self, nodes: Vec<LatticeNode>) -> Result<BatchInsertReport> {
+        let mut graph = self.nodes.write();
+        let mut report = BatchInsertReport::default();
+
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            if graph.contains_key(&node.id) || !seen.insert(node.id.clone()) {
+                if self.config.duplicate_policy == DuplicatePolicy::Fail {
+                    return Err(already_exists(&node.id));
+                }
+                report.duplicates.push(node.id);
+                continue;
+            }
+
+            match validate_node(&node) {
+                Ok(()) => candidates.push(node),
+                Err(err) => report.errors.push((node.id, err)),
+            }
+        }
+
+        // Reject nodes with unknown parents until no more rejections cascade
+        loop {
+            let batch_ids: HashSet<NodeId> = candidates.iter().map(|n| n.id.clone()).collect();
+            let (valid, invalid): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|node| {
+                node.parents
+                    .iter()
+                    .all(|p| graph.contains_key(p) || batch_ids.contains(p))
+            });
+            candidates = valid;
+
+            if invalid.is_empty() {
+                break;
+            }
+            for node in invalid {
+                let missing = node
+                    .parents
+                    .iter()
+                    .find(|p| !graph.contains_key(*p) && !batch_ids.contains(*p))
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                report
+                    .errors
+                    .push((node.id, SystemError::not_found("lattice node", missing)));
+            }
+        }
+
+        let (ordered, cyclic) = topological_order(candidates);
+        for node in cyclic {
+            report.errors.push((
+                node.id,
+                SystemError::validation("parents", "node is part of or depends on a cycle", None),
+            ));
+        }
+
+        if graph.len() + ordered.len() > self.config.max_nodes {
+            return Err(SystemError::InvalidState {
+                message: format!(
+                    "inserting {} nodes would exceed the limit of {} nodes",
+                    ordered.len(),
+                    self.config.max_nodes
+                ),
+                current_state: Some(format!("{} nodes", graph.len())),
+                expected_state: None,
+            });
+        }
+
+        report.inserted = ordered.len();
+        let mut changes = Vec::new();
+        for node in ordered {
+            if self.changes.receiver_count() > 0 {
+                changes.push(LatticeChangeEvent::NodeAdded(node.clone()));
+                changes.extend(node.parents.iter().map(|parent| LatticeChangeEvent::EdgeAdded {
+                    from: parent.clone(),
+                    to: node.id.clone(),
+                }));
+            }
+            graph.insert(node.id.clone(), node);
+        }
+        if !changes.is_empty() {
+            // Sent under the write lock so batches arrive in the order applied
+            let _ = self.changes.send(changes.into());
+        }
+        tracing::debug!(
+            "Batch inserted {} nodes ({} duplicates, {} errors)",
+            report.inserted,
+            report.duplicates.len(),
+            report.errors.len()
+        );
+
+        Ok(report)
+    }
+
+    /// Copy every node
+    pub fn snapshot(&self) -> LatticeSnapshot {
+        LatticeSnapshot {
+            nodes: self.nodes.read().clone(),
+        }
+    }
+
+    /// Changes turning `old` into `new`
+    pub fn diff(old: &LatticeSnapshot, new: &LatticeSnapshot) -> LatticeDiff {
+        LatticeDiff::between(old, new)
+    }
+
+    /// The diff rolling back `diff`
+    pub fn invert_diff(diff: LatticeDiff) -> LatticeDiff {
+        diff.invert()
+    }
+
+    /// Apply `diff` under a single write lock
+    ///
+    /// Nothing changes unless the whole diff applies: removed nodes and
+    /// edges and the old values of patches must be there, added nodes and
+    /// edges must not, and the result must have no unknown parents, no
+    /// cycles and at most [`LatticeConfig::max_nodes`] nodes.
+    ///
+    /// Subscribers receive, in one batch, the removed edges, the removed
+    /// nodes, the added nodes each followed by the edges from its parents,
+    /// the other modified nodes and the other added edges.
+    pub fn apply_diff(&self, diff: &LatticeDiff) -> Result<()> {
+        let mut graph = self.nodes.write();
+        // Nodes the diff touches as they will be, `None` once removed
+        let mut staged: HashMap<NodeId, Option<LatticeNode>> = HashMap::new();
+
+        for id in &diff.removed_nodes {
+            if !graph.contains_key(id) {
+                return Err(SystemError::not_found("lattice node", id.to_string()));
+            }
+            if staged.insert(id.clone(), None).is_some() {
+                return Err(SystemError::validation(
+                    "removed_nodes",
+                    "node is removed twice",
+                    Some(id.to_string()),
+                ));
+            }
+        }
+        for id in &diff.added_nodes {
+            if graph.contains_key(id) || staged.contains_key(id) {
+                return Err(already_exists(id));
+            }
+            staged.insert(id.clone(), Some(LatticeNode::new(id.clone(), "")));
+        }
+
+        for (id, patch) in &diff.modified_nodes {
+            match stage(&mut staged, &graph, id)? {
+                Some(node) => {
+                    patch.check(node)?;
+                    patch.apply(node);
+                },
+                // The patch of a removed node empties it
+                None => patch.check(&graph[id])?,
+            }
+        }
+
+        for (parent, child) in &diff.removed_edges {
+            let missing = || SystemError::not_found("lattice edge", format!("{parent} -> {child}"));
+            match stage(&mut staged, &graph, child)? {
+                Some(node) => {
+                    let index = node.parents.iter().position(|p| p == parent).ok_or_else(missing)?;
+                    node.parents.remove(index);
+                },
+                None if graph[child].parents.contains(parent) => {},
+                None => return Err(missing()),
+            }
+        }
+        for (parent, child) in &diff.added_edges {
+            let node = stage(&mut staged, &graph, child)?
+                .as_mut()
+                .ok_or_else(|| SystemError::not_found("lattice node", child.to_string()))?;
+            if node.parents.contains(parent) {
+                return Err(SystemError::AlreadyExists {
+                    resource_type: "lattice edge".to_string(),
+                    identifier: format!("{parent} -> {child}"),
+                });
+            }
+            node.parents.push(parent.clone());
+        }
+
+        let resulting = |id: &NodeId| match staged.get(id) {
+            Some(node) => node.as_ref(),
+            None => graph.get(id),
+        };
+        // Nodes the diff leaves alone may still have a removed parent
+        let removes = !diff.removed_nodes.is_empty();
+        let untouched = graph.values().filter(|node| removes && !staged.contains_key(&node.id));
+        for node in staged.values().flatten().chain(untouched) {
+            validate_node(node)?;
+            if let Some(parent) = node.parents.iter().find(|p| resulting(p).is_none()) {
+                return Err(SystemError::not_found("lattice node", parent.to_string()));
+            }
+        }
+        for (parent, child) in &diff.added_edges {
+            if is_ancestor(child, parent, resulting) {
+                return Err(SystemError::validation(
+                    "parents",
+                    "edge would create a cycle",
+                    Some(format!("{parent} -> {child}")),
+                ));
+            }
+        }
+
+        let count = graph.len() + diff.added_nodes.len() - diff.removed_nodes.len();
+        if count > self.config.max_nodes {
+            return Err(SystemError::InvalidState {
+                message: format!(
+                    "applying the diff would exceed the limit of {} nodes",
+                    self.config.max_nodes
+                ),
+                current_state: Some(format!("{} nodes", graph.len())),
+                expected_state: None,
+            });
+        }
+
+        if self.changes.receiver_count() > 0 {
+            let _ = self.changes.send(diff_changes(diff, &staged).into());
+        }
+        for (id, node) in staged {
+            match node {
+                Some(node) => graph.insert(id, node),
+                None => graph.remove(&id),
+            };
+        }
+        tracing::debug!(
+            "Applied diff adding {} and removing {} nodes",
+            diff.added_nodes.len(),
+            diff.removed_nodes.len()
+        );
+
+        Ok(())
+    }
+
+    /// Receive every change made from now on
+    ///
+    /// See [`subscribe_filtered`](Self::subscribe_filtered).
+    pub fn subscribe(&self) -> impl Stream<Item = LatticeChangeEvent> + Send + 'static {
+        self.subscribe_filtered(|_| true)
+    }
+
+    /// Receive the changes made from now on that match `predicate`
+    ///
+    /// Changes arrive in the order they were applied. A subscriber that falls
+    /// more than 1024 batches behind misses the oldest ones, which is logged.
+    /// The stream ends once the engine is dropped.
+    pub fn subscribe_filtered(
+        &self,
+        predicate: impl Fn(&LatticeChangeEvent) -> bool + Send + 'static,
+    ) -> impl Stream<Item = LatticeChangeEvent> + Send + 'static {
+        let receiver = self.changes.subscribe();
+        stream::unfold((receiver, predicate), |(mut receiver, predicate)| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(batch) => {
+                        let matched: Vec<_> =
+                            batch.iter().filter(|change| predicate(change)).cloned().collect();
+                        if !matched.is_empty() {
+                            return Some((stream::iter(matched), (receiver, predicate)));
+                        }
+                    },
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("Lattice subscriber missed {} change batches", missed);
+                    },
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .flatten()
+    }
+
+    /// Get a node by ID
+    pub fn get(&self, id: &NodeId) -> Option<LatticeNode> {
+        self.nodes.read().get(id).cloned()
+    }
+
+    /// Check whether a node exists
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.nodes.read().contains_key(id)
+    }
+
+    /// Number of nodes in the lattice
+    pub fn len(&self) -> usize {
+        self.nodes.read().len()
+    }
+
+    /// Check whether the lattice has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.read().is_empty()
+    }
+
+    /// Call `f` with the node set, held under the read lock
+    pub(crate) fn with_nodes<R>(&self, f: impl FnOnce(&HashMap<NodeId, LatticeNode>) -> R) -> R {
+        f(&self.nodes.read())
+    }
+}
+
+fn already_exists(id: &NodeId) -> SystemError {
+    SystemError::AlreadyExists {
+        resource_type: "lattice node".to_string(),
+        identifier: id.to_string(),
+    }
+}
+
+/// The staged state of the node `id`, staging it from `graph` if needed
+fn stage<'a>(
+    staged: &'a mut HashMap<NodeId, Option<LatticeNode>>,
+    graph: &HashMap<NodeId, LatticeNode>,
+    id: &NodeId,
+) -> Result<&'a mut Option<LatticeNode>> {
+    match staged.entry(id.clone()) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let node = graph
+                .get(id)
+                .ok_or_else(|| SystemError::not_found("lattice node", id.to_string()))?;
+            Ok(entry.insert(Some(node.clone())))
+        },
+    }
+}
+
+/// Whether `ancestor` is `node` or one of its ancestors, following the
+/// parents of the nodes `resolve` returns
+fn is_ancestor<'a>(
+    ancestor: &NodeId,
+    node: &'a NodeId,
+    resolve: impl Fn(&NodeId) -> Option<&'a LatticeNode>,
+) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![node];
+    while let Some(id) = pending.pop() {
+        if id == ancestor {
+            return true;
+        }
+        if seen.insert(id) {
+            pending.extend(resolve(id).into_iter().flat_map(|node| &node.parents));
+        }
+    }
+    false
+}
+
+/// Changes subscribers see when `diff` is applied, given the staged nodes
+fn diff_changes(
+    diff: &LatticeDiff,
+    staged: &HashMap<NodeId, Option<LatticeNode>>,
+) -> Vec<LatticeChangeEvent> {
+    let added: HashSet<&NodeId> = diff.added_nodes.iter().collect();
+    let mut changes: Vec<_> = diff
+        .removed_edges
+        .iter()
+        .map(|(from, to)| LatticeChangeEvent::EdgeRemoved {
+            from: from.clone(),
+            to: to.clone(),
+        })
+        .collect();
+    changes.extend(diff.removed_nodes.iter().cloned().map(LatticeChangeEvent::NodeRemoved));
+
+    let nodes = diff.added_nodes.iter().filter_map(|id| staged.get(id).cloned().flatten());
+    let (ordered, _) = topological_order(nodes.collect());
+    for node in ordered {
+        let edges: Vec<_> = node
+            .parents
+            .iter()
+            .map(|parent| LatticeChangeEvent::EdgeAdded {
+                from: parent.clone(),
+                to: node.id.clone(),
+            })
+            .collect();
+        changes.push(LatticeChangeEvent::NodeAdded(node));
+        changes.extend(edges);
+    }
+
+    let removed: HashSet<&NodeId> = diff.removed_nodes.iter().collect();
+    changes.extend(
+        diff.modified_nodes
+            .iter()
+            .filter(|(id, _)| !added.contains(id) && !removed.contains(id))
+            .map(|(id, patch)| LatticeChangeEvent::NodeModified {
+                id: id.clone(),
+                patch: patch.clone(),
+            }),
+    );
+    changes.extend(diff.added_edges.iter().filter(|(_, to)| !added.contains(to)).map(
+        |(from, to)| LatticeChangeEvent::EdgeAdded {
+            from: from.clone(),
+            to: to.clone(),
+        },
+    ));
+    changes
+}
+
+fn validate_node(node: &LatticeNode) -> Result<()> {
+    if node.id.as_str().is_empty() {
+        return Err(SystemError::validation("id", "must not be empty", None));
+    }
+    if node.parents.contains(&node.id) {
+        return Err(SystemError::validation(
+            "parents",
+            "node cannot be its own parent",
+            Some(node.id.to_string()),
+        ));
+    }
+    Ok(())
+}
+
+/// Order batch nodes so parents come before children (Kahn's algorithm)
+///
+/// Returns the ordered nodes and those left over because of a cycle.
+fn topological_order(nodes: Vec<LatticeNode>) -> (Vec<LatticeNode>, Vec<LatticeNode>) {
+    let index: HashMap<NodeId, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id.clone(), i))
+        .collect();
+
+    let mut pending = vec![0usize; nodes.len()];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        for parent in &node.parents {
+            if let Some(&p) = index.get(parent) {
+                pending[i] += 1;
+                children[p].push(i);
+            }
+        }
+    }
+
+    let mut queue: VecDeque<usize> = (0..nodes.len()).filter(|&i| pending[i] == 0).collect();
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(i) = queue.pop_front() {
+        order.push(i);
+        for &child in &children[i] {
+            pending[child] -= 1;
+            if pending[child] == 0 {
+                queue.push_back(child);
+            }
+        }
+    }
+
+    let mut slots: Vec<Option<LatticeNode>> = nodes.into_iter().map(Some).collect();
+    let ordered: Vec<LatticeNode> = order.iter().filter_map(|&i| slots[i].take()).collect();
+    let cyclic = slots.into_iter().flatten().collect();
+    (ordered, cyclic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, parents: &[&str]) -> LatticeNode {
+        LatticeNode {
+            parents: parents.iter().map(|&p| NodeId::from(p)).collect(),
+            ..LatticeNode::new(id, id)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_resolves_parents_within_batch() {
+        let engine = LatticeEngine::new(LatticeConfig::default());
+        engine.insert(node("top", &[])).await.unwrap();
+
+        // Children listed before their parents
+        let report = engine
+            .batch_insert(vec![
+                node("dog", &["mammal"]),
+                node("mammal", &["animal"]),
+                node("animal", &["top"]),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(report.inserted, 3);
+        assert!(report.duplicates.is_empty());
+        assert!(report.errors.is_empty());
+        assert_eq!(engine.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_reports_invalid_nodes() {
+        let engine = LatticeEngine::new(LatticeConfig::default());
+        engine.insert(node("top", &[])).await.unwrap();
+
+        let report = engine
+            .batch_insert(vec![
+                node("top", &[]),
+                node("a", &["top"]),
+                node("a", &["top"]),
+                node("orphan", &["missing"]),
+                node("orphan_child", &["orphan"]),
+                node("loop_a", &["loop_b"]),
+                node("loop_b", &["loop_a"]),
+                node("self", &["self"]),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(report.inserted, 1);
+        assert_eq!(report.duplicates, vec![NodeId::from("top"), NodeId::from("a")]);
+
+        let mut rejected: Vec<String> = report.errors.iter().map(|(id, _)| id.to_string()).collect();
+        rejected.sort();
+        assert_eq!(rejected, vec!["loop_a", "loop_b", "orphan", "orphan_child", "self"]);
+        assert_eq!(engine.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_fail_policy_rolls_back() {
+        let config = LatticeConfig {
+            duplicate_policy: DuplicatePolicy::Fail,
+            ..Default::default()
+        };
+        let engine = LatticeEngine::new(config);
+        engine.insert(node("top", &[])).await.unwrap();
+
+        let result = engine
+            .batch_insert(vec![node("a", &["top"]), node("top", &[])])
+            .await;
+
+        assert!(matches!(result, Err(SystemError::AlreadyExists { .. })));
+        assert!(!engine.contains(&NodeId::from("a")));
+        assert_eq!(engine.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_changes() {
+        let engine = LatticeEngine::new(LatticeConfig::default());
+        let changes = engine.subscribe();
+        let nodes = engine.subscribe_filtered(|change| {
+            matches!(change, LatticeChangeEvent::NodeAdded(_))
+        });
+        let mut batches = engine.changes.subscribe();
+
+        engine.insert(node("top", &[])).await.unwrap();
+        engine
+            .batch_insert(vec![node("dog", &["mammal"]), node("mammal", &["top"])])
+            .await
+            .unwrap();
+        // Nothing changes, so nothing is sent
+        engine.batch_insert(vec![node("top", &[])]).await.unwrap();
+
+        // One batch per call, with parents inserted before children
+        assert_eq!(batches.try_recv().unwrap().len(), 1);
+        let batch = batches.try_recv().unwrap();
+        assert_eq!(
+            batch[2..],
+            [
+                LatticeChangeEvent::NodeAdded(node("dog", &["mammal"])),
+                LatticeChangeEvent::EdgeAdded {
+                    from: NodeId::from("mammal"),
+                    to: NodeId::from("dog"),
+                },
+            ]
+        );
+        assert!(batches.try_recv().is_err());
+
+        drop(engine);
+        let changes: Vec<_> = changes.collect().await;
+        assert_eq!(changes.len(), 5);
+        assert_eq!(changes[0], LatticeChangeEvent::NodeAdded(node("top", &[])));
+        let ids: Vec<_> = nodes
+            .map(|change| match change {
+                LatticeChangeEvent::NodeAdded(node) => node.id.to_string(),
+                other => panic!("unexpected change {other:?}"),
+            })
+            .collect()
+            .await;
+        assert_eq!(ids, ["top", "mammal", "dog"]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_respects_max_nodes() {
+        let config = LatticeConfig {
+            max_nodes: 2,
+            ..Default::default()
+        };
+        let engine = LatticeEngine::new(config);
+
+        let result = engine
+            .batch_insert(vec![node("a", &[]), node("b", &[]), node("c", &[])])
+            .await;
+
+        assert!(matches!(result, Err(SystemError::InvalidState { .. })));
+        assert!(engine.is_empty());
+    }
+
+    fn tagged(id: &str, parents: &[&str], colour: &str) -> LatticeNode {
+        let mut node = node(id, parents);
+        node.attributes.insert("colour".to_string(), serde_json::json!(colour));
+        node
+    }
+
+    #[tokio::test]
+    async fn test_apply_and_invert_diff() {
+        let engine = LatticeEngine::new(LatticeConfig::default());
+        engine
+            .batch_insert(vec![
+                node("top", &[]),
+                tagged("a", &["top"], "red"),
+                node("b", &["top"]),
+                node("old", &["a", "b"]),
+            ])
+            .await
+            .unwrap();
+        let old = engine.snapshot();
+        let new = LatticeSnapshot::from_nodes([
+            node("top", &[]),
+            LatticeNode {
+                label: "A".to_string(),
+                ..node("a", &["top"])
+            },
+            tagged("b", &["a"], "blue"),
+            tagged("new", &["b"], "green"),
+        ]);
+
+        let diff = LatticeEngine::diff(&old, &new);
+        assert_eq!(diff.added_nodes, [NodeId::from("new")]);
+        assert_eq!(diff.removed_nodes, [NodeId::from("old")]);
+        let modified: Vec<_> = diff.modified_nodes.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(modified, ["a", "b", "new", "old"]);
+        assert_eq!(
+            diff.modified_nodes[0].1,
+            NodePatch {
+                label: Some(("a".to_string(), "A".to_string())),
+                attributes: [("colour".to_string(), (Some(serde_json::json!("red")), None))]
+                    .into(),
+            }
+        );
+        let edge = |from: &str, to: &str| (NodeId::from(from), NodeId::from(to));
+        assert_eq!(diff.added_edges, [edge("a", "b"), edge("b", "new")]);
+        assert_eq!(diff.removed_edges, [edge("a", "old"), edge("b", "old"), edge("top", "b")]);
+
+        let mut batches = engine.changes.subscribe();
+        engine.apply_diff(&diff).unwrap();
+        assert!(LatticeEngine::diff(&engine.snapshot(), &new).is_empty());
+        let changes = batches.try_recv().unwrap();
+        assert_eq!(changes.len(), 9);
+        assert_eq!(
+            changes[3..6],
+            [
+                LatticeChangeEvent::NodeRemoved(NodeId::from("old")),
+                LatticeChangeEvent::NodeAdded(tagged("new", &["b"], "green")),
+                LatticeChangeEvent::EdgeAdded {
+                    from: NodeId::from("b"),
+                    to: NodeId::from("new"),
+                },
+            ]
+        );
+        assert!(matches!(
+            &changes[6],
+            LatticeChangeEvent::NodeModified { id, .. } if id.as_str() == "a"
+        ));
+        assert_eq!(
+            changes[8],
+            LatticeChangeEvent::EdgeAdded {
+                from: NodeId::from("a"),
+                to: NodeId::from("b"),
+            }
+        );
+
+        // Applying it again conflicts, and changes nothing
+        assert!(engine.apply_diff(&diff).is_err());
+        assert!(LatticeEngine::diff(&engine.snapshot(), &new).is_empty());
+
+        engine.apply_diff(&LatticeEngine::invert_diff(diff)).unwrap();
+        assert!(LatticeEngine::diff(&engine.snapshot(), &old).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_diff_rejects_invalid_results() {
+        let engine = LatticeEngine::new(LatticeConfig {
+            max_nodes: 3,
+            ..Default::default()
+        });
+        engine
+            .batch_insert(vec![node("top", &[]), tagged("a", &["top"], "red")])
+            .await
+            .unwrap();
+        let before = engine.snapshot();
+        let edge = |from: &str, to: &str| (NodeId::from(from), NodeId::from(to));
+        let patch = |old: &str, new: &str| NodePatch {
+            label: None,
+            attributes: [(
+                "colour".to_string(),
+                (Some(serde_json::json!(old)), Some(serde_json::json!(new))),
+            )]
+            .into(),
+        };
+
+        let invalid = [
+            // A stale patch
+            LatticeDiff {
+                modified_nodes: vec![(NodeId::from("a"), patch("blue", "green"))],
+                ..Default::default()
+            },
+            // A cycle
+            LatticeDiff {
+                added_edges: vec![edge("a", "top")],
+                ..Default::default()
+            },
+            // A child left with a removed parent
+            LatticeDiff {
+                removed_nodes: vec![NodeId::from("top")],
+                ..Default::default()
+            },
+            // An unknown parent
+            LatticeDiff {
+                added_nodes: vec![NodeId::from("b")],
+                added_edges: vec![edge("missing", "b")],
+                ..Default::default()
+            },
+            // Too many nodes
+            LatticeDiff {
+                added_nodes: vec![NodeId::from("b"), NodeId::from("c")],
+                ..Default::default()
+            },
+        ];
+        for diff in &invalid {
+            assert!(engine.apply_diff(diff).is_err(), "{diff:?} should not apply");
+            assert_eq!(engine.snapshot(), before);
+        }
+
+        engine
+            .apply_diff(&LatticeDiff {
+                modified_nodes: vec![(NodeId::from("a"), patch("red", "green"))],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(engine.get(&NodeId::from("a")).unwrap().attributes["colour"], "green");
+    }
+}
@@ -0,0 +1,214 @@
+//! Diff module
+//!
+//! Snapshots of a lattice and the changes between two of them, so that a
+//! lattice can be moved from one schema version to the next and back.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shared_core::{Result, SystemError};
+
+use crate::lattice::{LatticeNode, NodeId};
+
+/// The nodes of a lattice at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatticeSnapshot {
+    /// Every node, by ID
+    pub nodes: HashMap<NodeId, LatticeNode>,
+}
+
+impl LatticeSnapshot {
+    /// Snapshot of `nodes`; a later node replaces an earlier one with the
+    /// same ID
+    pub fn from_nodes(nodes: impl IntoIterator<Item = LatticeNode>) -> Self {
+        Self {
+            nodes: nodes.into_iter().map(|node| (node.id.clone(), node)).collect(),
+        }
+    }
+
+    /// Edges from parents to children, sorted
+    fn edges(&self) -> Vec<(NodeId, NodeId)> {
+        let mut edges: Vec<_> = self
+            .nodes
+            .values()
+            .flat_map(|node| node.parents.iter().map(|parent| (parent.clone(), node.id.clone())))
+            .collect();
+        edges.sort_by(|a, b| edge_key(a).cmp(&edge_key(b)));
+        edges.dedup();
+        edges
+    }
+}
+
+/// Changes to the label and attributes of a node
+///
+/// Each change holds the old value then the new one. An attribute that is
+/// not set on one side is `None` there.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodePatch {
+    /// Old and new label, if it changed
+    pub label: Option<(String, String)>,
+    /// Old and new value of each attribute that changed
+    pub attributes: BTreeMap<String, (Option<Value>, Option<Value>)>,
+}
+
+impl NodePatch {
+    /// Changes from `old` to `new`, ignoring IDs and parents
+    pub fn between(old: &LatticeNode, new: &LatticeNode) -> Self {
+        let label = (old.label != new.label).then(|| (old.label.clone(), new.label.clone()));
+        let attributes = old
+            .attributes
+            .keys()
+            .chain(new.attributes.keys())
+            .filter(|key| old.attributes.get(*key) != new.attributes.get(*key))
+            .map(|key| {
+                let change = (old.attributes.get(key).cloned(), new.attributes.get(key).cloned());
+                (key.clone(), change)
+            })
+            .collect();
+        Self { label, attributes }
+    }
+
+    /// Whether nothing changes
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.attributes.is_empty()
+    }
+
+    /// The patch undoing this one
+    pub fn invert(self) -> Self {
+        Self {
+            label: self.label.map(|(old, new)| (new, old)),
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(|(key, (old, new))| (key, (new, old)))
+                .collect(),
+        }
+    }
+
+    /// Fail with `InvalidState` unless `node` has the old values
+    pub(crate) fn check(&self, node: &LatticeNode) -> Result<()> {
+        if let Some((old, _)) = &self.label {
+            if *old != node.label {
+                return Err(conflict(node, "label", old, &node.label));
+            }
+        }
+        for (key, (old, _)) in &self.attributes {
+            let current = node.attributes.get(key);
+            if old.as_ref() != current {
+                return Err(conflict(node, &format!("attribute '{key}'"), old, &current));
+            }
+        }
+        Ok(())
+    }
+
+    /// Give `node` the new values
+    pub(crate) fn apply(&self, node: &mut LatticeNode) {
+        if let Some((_, new)) = &self.label {
+            node.label.clone_from(new);
+        }
+        for (key, (_, new)) in &self.attributes {
+            match new {
+                Some(value) => node.attributes.insert(key.clone(), value.clone()),
+                None => node.attributes.remove(key),
+            };
+        }
+    }
+}
+
+/// Changes turning one [`LatticeSnapshot`] into another
+///
+/// Edges point from parent to child. An added node also has a patch from
+/// an empty node in `modified_nodes`, and a removed node a patch to an
+/// empty node, so that the diff can be applied and inverted on its own.
+/// Every list is sorted by ID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatticeDiff {
+    /// Nodes only in the new snapshot
+    pub added_nodes: Vec<NodeId>,
+    /// Nodes only in the old snapshot
+    pub removed_nodes: Vec<NodeId>,
+    /// Nodes whose label or attributes differ
+    pub modified_nodes: Vec<(NodeId, NodePatch)>,
+    /// Edges only in the new snapshot
+    pub added_edges: Vec<(NodeId, NodeId)>,
+    /// Edges only in the old snapshot
+    pub removed_edges: Vec<(NodeId, NodeId)>,
+}
+
+impl LatticeDiff {
+    /// Changes from `old` to `new`
+    pub fn between(old: &LatticeSnapshot, new: &LatticeSnapshot) -> Self {
+        let mut ids: Vec<&NodeId> = old.nodes.keys().chain(new.nodes.keys()).collect();
+        ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        ids.dedup();
+
+        let mut diff = Self::default();
+        for id in ids {
+            let (before, after) = match (old.nodes.get(id), new.nodes.get(id)) {
+                (Some(before), Some(after)) => (before.clone(), after.clone()),
+                (None, Some(after)) => {
+                    diff.added_nodes.push(id.clone());
+                    (LatticeNode::new(id.clone(), ""), after.clone())
+                },
+                (Some(before), None) => {
+                    diff.removed_nodes.push(id.clone());
+                    (before.clone(), LatticeNode::new(id.clone(), ""))
+                },
+                (None, None) => continue,
+            };
+            let patch = NodePatch::between(&before, &after);
+            if !patch.is_empty() {
+                diff.modified_nodes.push((id.clone(), patch));
+            }
+        }
+
+        let (old_edges, new_edges) = (old.edges(), new.edges());
+        let old_set: HashSet<_> = old_edges.iter().collect();
+        let new_set: HashSet<_> = new_edges.iter().collect();
+        diff.added_edges = new_edges.iter().filter(|e| !old_set.contains(e)).cloned().collect();
+        diff.removed_edges = old_edges.iter().filter(|e| !new_set.contains(e)).cloned().collect();
+        diff
+    }
+
+    /// Whether nothing changes
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.modified_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+
+    /// The diff undoing this one
+    pub fn invert(self) -> Self {
+        Self {
+            added_nodes: self.removed_nodes,
+            removed_nodes: self.added_nodes,
+            modified_nodes: self
+                .modified_nodes
+                .into_iter()
+                .map(|(id, patch)| (id, patch.invert()))
+                .collect(),
+            added_edges: self.removed_edges,
+            removed_edges: self.added_edges,
+        }
+    }
+}
+
+fn edge_key((from, to): &(NodeId, NodeId)) -> (&str, &str) {
+    (from.as_str(), to.as_str())
+}
+
+fn conflict(
+    node: &LatticeNode,
+    what: &str,
+    expected: &impl Serialize,
+    current: &impl Serialize,
+) -> SystemError {
+    SystemError::InvalidState {
+        message: format!("{what} of lattice node '{}' does not match the diff", node.id),
+        current_state: Some(serde_json::json!(current).to_string()),
+        expected_state: Some(serde_json::json!(expected).to_string()),
+    }
+}
@@ -1 +1,412 @@
 //! Lattice module
+//!
+//! Node types that make up the concept lattice, and structural metrics
+//! of a lattice.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use shared_core::{Id, Result, SystemError};
+
+use crate::core::LatticeEngine;
+
+/// Identifier of a lattice node
+pub type NodeId = Id;
+
+/// Concept in the lattice
+///
+/// Edges point from each parent (the more general concept) to the node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatticeNode {
+    /// Node ID
+    pub id: NodeId,
+    /// Human-readable concept label
+    pub label: String,
+    /// Concept attributes
+    pub attributes: HashMap<String, serde_json::Value>,
+    /// More general concepts this node is subsumed by
+    pub parents: Vec<NodeId>,
+}
+
+impl LatticeNode {
+    /// Create a node without attributes or parents
+    pub fn new(id: impl Into<NodeId>, label: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            attributes: HashMap::new(),
+            parents: Vec::new(),
+        }
+    }
+
+    /// Start building a node
+    pub fn builder() -> LatticeNodeBuilder {
+        LatticeNodeBuilder::default()
+    }
+}
+
+/// Builder for [`LatticeNode`]
+#[derive(Debug, Clone, Default)]
+pub struct LatticeNodeBuilder {
+    id: Option<NodeId>,
+    label: String,
+    attributes: HashMap<String, serde_json::Value>,
+    parents: Vec<NodeId>,
+}
+
+impl LatticeNodeBuilder {
+    /// Start from a copy of an existing node
+    pub fn extend(base: &LatticeNode) -> Self {
+        Self {
+            id: Some(base.id.clone()),
+            label: base.label.clone(),
+            attributes: base.attributes.clone(),
+            parents: base.parents.clone(),
+        }
+    }
+
+    /// Set the node ID
+    pub fn id(mut self, id: impl Into<NodeId>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set the concept label
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// Set an attribute, replacing any previous value for `key`
+    pub fn attribute(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.attributes.insert(key.into(), value);
+        self
+    }
+
+    /// Add a parent; adding the same parent twice has no effect
+    pub fn parent(mut self, parent_id: NodeId) -> Self {
+        if !self.parents.contains(&parent_id) {
+            self.parents.push(parent_id);
+        }
+        self
+    }
+
+    /// Build the node
+    ///
+    /// Fails with `Validation` if no ID was set.
+    pub fn build(self) -> Result<LatticeNode> {
+        let id = self
+            .id
+            .ok_or_else(|| SystemError::validation("id", "node ID is required", None))?;
+        Ok(LatticeNode {
+            id,
+            label: self.label,
+            attributes: self.attributes,
+            parents: self.parents,
+        })
+    }
+}
+
+/// Structural metrics of a lattice, see [`compute_metrics`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphMetrics {
+    /// Number of nodes
+    pub node_count: usize,
+    /// Number of edges from parents to children
+    pub edge_count: usize,
+    /// Edges on the longest path from a node without parents down to a node
+    pub max_depth: usize,
+    /// Average number of children of the nodes that have any
+    pub avg_branching_factor: f64,
+    /// Edges on the longest shortest path between two connected nodes,
+    /// whatever the direction of the edges
+    pub diameter: usize,
+    /// Nodes with neither parents nor children
+    pub isolated_nodes: usize,
+}
+
+/// Compute the structural metrics of the lattice of `engine`
+///
+/// Fails with `InvalidState` if the lattice has a cycle, as its depth is
+/// then unbounded. The diameter takes a breadth-first search per node.
+pub fn compute_metrics(engine: &LatticeEngine) -> Result<GraphMetrics> {
+    let structure = engine.with_nodes(Structure::new);
+    let order = structure.topological_order()?;
+
+    let count = structure.parents.len();
+    let mut depth = vec![0usize; count];
+    for &node in &order {
+        depth[node] = structure.parents[node]
+            .iter()
+            .map(|&p| depth[p] + 1)
+            .max()
+            .unwrap_or(0);
+    }
+
+    let edge_count: usize = structure.children.iter().map(Vec::len).sum();
+    let branching = structure
+        .children
+        .iter()
+        .filter(|children| !children.is_empty())
+        .count();
+    let avg_branching_factor = match branching {
+        0 => 0.0,
+        branching => edge_count as f64 / branching as f64,
+    };
+
+    Ok(GraphMetrics {
+        node_count: count,
+        edge_count,
+        max_depth: depth.into_iter().max().unwrap_or(0),
+        avg_branching_factor,
+        diameter: (0..count)
+            .map(|node| structure.eccentricity(node))
+            .max()
+            .unwrap_or(0),
+        isolated_nodes: (0..count)
+            .filter(|&node| {
+                structure.parents[node].is_empty() && structure.children[node].is_empty()
+            })
+            .count(),
+    })
+}
+
+/// Check that the lattice of `engine` is acyclic and has a top and a
+/// bottom element
+///
+/// The top is the one node without parents, the bottom the one node
+/// without children; an empty lattice has neither. Fails with
+/// `InvalidState` naming the first problem found.
+pub fn is_well_formed(engine: &LatticeEngine) -> Result<()> {
+    engine.with_nodes(|nodes| check_well_formed(&Structure::new(nodes)))
+}
+
+fn check_well_formed(structure: &Structure) -> Result<()> {
+    structure.topological_order()?;
+    let tops = structure.nodes_where(|node| structure.parents[node].is_empty());
+    let bottoms = structure.nodes_where(|node| structure.children[node].is_empty());
+    for (element, candidates) in [("top", tops), ("bottom", bottoms)] {
+        if candidates.len() != 1 {
+            return Err(SystemError::InvalidState {
+                message: format!("lattice has no {element} element"),
+                current_state: Some(format!("{} candidates: {:?}", candidates.len(), candidates)),
+                expected_state: Some(format!("a single {element} element")),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Edges of a lattice over node indexes
+struct Structure {
+    ids: Vec<NodeId>,
+    parents: Vec<Vec<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl Structure {
+    fn new(nodes: &HashMap<NodeId, LatticeNode>) -> Self {
+        let mut ids: Vec<NodeId> = nodes.keys().cloned().collect();
+        ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        let index: HashMap<&NodeId, usize> =
+            ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+
+        let mut parents = vec![Vec::new(); ids.len()];
+        let mut children = vec![Vec::new(); ids.len()];
+        for (i, id) in ids.iter().enumerate() {
+            for parent in &nodes[id].parents {
+                if let Some(&p) = index.get(parent) {
+                    parents[i].push(p);
+                    children[p].push(i);
+                }
+            }
+        }
+        Self {
+            ids,
+            parents,
+            children,
+        }
+    }
+
+    /// IDs of the nodes matching `predicate`
+    fn nodes_where(&self, predicate: impl Fn(usize) -> bool) -> Vec<NodeId> {
+        (0..self.ids.len())
+            .filter(|&node| predicate(node))
+            .map(|node| self.ids[node].clone())
+            .collect()
+    }
+
+    /// Nodes with parents before children (Kahn's algorithm)
+    ///
+    /// Fails with `InvalidState` if some nodes are on or below a cycle.
+    fn topological_order(&self) -> Result<Vec<usize>> {
+        let mut pending: Vec<usize> = self.parents.iter().map(Vec::len).collect();
+        let mut queue: VecDeque<usize> = (0..pending.len()).filter(|&i| pending[i] == 0).collect();
+        let mut order = Vec::with_capacity(pending.len());
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for &child in &self.children[node] {
+                pending[child] -= 1;
+                if pending[child] == 0 {
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        if order.len() < pending.len() {
+            let cyclic = self.nodes_where(|node| pending[node] > 0);
+            return Err(SystemError::InvalidState {
+                message: "lattice has a cycle".to_string(),
+                current_state: Some(format!("nodes on or below a cycle: {cyclic:?}")),
+                expected_state: Some("acyclic lattice".to_string()),
+            });
+        }
+        Ok(order)
+    }
+
+    /// Edges from `start` to the farthest node connected to it, by
+    /// breadth-first search ignoring the direction of edges
+    fn eccentricity(&self, start: usize) -> usize {
+        let mut distance = vec![None; self.ids.len()];
+        distance[start] = Some(0);
+        let mut queue = VecDeque::from([start]);
+        let mut farthest = 0;
+        while let Some(node) = queue.pop_front() {
+            let next = distance[node].unwrap_or(0) + 1;
+            for &neighbour in self.parents[node].iter().chain(&self.children[node]) {
+                if distance[neighbour].is_none() {
+                    distance[neighbour] = Some(next);
+                    farthest = next;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        farthest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LatticeConfig;
+    use serde_json::json;
+
+    fn node(id: &str, parents: &[&str]) -> LatticeNode {
+        LatticeNode {
+            parents: parents.iter().map(|&p| NodeId::from(p)).collect(),
+            ..LatticeNode::new(id, id)
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let node = LatticeNode::builder()
+            .id("dog")
+            .label("Dog")
+            .attribute("legs", json!(4))
+            .parent(NodeId::new("animal"))
+            .parent(NodeId::new("animal"))
+            .build()
+            .unwrap();
+        assert_eq!(node.id, NodeId::new("dog"));
+        assert_eq!(node.label, "Dog");
+        assert_eq!(node.attributes["legs"], json!(4));
+        assert_eq!(node.parents, vec![NodeId::new("animal")]);
+
+        let err = LatticeNode::builder()
+            .label("Nameless")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, SystemError::Validation { ref field, .. } if field == "id"));
+    }
+
+    #[test]
+    fn test_extend() {
+        let base = LatticeNode::builder()
+            .id("dog")
+            .label("Dog")
+            .attribute("legs", json!(4))
+            .parent(NodeId::new("animal"))
+            .build()
+            .unwrap();
+
+        let puppy = LatticeNodeBuilder::extend(&base)
+            .id("puppy")
+            .attribute("age", json!("young"))
+            .parent(NodeId::new("dog"))
+            .build()
+            .unwrap();
+        assert_eq!(puppy.id, NodeId::new("puppy"));
+        assert_eq!(puppy.label, "Dog");
+        assert_eq!(puppy.attributes.len(), 2);
+        assert_eq!(
+            puppy.parents,
+            vec![NodeId::new("animal"), NodeId::new("dog")]
+        );
+        assert_eq!(base.attributes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_compute_metrics() {
+        let engine = LatticeEngine::new(LatticeConfig::default());
+        let metrics = compute_metrics(&engine).unwrap();
+        assert_eq!(
+            (metrics.node_count, metrics.diameter, metrics.max_depth),
+            (0, 0, 0)
+        );
+        assert!(is_well_formed(&engine).is_err());
+
+        // top -> {a, b} -> c -> d
+        engine
+            .batch_insert(vec![
+                node("top", &[]),
+                node("a", &["top"]),
+                node("b", &["top"]),
+                node("c", &["a", "b"]),
+                node("d", &["c"]),
+            ])
+            .await
+            .unwrap();
+        let metrics = compute_metrics(&engine).unwrap();
+        assert_eq!(
+            metrics,
+            GraphMetrics {
+                node_count: 5,
+                edge_count: 5,
+                max_depth: 3,
+                avg_branching_factor: 1.25,
+                diameter: 3,
+                isolated_nodes: 0,
+            }
+        );
+        is_well_formed(&engine).unwrap();
+
+        // A lone node is a second top and bottom, but no farther from others
+        engine.insert(node("lone", &[])).await.unwrap();
+        let metrics = compute_metrics(&engine).unwrap();
+        assert_eq!((metrics.isolated_nodes, metrics.diameter), (1, 3));
+        let err = is_well_formed(&engine).unwrap_err();
+        assert!(
+            matches!(err, SystemError::InvalidState { ref message, .. } if message.contains("top"))
+        );
+    }
+
+    #[test]
+    fn test_cycles_are_not_well_formed() {
+        let nodes: HashMap<NodeId, LatticeNode> = [
+            node("top", &[]),
+            node("a", &["top", "b"]),
+            node("b", &["a"]),
+            node("c", &["b"]),
+        ]
+        .into_iter()
+        .map(|node| (node.id.clone(), node))
+        .collect();
+        let structure = Structure::new(&nodes);
+        let err = check_well_formed(&structure).unwrap_err();
+        assert!(
+            matches!(err, SystemError::InvalidState { ref message, .. } if message.contains("cycle"))
+        );
+        assert!(structure.topological_order().is_err());
+    }
+}
@@ -5,25 +5,43 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
-
 pub mod api;
 pub mod config;
 pub mod core;
+pub mod diff;
 pub mod lattice;
 pub mod query;
 pub mod reasoning;
 
+pub use crate::core::{BatchInsertReport, LatticeChangeEvent, LatticeEngine};
+pub use diff::{LatticeDiff, LatticeSnapshot, NodePatch};
+pub use lattice::{GraphMetrics, LatticeNode, LatticeNodeBuilder, NodeId};
+
+/// How batch inserts treat node IDs that already exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Skip duplicates and report them
+    #[default]
+    Skip,
+    /// Reject the whole batch
+    Fail,
+}
+
 /// Lattice engine configuration
 #[derive(Debug, Clone)]
 pub struct LatticeConfig {
     /// Maximum nodes
     pub max_nodes: usize,
+    /// Handling of duplicate node IDs in batch inserts
+    pub duplicate_policy: DuplicatePolicy,
 }
 
 impl Default for LatticeConfig {
     fn default() -> Self {
-        Self { max_nodes: 10000 }
+        Self {
+            max_nodes: 10000,
+            duplicate_policy: DuplicatePolicy::Skip,
+        }
     }
 }
 
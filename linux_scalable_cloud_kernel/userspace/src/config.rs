@@ -1 +1,202 @@
 //! Configuration module
+//!
+//! [`CloudKernelConfig`] is read from a TOML file with
+//! [`CloudKernelConfig::from_file`] or from `CLOUD_KERNEL__*` environment
+//! variables with [`CloudKernelConfig::from_env`], and validated either way.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use shared_core::config::Config;
+use shared_core::{Result, SystemError};
+
+/// Log levels accepted in [`CloudKernelConfig::log_level`]
+pub const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+/// Where cluster nodes keep their data
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// Each node's local disks
+    #[default]
+    Local,
+    /// An NFS export mounted on every node
+    Nfs {
+        /// Where the export is mounted
+        mount_point: PathBuf,
+    },
+    /// An S3-compatible object store
+    ObjectStore {
+        /// Bucket holding the data
+        bucket: String,
+        /// Region of the bucket
+        region: String,
+    },
+}
+
+/// Cloud kernel configuration
+///
+/// Fields missing from a file or the environment take their default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudKernelConfig {
+    /// Enable advanced features
+    pub advanced_features: bool,
+    /// Most nodes the cluster may grow to
+    pub max_nodes: usize,
+    /// Address range of the cluster network, as in `10.0.0.0/16`
+    pub network_cidr: String,
+    /// Where nodes keep their data
+    pub storage_backend: StorageBackend,
+    /// One of [`LOG_LEVELS`]
+    pub log_level: String,
+    /// Whether nodes export telemetry
+    pub enable_telemetry: bool,
+}
+
+impl Default for CloudKernelConfig {
+    fn default() -> Self {
+        Self {
+            advanced_features: false,
+            max_nodes: 64,
+            network_cidr: "10.0.0.0/16".to_string(),
+            storage_backend: StorageBackend::default(),
+            log_level: "info".to_string(),
+            enable_telemetry: false,
+        }
+    }
+}
+
+impl Config for CloudKernelConfig {
+    fn validate(&self) -> Result<()> {
+        if self.max_nodes == 0 {
+            return Err(SystemError::config("max_nodes must be > 0", Some("max_nodes".into())));
+        }
+        if let Err(err) = self.network_cidr.parse::<ipnet::IpNet>() {
+            return Err(SystemError::config(
+                format!("invalid network CIDR '{}': {err}", self.network_cidr),
+                Some("network_cidr".into()),
+            ));
+        }
+        if !LOG_LEVELS.contains(&self.log_level.as_str()) {
+            return Err(SystemError::config(
+                format!(
+                    "log level '{}' is not one of {}",
+                    self.log_level,
+                    LOG_LEVELS.join("|")
+                ),
+                Some("log_level".into()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl CloudKernelConfig {
+    /// Prefix of the environment variables read by
+    /// [`from_env`](Self::from_env), as in `CLOUD_KERNEL__MAX_NODES`
+    pub const ENV_PREFIX: &'static str = "CLOUD_KERNEL";
+
+    /// Load a configuration from the TOML file `path` and validate it
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let config = <Self as Config>::from_file(path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a configuration from the `CLOUD_KERNEL__*` environment
+    /// variables and validate it
+    pub fn from_env() -> Result<Self> {
+        let config = <Self as Config>::from_env(Self::ENV_PREFIX)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_key(config: CloudKernelConfig) -> Option<String> {
+        match config.validate() {
+            Err(SystemError::Config { key, .. }) => key,
+            other => panic!("expected a config error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(CloudKernelConfig::default().validate().is_ok());
+        let ipv6 = CloudKernelConfig {
+            network_cidr: "fd00::/64".to_string(),
+            ..CloudKernelConfig::default()
+        };
+        assert!(ipv6.validate().is_ok());
+
+        let no_nodes = CloudKernelConfig {
+            max_nodes: 0,
+            ..CloudKernelConfig::default()
+        };
+        assert_eq!(invalid_key(no_nodes).as_deref(), Some("max_nodes"));
+        for cidr in ["10.0.0.0", "10.0.0.0/33", "cluster"] {
+            let config = CloudKernelConfig {
+                network_cidr: cidr.to_string(),
+                ..CloudKernelConfig::default()
+            };
+            assert_eq!(invalid_key(config).as_deref(), Some("network_cidr"), "{cidr}");
+        }
+        let loud = CloudKernelConfig {
+            log_level: "verbose".to_string(),
+            ..CloudKernelConfig::default()
+        };
+        assert_eq!(invalid_key(loud).as_deref(), Some("log_level"));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kernel.toml");
+        std::fs::write(
+            &path,
+            r#"
+                max_nodes = 512
+                network_cidr = "172.16.0.0/12"
+                log_level = "debug"
+
+                [storage_backend]
+                type = "object_store"
+                bucket = "kernel-state"
+                region = "eu-west-1"
+            "#,
+        )
+        .unwrap();
+
+        let config = CloudKernelConfig::from_file(&path).unwrap();
+        assert_eq!(config.max_nodes, 512);
+        assert_eq!(
+            config.storage_backend,
+            StorageBackend::ObjectStore {
+                bucket: "kernel-state".to_string(),
+                region: "eu-west-1".to_string(),
+            }
+        );
+        assert!(!config.enable_telemetry);
+
+        std::fs::write(&path, "log_level = \"loud\"").unwrap();
+        assert!(CloudKernelConfig::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("CLOUD_KERNEL__MAX_NODES", "8");
+        std::env::set_var("CLOUD_KERNEL__ENABLE_TELEMETRY", "true");
+        let config = CloudKernelConfig::from_env();
+        std::env::remove_var("CLOUD_KERNEL__MAX_NODES");
+        std::env::remove_var("CLOUD_KERNEL__ENABLE_TELEMETRY");
+
+        let config = config.unwrap();
+        assert_eq!(config.max_nodes, 8);
+        assert!(config.enable_telemetry);
+        assert_eq!(config.log_level, "info");
+    }
+}
@@ -1 +1,352 @@
 //! Core module
+//!
+//! Resource control for containers through cgroup v2. A [`CgroupManager`]
+//! creates cgroups under one root, writing their limits to the `memory.max`,
+//! `cpu.max` and `io.max` interface files, and moves processes into them.
+//! Read the limits back with
+//! [`ResourceGovernorConfig::from_cgroups`](shared_core::ResourceGovernorConfig::from_cgroups)
+//! on [`CgroupHandle::path`].
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use shared_core::{Result, SystemError};
+
+/// Default mount point of the cgroup v2 hierarchy
+pub const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Controllers enabled for the cgroups a [`CgroupManager`] creates
+const CONTROLLERS: &str = "+memory +cpu +io";
+
+/// CPU bandwidth: `quota_micros` of CPU time every `period_micros`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuMax {
+    /// CPU time allowed per period, which may exceed the period on several
+    /// CPUs
+    pub quota_micros: u64,
+    /// Length of a period, from 1000 to 1000000
+    pub period_micros: u64,
+}
+
+/// I/O limits of one block device; `None` leaves a limit unset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoMax {
+    /// Device number, as in `8:0`
+    pub device: String,
+    /// Bytes read per second
+    pub read_bps: Option<u64>,
+    /// Bytes written per second
+    pub write_bps: Option<u64>,
+    /// Read operations per second
+    pub read_iops: Option<u64>,
+    /// Write operations per second
+    pub write_iops: Option<u64>,
+}
+
+/// Limits of a cgroup; `None` and empty fields mean unlimited
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CgroupLimits {
+    /// Memory the cgroup may use, in bytes
+    pub memory_max_bytes: Option<u64>,
+    /// CPU bandwidth
+    pub cpu_max: Option<CpuMax>,
+    /// Per-device I/O limits
+    pub io_max: Vec<IoMax>,
+}
+
+impl CgroupLimits {
+    /// Check the limits are values the kernel accepts
+    pub fn validate(&self) -> Result<()> {
+        if let Some(cpu) = self.cpu_max {
+            if !(1000..=1_000_000).contains(&cpu.period_micros) {
+                return Err(SystemError::validation(
+                    "cpu_max.period_micros",
+                    "period must be between 1000 and 1000000",
+                    Some(cpu.period_micros.to_string()),
+                ));
+            }
+            if cpu.quota_micros < 1000 {
+                return Err(SystemError::validation(
+                    "cpu_max.quota_micros",
+                    "quota must be at least 1000",
+                    Some(cpu.quota_micros.to_string()),
+                ));
+            }
+        }
+        for io in &self.io_max {
+            let valid = io.device.split_once(':').is_some_and(|(major, minor)| {
+                major.parse::<u32>().is_ok() && minor.parse::<u32>().is_ok()
+            });
+            if !valid {
+                return Err(SystemError::validation(
+                    "io_max.device",
+                    "expected a `major:minor` device number",
+                    Some(io.device.clone()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Contents of `memory.max`
+    fn memory_max(&self) -> String {
+        limit(self.memory_max_bytes)
+    }
+
+    /// Contents of `cpu.max`
+    fn cpu_max(&self) -> String {
+        match self.cpu_max {
+            Some(cpu) => format!("{} {}", cpu.quota_micros, cpu.period_micros),
+            None => "max".to_string(),
+        }
+    }
+
+    /// Lines of `io.max`, one per device
+    fn io_max(&self) -> impl Iterator<Item = String> + '_ {
+        self.io_max.iter().map(|io| {
+            format!(
+                "{} rbps={} wbps={} riops={} wiops={}",
+                io.device,
+                limit(io.read_bps),
+                limit(io.write_bps),
+                limit(io.read_iops),
+                limit(io.write_iops)
+            )
+        })
+    }
+}
+
+fn limit(value: Option<u64>) -> String {
+    value.map_or_else(|| "max".to_string(), |value| value.to_string())
+}
+
+/// Creates and removes cgroups under a cgroup v2 hierarchy
+#[derive(Debug, Clone)]
+pub struct CgroupManager {
+    cgroup_root: PathBuf,
+}
+
+impl CgroupManager {
+    /// Manage cgroups under `cgroup_root`, which must be an existing
+    /// directory
+    pub fn new(cgroup_root: impl Into<PathBuf>) -> Result<Self> {
+        let cgroup_root = cgroup_root.into();
+        if !cgroup_root.is_dir() {
+            return Err(SystemError::not_found(
+                "cgroup root",
+                cgroup_root.display().to_string(),
+            ));
+        }
+        Ok(Self { cgroup_root })
+    }
+
+    /// Directory the cgroups are created in
+    pub fn cgroup_root(&self) -> &Path {
+        &self.cgroup_root
+    }
+
+    /// Create the cgroup `name` with `limits`
+    ///
+    /// The memory, cpu and io controllers are enabled in the root's
+    /// `cgroup.subtree_control` first when the root has one. A cgroup that
+    /// already exists is an error, so two handles never own one directory.
+    pub fn create_cgroup(&self, name: &str, limits: CgroupLimits) -> Result<CgroupHandle> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(SystemError::validation(
+                "name",
+                "cgroup names must be a single path component",
+                Some(name.to_string()),
+            ));
+        }
+        limits.validate()?;
+
+        let subtree_control = self.cgroup_root.join("cgroup.subtree_control");
+        if subtree_control.exists() {
+            write(&subtree_control, CONTROLLERS)?;
+        }
+        let path = self.cgroup_root.join(name);
+        if path.exists() {
+            return Err(SystemError::AlreadyExists {
+                resource_type: "cgroup".to_string(),
+                identifier: name.to_string(),
+            });
+        }
+        fs::create_dir(&path)
+            .map_err(|e| SystemError::io(e, format!("creating {}", path.display())))?;
+        // From here on the handle removes the directory if a write fails
+        let handle = CgroupHandle {
+            name: name.to_string(),
+            path,
+            removed: false,
+        };
+        write(&handle.path.join("memory.max"), &limits.memory_max())?;
+        write(&handle.path.join("cpu.max"), &limits.cpu_max())?;
+        // The kernel takes one device per write
+        for line in limits.io_max() {
+            append(&handle.path.join("io.max"), &line)?;
+        }
+        tracing::debug!("Created cgroup {} with {:?}", name, limits);
+        Ok(handle)
+    }
+
+    /// Move the process `pid` into the cgroup of `handle`
+    pub fn move_process(&self, handle: &CgroupHandle, pid: u32) -> Result<()> {
+        write(&handle.path.join("cgroup.procs"), &pid.to_string())
+    }
+
+    /// Remove the cgroup of `handle`
+    ///
+    /// Fails while processes are still in the cgroup; they must exit or be
+    /// moved elsewhere first.
+    pub fn destroy_cgroup(&self, mut handle: CgroupHandle) -> Result<()> {
+        let result = handle.remove();
+        // A failed removal is reported here, not retried on drop
+        handle.removed = true;
+        result
+    }
+}
+
+impl Default for CgroupManager {
+    fn default() -> Self {
+        Self {
+            cgroup_root: PathBuf::from(DEFAULT_CGROUP_ROOT),
+        }
+    }
+}
+
+/// A cgroup created by [`CgroupManager::create_cgroup`]
+///
+/// Dropping the handle removes the cgroup, logging a warning if it cannot.
+#[derive(Debug)]
+pub struct CgroupHandle {
+    name: String,
+    path: PathBuf,
+    removed: bool,
+}
+
+impl CgroupHandle {
+    /// Name of the cgroup
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Directory of the cgroup
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn remove(&mut self) -> Result<()> {
+        let context = || format!("removing {}", self.path.display());
+        match fs::remove_dir(&self.path) {
+            Ok(()) => {},
+            // Outside cgroupfs the interface files are ordinary files
+            Err(e) if e.raw_os_error() == Some(libc::ENOTEMPTY) => {
+                fs::remove_dir_all(&self.path).map_err(|e| SystemError::io(e, context()))?;
+            },
+            Err(e) => return Err(SystemError::io(e, context())),
+        }
+        self.removed = true;
+        Ok(())
+    }
+}
+
+impl Drop for CgroupHandle {
+    fn drop(&mut self) {
+        if !self.removed {
+            if let Err(err) = self.remove() {
+                tracing::warn!("Failed to remove cgroup {}: {}", self.name, err);
+            }
+        }
+    }
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).map_err(|e| SystemError::io(e, format!("writing {}", path.display())))
+}
+
+fn append(path: &Path, line: &str) -> Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(format!("{line}\n").as_bytes()))
+        .map_err(|e| SystemError::io(e, format!("writing {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use shared_core::ResourceGovernorConfig;
+
+    use super::*;
+
+    fn limits() -> CgroupLimits {
+        CgroupLimits {
+            memory_max_bytes: Some(64 * 1024 * 1024),
+            cpu_max: Some(CpuMax {
+                quota_micros: 25_000,
+                period_micros: 100_000,
+            }),
+            io_max: vec![IoMax {
+                device: "8:0".to_string(),
+                write_bps: Some(1 << 20),
+                read_iops: Some(500),
+                ..IoMax::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_cgroup_lifecycle() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = CgroupManager::new(root.path()).unwrap();
+
+        let handle = manager.create_cgroup("web", limits()).unwrap();
+        let read = |file: &str| fs::read_to_string(handle.path().join(file)).unwrap();
+        assert_eq!(read("memory.max"), "67108864");
+        assert_eq!(read("cpu.max"), "25000 100000");
+        assert_eq!(read("io.max"), "8:0 rbps=max wbps=1048576 riops=500 wiops=max\n");
+
+        let config = ResourceGovernorConfig::from_cgroups(handle.path()).unwrap();
+        assert_eq!(config.ram_cap_bytes, Some(64 * 1024 * 1024));
+        assert_eq!(config.cpu_cap_percent, Some(25));
+        assert_eq!(config.io_ops_per_second, Some(500));
+
+        manager.move_process(&handle, 4242).unwrap();
+        assert_eq!(read("cgroup.procs"), "4242");
+        assert!(manager.create_cgroup("web", CgroupLimits::default()).is_err());
+
+        let path = handle.path().to_path_buf();
+        manager.destroy_cgroup(handle).unwrap();
+        assert!(!path.exists());
+
+        // Dropping a handle removes its cgroup too
+        let handle = manager.create_cgroup("batch", CgroupLimits::default()).unwrap();
+        let path = handle.path().to_path_buf();
+        assert_eq!(fs::read_to_string(path.join("cpu.max")).unwrap(), "max");
+        drop(handle);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_invalid_cgroups_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = CgroupManager::new(root.path()).unwrap();
+        for name in ["", "..", "a/b"] {
+            assert!(manager.create_cgroup(name, CgroupLimits::default()).is_err(), "{name}");
+        }
+
+        let mut bad = limits();
+        bad.cpu_max = Some(CpuMax {
+            quota_micros: 25_000,
+            period_micros: 10,
+        });
+        assert!(manager.create_cgroup("a", bad).is_err());
+        let mut bad = limits();
+        bad.io_max[0].device = "sda".to_string();
+        assert!(manager.create_cgroup("a", bad).is_err());
+        assert!(!root.path().join("a").exists());
+
+        assert!(CgroupManager::new(root.path().join("missing")).is_err());
+    }
+}
@@ -5,28 +5,13 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
-
 pub mod api;
 pub mod config;
 pub mod core;
 pub mod monitoring;
 pub mod orchestration;
 
-/// Cloud kernel configuration
-#[derive(Debug, Clone)]
-pub struct CloudKernelConfig {
-    /// Enable advanced features
-    pub advanced_features: bool,
-}
-
-impl Default for CloudKernelConfig {
-    fn default() -> Self {
-        Self {
-            advanced_features: false,
-        }
-    }
-}
+pub use config::{CloudKernelConfig, StorageBackend};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,272 @@
+//! Throughput and latency of the executor's scheduling policies
+//!
+//! Four workloads run on a [`WorkStealingPool`] under each
+//! [`SchedulingPolicy`]: uniform busy tasks, bimodal tasks (90% short, 10%
+//! long), CPU-bound fibonacci and I/O-bound sleeps. Criterion reports
+//! throughput; the submit-to-completion latency percentiles of each are
+//! printed after.
+//!
+//! The run then checks that work stealing keeps within 5% of the shared
+//! queue's throughput on the uniform workload and beats it by 20% on the
+//! bimodal one, exiting with an error if not. The check needs a core per
+//! worker and is skipped on smaller machines, whose numbers say little
+//! about scheduling.
+//!
+//! ```text
+//! cargo bench -p parallel_architecture_framework --bench executor
+//! ```
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{Criterion, Throughput};
+use parallel_architecture_framework::{
+    FrameworkConfig, SchedulingPolicy, ShutdownPolicy, WorkStealingPool,
+};
+use tokio::runtime::Runtime;
+
+const WORKERS: usize = 4;
+
+/// Runs of each workload the latencies and the throughput check take
+const ROUNDS: usize = 5;
+
+#[derive(Debug, Clone, Copy)]
+enum Workload {
+    Uniform,
+    Bimodal,
+    Fibonacci,
+    Io,
+}
+
+impl Workload {
+    const ALL: [Workload; 4] = [Self::Uniform, Self::Bimodal, Self::Fibonacci, Self::Io];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Uniform => "uniform",
+            Self::Bimodal => "bimodal",
+            Self::Fibonacci => "fibonacci",
+            Self::Io => "io",
+        }
+    }
+
+    fn tasks(self) -> usize {
+        match self {
+            Self::Uniform | Self::Bimodal => 10_000,
+            Self::Fibonacci => 1_000,
+            Self::Io => 200,
+        }
+    }
+
+    /// Run task `i` on a pool worker, blocking it for I/O
+    fn run_blocking(self, i: usize, runtime: &tokio::runtime::Handle) {
+        match self {
+            Self::Io => runtime.block_on(async {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }),
+            _ => self.compute(i),
+        }
+    }
+
+    fn compute(self, i: usize) {
+        match self {
+            Self::Uniform => spin(Duration::from_micros(10)),
+            // Every tenth task is long
+            Self::Bimodal if i % 10 == 9 => spin(Duration::from_micros(200)),
+            Self::Bimodal => spin(Duration::from_micros(5)),
+            Self::Fibonacci => {
+                black_box(fibonacci(black_box(20)));
+            },
+            Self::Io => unreachable!("I/O tasks do not compute"),
+        }
+    }
+}
+
+fn spin(duration: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        std::hint::spin_loop();
+    }
+}
+
+fn fibonacci(n: u64) -> u64 {
+    if n < 2 {
+        n
+    } else {
+        fibonacci(n - 1) + fibonacci(n - 2)
+    }
+}
+
+const POLICIES: [SchedulingPolicy; 3] = [
+    SchedulingPolicy::Fifo,
+    SchedulingPolicy::WorkStealing { steal_threshold: 2 },
+    SchedulingPolicy::Cooperative,
+];
+
+fn policy_name(policy: SchedulingPolicy) -> &'static str {
+    match policy {
+        SchedulingPolicy::Fifo => "fifo",
+        SchedulingPolicy::WorkStealing { .. } => "work_stealing",
+        SchedulingPolicy::Cooperative => "cooperative",
+    }
+}
+
+/// A pool scheduling its tasks by one policy, and the runtime I/O tasks
+/// sleep on
+struct Runner {
+    pool: WorkStealingPool,
+    runtime: Arc<Runtime>,
+}
+
+impl Runner {
+    fn new(scheduling: SchedulingPolicy, runtime: &Arc<Runtime>) -> Self {
+        let config = FrameworkConfig {
+            workers: WORKERS,
+            scheduling,
+            ..FrameworkConfig::default()
+        };
+        Self {
+            pool: WorkStealingPool::new(&config).expect("valid pool configuration"),
+            runtime: Arc::clone(runtime),
+        }
+    }
+
+    /// Run every task of `workload`, returning how long it took and each
+    /// task's submit-to-completion latency in nanoseconds
+    fn run(&self, workload: Workload) -> (Duration, Vec<u64>) {
+        let latencies: Arc<Vec<AtomicU64>> =
+            Arc::new((0..workload.tasks()).map(|_| AtomicU64::new(0)).collect());
+        let start = Instant::now();
+        for i in 0..workload.tasks() {
+            let submitted = Instant::now();
+            let latencies = Arc::clone(&latencies);
+            let runtime = self.runtime.handle().clone();
+            self.pool.spawn(move || {
+                workload.run_blocking(i, &runtime);
+                latencies[i].store(elapsed_nanos(submitted), Ordering::Relaxed);
+            });
+        }
+        self.pool.wait_idle();
+        let elapsed = start.elapsed();
+        (elapsed, latencies.iter().map(|latency| latency.load(Ordering::Relaxed)).collect())
+    }
+
+    fn shutdown(self) {
+        self.pool.shutdown(ShutdownPolicy::Drain);
+    }
+}
+
+fn elapsed_nanos(since: Instant) -> u64 {
+    u64::try_from(since.elapsed().as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn runtime() -> Arc<Runtime> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(WORKERS)
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    Arc::new(runtime)
+}
+
+fn bench_policies(c: &mut Criterion) {
+    let runtime = runtime();
+    for workload in Workload::ALL {
+        let mut group = c.benchmark_group(workload.name());
+        group.throughput(Throughput::Elements(workload.tasks() as u64));
+        group.sample_size(10);
+        for policy in POLICIES {
+            let runner = Runner::new(policy, &runtime);
+            group.bench_function(policy_name(policy), |b| b.iter(|| runner.run(workload)));
+            runner.shutdown();
+        }
+        group.finish();
+    }
+}
+
+/// Tasks per second of the fastest of [`ROUNDS`] runs, and the p50 and p99
+/// latencies over all of them
+struct Measurement {
+    throughput: f64,
+    p50: Duration,
+    p99: Duration,
+}
+
+fn measure(policy: SchedulingPolicy, workload: Workload, runtime: &Arc<Runtime>) -> Measurement {
+    let runner = Runner::new(policy, runtime);
+    let mut fastest = Duration::MAX;
+    let mut latencies = Vec::with_capacity(ROUNDS * workload.tasks());
+    for _ in 0..ROUNDS {
+        let (elapsed, round) = runner.run(workload);
+        fastest = fastest.min(elapsed);
+        latencies.extend(round);
+    }
+    runner.shutdown();
+    latencies.sort_unstable();
+    let percentile = |p: usize| Duration::from_nanos(latencies[(latencies.len() - 1) * p / 100]);
+    Measurement {
+        throughput: workload.tasks() as f64 / fastest.as_secs_f64(),
+        p50: percentile(50),
+        p99: percentile(99),
+    }
+}
+
+/// Print the throughput and latencies of every policy and workload, and
+/// check the throughput of work stealing against the shared queue
+fn report() -> Result<(), String> {
+    let runtime = runtime();
+    let mut failures = Vec::new();
+    for workload in Workload::ALL {
+        let measurements: Vec<_> =
+            POLICIES.iter().map(|&policy| measure(policy, workload, &runtime)).collect();
+        for (&policy, m) in POLICIES.iter().zip(&measurements) {
+            println!(
+                "{}/{}: {:.0} tasks/s, p50 {:?}, p99 {:?}",
+                workload.name(),
+                policy_name(policy),
+                m.throughput,
+                m.p50,
+                m.p99
+            );
+        }
+
+        let min_ratio = match workload {
+            Workload::Uniform => 0.95,
+            Workload::Bimodal => 1.2,
+            Workload::Fibonacci | Workload::Io => continue,
+        };
+        let ratio = measurements[1].throughput / measurements[0].throughput;
+        if ratio < min_ratio {
+            failures.push(format!(
+                "{}: work stealing throughput is {ratio:.2}x the shared queue's, \
+                 expected at least {min_ratio:.2}x",
+                workload.name()
+            ));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("\n"))
+    }
+}
+
+fn main() {
+    let mut criterion = Criterion::default().configure_from_args();
+    bench_policies(&mut criterion);
+    criterion.final_summary();
+
+    // `cargo test --benches` runs each benchmark once, to see it works
+    if std::env::args().any(|arg| arg == "--test") {
+        return;
+    }
+    let checked = report();
+    if num_cpus::get() < WORKERS {
+        println!("skipped the throughput check: it needs {WORKERS} cores");
+    } else if let Err(failures) = checked {
+        eprintln!("{failures}");
+        std::process::exit(1);
+    }
+}
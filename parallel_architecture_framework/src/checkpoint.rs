@@ -0,0 +1,277 @@
+//! Checkpoints of graph runs
+//!
+//! [`Executor::resume`](crate::executor::Executor::resume) saves a
+//! checkpoint of every task with a [`Task::fingerprint`] as it succeeds,
+//! and restores a task from its checkpoint instead of running it when
+//! nothing it depends on changed. A checkpoint is keyed by the task's
+//! fingerprint and, for each dependency, its key and output hash, so
+//! changing a task reruns it and everything downstream of it.
+//!
+//! Checkpoints carry a format version and a checksum; one that fails to
+//! verify or decode, or was written in another format, is ignored with a
+//! warning and the task runs.
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+
+use crate::scheduler::Task;
+
+/// Version of the layout of checkpoints, part of every key
+pub const CHECKPOINT_FORMAT: u32 = 1;
+
+/// How a checkpoint keeps the output of a task, see [`Task::save_output`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SavedOutput {
+    /// The output, serialized
+    Inline(serde_json::Value),
+    /// Where the task put the output, such as a file path or URL
+    External(String),
+}
+
+/// Storage of checkpoints by task identifier
+///
+/// Stores only keep bytes; checking them is up to the executor, so a store
+/// may lose or mangle checkpoints without breaking a run.
+pub trait CheckpointStore: Send + Sync {
+    /// Checkpoint stored for `task_id`, if any
+    fn get(&self, task_id: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Store `value` for `task_id`, replacing any checkpoint
+    fn put(&self, task_id: &str, value: &[u8]) -> Result<()>;
+}
+
+/// Keeps each checkpoint in a file of a directory
+#[derive(Debug, Clone)]
+pub struct DirectoryCheckpointStore {
+    dir: PathBuf,
+}
+
+impl DirectoryCheckpointStore {
+    /// Keep checkpoints in `dir`, created if missing
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| SystemError::io(e, format!("creating {}", dir.display())))?;
+        Ok(Self { dir })
+    }
+
+    /// The file of `task_id`, named by hash as identifiers may not be
+    /// valid file names
+    fn path(&self, task_id: &str) -> PathBuf {
+        self.dir.join(blake3::hash(task_id.as_bytes()).to_hex().as_str())
+    }
+}
+
+impl CheckpointStore for DirectoryCheckpointStore {
+    fn get(&self, task_id: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(task_id)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(SystemError::io(e, format!("reading checkpoint of {task_id}"))),
+        }
+    }
+
+    fn put(&self, task_id: &str, value: &[u8]) -> Result<()> {
+        // Readers see the old checkpoint or the new one, never half of one
+        let path = self.path(task_id);
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, value)
+            .and_then(|()| fs::rename(&temporary, path))
+            .map_err(|e| SystemError::io(e, format!("writing checkpoint of {task_id}")))
+    }
+}
+
+/// Keeps checkpoints in a sled database
+#[cfg(feature = "sled-checkpoints")]
+#[derive(Debug, Clone)]
+pub struct SledCheckpointStore {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled-checkpoints")]
+impl SledCheckpointStore {
+    /// Open the database at `path`, created if missing
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let db = sled::open(path)
+            .map_err(|e| SystemError::io(e, format!("opening {}", path.display())))?;
+        Ok(Self { db })
+    }
+}
+
+#[cfg(feature = "sled-checkpoints")]
+impl CheckpointStore for SledCheckpointStore {
+    fn get(&self, task_id: &str) -> Result<Option<Vec<u8>>> {
+        let value = self.db.get(task_id).map_err(|e| SystemError::io(e, "reading checkpoint"))?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    fn put(&self, task_id: &str, value: &[u8]) -> Result<()> {
+        self.db.insert(task_id, value).map_err(|e| SystemError::io(e, "writing checkpoint"))?;
+        self.db.flush().map_err(|e| SystemError::io(e, "writing checkpoint"))?;
+        Ok(())
+    }
+}
+
+/// What is stored for a task
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    format: u32,
+    task: String,
+    /// [`key`] of the task when it ran
+    key: String,
+    output_hash: String,
+    output: SavedOutput,
+}
+
+/// A task's key and output hash, which its dependents' keys cover
+#[derive(Debug, Clone)]
+pub(crate) struct Lineage {
+    pub(crate) key: String,
+    pub(crate) output_hash: String,
+}
+
+/// Key of `task` given the lineage of each of its dependencies, or `None`
+/// if the task has no fingerprint or a dependency has no lineage
+pub(crate) fn key(task: &dyn Task, dependencies: &[(&str, Option<&Lineage>)]) -> Option<String> {
+    let fingerprint = task.fingerprint()?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&CHECKPOINT_FORMAT.to_le_bytes());
+    for part in [task.id(), fingerprint.as_str()] {
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    for &(id, lineage) in dependencies {
+        let lineage = lineage?;
+        for part in [id, &lineage.key, &lineage.output_hash] {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+    }
+    Some(hasher.finalize().to_hex().to_string())
+}
+
+/// Output saved for `task` under `key`, with its hash, if there is a sound
+/// checkpoint of it
+pub(crate) fn load(
+    store: &dyn CheckpointStore,
+    task: &str,
+    key: &str,
+) -> Option<(SavedOutput, String)> {
+    let entry = match store.get(task) {
+        Ok(entry) => entry?,
+        Err(err) => {
+            tracing::warn!("Failed to read checkpoint of {}: {}", task, err);
+            return None;
+        },
+    };
+    let Some(checkpoint) = decode(&entry) else {
+        tracing::warn!("Ignoring corrupt checkpoint of {}", task);
+        return None;
+    };
+    if checkpoint.format != CHECKPOINT_FORMAT || checkpoint.task != task {
+        tracing::warn!(
+            "Ignoring checkpoint of {} in format {}, expected {}",
+            task,
+            checkpoint.format,
+            CHECKPOINT_FORMAT
+        );
+        return None;
+    }
+    (checkpoint.key == key).then_some((checkpoint.output, checkpoint.output_hash))
+}
+
+/// Save `output` for `task` under `key`, returning its hash; failures only
+/// cost a rerun later
+pub(crate) fn save(
+    store: &dyn CheckpointStore,
+    task: &str,
+    key: String,
+    output: SavedOutput,
+) -> Option<String> {
+    let output_hash = match serde_json::to_vec(&output) {
+        Ok(bytes) => blake3::hash(&bytes).to_hex().to_string(),
+        Err(err) => {
+            tracing::warn!("Failed to save checkpoint of {}: {}", task, err);
+            return None;
+        },
+    };
+    let checkpoint = Checkpoint {
+        format: CHECKPOINT_FORMAT,
+        task: task.to_string(),
+        key,
+        output_hash: output_hash.clone(),
+        output,
+    };
+    let result = serde_json::to_vec(&checkpoint).map_err(SystemError::from).and_then(|payload| {
+        let mut entry = blake3::hash(&payload).as_bytes().to_vec();
+        entry.extend(payload);
+        store.put(task, &entry)
+    });
+    match result {
+        Ok(()) => Some(output_hash),
+        Err(err) => {
+            tracing::warn!("Failed to save checkpoint of {}: {}", task, err);
+            None
+        },
+    }
+}
+
+/// Checkpoint of an entry: a BLAKE3 checksum, then the JSON payload
+fn decode(entry: &[u8]) -> Option<Checkpoint> {
+    if entry.len() < blake3::OUT_LEN {
+        return None;
+    }
+    let (checksum, payload) = entry.split_at(blake3::OUT_LEN);
+    if blake3::hash(payload).as_bytes() != checksum {
+        return None;
+    }
+    serde_json::from_slice(payload).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DirectoryCheckpointStore::new(dir.path().join("checkpoints")).unwrap();
+        assert_eq!(store.get("a/b").unwrap(), None);
+        let output = SavedOutput::Inline(serde_json::json!([1, 2]));
+        let hash = save(&store, "a/b", "key".into(), output.clone()).unwrap();
+        assert_eq!(load(&store, "a/b", "key"), Some((output, hash)));
+        assert_eq!(load(&store, "a/b", "other key"), None);
+
+        // Corrupt and other-format checkpoints are ignored
+        let mut entry = store.get("a/b").unwrap().unwrap();
+        *entry.last_mut().unwrap() ^= 1;
+        store.put("a/b", &entry).unwrap();
+        assert_eq!(load(&store, "a/b", "key"), None);
+        let stale = Checkpoint {
+            format: CHECKPOINT_FORMAT + 1,
+            task: "a/b".into(),
+            key: "key".into(),
+            output_hash: String::new(),
+            output: SavedOutput::External("s3://bucket/a".into()),
+        };
+        let payload = serde_json::to_vec(&stale).unwrap();
+        let mut entry = blake3::hash(&payload).as_bytes().to_vec();
+        entry.extend(payload);
+        store.put("a/b", &entry).unwrap();
+        assert_eq!(load(&store, "a/b", "key"), None);
+    }
+
+    #[cfg(feature = "sled-checkpoints")]
+    #[test]
+    fn test_sled_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SledCheckpointStore::open(dir.path().join("checkpoints")).unwrap();
+        let output = SavedOutput::External("/data/out.parquet".into());
+        let hash = save(&store, "t", "key".into(), output.clone()).unwrap();
+        assert_eq!(load(&store, "t", "key"), Some((output, hash)));
+    }
+}
@@ -1 +1,427 @@
 //! Communication module
+//!
+//! Streams between the tasks of a [`TaskGraph`](crate::scheduler::TaskGraph).
+//! [`TaskGraph::connect`](crate::scheduler::TaskGraph::connect) declares a
+//! bounded channel from a producer task to a consumer task, which the
+//! executor then starts together. The producer sends items through the
+//! [`Sender`] of [`TaskContext::sender`](crate::scheduler::TaskContext::sender),
+//! waiting while the channel is full; the consumer takes them from the
+//! [`Receiver`] of [`TaskContext::receiver`](crate::scheduler::TaskContext::receiver).
+//! The channel closes when the producer's run ends: the consumer then gets
+//! [`StreamItem::EndOfStream`], or [`StreamItem::UpstreamFailed`] if the
+//! producer failed or was cancelled.
+//!
+//! [`fan_out`] and [`fan_in`] spread one stream over several and merge
+//! several into one.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use futures::stream::{self, StreamExt};
+use parking_lot::Mutex;
+use shared_core::{Result, SystemError};
+use tokio::sync::mpsc;
+
+/// What a [`Receiver`] gets from its channel
+#[derive(Debug)]
+pub enum StreamItem<T> {
+    /// An item the producer sent
+    Item(T),
+    /// The producer succeeded and every item it sent has been received
+    EndOfStream,
+    /// The producer failed or was cancelled after sending the items
+    /// received
+    UpstreamFailed(SystemError),
+}
+
+/// State shared by the two ends of a channel
+#[derive(Debug)]
+struct ChannelState {
+    producer: String,
+    consumer: String,
+    /// Set when the producer's run ends: `None` if it succeeded, else why
+    /// it did not
+    outcome: OnceLock<Option<String>>,
+    /// Most items queued at once
+    peak: AtomicUsize,
+}
+
+/// Sending end of a channel, held by the producer
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: mpsc::Sender<T>,
+    state: Arc<ChannelState>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<T: Send> Sender<T> {
+    /// Send `item`, waiting while the channel is full
+    ///
+    /// Fails with a `Concurrency` error if the consumer is done with the
+    /// channel.
+    pub async fn send(&self, item: T) -> Result<()> {
+        self.inner.send(item).await.map_err(|_| SystemError::Concurrency {
+            message: format!("consumer `{}` closed the stream", self.state.consumer),
+            thread_id: None,
+        })?;
+        self.state.peak.fetch_max(self.queued(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Items the channel holds at most
+    pub fn capacity(&self) -> usize {
+        self.inner.max_capacity()
+    }
+
+    /// Items sent but not yet received
+    pub fn queued(&self) -> usize {
+        self.inner.max_capacity() - self.inner.capacity()
+    }
+
+    /// Identifier of the consuming task
+    pub fn consumer(&self) -> &str {
+        &self.state.consumer
+    }
+}
+
+/// Receiving end of a channel, held by the consumer
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: mpsc::Receiver<T>,
+    state: Arc<ChannelState>,
+}
+
+impl<T> Receiver<T> {
+    /// Take the next item, waiting until one is sent or the producer's run
+    /// ends; once it has, every call returns how it ended
+    pub async fn recv(&mut self) -> StreamItem<T> {
+        if let Some(item) = self.inner.recv().await {
+            return StreamItem::Item(item);
+        }
+        // Senders are all dropped only after the outcome is set
+        match self.state.outcome.get() {
+            Some(None) => StreamItem::EndOfStream,
+            Some(Some(reason)) => StreamItem::UpstreamFailed(SystemError::Concurrency {
+                message: format!("upstream task `{}` failed: {reason}", self.state.producer),
+                thread_id: None,
+            }),
+            None => StreamItem::UpstreamFailed(SystemError::Concurrency {
+                message: format!("upstream task `{}` stopped", self.state.producer),
+                thread_id: None,
+            }),
+        }
+    }
+
+    /// Items sent but not yet received
+    pub fn queued(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Most items the channel held at once
+    pub fn peak(&self) -> usize {
+        self.state.peak.load(Ordering::Relaxed)
+    }
+
+    /// Items the channel holds at most
+    pub fn capacity(&self) -> usize {
+        self.inner.max_capacity()
+    }
+
+    /// Identifier of the producing task
+    pub fn producer(&self) -> &str {
+        &self.state.producer
+    }
+}
+
+/// Send every item of `input` to each of `outputs` in turn, returning how
+/// many were sent once `input` ends
+///
+/// Fails with the `UpstreamFailed` error if `input` does, with a
+/// `Validation` error if there are no `outputs`, and as [`Sender::send`]
+/// does.
+pub async fn fan_out<T: Send>(mut input: Receiver<T>, outputs: &[Sender<T>]) -> Result<u64> {
+    if outputs.is_empty() {
+        return Err(SystemError::validation("outputs", "nothing to fan out to", None));
+    }
+    let mut sent = 0;
+    for output in outputs.iter().cycle() {
+        match input.recv().await {
+            StreamItem::Item(item) => output.send(item).await?,
+            StreamItem::EndOfStream => break,
+            StreamItem::UpstreamFailed(err) => return Err(err),
+        }
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+/// Send the items of all `inputs` to `output` as they arrive, returning how
+/// many were sent once every input has ended
+///
+/// Fails with the first `UpstreamFailed` error of an input, and as
+/// [`Sender::send`] does.
+pub async fn fan_in<T: Send + 'static>(
+    inputs: Vec<Receiver<T>>,
+    output: &Sender<T>,
+) -> Result<u64> {
+    let streams = inputs.into_iter().map(|input| {
+        stream::unfold(Some(input), |input| async move {
+            let mut input = input?;
+            match input.recv().await {
+                StreamItem::EndOfStream => None,
+                StreamItem::Item(item) => Some((Ok(item), Some(input))),
+                StreamItem::UpstreamFailed(err) => Some((Err(err), None)),
+            }
+        })
+        .boxed()
+    });
+    let mut merged = stream::select_all(streams);
+    let mut sent = 0;
+    while let Some(item) = merged.next().await {
+        output.send(item?).await?;
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+/// A channel declared with [`TaskGraph::connect`](crate::scheduler::TaskGraph::connect)
+pub(crate) struct Connection {
+    pub(crate) producer: String,
+    pub(crate) consumer: String,
+    pub(crate) capacity: usize,
+    open: fn(&Connection) -> Endpoints,
+}
+
+impl Connection {
+    pub(crate) fn new<T: Send + 'static>(producer: &str, consumer: &str, capacity: usize) -> Self {
+        Self {
+            producer: producer.to_string(),
+            consumer: consumer.to_string(),
+            capacity,
+            open: open::<T>,
+        }
+    }
+
+    /// Create the channel for a run
+    pub(crate) fn open(&self) -> Endpoints {
+        (self.open)(self)
+    }
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("producer", &self.producer)
+            .field("consumer", &self.consumer)
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The two ends of a channel, type-erased, and its [`Closer`]
+pub(crate) struct Endpoints {
+    pub(crate) sender: Arc<dyn Any + Send + Sync>,
+    pub(crate) receiver: Box<dyn Any + Send>,
+    pub(crate) closer: Closer,
+}
+
+fn open<T: Send + 'static>(connection: &Connection) -> Endpoints {
+    let (sender, receiver) = mpsc::channel::<T>(connection.capacity);
+    let state = Arc::new(ChannelState {
+        producer: connection.producer.clone(),
+        consumer: connection.consumer.clone(),
+        outcome: OnceLock::new(),
+        peak: AtomicUsize::new(0),
+    });
+    let sender = Sender {
+        inner: sender,
+        state: Arc::clone(&state),
+    };
+    Endpoints {
+        closer: Closer {
+            state: Arc::clone(&state),
+            _sender: Box::new(sender.clone()),
+        },
+        sender: Arc::new(sender),
+        receiver: Box::new(Receiver {
+            inner: receiver,
+            state,
+        }),
+    }
+}
+
+/// Keeps a channel open until its producer's run ends
+///
+/// It holds a sender of its own, so the consumer cannot see the channel
+/// close before the outcome is set; dropped without
+/// [`close`](Self::close), as when the producer is aborted, it records a
+/// cancellation.
+pub(crate) struct Closer {
+    state: Arc<ChannelState>,
+    _sender: Box<dyn Any + Send>,
+}
+
+impl Closer {
+    /// Close the channel once the producer succeeded, or failed with `error`
+    pub(crate) fn close(self, error: Option<&SystemError>) {
+        let _ = self.state.outcome.set(error.map(ToString::to_string));
+    }
+}
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        let _ = self.state.outcome.set(Some("task cancelled".to_string()));
+    }
+}
+
+/// Channel ends of a running task
+#[derive(Clone, Default)]
+pub(crate) struct Streams {
+    /// A `Sender<T>` by consumer
+    pub(crate) senders: HashMap<String, Arc<dyn Any + Send + Sync>>,
+    /// A `Receiver<T>` by producer, until the task takes it
+    pub(crate) receivers: Arc<Mutex<HashMap<String, Box<dyn Any + Send>>>>,
+}
+
+impl Streams {
+    pub(crate) fn sender<T: Send + 'static>(&self, consumer: &str) -> Result<Sender<T>> {
+        let sender = self
+            .senders
+            .get(consumer)
+            .ok_or_else(|| SystemError::not_found("stream", consumer))?;
+        sender.downcast_ref::<Sender<T>>().cloned().ok_or_else(|| mismatch::<T>(consumer))
+    }
+
+    pub(crate) fn receiver<T: Send + 'static>(&self, producer: &str) -> Result<Receiver<T>> {
+        let mut receivers = self.receivers.lock();
+        match receivers.get(producer) {
+            None => return Err(SystemError::not_found("stream", producer)),
+            Some(receiver) if !receiver.is::<Receiver<T>>() => return Err(mismatch::<T>(producer)),
+            Some(_) => {},
+        }
+        let receiver = receivers.remove(producer).and_then(|r| r.downcast().ok());
+        receiver.map(|receiver| *receiver).ok_or_else(|| mismatch::<T>(producer))
+    }
+}
+
+impl std::fmt::Debug for Streams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let receivers: Vec<String> = self.receivers.lock().keys().cloned().collect();
+        f.debug_struct("Streams")
+            .field("senders", &self.senders.keys().collect::<Vec<_>>())
+            .field("receivers", &receivers)
+            .finish()
+    }
+}
+
+fn mismatch<T>(task_id: &str) -> SystemError {
+    SystemError::validation(
+        "stream",
+        format!("stream does not carry `{}`", std::any::type_name::<T>()),
+        Some(task_id.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel<T: Send + 'static>(capacity: usize) -> (Sender<T>, Receiver<T>, Closer) {
+        let endpoints = Connection::new::<T>("producer", "consumer", capacity).open();
+        let sender = endpoints.sender.downcast_ref::<Sender<T>>().unwrap().clone();
+        let receiver = *endpoints.receiver.downcast::<Receiver<T>>().ok().unwrap();
+        (sender, receiver, endpoints.closer)
+    }
+
+    #[tokio::test]
+    async fn test_channel_closes_with_producer() {
+        let (sender, mut receiver, closer) = channel::<u32>(2);
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!((sender.queued(), receiver.peak()), (2, 2));
+        // Full: the third item waits for room
+        let third = tokio::spawn({
+            let sender = sender.clone();
+            async move { sender.send(3).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!third.is_finished());
+        assert!(matches!(receiver.recv().await, StreamItem::Item(1)));
+        third.await.unwrap().unwrap();
+        drop(sender);
+        closer.close(None);
+        assert!(matches!(receiver.recv().await, StreamItem::Item(2)));
+        assert!(matches!(receiver.recv().await, StreamItem::Item(3)));
+        assert!(matches!(receiver.recv().await, StreamItem::EndOfStream));
+        assert!(matches!(receiver.recv().await, StreamItem::EndOfStream));
+        assert!(receiver.peak() <= receiver.capacity());
+
+        // Aborted producers fail their streams
+        let (sender, mut receiver, closer) = channel::<u32>(2);
+        drop((sender, closer));
+        assert!(matches!(receiver.recv().await, StreamItem::UpstreamFailed(_)));
+
+        let (sender, receiver, _closer) = channel::<u32>(2);
+        drop(receiver);
+        assert!(matches!(sender.send(1).await, Err(SystemError::Concurrency { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_and_in() {
+        let (source, input, source_closer) = channel::<u32>(4);
+        let (outputs, branches): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sender, receiver, closer) = channel::<u32>(4);
+                ((sender, closer), receiver)
+            })
+            .unzip();
+        let (outputs, closers): (Vec<_>, Vec<_>) = outputs.into_iter().unzip();
+        let (merged, mut sink, merged_closer) = channel::<u32>(4);
+
+        let fanned_out = tokio::spawn(async move {
+            let sent = fan_out(input, &outputs).await;
+            drop(outputs);
+            closers.into_iter().for_each(|closer| closer.close(None));
+            sent
+        });
+        let fanned_in = tokio::spawn(async move {
+            let sent = fan_in(branches, &merged).await;
+            drop(merged);
+            merged_closer.close(None);
+            sent
+        });
+        tokio::spawn(async move {
+            for i in 0..100 {
+                source.send(i).await.unwrap();
+            }
+            drop(source);
+            source_closer.close(None);
+        });
+
+        let mut received = Vec::new();
+        while let StreamItem::Item(item) = sink.recv().await {
+            received.push(item);
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+        assert_eq!(fanned_out.await.unwrap().unwrap(), 100);
+        assert_eq!(fanned_in.await.unwrap().unwrap(), 100);
+
+        let (_, input, closer) = channel::<u32>(1);
+        closer.close(Some(&SystemError::internal("boom", None)));
+        let (output, _receiver, _closer) = channel::<u32>(1);
+        let err = fan_in(vec![input], &output).await.unwrap_err();
+        assert!(err.to_string().contains("boom"), "{err}");
+        assert!(fan_out(channel::<u32>(1).1, &[]).await.is_err());
+    }
+}
@@ -0,0 +1,1237 @@
+//! Distributed module
+//!
+//! Runs graph tasks on other processes or machines. A [`WorkerServer`]
+//! listens at the address of its [`ServerConfig`], registers with a
+//! [`Coordinator`] and runs the handlers of its [`TaskRegistry`] the
+//! coordinator invokes, each under a permit of its own
+//! [`ResourceGovernor`]. [`RemoteTask`]s, added to a
+//! [`TaskGraph`](crate::scheduler::TaskGraph) run by an
+//! [`Executor`](crate::executor::Executor), are sent to the least loaded
+//! live worker, preferring workers labelled with the task's locality hint.
+//!
+//! Workers send a heartbeat every interval the coordinator sets. A worker
+//! that misses too many in a row or drops its connection is declared dead,
+//! and the tasks it was running are sent to other workers; each
+//! [`RemoteOutput`] lists the workers its task lost.
+//!
+//! Frames are bincode messages prefixed with their length as a big-endian
+//! `u32`. Invocations are signed with the coordinator's [`KeyPair`], and
+//! workers reject those that fail to verify against its public key.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use shared_core::config::ServerConfig;
+use shared_core::crypto::{KeyPair, PublicKey};
+use shared_core::{ResourceGovernor, ResourceGovernorConfig, Result, SystemError};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::scheduler::{Task, TaskContext, TaskOutput, TaskPolicy};
+
+/// Largest frame either side accepts
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// Frames exchanged by a coordinator and its workers
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    /// Worker to coordinator, on a connection of its own: the worker
+    /// listens at `address` and runs up to `capacity` tasks at once
+    Register {
+        worker: String,
+        address: SocketAddr,
+        labels: Vec<String>,
+        capacity: usize,
+    },
+    /// Coordinator to worker, accepting a registration
+    Registered,
+    /// Coordinator to worker, refusing a registration
+    Rejected { reason: String },
+    /// Coordinator to worker, opening the connection tasks are sent on
+    Session {
+        worker: String,
+        heartbeat_interval: Duration,
+    },
+    /// Coordinator to worker: run the signed, encoded [`Invocation`]
+    Invoke {
+        invocation: u64,
+        payload: Vec<u8>,
+        signature: Vec<u8>,
+    },
+    /// Worker to coordinator, every heartbeat interval
+    Heartbeat,
+    /// Worker to coordinator: the output of an invocation, or its error
+    /// encoded as JSON
+    Completed {
+        invocation: u64,
+        result: std::result::Result<Vec<u8>, Vec<u8>>,
+    },
+}
+
+/// What a coordinator signs and sends a worker to run
+#[derive(Debug, Serialize, Deserialize)]
+struct Invocation {
+    invocation: u64,
+    /// Worker the invocation is meant for, so it cannot be replayed to
+    /// another
+    worker: String,
+    handler: String,
+    input: Vec<u8>,
+    dependencies: HashMap<String, Vec<u8>>,
+}
+
+/// Encode `value` as task inputs and outputs are, with bincode
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    Ok(bincode::serialize(value)?)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), message: &Message) -> Result<()> {
+    let bytes = encode(message)?;
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|_| bytes.len() <= MAX_FRAME_BYTES)
+        .ok_or_else(|| {
+            SystemError::validation(
+                "frame",
+                format!(
+                    "{} bytes exceeds the limit of {MAX_FRAME_BYTES}",
+                    bytes.len()
+                ),
+                None,
+            )
+        })?;
+    writer
+        .write_u32(len)
+        .await
+        .map_err(|e| SystemError::io(e, "writing frame"))?;
+    writer
+        .write_all(&bytes)
+        .await
+        .map_err(|e| SystemError::io(e, "writing frame"))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| SystemError::io(e, "writing frame"))
+}
+
+/// Next frame, or `None` once the peer closed the connection
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<Message>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => usize::try_from(len).unwrap_or(usize::MAX),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(SystemError::io(e, "reading frame")),
+    };
+    if len > MAX_FRAME_BYTES {
+        return Err(SystemError::validation(
+            "frame",
+            format!("{len} bytes exceeds the limit of {MAX_FRAME_BYTES}"),
+            None,
+        ));
+    }
+    let mut bytes = vec![0; len];
+    reader
+        .read_exact(&mut bytes)
+        .await
+        .map_err(|e| SystemError::io(e, "reading frame"))?;
+    decode(&bytes).map(Some)
+}
+
+/// Run `future`, failing with a `Timeout` error for `operation` after
+/// `limit`
+async fn within<T>(
+    limit: Duration,
+    operation: &str,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let limit_ms = u64::try_from(limit.as_millis()).unwrap_or(u64::MAX);
+    tokio::time::timeout(limit, future)
+        .await
+        .unwrap_or_else(|_| Err(SystemError::timeout(operation, limit_ms)))
+}
+
+/// A handler a [`WorkerServer`] runs when invoked by name
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait RemoteHandler: Send + Sync {
+    /// Run with the input and dependency outputs of an invocation,
+    /// returning the encoded output
+    async fn run(&self, input: RemoteInput) -> Result<Vec<u8>>;
+}
+
+/// A [`RemoteHandler`] made of an async function, see
+/// [`TaskRegistry::register_fn`]
+struct FnHandler<F>(F);
+
+#[async_trait]
+impl<F, Fut, O> RemoteHandler for FnHandler<F>
+where
+    F: Fn(RemoteInput) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<O>> + Send,
+    O: Serialize,
+{
+    async fn run(&self, input: RemoteInput) -> Result<Vec<u8>> {
+        let output = (self.0)(input).await?;
+        encode(&output)
+    }
+}
+
+/// Handlers of a [`WorkerServer`], by name
+#[derive(Clone, Default)]
+pub struct TaskRegistry {
+    handlers: HashMap<String, Arc<dyn RemoteHandler>>,
+}
+
+impl TaskRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `handler` as `name`, replacing any handler of that name
+    pub fn register(
+        mut self,
+        name: impl Into<String>,
+        handler: impl RemoteHandler + 'static,
+    ) -> Self {
+        self.handlers.insert(name.into(), Arc::new(handler));
+        self
+    }
+
+    /// Add an async function as `name`, whose output is encoded with
+    /// [`encode`]
+    pub fn register_fn<F, Fut, O>(self, name: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(RemoteInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<O>> + Send + 'static,
+        O: Serialize + 'static,
+    {
+        self.register(name, FnHandler(handler))
+    }
+
+    /// Names of the handlers, in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+}
+
+impl std::fmt::Debug for TaskRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskRegistry")
+            .field("handlers", &self.handlers.keys())
+            .finish()
+    }
+}
+
+/// What a [`RemoteHandler`] is invoked with
+#[derive(Debug, Clone)]
+pub struct RemoteInput {
+    input: Vec<u8>,
+    dependencies: HashMap<String, Vec<u8>>,
+}
+
+impl RemoteInput {
+    /// The task's input as a `T`
+    pub fn input<T: DeserializeOwned>(&self) -> Result<T> {
+        decode(&self.input)
+    }
+
+    /// Output of the task's dependency `task_id` as a `T`
+    pub fn dependency<T: DeserializeOwned>(&self, task_id: &str) -> Result<T> {
+        let output = self
+            .dependencies
+            .get(task_id)
+            .ok_or_else(|| SystemError::not_found("task output", task_id))?;
+        decode(output)
+    }
+}
+
+/// Output of a [`RemoteTask`], and where the task ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteOutput {
+    /// Worker that ran the task
+    pub worker: String,
+    /// Workers declared dead while running the task, in order; the task
+    /// was rescheduled after each
+    pub lost_workers: Vec<String>,
+    /// The encoded output
+    pub bytes: Vec<u8>,
+}
+
+impl RemoteOutput {
+    /// The output as a `T`
+    pub fn value<T: DeserializeOwned>(&self) -> Result<T> {
+        decode(&self.bytes)
+    }
+
+    /// Whether the task had to be rescheduled
+    pub fn was_rescheduled(&self) -> bool {
+        !self.lost_workers.is_empty()
+    }
+}
+
+/// A graph task run by a worker of a [`Coordinator`], from
+/// [`Coordinator::task`]
+///
+/// Its output is a [`RemoteOutput`]. Its dependencies must be remote tasks
+/// too; their outputs reach the handler through
+/// [`RemoteInput::dependency`]. When its run is cancelled the task stops
+/// waiting for its worker, which still runs the invocation to the end.
+#[derive(Debug, Clone)]
+pub struct RemoteTask {
+    id: String,
+    handler: String,
+    input: Vec<u8>,
+    dependencies: Vec<String>,
+    locality: Option<String>,
+    policy: TaskPolicy,
+    coordinator: Coordinator,
+}
+
+impl RemoteTask {
+    /// Depend on task `id`
+    pub fn depends_on(mut self, id: impl Into<String>) -> Self {
+        self.dependencies.push(id.into());
+        self
+    }
+
+    /// Prefer workers labelled `label`, running on others only if none
+    /// has room
+    pub fn prefer(mut self, label: impl Into<String>) -> Self {
+        self.locality = Some(label.into());
+        self
+    }
+
+    /// Set how the executor recovers from the task failing
+    pub fn with_policy(mut self, policy: TaskPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+#[async_trait]
+impl Task for RemoteTask {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.dependencies.clone()
+    }
+
+    fn policy(&self) -> TaskPolicy {
+        self.policy.clone()
+    }
+
+    async fn run(&self, ctx: TaskContext) -> Result<TaskOutput> {
+        let mut dependencies = HashMap::new();
+        for id in &self.dependencies {
+            let output = ctx.output::<RemoteOutput>(id)?;
+            dependencies.insert(id.clone(), output.bytes.clone());
+        }
+        let invoke = self.coordinator.inner.invoke(
+            &self.handler,
+            &self.input,
+            dependencies,
+            self.locality.as_deref(),
+        );
+        tokio::select! {
+            output = invoke => output.map(TaskOutput::new),
+            () = ctx.cancellation().cancelled() => Err(SystemError::Concurrency {
+                message: format!("task {} cancelled", self.id),
+                thread_id: None,
+            }),
+        }
+    }
+}
+
+/// Settings of a [`Coordinator`]
+#[derive(Debug, Clone)]
+pub struct CoordinatorConfig {
+    /// How often workers send a heartbeat
+    pub heartbeat_interval: Duration,
+    /// Heartbeats a worker may miss in a row before it is declared dead,
+    /// at least 1
+    pub missed_heartbeats: u32,
+    /// Times a task is rescheduled after losing its worker before it fails
+    /// with a retriable `Network` error
+    pub max_reschedules: u32,
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(1),
+            missed_heartbeats: 3,
+            max_reschedules: 3,
+        }
+    }
+}
+
+/// Schedules [`RemoteTask`]s on the [`WorkerServer`]s registered with it
+///
+/// Clones share the same workers; the coordinator stops listening and
+/// drops its workers once the last clone, and the last task made by it, is
+/// dropped.
+#[derive(Clone)]
+pub struct Coordinator {
+    inner: Arc<CoordinatorInner>,
+}
+
+struct CoordinatorInner {
+    keypair: KeyPair,
+    config: CoordinatorConfig,
+    address: SocketAddr,
+    handshake_timeout: Duration,
+    workers: Mutex<HashMap<String, Arc<WorkerLink>>>,
+    next_invocation: AtomicU64,
+    /// Stops registrations and sessions once dropped
+    stop: CancellationToken,
+}
+
+/// A registered worker, as the coordinator sees it
+struct WorkerLink {
+    name: String,
+    labels: Vec<String>,
+    capacity: usize,
+    frames: mpsc::UnboundedSender<Message>,
+    /// Invocations sent and not completed; `None` once the worker is dead,
+    /// which drops every waiting sender
+    in_flight: Mutex<Option<Pending>>,
+}
+
+/// Where to send the result of each invocation sent to a worker
+type Pending = HashMap<u64, oneshot::Sender<Result<Vec<u8>>>>;
+
+impl Coordinator {
+    /// Listen for worker registrations at the address of `server`,
+    /// signing invocations with `keypair`
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub async fn bind(
+        server: &ServerConfig,
+        keypair: KeyPair,
+        config: CoordinatorConfig,
+    ) -> Result<Self> {
+        if config.missed_heartbeats == 0 {
+            return Err(SystemError::config(
+                "missed_heartbeats must be > 0",
+                Some("missed_heartbeats".to_string()),
+            ));
+        }
+        let listener = TcpListener::bind((server.host.as_str(), server.port))
+            .await
+            .map_err(|e| SystemError::io(e, format!("binding {}:{}", server.host, server.port)))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| SystemError::io(e, "reading listen address"))?;
+        let inner = Arc::new(CoordinatorInner {
+            keypair,
+            config,
+            address,
+            handshake_timeout: Duration::from_secs(server.timeout_secs),
+            workers: Mutex::new(HashMap::new()),
+            next_invocation: AtomicU64::new(0),
+            stop: CancellationToken::new(),
+        });
+        tokio::spawn(accept_registrations(
+            listener,
+            Arc::downgrade(&inner),
+            inner.stop.clone(),
+        ));
+        tracing::info!("Coordinator listening for workers on {}", address);
+        Ok(Self { inner })
+    }
+
+    /// Address workers register at
+    pub fn local_addr(&self) -> SocketAddr {
+        self.inner.address
+    }
+
+    /// Key workers verify invocations with
+    pub fn public_key(&self) -> PublicKey {
+        self.inner.keypair.public_key()
+    }
+
+    /// Names of the live workers, sorted
+    pub fn workers(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.inner.workers.lock().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// A task `id` running the handler `handler` of a worker on `input`
+    pub fn task(
+        &self,
+        id: impl Into<String>,
+        handler: impl Into<String>,
+        input: &impl Serialize,
+    ) -> Result<RemoteTask> {
+        Ok(RemoteTask {
+            id: id.into(),
+            handler: handler.into(),
+            input: encode(input)?,
+            dependencies: Vec::new(),
+            locality: None,
+            policy: TaskPolicy::default(),
+            coordinator: self.clone(),
+        })
+    }
+}
+
+impl std::fmt::Debug for Coordinator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Coordinator")
+            .field("address", &self.inner.address)
+            .field("workers", &self.workers())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for CoordinatorInner {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+impl CoordinatorInner {
+    /// Handle a registration, answering it on `stream`
+    async fn register(self: Arc<Self>, mut stream: TcpStream) -> Result<()> {
+        let timeout = self.handshake_timeout;
+        let Some(Message::Register {
+            worker,
+            address,
+            labels,
+            capacity,
+        }) = within(timeout, "reading registration", read_frame(&mut stream)).await?
+        else {
+            return Err(SystemError::validation(
+                "frame",
+                "expected a registration",
+                None,
+            ));
+        };
+        let result = within(
+            timeout,
+            "opening session",
+            self.connect(&worker, address, labels, capacity),
+        )
+        .await;
+        let answer = match &result {
+            Ok(()) => Message::Registered,
+            Err(err) => Message::Rejected {
+                reason: err.to_string(),
+            },
+        };
+        write_frame(&mut stream, &answer).await?;
+        result
+    }
+
+    /// Open a session with worker `name`, listening at `address`
+    async fn connect(
+        self: &Arc<Self>,
+        name: &str,
+        address: SocketAddr,
+        labels: Vec<String>,
+        capacity: usize,
+    ) -> Result<()> {
+        let already_registered = || SystemError::AlreadyExists {
+            resource_type: "worker".to_string(),
+            identifier: name.to_string(),
+        };
+        if self.workers.lock().contains_key(name) {
+            return Err(already_registered());
+        }
+        let mut stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| SystemError::io(e, format!("connecting to worker {name} at {address}")))?;
+        let session = Message::Session {
+            worker: name.to_string(),
+            heartbeat_interval: self.config.heartbeat_interval,
+        };
+        write_frame(&mut stream, &session).await?;
+
+        let (frames, outbox) = mpsc::unbounded_channel();
+        let link = Arc::new(WorkerLink {
+            name: name.to_string(),
+            labels,
+            capacity: capacity.max(1),
+            frames,
+            in_flight: Mutex::new(Some(HashMap::new())),
+        });
+        {
+            let mut workers = self.workers.lock();
+            if workers.contains_key(name) {
+                return Err(already_registered());
+            }
+            workers.insert(name.to_string(), Arc::clone(&link));
+        }
+        tracing::info!("Worker {} registered from {}", name, address);
+        let (reader, writer) = stream.into_split();
+        let silence = self.config.heartbeat_interval * self.config.missed_heartbeats;
+        tokio::spawn(run_session(
+            link,
+            reader,
+            writer,
+            outbox,
+            silence,
+            Arc::downgrade(self),
+            self.stop.clone(),
+        ));
+        Ok(())
+    }
+
+    /// Live worker to run a task on: one with room before one without,
+    /// then one labelled `locality`, then the least loaded
+    fn pick(&self, locality: Option<&str>) -> Option<Arc<WorkerLink>> {
+        let workers = self.workers.lock();
+        workers
+            .values()
+            .filter_map(|link| Some((link, link.load()?)))
+            .min_by_key(|&(link, load)| {
+                let preferred =
+                    locality.is_some_and(|label| link.labels.iter().any(|l| l == label));
+                (load >= link.capacity, !preferred, load, &link.name)
+            })
+            .map(|(link, _)| Arc::clone(link))
+    }
+
+    /// Run `handler` on a worker, moving it to another each time its
+    /// worker is lost
+    async fn invoke(
+        &self,
+        handler: &str,
+        input: &[u8],
+        dependencies: HashMap<String, Vec<u8>>,
+        locality: Option<&str>,
+    ) -> Result<RemoteOutput> {
+        let mut lost_workers = Vec::new();
+        loop {
+            let link = self.pick(locality).ok_or_else(|| {
+                SystemError::network("invoke", format!("no live worker to run {handler}"), None)
+            })?;
+            let invocation = self.next_invocation.fetch_add(1, Ordering::Relaxed);
+            let payload = encode(&Invocation {
+                invocation,
+                worker: link.name.clone(),
+                handler: handler.to_string(),
+                input: input.to_vec(),
+                dependencies: dependencies.clone(),
+            })?;
+            let signature = self.keypair.sign(&payload);
+            let (sender, receiver) = oneshot::channel();
+            let sent = match link.in_flight.lock().as_mut() {
+                Some(in_flight) => {
+                    in_flight.insert(invocation, sender);
+                    let invoke = Message::Invoke {
+                        invocation,
+                        payload,
+                        signature,
+                    };
+                    link.frames.send(invoke).is_ok()
+                },
+                None => false,
+            };
+            // The sender is dropped when the worker is lost
+            if sent {
+                if let Ok(result) = receiver.await {
+                    return result.map(|bytes| RemoteOutput {
+                        worker: link.name.clone(),
+                        lost_workers,
+                        bytes,
+                    });
+                }
+            }
+
+            lost_workers.push(link.name.clone());
+            let attempts = u32::try_from(lost_workers.len()).unwrap_or(u32::MAX);
+            if attempts > self.config.max_reschedules {
+                return Err(SystemError::network(
+                    "invoke",
+                    format!("{handler} lost workers {}", lost_workers.join(", ")),
+                    Some(attempts),
+                ));
+            }
+            tracing::warn!(
+                "Worker {} lost while running {}, rescheduling",
+                link.name,
+                handler
+            );
+        }
+    }
+}
+
+impl WorkerLink {
+    /// Tasks running on the worker, or `None` if it is dead
+    fn load(&self) -> Option<usize> {
+        self.in_flight.lock().as_ref().map(HashMap::len)
+    }
+
+    fn complete(&self, invocation: u64, result: std::result::Result<Vec<u8>, Vec<u8>>) {
+        let Some(sender) = self
+            .in_flight
+            .lock()
+            .as_mut()
+            .and_then(|in_flight| in_flight.remove(&invocation))
+        else {
+            return;
+        };
+        let result = result.map_err(|error| {
+            serde_json::from_slice(&error).unwrap_or_else(|e| {
+                SystemError::internal(
+                    format!("undecodable error from worker {}: {e}", self.name),
+                    None,
+                )
+            })
+        });
+        let _ = sender.send(result);
+    }
+}
+
+/// Accept registrations until the coordinator is dropped
+async fn accept_registrations(
+    listener: TcpListener,
+    coordinator: Weak<CoordinatorInner>,
+    stop: CancellationToken,
+) {
+    loop {
+        let accepted = tokio::select! {
+            () = stop.cancelled() => return,
+            accepted = listener.accept() => accepted,
+        };
+        let (stream, peer) = match accepted {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                tracing::warn!("Failed to accept a worker registration: {}", err);
+                continue;
+            },
+        };
+        let Some(coordinator) = coordinator.upgrade() else {
+            return;
+        };
+        tokio::spawn(async move {
+            if let Err(err) = coordinator.register(stream).await {
+                tracing::warn!("Registration from {} failed: {}", peer, err);
+            }
+        });
+    }
+}
+
+/// Relay frames to and from a worker until it is dead, then drop it
+async fn run_session(
+    link: Arc<WorkerLink>,
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    mut outbox: mpsc::UnboundedReceiver<Message>,
+    silence: Duration,
+    coordinator: Weak<CoordinatorInner>,
+    stop: CancellationToken,
+) {
+    let writing = tokio::spawn(async move {
+        while let Some(message) = outbox.recv().await {
+            if write_frame(&mut writer, &message).await.is_err() {
+                break;
+            }
+        }
+    });
+    let reason = loop {
+        let frame = tokio::select! {
+            () = stop.cancelled() => break "coordinator stopped".to_string(),
+            frame = tokio::time::timeout(silence, read_frame(&mut reader)) => frame,
+        };
+        match frame {
+            Err(_) => break format!("no heartbeat for {silence:?}"),
+            Ok(Err(err)) => break err.to_string(),
+            Ok(Ok(None)) => break "connection closed".to_string(),
+            Ok(Ok(Some(Message::Heartbeat))) => {},
+            Ok(Ok(Some(Message::Completed { invocation, result }))) => {
+                link.complete(invocation, result);
+            },
+            Ok(Ok(Some(_))) => break "unexpected frame".to_string(),
+        }
+    };
+    writing.abort();
+    let lost = link
+        .in_flight
+        .lock()
+        .take()
+        .map_or(0, |in_flight| in_flight.len());
+    tracing::warn!(
+        "Worker {} is dead ({}), {} tasks to reschedule",
+        link.name,
+        reason,
+        lost
+    );
+    if let Some(coordinator) = coordinator.upgrade() {
+        let mut workers = coordinator.workers.lock();
+        if workers
+            .get(&link.name)
+            .is_some_and(|current| Arc::ptr_eq(current, &link))
+        {
+            workers.remove(&link.name);
+        }
+    }
+}
+
+/// Runs the handlers of a [`TaskRegistry`] for a [`Coordinator`]
+///
+/// Binary-ready: [`bind`](Self::bind) then [`serve`](Self::serve) is all a
+/// worker process needs.
+pub struct WorkerServer {
+    listener: TcpListener,
+    labels: Vec<String>,
+    capacity: usize,
+    handshake_timeout: Duration,
+    shared: Arc<WorkerShared>,
+}
+
+/// What every session of a worker uses
+struct WorkerShared {
+    name: String,
+    registry: TaskRegistry,
+    governor: ResourceGovernor,
+    coordinator_key: PublicKey,
+}
+
+impl WorkerServer {
+    /// Listen at the address of `server` for the coordinator whose public
+    /// key is `coordinator_key`
+    ///
+    /// The worker offers to run `server.workers` tasks at once, or one per
+    /// CPU if unset; its governor, unlimited by default, decides how many
+    /// actually do.
+    pub async fn bind(
+        name: impl Into<String>,
+        server: &ServerConfig,
+        registry: TaskRegistry,
+        coordinator_key: PublicKey,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind((server.host.as_str(), server.port))
+            .await
+            .map_err(|e| SystemError::io(e, format!("binding {}:{}", server.host, server.port)))?;
+        Ok(Self {
+            listener,
+            labels: Vec::new(),
+            capacity: server.workers.unwrap_or_else(num_cpus::get),
+            handshake_timeout: Duration::from_secs(server.timeout_secs),
+            shared: Arc::new(WorkerShared {
+                name: name.into(),
+                registry,
+                governor: ResourceGovernor::new(ResourceGovernorConfig::default())?,
+                coordinator_key,
+            }),
+        })
+    }
+
+    /// Set the labels tasks may prefer the worker by
+    pub fn with_labels(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.labels = labels.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Run every invocation under a permit of `governor`, labelled with
+    /// the handler's name
+    pub fn with_governor(mut self, governor: ResourceGovernor) -> Self {
+        if let Some(shared) = Arc::get_mut(&mut self.shared) {
+            shared.governor = governor;
+        }
+        self
+    }
+
+    /// Address the worker listens at
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .map_err(|e| SystemError::io(e, "reading listen address"))
+    }
+
+    /// Register with the coordinator listening at `coordinator` and serve
+    /// it in the background
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub async fn start(self, coordinator: SocketAddr) -> Result<WorkerHandle> {
+        let registration = Message::Register {
+            worker: self.shared.name.clone(),
+            address: self.local_addr()?,
+            labels: self.labels,
+            capacity: self.capacity,
+        };
+        let handle = WorkerHandle {
+            name: self.shared.name.clone(),
+            serving: tokio::spawn(accept_sessions(self.listener, self.shared)),
+        };
+        let registered = within(self.handshake_timeout, "registering", async {
+            let mut stream = TcpStream::connect(coordinator)
+                .await
+                .map_err(|e| SystemError::io(e, format!("connecting to {coordinator}")))?;
+            write_frame(&mut stream, &registration).await?;
+            match read_frame(&mut stream).await? {
+                Some(Message::Registered) => Ok(()),
+                Some(Message::Rejected { reason }) => Err(SystemError::validation(
+                    "worker",
+                    reason,
+                    Some(handle.name.clone()),
+                )),
+                _ => Err(SystemError::network(
+                    "register",
+                    "coordinator hung up",
+                    None,
+                )),
+            }
+        })
+        .await;
+        if let Err(err) = registered {
+            handle.kill();
+            return Err(err);
+        }
+        tracing::info!("Worker {} registered with {}", handle.name, coordinator);
+        Ok(handle)
+    }
+
+    /// Register with the coordinator listening at `coordinator` and serve
+    /// it until the listener fails
+    pub async fn serve(self, coordinator: SocketAddr) -> Result<()> {
+        self.start(coordinator).await?.await
+    }
+}
+
+impl std::fmt::Debug for WorkerServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerServer")
+            .field("name", &self.shared.name)
+            .field("address", &self.listener.local_addr().ok())
+            .field("labels", &self.labels)
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`WorkerServer`] serving in the background, from
+/// [`WorkerServer::start`]
+///
+/// Awaiting the handle waits for the server to fail. Dropping it leaves
+/// the server going.
+pub struct WorkerHandle {
+    name: String,
+    serving: tokio::task::JoinHandle<Result<()>>,
+}
+
+impl WorkerHandle {
+    /// Name the worker registered as
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stop at once, dropping every connection and running task, as a
+    /// crashed worker would
+    pub fn kill(&self) {
+        self.serving.abort();
+    }
+}
+
+impl Future for WorkerHandle {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.serving.poll_unpin(cx).map(|joined| {
+            joined.unwrap_or_else(|err| {
+                Err(SystemError::Concurrency {
+                    message: format!("worker stopped: {err}"),
+                    thread_id: None,
+                })
+            })
+        })
+    }
+}
+
+impl std::fmt::Debug for WorkerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerHandle")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serve each coordinator connection until the listener fails; dropping
+/// this drops every session
+async fn accept_sessions(listener: TcpListener, shared: Arc<WorkerShared>) -> Result<()> {
+    let mut sessions = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) =
+                    accepted.map_err(|e| SystemError::io(e, "accepting coordinator"))?;
+                let shared = Arc::clone(&shared);
+                sessions.spawn(async move {
+                    if let Err(err) = serve_session(stream, &shared).await {
+                        tracing::warn!("Session of {} with {} failed: {}", shared.name, peer, err);
+                    }
+                });
+            },
+            Some(_) = sessions.join_next() => {},
+        }
+    }
+}
+
+/// Run the invocations of one coordinator connection, sending heartbeats
+/// meanwhile
+async fn serve_session(stream: TcpStream, shared: &Arc<WorkerShared>) -> Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+    let heartbeat_interval = match read_frame(&mut reader).await? {
+        Some(Message::Session {
+            worker,
+            heartbeat_interval,
+        }) if worker == shared.name => heartbeat_interval,
+        _ => return Err(SystemError::validation("frame", "expected a session", None)),
+    };
+
+    let (frames, mut outbox) = mpsc::unbounded_channel();
+    let writing = async move {
+        let mut heartbeat = tokio::time::interval(heartbeat_interval);
+        loop {
+            let message = tokio::select! {
+                _ = heartbeat.tick() => Message::Heartbeat,
+                message = outbox.recv() => match message {
+                    Some(message) => message,
+                    None => return Ok(()),
+                },
+            };
+            write_frame(&mut writer, &message).await?;
+        }
+    };
+    // Dropped with the session, aborting the invocations still running
+    let mut runs = JoinSet::new();
+    let reading = async {
+        loop {
+            let Some(message) = read_frame(&mut reader).await? else {
+                return Ok(());
+            };
+            let Message::Invoke {
+                invocation,
+                payload,
+                signature,
+            } = message
+            else {
+                return Err(SystemError::validation(
+                    "frame",
+                    "expected an invocation",
+                    None,
+                ));
+            };
+            while runs.try_join_next().is_some() {}
+            let shared = Arc::clone(shared);
+            let frames = frames.clone();
+            runs.spawn(async move {
+                let result = shared.run(invocation, &payload, &signature).await;
+                if let Err(err) = &result {
+                    tracing::debug!(
+                        "Invocation {} failed on {}: {}",
+                        invocation,
+                        shared.name,
+                        err
+                    );
+                }
+                let result = result.map_err(|err| serde_json::to_vec(&err).unwrap_or_default());
+                let _ = frames.send(Message::Completed { invocation, result });
+            });
+        }
+    };
+    tokio::select! {
+        written = writing => written,
+        read = reading => read,
+    }
+}
+
+impl WorkerShared {
+    /// Verify and run an invocation under a permit of the governor
+    async fn run(&self, invocation: u64, payload: &[u8], signature: &[u8]) -> Result<Vec<u8>> {
+        let call = open_invocation(
+            &self.coordinator_key,
+            &self.name,
+            invocation,
+            payload,
+            signature,
+        )?;
+        let handler = self
+            .registry
+            .handlers
+            .get(&call.handler)
+            .cloned()
+            .ok_or_else(|| SystemError::not_found("remote handler", &call.handler))?;
+        let _permit = self.governor.acquire_permit_labeled(&call.handler).await?;
+        let input = RemoteInput {
+            input: call.input,
+            dependencies: call.dependencies,
+        };
+        AssertUnwindSafe(handler.run(input))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|_| {
+                Err(SystemError::internal(
+                    format!("handler {} panicked", call.handler),
+                    None,
+                ))
+            })
+    }
+}
+
+/// The invocation in `payload`, if `signature` is the coordinator's and it
+/// is meant for `worker` as invocation `invocation`
+fn open_invocation(
+    coordinator_key: &PublicKey,
+    worker: &str,
+    invocation: u64,
+    payload: &[u8],
+    signature: &[u8],
+) -> Result<Invocation> {
+    let denied = |reason: &str| SystemError::PermissionDenied {
+        operation: format!("invocation {invocation}: {reason}"),
+        required_permission: Some("coordinator signature".to_string()),
+    };
+    coordinator_key
+        .verify(payload, signature)
+        .map_err(|_| denied("signature does not verify"))?;
+    let call: Invocation = decode(payload)?;
+    if call.invocation != invocation || call.worker != worker {
+        return Err(denied("meant for another invocation or worker"));
+    }
+    Ok(call)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(worker: &str) -> Vec<u8> {
+        encode(&Invocation {
+            invocation: 7,
+            worker: worker.to_string(),
+            handler: "square".to_string(),
+            input: encode(&3u64).unwrap(),
+            dependencies: HashMap::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_workers_reject_tampered_invocations() {
+        let keypair = KeyPair::from_seed(&[1; 32]);
+        let key = keypair.public_key();
+        let payload = invocation("w1");
+        let signature = keypair.sign(&payload);
+        let call = open_invocation(&key, "w1", 7, &payload, &signature).unwrap();
+        assert_eq!(call.handler, "square");
+
+        let mut tampered = payload.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let forged = KeyPair::from_seed(&[2; 32]).sign(&payload);
+        for (worker, id, payload, signature) in [
+            ("w1", 7, &tampered, &signature),
+            ("w1", 7, &payload, &forged),
+            ("w2", 7, &payload, &signature),
+            ("w1", 8, &payload, &signature),
+        ] {
+            let err = open_invocation(&key, worker, id, payload, signature).unwrap_err();
+            assert!(matches!(err, SystemError::PermissionDenied { .. }), "{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        write_frame(&mut client, &Message::Heartbeat).await.unwrap();
+        let completed = Message::Completed {
+            invocation: 3,
+            result: Ok(vec![1, 2, 3]),
+        };
+        write_frame(&mut client, &completed).await.unwrap();
+        drop(client);
+        assert!(matches!(
+            read_frame(&mut server).await.unwrap(),
+            Some(Message::Heartbeat)
+        ));
+        assert!(matches!(
+            read_frame(&mut server).await.unwrap(),
+            Some(Message::Completed { invocation: 3, result: Ok(bytes) }) if bytes == [1, 2, 3]
+        ));
+        assert!(read_frame(&mut server).await.unwrap().is_none());
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_u32(u32::MAX).await.unwrap();
+        assert!(read_frame(&mut server).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_silent_workers_are_declared_dead() {
+        let server = ServerConfig {
+            port: 0,
+            ..ServerConfig::default()
+        };
+        let config = CoordinatorConfig {
+            heartbeat_interval: Duration::from_millis(20),
+            missed_heartbeats: 2,
+            ..CoordinatorConfig::default()
+        };
+        let coordinator = Coordinator::bind(&server, KeyPair::generate(), config)
+            .await
+            .unwrap();
+
+        // Registers and opens its session, then never sends a heartbeat
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut registration = TcpStream::connect(coordinator.local_addr()).await.unwrap();
+        let register = Message::Register {
+            worker: "mute".to_string(),
+            address: listener.local_addr().unwrap(),
+            labels: Vec::new(),
+            capacity: 1,
+        };
+        write_frame(&mut registration, &register).await.unwrap();
+        let (mut session, _) = listener.accept().await.unwrap();
+        assert!(matches!(
+            read_frame(&mut session).await.unwrap(),
+            Some(Message::Session { .. })
+        ));
+        assert!(matches!(
+            read_frame(&mut registration).await.unwrap(),
+            Some(Message::Registered)
+        ));
+        assert_eq!(coordinator.workers(), ["mute"]);
+
+        // Registering the same name again is refused
+        let mut again = TcpStream::connect(coordinator.local_addr()).await.unwrap();
+        write_frame(&mut again, &register).await.unwrap();
+        assert!(matches!(
+            read_frame(&mut again).await.unwrap(),
+            Some(Message::Rejected { .. })
+        ));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(coordinator.workers().is_empty());
+        let task = coordinator.task("t", "square", &3u64).unwrap();
+        let err = task.run(TaskContext::default()).await.unwrap_err();
+        assert!(err.is_retriable(), "{err}");
+    }
+}
@@ -0,0 +1,778 @@
+//! Dynamic module
+//!
+//! A [`DynamicGraph`] runs tasks submitted one at a time as work arrives,
+//! per incoming message say, rather than a [`TaskGraph`](crate::TaskGraph)
+//! known up front. Each task may depend on tasks submitted before it.
+//! Submissions wait while the pending queue, or the submitting source's
+//! share of it, is full, and ready tasks start in turn across sources so
+//! that one busy producer cannot starve the others. Finished tasks are
+//! forgotten once enough newer ones have finished and no live task needs
+//! their output, so a graph can run for days in bounded memory.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use shared_core::{Result, SystemError};
+use tokio::sync::{mpsc, watch, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::executor::{attempt, OutputPath, Sandbox, TaskReport, TaskState};
+use crate::scheduler::{Task, TaskContext, TaskOutput, TaskPolicy};
+
+/// Limits of a [`DynamicGraph`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicGraphConfig {
+    /// Tasks submitted but not started, past which submissions wait
+    pub queue_capacity: usize,
+    /// Tasks of one source submitted but not started, past which that
+    /// source's submissions wait
+    pub source_capacity: usize,
+    /// Finished tasks whose reports and outputs are kept, newest first;
+    /// older ones are kept only while a live task depends on them
+    pub retained_reports: usize,
+}
+
+impl Default for DynamicGraphConfig {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 1024,
+            source_capacity: 256,
+            retained_reports: 1024,
+        }
+    }
+}
+
+impl DynamicGraphConfig {
+    /// Check every limit is positive
+    pub fn validate(&self) -> Result<()> {
+        let limits = [
+            ("queue_capacity", self.queue_capacity),
+            ("source_capacity", self.source_capacity),
+            ("retained_reports", self.retained_reports),
+        ];
+        match limits.into_iter().find(|&(_, limit)| limit == 0) {
+            Some((field, _)) => {
+                Err(SystemError::config(format!("{field} must be > 0"), Some(field.to_string())))
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+/// A task of a [`DynamicGraph`] that finished
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// Source that submitted the task
+    pub source: String,
+    /// How the task ran
+    pub report: Arc<TaskReport>,
+}
+
+/// Tasks run as they are submitted, from
+/// [`Executor::dynamic_graph`](crate::executor::Executor::dynamic_graph)
+///
+/// Clones share the same graph.
+#[derive(Clone)]
+pub struct DynamicGraph {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    workers: usize,
+    sandbox: Sandbox,
+    config: DynamicGraphConfig,
+    /// Slots of the pending queue
+    queue: Arc<Semaphore>,
+    state: Mutex<State>,
+    /// Tasks submitted and not finished
+    live: watch::Sender<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    nodes: HashMap<String, Node>,
+    /// Ready tasks of each source, in the order they became ready
+    ready: HashMap<String, VecDeque<String>>,
+    /// Sources with ready tasks, the next to start one first
+    turns: VecDeque<String>,
+    /// Slots of the pending queue of each source with tasks not started
+    sources: HashMap<String, Arc<Semaphore>>,
+    /// Finished tasks, oldest first
+    finished: VecDeque<String>,
+    running: usize,
+    subscribers: Vec<mpsc::UnboundedSender<Completion>>,
+    /// Seeds the backoff jitter of each task
+    rng: Option<StdRng>,
+}
+
+/// A submitted task
+struct Node {
+    task: Arc<dyn Task>,
+    source: String,
+    /// Dependencies not finished yet
+    waiting_on: usize,
+    /// Live tasks depending on this one, which keep it from being forgotten
+    dependents: Vec<String>,
+    /// Submissions depending on this one waiting for a slot, which also
+    /// keep it from being forgotten
+    held: usize,
+    /// Slots of the pending queue, held until the task starts
+    permits: Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)>,
+    ready_at: Option<Instant>,
+    report: Option<Arc<TaskReport>>,
+    output: Option<TaskOutput>,
+}
+
+impl DynamicGraph {
+    /// Create a graph running up to `workers` tasks at once in `sandbox`,
+    /// seeding their backoff jitter from `seed` if there is one
+    pub(crate) fn new(
+        workers: usize,
+        seed: Option<u64>,
+        sandbox: Sandbox,
+        config: DynamicGraphConfig,
+    ) -> Result<Self> {
+        config.validate()?;
+        let state = State {
+            rng: Some(match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            }),
+            ..State::default()
+        };
+        Ok(Self {
+            inner: Arc::new(Inner {
+                workers,
+                sandbox,
+                queue: Arc::new(Semaphore::new(config.queue_capacity)),
+                config,
+                state: Mutex::new(state),
+                live: watch::Sender::new(0),
+            }),
+        })
+    }
+
+    /// Submit `task` on behalf of `source`, to run once its dependencies
+    /// have succeeded
+    ///
+    /// Waits while the pending queue or `source`'s share of it is full,
+    /// holding on to the dependencies meanwhile. Dependencies must have
+    /// been submitted before, and either not be finished or still be
+    /// retained; otherwise this fails with a `NotFound` error. A task whose dependency did not succeed is
+    /// skipped. Identifiers of live or retained tasks cannot be reused, and
+    /// tasks may retry and time out but not fall back or name a resource
+    /// class; these are `Validation` errors. Retry backoffs hold the
+    /// task's worker slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub async fn submit_task(&self, source: &str, task: impl Task + 'static) -> Result<()> {
+        let task: Arc<dyn Task> = Arc::new(task);
+        let id = task.id().to_string();
+        let invalid =
+            |field: &str, reason: &str| SystemError::validation(field, reason, Some(id.clone()));
+        let policy = task.policy();
+        policy.retry.validate()?;
+        if policy.fallback.is_some() {
+            return Err(invalid("fallback", "dynamic graph tasks cannot fall back"));
+        }
+        if task.resource_class().is_some() {
+            return Err(invalid("resource_class", "dynamic graph tasks cannot name a class"));
+        }
+
+        let dependencies = task.dependencies();
+        let (source_slots, mut hold) = {
+            let mut state = self.inner.state.lock();
+            if let Some(unknown) = dependencies.iter().find(|id| !state.nodes.contains_key(*id)) {
+                return Err(SystemError::not_found("task", unknown));
+            }
+            let hold = Hold::new(&self.inner.state, &mut state, &dependencies);
+            let capacity = self.inner.config.source_capacity;
+            let slots = state
+                .sources
+                .entry(source.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(capacity)));
+            (Arc::clone(slots), hold)
+        };
+        let closed = |_| SystemError::internal("pending queue closed", None);
+        let source_permit = source_slots.acquire_owned().await.map_err(closed)?;
+        let queue_permit = Arc::clone(&self.inner.queue).acquire_owned().await.map_err(closed)?;
+
+        let mut state = self.inner.state.lock();
+        hold.release(&mut state);
+        if state.nodes.contains_key(&id) {
+            return Err(invalid("task_id", "duplicate task identifier"));
+        }
+        let mut waiting_on = 0;
+        let mut failed = false;
+        for dependency in &dependencies {
+            let node = state
+                .nodes
+                .get(dependency)
+                .ok_or_else(|| SystemError::not_found("task", dependency))?;
+            match &node.report {
+                None => waiting_on += 1,
+                Some(report) => failed |= report.state != TaskState::Succeeded,
+            }
+        }
+        for dependency in &dependencies {
+            if let Some(node) = state.nodes.get_mut(dependency) {
+                if node.report.is_none() {
+                    node.dependents.push(id.clone());
+                }
+            }
+        }
+        state.nodes.insert(
+            id.clone(),
+            Node {
+                task,
+                source: source.to_string(),
+                waiting_on,
+                dependents: Vec::new(),
+                held: 0,
+                permits: Some((source_permit, queue_permit)),
+                ready_at: None,
+                report: None,
+                output: None,
+            },
+        );
+        self.inner.live.send_modify(|live| *live += 1);
+        if failed {
+            self.finish(&mut state, skipped(id), None);
+        } else if waiting_on == 0 {
+            state.make_ready(&id);
+        }
+        drop(state);
+        self.dispatch();
+        Ok(())
+    }
+
+    /// Receive a [`Completion`] for every task finishing from now on
+    ///
+    /// The channel is unbounded, so a subscriber should keep up; it is
+    /// dropped from the graph once its receiver is.
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<Completion> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.inner.state.lock().subscribers.push(sender);
+        receiver
+    }
+
+    /// Report of task `id`, if it finished and is still retained
+    pub fn report(&self, id: &str) -> Option<Arc<TaskReport>> {
+        self.inner.state.lock().nodes.get(id)?.report.clone()
+    }
+
+    /// Output of task `id`, if it succeeded and is still retained
+    pub fn output(&self, id: &str) -> Option<TaskOutput> {
+        self.inner.state.lock().nodes.get(id)?.output.clone()
+    }
+
+    /// Tasks submitted and not finished
+    pub fn live(&self) -> usize {
+        *self.inner.live.borrow()
+    }
+
+    /// Tasks the graph keeps track of: live ones and retained finished ones
+    pub fn len(&self) -> usize {
+        self.inner.state.lock().nodes.len()
+    }
+
+    /// Whether the graph keeps track of no task
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wait until every task submitted so far has finished
+    pub async fn drain(&self) {
+        let mut live = self.inner.live.subscribe();
+        // The sender lives as long as `self`
+        let _ = live.wait_for(|&live| live == 0).await;
+    }
+
+    /// Start ready tasks, in turn across sources, while workers are free
+    fn dispatch(&self) {
+        let mut state = self.inner.state.lock();
+        while state.running < self.inner.workers {
+            let Some(id) = state.next_ready() else {
+                break;
+            };
+            let Some(node) = state.nodes.get(&id) else {
+                continue;
+            };
+            let wait = node.ready_at.map_or(Duration::ZERO, |at| at.elapsed());
+            let task = Arc::clone(&node.task);
+            state.release(&id);
+            let inputs: HashMap<String, TaskOutput> = task
+                .dependencies()
+                .into_iter()
+                .filter_map(|dependency| {
+                    let output = state.nodes.get(&dependency)?.output.clone()?;
+                    Some((dependency, output))
+                })
+                .collect();
+            let seed = state.rng.as_mut().map_or(0, RngCore::next_u64);
+            state.running += 1;
+            let graph = self.clone();
+            tokio::spawn(async move {
+                let sandbox = &graph.inner.sandbox;
+                let (report, output) = run(&task, inputs, wait, seed, sandbox).await;
+                let mut state = graph.inner.state.lock();
+                state.running -= 1;
+                graph.finish(&mut state, report, output);
+                drop(state);
+                graph.dispatch();
+            });
+        }
+    }
+
+    /// Record that a task finished as its `report` says, making ready or
+    /// skipping its dependents, and forget the oldest finished tasks past
+    /// the retained count
+    fn finish(&self, state: &mut State, report: TaskReport, output: Option<TaskOutput>) {
+        let mut finished = vec![(report, output)];
+        while let Some((report, output)) = finished.pop() {
+            let id = report.id.clone();
+            state.release(&id);
+            let Some(node) = state.nodes.get_mut(&id).filter(|node| node.report.is_none()) else {
+                continue;
+            };
+            let succeeded = report.state == TaskState::Succeeded;
+            let report = Arc::new(report);
+            node.report = Some(Arc::clone(&report));
+            node.output = output;
+            let source = node.source.clone();
+            let task = Arc::clone(&node.task);
+            let dependents = std::mem::take(&mut node.dependents);
+            for dependency in task.dependencies() {
+                if let Some(dependency) = state.nodes.get_mut(&dependency) {
+                    dependency.dependents.retain(|dependent| *dependent != id);
+                }
+            }
+            state.finished.push_back(id);
+            self.inner.live.send_modify(|live| *live -= 1);
+            let completion = Completion { source, report };
+            state.subscribers.retain(|subscriber| subscriber.send(completion.clone()).is_ok());
+            for dependent in dependents {
+                if !succeeded {
+                    finished.push((skipped(dependent), None));
+                    continue;
+                }
+                let Some(node) = state.nodes.get_mut(&dependent) else {
+                    continue;
+                };
+                node.waiting_on -= 1;
+                if node.waiting_on == 0 {
+                    state.make_ready(&dependent);
+                }
+            }
+        }
+        state.collect(self.inner.config.retained_reports);
+    }
+}
+
+impl State {
+    /// Free the pending queue slots task `id` holds, forgetting its
+    /// source's slots once nothing holds or waits for them
+    fn release(&mut self, id: &str) {
+        let Some(node) = self.nodes.get_mut(id) else {
+            return;
+        };
+        if node.permits.take().is_none() {
+            return;
+        }
+        let unused = |slots: &Arc<Semaphore>| Arc::strong_count(slots) == 1;
+        if self.sources.get(&node.source).is_some_and(unused) {
+            self.sources.remove(&node.source);
+        }
+    }
+
+    /// Queue task `id` to start in its source's turn
+    fn make_ready(&mut self, id: &str) {
+        let Some(node) = self.nodes.get_mut(id) else {
+            return;
+        };
+        node.ready_at = Some(Instant::now());
+        let queue = self.ready.entry(node.source.clone()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(node.source.clone());
+        }
+        queue.push_back(id.to_string());
+    }
+
+    /// Ready task of the source whose turn it is, passing the turn on
+    fn next_ready(&mut self) -> Option<String> {
+        while let Some(source) = self.turns.pop_front() {
+            let Some(queue) = self.ready.get_mut(&source) else {
+                continue;
+            };
+            let next = queue.pop_front();
+            if queue.is_empty() {
+                self.ready.remove(&source);
+            } else {
+                self.turns.push_back(source);
+            }
+            if next.is_some() {
+                return next;
+            }
+        }
+        None
+    }
+
+    /// Forget the oldest finished tasks no live task depends on, until at
+    /// most `retained` are left
+    fn collect(&mut self, retained: usize) {
+        while self.finished.len() > retained {
+            let nodes = &self.nodes;
+            let Some(position) = self
+                .finished
+                .iter()
+                .position(|id| nodes.get(id).map_or(true, Node::is_unused))
+            else {
+                break;
+            };
+            if let Some(id) = self.finished.remove(position) {
+                self.nodes.remove(&id);
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Whether no live task or waiting submission needs the task's output
+    fn is_unused(&self) -> bool {
+        self.dependents.is_empty() && self.held == 0
+    }
+}
+
+/// Dependencies held by a submission waiting for a slot, let go when it is
+/// released or dropped
+struct Hold<'a> {
+    state: &'a Mutex<State>,
+    ids: Vec<String>,
+}
+
+impl<'a> Hold<'a> {
+    /// Hold the tasks `ids` of `state`, locked from `lock`
+    fn new(lock: &'a Mutex<State>, state: &mut State, ids: &[String]) -> Self {
+        for id in ids {
+            if let Some(node) = state.nodes.get_mut(id) {
+                node.held += 1;
+            }
+        }
+        Self {
+            state: lock,
+            ids: ids.to_vec(),
+        }
+    }
+
+    /// Let go of the held tasks in `state`, already locked
+    fn release(&mut self, state: &mut State) {
+        for id in self.ids.drain(..) {
+            if let Some(node) = state.nodes.get_mut(&id) {
+                node.held -= 1;
+            }
+        }
+    }
+}
+
+impl Drop for Hold<'_> {
+    fn drop(&mut self) {
+        if !self.ids.is_empty() {
+            let mut state = self.state.lock();
+            self.release(&mut state);
+        }
+    }
+}
+
+impl std::fmt::Debug for DynamicGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.inner.state.lock();
+        f.debug_struct("DynamicGraph")
+            .field("workers", &self.inner.workers)
+            .field("config", &self.inner.config)
+            .field("tracked", &state.nodes.len())
+            .field("running", &state.running)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Run `task` in `sandbox` with the outputs of its dependencies, retrying
+/// as its policy says, after it was ready for `wait`
+async fn run(
+    task: &Arc<dyn Task>,
+    inputs: HashMap<String, TaskOutput>,
+    wait: Duration,
+    seed: u64,
+    sandbox: &Sandbox,
+) -> (TaskReport, Option<TaskOutput>) {
+    let TaskPolicy { timeout, retry, .. } = task.policy();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut duration = Duration::ZERO;
+    let mut backoffs = Vec::new();
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        let ctx = TaskContext::new(inputs.clone(), attempts, CancellationToken::new());
+        let started = Instant::now();
+        let result = attempt(task, ctx, timeout, sandbox).await;
+        duration += started.elapsed();
+        match result {
+            Err(err) if retry.should_retry(attempts, &err) => {
+                let backoff = retry.backoff(attempts, &err, &mut rng);
+                tracing::debug!("Task {} attempt {} failed: {}", task.id(), attempts, err);
+                backoffs.push(backoff);
+                tokio::time::sleep(backoff).await;
+            },
+            result => break result,
+        }
+    };
+    let (state, path, output, error) = match result {
+        Ok(output) => (TaskState::Succeeded, Some(OutputPath::Primary), Some(output), None),
+        Err(err) => {
+            tracing::warn!("Task {} failed: {}", task.id(), err);
+            (TaskState::Failed, None, None, Some(err))
+        },
+    };
+    let report = TaskReport {
+        id: task.id().to_string(),
+        state,
+        duration,
+        attempts,
+        backoffs,
+        path,
+        error,
+        wait,
+        throttled: Duration::ZERO,
+    };
+    (report, output)
+}
+
+/// Report of task `id`, skipped as a dependency did not succeed
+fn skipped(id: String) -> TaskReport {
+    TaskReport {
+        id,
+        state: TaskState::Skipped,
+        duration: Duration::ZERO,
+        attempts: 0,
+        backoffs: Vec::new(),
+        path: None,
+        error: None,
+        wait: Duration::ZERO,
+        throttled: Duration::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::executor::Executor;
+    use crate::FrameworkConfig;
+
+    /// Waits for `gate` to open if it has one, logs its start in `starts`,
+    /// then sums its dependencies' outputs plus one, or fails if told to
+    struct Job {
+        id: String,
+        dependencies: Vec<String>,
+        gate: Option<Arc<Semaphore>>,
+        fail: bool,
+        starts: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Job {
+        fn new(id: impl Into<String>, dependencies: &[&str]) -> Self {
+            Self {
+                id: id.into(),
+                dependencies: dependencies.iter().map(|id| id.to_string()).collect(),
+                gate: None,
+                fail: false,
+                starts: Arc::default(),
+            }
+        }
+
+        fn gated(mut self, gate: &Arc<Semaphore>) -> Self {
+            self.gate = Some(Arc::clone(gate));
+            self
+        }
+
+        fn logged(mut self, starts: &Arc<Mutex<Vec<String>>>) -> Self {
+            self.starts = Arc::clone(starts);
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Task for Job {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.dependencies.clone()
+        }
+
+        async fn run(&self, ctx: TaskContext) -> Result<TaskOutput> {
+            if let Some(gate) = &self.gate {
+                let _open = gate.acquire().await;
+            }
+            self.starts.lock().push(self.id.clone());
+            if self.fail {
+                return Err(SystemError::internal(format!("{} failed", self.id), None));
+            }
+            let mut sum = 1_u64;
+            for dependency in &self.dependencies {
+                sum += ctx.output::<u64>(dependency)?;
+            }
+            Ok(TaskOutput::new(sum))
+        }
+    }
+
+    fn graph(workers: usize, limits: DynamicGraphConfig) -> DynamicGraph {
+        let config = FrameworkConfig {
+            workers,
+            ..FrameworkConfig::default()
+        };
+        Executor::new(&config).unwrap().dynamic_graph(limits).unwrap()
+    }
+
+    fn limits(queue_capacity: usize, source_capacity: usize, retained: usize) -> DynamicGraphConfig {
+        DynamicGraphConfig {
+            queue_capacity,
+            source_capacity,
+            retained_reports: retained,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_and_failures() {
+        let graph = graph(4, DynamicGraphConfig::default());
+        let mut completions = graph.subscribe();
+        graph.submit_task("s", Job::new("a", &[])).await.unwrap();
+        graph.submit_task("s", Job::new("b", &["a"])).await.unwrap();
+        graph.submit_task("s", Job::new("c", &["a", "b"])).await.unwrap();
+        graph.drain().await;
+        assert_eq!(graph.output("c").unwrap().downcast_ref::<u64>(), Some(&4));
+        // Depending on a task that already finished
+        graph.submit_task("s", Job::new("d", &["c"])).await.unwrap();
+        graph.drain().await;
+        assert_eq!(graph.output("d").unwrap().downcast_ref::<u64>(), Some(&5));
+
+        let failing = Job { fail: true, ..Job::new("e", &[]) };
+        graph.submit_task("s", failing).await.unwrap();
+        graph.submit_task("s", Job::new("f", &["e"])).await.unwrap();
+        graph.submit_task("s", Job::new("g", &["f"])).await.unwrap();
+        graph.drain().await;
+        assert_eq!(graph.report("e").unwrap().state, TaskState::Failed);
+        assert_eq!(graph.report("f").unwrap().state, TaskState::Skipped);
+        assert_eq!(graph.report("g").unwrap().state, TaskState::Skipped);
+        assert_eq!(graph.live(), 0);
+
+        let mut finished = Vec::new();
+        while let Ok(completion) = completions.try_recv() {
+            assert_eq!(completion.source, "s");
+            finished.push(completion.report.id.clone());
+        }
+        finished.sort();
+        assert_eq!(finished, ["a", "b", "c", "d", "e", "f", "g"]);
+
+        let unknown = graph.submit_task("s", Job::new("h", &["ghost"])).await.unwrap_err();
+        assert!(matches!(unknown, SystemError::NotFound { .. }), "{unknown}");
+        let duplicate = graph.submit_task("s", Job::new("a", &[])).await.unwrap_err();
+        assert!(matches!(duplicate, SystemError::Validation { .. }), "{duplicate}");
+        assert_eq!(graph.live(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_submit_waits_while_queue_is_full() {
+        let gate = Arc::new(Semaphore::new(0));
+        let graph = graph(1, limits(2, 2, 16));
+        // One running and two pending fill the worker and the queue
+        for id in ["a", "b", "c"] {
+            graph.submit_task("s", Job::new(id, &[]).gated(&gate)).await.unwrap();
+        }
+        tokio::task::yield_now().await;
+        let submit = graph.submit_task("t", Job::new("d", &[]));
+        let mut submit = Box::pin(submit);
+        assert!((&mut submit).now_or_never().is_none());
+
+        gate.add_permits(3);
+        submit.await.unwrap();
+        graph.drain().await;
+        assert_eq!(graph.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_sources_share_the_queue_and_take_turns() {
+        let gate = Arc::new(Semaphore::new(0));
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let graph = graph(1, limits(16, 3, 16));
+        graph.submit_task("gate", Job::new("blocker", &[]).gated(&gate)).await.unwrap();
+        tokio::task::yield_now().await;
+        for id in ["a1", "a2", "a3"] {
+            graph.submit_task("noisy", Job::new(id, &[]).logged(&starts)).await.unwrap();
+        }
+        // The noisy source used up its share, the quiet one did not
+        let mut noisy = Box::pin(graph.submit_task("noisy", Job::new("a4", &[]).logged(&starts)));
+        assert!((&mut noisy).now_or_never().is_none());
+        let quiet = graph.submit_task("quiet", Job::new("b1", &[]).logged(&starts));
+        assert!(quiet.now_or_never().is_some());
+
+        gate.add_permits(1);
+        noisy.await.unwrap();
+        graph.drain().await;
+        assert_eq!(*starts.lock(), ["a1", "b1", "a2", "a3", "a4"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_continuous_ingestion_stays_bounded() {
+        const TASKS: usize = 10_000;
+        let (workers, queue, retained) = (4, 32, 64);
+        let graph = graph(workers, limits(queue, 16, retained));
+        let mut completions = graph.subscribe();
+        let observer = tokio::spawn({
+            let graph = graph.clone();
+            async move {
+                let (mut seen, mut peak) = (0, 0);
+                while seen < TASKS {
+                    completions.recv().await.unwrap();
+                    seen += 1;
+                    peak = peak.max(graph.len());
+                }
+                (seen, peak)
+            }
+        });
+        let producers: Vec<_> = (0..4)
+            .map(|producer| {
+                let graph = graph.clone();
+                tokio::spawn(async move {
+                    for i in 0..TASKS / 4 {
+                        let job = Job::new(format!("p{producer}-{i}"), &[]);
+                        graph.submit_task(&producer.to_string(), job).await?;
+                    }
+                    Ok::<_, SystemError>(())
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.await.unwrap().unwrap();
+        }
+        graph.drain().await;
+        let (seen, peak) = observer.await.unwrap();
+        assert_eq!(seen, TASKS);
+        assert!(peak <= queue + workers + retained, "tracked {peak} tasks at once");
+        assert!(graph.len() <= retained);
+    }
+
+    #[test]
+    fn test_config_limits_must_be_positive() {
+        assert!(DynamicGraphConfig::default().validate().is_ok());
+        let err = limits(1, 0, 1).validate().unwrap_err();
+        assert!(matches!(err, SystemError::Config { .. }), "{err}");
+    }
+}
@@ -1 +1,3384 @@
 //! Executor module
+//!
+//! [`TaskGroup`] runs a set of related tasks so that their results, panics
+//! and cancellation are handled together instead of task by task.
+//! [`Executor`] runs a [`TaskGraph`], each task once its dependencies have
+//! succeeded, retrying and falling back as its
+//! [`TaskPolicy`](crate::scheduler::TaskPolicy) says;
+//! [`Executor::run_graph_detached`] returns a [`RunHandle`] to cancel the
+//! run with, and [`Executor::explain`] plans a run without starting it.
+//! Every task runs sandboxed: its panics become failures of the task,
+//! [`ExecutionKind::Blocking`] tasks run on a blocking pool of their own,
+//! and [`Executor::with_watchdog`] catches async tasks blocking the runtime.
+//! Runs are traced and counted in metrics, and [`Executor::status`] tells
+//! what the graphs being run are doing. [`Executor::run_deterministic`]
+//! runs a graph one task at a time in a reproducible order, which
+//! [`Executor::run_replay`] repeats.
+//! [`WorkStealingPool`]
+//! runs fine-grained closures on threads, each with its own queue, that
+//! steal from each other when idle; the `executor` benchmark compares it
+//! with a single shared queue.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+use futures::FutureExt;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use serde::Serialize;
+use shared_core::{ResourceGovernor, Result, SystemError};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::checkpoint::{self, CheckpointStore, Lineage};
+use crate::communication::{Closer, Streams};
+use crate::dynamic::{DynamicGraph, DynamicGraphConfig};
+use crate::replay::{self, Choices, DecisionLog};
+use crate::scheduler::{
+    ExecutionKind, OperationPriority, Task, TaskContext, TaskGraph, TaskOutput,
+};
+use crate::status::{ExecutorStatus, RunTracker, Runs, TaskPhase};
+use crate::{FrameworkConfig, SchedulingPolicy};
+
+/// Tasks of a group, tagged with their spawn order
+struct Tasks<T> {
+    set: JoinSet<(usize, Result<T>)>,
+    spawned: usize,
+}
+
+/// A group of tasks awaited together
+///
+/// A task that panics or is cancelled yields an `Err` instead of
+/// unwinding into the caller. Dropping the group aborts its remaining
+/// tasks.
+pub struct TaskGroup<T: Send + 'static> {
+    tasks: Mutex<Tasks<T>>,
+    token: CancellationToken,
+    cancel_on_error: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> TaskGroup<T> {
+    /// Create an empty group
+    pub fn new() -> Self {
+        Self {
+            tasks: Mutex::new(Tasks {
+                set: JoinSet::new(),
+                spawned: 0,
+            }),
+            token: CancellationToken::new(),
+            cancel_on_error: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Spawn `task` on the current Tokio runtime as part of the group
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let token = self.token.clone();
+        let cancel_on_error = Arc::clone(&self.cancel_on_error);
+        let mut tasks = self.tasks.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = tasks.spawned;
+        tasks.spawned += 1;
+        tasks.set.spawn(async move {
+            let result = tokio::select! {
+                biased;
+                _ = token.cancelled() => Err(cancelled()),
+                result = catch_panic(task) => result,
+            };
+            if result.is_err() && cancel_on_error.load(Ordering::SeqCst) {
+                token.cancel();
+            }
+            (index, result)
+        });
+    }
+
+    /// Cancel every other task as soon as any task returns `Err`
+    ///
+    /// Applies to tasks spawned before and after the call. Cancelled tasks
+    /// yield a `Concurrency` error.
+    pub fn cancel_on_first_error(&mut self) {
+        self.cancel_on_error.store(true, Ordering::SeqCst);
+    }
+
+    /// Cancel every task still running
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Number of tasks not yet awaited
+    pub fn len(&self) -> usize {
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner).set.len()
+    }
+
+    /// Whether every task has been awaited
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Await every task, returning their results in spawn order
+    pub async fn wait_all(self) -> Vec<Result<T>> {
+        let mut tasks = self.tasks.into_inner().unwrap_or_else(PoisonError::into_inner);
+        let mut results = Vec::with_capacity(tasks.set.len());
+        let mut lost = Vec::new();
+        while let Some(joined) = tasks.set.join_next().await {
+            match joined {
+                Ok(result) => results.push(result),
+                Err(err) => lost.push(Err(join_failed(err))),
+            }
+        }
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).chain(lost).collect()
+    }
+
+    /// Await the first task to complete
+    ///
+    /// Returns its result and the group of the remaining tasks. The result
+    /// is an `InvalidState` error if the group has no tasks left.
+    pub async fn wait_first(mut self) -> (Result<T>, TaskGroup<T>) {
+        let tasks = self.tasks.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let joined = tasks.set.join_next().await;
+        let result = match joined {
+            Some(Ok((_, result))) => result,
+            Some(Err(err)) => Err(join_failed(err)),
+            None => Err(SystemError::InvalidState {
+                message: "task group has no tasks to wait for".to_string(),
+                current_state: Some("empty".to_string()),
+                expected_state: Some("running".to_string()),
+            }),
+        };
+        (result, self)
+    }
+}
+
+impl<T: Send + 'static> Default for TaskGroup<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + 'static> std::fmt::Debug for TaskGroup<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskGroup")
+            .field("pending", &self.len())
+            .field("cancelled", &self.token.is_cancelled())
+            .field("cancel_on_error", &self.cancel_on_error.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+/// What [`Executor::run_graph`] does when a task fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum ErrorPolicy {
+    /// Cancel the running tasks and start no others
+    #[default]
+    FailFast,
+    /// Keep running every task that does not depend on a failed one
+    Continue,
+}
+
+/// How a task of a graph ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TaskState {
+    /// Ran and returned an output, or its fallback did
+    Succeeded,
+    /// Ran and returned an error, or panicked
+    Failed,
+    /// Was running when another task failed under [`ErrorPolicy::FailFast`],
+    /// or when the run was cancelled
+    Cancelled,
+    /// Never ran, because a task failed before its dependencies succeeded,
+    /// or because it is a fallback that was not needed
+    Skipped,
+}
+
+/// What the watchdog of [`Executor::with_watchdog`] does with an async task
+/// that blocked the runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum WatchdogAction {
+    /// Log a warning and let the task carry on
+    #[default]
+    Log,
+    /// Log a warning and fail the attempt with a `Concurrency` error
+    Fail,
+}
+
+/// Which task produced the output of a task of a graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OutputPath {
+    /// The task itself
+    Primary,
+    /// The task's fallback, after every attempt at the task failed
+    Fallback,
+    /// The task's checkpoint, restored instead of running the task
+    Checkpoint,
+}
+
+/// How one task of a graph ran
+#[derive(Debug, Serialize)]
+pub struct TaskReport {
+    /// Task identifier
+    pub id: String,
+    /// How the task ended
+    pub state: TaskState,
+    /// Time the task ran for over all its attempts, zero if it never
+    /// started
+    pub duration: Duration,
+    /// Times the task was started
+    pub attempts: u32,
+    /// Time waited before each retry
+    pub backoffs: Vec<Duration>,
+    /// Which task produced the output, if the task has one
+    pub path: Option<OutputPath>,
+    /// Why the task, or its last attempt before falling back, failed
+    pub error: Option<SystemError>,
+    /// Time the task was ready but not started, over all its attempts
+    pub wait: Duration,
+    /// Time the task was held back by the executor's governor, over all its
+    /// attempts: ready while the governor was paused or its RAM reservation
+    /// did not fit, or started and waiting for a permit
+    pub throttled: Duration,
+}
+
+/// Where a graph run is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RunStatus {
+    /// Tasks are being started
+    Running,
+    /// Cancelled; running and cleanup tasks are finishing
+    Cancelling,
+    /// Every task succeeded
+    Succeeded,
+    /// Some task did not succeed
+    Failed,
+    /// Cancelled before every task succeeded
+    Cancelled,
+}
+
+impl RunStatus {
+    const ALL: [RunStatus; 5] =
+        [Self::Running, Self::Cancelling, Self::Succeeded, Self::Failed, Self::Cancelled];
+
+    fn from_u8(status: u8) -> Self {
+        Self::ALL[usize::from(status)]
+    }
+}
+
+/// A graph running in the background, from
+/// [`Executor::run_graph_detached`]
+///
+/// Awaiting the handle gives the report of the run. Dropping it leaves the
+/// run going.
+pub struct RunHandle {
+    token: CancellationToken,
+    status: Arc<AtomicU8>,
+    run: tokio::task::JoinHandle<Result<GraphReport>>,
+}
+
+impl RunHandle {
+    /// Cancel the run
+    ///
+    /// No task starts afterwards but those whose policy sets `always_run`.
+    /// Running tasks find the cancellation token of their context
+    /// cancelled; those still running after the executor's cancel grace
+    /// period are aborted. Tasks stopping with an error are reported as
+    /// cancelled, tasks that never started as skipped.
+    pub fn cancel(&self) {
+        let _ = self.status.compare_exchange(
+            RunStatus::Running as u8,
+            RunStatus::Cancelling as u8,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+        self.token.cancel();
+    }
+
+    /// Where the run is
+    pub fn status(&self) -> RunStatus {
+        RunStatus::from_u8(self.status.load(Ordering::SeqCst))
+    }
+}
+
+impl Future for RunHandle {
+    type Output = Result<GraphReport>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.run.poll_unpin(cx).map(|joined| joined.unwrap_or_else(|err| Err(join_failed(err))))
+    }
+}
+
+impl std::fmt::Debug for RunHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunHandle").field("status", &self.status()).finish_non_exhaustive()
+    }
+}
+
+/// Result of [`Executor::run_graph`]
+#[derive(Debug, Serialize)]
+pub struct GraphReport {
+    /// How the run ended
+    pub status: RunStatus,
+    /// Every task, in the order it was added to the graph
+    pub tasks: Vec<TaskReport>,
+    /// Time the whole graph took
+    pub duration: Duration,
+    /// The chain of dependent tasks with the longest total duration, first
+    /// task first
+    pub critical_path: Vec<String>,
+    /// Outputs of the tasks that succeeded, by task identifier
+    #[serde(skip)]
+    pub outputs: HashMap<String, TaskOutput>,
+    /// Whether the run was one of
+    /// [`Executor::run_deterministic`] or [`Executor::run_replay`], whose
+    /// durations are virtual
+    pub deterministic: bool,
+}
+
+impl GraphReport {
+    /// Whether every task succeeded
+    pub fn is_success(&self) -> bool {
+        self.tasks.iter().all(|task| task.state == TaskState::Succeeded)
+    }
+
+    /// Report of task `id`
+    pub fn task(&self, id: &str) -> Option<&TaskReport> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+
+    /// Output of task `id` as a `T`, if it succeeded with one
+    pub fn output<T: std::any::Any>(&self, id: &str) -> Option<&T> {
+        self.outputs.get(id)?.downcast_ref()
+    }
+
+    /// Sum of the durations of the tasks on the critical path
+    pub fn critical_path_duration(&self) -> Duration {
+        self.critical_path
+            .iter()
+            .filter_map(|id| self.task(id))
+            .map(|task| task.duration)
+            .sum()
+    }
+}
+
+/// Upper bounds of the buckets of a [`WaitHistogram`]
+pub const WAIT_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+];
+
+/// How long tasks waited between becoming ready and starting
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WaitHistogram {
+    /// Tasks per bucket: `counts[i]` waited up to `WAIT_BUCKETS[i]`, and
+    /// longer than the bucket before; the last bucket counts longer waits
+    pub counts: [u64; WAIT_BUCKETS.len() + 1],
+    /// Total time waited
+    pub total: Duration,
+    /// Longest wait
+    pub max: Duration,
+}
+
+impl WaitHistogram {
+    /// Number of waits recorded
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Average wait, zero if none was recorded
+    pub fn mean(&self) -> Duration {
+        let count = u32::try_from(self.count()).unwrap_or(u32::MAX);
+        self.total.checked_div(count).unwrap_or_default()
+    }
+
+    fn record(&mut self, wait: Duration) {
+        let bucket = WAIT_BUCKETS.iter().position(|&bound| wait <= bound);
+        self.counts[bucket.unwrap_or(WAIT_BUCKETS.len())] += 1;
+        self.total += wait;
+        self.max = self.max.max(wait);
+    }
+}
+
+/// How an [`Executor`] used the slots of one resource class
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ClassStats {
+    /// Tasks of the class a run may have running at once
+    pub capacity: usize,
+    /// Tasks of the class running now, over all runs
+    pub running: usize,
+    /// Tasks of the class started
+    pub started: u64,
+    /// Total time the class's slots were held
+    pub busy: Duration,
+    /// How long the class's tasks waited to start
+    pub wait_times: WaitHistogram,
+}
+
+impl ClassStats {
+    /// Share of the class's slots held now, above 1 if several runs hold
+    /// them or streaming tasks went past the capacity
+    pub fn utilization(&self) -> f64 {
+        self.running as f64 / self.capacity as f64
+    }
+}
+
+/// How an [`Executor`] scheduled the tasks of all its runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SchedulerStats {
+    /// Per declared resource class, by name
+    pub classes: BTreeMap<String, ClassStats>,
+    /// How long every task waited to start
+    pub wait_times: WaitHistogram,
+}
+
+/// How [`Executor::explain`] expects a graph to run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutionPlan {
+    /// Every task, each after its dependencies and otherwise by wave, then
+    /// in the order it was added to the graph
+    pub order: Vec<String>,
+    /// Tasks able to start together, each wave once those before it are
+    /// done; fallbacks, which only run in place of another task, are in
+    /// none
+    pub waves: Vec<PlanWave>,
+    /// The chain of dependent tasks with the largest total
+    /// [`Task::cost_hint`], first task first
+    pub critical_path: Vec<String>,
+    /// Sum of the cost hints of the tasks on the critical path
+    pub critical_path_cost: Duration,
+    /// Tasks of each resource class, by class name
+    pub placements: BTreeMap<String, Vec<String>>,
+}
+
+/// Tasks of an [`ExecutionPlan`] that could run at the same time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PlanWave {
+    /// Identifiers of the tasks, in the order they were added to the graph
+    pub tasks: Vec<String>,
+    /// Most of the tasks the executor would run at once, within its worker
+    /// and resource class limits
+    pub parallelism: usize,
+}
+
+/// How far a task of a running graph got
+#[derive(Default)]
+struct Progress {
+    state: Option<TaskState>,
+    started_at: Option<Instant>,
+    duration: Duration,
+    attempts: u32,
+    backoffs: Vec<Duration>,
+    path: Option<OutputPath>,
+    error: Option<SystemError>,
+    wait: Duration,
+    throttled: Duration,
+}
+
+/// How often ready tasks held back by the governor of
+/// [`Executor::with_admission`] are looked at again
+const ADMISSION_POLL: Duration = Duration::from_millis(10);
+
+/// Default of [`Executor::with_cancel_grace`]
+pub const DEFAULT_CANCEL_GRACE: Duration = Duration::from_secs(10);
+
+/// Runs task graphs on a fixed number of concurrent slots
+#[derive(Clone)]
+pub struct Executor {
+    workers: usize,
+    policy: ErrorPolicy,
+    backoff_seed: Option<u64>,
+    cancel_grace: Duration,
+    classes: HashMap<String, usize>,
+    aging: Option<Duration>,
+    stats: Arc<parking_lot::Mutex<SchedulerStats>>,
+    governor: Option<Arc<ResourceGovernor>>,
+    sandbox: Sandbox,
+    runs: Arc<Runs>,
+}
+
+impl std::fmt::Debug for Executor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Executor")
+            .field("workers", &self.workers)
+            .field("policy", &self.policy)
+            .field("cancel_grace", &self.cancel_grace)
+            .field("classes", &self.classes)
+            .field("aging", &self.aging)
+            .field("governed", &self.governor.is_some())
+            .field("watchdog", &self.sandbox.watchdog)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Executor {
+    /// Create an executor running up to `config.workers` tasks at once,
+    /// and up to the capacity of each of `config.resource_classes` of the
+    /// tasks of that class
+    pub fn new(config: &FrameworkConfig) -> Result<Self> {
+        if config.workers == 0 {
+            return Err(SystemError::config("workers must be > 0", Some("workers".to_string())));
+        }
+        if config.blocking_workers == 0 {
+            return Err(SystemError::config(
+                "blocking workers must be > 0",
+                Some("blocking_workers".to_string()),
+            ));
+        }
+        if let Some((class, _)) = config.resource_classes.iter().find(|(_, &slots)| slots == 0) {
+            return Err(SystemError::config(
+                "resource class capacity must be > 0",
+                Some(format!("resource_classes.{class}")),
+            ));
+        }
+        if config.priority_aging == Some(Duration::ZERO) {
+            return Err(SystemError::config(
+                "priority aging must be positive",
+                Some("priority_aging".to_string()),
+            ));
+        }
+        let classes = config
+            .resource_classes
+            .iter()
+            .map(|(class, &capacity)| {
+                (class.clone(), ClassStats { capacity, ..ClassStats::default() })
+            })
+            .collect();
+        Ok(Self {
+            workers: config.workers,
+            policy: ErrorPolicy::default(),
+            backoff_seed: None,
+            cancel_grace: DEFAULT_CANCEL_GRACE,
+            classes: config.resource_classes.clone(),
+            aging: config.priority_aging,
+            stats: Arc::new(parking_lot::Mutex::new(SchedulerStats {
+                classes,
+                ..SchedulerStats::default()
+            })),
+            governor: None,
+            sandbox: Sandbox {
+                blocking: Arc::new(Semaphore::new(config.blocking_workers)),
+                watchdog: None,
+            },
+            runs: Arc::default(),
+        })
+    }
+
+    /// How the tasks of every run so far were scheduled
+    pub fn stats(&self) -> SchedulerStats {
+        self.stats.lock().clone()
+    }
+
+    /// What the graphs being run are doing, and the scheduler stats
+    pub fn status(&self) -> ExecutorStatus {
+        ExecutorStatus {
+            graphs: self.runs.snapshot(),
+            stats: self.stats(),
+        }
+    }
+
+    /// Set what happens when a task fails
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set how long tasks may keep running once their run is cancelled
+    /// before they are aborted
+    pub fn with_cancel_grace(mut self, grace: Duration) -> Self {
+        self.cancel_grace = grace;
+        self
+    }
+
+    /// Seed retry backoff jitter from `governor`, so that the backoffs of
+    /// a deterministic governor are the same from run to run
+    pub fn with_governor(mut self, governor: &ResourceGovernor) -> Self {
+        self.backoff_seed = governor.is_deterministic().then(|| governor.get_rng().next_u64());
+        self
+    }
+
+    /// Admit tasks through `governor`
+    ///
+    /// Every attempt runs under a permit acquired with the task identifier
+    /// as its label. Ready tasks do not start while the governor is paused,
+    /// nor while their [`Task::estimated_ram`] would take the governor's
+    /// RAM usage past its cap; the estimate is reserved with the governor
+    /// while the task runs. A graph with a task estimated to need more RAM
+    /// than the cap fails to run with a `Validation` error.
+    pub fn with_admission(mut self, governor: Arc<ResourceGovernor>) -> Self {
+        self.governor = Some(governor);
+        self
+    }
+
+    /// Watch async tasks for blocking the runtime, taking `action` on any
+    /// task a single poll of which takes longer than `threshold`
+    ///
+    /// A poll cannot be interrupted, so the watchdog acts once it returns.
+    /// Move blocking work to [`ExecutionKind::Blocking`] tasks instead.
+    pub fn with_watchdog(mut self, threshold: Duration, action: WatchdogAction) -> Self {
+        self.sandbox.watchdog = Some((threshold, action));
+        self
+    }
+
+    /// Run every task of `graph` once its dependencies have succeeded
+    ///
+    /// Ready tasks start by [`Task::priority`], highest first, then in the
+    /// order they were added to the graph; a ready task's priority is
+    /// boosted one level every `priority_aging` it waits. Tasks with a
+    /// [`Task::resource_class`] also wait for a free slot of their class,
+    /// which must be declared in the config, or the run fails with a
+    /// `Validation` error; a task waiting for a slot does not hold back
+    /// tasks of other classes. Each task gets the outputs of its
+    /// dependencies in its [`TaskContext`].
+    /// A task failing with a retriable error is started again after a
+    /// backoff, freeing its slot meanwhile, until its policy's attempts run
+    /// out; then its fallback, if it has one, runs in its place. Tasks
+    /// whose policy sets `always_run` also run when the graph stops early
+    /// or their dependencies fail, once every other task is done. Tasks
+    /// streaming to each other through [`TaskGraph::connect`] channels
+    /// start together once all their dependencies have succeeded, even
+    /// past the worker and class limits, as a producer waits on its
+    /// consumer. A task that panics fails with a `Concurrency` error
+    /// carrying the panic message. Task
+    /// failures are reported in the [`GraphReport`], not as an error; an
+    /// invalid graph fails with the error of [`TaskGraph::validate`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub async fn run_graph(&self, graph: TaskGraph) -> Result<GraphReport> {
+        let status = AtomicU8::new(RunStatus::Running as u8);
+        self.run(graph, CancellationToken::new(), &status, None).await
+    }
+
+    /// Run `graph` as [`run_graph`](Self::run_graph) does, saving a
+    /// checkpoint of every task with a [`Task::fingerprint`] in `store` as
+    /// it succeeds, and restoring tasks from theirs instead of running them
+    ///
+    /// A task is restored if its fingerprint and the checkpoints of its
+    /// dependencies are those it ran with; otherwise it runs again, and so
+    /// do the tasks downstream of it. Tasks connected by streams, run in
+    /// place of another or whose outputs cannot be saved are never
+    /// restored, nor are the tasks depending on them. Restored tasks
+    /// report [`OutputPath::Checkpoint`] as their path and no attempts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub async fn resume(
+        &self,
+        graph: TaskGraph,
+        store: &dyn CheckpointStore,
+    ) -> Result<GraphReport> {
+        let status = AtomicU8::new(RunStatus::Running as u8);
+        self.run(graph, CancellationToken::new(), &status, Some(store)).await
+    }
+
+    /// Run `graph` one task at a time, starting of the ready tasks of the
+    /// highest priority one drawn from a generator seeded with `seed`
+    ///
+    /// Runs with the same seed start tasks in the same order, as long as
+    /// the tasks fail alike; retry backoffs are drawn from the same
+    /// generator. Time is virtual, as the [`replay`](crate::replay) module
+    /// explains, and the report is marked
+    /// [`deterministic`](GraphReport::deterministic). The error policy
+    /// applies; resource classes, priority aging and the governor do not.
+    /// Returns the report with the log of every choice made, for
+    /// [`run_replay`](Self::run_replay). A graph with stream connections
+    /// fails with a `Validation` error, as does an invalid graph.
+    pub async fn run_deterministic(
+        &self,
+        graph: TaskGraph,
+        seed: u64,
+    ) -> Result<(GraphReport, DecisionLog)> {
+        replay::run_serial(graph, self.policy, &self.sandbox, seed, replay::seeded(seed)).await
+    }
+
+    /// Run `graph` again as the deterministic run that made `log` did,
+    /// following its choices
+    ///
+    /// Fails with an `InvalidState` error if the tasks of `graph` or their
+    /// dependencies differ from those `log` was made with, or once the run
+    /// departs from `log`, e.g. because a task that succeeded now fails.
+    pub async fn run_replay(&self, graph: TaskGraph, log: &DecisionLog) -> Result<GraphReport> {
+        let choices = Choices::Replay { log, next: 0 };
+        let (report, _) =
+            replay::run_serial(graph, self.policy, &self.sandbox, log.seed, choices).await?;
+        Ok(report)
+    }
+
+    /// Start running `graph` in the background as
+    /// [`run_graph`](Self::run_graph) does, returning a handle to cancel
+    /// the run or await its report
+    ///
+    /// An invalid graph fails with the error of [`TaskGraph::validate`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn run_graph_detached(&self, graph: TaskGraph) -> Result<RunHandle> {
+        graph.validate()?;
+        let token = CancellationToken::new();
+        let status = Arc::new(AtomicU8::new(RunStatus::Running as u8));
+        let executor = self.clone();
+        let run = tokio::spawn({
+            let token = token.clone();
+            let status = Arc::clone(&status);
+            async move { executor.run(graph, token, &status, None).await }
+        });
+        Ok(RunHandle { token, status, run })
+    }
+
+    /// Start a [`DynamicGraph`] running tasks as they are submitted, up to
+    /// `workers` at once, with the limits of `config`
+    ///
+    /// Backoff jitter is seeded as for graph runs; the error policy,
+    /// resource classes and governor do not apply. An invalid `config`
+    /// fails with a `Config` error.
+    pub fn dynamic_graph(&self, config: DynamicGraphConfig) -> Result<DynamicGraph> {
+        DynamicGraph::new(self.workers, self.backoff_seed, self.sandbox.clone(), config)
+    }
+
+    /// Plan how `graph` would run, without running any of it
+    ///
+    /// Tasks are grouped into waves by the longest chain of dependencies
+    /// leading to them, and the critical path is weighed with their
+    /// [`Task::cost_hint`]s. A graph that could not run fails as
+    /// [`run_graph`](Self::run_graph) would, with a `Validation` error.
+    pub fn explain(&self, graph: &TaskGraph) -> Result<ExecutionPlan> {
+        let dependencies = graph.dependencies()?;
+        let fallbacks = graph.fallbacks(&dependencies)?;
+        graph.stream_groups(&dependencies)?;
+        let admission = Admission::new(self, graph)?;
+        let count = graph.tasks.len();
+        let mut standby = vec![false; count];
+        for &fallback in fallbacks.iter().flatten() {
+            standby[fallback] = true;
+        }
+        let mut depths = vec![None; count];
+        for task in 0..count {
+            depth(task, &dependencies, &mut depths);
+        }
+        let depths: Vec<usize> = depths.into_iter().map(Option::unwrap_or_default).collect();
+        let id = |task: usize| graph.tasks[task].id().to_string();
+
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by_key(|&task| (depths[task], task));
+        let wave_count = depths.iter().max().map_or(0, |&deepest| deepest + 1);
+        let mut waves = Vec::new();
+        for wave in 0..wave_count {
+            let tasks: Vec<usize> =
+                (0..count).filter(|&task| depths[task] == wave && !standby[task]).collect();
+            if tasks.is_empty() {
+                continue;
+            }
+            let mut per_class: HashMap<&str, usize> = HashMap::new();
+            let mut unclassed = 0;
+            for &task in &tasks {
+                match &admission.classes[task] {
+                    Some(class) => *per_class.entry(class).or_default() += 1,
+                    None => unclassed += 1,
+                }
+            }
+            let classed: usize =
+                per_class.iter().map(|(class, &tasks)| tasks.min(self.classes[*class])).sum();
+            waves.push(PlanWave {
+                tasks: tasks.into_iter().map(id).collect(),
+                parallelism: (unclassed + classed).min(self.workers),
+            });
+        }
+
+        let costs: Vec<Duration> = graph
+            .tasks
+            .iter()
+            .zip(&standby)
+            .map(|(task, &standby)| {
+                if standby {
+                    Duration::ZERO
+                } else {
+                    task.cost_hint().unwrap_or_default()
+                }
+            })
+            .collect();
+        let critical = critical_path(&costs, &dependencies);
+        let critical_path_cost = critical.iter().map(|&task| costs[task]).sum();
+        let mut placements: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (task, class) in admission.classes.iter().enumerate() {
+            if let Some(class) = class {
+                placements.entry(class.clone()).or_default().push(id(task));
+            }
+        }
+        Ok(ExecutionPlan {
+            order: order.into_iter().map(id).collect(),
+            waves,
+            critical_path: critical.into_iter().map(id).collect(),
+            critical_path_cost,
+            placements,
+        })
+    }
+
+    /// Run `graph` until it is done or `token` is cancelled, keeping
+    /// `status` up to date and checkpoints in `checkpoints`
+    ///
+    /// The run is traced in a `graph` span, each attempt at a task in a
+    /// `task` span within it, and its progress is visible in
+    /// [`status`](Self::status).
+    async fn run(
+        &self,
+        graph: TaskGraph,
+        token: CancellationToken,
+        status: &AtomicU8,
+        checkpoints: Option<&dyn CheckpointStore>,
+    ) -> Result<GraphReport> {
+        let tracker = self.runs.begin(graph.tasks.iter().map(|task| task.id().to_string()));
+        let span = tracing::info_span!("graph", graph_id = tracker.id(), tasks = graph.tasks.len());
+        let report = self
+            .execute(graph, token, status, checkpoints, &tracker)
+            .instrument(span)
+            .await?;
+        shared_core::histogram!(
+            "executor_graph_duration_seconds",
+            report.duration.as_secs_f64()
+        );
+        Ok(report)
+    }
+
+    /// Body of [`run`](Self::run), recording progress with `tracker`
+    async fn execute(
+        &self,
+        graph: TaskGraph,
+        token: CancellationToken,
+        status: &AtomicU8,
+        checkpoints: Option<&dyn CheckpointStore>,
+        tracker: &RunTracker,
+    ) -> Result<GraphReport> {
+        let dependencies = graph.dependencies()?;
+        let fallbacks = graph.fallbacks(&dependencies)?;
+        let peers = graph.stream_groups(&dependencies)?;
+        let policies: Vec<_> = graph.tasks.iter().map(|task| task.policy()).collect();
+        let priorities: Vec<_> = graph.tasks.iter().map(|task| task.priority()).collect();
+        let mut admission = Admission::new(self, &graph)?;
+        let mut rng = match self.backoff_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let started = Instant::now();
+        let count = graph.tasks.len();
+        let mut dependents = vec![Vec::new(); count];
+        for (task, task_dependencies) in dependencies.iter().enumerate() {
+            for &dependency in task_dependencies {
+                dependents[dependency].push(task);
+            }
+        }
+        let mut waiting_on: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+        // Fallbacks only run in place of another task
+        let mut standby = vec![false; count];
+        for &fallback in fallbacks.iter().flatten() {
+            standby[fallback] = true;
+        }
+        // Channel ends each task starts with, and the channels it closes
+        let mut streams: Vec<Streams> = (0..count).map(|_| Streams::default()).collect();
+        let mut closers: Vec<Vec<Closer>> = (0..count).map(|_| Vec::new()).collect();
+        let position = |id: &str| graph.tasks.iter().position(|task| task.id() == id);
+        for connection in &graph.connections {
+            let endpoints = connection.open();
+            // Validated above
+            let (Some(producer), Some(consumer)) =
+                (position(&connection.producer), position(&connection.consumer))
+            else {
+                continue;
+            };
+            let sender = endpoints.sender;
+            streams[producer].senders.insert(connection.consumer.clone(), sender);
+            let receiver = endpoints.receiver;
+            streams[consumer].receivers.lock().insert(connection.producer.clone(), receiver);
+            closers[producer].push(endpoints.closer);
+        }
+        // Each a task and the task run for it, itself or its fallback
+        let mut ready: Vec<(usize, usize)> = (0..count)
+            .filter(|&task| {
+                let can_start = |task: usize| waiting_on[task] == 0;
+                can_start(task) && !standby[task] && peers[task].iter().all(|&peer| can_start(peer))
+            })
+            .map(|task| (task, task))
+            .collect();
+        // When each ready task became ready, and since when the governor
+        // holds it back
+        let mut ready_at: Vec<Option<Instant>> = vec![None; count];
+        let mut throttled_since: Vec<Option<Instant>> = vec![None; count];
+        let mut outputs: Vec<Option<TaskOutput>> = vec![None; count];
+        // Of every task checkpointed or restored
+        let mut lineage: Vec<Option<Lineage>> = vec![None; count];
+        let mut progress: Vec<Progress> = (0..count).map(|_| Progress::default()).collect();
+        let mut running = JoinSet::new();
+        let mut retrying = JoinSet::new();
+        let mut failed = false;
+        let mut cancel_requested = false;
+        // When tasks still running after a cancellation are aborted
+        let mut abort_at = None;
+        // Set once only `always_run` tasks are left to run, each getting a
+        // token that is never cancelled
+        let mut cleanup: Option<CancellationToken> = None;
+
+        loop {
+            let now = Instant::now();
+            let mut stamps = vec![None; count];
+            for &(_, runner) in &ready {
+                stamps[runner] = Some(ready_at[runner].unwrap_or(now));
+            }
+            ready_at = stamps;
+            while running.len() < self.workers {
+                // Most urgent first, with the tasks it streams to and from,
+                // even past the worker and class limits
+                let Some(next) = self.next_ready(&ready, &ready_at, &priorities, &admission, now)
+                else {
+                    break;
+                };
+                let mut batch = vec![ready.remove(next)];
+                ready.retain(|&entry| {
+                    let connected = peers[batch[0].0].contains(&entry.0);
+                    if connected {
+                        batch.push(entry);
+                    }
+                    !connected
+                });
+                for (task, runner) in batch {
+                    let waited = ready_at[runner].take().map_or(Duration::ZERO, |at| now - at);
+                    progress[runner].wait += waited;
+                    if let Some(since) = throttled_since[runner].take() {
+                        progress[runner].throttled += now - since;
+                    }
+                    admission.take(runner, waited);
+                    let restored = checkpoints
+                        .filter(|_| runner == task && peers[task].is_empty())
+                        .and_then(|store| {
+                            let key = checkpoint_key(&graph, &dependencies, &lineage, task)?;
+                            let id = graph.tasks[task].id();
+                            let (saved, output_hash) = checkpoint::load(store, id, &key)?;
+                            match graph.tasks[task].restore_output(&saved) {
+                                Ok(output) => Some((output, Lineage { key, output_hash })),
+                                Err(err) => {
+                                    tracing::warn!("Failed to restore {}: {}", id, err);
+                                    None
+                                },
+                            }
+                        });
+                    if let Some((output, task_lineage)) = restored {
+                        tracing::debug!("Restored {} from its checkpoint", graph.tasks[task].id());
+                        lineage[task] = Some(task_lineage);
+                        progress[task].path = Some(OutputPath::Checkpoint);
+                        running.spawn(async move {
+                            (task, task, Ok(output), Duration::ZERO, Duration::ZERO)
+                        });
+                        continue;
+                    }
+                    let inputs = dependencies[task].iter().filter_map(|&dependency| {
+                        let output = outputs[dependency].clone()?;
+                        Some((graph.tasks[dependency].id().to_string(), output))
+                    });
+                    let run = &mut progress[runner];
+                    run.attempts += 1;
+                    run.started_at.get_or_insert_with(Instant::now);
+                    let cancellation = cleanup.as_ref().unwrap_or(&token).clone();
+                    let ctx = TaskContext::new(inputs.collect(), run.attempts, cancellation)
+                        .with_streams(std::mem::take(&mut streams[runner]));
+                    let closers = std::mem::take(&mut closers[runner]);
+                    let timeout = policies[runner].timeout;
+                    let runner_task = Arc::clone(&graph.tasks[runner]);
+                    let governor = self.governor.clone();
+                    let sandbox = self.sandbox.clone();
+                    let span = tracing::info_span!(
+                        "task",
+                        task_id = runner_task.id(),
+                        attempt = run.attempts
+                    );
+                    tracker.start(runner);
+                    shared_core::count!("executor_tasks_started_total", 1);
+                    running.spawn(
+                        async move {
+                            let admitted = Instant::now();
+                            let permit = match &governor {
+                                Some(governor) => governor
+                                    .acquire_permit_labeled(runner_task.id())
+                                    .await
+                                    .map(Some),
+                                None => Ok(None),
+                            };
+                            let throttled = admitted.elapsed();
+                            let started = Instant::now();
+                            let result = match permit {
+                                Ok(_permit) => attempt(&runner_task, ctx, timeout, &sandbox).await,
+                                Err(err) => Err(err),
+                            };
+                            for closer in closers {
+                                closer.close(result.as_ref().err());
+                            }
+                            (task, runner, result, started.elapsed(), throttled)
+                        }
+                        .instrument(span),
+                    );
+                }
+            }
+            let mut throttled = vec![None; count];
+            for &(_, runner) in &ready {
+                match (admission.is_throttled(runner), throttled_since[runner]) {
+                    (true, since) => throttled[runner] = Some(since.unwrap_or(now)),
+                    (false, Some(since)) => progress[runner].throttled += now - since,
+                    (false, None) => {},
+                }
+            }
+            throttled_since = throttled;
+            if running.is_empty() {
+                // Of aborted tasks, which never join with their runner
+                admission.release_all();
+            }
+            // Ready tasks the governor holds back are looked at again after
+            // a while, as nothing else wakes the loop when it lets them go
+            let throttling = throttled_since.iter().any(Option::is_some);
+            if running.is_empty() && retrying.is_empty() && !throttling {
+                // Cleanup tasks whose dependencies did not all succeed are
+                // left; they run now, in dependency order
+                let left: Vec<usize> = (0..count)
+                    .filter(|&task| {
+                        let run = &progress[task];
+                        policies[task].always_run
+                            && run.attempts == 0
+                            && run.state.is_none()
+                            && !standby[task]
+                    })
+                    .collect();
+                if cleanup.is_some() || left.is_empty() {
+                    break;
+                }
+                cleanup = Some(CancellationToken::new());
+                for &task in &left {
+                    waiting_on[task] =
+                        dependencies[task].iter().filter(|dep| left.contains(dep)).count();
+                }
+                ready = left
+                    .into_iter()
+                    .filter(|&task| waiting_on[task] == 0)
+                    .map(|task| (task, task))
+                    .collect();
+                continue;
+            }
+            tracker.load(ready.len(), running.len());
+            let joined = tokio::select! {
+                // Seen before the tasks it stops, which then count as cancelled
+                biased;
+                () = token.cancelled(), if !cancel_requested && cleanup.is_none() => {
+                    tracing::info!("Graph run cancelled, {} tasks running", running.len());
+                    cancel_requested = true;
+                    status.store(RunStatus::Cancelling as u8, Ordering::SeqCst);
+                    ready.clear();
+                    retrying.abort_all();
+                    abort_at = Some(tokio::time::Instant::now() + self.cancel_grace);
+                    continue;
+                },
+                Some(joined) = running.join_next() => joined,
+                Some(retry) = retrying.join_next() => {
+                    // Aborted retries were cancelled along with the graph
+                    if let Ok(entry) = retry {
+                        ready.push(entry);
+                        ready.sort_unstable();
+                    }
+                    continue;
+                },
+                () = tokio::time::sleep(ADMISSION_POLL), if throttling => continue,
+                () = sleep_until(abort_at), if abort_at.is_some() => {
+                    tracing::warn!("Aborting {} tasks ignoring cancellation", running.len());
+                    running.abort_all();
+                    abort_at = None;
+                    continue;
+                },
+                else => break,
+            };
+            // Panics are caught, so only aborted tasks fail to join
+            let Ok((task, runner, result, duration, throttled)) = joined else {
+                continue;
+            };
+            admission.release(runner);
+            progress[runner].duration += duration;
+            progress[runner].throttled += throttled;
+            let err = match result {
+                Ok(output) => {
+                    if runner != task {
+                        outputs[runner] = Some(output.clone());
+                        progress[runner].state = Some(TaskState::Succeeded);
+                        progress[runner].path = Some(OutputPath::Primary);
+                        tracker.set(runner, TaskPhase::Succeeded);
+                    }
+                    tracker.set(task, TaskPhase::Succeeded);
+                    shared_core::count!("executor_tasks_completed_total", 1);
+                    let restored = progress[task].path == Some(OutputPath::Checkpoint);
+                    if let (Some(store), false, true) = (checkpoints, restored, runner == task) {
+                        lineage[task] =
+                            save_checkpoint(store, &graph, &dependencies, &lineage, task, &output);
+                    }
+                    outputs[task] = Some(output);
+                    progress[task].state = Some(TaskState::Succeeded);
+                    if !restored {
+                        progress[task].path = Some(if runner == task {
+                            OutputPath::Primary
+                        } else {
+                            OutputPath::Fallback
+                        });
+                    }
+                    for &dependent in &dependents[task] {
+                        waiting_on[dependent] -= 1;
+                        let runs = match cleanup {
+                            Some(_) => policies[dependent].always_run,
+                            None => !standby[dependent] && !failed && !cancel_requested,
+                        };
+                        // Connected tasks start once they all can
+                        let group = &peers[dependent];
+                        if waiting_on[dependent] == 0
+                            && runs
+                            && group.iter().all(|&peer| waiting_on[peer] == 0)
+                        {
+                            let group = std::iter::once(dependent).chain(group.iter().copied());
+                            ready.extend(group.map(|task| (task, task)));
+                        }
+                    }
+                    ready.sort_unstable();
+                    continue;
+                },
+                Err(err) => err,
+            };
+            let id = graph.tasks[runner].id();
+            if cancel_requested && cleanup.is_none() {
+                tracing::debug!("Task {} stopped after cancellation: {}", id, err);
+                progress[runner].error = Some(err);
+                progress[runner].state = Some(TaskState::Cancelled);
+                progress[task].state = Some(TaskState::Cancelled);
+                tracker.set(runner, TaskPhase::Cancelled);
+                tracker.set(task, TaskPhase::Cancelled);
+                continue;
+            }
+            let run = &mut progress[runner];
+            let retry = &policies[runner].retry;
+            if retry.should_retry(run.attempts, &err) {
+                let wait = retry.backoff(run.attempts, &err, &mut rng);
+                tracing::debug!(
+                    "Task {} attempt {} failed, retrying in {:?}: {}",
+                    id,
+                    run.attempts,
+                    wait,
+                    err
+                );
+                run.backoffs.push(wait);
+                tracker.set(runner, TaskPhase::Pending);
+                retrying.spawn(async move {
+                    tokio::time::sleep(wait).await;
+                    (task, runner)
+                });
+                continue;
+            }
+            tracing::warn!("Task {} failed: {}", id, err);
+            run.state = Some(TaskState::Failed);
+            run.error = Some(err);
+            tracker.set(runner, TaskPhase::Failed);
+            shared_core::count!("executor_tasks_failed_total", 1);
+            match fallbacks[task] {
+                Some(fallback) if runner == task => {
+                    tracing::info!("Task {} falls back to {}", id, graph.tasks[fallback].id());
+                    ready.push((task, fallback));
+                    ready.sort_unstable();
+                    continue;
+                },
+                // The fallback failed too
+                Some(_) => {
+                    progress[task].state = Some(TaskState::Failed);
+                    tracker.set(task, TaskPhase::Failed);
+                },
+                None => {},
+            }
+            if self.policy == ErrorPolicy::FailFast && cleanup.is_none() {
+                failed = true;
+                ready.clear();
+                running.abort_all();
+                retrying.abort_all();
+            }
+        }
+
+        let tasks: Vec<TaskReport> = graph
+            .tasks
+            .iter()
+            .zip(progress)
+            .map(|(task, run)| {
+                // Aborted tasks had started; the rest never became ready
+                let (state, duration, error) = match (run.state, run.started_at) {
+                    (Some(state), _) => (state, run.duration, run.error),
+                    (None, Some(at)) => (TaskState::Cancelled, at.elapsed(), Some(cancelled())),
+                    (None, None) => (TaskState::Skipped, Duration::ZERO, None),
+                };
+                TaskReport {
+                    id: task.id().to_string(),
+                    state,
+                    duration,
+                    attempts: run.attempts,
+                    backoffs: run.backoffs,
+                    path: run.path,
+                    error,
+                    wait: run.wait,
+                    throttled: run.throttled,
+                }
+            })
+            .collect();
+        let durations: Vec<Duration> = tasks.iter().map(|task| task.duration).collect();
+        let critical_path = critical_path(&durations, &dependencies)
+            .into_iter()
+            .map(|task| tasks[task].id.clone())
+            .collect();
+        let succeeded = tasks.iter().all(|task| task.state == TaskState::Succeeded);
+        let final_status = match (succeeded, cancel_requested) {
+            (true, _) => RunStatus::Succeeded,
+            (false, true) => RunStatus::Cancelled,
+            (false, false) => RunStatus::Failed,
+        };
+        status.store(final_status as u8, Ordering::SeqCst);
+        let outputs = graph
+            .tasks
+            .iter()
+            .zip(outputs)
+            .filter_map(|(task, output)| Some((task.id().to_string(), output?)))
+            .collect();
+        Ok(GraphReport {
+            status: final_status,
+            tasks,
+            duration: started.elapsed(),
+            critical_path,
+            outputs,
+            deterministic: false,
+        })
+    }
+}
+
+impl Executor {
+    /// Position in `ready` of the entry to start next: of those admitted,
+    /// the one whose runner is the most
+    /// urgent once aged, then the earliest added
+    fn next_ready(
+        &self,
+        ready: &[(usize, usize)],
+        ready_at: &[Option<Instant>],
+        priorities: &[OperationPriority],
+        admission: &Admission<'_>,
+        now: Instant,
+    ) -> Option<usize> {
+        let urgency = |runner: usize| {
+            let waited = ready_at[runner].map_or(Duration::ZERO, |at| now - at);
+            let levels = self.aging.map_or(0, |aging| waited.as_nanos() / aging.as_nanos());
+            priorities[runner].boosted(u32::try_from(levels).unwrap_or(u32::MAX))
+        };
+        ready
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(_, runner))| admission.admits(runner))
+            .max_by_key(|&(_, &(task, runner))| {
+                (urgency(runner), std::cmp::Reverse((task, runner)))
+            })
+            .map(|(position, _)| position)
+    }
+}
+
+/// What the tasks of one graph run hold while they run: slots of their
+/// resource classes, counted in the stats of the executor, and RAM
+/// reserved with its governor
+///
+/// Dropping it gives everything back.
+struct Admission<'a> {
+    capacity: &'a HashMap<String, usize>,
+    stats: &'a parking_lot::Mutex<SchedulerStats>,
+    governor: Option<&'a ResourceGovernor>,
+    /// Class of each task
+    classes: Vec<Option<String>>,
+    /// RAM each task is estimated to need, 0 if it has no estimate
+    estimates: Vec<u64>,
+    /// Tasks of each class holding one of its slots
+    used: HashMap<String, usize>,
+    /// When each task took its class's slot, while it holds it
+    held: Vec<Option<Instant>>,
+    /// RAM each task has reserved
+    reserved: Vec<u64>,
+}
+
+impl<'a> Admission<'a> {
+    /// Admission of the tasks of `graph`, failing with a `Validation` error
+    /// if one needs a class `executor` does not declare, or more RAM than
+    /// its governor's cap
+    fn new(executor: &'a Executor, graph: &TaskGraph) -> Result<Self> {
+        let governor = executor.governor.as_deref();
+        let ram_cap = governor.and_then(|governor| governor.config().ram_cap_bytes);
+        let mut classes = Vec::with_capacity(graph.tasks.len());
+        let mut estimates = Vec::with_capacity(graph.tasks.len());
+        for task in &graph.tasks {
+            let invalid = |field: &str, reason: String| {
+                SystemError::validation(field, reason, Some(task.id().to_string()))
+            };
+            let class = task.resource_class();
+            let declared = |class: &&String| executor.classes.contains_key(*class);
+            if let Some(class) = class.as_ref().filter(|class| !declared(class)) {
+                let reason = format!("undeclared resource class `{class}`");
+                return Err(invalid("resource_class", reason));
+            }
+            let estimate = task.estimated_ram().filter(|_| governor.is_some()).unwrap_or(0);
+            if let Some(cap) = ram_cap.filter(|&cap| estimate > cap) {
+                return Err(invalid(
+                    "estimated_ram",
+                    format!("{estimate} bytes exceed the RAM cap of {cap} bytes"),
+                ));
+            }
+            classes.push(class);
+            estimates.push(estimate);
+        }
+        Ok(Self {
+            capacity: &executor.classes,
+            stats: &executor.stats,
+            governor,
+            held: vec![None; classes.len()],
+            reserved: vec![0; classes.len()],
+            classes,
+            estimates,
+            used: HashMap::new(),
+        })
+    }
+
+    /// Whether `task` can start now
+    fn admits(&self, task: usize) -> bool {
+        self.has_slot(task) && !self.is_throttled(task)
+    }
+
+    /// Whether `task` can start without going past its class's capacity
+    fn has_slot(&self, task: usize) -> bool {
+        match &self.classes[task] {
+            Some(class) => self.used.get(class).copied().unwrap_or(0) < self.capacity[class],
+            None => true,
+        }
+    }
+
+    /// Whether the governor holds `task` back, being paused or out of RAM
+    /// for its estimate
+    fn is_throttled(&self, task: usize) -> bool {
+        let Some(governor) = self.governor else {
+            return false;
+        };
+        let estimate = self.estimates[task];
+        let out_of_ram = governor.config().ram_cap_bytes.is_some_and(|cap| {
+            estimate > 0 && governor.current_ram_usage().saturating_add(estimate) > cap
+        });
+        governor.is_paused() || out_of_ram
+    }
+
+    /// Start `task`, which was ready for `waited`, taking a slot of its
+    /// class and reserving its RAM
+    fn take(&mut self, task: usize, waited: Duration) {
+        if let (Some(governor), estimate @ 1..) = (self.governor, self.estimates[task]) {
+            governor.track_ram_allocation(estimate);
+            self.reserved[task] = estimate;
+        }
+        let mut stats = self.stats.lock();
+        stats.wait_times.record(waited);
+        let Some(class) = &self.classes[task] else {
+            return;
+        };
+        *self.used.entry(class.clone()).or_default() += 1;
+        self.held[task] = Some(Instant::now());
+        if let Some(class_stats) = stats.classes.get_mut(class) {
+            class_stats.running += 1;
+            class_stats.started += 1;
+            class_stats.wait_times.record(waited);
+        }
+    }
+
+    /// Give back what `task` holds
+    fn release(&mut self, task: usize) {
+        let reserved = std::mem::take(&mut self.reserved[task]);
+        if let (Some(governor), 1..) = (self.governor, reserved) {
+            governor.track_ram_deallocation(reserved);
+        }
+        let (Some(taken_at), Some(class)) = (self.held[task].take(), &self.classes[task]) else {
+            return;
+        };
+        if let Some(used) = self.used.get_mut(class) {
+            *used -= 1;
+        }
+        if let Some(class_stats) = self.stats.lock().classes.get_mut(class) {
+            class_stats.running -= 1;
+            class_stats.busy += taken_at.elapsed();
+        }
+    }
+
+    /// Give back everything held
+    fn release_all(&mut self) {
+        for task in 0..self.held.len() {
+            self.release(task);
+        }
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+/// Wait until `deadline`, forever if there is none
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// How tasks are kept from taking down or stalling the runtime
+#[derive(Debug, Clone)]
+pub(crate) struct Sandbox {
+    /// Threads of the blocking pool free to run a task
+    blocking: Arc<Semaphore>,
+    /// Longest an async task may block the runtime in one poll, and what
+    /// happens when it does
+    watchdog: Option<(Duration, WatchdogAction)>,
+}
+
+impl Sandbox {
+    /// Run `task` on the blocking pool or, watched, on the runtime, as its
+    /// [`Task::execution_kind`] says
+    async fn run(&self, task: Arc<dyn Task>, ctx: TaskContext) -> Result<TaskOutput> {
+        match (task.execution_kind(), self.watchdog) {
+            (ExecutionKind::Blocking, _) => {
+                let permit = Arc::clone(&self.blocking)
+                    .acquire_owned()
+                    .await
+                    .map_err(|_| cancelled())?;
+                let runtime = tokio::runtime::Handle::current();
+                // The permit goes with the thread, which keeps running if the
+                // attempt times out, so that the pool stays bounded
+                let job = tokio::task::spawn_blocking(move || {
+                    let _permit = permit;
+                    runtime.block_on(catch_panic(task.run(ctx)))
+                });
+                job.await.unwrap_or_else(|err| Err(join_failed(err)))
+            },
+            (ExecutionKind::Async, Some((threshold, action))) => {
+                let run = Watched {
+                    id: task.id(),
+                    run: task.run(ctx),
+                    threshold,
+                    action,
+                };
+                catch_panic(run).await
+            },
+            (ExecutionKind::Async, None) => catch_panic(task.run(ctx)).await,
+        }
+    }
+}
+
+/// Future of an async task, timing each of its polls against the watchdog
+/// threshold
+struct Watched<'a> {
+    id: &'a str,
+    run: Pin<Box<dyn Future<Output = Result<TaskOutput>> + Send + 'a>>,
+    threshold: Duration,
+    action: WatchdogAction,
+}
+
+impl Future for Watched<'_> {
+    type Output = Result<TaskOutput>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let started = Instant::now();
+        let poll = self.run.as_mut().poll(cx);
+        let blocked = started.elapsed();
+        if blocked <= self.threshold {
+            return poll;
+        }
+        tracing::warn!("Task {} blocked the runtime for {:?}", self.id, blocked);
+        match self.action {
+            WatchdogAction::Log => poll,
+            WatchdogAction::Fail => Poll::Ready(Err(SystemError::Concurrency {
+                message: format!(
+                    "task {} blocked the runtime for {:?}, longer than {:?}",
+                    self.id, blocked, self.threshold
+                ),
+                thread_id: None,
+            })),
+        }
+    }
+}
+
+/// Run `task` once in `sandbox`, cancelling it with a `Timeout` error
+/// after `timeout`
+pub(crate) async fn attempt(
+    task: &Arc<dyn Task>,
+    ctx: TaskContext,
+    timeout: Option<Duration>,
+    sandbox: &Sandbox,
+) -> Result<TaskOutput> {
+    let run = sandbox.run(Arc::clone(task), ctx);
+    match timeout {
+        Some(limit) => tokio::time::timeout(limit, run).await.map_err(|_| {
+            let limit_ms = u64::try_from(limit.as_millis()).unwrap_or(u64::MAX);
+            SystemError::timeout(format!("task {}", task.id()), limit_ms)
+        })?,
+        None => run.await,
+    }
+}
+
+/// Length of the longest chain of dependencies leading to `task`,
+/// memoized in `depths`
+fn depth(task: usize, dependencies: &[Vec<usize>], depths: &mut [Option<usize>]) -> usize {
+    if let Some(depth) = depths[task] {
+        return depth;
+    }
+    let mut deepest = 0;
+    for &dependency in &dependencies[task] {
+        deepest = deepest.max(depth(dependency, dependencies, depths) + 1);
+    }
+    depths[task] = Some(deepest);
+    deepest
+}
+
+/// Tasks of the dependency chain with the longest total duration, given
+/// the duration of each task, first task first
+pub(crate) fn critical_path(durations: &[Duration], dependencies: &[Vec<usize>]) -> Vec<usize> {
+    let mut longest = vec![None; durations.len()];
+    let end = (0..durations.len())
+        .map(|task| (longest_chain(task, durations, dependencies, &mut longest), task))
+        .max_by_key(|&(duration, task)| (duration, std::cmp::Reverse(task)));
+    let mut path = Vec::new();
+    let mut next = end.map(|(_, task)| task);
+    while let Some(task) = next {
+        path.push(task);
+        next = longest[task].and_then(|(_, via)| via);
+    }
+    path.reverse();
+    path
+}
+
+/// Duration of the longest chain ending at `task`, memoized in `longest`
+/// with the dependency the chain comes through
+fn longest_chain(
+    task: usize,
+    durations: &[Duration],
+    dependencies: &[Vec<usize>],
+    longest: &mut [Option<(Duration, Option<usize>)>],
+) -> Duration {
+    if let Some((duration, _)) = longest[task] {
+        return duration;
+    }
+    let mut best = (Duration::ZERO, None);
+    for &dependency in &dependencies[task] {
+        let duration = longest_chain(dependency, durations, dependencies, longest);
+        if best.1.is_none() || duration > best.0 {
+            best = (duration, Some(dependency));
+        }
+    }
+    let total = best.0 + durations[task];
+    longest[task] = Some((total, best.1));
+    total
+}
+
+/// How often an idle pool worker looks for work without being woken
+const IDLE_POLL: Duration = Duration::from_millis(1);
+
+/// A closure run by a [`WorkStealingPool`]
+type Job = Box<dyn FnOnce() + Send>;
+
+/// What [`WorkStealingPool::shutdown`] does with tasks not yet started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum ShutdownPolicy {
+    /// Run every submitted task, then stop
+    #[default]
+    Drain,
+    /// Finish the running tasks and discard the others
+    Cancel,
+}
+
+/// Counters of one [`WorkStealingPool`] worker
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WorkerStats {
+    /// Tasks the worker ran
+    pub executed: u64,
+    /// Tasks the worker took from other workers' queues
+    pub steals: u64,
+    /// Tasks waiting in the worker's queue
+    pub queue_depth: usize,
+}
+
+/// Counters of a [`WorkStealingPool`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutorStats {
+    /// Per worker, by index
+    pub workers: Vec<WorkerStats>,
+    /// Tasks submitted from outside the pool and not yet taken by a worker
+    pub injector_depth: usize,
+    /// Tasks that panicked
+    pub panicked: u64,
+    /// Tasks discarded by a [`ShutdownPolicy::Cancel`] shutdown
+    pub cancelled: u64,
+}
+
+impl ExecutorStats {
+    /// Tasks run by all workers
+    pub fn executed(&self) -> u64 {
+        self.workers.iter().map(|worker| worker.executed).sum()
+    }
+
+    /// Tasks stolen by all workers
+    pub fn steals(&self) -> u64 {
+        self.workers.iter().map(|worker| worker.steals).sum()
+    }
+}
+
+/// Pool state
+const RUNNING: u8 = 0;
+const DRAINING: u8 = 1;
+const CANCELLING: u8 = 2;
+
+/// State shared by a pool, its workers and its handles
+struct Pool {
+    scheduling: SchedulingPolicy,
+    injector: Injector<Job>,
+    stealers: Vec<Stealer<Job>>,
+    executed: Vec<AtomicU64>,
+    steals: Vec<AtomicU64>,
+    panicked: AtomicU64,
+    cancelled: AtomicU64,
+    /// Tasks submitted and not yet run or discarded
+    pending: AtomicUsize,
+    state: AtomicU8,
+    /// Signalled when work is submitted, the pool stops or goes idle
+    wake: parking_lot::Condvar,
+    lock: parking_lot::Mutex<()>,
+}
+
+/// Queue of the pool worker running on this thread
+struct LocalQueue {
+    pool: *const Pool,
+    index: usize,
+    worker: Worker<Job>,
+}
+
+thread_local! {
+    static LOCAL_QUEUE: RefCell<Option<LocalQueue>> = const { RefCell::new(None) };
+}
+
+/// Threads running closures, each with its own queue
+///
+/// Under [`SchedulingPolicy::WorkStealing`], tasks submitted from a worker
+/// go to the back of that worker's queue; tasks submitted from elsewhere go
+/// to a shared injector queue. A worker runs its own tasks first, then
+/// takes a batch from the injector, then steals from the other workers.
+/// Under [`SchedulingPolicy::Fifo`], every task goes through the injector.
+/// Under [`SchedulingPolicy::Cooperative`], tasks submitted from a worker
+/// also go to its own queue, but a worker runs its own tasks then takes
+/// one task at a time from the injector, and never steals.
+/// Dropping the pool shuts it down with [`ShutdownPolicy::Cancel`].
+pub struct WorkStealingPool {
+    pool: Arc<Pool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Submits tasks to a [`WorkStealingPool`] from anywhere, including its
+/// own tasks
+#[derive(Clone)]
+pub struct PoolHandle {
+    pool: Arc<Pool>,
+}
+
+impl WorkStealingPool {
+    /// Start `config.workers` worker threads, pinned to CPU cores if
+    /// `config.pin_workers` is set
+    ///
+    /// Workers that cannot be pinned run unpinned, with a warning.
+    pub fn new(config: &FrameworkConfig) -> Result<Self> {
+        if config.workers == 0 {
+            return Err(SystemError::config("workers must be > 0", Some("workers".to_string())));
+        }
+        let workers: Vec<Worker<Job>> = (0..config.workers).map(|_| Worker::new_fifo()).collect();
+        let pool = Arc::new(Pool {
+            scheduling: config.scheduling,
+            injector: Injector::new(),
+            stealers: workers.iter().map(Worker::stealer).collect(),
+            executed: (0..config.workers).map(|_| AtomicU64::new(0)).collect(),
+            steals: (0..config.workers).map(|_| AtomicU64::new(0)).collect(),
+            panicked: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+            state: AtomicU8::new(RUNNING),
+            wake: parking_lot::Condvar::new(),
+            lock: parking_lot::Mutex::new(()),
+        });
+        let cores = if config.pin_workers {
+            core_affinity::get_core_ids().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let mut threads = Vec::with_capacity(config.workers);
+        for (index, worker) in workers.into_iter().enumerate() {
+            let pool = Arc::clone(&pool);
+            let core = (!cores.is_empty()).then(|| cores[index % cores.len()]);
+            let pin = config.pin_workers;
+            let thread = std::thread::Builder::new()
+                .name(format!("paf-worker-{index}"))
+                .spawn(move || {
+                    if pin && !core.is_some_and(core_affinity::set_for_current) {
+                        tracing::warn!("Could not pin pool worker {} to a core", index);
+                    }
+                    run_worker(&pool, index, worker);
+                })
+                .map_err(|e| SystemError::io(e, "spawning pool worker"))?;
+            threads.push(thread);
+        }
+        Ok(Self { pool, threads })
+    }
+
+    /// Handle submitting tasks to the pool
+    pub fn handle(&self) -> PoolHandle {
+        PoolHandle {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+
+    /// Run `task` on the pool
+    pub fn spawn(&self, task: impl FnOnce() + Send + 'static) {
+        // The pool is running until `shutdown` consumes it
+        let _ = self.handle().spawn(task);
+    }
+
+    /// Block until every submitted task has run
+    pub fn wait_idle(&self) {
+        let mut guard = self.pool.lock.lock();
+        while self.pool.pending.load(Ordering::SeqCst) > 0 {
+            self.pool.wake.wait_for(&mut guard, IDLE_POLL);
+        }
+    }
+
+    /// Current counters, also recorded as metrics
+    pub fn stats(&self) -> ExecutorStats {
+        let stats = self.pool.stats();
+        shared_core::gauge!("executor_injector_depth", stats.injector_depth as f64);
+        shared_core::gauge!(
+            "executor_queue_depth",
+            stats.workers.iter().map(|worker| worker.queue_depth).sum::<usize>() as f64
+        );
+        stats
+    }
+
+    /// Stop the workers, running or discarding the tasks not yet started
+    /// as `policy` says, and return the final counters
+    pub fn shutdown(mut self, policy: ShutdownPolicy) -> ExecutorStats {
+        self.stop(policy);
+        self.pool.stats()
+    }
+
+    fn stop(&mut self, policy: ShutdownPolicy) {
+        let state = match policy {
+            ShutdownPolicy::Drain => DRAINING,
+            ShutdownPolicy::Cancel => CANCELLING,
+        };
+        self.pool.state.store(state, Ordering::SeqCst);
+        self.pool.wake.notify_all();
+        for thread in self.threads.drain(..) {
+            // Task panics are caught, so workers do not panic
+            let _ = thread.join();
+        }
+        // Tasks submitted through handles after the workers stopped
+        loop {
+            match self.pool.injector.steal() {
+                Steal::Success(_) => self.pool.discard(),
+                Steal::Retry => {},
+                Steal::Empty => break,
+            }
+        }
+    }
+}
+
+impl Drop for WorkStealingPool {
+    fn drop(&mut self) {
+        if !self.threads.is_empty() {
+            self.stop(ShutdownPolicy::Cancel);
+        }
+    }
+}
+
+impl std::fmt::Debug for WorkStealingPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkStealingPool")
+            .field("workers", &self.pool.stealers.len())
+            .field("pending", &self.pool.pending.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl PoolHandle {
+    /// Run `task` on the pool
+    ///
+    /// From a task of the pool, `task` goes to the back of the current
+    /// worker's queue. Fails with an `InvalidState` error once the pool is
+    /// shutting down.
+    pub fn spawn(&self, task: impl FnOnce() + Send + 'static) -> Result<()> {
+        if self.pool.state.load(Ordering::SeqCst) != RUNNING {
+            return Err(SystemError::InvalidState {
+                message: "work-stealing pool is shutting down".to_string(),
+                current_state: Some("stopping".to_string()),
+                expected_state: Some("running".to_string()),
+            });
+        }
+        self.pool.pending.fetch_add(1, Ordering::SeqCst);
+        let job: Job = Box::new(task);
+        let pool: *const Pool = &*self.pool;
+        let shared = self.pool.scheduling == SchedulingPolicy::Fifo;
+        let job = LOCAL_QUEUE.with(|local| match &*local.borrow() {
+            Some(queue) if queue.pool == pool && !shared => {
+                queue.worker.push(job);
+                None
+            },
+            _ => Some(job),
+        });
+        if let Some(job) = job {
+            self.pool.injector.push(job);
+        }
+        self.pool.wake.notify_one();
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for PoolHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoolHandle").finish_non_exhaustive()
+    }
+}
+
+impl Pool {
+    fn stats(&self) -> ExecutorStats {
+        ExecutorStats {
+            workers: (0..self.stealers.len())
+                .map(|index| WorkerStats {
+                    executed: self.executed[index].load(Ordering::Relaxed),
+                    steals: self.steals[index].load(Ordering::Relaxed),
+                    queue_depth: self.stealers[index].len(),
+                })
+                .collect(),
+            injector_depth: self.injector.len(),
+            panicked: self.panicked.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Next task for worker `index`: its own, the injector's, then another
+    /// worker's
+    fn find_job(&self, index: usize, local: &Worker<Job>) -> Option<Job> {
+        let steal_threshold = match self.scheduling {
+            SchedulingPolicy::Fifo => return self.pop_injector(),
+            SchedulingPolicy::Cooperative => return local.pop().or_else(|| self.pop_injector()),
+            SchedulingPolicy::WorkStealing { steal_threshold } => steal_threshold.max(1),
+        };
+        if let Some(job) = local.pop() {
+            return Some(job);
+        }
+        loop {
+            match self.injector.steal_batch_and_pop(local) {
+                Steal::Success(job) => return Some(job),
+                Steal::Retry => continue,
+                Steal::Empty => break,
+            }
+        }
+        let others = self.stealers.len();
+        let mut retry = true;
+        while retry {
+            retry = false;
+            for offset in 1..others {
+                let victim = &self.stealers[(index + offset) % others];
+                if victim.len() < steal_threshold {
+                    continue;
+                }
+                match victim.steal_batch_and_pop(local) {
+                    Steal::Success(job) => {
+                        self.steals[index].fetch_add(1, Ordering::Relaxed);
+                        shared_core::count!("executor_steals_total", 1);
+                        return Some(job);
+                    },
+                    Steal::Retry => retry = true,
+                    Steal::Empty => {},
+                }
+            }
+        }
+        None
+    }
+
+    /// Next task of the injector alone
+    fn pop_injector(&self) -> Option<Job> {
+        loop {
+            match self.injector.steal() {
+                Steal::Success(job) => return Some(job),
+                Steal::Retry => continue,
+                Steal::Empty => return None,
+            }
+        }
+    }
+
+    /// Account for a task that will not run
+    fn discard(&self) {
+        self.cancelled.fetch_add(1, Ordering::Relaxed);
+        self.finish();
+    }
+
+    /// Account for a task that ran or was discarded
+    fn finish(&self) {
+        if self.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let _guard = self.lock.lock();
+            self.wake.notify_all();
+        }
+    }
+}
+
+/// Run tasks as worker `index` of `pool` until it stops
+fn run_worker(pool: &Arc<Pool>, index: usize, worker: Worker<Job>) {
+    LOCAL_QUEUE.with(|local| {
+        *local.borrow_mut() = Some(LocalQueue {
+            pool: Arc::as_ptr(pool),
+            index,
+            worker,
+        });
+    });
+    loop {
+        let state = pool.state.load(Ordering::SeqCst);
+        if state == CANCELLING {
+            break;
+        }
+        // Not borrowed while the task runs, so it can spawn onto the queue
+        let job = LOCAL_QUEUE.with(|local| {
+            let local = local.borrow();
+            let queue = local.as_ref().expect("the worker's queue is installed");
+            pool.find_job(queue.index, &queue.worker)
+        });
+        match job {
+            Some(job) => {
+                if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    pool.panicked.fetch_add(1, Ordering::Relaxed);
+                }
+                pool.executed[index].fetch_add(1, Ordering::Relaxed);
+                shared_core::count!("executor_tasks_executed_total", 1);
+                pool.finish();
+            },
+            None if state == DRAINING && pool.pending.load(Ordering::SeqCst) == 0 => break,
+            None => {
+                let mut guard = pool.lock.lock();
+                pool.wake.wait_for(&mut guard, IDLE_POLL);
+            },
+        }
+    }
+    if let Some(queue) = LOCAL_QUEUE.with(|local| local.borrow_mut().take()) {
+        while queue.worker.pop().is_some() {
+            pool.discard();
+        }
+    }
+}
+
+/// Checkpoint key of `task`, given the `lineage` of the tasks it depends on
+fn checkpoint_key(
+    graph: &TaskGraph,
+    dependencies: &[Vec<usize>],
+    lineage: &[Option<Lineage>],
+    task: usize,
+) -> Option<String> {
+    let inputs: Vec<_> = dependencies[task]
+        .iter()
+        .map(|&dependency| (graph.tasks[dependency].id(), lineage[dependency].as_ref()))
+        .collect();
+    checkpoint::key(&*graph.tasks[task], &inputs)
+}
+
+/// Save a checkpoint of `task`, which produced `output`, returning its
+/// lineage if it was saved
+fn save_checkpoint(
+    store: &dyn CheckpointStore,
+    graph: &TaskGraph,
+    dependencies: &[Vec<usize>],
+    lineage: &[Option<Lineage>],
+    task: usize,
+    output: &TaskOutput,
+) -> Option<Lineage> {
+    let key = checkpoint_key(graph, dependencies, lineage, task)?;
+    let saved = graph.tasks[task].save_output(output)?;
+    let output_hash = checkpoint::save(store, graph.tasks[task].id(), key.clone(), saved)?;
+    Some(Lineage { key, output_hash })
+}
+
+pub(crate) fn cancelled() -> SystemError {
+    SystemError::Concurrency {
+        message: "task cancelled".to_string(),
+        thread_id: None,
+    }
+}
+
+/// Await `future`, turning a panic into a `Concurrency` error carrying its
+/// message
+pub(crate) async fn catch_panic<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|panic| Err(panicked(panic.as_ref())))
+}
+
+fn panicked(panic: &(dyn std::any::Any + Send)) -> SystemError {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    SystemError::Concurrency {
+        message: format!("task panicked: {message}"),
+        thread_id: None,
+    }
+}
+
+fn join_failed(err: tokio::task::JoinError) -> SystemError {
+    SystemError::Concurrency {
+        message: format!("task failed to complete: {err}"),
+        thread_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use shared_core::{ResourceGovernorConfig, RetryPolicy};
+
+    use super::*;
+    use crate::checkpoint::{CheckpointStore, SavedOutput};
+    use crate::communication::StreamItem;
+    use crate::scheduler::TaskPolicy;
+
+    #[tokio::test]
+    async fn test_wait_all_collects_failures() {
+        let group = TaskGroup::new();
+        for i in 0..4_u64 {
+            group.spawn(async move {
+                // Finish in reverse spawn order
+                tokio::time::sleep(Duration::from_millis(40 - i * 10)).await;
+                match i {
+                    1 => Err(SystemError::internal("boom", None)),
+                    2 => panic!("task {i} panicked"),
+                    _ => Ok(i),
+                }
+            });
+        }
+
+        let results = group.wait_all().await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &0);
+        assert!(matches!(results[1], Err(SystemError::Internal { .. })));
+        assert!(results[2].as_ref().unwrap_err().to_string().contains("task 2 panicked"));
+        assert_eq!(results[3].as_ref().unwrap(), &3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_first_returns_remaining() {
+        let group = TaskGroup::new();
+        group.spawn(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok("slow")
+        });
+        group.spawn(async { Ok("fast") });
+
+        let (first, rest) = group.wait_first().await;
+        assert_eq!(first.unwrap(), "fast");
+        assert_eq!(rest.len(), 1);
+        let (second, rest) = rest.wait_first().await;
+        assert_eq!(second.unwrap(), "slow");
+        let (none, _) = rest.wait_first().await;
+        assert!(matches!(none, Err(SystemError::InvalidState { .. })));
+    }
+
+    /// Sleeps, then returns the sum of its dependencies' outputs plus one,
+    /// or fails if told to
+    struct Step {
+        id: &'static str,
+        dependencies: &'static [&'static str],
+        sleep_ms: u64,
+        fail: bool,
+        /// Attempts failing with a retriable error before the task succeeds
+        flaky: u32,
+        /// Whether the task keeps sleeping when its run is cancelled
+        stubborn: bool,
+        policy: TaskPolicy,
+        ram: Option<u64>,
+        running: Arc<std::sync::atomic::AtomicUsize>,
+        peak: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Step {
+        fn new(id: &'static str, dependencies: &'static [&'static str], sleep_ms: u64) -> Self {
+            Self {
+                id,
+                dependencies,
+                sleep_ms,
+                fail: false,
+                flaky: 0,
+                stubborn: false,
+                policy: TaskPolicy::default(),
+                ram: None,
+                running: Arc::default(),
+                peak: Arc::default(),
+            }
+        }
+
+        fn failing(mut self) -> Self {
+            self.fail = true;
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::scheduler::Task for Step {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.dependencies.iter().map(|id| id.to_string()).collect()
+        }
+
+        fn policy(&self) -> TaskPolicy {
+            self.policy.clone()
+        }
+
+        fn estimated_ram(&self) -> Option<u64> {
+            self.ram
+        }
+
+        async fn run(&self, ctx: TaskContext) -> Result<TaskOutput> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            let sleep = tokio::time::sleep(Duration::from_millis(self.sleep_ms));
+            let cancelled = tokio::select! {
+                () = sleep => false,
+                () = ctx.cancellation().cancelled(), if !self.stubborn => true,
+            };
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            if cancelled {
+                return Err(super::cancelled());
+            }
+            if self.fail {
+                return Err(SystemError::internal(format!("{} failed", self.id), None));
+            }
+            if ctx.attempt() <= self.flaky {
+                let attempt = Some(ctx.attempt());
+                return Err(SystemError::network(self.id, "connection reset", attempt));
+            }
+            let mut sum = 1_u64;
+            for dependency in self.dependencies {
+                sum += ctx.output::<u64>(dependency)?;
+            }
+            Ok(TaskOutput::new(sum))
+        }
+    }
+
+    /// Streams numbers from `input`, or `0..items` without one, doubled to
+    /// `output`, failing after `fail_after` of them; its output is how many
+    /// it took and the most its input queued
+    struct Stage {
+        id: &'static str,
+        input: Option<&'static str>,
+        output: Option<&'static str>,
+        items: u64,
+        fail_after: Option<u64>,
+    }
+
+    impl Stage {
+        fn new(
+            id: &'static str,
+            input: Option<&'static str>,
+            output: Option<&'static str>,
+        ) -> Self {
+            Self {
+                id,
+                input,
+                output,
+                items: 0,
+                fail_after: None,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::scheduler::Task for Stage {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        async fn run(&self, ctx: TaskContext) -> Result<TaskOutput> {
+            let output = self.output.map(|consumer| ctx.sender::<u64>(consumer)).transpose()?;
+            let mut input = self.input.map(|producer| ctx.receiver::<u64>(producer)).transpose()?;
+            let mut taken = 0_u64;
+            loop {
+                if self.fail_after == Some(taken) {
+                    return Err(SystemError::internal(format!("{} failed", self.id), None));
+                }
+                let item = match &mut input {
+                    None if taken == self.items => break,
+                    None => taken,
+                    Some(input) => match input.recv().await {
+                        StreamItem::Item(item) => item,
+                        StreamItem::EndOfStream => break,
+                        StreamItem::UpstreamFailed(err) => return Err(err),
+                    },
+                };
+                taken += 1;
+                if let Some(output) = &output {
+                    output.send(item * 2).await?;
+                }
+            }
+            let peak = input.map_or(0, |input| input.peak());
+            Ok(TaskOutput::new((taken, peak)))
+        }
+    }
+
+    /// Link `i` of a chain: adds one to the previous link's output, or
+    /// starts from zero, counting its runs
+    struct Link {
+        i: usize,
+        fingerprint: &'static str,
+        fail: bool,
+        runs: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::scheduler::Task for Link {
+        fn id(&self) -> &str {
+            ["t1", "t2", "t3", "t4", "t5"][self.i]
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            match self.i {
+                0 => Vec::new(),
+                i => vec![format!("t{i}")],
+            }
+        }
+
+        fn fingerprint(&self) -> Option<String> {
+            Some(self.fingerprint.to_string())
+        }
+
+        fn save_output(&self, output: &TaskOutput) -> Option<SavedOutput> {
+            output.downcast_ref::<u64>().map(|&n| SavedOutput::Inline(n.into()))
+        }
+
+        fn restore_output(&self, saved: &SavedOutput) -> Result<TaskOutput> {
+            match saved {
+                SavedOutput::Inline(value) => value.as_u64().map(TaskOutput::new),
+                SavedOutput::External(_) => None,
+            }
+            .ok_or_else(|| SystemError::validation("saved_output", "not a number", None))
+        }
+
+        async fn run(&self, ctx: TaskContext) -> Result<TaskOutput> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                return Err(SystemError::internal("killed", None));
+            }
+            let previous = match self.i {
+                0 => 0,
+                i => *ctx.output::<u64>(&format!("t{i}"))?,
+            };
+            Ok(TaskOutput::new(previous + 1))
+        }
+    }
+
+    fn executor(workers: usize, policy: ErrorPolicy) -> Executor {
+        let config = FrameworkConfig {
+            workers,
+            ..FrameworkConfig::default()
+        };
+        Executor::new(&config).unwrap().with_error_policy(policy)
+    }
+
+    /// `top` feeds `left` and `right`, which feed `bottom`; `left` may fail,
+    /// `side` is independent and slow
+    fn diamond(fail_left: bool) -> TaskGraph {
+        let left = Step::new("left", &["top"], 30);
+        TaskGraph::new()
+            .task(Step::new("top", &[], 10))
+            .task(if fail_left { left.failing() } else { left })
+            .task(Step::new("right", &["top"], 5))
+            .task(Step::new("bottom", &["left", "right"], 10))
+            .task(Step::new("side", &[], 100))
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_diamond() {
+        let report = executor(4, ErrorPolicy::FailFast).run_graph(diamond(false)).await.unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.tasks.len(), 5);
+        assert!(report.task("left").unwrap().duration >= Duration::from_millis(30));
+        // `bottom` gets 1 + (1 + 1) + (1 + 1) through the typed context
+        assert_eq!(report.output::<u64>("bottom"), Some(&5));
+        assert_eq!(report.critical_path, ["side"]);
+        assert!(report.critical_path_duration() >= Duration::from_millis(100));
+
+        let graph = TaskGraph::new()
+            .task(Step::new("top", &[], 10))
+            .task(Step::new("left", &["top"], 60))
+            .task(Step::new("right", &["top"], 5))
+            .task(Step::new("bottom", &["left", "right"], 10));
+        let report = executor(4, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert_eq!(report.critical_path, ["top", "left", "bottom"]);
+
+        let cyclic = TaskGraph::new()
+            .task(Step::new("a", &["b"], 0))
+            .task(Step::new("b", &["a"], 0));
+        assert!(executor(1, ErrorPolicy::FailFast).run_graph(cyclic).await.is_err());
+        let config = FrameworkConfig {
+            workers: 0,
+            ..FrameworkConfig::default()
+        };
+        assert!(Executor::new(&config).is_err());
+        assert!(WorkStealingPool::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_failure_policies() {
+        let report = executor(4, ErrorPolicy::FailFast).run_graph(diamond(true)).await.unwrap();
+        assert!(!report.is_success());
+        let state = |id| report.task(id).unwrap().state;
+        assert_eq!(state("top"), TaskState::Succeeded);
+        assert_eq!(state("left"), TaskState::Failed);
+        assert_eq!(state("bottom"), TaskState::Skipped);
+        // The slow independent task is cut short
+        assert_eq!(state("side"), TaskState::Cancelled);
+        assert!(report.duration < Duration::from_millis(100));
+        let error = report.task("left").unwrap().error.as_ref().unwrap();
+        assert!(error.to_string().contains("left failed"));
+        assert_eq!(report.output::<u64>("top"), Some(&1));
+        assert_eq!(report.output::<u64>("left"), None);
+
+        let report = executor(4, ErrorPolicy::Continue).run_graph(diamond(true)).await.unwrap();
+        let state = |id| report.task(id).unwrap().state;
+        assert_eq!(state("left"), TaskState::Failed);
+        assert_eq!(state("right"), TaskState::Succeeded);
+        assert_eq!(state("bottom"), TaskState::Skipped);
+        assert_eq!(state("side"), TaskState::Succeeded);
+    }
+
+    fn retry(jitter: bool) -> TaskPolicy {
+        TaskPolicy {
+            retry: RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_secs(1),
+                multiplier: 2,
+                jitter,
+            },
+            ..TaskPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_retries_failed_tasks() {
+        let flaky = Step {
+            flaky: 2,
+            policy: retry(false),
+            ..Step::new("flaky", &["top"], 0)
+        };
+        let graph = TaskGraph::new()
+            .task(Step::new("top", &[], 0))
+            .task(flaky)
+            .task(Step::new("bottom", &["flaky"], 0));
+        let report = executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert!(report.is_success());
+        let flaky = report.task("flaky").unwrap();
+        assert_eq!(flaky.attempts, 3);
+        assert_eq!(flaky.backoffs, [10, 20].map(Duration::from_millis));
+        assert_eq!(flaky.path, Some(OutputPath::Primary));
+        assert_eq!(report.output::<u64>("bottom"), Some(&3));
+        assert!(report.duration >= Duration::from_millis(30));
+
+        // Out of attempts
+        let graph = TaskGraph::new().task(Step {
+            flaky: 3,
+            policy: retry(false),
+            ..Step::new("flaky", &[], 0)
+        });
+        let report = executor(1, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        let flaky = report.task("flaky").unwrap();
+        assert_eq!((flaky.state, flaky.attempts), (TaskState::Failed, 3));
+        assert!(matches!(flaky.error, Some(SystemError::Network { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_falls_back_after_timeout() {
+        let slow = Step {
+            policy: TaskPolicy {
+                timeout: Some(Duration::from_millis(20)),
+                fallback: Some("cached".to_string()),
+                ..retry(false)
+            },
+            ..Step::new("slow", &["top"], 60_000)
+        };
+        let graph = TaskGraph::new()
+            .task(Step::new("top", &[], 0))
+            .task(slow)
+            .task(Step::new("cached", &["top"], 0))
+            .task(Step::new("bottom", &["slow"], 0))
+            .task(Step::new("unused", &[], 0).failing())
+            .task(Step {
+                policy: TaskPolicy {
+                    fallback: Some("unused".to_string()),
+                    ..TaskPolicy::default()
+                },
+                ..Step::new("fine", &[], 0)
+            });
+        let report = executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert!(!report.is_success());
+        let slow = report.task("slow").unwrap();
+        assert_eq!(slow.state, TaskState::Succeeded);
+        assert_eq!(slow.attempts, 3);
+        assert_eq!(slow.path, Some(OutputPath::Fallback));
+        assert!(matches!(slow.error, Some(SystemError::Timeout { .. })));
+        assert!(report.duration < Duration::from_secs(1));
+        // The fallback's output stands in for `slow`'s: 1 + 1, then 2 + 1
+        assert_eq!(report.output::<u64>("slow"), Some(&2));
+        assert_eq!(report.output::<u64>("bottom"), Some(&3));
+        let cached = report.task("cached").unwrap();
+        assert_eq!((cached.attempts, cached.path), (1, Some(OutputPath::Primary)));
+        // Fallbacks that are not needed do not run
+        let unused = report.task("unused").unwrap();
+        assert_eq!((unused.state, unused.attempts), (TaskState::Skipped, 0));
+
+        // A fallback failing fails its primary
+        let graph = TaskGraph::new()
+            .task(Step {
+                fail: true,
+                policy: TaskPolicy {
+                    fallback: Some("broken".to_string()),
+                    ..TaskPolicy::default()
+                },
+                ..Step::new("primary", &[], 0)
+            })
+            .task(Step::new("broken", &[], 0).failing());
+        let report = executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        let state = |id| report.task(id).unwrap().state;
+        assert_eq!((state("primary"), state("broken")), (TaskState::Failed, TaskState::Failed));
+        assert!(report.output::<u64>("primary").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_backoff_is_deterministic() {
+        let backoffs = || async {
+            let governor = ResourceGovernor::new(ResourceGovernorConfig::testing()).unwrap();
+            let graph = TaskGraph::new().task(Step {
+                flaky: 2,
+                policy: retry(true),
+                ..Step::new("flaky", &[], 0)
+            });
+            let executor = executor(1, ErrorPolicy::FailFast).with_governor(&governor);
+            let report = executor.run_graph(graph).await.unwrap();
+            report.task("flaky").unwrap().backoffs.clone()
+        };
+        let first = backoffs().await;
+        assert_eq!(first.len(), 2);
+        assert!((Duration::from_millis(5)..=Duration::from_millis(10)).contains(&first[0]));
+        assert!((Duration::from_millis(10)..=Duration::from_millis(20)).contains(&first[1]));
+        assert_eq!(backoffs().await, first);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_detached_run() {
+        let cleanup = Step {
+            policy: TaskPolicy {
+                always_run: true,
+                ..TaskPolicy::default()
+            },
+            ..Step::new("cleanup", &["bottom"], 0)
+        };
+        let graph = diamond(false).task(cleanup);
+        let mut handle = executor(4, ErrorPolicy::FailFast).run_graph_detached(graph).unwrap();
+        assert_eq!(handle.status(), RunStatus::Running);
+        // `top` and `right` are done, `left` and `side` running
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        handle.cancel();
+        assert_eq!(handle.status(), RunStatus::Cancelling);
+        let report = (&mut handle).await.unwrap();
+        assert_eq!(handle.status(), RunStatus::Cancelled);
+        assert_eq!(report.status, RunStatus::Cancelled);
+
+        let state = |id| report.task(id).unwrap().state;
+        assert_eq!(state("top"), TaskState::Succeeded);
+        assert_eq!(state("right"), TaskState::Succeeded);
+        assert_eq!(state("left"), TaskState::Cancelled);
+        assert_eq!(state("side"), TaskState::Cancelled);
+        assert_eq!(state("bottom"), TaskState::Skipped);
+        // The cleanup step runs without `bottom`'s output, so it fails
+        let cleanup = report.task("cleanup").unwrap();
+        assert_eq!((cleanup.state, cleanup.attempts), (TaskState::Failed, 1));
+        assert!(matches!(cleanup.error, Some(SystemError::NotFound { .. })));
+        assert!(report.duration < Duration::from_millis(100), "{:?}", report.duration);
+
+        // Tasks ignoring the cancellation are aborted after the grace period
+        let graph = TaskGraph::new().task(Step {
+            stubborn: true,
+            ..Step::new("stubborn", &[], 60_000)
+        });
+        let handle = executor(1, ErrorPolicy::FailFast)
+            .with_cancel_grace(Duration::from_millis(20))
+            .run_graph_detached(graph)
+            .unwrap();
+        handle.cancel();
+        let report = tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+        assert_eq!(report.task("stubborn").unwrap().state, TaskState::Cancelled);
+        assert_eq!(report.status, RunStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_streams_between_tasks() {
+        const ITEMS: u64 = 1_000_000;
+        let graph = TaskGraph::new()
+            .task(Stage {
+                items: ITEMS,
+                ..Stage::new("source", None, Some("double"))
+            })
+            .task(Stage::new("double", Some("source"), Some("sink")))
+            .task(Stage::new("sink", Some("double"), None))
+            .connect::<u64>("source", "double", 100)
+            .connect::<u64>("double", "sink", 100);
+        // Connected tasks start together, even on one worker
+        let report = executor(1, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert_eq!(report.status, RunStatus::Succeeded, "{report:?}");
+        for (stage, input) in [("double", "source"), ("sink", "double")] {
+            let &(taken, peak) = report.output::<(u64, usize)>(stage).unwrap();
+            assert_eq!(taken, ITEMS, "{stage}");
+            assert!(peak > 0 && peak <= 100, "{input} queued {peak} items");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_upstream_failure() {
+        let graph = TaskGraph::new()
+            .task(Stage {
+                items: 1_000,
+                fail_after: Some(500),
+                ..Stage::new("source", None, Some("sink"))
+            })
+            .task(Stage::new("sink", Some("source"), None))
+            .task(Step::new("after", &["sink"], 0))
+            .connect::<u64>("source", "sink", 10);
+        let report = executor(2, ErrorPolicy::Continue).run_graph(graph).await.unwrap();
+        assert_eq!(report.status, RunStatus::Failed);
+        let sink = report.task("sink").unwrap();
+        assert_eq!(sink.state, TaskState::Failed);
+        let err = sink.error.as_ref().unwrap().to_string();
+        assert!(err.contains("upstream task `source` failed: "), "{err}");
+        assert_eq!(report.task("source").unwrap().state, TaskState::Failed);
+        assert_eq!(report.task("after").unwrap().state, TaskState::Skipped);
+
+        // Connected tasks wait for each other's dependencies
+        let graph = TaskGraph::new()
+            .task(Step::new("gate", &[], 0).failing())
+            .task(Stage::new("source", None, Some("sink")))
+            .task(Step::new("sink", &["gate"], 0))
+            .connect::<u64>("source", "sink", 10);
+        let report = executor(2, ErrorPolicy::Continue).run_graph(graph).await.unwrap();
+        assert_eq!(report.task("source").unwrap().state, TaskState::Skipped);
+        assert_eq!(report.task("sink").unwrap().state, TaskState::Skipped);
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::checkpoint::DirectoryCheckpointStore::new(dir.path()).unwrap();
+        let runs: Vec<Arc<std::sync::atomic::AtomicUsize>> =
+            (0..5).map(|_| Arc::default()).collect();
+        let chain = |fingerprints: [&'static str; 5], kill_at: Option<usize>| {
+            (0..5).fold(TaskGraph::new(), |graph, i| {
+                graph.task(Link {
+                    i,
+                    fingerprint: fingerprints[i],
+                    fail: kill_at == Some(i),
+                    runs: Arc::clone(&runs[i]),
+                })
+            })
+        };
+        let runs_since = |before: &[usize]| -> Vec<usize> {
+            runs.iter()
+                .zip(before)
+                .map(|(runs, before)| runs.load(Ordering::SeqCst) - before)
+                .collect()
+        };
+        let paths = |report: &GraphReport| -> Vec<Option<OutputPath>> {
+            report.tasks.iter().map(|task| task.path).collect()
+        };
+        let executor = executor(2, ErrorPolicy::FailFast);
+
+        // Killed after the third task
+        let report = executor.resume(chain(["v1"; 5], Some(3)), &store).await.unwrap();
+        assert_eq!(report.status, RunStatus::Failed);
+        assert_eq!(runs_since(&[0; 5]), [1, 1, 1, 1, 0]);
+
+        let report = executor.resume(chain(["v1"; 5], None), &store).await.unwrap();
+        assert_eq!(report.status, RunStatus::Succeeded);
+        assert_eq!(runs_since(&[1, 1, 1, 1, 0]), [0, 0, 0, 1, 1]);
+        let restored = Some(OutputPath::Checkpoint);
+        let ran = Some(OutputPath::Primary);
+        assert_eq!(paths(&report), [restored, restored, restored, ran, ran]);
+        assert_eq!(report.output::<u64>("t5"), Some(&5));
+        assert_eq!(report.task("t1").unwrap().attempts, 0);
+
+        // Changing the second task reruns it and everything after it
+        let fingerprints = ["v1", "v2", "v1", "v1", "v1"];
+        let report = executor.resume(chain(fingerprints, None), &store).await.unwrap();
+        assert_eq!(runs_since(&[1, 1, 1, 2, 1]), [0, 1, 1, 1, 1]);
+        assert_eq!(paths(&report), [restored, ran, ran, ran, ran]);
+
+        // A corrupt checkpoint is ignored; as the rerun's output is the
+        // same, the tasks after it are still restored
+        let mut entry = store.get("t3").unwrap().unwrap();
+        entry[0] ^= 1;
+        store.put("t3", &entry).unwrap();
+        let report = executor.resume(chain(fingerprints, None), &store).await.unwrap();
+        assert_eq!(runs_since(&[1, 2, 2, 3, 2]), [0, 0, 1, 0, 0]);
+        assert_eq!(report.output::<u64>("t5"), Some(&5));
+    }
+
+    #[tokio::test]
+    async fn test_always_run_tasks_run_after_failures() {
+        let cleanup = Step {
+            policy: TaskPolicy {
+                always_run: true,
+                ..TaskPolicy::default()
+            },
+            ..Step::new("cleanup", &["top"], 0)
+        };
+        let graph = diamond(true).task(cleanup);
+        let report = executor(4, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert_eq!(report.status, RunStatus::Failed);
+        assert_eq!(report.task("cleanup").unwrap().state, TaskState::Succeeded);
+        assert_eq!(report.task("bottom").unwrap().state, TaskState::Skipped);
+
+        let report = executor(4, ErrorPolicy::FailFast).run_graph(diamond(false)).await.unwrap();
+        assert_eq!(report.status, RunStatus::Succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_runs_tasks_in_parallel() {
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let graph = ["a", "b", "c", "d"].into_iter().fold(TaskGraph::new(), |graph, id| {
+            graph.task(Step {
+                peak: Arc::clone(&peak),
+                running: Arc::clone(&running),
+                ..Step::new(id, &[], 50)
+            })
+        });
+        let report = executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert!(report.is_success());
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        // Two rounds of two tasks, not four rounds of one
+        assert!(report.duration >= Duration::from_millis(100));
+        assert!(report.duration < Duration::from_millis(200), "{:?}", report.duration);
+    }
+
+    fn governor(ram_cap_bytes: u64) -> Arc<ResourceGovernor> {
+        let config = ResourceGovernorConfig {
+            ram_cap_bytes: Some(ram_cap_bytes),
+            ..ResourceGovernorConfig::default()
+        };
+        Arc::new(ResourceGovernor::new(config).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_admission_reserves_estimated_ram() {
+        let governor = governor(100);
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        // Any two together need more than the cap
+        let heavy = |id| Step {
+            ram: Some(60),
+            peak: Arc::clone(&peak),
+            running: Arc::clone(&running),
+            ..Step::new(id, &[], 30)
+        };
+        let graph = TaskGraph::new().task(heavy("a")).task(heavy("b").failing()).task(heavy("c"));
+        let executor = executor(4, ErrorPolicy::Continue).with_admission(Arc::clone(&governor));
+        let run = executor.run_graph_detached(graph).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(governor.current_ram_usage(), 60);
+        let report = run.await.unwrap();
+
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        let states: Vec<_> = report.tasks.iter().map(|task| task.state).collect();
+        assert_eq!(states, [TaskState::Succeeded, TaskState::Failed, TaskState::Succeeded]);
+        // Released after succeeding and failing alike
+        assert_eq!(governor.current_ram_usage(), 0);
+        let mut throttled: Vec<_> = report.tasks.iter().map(|task| task.throttled).collect();
+        throttled.sort();
+        assert!(throttled[1] >= Duration::from_millis(20), "{throttled:?}");
+        assert!(throttled[2] >= Duration::from_millis(50), "{throttled:?}");
+        assert!(report.task("c").unwrap().wait >= Duration::from_millis(50));
+        let labels = governor.statistics().labels;
+        assert!(["a", "b", "c"].iter().all(|id| labels[*id].total_operations == 1));
+
+        let greedy = TaskGraph::new().task(Step {
+            ram: Some(101),
+            ..Step::new("greedy", &[], 0)
+        });
+        let err = executor.run_graph(greedy).await.unwrap_err();
+        assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_paused_governor_holds_back_tasks() {
+        let governor = governor(1024);
+        governor.pause();
+        let executor = executor(2, ErrorPolicy::FailFast).with_admission(Arc::clone(&governor));
+        let graph = TaskGraph::new().task(Step::new("a", &[], 0));
+        let run = executor.run_graph_detached(graph).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(governor.statistics().total_operations, 0);
+
+        governor.resume();
+        let report = run.await.unwrap();
+        assert!(report.is_success());
+        assert!(report.tasks[0].throttled >= Duration::from_millis(40));
+    }
+
+    /// Sleeps in a resource class, logging when it starts
+    struct Classed {
+        id: &'static str,
+        dependencies: &'static [&'static str],
+        class: Option<&'static str>,
+        priority: OperationPriority,
+        sleep_ms: u64,
+        starts: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::scheduler::Task for Classed {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.dependencies.iter().map(|id| id.to_string()).collect()
+        }
+
+        fn priority(&self) -> OperationPriority {
+            self.priority
+        }
+
+        fn resource_class(&self) -> Option<String> {
+            self.class.map(str::to_string)
+        }
+
+        fn cost_hint(&self) -> Option<Duration> {
+            Some(Duration::from_millis(self.sleep_ms))
+        }
+
+        async fn run(&self, _: TaskContext) -> Result<TaskOutput> {
+            self.starts.lock().unwrap().push(self.id);
+            tokio::time::sleep(Duration::from_millis(self.sleep_ms)).await;
+            Ok(TaskOutput::empty())
+        }
+    }
+
+    /// An executor with one `gpu` slot, boosting priorities every `aging`
+    fn one_gpu(aging: Option<Duration>) -> Executor {
+        let config = FrameworkConfig {
+            workers: 8,
+            resource_classes: HashMap::from([("gpu".to_string(), 1)]),
+            priority_aging: aging,
+            ..FrameworkConfig::default()
+        };
+        Executor::new(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_task_jumps_class_queue() {
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let task = |id, dependencies, class, priority, sleep_ms| Classed {
+            id,
+            dependencies,
+            class,
+            priority,
+            sleep_ms,
+            starts: Arc::clone(&starts),
+        };
+        let low = |id| task(id, &[], Some("gpu"), OperationPriority::Low, 40);
+        let graph = TaskGraph::new()
+            .task(low("low1"))
+            .task(low("low2"))
+            .task(low("low3"))
+            .task(task("gate", &[], None, OperationPriority::Normal, 10))
+            .task(task("high", &["gate"], Some("gpu"), OperationPriority::High, 10));
+        let executor = one_gpu(None);
+        let report = executor.run_graph(graph).await.unwrap();
+
+        assert!(report.is_success());
+        // The running low priority task finishes; the queued ones wait
+        let starts = starts.lock().unwrap();
+        let gpu: Vec<_> = starts.iter().copied().filter(|&id| id != "gate").collect();
+        assert_eq!(gpu, ["low1", "high", "low2", "low3"]);
+        let stats = executor.stats();
+        let class = &stats.classes["gpu"];
+        assert_eq!((class.capacity, class.running, class.started), (1, 0, 4));
+        assert_eq!(class.wait_times.count(), 4);
+        assert_eq!(class.utilization(), 0.0);
+        // Three low priority tasks in a row held the slot
+        assert!(class.busy >= Duration::from_millis(130), "{:?}", class.busy);
+        assert!(class.wait_times.max >= Duration::from_millis(70));
+        assert_eq!(stats.wait_times.count(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_aging_runs_starved_tasks() {
+        let run = |aging| async move {
+            let starts = Arc::new(Mutex::new(Vec::new()));
+            let task = |id, dependencies, priority| Classed {
+                id,
+                dependencies,
+                class: Some("gpu"),
+                priority,
+                sleep_ms: 30,
+                starts: Arc::clone(&starts),
+            };
+            let high = |id, dependencies| task(id, dependencies, OperationPriority::High);
+            // High priority tasks keep coming, one after the other
+            let graph = TaskGraph::new()
+                .task(task("low", &[], OperationPriority::Low))
+                .task(high("h0", &[]))
+                .task(high("h1", &["h0"]))
+                .task(high("h2", &["h1"]))
+                .task(high("h3", &["h2"]))
+                .task(high("h4", &["h3"]))
+                .task(high("h5", &["h4"]));
+            assert!(one_gpu(aging).run_graph(graph).await.unwrap().is_success());
+            let starts = starts.lock().unwrap();
+            starts.iter().position(|&id| id == "low").unwrap()
+        };
+
+        assert_eq!(run(None).await, 6);
+        let position = run(Some(Duration::from_millis(40))).await;
+        assert!((1..6).contains(&position), "{position}");
+    }
+
+    #[tokio::test]
+    async fn test_resource_classes_must_be_declared() {
+        let config = FrameworkConfig {
+            resource_classes: HashMap::from([("gpu".to_string(), 0)]),
+            ..FrameworkConfig::default()
+        };
+        assert!(matches!(Executor::new(&config), Err(SystemError::Config { .. })));
+
+        let graph = TaskGraph::new().task(Classed {
+            id: "train",
+            dependencies: &[],
+            class: Some("tpu"),
+            priority: OperationPriority::Normal,
+            sleep_ms: 0,
+            starts: Arc::default(),
+        });
+        let err = one_gpu(None).run_graph(graph).await.unwrap_err();
+        assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+    }
+
+    #[test]
+    fn test_explain_plans_without_running() {
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let task = |id, dependencies, class, sleep_ms| Classed {
+            id,
+            dependencies,
+            class,
+            priority: OperationPriority::Normal,
+            sleep_ms,
+            starts: Arc::clone(&starts),
+        };
+        let graph = TaskGraph::new()
+            .task(task("merge", &["train", "tune"], None, 10))
+            .task(task("load", &[], None, 10))
+            .task(task("train", &["load"], Some("gpu"), 50))
+            .task(task("tune", &["load"], Some("gpu"), 20))
+            .task(task("report", &["load"], None, 5));
+        let plan = one_gpu(None).explain(&graph).unwrap();
+        assert!(starts.lock().unwrap().is_empty());
+
+        assert_eq!(plan.order, ["load", "train", "tune", "report", "merge"]);
+        let waves: Vec<(Vec<String>, usize)> =
+            plan.waves.iter().map(|wave| (wave.tasks.clone(), wave.parallelism)).collect();
+        assert_eq!(
+            waves,
+            [
+                (vec!["load".to_string()], 1),
+                // Only one of the `gpu` tasks at a time
+                (vec!["train".to_string(), "tune".to_string(), "report".to_string()], 2),
+                (vec!["merge".to_string()], 1),
+            ]
+        );
+        assert_eq!(plan.critical_path, ["load", "train", "merge"]);
+        assert_eq!(plan.critical_path_cost, Duration::from_millis(70));
+        assert_eq!(plan.placements["gpu"], ["train", "tune"]);
+        assert_eq!(plan.placements.len(), 1);
+
+        let undeclared = TaskGraph::new().task(task("train", &[], Some("tpu"), 0));
+        let err = one_gpu(None).explain(&undeclared).unwrap_err();
+        assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+    }
+
+    fn pool(workers: usize) -> WorkStealingPool {
+        let config = FrameworkConfig {
+            workers,
+            ..FrameworkConfig::default()
+        };
+        WorkStealingPool::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_pool_runs_every_task() {
+        let pool = pool(4);
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100_000 {
+            let done = Arc::clone(&done);
+            pool.spawn(move || {
+                done.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        pool.spawn(|| panic!("task panicked"));
+        pool.wait_idle();
+        assert_eq!(done.load(Ordering::Relaxed), 100_000);
+        let stats = pool.shutdown(ShutdownPolicy::Drain);
+        assert_eq!(stats.executed(), 100_001);
+        assert_eq!(stats.panicked, 1);
+        assert_eq!(stats.workers.len(), 4);
+        assert_eq!(stats.injector_depth, 0);
+    }
+
+    #[test]
+    fn test_pool_workers_steal_under_imbalance() {
+        let pool = pool(4);
+        let handle = pool.handle();
+        let done = Arc::new(AtomicUsize::new(0));
+        let spawner = Arc::clone(&done);
+        // One task fills its worker's queue; the others only get work by
+        // stealing
+        pool.spawn(move || {
+            for _ in 0..100_000 {
+                let done = Arc::clone(&spawner);
+                handle
+                    .spawn(move || {
+                        let mut x = 0_u64;
+                        for i in 0..100 {
+                            x = std::hint::black_box(x + i);
+                        }
+                        done.fetch_add(1, Ordering::Relaxed);
+                    })
+                    .unwrap();
+            }
+        });
+        pool.wait_idle();
+        assert_eq!(done.load(Ordering::Relaxed), 100_000);
+        let stats = pool.stats();
+        assert!(stats.steals() > 0, "{stats:?}");
+        let busy = stats.workers.iter().filter(|worker| worker.executed > 0).count();
+        assert!(busy > 1, "{stats:?}");
+    }
+
+    #[test]
+    fn test_pool_shutdown_policies() {
+        for policy in [ShutdownPolicy::Drain, ShutdownPolicy::Cancel] {
+            let pool = pool(1);
+            let done = Arc::new(AtomicUsize::new(0));
+            let (started, wait) = std::sync::mpsc::channel();
+            pool.spawn(move || {
+                started.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(50));
+            });
+            for _ in 0..100 {
+                let done = Arc::clone(&done);
+                pool.spawn(move || {
+                    done.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+            wait.recv().unwrap();
+            let handle = pool.handle();
+            let stats = pool.shutdown(policy);
+            assert!(handle.spawn(|| {}).is_err());
+            match policy {
+                ShutdownPolicy::Drain => {
+                    assert_eq!(done.load(Ordering::Relaxed), 100);
+                    assert_eq!((stats.executed(), stats.cancelled), (101, 0));
+                },
+                ShutdownPolicy::Cancel => {
+                    // The running task finishes; the queued ones never start
+                    assert_eq!(done.load(Ordering::Relaxed), 0);
+                    assert_eq!((stats.executed(), stats.cancelled), (1, 100));
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_pool_pins_workers() {
+        let config = FrameworkConfig {
+            workers: 2,
+            pin_workers: true,
+            ..FrameworkConfig::default()
+        };
+        let pool = WorkStealingPool::new(&config).unwrap();
+        let done = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&done);
+        pool.spawn(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        pool.wait_idle();
+        assert_eq!(done.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_fifo_pool_shares_one_queue() {
+        let config = FrameworkConfig {
+            workers: 4,
+            scheduling: SchedulingPolicy::Fifo,
+            ..FrameworkConfig::default()
+        };
+        let pool = WorkStealingPool::new(&config).unwrap();
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..1_000 {
+            let handle = pool.handle();
+            let counter = Arc::clone(&done);
+            pool.spawn(move || {
+                // Even tasks spawned from a worker go to the shared queue
+                let counter = Arc::clone(&counter);
+                handle
+                    .spawn(move || {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    })
+                    .unwrap();
+            });
+        }
+        pool.wait_idle();
+        let stats = pool.shutdown(ShutdownPolicy::Drain);
+        assert_eq!(done.load(Ordering::Relaxed), 1_000);
+        assert_eq!(stats.executed(), 2_000);
+        assert_eq!(stats.steals(), 0);
+        assert!(stats.workers.iter().all(|worker| worker.queue_depth == 0));
+    }
+
+    #[test]
+    fn test_cooperative_pool_keeps_spawned_tasks_on_their_worker() {
+        let config = FrameworkConfig {
+            workers: 4,
+            scheduling: SchedulingPolicy::Cooperative,
+            ..FrameworkConfig::default()
+        };
+        let pool = WorkStealingPool::new(&config).unwrap();
+        let moved = Arc::new(AtomicUsize::new(0));
+        for _ in 0..200 {
+            let handle = pool.handle();
+            let moved = Arc::clone(&moved);
+            pool.spawn(move || {
+                let spawner = std::thread::current().id();
+                for _ in 0..5 {
+                    let moved = Arc::clone(&moved);
+                    handle
+                        .spawn(move || {
+                            if std::thread::current().id() != spawner {
+                                moved.fetch_add(1, Ordering::Relaxed);
+                            }
+                        })
+                        .unwrap();
+                }
+                // Idle workers would steal the tasks spawned above by now
+                std::thread::sleep(Duration::from_micros(100));
+            });
+        }
+        pool.wait_idle();
+        let stats = pool.shutdown(ShutdownPolicy::Drain);
+        assert_eq!(stats.executed(), 1_200);
+        assert_eq!(stats.steals(), 0);
+        assert_eq!(moved.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_first_error() {
+        let mut group = TaskGroup::new();
+        group.cancel_on_first_error();
+        group.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        });
+        group.spawn(async { Err(SystemError::internal("boom", None)) });
+
+        let results = tokio::time::timeout(Duration::from_secs(5), group.wait_all())
+            .await
+            .expect("the slow task should have been cancelled");
+        assert!(results[0].as_ref().unwrap_err().to_string().contains("task cancelled"));
+        assert!(matches!(results[1], Err(SystemError::Internal { .. })));
+    }
+
+    /// Sleeps asynchronously for a moment, then blocks its thread for
+    /// `block_ms` or panics
+    struct Unruly {
+        id: &'static str,
+        kind: ExecutionKind,
+        block_ms: u64,
+        panics: bool,
+    }
+
+    impl Unruly {
+        fn blocking(id: &'static str, kind: ExecutionKind, block_ms: u64) -> Self {
+            Self {
+                id,
+                kind,
+                block_ms,
+                panics: false,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::scheduler::Task for Unruly {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn execution_kind(&self) -> ExecutionKind {
+            self.kind
+        }
+
+        async fn run(&self, _ctx: TaskContext) -> Result<TaskOutput> {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(!self.panics, "{} exploded", self.id);
+            std::thread::sleep(Duration::from_millis(self.block_ms));
+            Ok(TaskOutput::empty())
+        }
+    }
+
+    /// Ticks every 10ms; its output is the longest time between two ticks
+    struct Ticker;
+
+    #[async_trait::async_trait]
+    impl crate::scheduler::Task for Ticker {
+        fn id(&self) -> &str {
+            "ticker"
+        }
+
+        async fn run(&self, _ctx: TaskContext) -> Result<TaskOutput> {
+            let mut last = Instant::now();
+            let mut longest = Duration::ZERO;
+            for _ in 0..20 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                longest = longest.max(last.elapsed());
+                last = Instant::now();
+            }
+            Ok(TaskOutput::new(longest))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_fails_with_payload() {
+        for kind in [ExecutionKind::Async, ExecutionKind::Blocking] {
+            let graph = TaskGraph::new()
+                .task(Unruly {
+                    panics: true,
+                    ..Unruly::blocking("unruly", kind, 0)
+                })
+                .task(Step::new("after", &["unruly"], 0))
+                .task(Step::new("side", &[], 0));
+            let executor = executor(2, ErrorPolicy::Continue);
+            let report = executor.run_graph(graph).await.unwrap();
+
+            let unruly = report.task("unruly").unwrap();
+            assert_eq!(unruly.state, TaskState::Failed);
+            assert_eq!(unruly.attempts, 1);
+            let message = unruly.error.as_ref().unwrap().to_string();
+            assert!(message.contains("task panicked: unruly exploded"), "{message}");
+            assert_eq!(report.task("after").unwrap().state, TaskState::Skipped);
+            assert_eq!(report.task("side").unwrap().state, TaskState::Succeeded);
+
+            // The executor is unharmed
+            let graph = TaskGraph::new().task(Step::new("again", &[], 0));
+            assert!(executor.run_graph(graph).await.unwrap().is_success());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocking_tasks_leave_runtime_responsive() {
+        // A single-threaded runtime stalls while an async task blocks it
+        let graph = TaskGraph::new()
+            .task(Unruly::blocking("unruly", ExecutionKind::Async, 300))
+            .task(Ticker);
+        let report = executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert!(*report.output::<Duration>("ticker").unwrap() >= Duration::from_millis(250));
+
+        let graph = TaskGraph::new()
+            .task(Unruly::blocking("unruly", ExecutionKind::Blocking, 300))
+            .task(Ticker);
+        let report = executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap();
+        assert!(report.is_success());
+        assert!(*report.output::<Duration>("ticker").unwrap() < Duration::from_millis(100));
+        assert!(report.task("unruly").unwrap().duration >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pool_is_bounded() {
+        let config = FrameworkConfig {
+            workers: 4,
+            blocking_workers: 1,
+            ..FrameworkConfig::default()
+        };
+        let executor = Executor::new(&config).unwrap();
+        let graph = TaskGraph::new()
+            .task(Unruly::blocking("a", ExecutionKind::Blocking, 50))
+            .task(Unruly::blocking("b", ExecutionKind::Blocking, 50));
+        let report = executor.run_graph(graph).await.unwrap();
+        assert!(report.is_success());
+        assert!(report.duration >= Duration::from_millis(100));
+
+        let config = FrameworkConfig {
+            blocking_workers: 0,
+            ..config
+        };
+        assert!(Executor::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_catches_tasks_blocking_runtime() {
+        let graph = || {
+            TaskGraph::new()
+                .task(Unruly::blocking("hog", ExecutionKind::Async, 50))
+                .task(Unruly::blocking("offloaded", ExecutionKind::Blocking, 50))
+        };
+
+        let logging = executor(2, ErrorPolicy::Continue)
+            .with_watchdog(Duration::from_millis(10), WatchdogAction::Log);
+        assert!(logging.run_graph(graph()).await.unwrap().is_success());
+
+        let failing = executor(2, ErrorPolicy::Continue)
+            .with_watchdog(Duration::from_millis(10), WatchdogAction::Fail);
+        let report = failing.run_graph(graph()).await.unwrap();
+        let hog = report.task("hog").unwrap();
+        assert_eq!(hog.state, TaskState::Failed);
+        let message = hog.error.as_ref().unwrap().to_string();
+        assert!(message.contains("task hog blocked the runtime"), "{message}");
+        assert_eq!(report.task("offloaded").unwrap().state, TaskState::Succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_emits_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+        // Records the metrics of each thread apart, so tests do not mix
+        let _ = DebuggingRecorder::per_thread().install();
+        let graph = TaskGraph::new()
+            .task(Step::new("top", &[], 0))
+            .task(Step::new("left", &["top"], 0).failing())
+            .task(Step::new("right", &["top"], 0))
+            .task(Step::new("bottom", &["left"], 0));
+        executor(2, ErrorPolicy::Continue).run_graph(graph).await.unwrap();
+
+        let snapshot = Snapshotter::current_thread_snapshot().unwrap().into_vec();
+        let metric = |name: &str| {
+            snapshot
+                .iter()
+                .find(|(key, ..)| key.key().name() == name)
+                .map(|(.., value)| value)
+                .unwrap_or_else(|| panic!("{name} was not emitted"))
+        };
+        assert_eq!(metric("executor_tasks_started_total"), &DebugValue::Counter(3));
+        assert_eq!(metric("executor_tasks_completed_total"), &DebugValue::Counter(2));
+        assert_eq!(metric("executor_tasks_failed_total"), &DebugValue::Counter(1));
+        for gauge in ["executor_queue_depth", "executor_active_tasks"] {
+            assert!(matches!(metric(gauge), DebugValue::Gauge(value) if value.into_inner() == 0.0));
+        }
+        let DebugValue::Histogram(durations) = metric("executor_graph_duration_seconds") else {
+            panic!("graph durations are not a histogram");
+        };
+        assert_eq!(durations.len(), 1);
+    }
+
+    /// A span's name, fields and parent's name
+    type SpanRecord = (String, Vec<String>, Option<String>);
+
+    /// Spans created
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<SpanRecord>>>);
+
+    impl<S> tracing_subscriber::Layer<S> for Spans
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = Vec::new();
+            attrs.record(&mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| {
+                fields.push(format!("{}={value:?}", field.name()));
+            });
+            let parent = ctx.span(id).and_then(|span| span.parent()).map(|p| p.name().to_string());
+            let name = attrs.metadata().name().to_string();
+            self.0.lock().unwrap().push((name, fields, parent));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_traces_tasks_in_graph_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let spans = Spans::default();
+        let _default = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(spans.clone()),
+        );
+        let flaky = Step {
+            flaky: 1,
+            policy: retry(false),
+            ..Step::new("flaky", &["top"], 0)
+        };
+        let graph = TaskGraph::new().task(Step::new("top", &[], 0)).task(flaky);
+        assert!(executor(2, ErrorPolicy::FailFast).run_graph(graph).await.unwrap().is_success());
+
+        let spans = spans.0.lock().unwrap().clone();
+        assert_eq!(spans.len(), 4);
+        let (name, fields, parent) = &spans[0];
+        assert_eq!((name.as_str(), parent), ("graph", &None));
+        assert!(fields.contains(&"tasks=2".to_string()));
+        let tasks: Vec<(&str, &str)> = spans[1..]
+            .iter()
+            .map(|(name, fields, parent)| {
+                assert_eq!((name.as_str(), parent.as_deref()), ("task", Some("graph")));
+                (fields[0].as_str(), fields[1].as_str())
+            })
+            .collect();
+        assert_eq!(
+            tasks,
+            [
+                ("task_id=\"top\"", "attempt=1"),
+                ("task_id=\"flaky\"", "attempt=1"),
+                ("task_id=\"flaky\"", "attempt=2"),
+            ]
+        );
+    }
+}
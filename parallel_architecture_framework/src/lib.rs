@@ -5,26 +5,97 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
+use std::collections::HashMap;
+use std::time::Duration;
 
 pub mod api;
+pub mod checkpoint;
 pub mod communication;
 pub mod config;
 pub mod core;
+pub mod distributed;
+pub mod dynamic;
 pub mod executor;
+pub mod par;
+pub mod replay;
 pub mod scheduler;
+pub mod status;
+
+pub use checkpoint::{CheckpointStore, DirectoryCheckpointStore, SavedOutput};
+#[cfg(feature = "sled-checkpoints")]
+pub use checkpoint::SledCheckpointStore;
+pub use communication::StreamItem;
+pub use distributed::{
+    Coordinator, CoordinatorConfig, RemoteHandler, RemoteInput, RemoteOutput, RemoteTask,
+    TaskRegistry, WorkerHandle, WorkerServer,
+};
+pub use dynamic::{Completion, DynamicGraph, DynamicGraphConfig};
+pub use executor::{
+    ClassStats, ErrorPolicy, ExecutionPlan, Executor, ExecutorStats, GraphReport, OutputPath,
+    PlanWave, PoolHandle, RunHandle, RunStatus, SchedulerStats, ShutdownPolicy, TaskGroup,
+    TaskReport, TaskState, WaitHistogram, WatchdogAction, WorkStealingPool, WorkerStats,
+};
+pub use par::{par_for_each_stream, par_map, par_map_reduce, Parallel};
+pub use replay::{Decision, DecisionLog};
+pub use scheduler::{
+    ExecutionKind, OperationPriority, Task, TaskContext, TaskGraph, TaskOutput, TaskPolicy,
+};
+pub use status::{ExecutorStatus, GraphStatus, StatusServer, TaskPhase, TaskStatus};
 
 /// Framework configuration
 #[derive(Debug, Clone)]
 pub struct FrameworkConfig {
     /// Number of worker threads
     pub workers: usize,
+    /// Threads an [`Executor`] runs [`ExecutionKind::Blocking`] tasks on at
+    /// once, independently of `workers`
+    pub blocking_workers: usize,
+    /// Whether to pin each [`WorkStealingPool`] worker to a CPU core
+    pub pin_workers: bool,
+    /// How [`WorkStealingPool`] workers share tasks
+    pub scheduling: SchedulingPolicy,
+    /// Tasks of each resource class an [`Executor`] runs at once, on top of
+    /// the `workers` limit, by class name
+    pub resource_classes: HashMap<String, usize>,
+    /// How long a ready task waits before an [`Executor`] boosts its
+    /// priority one level, and again after every further wait as long, so
+    /// that low priority tasks cannot starve; `None` never boosts
+    pub priority_aging: Option<Duration>,
+}
+
+/// How the workers of a [`WorkStealingPool`] share tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SchedulingPolicy {
+    /// Every task goes through one shared queue, taken one at a time
+    Fifo,
+    /// Every worker has its own queue; idle workers take batches from the
+    /// shared queue and steal from workers with at least
+    /// `steal_threshold` queued tasks
+    WorkStealing {
+        /// Queued tasks a worker must have to be stolen from, at least 1
+        steal_threshold: usize,
+    },
+    /// Every worker has its own queue that no other worker takes from: a
+    /// task's spawned tasks wait for the worker it ran on, and idle workers
+    /// take one task at a time from the shared queue
+    Cooperative,
+}
+
+impl Default for SchedulingPolicy {
+    fn default() -> Self {
+        Self::WorkStealing { steal_threshold: 1 }
+    }
 }
 
 impl Default for FrameworkConfig {
     fn default() -> Self {
         Self {
             workers: num_cpus::get(),
+            blocking_workers: num_cpus::get(),
+            pin_workers: false,
+            scheduling: SchedulingPolicy::default(),
+            resource_classes: HashMap::new(),
+            priority_aging: Some(Duration::from_secs(30)),
         }
     }
 }
@@ -0,0 +1,557 @@
+//! Par module
+//!
+//! Runs a closure over many items with bounded parallelism, without a
+//! hand-built [`TaskGraph`]: [`par_map`] maps items in order,
+//! [`par_map_reduce`] folds them, and [`par_for_each_stream`] consumes an
+//! async stream. [`Parallel`] sets the chunk size, a governor to admit
+//! chunks through, and whether to stop at the first error or collect them
+//! all into an [`ErrorCollection`].
+//!
+//! Items are split into chunks, each run as one task of a graph on an
+//! [`Executor`], so per-task overhead is paid once per chunk.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream, StreamExt};
+use parking_lot::Mutex;
+use shared_core::{ErrorCollection, ResourceGovernor, Result, SystemError};
+
+use crate::executor::{ErrorPolicy, Executor, TaskState};
+use crate::scheduler::{Task, TaskContext, TaskGraph, TaskOutput};
+use crate::FrameworkConfig;
+
+/// Chunks per unit of concurrency when the chunk size is left to
+/// [`Parallel`], so that uneven chunks still keep every slot busy
+const CHUNKS_PER_WORKER: usize = 4;
+
+/// Turns the items of a chunk into its output
+type Work<T, O> = Arc<dyn Fn(Vec<T>) -> BoxFuture<'static, Result<O>> + Send + Sync>;
+
+/// Runs closures over items with bounded parallelism
+///
+/// By default the chunk size spreads the items over four chunks per unit
+/// of concurrency, and stream items are run one per chunk.
+#[derive(Clone)]
+pub struct Parallel {
+    concurrency: usize,
+    chunk_size: Option<usize>,
+    governor: Option<Arc<ResourceGovernor>>,
+}
+
+impl Default for Parallel {
+    /// One chunk at a time per CPU
+    fn default() -> Self {
+        Self::new(num_cpus::get())
+    }
+}
+
+impl std::fmt::Debug for Parallel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Parallel")
+            .field("concurrency", &self.concurrency)
+            .field("chunk_size", &self.chunk_size)
+            .field("governed", &self.governor.is_some())
+            .finish()
+    }
+}
+
+impl Parallel {
+    /// Run up to `concurrency` chunks at once
+    ///
+    /// A `concurrency` of 0 makes every run fail with a `Config` error.
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            concurrency,
+            chunk_size: None,
+            governor: None,
+        }
+    }
+
+    /// Put `chunk_size` items in each chunk, the last one excepted
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size.max(1));
+        self
+    }
+
+    /// Admit chunks through `governor`, as
+    /// [`Executor::with_admission`] does
+    pub fn with_governor(mut self, governor: Arc<ResourceGovernor>) -> Self {
+        self.governor = Some(governor);
+        self
+    }
+
+    /// Apply `f` to every item, returning the results in the order of the
+    /// items
+    ///
+    /// `f` runs on blocking threads. The first error stops the run and is
+    /// returned; items after it in its chunk are not mapped.
+    pub async fn map<T, R, F>(&self, items: impl IntoIterator<Item = T>, f: F) -> Result<Vec<R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Result<R> + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let work: Work<T, Vec<R>> = Arc::new(move |items| {
+            let f = Arc::clone(&f);
+            blocking(move || items.into_iter().map(|item| f(item)).collect())
+        });
+        let chunks = self.run(self.chunks(items.into_iter().collect()), work).await?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    /// Apply `f` to every item as [`map`](Self::map) does, but map every
+    /// item whatever fails and return every error, in the order of the
+    /// items, if any did
+    pub async fn map_collect<T, R, F>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        f: F,
+    ) -> std::result::Result<Vec<R>, ErrorCollection>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Result<R> + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let work: Work<T, Vec<Result<R>>> = Arc::new(move |items| {
+            let f = Arc::clone(&f);
+            blocking(move || Ok(items.into_iter().map(|item| f(item)).collect()))
+        });
+        let chunks = self.run(self.chunks(items.into_iter().collect()), work).await;
+        let mut errors = ErrorCollection::new();
+        let mut values = Vec::new();
+        for result in chunks.map_err(|err| ErrorCollection::from_iter([err]))?.into_iter().flatten()
+        {
+            match result {
+                Ok(value) => values.push(value),
+                Err(err) => errors.push(err),
+            }
+        }
+        errors.into_result(values)
+    }
+
+    /// Map every item with `map` and combine the results with `reduce`,
+    /// starting from `identity`
+    ///
+    /// Each chunk is folded on its own, then the chunks are folded in
+    /// order, so the result is that of a sequential fold if `reduce` is
+    /// associative and `identity` its identity element. The first error
+    /// stops the run and is returned.
+    pub async fn map_reduce<T, R, M, F>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        map: M,
+        reduce: F,
+        identity: R,
+    ) -> Result<R>
+    where
+        T: Send + 'static,
+        R: Clone + Send + Sync + 'static,
+        M: Fn(T) -> Result<R> + Send + Sync + 'static,
+        F: Fn(R, R) -> R + Send + Sync + 'static,
+    {
+        let map = Arc::new(map);
+        let reduce = Arc::new(reduce);
+        let work: Work<T, R> = {
+            let (reduce, identity) = (Arc::clone(&reduce), identity.clone());
+            Arc::new(move |items: Vec<T>| {
+                let (map, reduce, identity) =
+                    (Arc::clone(&map), Arc::clone(&reduce), identity.clone());
+                blocking(move || {
+                    items.into_iter().try_fold(identity, |acc, item| Ok(reduce(acc, map(item)?)))
+                })
+            })
+        };
+        let chunks = self.run(self.chunks(items.into_iter().collect()), work).await?;
+        Ok(chunks.into_iter().fold(identity, |acc, chunk| reduce(acc, chunk)))
+    }
+
+    /// Await `f` on every item of `stream`
+    ///
+    /// The stream is read in batches of one chunk per unit of concurrency,
+    /// each batch run to completion before the next is read, so that a
+    /// slow consumer holds back the stream. The first error stops the run
+    /// and is returned; the rest of the stream is not read.
+    pub async fn for_each_stream<S, F, Fut>(&self, stream: S, f: F) -> Result<()>
+    where
+        S: Stream + Send,
+        S::Item: Send + 'static,
+        F: Fn(S::Item) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        let work: Work<S::Item, ()> = Arc::new(move |items| {
+            let f = Arc::clone(&f);
+            async move {
+                for item in items {
+                    f(item).await?;
+                }
+                Ok(())
+            }
+            .boxed()
+        });
+        let mut batches = std::pin::pin!(self.batches(stream));
+        while let Some(chunks) = batches.next().await {
+            self.run(chunks, Arc::clone(&work)).await?;
+        }
+        Ok(())
+    }
+
+    /// Await `f` on every item of `stream` as
+    /// [`for_each_stream`](Self::for_each_stream) does, but read the
+    /// whole stream whatever fails and return every error if any did
+    pub async fn for_each_stream_collect<S, F, Fut>(
+        &self,
+        stream: S,
+        f: F,
+    ) -> std::result::Result<(), ErrorCollection>
+    where
+        S: Stream + Send,
+        S::Item: Send + 'static,
+        F: Fn(S::Item) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        let work: Work<S::Item, Vec<SystemError>> = Arc::new(move |items| {
+            let f = Arc::clone(&f);
+            async move {
+                let mut errors = Vec::new();
+                for item in items {
+                    if let Err(err) = f(item).await {
+                        errors.push(err);
+                    }
+                }
+                Ok(errors)
+            }
+            .boxed()
+        });
+        let mut errors = ErrorCollection::new();
+        let mut batches = std::pin::pin!(self.batches(stream));
+        while let Some(chunks) = batches.next().await {
+            match self.run(chunks, Arc::clone(&work)).await {
+                Ok(chunks) => errors.extend(chunks.into_iter().flatten()),
+                Err(err) => {
+                    errors.push(err);
+                    break;
+                },
+            }
+        }
+        errors.into_result(())
+    }
+
+    /// `items` split into chunks
+    fn chunks<T>(&self, items: Vec<T>) -> Vec<Vec<T>> {
+        let size = self.chunk_size.unwrap_or_else(|| {
+            items.len().div_ceil(self.concurrency.max(1) * CHUNKS_PER_WORKER).max(1)
+        });
+        let mut chunks = Vec::with_capacity(items.len().div_ceil(size));
+        let mut items = items.into_iter().peekable();
+        while items.peek().is_some() {
+            chunks.push(items.by_ref().take(size).collect());
+        }
+        chunks
+    }
+
+    /// `stream` split into batches of up to one chunk per unit of
+    /// concurrency
+    fn batches<S: Stream>(&self, stream: S) -> impl Stream<Item = Vec<Vec<S::Item>>> {
+        let size = self.chunk_size.unwrap_or(1);
+        let per_batch = self.concurrency.max(1);
+        stream.chunks(size).chunks(per_batch)
+    }
+
+    /// Run `work` on every chunk, returning the outputs in chunk order, or
+    /// the error of the first chunk that failed
+    async fn run<T, O>(&self, chunks: Vec<Vec<T>>, work: Work<T, O>) -> Result<Vec<O>>
+    where
+        T: Send + 'static,
+        O: Send + 'static,
+    {
+        if chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+        let config = FrameworkConfig {
+            workers: self.concurrency,
+            ..FrameworkConfig::default()
+        };
+        let mut executor = Executor::new(&config)?.with_error_policy(ErrorPolicy::FailFast);
+        if let Some(governor) = &self.governor {
+            executor = executor.with_admission(Arc::clone(governor));
+        }
+        let ids: Vec<String> = (0..chunks.len()).map(|index| format!("chunk-{index}")).collect();
+        let graph = ids.iter().zip(chunks).fold(TaskGraph::new(), |graph, (id, items)| {
+            graph.task(Chunk {
+                id: id.clone(),
+                items: Mutex::new(Some(items)),
+                work: Arc::clone(&work),
+            })
+        });
+        let mut report = executor.run_graph(graph).await?;
+        if !report.is_success() {
+            let failed = report.tasks.into_iter().find(|task| task.state == TaskState::Failed);
+            return Err(failed.and_then(|task| task.error).unwrap_or_else(|| {
+                SystemError::internal("parallel run stopped without a failed chunk", None)
+            }));
+        }
+        ids.iter()
+            .map(|id| {
+                let output = report.outputs.remove(id).ok_or_else(|| missing(id))?;
+                let slot = output.downcast_ref::<Mutex<Option<O>>>().ok_or_else(|| missing(id))?;
+                let value = slot.lock().take();
+                value.ok_or_else(|| missing(id))
+            })
+            .collect()
+    }
+}
+
+/// Apply `f` to every item with up to `concurrency` chunks running at
+/// once, returning the results in the order of the items
+///
+/// See [`Parallel::map`].
+pub async fn par_map<T, R, F>(
+    items: impl IntoIterator<Item = T>,
+    concurrency: usize,
+    f: F,
+) -> Result<Vec<R>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Result<R> + Send + Sync + 'static,
+{
+    Parallel::new(concurrency).map(items, f).await
+}
+
+/// Map every item with `map` and combine the results with `reduce`,
+/// starting from `identity`, with one chunk running at once per CPU
+///
+/// See [`Parallel::map_reduce`].
+pub async fn par_map_reduce<T, R, M, F>(
+    items: impl IntoIterator<Item = T>,
+    map: M,
+    reduce: F,
+    identity: R,
+) -> Result<R>
+where
+    T: Send + 'static,
+    R: Clone + Send + Sync + 'static,
+    M: Fn(T) -> Result<R> + Send + Sync + 'static,
+    F: Fn(R, R) -> R + Send + Sync + 'static,
+{
+    Parallel::default().map_reduce(items, map, reduce, identity).await
+}
+
+/// Await `f` on every item of `stream`, up to `concurrency` at once
+///
+/// See [`Parallel::for_each_stream`].
+pub async fn par_for_each_stream<S, F, Fut>(stream: S, concurrency: usize, f: F) -> Result<()>
+where
+    S: Stream + Send,
+    S::Item: Send + 'static,
+    F: Fn(S::Item) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    Parallel::new(concurrency).for_each_stream(stream, f).await
+}
+
+/// Run `f` on a blocking thread
+fn blocking<O: Send + 'static>(
+    f: impl FnOnce() -> Result<O> + Send + 'static,
+) -> BoxFuture<'static, Result<O>> {
+    tokio::task::spawn_blocking(f)
+        .map(|joined| {
+            joined.unwrap_or_else(|err| {
+                Err(SystemError::internal(format!("chunk panicked: {err}"), None))
+            })
+        })
+        .boxed()
+}
+
+fn missing(chunk: &str) -> SystemError {
+    SystemError::internal(format!("{chunk} succeeded without an output"), None)
+}
+
+/// Some of the items, run as one task
+struct Chunk<T, O> {
+    id: String,
+    /// Taken by the task's only attempt
+    items: Mutex<Option<Vec<T>>>,
+    work: Work<T, O>,
+}
+
+#[async_trait]
+impl<T: Send + 'static, O: Send + 'static> Task for Chunk<T, O> {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn run(&self, _: TaskContext) -> Result<TaskOutput> {
+        let items = self.items.lock().take().ok_or_else(|| {
+            SystemError::internal(format!("{} already ran", self.id), None)
+        })?;
+        let output = (self.work)(items).await?;
+        Ok(TaskOutput::new(Mutex::new(Some(output))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use shared_core::ResourceGovernorConfig;
+
+    use super::*;
+
+    fn odd_fails(n: u64) -> Result<u64> {
+        if n % 2 == 1 {
+            return Err(SystemError::validation("n", "odd", Some(n.to_string())));
+        }
+        Ok(n * 10)
+    }
+
+    #[tokio::test]
+    async fn test_par_map_keeps_order() {
+        let squares = par_map(0..1000u64, 8, |n| Ok(n * n)).await.unwrap();
+        assert_eq!(squares, (0..1000u64).map(|n| n * n).collect::<Vec<_>>());
+
+        // Later items finishing first still come back in order
+        let slow_first = Parallel::new(4)
+            .with_chunk_size(1)
+            .map(0..8u64, |n| {
+                std::thread::sleep(Duration::from_millis(8 * (8 - n)));
+                Ok(n)
+            })
+            .await
+            .unwrap();
+        assert_eq!(slow_first, (0..8).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_empty_input() {
+        assert!(par_map(Vec::<u64>::new(), 4, Ok).await.unwrap().is_empty());
+        let sum = par_map_reduce(Vec::<u64>::new(), Ok, |a, b| a + b, 7).await.unwrap();
+        assert_eq!(sum, 7);
+        let stream = futures::stream::iter(Vec::<u64>::new());
+        assert!(par_for_each_stream(stream, 4, |_| async { Ok(()) }).await.is_ok());
+        assert_eq!(Parallel::new(2).map_collect(Vec::<u64>::new(), Ok).await.unwrap(), [0u64; 0]);
+    }
+
+    #[tokio::test]
+    async fn test_first_error_propagates() {
+        let err = Parallel::new(2)
+            .with_chunk_size(4)
+            .map(vec![2, 4, 6, 8, 10, 13, 14], odd_fails)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SystemError::Validation { value: Some(ref n), .. } if n == "13"));
+
+        let reduced = par_map_reduce([1u64, 2, 3], odd_fails, |a, b| a + b, 0).await;
+        assert!(reduced.is_err());
+
+        let seen = Arc::new(AtomicUsize::new(0));
+        let stream = futures::stream::iter(0..100u64);
+        let err = Parallel::new(2)
+            .with_chunk_size(5)
+            .for_each_stream(stream, {
+                let seen = Arc::clone(&seen);
+                move |n| {
+                    seen.fetch_add(1, Ordering::SeqCst);
+                    async move { odd_fails(n + 1).map(drop) }
+                }
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SystemError::Validation { .. }));
+        // Only the first batch of the stream was read
+        assert!(seen.load(Ordering::SeqCst) <= 10);
+    }
+
+    #[tokio::test]
+    async fn test_collect_all_errors() {
+        let errors = Parallel::new(3)
+            .with_chunk_size(2)
+            .map_collect(0..10u64, odd_fails)
+            .await
+            .unwrap_err();
+        let odd: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(odd.len(), 5);
+        assert!(odd[0].contains("odd"), "{odd:?}");
+        let values = Parallel::new(3).map_collect([0u64, 2, 4], odd_fails).await.unwrap();
+        assert_eq!(values, [0, 20, 40]);
+
+        let errors = Parallel::new(2)
+            .for_each_stream_collect(futures::stream::iter(0..10u64), |n| async move {
+                odd_fails(n).map(drop)
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(errors.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_stream_concurrency_is_bounded() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let stream = futures::stream::iter(0..12u64);
+        par_for_each_stream(stream, 3, {
+            let (running, peak) = (Arc::clone(&running), Arc::clone(&peak));
+            move |_| {
+                let (running, peak) = (Arc::clone(&running), Arc::clone(&peak));
+                async move {
+                    peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_chunks_go_through_the_governor() {
+        let governor = Arc::new(ResourceGovernor::new(ResourceGovernorConfig::default()).unwrap());
+        let doubled = Parallel::new(2)
+            .with_chunk_size(3)
+            .with_governor(Arc::clone(&governor))
+            .map(0..9u64, |n| Ok(n * 2))
+            .await
+            .unwrap();
+        assert_eq!(doubled.len(), 9);
+        assert_eq!(governor.statistics().labels.len(), 3);
+        assert!(Parallel::new(0).map([1u64], Ok).await.is_err());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_map_reduce_matches_sequential_fold(
+            items in proptest::collection::vec(0..1000u64, 0..200),
+            concurrency in 1..6usize,
+            chunk_size in 1..20usize,
+        ) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let parallel = Parallel::new(concurrency).with_chunk_size(chunk_size);
+            let sum = runtime
+                .block_on(parallel.map_reduce(items.clone(), Ok, u64::wrapping_add, 0))
+                .unwrap();
+            proptest::prop_assert_eq!(sum, items.iter().fold(0u64, |a, &b| a.wrapping_add(b)));
+            // Associative but not commutative
+            let joined = runtime
+                .block_on(parallel.map_reduce(
+                    items.clone(),
+                    |n| Ok(format!("{n},")),
+                    |a, b| a + &b,
+                    String::new(),
+                ))
+                .unwrap();
+            let sequential: String = items.iter().map(|n| format!("{n},")).collect();
+            proptest::prop_assert_eq!(joined, sequential);
+        }
+    }
+}
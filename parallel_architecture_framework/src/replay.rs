@@ -0,0 +1,650 @@
+//! Replay module
+//!
+//! [`Executor::run_deterministic`](crate::Executor::run_deterministic)
+//! runs a graph one task at a time, breaking ties between ready tasks with
+//! a seeded generator, so that a heisenbug of parallel runs can be
+//! reproduced. Every choice it makes goes into a [`DecisionLog`], which
+//! [`Executor::run_replay`](crate::Executor::run_replay) follows to run the
+//! graph in the same order again.
+//!
+//! Time is virtual in these runs: an attempt takes the
+//! [`Task::cost_hint`](crate::Task::cost_hint) of its task, and a backoff
+//! its length, without any waiting. An attempt whose cost hint exceeds its
+//! timeout fails with a `Timeout` error without running, as its timeout
+//! would have fired first.
+
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, RetryPolicy, SystemError};
+use tokio_util::sync::CancellationToken;
+
+use crate::executor::{
+    attempt, cancelled, critical_path, ErrorPolicy, GraphReport, OutputPath, RunStatus, Sandbox,
+    TaskReport, TaskState,
+};
+use crate::scheduler::{TaskContext, TaskGraph, TaskOutput};
+
+/// A choice made by a deterministic run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+    /// Started `chosen` out of the `ready` tasks, in graph order
+    Start {
+        /// Tasks ready to start
+        ready: Vec<String>,
+        /// Task started
+        chosen: String,
+    },
+    /// Retried `task` after waiting `backoff`
+    Retry {
+        /// Task retried
+        task: String,
+        /// Virtual time waited
+        backoff: Duration,
+    },
+}
+
+/// Every choice a deterministic run made, in order, with the shape of the
+/// graph it ran
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecisionLog {
+    /// Seed of the run
+    pub seed: u64,
+    /// Each task, in the order it was added to the graph, with the
+    /// identifiers of its dependencies
+    pub shape: Vec<(String, Vec<String>)>,
+    /// Choices, first made first
+    pub decisions: Vec<Decision>,
+}
+
+/// Where the choices of a deterministic run come from
+pub(crate) enum Choices<'a> {
+    /// Drawn from a generator
+    Seeded(Box<StdRng>),
+    /// Read from a log, at `next`
+    Replay {
+        log: &'a DecisionLog,
+        next: usize,
+    },
+}
+
+impl Choices<'_> {
+    /// Position in `ready` of the task to start, among the `tied` ones
+    fn start(&mut self, ready: &[String], tied: &[usize]) -> Result<usize> {
+        match self {
+            Self::Seeded(rng) => Ok(tied[rng.gen_range(0..tied.len())]),
+            Self::Replay { log, next } => {
+                let logged = log.decisions.get(*next);
+                *next += 1;
+                match logged {
+                    Some(Decision::Start { ready: logged, chosen }) if logged == ready => {
+                        ready.iter().position(|id| id == chosen).ok_or_else(|| {
+                            diverged(*next - 1, format!("start {chosen}"), format!("{ready:?}"))
+                        })
+                    },
+                    other => Err(diverged(
+                        *next - 1,
+                        expected(other),
+                        format!("ready tasks {ready:?}"),
+                    )),
+                }
+            },
+        }
+    }
+
+    /// Backoff before attempt `attempts + 1` at `task`
+    fn backoff(
+        &mut self,
+        task: &str,
+        attempts: u32,
+        err: &SystemError,
+        retry: &RetryPolicy,
+    ) -> Result<Duration> {
+        match self {
+            Self::Seeded(rng) => Ok(retry.backoff(attempts, err, rng)),
+            Self::Replay { log, next } => {
+                let logged = log.decisions.get(*next);
+                *next += 1;
+                match logged {
+                    Some(Decision::Retry { task: logged, backoff }) if logged == task => {
+                        Ok(*backoff)
+                    },
+                    other => {
+                        Err(diverged(*next - 1, expected(other), format!("a retry of {task}")))
+                    },
+                }
+            },
+        }
+    }
+}
+
+/// How far a task of a deterministic run got
+#[derive(Default)]
+struct Progress {
+    state: Option<TaskState>,
+    duration: Duration,
+    attempts: u32,
+    backoffs: Vec<Duration>,
+    path: Option<OutputPath>,
+    error: Option<SystemError>,
+    wait: Duration,
+    ready_since: Option<Duration>,
+}
+
+/// Run `graph` one task at a time as `choices` say, under `policy`
+///
+/// Tasks streaming to each other cannot run one at a time, so a graph
+/// with connections fails with a `Validation` error. So does an invalid
+/// graph, with the error of [`TaskGraph::validate`]. Following a log made
+/// for another graph, or one the run departs from, fails with an
+/// `InvalidState` error.
+pub(crate) async fn run_serial(
+    graph: TaskGraph,
+    policy: ErrorPolicy,
+    sandbox: &Sandbox,
+    seed: u64,
+    mut choices: Choices<'_>,
+) -> Result<(GraphReport, DecisionLog)> {
+    let dependencies = graph.dependencies()?;
+    let fallbacks = graph.fallbacks(&dependencies)?;
+    if let Some(connection) = graph.connections.first() {
+        return Err(SystemError::validation(
+            "connections",
+            "tasks streaming to each other cannot run one at a time",
+            Some(format!("{} -> {}", connection.producer, connection.consumer)),
+        ));
+    }
+    let id = |task: usize| graph.tasks[task].id().to_string();
+    let shape: Vec<(String, Vec<String>)> = dependencies
+        .iter()
+        .enumerate()
+        .map(|(task, deps)| (id(task), deps.iter().map(|&dep| id(dep)).collect()))
+        .collect();
+    if let Choices::Replay { log, .. } = &choices {
+        check_shape(&log.shape, &shape)?;
+    }
+    let mut log = DecisionLog {
+        seed,
+        shape,
+        decisions: Vec::new(),
+    };
+
+    let count = graph.tasks.len();
+    let policies: Vec<_> = graph.tasks.iter().map(|task| task.policy()).collect();
+    let priorities: Vec<_> = graph.tasks.iter().map(|task| task.priority()).collect();
+    let mut dependents = vec![Vec::new(); count];
+    for (task, task_dependencies) in dependencies.iter().enumerate() {
+        for &dependency in task_dependencies {
+            dependents[dependency].push(task);
+        }
+    }
+    let mut waiting_on: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+    let mut standby = vec![false; count];
+    for &fallback in fallbacks.iter().flatten() {
+        standby[fallback] = true;
+    }
+    let mut progress: Vec<Progress> = (0..count).map(|_| Progress::default()).collect();
+    let mut outputs: Vec<Option<TaskOutput>> = vec![None; count];
+    let mut clock = Duration::ZERO;
+    // Each a task and the task run for it, itself or its fallback
+    let mut ready: Vec<(usize, usize)> = (0..count)
+        .filter(|&task| waiting_on[task] == 0 && !standby[task])
+        .map(|task| (task, task))
+        .collect();
+    for &(_, runner) in &ready {
+        progress[runner].ready_since = Some(clock);
+    }
+    let token = CancellationToken::new();
+    let mut failed = false;
+    let mut cleanup = false;
+
+    loop {
+        if ready.is_empty() {
+            // Cleanup tasks whose dependencies did not all succeed run last
+            let left: Vec<usize> = (0..count)
+                .filter(|&task| {
+                    let run = &progress[task];
+                    policies[task].always_run
+                        && run.attempts == 0
+                        && run.state.is_none()
+                        && !standby[task]
+                })
+                .collect();
+            if cleanup || left.is_empty() {
+                break;
+            }
+            cleanup = true;
+            for &task in &left {
+                waiting_on[task] =
+                    dependencies[task].iter().filter(|dep| left.contains(dep)).count();
+            }
+            ready = left
+                .into_iter()
+                .filter(|&task| waiting_on[task] == 0)
+                .map(|task| (task, task))
+                .collect();
+            for &(_, runner) in &ready {
+                progress[runner].ready_since = Some(clock);
+            }
+            continue;
+        }
+
+        let ids: Vec<String> = ready.iter().map(|&(_, runner)| id(runner)).collect();
+        let top = ready.iter().map(|&(_, runner)| priorities[runner]).max();
+        let tied: Vec<usize> = (0..ready.len())
+            .filter(|&position| Some(priorities[ready[position].1]) == top)
+            .collect();
+        let next = choices.start(&ids, &tied)?;
+        let (task, runner) = ready.remove(next);
+        log.decisions.push(Decision::Start {
+            chosen: ids[next].clone(),
+            ready: ids,
+        });
+
+        let run = &mut progress[runner];
+        run.attempts += 1;
+        run.wait += clock - run.ready_since.take().unwrap_or(clock);
+        let inputs = dependencies[task].iter().filter_map(|&dependency| {
+            Some((id(dependency), outputs[dependency].clone()?))
+        });
+        let ctx = TaskContext::new(inputs.collect(), run.attempts, token.clone());
+        let runner_task = &graph.tasks[runner];
+        let cost = runner_task.cost_hint().unwrap_or_default();
+        let (result, spent) = match policies[runner].timeout {
+            Some(limit) if cost > limit => {
+                let limit_ms = u64::try_from(limit.as_millis()).unwrap_or(u64::MAX);
+                (Err(SystemError::timeout(format!("task {}", runner_task.id()), limit_ms)), limit)
+            },
+            _ => (attempt(runner_task, ctx, None, sandbox).await, cost),
+        };
+        clock += spent;
+        run.duration += spent;
+
+        let err = match result {
+            Ok(output) => {
+                if runner != task {
+                    outputs[runner] = Some(output.clone());
+                    progress[runner].state = Some(TaskState::Succeeded);
+                    progress[runner].path = Some(OutputPath::Primary);
+                }
+                outputs[task] = Some(output);
+                progress[task].state = Some(TaskState::Succeeded);
+                progress[task].path = Some(if runner == task {
+                    OutputPath::Primary
+                } else {
+                    OutputPath::Fallback
+                });
+                for &dependent in &dependents[task] {
+                    waiting_on[dependent] -= 1;
+                    let runs = if cleanup {
+                        policies[dependent].always_run
+                    } else {
+                        !standby[dependent] && !failed
+                    };
+                    if waiting_on[dependent] == 0 && runs {
+                        progress[dependent].ready_since = Some(clock);
+                        ready.push((dependent, dependent));
+                    }
+                }
+                ready.sort_unstable();
+                continue;
+            },
+            Err(err) => err,
+        };
+        let retry = &policies[runner].retry;
+        if retry.should_retry(run.attempts, &err) {
+            let backoff = choices.backoff(runner_task.id(), run.attempts, &err, retry)?;
+            log.decisions.push(Decision::Retry {
+                task: runner_task.id().to_string(),
+                backoff,
+            });
+            tracing::debug!("Task {} attempt {} failed: {}", runner_task.id(), run.attempts, err);
+            run.backoffs.push(backoff);
+            clock += backoff;
+            run.ready_since = Some(clock);
+            ready.push((task, runner));
+            ready.sort_unstable();
+            continue;
+        }
+        tracing::warn!("Task {} failed: {}", runner_task.id(), err);
+        run.state = Some(TaskState::Failed);
+        run.error = Some(err);
+        match fallbacks[task] {
+            Some(fallback) if runner == task => {
+                progress[fallback].ready_since = Some(clock);
+                ready.push((task, fallback));
+                ready.sort_unstable();
+                continue;
+            },
+            // The fallback failed too
+            Some(_) => progress[task].state = Some(TaskState::Failed),
+            None => {},
+        }
+        if policy == ErrorPolicy::FailFast && !cleanup {
+            failed = true;
+            ready.clear();
+        }
+    }
+
+    if let Choices::Replay { log: replayed, next } = &choices {
+        if *next < replayed.decisions.len() {
+            return Err(diverged(*next, expected(replayed.decisions.get(*next)), "no more choices"));
+        }
+    }
+
+    let tasks: Vec<TaskReport> = graph
+        .tasks
+        .iter()
+        .zip(progress)
+        .map(|(task, run)| {
+            // Tasks waiting to retry when the run stopped had started
+            let (state, error) = match (run.state, run.attempts) {
+                (Some(state), _) => (state, run.error),
+                (None, 0) => (TaskState::Skipped, None),
+                (None, _) => (TaskState::Cancelled, Some(cancelled())),
+            };
+            TaskReport {
+                id: task.id().to_string(),
+                state,
+                duration: run.duration,
+                attempts: run.attempts,
+                backoffs: run.backoffs,
+                path: run.path,
+                error,
+                wait: run.wait,
+                throttled: Duration::ZERO,
+            }
+        })
+        .collect();
+    let durations: Vec<Duration> = tasks.iter().map(|task| task.duration).collect();
+    let critical_path = critical_path(&durations, &dependencies)
+        .into_iter()
+        .map(|task| tasks[task].id.clone())
+        .collect();
+    let status = if tasks.iter().all(|task| task.state == TaskState::Succeeded) {
+        RunStatus::Succeeded
+    } else {
+        RunStatus::Failed
+    };
+    let outputs = graph
+        .tasks
+        .iter()
+        .zip(outputs)
+        .filter_map(|(task, output)| Some((task.id().to_string(), output?)))
+        .collect();
+    let report = GraphReport {
+        status,
+        tasks,
+        duration: clock,
+        critical_path,
+        outputs,
+        deterministic: true,
+    };
+    Ok((report, log))
+}
+
+/// Seed a deterministic run
+pub(crate) fn seeded(seed: u64) -> Choices<'static> {
+    Choices::Seeded(Box::new(StdRng::seed_from_u64(seed)))
+}
+
+/// Fail with `InvalidState` naming the first difference between the shape
+/// of the logged graph and `shape`
+fn check_shape(logged: &[(String, Vec<String>)], shape: &[(String, Vec<String>)]) -> Result<()> {
+    let find = |tasks: &[(String, Vec<String>)], id: &str| {
+        tasks.iter().find(|(task, _)| task == id).map(|(_, deps)| deps.clone())
+    };
+    let difference = shape
+        .iter()
+        .find_map(|(id, deps)| match find(logged, id) {
+            None => Some(format!("task {id} is not in the log")),
+            Some(logged) if logged != *deps => Some(format!(
+                "task {id} depends on {deps:?} instead of {logged:?}"
+            )),
+            Some(_) => None,
+        })
+        .or_else(|| {
+            logged
+                .iter()
+                .find(|(id, _)| find(shape, id).is_none())
+                .map(|(id, _)| format!("task {id} of the log is not in the graph"))
+        })
+        .or_else(|| (logged != shape).then(|| "tasks were added in another order".to_string()));
+    match difference {
+        Some(difference) => Err(SystemError::InvalidState {
+            message: format!("graph does not match the decision log: {difference}"),
+            current_state: Some(format!("{} tasks", shape.len())),
+            expected_state: Some(format!("the {} tasks of the log", logged.len())),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// What the logged `decision` expected, for errors
+fn expected(decision: Option<&Decision>) -> String {
+    match decision {
+        Some(Decision::Start { ready, chosen }) => format!("start {chosen} out of {ready:?}"),
+        Some(Decision::Retry { task, .. }) => format!("a retry of {task}"),
+        None => "the end of the log".to_string(),
+    }
+}
+
+/// Error of a replay departing from its log at decision `step`
+fn diverged(step: usize, expected: impl Into<String>, found: impl Into<String>) -> SystemError {
+    SystemError::InvalidState {
+        message: format!("run departed from the decision log at decision {step}"),
+        current_state: Some(found.into()),
+        expected_state: Some(expected.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::scheduler::{OperationPriority, Task, TaskPolicy};
+    use crate::{Executor, FrameworkConfig};
+
+    /// Records when it runs, failing its first `failures` attempts
+    struct Step {
+        id: &'static str,
+        dependencies: &'static [&'static str],
+        priority: OperationPriority,
+        cost: Option<Duration>,
+        policy: TaskPolicy,
+        failures: AtomicU32,
+        order: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Step {
+        fn new(id: &'static str, order: &Arc<Mutex<Vec<String>>>) -> Self {
+            Self {
+                id,
+                dependencies: &[],
+                priority: OperationPriority::Normal,
+                cost: None,
+                policy: TaskPolicy::default(),
+                failures: AtomicU32::new(0),
+                order: Arc::clone(order),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Task for Step {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.dependencies.iter().map(|id| id.to_string()).collect()
+        }
+
+        fn policy(&self) -> TaskPolicy {
+            self.policy.clone()
+        }
+
+        fn priority(&self) -> OperationPriority {
+            self.priority
+        }
+
+        fn cost_hint(&self) -> Option<Duration> {
+            self.cost
+        }
+
+        async fn run(&self, _ctx: TaskContext) -> Result<TaskOutput> {
+            self.order.lock().push(self.id.to_string());
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err(SystemError::network("fetch", "connection reset", None));
+            }
+            Ok(TaskOutput::empty())
+        }
+    }
+
+    /// Eight independent tasks, one of them urgent, and one depending on
+    /// them all
+    fn fan_in(order: &Arc<Mutex<Vec<String>>>) -> TaskGraph {
+        const IDS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let mut graph = TaskGraph::new();
+        for id in IDS {
+            let priority = match id {
+                "h" => OperationPriority::High,
+                _ => OperationPriority::Normal,
+            };
+            graph = graph.task(Step { priority, ..Step::new(id, order) });
+        }
+        graph.task(Step {
+            dependencies: &IDS,
+            ..Step::new("sink", order)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_run_deterministic_is_reproducible() {
+        let executor = Executor::new(&FrameworkConfig::default()).unwrap();
+        let mut runs = Vec::new();
+        for seed in [7, 7, 8] {
+            let order = Arc::default();
+            let (report, log) = executor.run_deterministic(fan_in(&order), seed).await.unwrap();
+            assert!(report.is_success() && report.deterministic);
+            let order = order.lock().clone();
+            runs.push((order, log));
+        }
+
+        assert_eq!(runs[0], runs[1]);
+        assert_ne!(runs[0].0, runs[2].0);
+        for (order, log) in &runs {
+            // The urgent task wins every tie, and the sink waits for all
+            assert_eq!((order[0].as_str(), order[8].as_str()), ("h", "sink"));
+            assert_eq!(log.decisions.len(), 9);
+            assert_eq!(
+                log.decisions[0],
+                Decision::Start {
+                    ready: ["a", "b", "c", "d", "e", "f", "g", "h"].map(String::from).to_vec(),
+                    chosen: "h".to_string(),
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_replay_follows_the_log() {
+        let executor = Executor::new(&FrameworkConfig::default()).unwrap();
+        let order = Arc::default();
+        let (_, log) = executor.run_deterministic(fan_in(&order), 3).await.unwrap();
+        let recorded = order.lock().clone();
+
+        let replayed = Arc::default();
+        let report = executor.run_replay(fan_in(&replayed), &log).await.unwrap();
+        assert!(report.is_success() && report.deterministic);
+        assert_eq!(*replayed.lock(), recorded);
+
+        let grown = fan_in(&replayed).task(Step::new("extra", &replayed));
+        let err = executor.run_replay(grown, &log).await.unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                SystemError::InvalidState { message, .. }
+                    if message.ends_with("task extra is not in the log")
+            ),
+            "{err:?}"
+        );
+
+        let reshaped = TaskGraph::new()
+            .task(Step::new("a", &replayed))
+            .task(Step {
+                dependencies: &["a"],
+                ..Step::new("b", &replayed)
+            });
+        let (_, small) = executor.run_deterministic(reshaped, 3).await.unwrap();
+        let flat = TaskGraph::new().task(Step::new("a", &replayed)).task(Step::new("b", &replayed));
+        let err = executor.run_replay(flat, &small).await.unwrap_err();
+        assert!(err.to_string().contains("task b depends on [] instead of [\"a\"]"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_run_deterministic_uses_virtual_time() {
+        let executor = Executor::new(&FrameworkConfig::default())
+            .unwrap()
+            .with_error_policy(ErrorPolicy::Continue);
+        let retry = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(60),
+            ..RetryPolicy::default()
+        };
+        let graph = |order: &Arc<Mutex<Vec<String>>>| {
+            TaskGraph::new()
+                .task(Step {
+                    cost: Some(Duration::from_secs(30)),
+                    policy: TaskPolicy {
+                        timeout: Some(Duration::from_secs(10)),
+                        retry,
+                        ..TaskPolicy::default()
+                    },
+                    ..Step::new("slow", order)
+                })
+                .task(Step {
+                    cost: Some(Duration::from_secs(1)),
+                    policy: TaskPolicy {
+                        retry,
+                        ..TaskPolicy::default()
+                    },
+                    failures: AtomicU32::new(1),
+                    ..Step::new("flaky", order)
+                })
+        };
+
+        let started = std::time::Instant::now();
+        let order = Arc::default();
+        let (report, log) = executor.run_deterministic(graph(&order), 11).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Timed out every attempt without running
+        let slow = report.task("slow").unwrap();
+        assert_eq!((slow.state, slow.attempts), (TaskState::Failed, 3));
+        assert_eq!(slow.duration, Duration::from_secs(30));
+        assert!(matches!(slow.error, Some(SystemError::Timeout { duration_ms: 10_000, .. })));
+        let flaky = report.task("flaky").unwrap();
+        assert_eq!((flaky.state, flaky.attempts), (TaskState::Succeeded, 2));
+        assert_eq!(flaky.duration, Duration::from_secs(2));
+        assert_eq!(*order.lock(), ["flaky", "flaky"]);
+
+        let backoffs: Duration =
+            report.tasks.iter().flat_map(|task| task.backoffs.iter()).sum();
+        assert_eq!(report.duration, Duration::from_secs(32) + backoffs);
+        let retries = log.decisions.iter().filter(|d| matches!(d, Decision::Retry { .. }));
+        assert_eq!(retries.count(), 3);
+
+        let replayed = executor.run_replay(graph(&Arc::default()), &log).await.unwrap();
+        assert_eq!(replayed.duration, report.duration);
+        assert_eq!(replayed.task("slow").unwrap().backoffs, slow.backoffs);
+    }
+}
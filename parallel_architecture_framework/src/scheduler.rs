@@ -1 +1,919 @@
 //! Scheduler module
+//!
+//! A [`TaskGraph`] is a set of [`Task`]s, each depending on the outputs of
+//! others. The graph must be acyclic; [`TaskGraph::validate`] names the
+//! cycle if it is not. [`Executor::run_graph`](crate::executor::Executor::run_graph)
+//! runs it, recovering from failures as each task's [`TaskPolicy`] says.
+//! Tasks may also stream items to each other over the channels of
+//! [`TaskGraph::connect`]. Ready tasks start by [`OperationPriority`], and
+//! tasks needing a scarce resource name its class, whose capacity
+//! [`FrameworkConfig::resource_classes`](crate::FrameworkConfig::resource_classes)
+//! declares. [`TaskGraph::to_dot`] and [`TaskGraph::to_mermaid`] draw the
+//! graph, coloured by how the tasks of a run ended.
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, RetryPolicy, SystemError};
+use tokio_util::sync::CancellationToken;
+
+use crate::checkpoint::SavedOutput;
+use crate::communication::{Connection, Receiver, Sender, Streams};
+use crate::executor::{GraphReport, TaskState};
+
+/// A unit of work in a [`TaskGraph`]
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait Task: Send + Sync {
+    /// Identifier, unique within the graph
+    fn id(&self) -> &str;
+
+    /// Identifiers of the tasks whose outputs this task needs
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// How to recover when the task fails
+    fn policy(&self) -> TaskPolicy {
+        TaskPolicy::default()
+    }
+
+    /// How urgently to start the task among the ready ones
+    fn priority(&self) -> OperationPriority {
+        OperationPriority::Normal
+    }
+
+    /// Bytes of RAM the task needs while it runs, reserved with the
+    /// governor of an executor admitting tasks through one
+    fn estimated_ram(&self) -> Option<u64> {
+        None
+    }
+
+    /// Class of the scarce resource the task needs, e.g. `"gpu"`, which
+    /// [`FrameworkConfig::resource_classes`](crate::FrameworkConfig::resource_classes)
+    /// must declare
+    fn resource_class(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the task runs on the async runtime or, for CPU-bound and
+    /// blocking work, on the executor's blocking pool
+    fn execution_kind(&self) -> ExecutionKind {
+        ExecutionKind::Async
+    }
+
+    /// How long the task is expected to run, which
+    /// [`Executor::explain`](crate::executor::Executor::explain) weighs its
+    /// critical path with; tasks without a hint count as taking no time
+    fn cost_hint(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Fingerprint of the task's code and configuration, which must change
+    /// whenever its output would; only tasks with one are checkpointed by
+    /// [`Executor::resume`](crate::executor::Executor::resume)
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
+
+    /// Save `output` in a checkpoint, or `None` if it cannot be
+    ///
+    /// The default saves empty outputs only.
+    fn save_output(&self, output: &TaskOutput) -> Option<SavedOutput> {
+        output.downcast_ref::<()>().map(|()| SavedOutput::Inline(serde_json::Value::Null))
+    }
+
+    /// Restore an output saved by [`save_output`](Self::save_output)
+    fn restore_output(&self, saved: &SavedOutput) -> Result<TaskOutput> {
+        match saved {
+            SavedOutput::Inline(serde_json::Value::Null) => Ok(TaskOutput::empty()),
+            _ => Err(SystemError::validation(
+                "saved_output",
+                "task cannot restore outputs",
+                Some(self.id().to_string()),
+            )),
+        }
+    }
+
+    /// Run the task once all its dependencies have succeeded
+    async fn run(&self, ctx: TaskContext) -> Result<TaskOutput>;
+}
+
+/// Where the future of [`Task::run`] is polled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ExecutionKind {
+    /// On the async runtime, which the task must not block
+    #[default]
+    Async,
+    /// On a thread of the executor's blocking pool, sized by
+    /// [`FrameworkConfig::blocking_workers`](crate::FrameworkConfig::blocking_workers),
+    /// where it may block or hog the CPU without stalling other tasks
+    Blocking,
+}
+
+/// How urgently a task should start, least urgent first
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum OperationPriority {
+    /// Background work, run when nothing else is ready
+    Low,
+    /// Most tasks
+    #[default]
+    Normal,
+    /// Work others are waiting on
+    High,
+    /// Latency-critical work, run before anything else
+    Critical,
+}
+
+impl OperationPriority {
+    const ALL: [OperationPriority; 4] = [Self::Low, Self::Normal, Self::High, Self::Critical];
+
+    /// The priority `levels` above this one, at most `Critical`
+    pub fn boosted(self, levels: u32) -> Self {
+        let level = (self as usize).saturating_add(levels as usize);
+        Self::ALL[level.min(Self::ALL.len() - 1)]
+    }
+}
+
+/// How the executor recovers from a task failing
+///
+/// The default runs the task once, without a time limit or fallback, and
+/// only if its dependencies succeed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskPolicy {
+    /// Time an attempt may run before it is cancelled and counts as failed
+    /// with a `Timeout` error
+    pub timeout: Option<Duration>,
+    /// When to run the task again after a retriable failure
+    pub retry: RetryPolicy,
+    /// Task run instead once every attempt failed, whose output then
+    /// stands in for this task's
+    ///
+    /// The fallback only runs in place of this task, with the outputs of
+    /// this task's dependencies; no other task may depend on it.
+    pub fallback: Option<String>,
+    /// Whether to run the task even when the run is cancelled, stops at a
+    /// failure or its dependencies fail, as cleanup steps must
+    ///
+    /// It then runs once every other task is done, with the outputs its
+    /// dependencies produced.
+    pub always_run: bool,
+}
+
+impl Default for TaskPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retry: RetryPolicy::never(),
+            fallback: None,
+            always_run: false,
+        }
+    }
+}
+
+/// Value a task hands to its dependents
+#[derive(Clone)]
+pub struct TaskOutput(Arc<dyn Any + Send + Sync>);
+
+impl TaskOutput {
+    /// Wrap `value`
+    pub fn new<T: Any + Send + Sync>(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+
+    /// Output of a task with nothing to hand on
+    pub fn empty() -> Self {
+        Self::new(())
+    }
+
+    /// The value, if it is a `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl std::fmt::Debug for TaskOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskOutput").finish_non_exhaustive()
+    }
+}
+
+/// Outputs of the dependencies of a running task
+#[derive(Debug, Clone)]
+pub struct TaskContext {
+    outputs: HashMap<String, TaskOutput>,
+    attempt: u32,
+    cancellation: CancellationToken,
+    streams: Streams,
+}
+
+impl Default for TaskContext {
+    fn default() -> Self {
+        Self::new(HashMap::new(), 1, CancellationToken::new())
+    }
+}
+
+impl TaskContext {
+    pub(crate) fn new(
+        outputs: HashMap<String, TaskOutput>,
+        attempt: u32,
+        cancellation: CancellationToken,
+    ) -> Self {
+        Self {
+            outputs,
+            attempt,
+            cancellation,
+            streams: Streams::default(),
+        }
+    }
+
+    pub(crate) fn with_streams(mut self, streams: Streams) -> Self {
+        self.streams = streams;
+        self
+    }
+
+    /// Which attempt at the task this is, counted from 1
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Token cancelled when the run is; long tasks should stop early, as
+    /// they are aborted once the executor's cancel grace period is over
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Whether the run was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Output of the dependency `task_id` as a `T`
+    ///
+    /// Fails with a `NotFound` error if `task_id` is not a dependency, and a
+    /// `Validation` error if its output is not a `T`.
+    pub fn output<T: Any>(&self, task_id: &str) -> Result<&T> {
+        let output = self
+            .outputs
+            .get(task_id)
+            .ok_or_else(|| SystemError::not_found("task output", task_id))?;
+        output.downcast_ref().ok_or_else(|| {
+            SystemError::validation(
+                "task_output",
+                format!("output is not a `{}`", std::any::type_name::<T>()),
+                Some(task_id.to_string()),
+            )
+        })
+    }
+
+    /// Sender of the stream to the task `consumer`, connected with
+    /// [`TaskGraph::connect`]
+    ///
+    /// Fails with a `NotFound` error if there is no such stream, and a
+    /// `Validation` error if it does not carry `T`s.
+    pub fn sender<T: Send + 'static>(&self, consumer: &str) -> Result<Sender<T>> {
+        self.streams.sender(consumer)
+    }
+
+    /// Receiver of the stream from the task `producer`, connected with
+    /// [`TaskGraph::connect`]
+    ///
+    /// The receiver can be taken once; after that, and if there is no such
+    /// stream, this fails with a `NotFound` error. A stream not carrying
+    /// `T`s is a `Validation` error.
+    pub fn receiver<T: Send + 'static>(&self, producer: &str) -> Result<Receiver<T>> {
+        self.streams.receiver(producer)
+    }
+}
+
+/// Tasks and the dependencies between them
+#[derive(Default)]
+pub struct TaskGraph {
+    pub(crate) tasks: Vec<Arc<dyn Task>>,
+    pub(crate) connections: Vec<Connection>,
+}
+
+impl TaskGraph {
+    /// Create an empty graph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `task` to the graph
+    pub fn task(mut self, task: impl Task + 'static) -> Self {
+        self.tasks.push(Arc::new(task));
+        self
+    }
+
+    /// Stream `T`s from the task `producer` to the task `consumer` over a
+    /// channel holding up to `capacity` of them
+    ///
+    /// Connected tasks start together, once all of their dependencies have
+    /// succeeded, so they cannot depend on each other. As streamed items
+    /// cannot be taken back, they may not retry, fall back or always run.
+    pub fn connect<T: Send + 'static>(
+        mut self,
+        producer: &str,
+        consumer: &str,
+        capacity: usize,
+    ) -> Self {
+        self.connections.push(Connection::new::<T>(producer, consumer, capacity));
+        self
+    }
+
+    /// Number of tasks
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether the graph has no tasks
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Check the graph can run
+    ///
+    /// Duplicate task identifiers, dependencies on unknown tasks, dependency
+    /// cycles, misused fallbacks and invalid connections are `Validation`
+    /// errors; a cycle is listed as `a -> b -> a`.
+    pub fn validate(&self) -> Result<()> {
+        let dependencies = self.dependencies()?;
+        self.fallbacks(&dependencies)?;
+        self.stream_groups(&dependencies).map(|_| ())
+    }
+
+    /// Render the graph as a Graphviz DOT digraph
+    ///
+    /// Each task is a node with an edge from each of its dependencies; a
+    /// dashed edge leads from a task to its fallback, and a bold one from a
+    /// producer to its consumer. Given the `report` of a run, nodes are
+    /// filled by how their task ended, those missing from it as pending.
+    pub fn to_dot(&self, report: Option<&GraphReport>) -> String {
+        let mut dot = String::from("digraph tasks {\n    node [shape=box];\n");
+        for (i, task) in self.tasks.iter().enumerate() {
+            let fill = report.map_or_else(String::new, |report| {
+                let (_, colour) = state_style(report, task.id());
+                format!(", style=filled, fillcolor=\"{colour}\"")
+            });
+            let _ = writeln!(dot, "    t{i} [label={}{fill}];", dot_quote(task.id()));
+        }
+        for (from, to, edge) in self.edges() {
+            let attributes = match edge {
+                Edge::Dependency => "",
+                Edge::Fallback => " [style=dashed, label=\"fallback\"]",
+                Edge::Stream => " [style=bold, label=\"stream\"]",
+            };
+            let _ = writeln!(dot, "    t{from} -> t{to}{attributes};");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the graph as a Mermaid flowchart, for embedding in Markdown
+    ///
+    /// Edges are drawn as by [`to_dot`](Self::to_dot): dotted to fallbacks,
+    /// thick along streams. Given the `report` of a run, each node gets the
+    /// class of how its task ended, styled by the chart's `classDef`s.
+    pub fn to_mermaid(&self, report: Option<&GraphReport>) -> String {
+        let mut chart = String::from("flowchart TD\n");
+        for (i, task) in self.tasks.iter().enumerate() {
+            let class = report.map_or_else(String::new, |report| {
+                let (state, _) = state_style(report, task.id());
+                format!(":::{state}")
+            });
+            let label = task.id().replace('"', "#quot;");
+            let _ = writeln!(chart, "    t{i}[\"{label}\"]{class}");
+        }
+        for (from, to, edge) in self.edges() {
+            let arrow = match edge {
+                Edge::Dependency => "-->",
+                Edge::Fallback => "-.->|fallback|",
+                Edge::Stream => "==>|stream|",
+            };
+            let _ = writeln!(chart, "    t{from} {arrow} t{to}");
+        }
+        if report.is_some() {
+            for (state, colour) in STATE_STYLES {
+                let _ = writeln!(chart, "    classDef {state} fill:{colour}");
+            }
+        }
+        chart
+    }
+
+    /// Edges between the positions of known tasks: dependency to
+    /// dependent, task to fallback and producer to consumer
+    fn edges(&self) -> Vec<(usize, usize, Edge)> {
+        let position = |id: &str| self.tasks.iter().position(|task| task.id() == id);
+        let mut edges = Vec::new();
+        for (i, task) in self.tasks.iter().enumerate() {
+            for dependency in task.dependencies() {
+                if let Some(from) = position(&dependency) {
+                    edges.push((from, i, Edge::Dependency));
+                }
+            }
+            if let Some(fallback) = task.policy().fallback.as_deref().and_then(position) {
+                edges.push((i, fallback, Edge::Fallback));
+            }
+        }
+        for connection in &self.connections {
+            if let (Some(from), Some(to)) =
+                (position(&connection.producer), position(&connection.consumer))
+            {
+                edges.push((from, to, Edge::Stream));
+            }
+        }
+        edges
+    }
+
+    /// The tasks each task starts together with, being connected to them
+    /// through streams, checked against the `dependencies` of
+    /// [`dependencies`](Self::dependencies)
+    ///
+    /// Connections must join two different known tasks at most once, with
+    /// room for an item, and tasks connected to others may neither depend
+    /// on them, retry, fall back, stand in as a fallback nor always run.
+    pub(crate) fn stream_groups(&self, dependencies: &[Vec<usize>]) -> Result<Vec<Vec<usize>>> {
+        let invalid = |connection: &Connection, reason: &str| {
+            SystemError::validation(
+                "connection",
+                reason,
+                Some(format!("{} -> {}", connection.producer, connection.consumer)),
+            )
+        };
+        let position = |id: &str| self.tasks.iter().position(|task| task.id() == id);
+        // Each task's group, merged as connections join them
+        let mut group: Vec<usize> = (0..self.tasks.len()).collect();
+        let mut connected = HashSet::new();
+        for connection in &self.connections {
+            let (Some(producer), Some(consumer)) =
+                (position(&connection.producer), position(&connection.consumer))
+            else {
+                return Err(invalid(connection, "connects an unknown task"));
+            };
+            if producer == consumer {
+                return Err(invalid(connection, "connects a task to itself"));
+            }
+            if connection.capacity == 0 {
+                return Err(invalid(connection, "capacity must be positive"));
+            }
+            if !connected.insert((producer, consumer)) {
+                return Err(invalid(connection, "duplicate connection"));
+            }
+            let (from, to) = (group[producer], group[consumer]);
+            for task_group in &mut group {
+                if *task_group == to {
+                    *task_group = from;
+                }
+            }
+        }
+        let fallbacks: HashSet<String> =
+            self.tasks.iter().filter_map(|task| task.policy().fallback).collect();
+        let mut peers = vec![Vec::new(); self.tasks.len()];
+        for (task, &task_group) in group.iter().enumerate() {
+            let members: Vec<usize> = (0..self.tasks.len())
+                .filter(|&other| other != task && group[other] == task_group)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            let id = self.tasks[task].id();
+            let policy = self.tasks[task].policy();
+            let reason = if policy.retry.max_attempts > 1 {
+                Some("retries")
+            } else if policy.fallback.is_some() {
+                Some("has a fallback")
+            } else if fallbacks.contains(id) {
+                Some("is a fallback")
+            } else if policy.always_run {
+                Some("always runs")
+            } else if members.iter().any(|&other| depends_on(dependencies, task, other)) {
+                Some("depends on a task it is connected to")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Err(SystemError::validation(
+                    "connection",
+                    format!("connected task {reason}"),
+                    Some(id.to_string()),
+                ));
+            }
+            peers[task] = members;
+        }
+        Ok(peers)
+    }
+
+    /// Index of every task's fallback, checked against the `dependencies`
+    /// of [`dependencies`](Self::dependencies)
+    ///
+    /// A fallback must be another task, without a fallback of its own,
+    /// standing in for a single task that has all of its dependencies, and
+    /// that no task depends on.
+    pub(crate) fn fallbacks(&self, dependencies: &[Vec<usize>]) -> Result<Vec<Option<usize>>> {
+        let invalid = |task: &dyn Task, reason: String| {
+            SystemError::validation("fallback", reason, Some(task.id().to_string()))
+        };
+        let mut fallbacks = Vec::with_capacity(self.tasks.len());
+        let mut used = HashSet::new();
+        for (i, task) in self.tasks.iter().enumerate() {
+            let policy = task.policy();
+            policy.retry.validate()?;
+            let Some(id) = policy.fallback else {
+                fallbacks.push(None);
+                continue;
+            };
+            let Some(fallback) = self.tasks.iter().position(|task| task.id() == id) else {
+                return Err(invalid(&**task, format!("falls back to an unknown task `{id}`")));
+            };
+            if fallback == i {
+                return Err(invalid(&**task, "falls back to itself".to_string()));
+            }
+            if self.tasks[fallback].policy().fallback.is_some() {
+                return Err(invalid(&**task, format!("fallback `{id}` has a fallback")));
+            }
+            if !used.insert(fallback) {
+                return Err(invalid(&**task, format!("fallback `{id}` is shared")));
+            }
+            if dependencies.iter().any(|task_dependencies| task_dependencies.contains(&fallback)) {
+                return Err(invalid(&**task, format!("fallback `{id}` has dependents")));
+            }
+            if !dependencies[fallback].iter().all(|d| dependencies[i].contains(d)) {
+                return Err(invalid(
+                    &**task,
+                    format!("fallback `{id}` depends on tasks this task does not"),
+                ));
+            }
+            fallbacks.push(Some(fallback));
+        }
+        Ok(fallbacks)
+    }
+
+    /// Index of every task's dependencies, checked
+    pub(crate) fn dependencies(&self) -> Result<Vec<Vec<usize>>> {
+        let mut index = HashMap::with_capacity(self.tasks.len());
+        for (i, task) in self.tasks.iter().enumerate() {
+            if index.insert(task.id().to_string(), i).is_some() {
+                return Err(SystemError::validation(
+                    "task_id",
+                    "duplicate task identifier",
+                    Some(task.id().to_string()),
+                ));
+            }
+        }
+        let dependencies = self
+            .tasks
+            .iter()
+            .map(|task| {
+                task.dependencies()
+                    .iter()
+                    .map(|dependency| {
+                        index.get(dependency).copied().ok_or_else(|| {
+                            SystemError::validation(
+                                "dependencies",
+                                format!("task `{}` depends on an unknown task", task.id()),
+                                Some(dependency.clone()),
+                            )
+                        })
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<usize>>>>()?;
+        if let Some(cycle) = find_cycle(&dependencies) {
+            let mut names: Vec<&str> = cycle.iter().map(|&i| self.tasks[i].id()).collect();
+            names.push(names[0]);
+            return Err(SystemError::validation(
+                "dependencies",
+                format!("dependency cycle: {}", names.join(" -> ")),
+                None,
+            ));
+        }
+        Ok(dependencies)
+    }
+}
+
+impl std::fmt::Debug for TaskGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<&str> = self.tasks.iter().map(|task| task.id()).collect();
+        f.debug_struct("TaskGraph").field("tasks", &ids).finish()
+    }
+}
+
+/// Kind of an edge drawn between two tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Dependency,
+    Fallback,
+    Stream,
+}
+
+/// Fill colour of the tasks of a drawn graph, by how they ended
+const STATE_STYLES: [(&str, &str); 5] = [
+    ("pending", "#ffffff"),
+    ("succeeded", "#c8e6c9"),
+    ("failed", "#ffcdd2"),
+    ("cancelled", "#ffe0b2"),
+    ("skipped", "#e0e0e0"),
+];
+
+/// Name and fill colour of how task `id` ended in `report`, pending if it
+/// is not there
+fn state_style(report: &GraphReport, id: &str) -> (&'static str, &'static str) {
+    let style = match report.task(id).map(|task| task.state) {
+        None => 0,
+        Some(TaskState::Succeeded) => 1,
+        Some(TaskState::Failed) => 2,
+        Some(TaskState::Cancelled) => 3,
+        Some(TaskState::Skipped) => 4,
+    };
+    STATE_STYLES[style]
+}
+
+/// `text` as a quoted DOT string
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Whether `task` depends on `other`, directly or not
+fn depends_on(dependencies: &[Vec<usize>], task: usize, other: usize) -> bool {
+    let mut seen = vec![false; dependencies.len()];
+    let mut stack = vec![task];
+    while let Some(next) = stack.pop() {
+        for &dependency in &dependencies[next] {
+            if dependency == other {
+                return true;
+            }
+            if !std::mem::replace(&mut seen[dependency], true) {
+                stack.push(dependency);
+            }
+        }
+    }
+    false
+}
+
+/// Tasks of a dependency cycle, each depending on the next, if there is one
+fn find_cycle(dependencies: &[Vec<usize>]) -> Option<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        OnPath,
+        Done,
+    }
+    let mut marks = vec![Mark::New; dependencies.len()];
+    for root in 0..dependencies.len() {
+        if marks[root] != Mark::New {
+            continue;
+        }
+        // Depth-first, each frame a task and its next dependency to visit
+        let mut path = vec![(root, 0)];
+        marks[root] = Mark::OnPath;
+        while let Some((task, next)) = path.last_mut() {
+            let task = *task;
+            let Some(&dependency) = dependencies[task].get(*next) else {
+                marks[task] = Mark::Done;
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            match marks[dependency] {
+                Mark::New => {
+                    marks[dependency] = Mark::OnPath;
+                    path.push((dependency, 0));
+                },
+                Mark::OnPath => {
+                    let start = path.iter().position(|&(t, _)| t == dependency)?;
+                    return Some(path[start..].iter().map(|&(t, _)| t).collect());
+                },
+                Mark::Done => {},
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str, &'static [&'static str]);
+
+    #[async_trait]
+    impl Task for Named {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.1.iter().map(|id| id.to_string()).collect()
+        }
+
+        fn policy(&self) -> TaskPolicy {
+            // `x~y` falls back to `y`
+            TaskPolicy {
+                fallback: self.0.split_once('~').map(|(_, fallback)| fallback.to_string()),
+                ..TaskPolicy::default()
+            }
+        }
+
+        async fn run(&self, _: TaskContext) -> Result<TaskOutput> {
+            Ok(TaskOutput::empty())
+        }
+    }
+
+    #[test]
+    fn test_priority_boost_saturates() {
+        assert_eq!(OperationPriority::default(), OperationPriority::Normal);
+        assert_eq!(OperationPriority::Low.boosted(0), OperationPriority::Low);
+        assert_eq!(OperationPriority::Low.boosted(2), OperationPriority::High);
+        assert_eq!(OperationPriority::Normal.boosted(u32::MAX), OperationPriority::Critical);
+        assert!(OperationPriority::High > OperationPriority::Normal);
+    }
+
+    #[test]
+    fn test_validate() {
+        let graph = TaskGraph::new().task(Named("a", &[])).task(Named("b", &["a"]));
+        assert!(graph.validate().is_ok());
+
+        let cyclic = TaskGraph::new()
+            .task(Named("root", &[]))
+            .task(Named("a", &["root", "c"]))
+            .task(Named("b", &["a"]))
+            .task(Named("c", &["b"]));
+        let err = cyclic.validate().unwrap_err();
+        assert!(err.to_string().contains("dependency cycle: a -> c -> b -> a"), "{err}");
+        let looped = TaskGraph::new().task(Named("self", &["self"]));
+        assert!(looped.validate().unwrap_err().to_string().contains("self -> self"));
+
+        let unknown = TaskGraph::new().task(Named("a", &["ghost"]));
+        assert!(unknown.validate().unwrap_err().to_string().contains("unknown task"));
+        let duplicate = TaskGraph::new().task(Named("a", &[])).task(Named("a", &[]));
+        assert!(duplicate.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_fallbacks() {
+        let valid = TaskGraph::new()
+            .task(Named("in", &[]))
+            .task(Named("a~b", &["in"]))
+            .task(Named("b", &["in"]));
+        assert!(valid.validate().is_ok());
+
+        let invalid = [
+            TaskGraph::new().task(Named("a~ghost", &[])),
+            TaskGraph::new().task(Named("a~b~c", &[])).task(Named("b~c", &[])),
+            // Shared
+            TaskGraph::new().task(Named("a~b", &[])).task(Named("c~b", &[])).task(Named("b", &[])),
+            // Depended on
+            TaskGraph::new().task(Named("a~b", &[])).task(Named("b", &[])).task(Named("c", &["b"])),
+            // Needing an output the primary does not get
+            TaskGraph::new().task(Named("i", &[])).task(Named("a~b", &[])).task(Named("b", &["i"])),
+        ];
+        for graph in invalid {
+            let err = graph.validate().unwrap_err();
+            assert!(matches!(err, SystemError::Validation { .. }), "{graph:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_validate_connections() {
+        let graph = || {
+            TaskGraph::new()
+                .task(Named("in", &[]))
+                .task(Named("a", &["in"]))
+                .task(Named("b", &[]))
+                .task(Named("c", &["a"]))
+        };
+        let valid = graph().connect::<u64>("a", "b", 8).connect::<u64>("b", "a", 8);
+        assert!(valid.validate().is_ok());
+        let dependencies = valid.dependencies().unwrap();
+        let peers = valid.stream_groups(&dependencies).unwrap();
+        assert_eq!(peers, [vec![], vec![2], vec![1], vec![]]);
+
+        let invalid = [
+            graph().connect::<u64>("a", "ghost", 8),
+            graph().connect::<u64>("a", "a", 8),
+            graph().connect::<u64>("a", "b", 0),
+            graph().connect::<u64>("a", "b", 8).connect::<u64>("a", "b", 8),
+            // Depending on each other, through `a`
+            graph().connect::<u64>("in", "c", 8),
+            graph().task(Named("x~y", &[])).task(Named("y", &[])).connect::<u64>("x", "a", 8),
+        ];
+        for graph in invalid {
+            let err = graph.validate().unwrap_err();
+            assert!(matches!(err, SystemError::Validation { .. }), "{graph:?}: {err}");
+        }
+    }
+
+    /// `in` feeds `a`, falling back to `b`, which feeds `c`, which `s`
+    /// streams to
+    fn fixture() -> TaskGraph {
+        TaskGraph::new()
+            .task(Named("in", &[]))
+            .task(Named("a~b", &["in"]))
+            .task(Named("b", &["in"]))
+            .task(Named("c", &["a~b"]))
+            .task(Named("s", &[]))
+            .connect::<u64>("s", "c", 8)
+    }
+
+    /// Report of a run in which `a` failed, so that `c` was cancelled
+    /// before `s` started
+    fn fixture_report() -> GraphReport {
+        let tasks = [
+            ("in", TaskState::Succeeded),
+            ("a~b", TaskState::Failed),
+            ("b", TaskState::Succeeded),
+            ("c", TaskState::Cancelled),
+        ];
+        GraphReport {
+            status: crate::executor::RunStatus::Cancelled,
+            tasks: tasks
+                .into_iter()
+                .map(|(id, state)| crate::executor::TaskReport {
+                    id: id.to_string(),
+                    state,
+                    duration: Duration::ZERO,
+                    attempts: 1,
+                    backoffs: Vec::new(),
+                    path: None,
+                    error: None,
+                    wait: Duration::ZERO,
+                    throttled: Duration::ZERO,
+                })
+                .collect(),
+            duration: Duration::ZERO,
+            critical_path: Vec::new(),
+            outputs: HashMap::new(),
+            deterministic: false,
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let expected = "\
+digraph tasks {
+    node [shape=box];
+    t0 [label=\"in\"];
+    t1 [label=\"a~b\"];
+    t2 [label=\"b\"];
+    t3 [label=\"c\"];
+    t4 [label=\"s\"];
+    t0 -> t1;
+    t1 -> t2 [style=dashed, label=\"fallback\"];
+    t0 -> t2;
+    t1 -> t3;
+    t4 -> t3 [style=bold, label=\"stream\"];
+}
+";
+        assert_eq!(fixture().to_dot(None), expected);
+
+        let styled = fixture().to_dot(Some(&fixture_report()));
+        assert!(styled.contains("t0 [label=\"in\", style=filled, fillcolor=\"#c8e6c9\"];"));
+        assert!(styled.contains("t1 [label=\"a~b\", style=filled, fillcolor=\"#ffcdd2\"];"));
+        assert!(styled.contains("t3 [label=\"c\", style=filled, fillcolor=\"#ffe0b2\"];"));
+        assert!(styled.contains("t4 [label=\"s\", style=filled, fillcolor=\"#ffffff\"];"));
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let expected = "\
+flowchart TD
+    t0[\"in\"]:::succeeded
+    t1[\"a~b\"]:::failed
+    t2[\"b\"]:::succeeded
+    t3[\"c\"]:::cancelled
+    t4[\"s\"]:::pending
+    t0 --> t1
+    t1 -.->|fallback| t2
+    t0 --> t2
+    t1 --> t3
+    t4 ==>|stream| t3
+    classDef pending fill:#ffffff
+    classDef succeeded fill:#c8e6c9
+    classDef failed fill:#ffcdd2
+    classDef cancelled fill:#ffe0b2
+    classDef skipped fill:#e0e0e0
+";
+        assert_eq!(fixture().to_mermaid(Some(&fixture_report())), expected);
+
+        let plain = fixture().to_mermaid(None);
+        assert!(plain.starts_with("flowchart TD\n    t0[\"in\"]\n"));
+        assert!(!plain.contains("classDef"));
+    }
+
+    #[test]
+    fn test_context_outputs_are_typed() {
+        let outputs = HashMap::from([("a".to_string(), TaskOutput::new(7_u64))]);
+        let ctx = TaskContext::new(outputs, 1, CancellationToken::new());
+        assert_eq!(ctx.attempt(), 1);
+        assert_eq!(ctx.output::<u64>("a").unwrap(), &7);
+        assert!(matches!(ctx.output::<String>("a"), Err(SystemError::Validation { .. })));
+        assert!(matches!(ctx.output::<u64>("b"), Err(SystemError::NotFound { .. })));
+    }
+}
@@ -0,0 +1,379 @@
+//! Status module
+//!
+//! [`Executor::status`] tells what the graphs an executor is running are
+//! doing, task by task. A [`StatusServer`] serves it over HTTP for
+//! operators of long-running executors:
+//!
+//! - `GET /status` returns the [`ExecutorStatus`] as JSON
+//! - `GET /healthz` reports a [`HealthRegistry`], with `503` when unhealthy
+//!
+//! The executor also publishes `executor_queue_depth` and
+//! `executor_active_tasks` gauges, summed over its running graphs.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use parking_lot::Mutex;
+use serde::Serialize;
+use shared_core::config::ServerConfig;
+use shared_core::{HealthRegistry, HealthReport, Result, SystemError};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+use crate::executor::{Executor, SchedulerStats, TaskState};
+
+/// Where a task of a running graph is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TaskPhase {
+    /// Waiting for its dependencies, a free slot or its next attempt
+    Pending,
+    /// An attempt is running
+    Running,
+    /// Returned an output, or its fallback did
+    Succeeded,
+    /// Failed every attempt
+    Failed,
+    /// Stopped by the run being cancelled
+    Cancelled,
+    /// Never ran
+    Skipped,
+}
+
+impl From<TaskState> for TaskPhase {
+    fn from(state: TaskState) -> Self {
+        match state {
+            TaskState::Succeeded => Self::Succeeded,
+            TaskState::Failed => Self::Failed,
+            TaskState::Cancelled => Self::Cancelled,
+            TaskState::Skipped => Self::Skipped,
+        }
+    }
+}
+
+/// One task of a running graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskStatus {
+    /// Task identifier
+    pub id: String,
+    /// Where the task is
+    pub phase: TaskPhase,
+    /// Times the task was started so far
+    pub attempts: u32,
+}
+
+/// A graph being run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphStatus {
+    /// Identifier of the run, unique within the executor
+    pub id: u64,
+    /// Milliseconds since the run started
+    pub elapsed_ms: u64,
+    /// Tasks ready to start
+    pub queued: usize,
+    /// Tasks running
+    pub active: usize,
+    /// Every task, in the order it was added to the graph
+    pub tasks: Vec<TaskStatus>,
+}
+
+/// What an [`Executor`] is doing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExecutorStatus {
+    /// Graphs being run, oldest run first
+    pub graphs: Vec<GraphStatus>,
+    /// How the tasks of every run so far were scheduled
+    pub stats: SchedulerStats,
+}
+
+/// Graphs an executor is running
+#[derive(Debug, Default)]
+pub(crate) struct Runs {
+    next_id: AtomicU64,
+    graphs: Mutex<BTreeMap<u64, (Instant, GraphStatus)>>,
+}
+
+impl Runs {
+    /// Track a run of the tasks `ids` until the returned tracker is dropped
+    pub(crate) fn begin(self: &Arc<Self>, ids: impl IntoIterator<Item = String>) -> RunTracker {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let tasks = ids
+            .into_iter()
+            .map(|id| TaskStatus {
+                id,
+                phase: TaskPhase::Pending,
+                attempts: 0,
+            })
+            .collect();
+        let status = GraphStatus {
+            id,
+            elapsed_ms: 0,
+            queued: 0,
+            active: 0,
+            tasks,
+        };
+        self.graphs.lock().insert(id, (Instant::now(), status));
+        RunTracker {
+            runs: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// Status of every graph being run
+    pub(crate) fn snapshot(&self) -> Vec<GraphStatus> {
+        self.graphs
+            .lock()
+            .values()
+            .map(|(started, status)| GraphStatus {
+                elapsed_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+                ..status.clone()
+            })
+            .collect()
+    }
+}
+
+/// Publish the queue depth and active tasks summed over `graphs`
+fn publish(graphs: &BTreeMap<u64, (Instant, GraphStatus)>) {
+    let (queued, active) = graphs
+        .values()
+        .fold((0, 0), |(queued, active), (_, graph)| (queued + graph.queued, active + graph.active));
+    shared_core::gauge!("executor_queue_depth", queued as f64);
+    shared_core::gauge!("executor_active_tasks", active as f64);
+}
+
+/// Keeps the status of one graph run up to date, and forgets it once
+/// dropped
+pub(crate) struct RunTracker {
+    runs: Arc<Runs>,
+    id: u64,
+}
+
+impl RunTracker {
+    /// Identifier of the run
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Record that the task at `index` started another attempt
+    pub(crate) fn start(&self, index: usize) {
+        self.update(index, |task| {
+            task.phase = TaskPhase::Running;
+            task.attempts += 1;
+        });
+    }
+
+    /// Record that the task at `index` is now in `phase`
+    pub(crate) fn set(&self, index: usize, phase: TaskPhase) {
+        self.update(index, |task| task.phase = phase);
+    }
+
+    /// Record how many tasks are ready to start and running
+    pub(crate) fn load(&self, queued: usize, active: usize) {
+        let mut graphs = self.runs.graphs.lock();
+        if let Some((_, graph)) = graphs.get_mut(&self.id) {
+            graph.queued = queued;
+            graph.active = active;
+        }
+        publish(&graphs);
+    }
+
+    fn update(&self, index: usize, update: impl FnOnce(&mut TaskStatus)) {
+        let mut graphs = self.runs.graphs.lock();
+        if let Some(task) = graphs.get_mut(&self.id).and_then(|(_, graph)| graph.tasks.get_mut(index))
+        {
+            update(task);
+        }
+    }
+}
+
+impl Drop for RunTracker {
+    fn drop(&mut self) {
+        let mut graphs = self.runs.graphs.lock();
+        graphs.remove(&self.id);
+        publish(&graphs);
+    }
+}
+
+/// HTTP server of an executor's status and health
+///
+/// Dropping it stops the server.
+pub struct StatusServer {
+    address: SocketAddr,
+    stop: CancellationToken,
+}
+
+/// Shared state of the status handlers
+#[derive(Clone)]
+struct StatusState {
+    executor: Executor,
+    health: HealthRegistry,
+}
+
+impl StatusServer {
+    /// Serve the status of `executor` and the checks of `health` at the
+    /// address of `server`
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub async fn bind(
+        server: &ServerConfig,
+        executor: Executor,
+        health: HealthRegistry,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind((server.host.as_str(), server.port))
+            .await
+            .map_err(|e| SystemError::io(e, format!("binding {}:{}", server.host, server.port)))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| SystemError::io(e, "reading listen address"))?;
+        let router = Router::new()
+            .route("/status", get(status))
+            .route("/healthz", get(healthz))
+            .with_state(StatusState { executor, health });
+        let stop = CancellationToken::new();
+        let shutdown = stop.clone().cancelled_owned();
+        tokio::spawn(async move {
+            if let Err(err) = axum::serve(listener, router).with_graceful_shutdown(shutdown).await {
+                tracing::warn!("Status server failed: {}", err);
+            }
+        });
+        tracing::info!("Serving executor status on {}", address);
+        Ok(Self { address, stop })
+    }
+
+    /// Address the server listens at
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+async fn status(State(state): State<StatusState>) -> Json<ExecutorStatus> {
+    Json(state.executor.status())
+}
+
+async fn healthz(State(state): State<StatusState>) -> (StatusCode, Json<HealthReport>) {
+    let report = state.health.report().await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use serde_json::json;
+    use shared_core::HealthCheck;
+    use tokio::sync::Semaphore;
+
+    use super::*;
+    use crate::scheduler::{Task, TaskContext, TaskGraph, TaskOutput};
+    use crate::FrameworkConfig;
+
+    /// Runs until `gate` lets it through
+    struct Gated {
+        id: &'static str,
+        dependencies: &'static [&'static str],
+        gate: Arc<Semaphore>,
+    }
+
+    #[async_trait]
+    impl Task for Gated {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.dependencies.iter().map(|id| id.to_string()).collect()
+        }
+
+        async fn run(&self, _ctx: TaskContext) -> Result<TaskOutput> {
+            let _permit = self.gate.acquire().await;
+            Ok(TaskOutput::empty())
+        }
+    }
+
+    struct Unhealthy;
+
+    #[async_trait]
+    impl HealthCheck for Unhealthy {
+        async fn check(&self) -> Result<()> {
+            Err(SystemError::internal("disk full", None))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_server() {
+        let executor = Executor::new(&FrameworkConfig::default()).unwrap();
+        let health = HealthRegistry::default();
+        let server = ServerConfig {
+            port: 0,
+            ..ServerConfig::default()
+        };
+        let server = StatusServer::bind(&server, executor.clone(), health.clone()).await.unwrap();
+        let base = format!("http://{}", server.local_addr());
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("{base}{path}")).send();
+
+        let gate = Arc::new(Semaphore::new(0));
+        let graph = TaskGraph::new()
+            .task(Gated {
+                id: "first",
+                dependencies: &[],
+                gate: Arc::clone(&gate),
+            })
+            .task(Gated {
+                id: "second",
+                dependencies: &["first"],
+                gate: Arc::clone(&gate),
+            });
+        let run = executor.run_graph_detached(graph).unwrap();
+        while !executor.status().graphs.first().is_some_and(|graph| graph.active > 0) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let status: serde_json::Value = get("/status").await.unwrap().json().await.unwrap();
+        let graph = &status["graphs"][0];
+        assert!(graph["id"].is_u64() && graph["elapsed_ms"].is_u64());
+        assert_eq!(graph["queued"], json!(0));
+        assert_eq!(graph["active"], json!(1));
+        assert_eq!(
+            graph["tasks"],
+            json!([
+                { "id": "first", "phase": "Running", "attempts": 1 },
+                { "id": "second", "phase": "Pending", "attempts": 0 },
+            ])
+        );
+        assert!(status["stats"]["classes"].is_object());
+        assert!(status["stats"]["wait_times"].is_object());
+
+        gate.add_permits(2);
+        assert!(run.await.unwrap().is_success());
+        let status: serde_json::Value = get("/status").await.unwrap().json().await.unwrap();
+        assert_eq!(status["graphs"], json!([]));
+
+        assert_eq!(get("/healthz").await.unwrap().status(), 200);
+        health.register("disk", Arc::new(Unhealthy));
+        let response = get("/healthz").await.unwrap();
+        assert_eq!(response.status(), 503);
+        let report: HealthReport = response.json().await.unwrap();
+        assert_eq!(report.checks["disk"].error.as_deref(), Some("Internal error: disk full"));
+    }
+}
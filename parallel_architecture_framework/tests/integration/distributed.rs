@@ -0,0 +1,147 @@
+//! Runs graphs on a coordinator and two in-process workers over localhost
+
+use std::time::Duration;
+
+use parallel_architecture_framework::{
+    Coordinator, CoordinatorConfig, Executor, FrameworkConfig, RemoteOutput, RunStatus, TaskGraph,
+    TaskRegistry, TaskState, WorkerServer,
+};
+use shared_core::config::ServerConfig;
+use shared_core::crypto::{KeyPair, PublicKey};
+use shared_core::SystemError;
+use tokio::sync::mpsc;
+
+fn localhost() -> ServerConfig {
+    ServerConfig {
+        port: 0,
+        workers: Some(4),
+        timeout_secs: 5,
+        ..ServerConfig::default()
+    }
+}
+
+/// Squares slowly, announcing each start on `started`, and sums the
+/// squares of its dependencies
+fn registry(started: mpsc::UnboundedSender<()>) -> TaskRegistry {
+    TaskRegistry::new()
+        .register_fn("square", move |input| {
+            let started = started.clone();
+            async move {
+                let _ = started.send(());
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                let n: u64 = input.input()?;
+                Ok(n * n)
+            }
+        })
+        .register_fn("sum", |input| async move {
+            let parts: Vec<String> = input.input()?;
+            parts
+                .iter()
+                .map(|part| input.dependency::<u64>(part))
+                .sum::<shared_core::Result<u64>>()
+        })
+}
+
+async fn coordinator() -> Coordinator {
+    let config = CoordinatorConfig {
+        heartbeat_interval: Duration::from_millis(50),
+        ..CoordinatorConfig::default()
+    };
+    Coordinator::bind(&localhost(), KeyPair::generate(), config)
+        .await
+        .unwrap()
+}
+
+async fn worker(
+    name: &str,
+    labels: &[&str],
+    coordinator: &Coordinator,
+    key: PublicKey,
+) -> (
+    parallel_architecture_framework::WorkerHandle,
+    mpsc::UnboundedReceiver<()>,
+) {
+    let (started, starts) = mpsc::unbounded_channel();
+    let worker = WorkerServer::bind(name, &localhost(), registry(started), key)
+        .await
+        .unwrap()
+        .with_labels(labels.iter().copied());
+    (
+        worker.start(coordinator.local_addr()).await.unwrap(),
+        starts,
+    )
+}
+
+fn squares_and_sum(coordinator: &Coordinator) -> TaskGraph {
+    let parts: Vec<String> = (1..=4).map(|n| format!("square{n}")).collect();
+    let mut sum = coordinator.task("sum", "sum", &parts).unwrap();
+    let mut graph = TaskGraph::new();
+    for (n, part) in (1u64..).zip(&parts) {
+        graph = graph.task(coordinator.task(part, "square", &n).unwrap().prefer("edge"));
+        sum = sum.depends_on(part);
+    }
+    graph.task(sum)
+}
+
+#[tokio::test]
+async fn test_tasks_of_a_killed_worker_are_rescheduled() {
+    let coordinator = coordinator().await;
+    let (edge, mut edge_starts) =
+        worker("edge-1", &["edge"], &coordinator, coordinator.public_key()).await;
+    let (_core, _) = worker("core-1", &[], &coordinator, coordinator.public_key()).await;
+    assert_eq!(coordinator.workers(), ["core-1", "edge-1"]);
+
+    let executor = Executor::new(&FrameworkConfig {
+        workers: 4,
+        ..FrameworkConfig::default()
+    })
+    .unwrap();
+    let run = executor
+        .run_graph_detached(squares_and_sum(&coordinator))
+        .unwrap();
+
+    // The squares prefer the edge worker; it dies while running them
+    edge_starts.recv().await.unwrap();
+    edge.kill();
+    let report = run.await.unwrap();
+
+    assert_eq!(report.status, RunStatus::Succeeded);
+    let sum = report.output::<RemoteOutput>("sum").unwrap();
+    assert_eq!(sum.value::<u64>().unwrap(), 1 + 4 + 9 + 16);
+    let rescheduled: Vec<&RemoteOutput> = (1..=4)
+        .map(|n| {
+            report
+                .output::<RemoteOutput>(&format!("square{n}"))
+                .unwrap()
+        })
+        .filter(|output| output.was_rescheduled())
+        .collect();
+    assert!(!rescheduled.is_empty());
+    for output in rescheduled {
+        assert_eq!(output.lost_workers, ["edge-1"]);
+        assert_eq!(output.worker, "core-1");
+    }
+    assert_eq!(coordinator.workers(), ["core-1"]);
+}
+
+#[tokio::test]
+async fn test_workers_reject_invocations_of_other_coordinators() {
+    let coordinator = coordinator().await;
+    let stranger = KeyPair::generate().public_key();
+    let (_worker, _) = worker("edge-1", &["edge"], &coordinator, stranger).await;
+
+    let executor = Executor::new(&FrameworkConfig {
+        workers: 2,
+        ..FrameworkConfig::default()
+    })
+    .unwrap();
+    let graph = TaskGraph::new().task(coordinator.task("square", "square", &3u64).unwrap());
+    let report = executor.run_graph(graph).await.unwrap();
+
+    let task = report.task("square").unwrap();
+    assert_eq!(task.state, TaskState::Failed);
+    assert!(matches!(
+        task.error,
+        Some(SystemError::PermissionDenied { .. })
+    ));
+}
@@ -9,11 +9,12 @@ use rand::rngs::OsRng;
 use ring::{
     aead::{Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, AES_256_GCM},
     error::Unspecified,
+    hmac,
     rand::{SecureRandom, SystemRandom},
 };
 use serde::{Deserialize, Serialize};
 use std::num::Wrapping;
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{ZeroizeOnDrop, Zeroizing};
 
 /// Ed25519 keypair for signing and verification
 #[derive(Clone)]
@@ -23,6 +24,7 @@ pub struct KeyPair {
 
 impl KeyPair {
     /// Generate a new random keypair
+    #[allow(clippy::must_use_candidate)]
     pub fn generate() -> Self {
         use rand::RngCore;
         let mut csprng = OsRng;
@@ -33,12 +35,14 @@ impl KeyPair {
     }
 
     /// Create a keypair from a seed
+    #[allow(clippy::must_use_candidate)]
     pub fn from_seed(seed: &[u8; 32]) -> Self {
         let signing_key = SigningKey::from_bytes(seed);
         Self { signing_key }
     }
 
     /// Get the public key
+    #[allow(clippy::must_use_candidate)]
     pub fn public_key(&self) -> PublicKey {
         PublicKey {
             verifying_key: self.signing_key.verifying_key(),
@@ -46,11 +50,19 @@ impl KeyPair {
     }
 
     /// Sign a message
+    #[must_use]
     pub fn sign(&self, message: &[u8]) -> Vec<u8> {
         self.signing_key.sign(message).to_bytes().to_vec()
     }
 
+    /// Sign a message with every keypair, one signature per keypair in order
+    #[must_use]
+    pub fn multi_sign(message: &[u8], keypairs: &[&KeyPair]) -> Vec<Vec<u8>> {
+        keypairs.iter().map(|keypair| keypair.sign(message)).collect()
+    }
+
     /// Get the signing key bytes
+    #[must_use]
     pub fn to_bytes(&self) -> [u8; 32] {
         self.signing_key.to_bytes()
     }
@@ -80,13 +92,106 @@ impl PublicKey {
             .map_err(|e| SystemError::crypto("signature_verify", e.to_string()))
     }
 
+    /// Verify many `(message, signature)` pairs made with this key
+    ///
+    /// Runs a single batch verification and only checks items one by one if
+    /// the batch fails. Returns one flag per item, in input order.
+    #[must_use]
+    pub fn verify_batch(&self, items: &[(&[u8], &[u8])]) -> Vec<bool> {
+        let parsed: Vec<Option<Signature>> = items
+            .iter()
+            .map(|(_, signature)| Signature::from_slice(signature).ok())
+            .collect();
+
+        let mut messages = Vec::with_capacity(items.len());
+        let mut signatures = Vec::with_capacity(items.len());
+        for ((message, _), signature) in items.iter().zip(&parsed) {
+            if let Some(signature) = signature {
+                messages.push(*message);
+                signatures.push(*signature);
+            }
+        }
+        let keys = vec![self.verifying_key; signatures.len()];
+
+        if !signatures.is_empty()
+            && ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok()
+        {
+            return parsed.iter().map(Option::is_some).collect();
+        }
+
+        items
+            .iter()
+            .map(|(message, signature)| self.verify(message, signature).is_ok())
+            .collect()
+    }
+
+    /// Check that at least `threshold` distinct keys signed `message`
+    ///
+    /// Each signature is checked against its corresponding key first and
+    /// then against the keys not yet matched, so signatures may come in any
+    /// order. A key counts at most once, however often it is listed.
+    /// Returns the number of valid signatures, or a `Crypto` error if it is
+    /// below `threshold`.
+    pub fn verify_threshold(
+        message: &[u8],
+        public_keys: &[&PublicKey],
+        signatures: &[Vec<u8>],
+        threshold: usize,
+    ) -> Result<usize> {
+        if threshold == 0 {
+            return Err(SystemError::validation(
+                "threshold",
+                "must be at least 1",
+                Some("0".to_string()),
+            ));
+        }
+
+        let mut keys: Vec<&PublicKey> = Vec::with_capacity(public_keys.len());
+        for key in public_keys {
+            if !keys.contains(key) {
+                keys.push(key);
+            }
+        }
+        let mut matched = vec![false; keys.len()];
+        let mut valid_count = 0;
+        for (position, signature) in signatures.iter().enumerate() {
+            let Ok(signature) = Signature::from_slice(signature) else {
+                continue;
+            };
+            let candidates = public_keys
+                .get(position)
+                .and_then(|key| keys.iter().position(|k| k == key))
+                .into_iter()
+                .chain(0..keys.len());
+            for index in candidates {
+                if !matched[index] && keys[index].verifying_key.verify(message, &signature).is_ok()
+                {
+                    matched[index] = true;
+                    valid_count += 1;
+                    break;
+                }
+            }
+        }
+
+        if valid_count >= threshold {
+            Ok(valid_count)
+        } else {
+            Err(SystemError::crypto(
+                "threshold_verify",
+                format!("{valid_count} valid signature(s), {threshold} required"),
+            ))
+        }
+    }
+
     /// Get the public key bytes
+    #[allow(clippy::must_use_candidate)]
     pub fn to_bytes(&self) -> [u8; 32] {
         self.verifying_key.to_bytes()
     }
 }
 
 /// Hash data using BLAKE3
+#[allow(clippy::must_use_candidate)]
 pub fn hash_blake3(data: &[u8]) -> [u8; 32] {
     let mut hasher = Blake3Hasher::new();
     hasher.update(data);
@@ -94,12 +199,81 @@ pub fn hash_blake3(data: &[u8]) -> [u8; 32] {
 }
 
 /// Hash data using BLAKE3 with a key (for HMAC-like operation)
+#[allow(clippy::must_use_candidate)]
 pub fn hash_blake3_keyed(key: &[u8; 32], data: &[u8]) -> [u8; 32] {
     let mut hasher = Blake3Hasher::new_keyed(key);
     hasher.update(data);
     *hasher.finalize().as_bytes()
 }
 
+/// Minimum accepted length of an [`HmacKey`], in bytes
+pub const HMAC_MIN_KEY_LEN: usize = 16;
+
+/// HMAC-SHA-256 of `message` under `key`
+///
+/// For interoperability with systems that do not support BLAKE3; prefer
+/// [`hash_blake3_keyed`] otherwise.
+#[must_use]
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    let mut tag = [0u8; 32];
+    tag.copy_from_slice(hmac::sign(&key, message).as_ref());
+    tag
+}
+
+/// Check an HMAC-SHA-256 tag in constant time
+#[must_use]
+pub fn hmac_sha256_verify(key: &[u8], message: &[u8], expected: &[u8]) -> bool {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::verify(&key, message, expected).is_ok()
+}
+
+/// HMAC-SHA-256 key
+///
+/// The key bytes are wiped on drop and never printed.
+#[derive(Clone)]
+pub struct HmacKey {
+    raw: Zeroizing<Vec<u8>>,
+    key: hmac::Key,
+}
+
+impl HmacKey {
+    /// Create a key of at least [`HMAC_MIN_KEY_LEN`] bytes
+    pub fn new(key_bytes: &[u8]) -> Result<Self> {
+        if key_bytes.len() < HMAC_MIN_KEY_LEN {
+            return Err(SystemError::validation(
+                "hmac_key",
+                format!("must be at least {HMAC_MIN_KEY_LEN} bytes"),
+                Some(format!("{} bytes", key_bytes.len())),
+            ));
+        }
+        Ok(Self {
+            raw: Zeroizing::new(key_bytes.to_vec()),
+            key: hmac::Key::new(hmac::HMAC_SHA256, key_bytes),
+        })
+    }
+
+    /// Compute the tag of a message
+    #[must_use]
+    pub fn sign(&self, message: &[u8]) -> [u8; 32] {
+        let mut tag = [0u8; 32];
+        tag.copy_from_slice(hmac::sign(&self.key, message).as_ref());
+        tag
+    }
+
+    /// Check a tag in constant time
+    #[must_use]
+    pub fn verify(&self, message: &[u8], expected: &[u8]) -> bool {
+        hmac::verify(&self.key, message, expected).is_ok()
+    }
+}
+
+impl std::fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacKey").field("len", &self.raw.len()).finish_non_exhaustive()
+    }
+}
+
 /// AES-256-GCM encryption key
 #[derive(ZeroizeOnDrop)]
 pub struct EncryptionKey {
@@ -232,6 +406,54 @@ mod tests {
         assert!(public_key.verify(wrong_message, &signature).is_err());
     }
 
+    #[test]
+    fn test_verify_batch() {
+        let keypair = KeyPair::generate();
+        let public_key = keypair.public_key();
+
+        let messages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 16]).collect();
+        let mut signatures: Vec<Vec<u8>> = messages.iter().map(|m| keypair.sign(m)).collect();
+        let items = |signatures: &[Vec<u8>]| -> Vec<bool> {
+            let pairs: Vec<(&[u8], &[u8])> = messages
+                .iter()
+                .zip(signatures)
+                .map(|(m, s)| (m.as_slice(), s.as_slice()))
+                .collect();
+            public_key.verify_batch(&pairs)
+        };
+
+        assert!(items(&signatures).iter().all(|&ok| ok));
+
+        signatures[2] = keypair.sign(b"something else");
+        signatures[5] = vec![0; 3];
+        let expected: Vec<bool> = (0..8).map(|i| i != 2 && i != 5).collect();
+        assert_eq!(items(&signatures), expected);
+
+        assert_eq!(public_key.verify_batch(&[]), Vec::<bool>::new());
+    }
+
+    #[test]
+    fn test_threshold_signatures() {
+        let keypairs: Vec<KeyPair> = (0..4u8).map(|i| KeyPair::from_seed(&[i; 32])).collect();
+        let public_keys: Vec<PublicKey> = keypairs.iter().map(KeyPair::public_key).collect();
+        let keys: Vec<&PublicKey> = public_keys.iter().collect();
+        let message = b"attest block 42";
+
+        // Signers 3 and 1 of 4, in reverse order, plus a forged signature
+        let mut signatures = KeyPair::multi_sign(message, &[&keypairs[3], &keypairs[1]]);
+        signatures.push(keypairs[0].sign(b"something else"));
+        assert_eq!(signatures.len(), 3);
+        assert_eq!(PublicKey::verify_threshold(message, &keys, &signatures, 2).unwrap(), 2);
+        let err = PublicKey::verify_threshold(message, &keys, &signatures, 3).unwrap_err();
+        assert!(matches!(err, SystemError::Crypto { .. }));
+
+        // A repeated signer counts once
+        let repeated = vec![signatures[0].clone(), signatures[0].clone()];
+        let doubled = [keys[3], keys[3]];
+        assert!(PublicKey::verify_threshold(message, &doubled, &repeated, 2).is_err());
+        assert!(PublicKey::verify_threshold(message, &keys, &[], 0).is_err());
+    }
+
     #[test]
     fn test_blake3_hash() {
         let data = b"test data";
@@ -243,6 +465,34 @@ mod tests {
     }
 
     #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2
+        let tag = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let expected = [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+            0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+            0x64, 0xec, 0x38, 0x43,
+        ];
+        assert_eq!(tag, expected);
+        assert!(hmac_sha256_verify(b"Jefe", b"what do ya want for nothing?", &tag));
+        assert!(!hmac_sha256_verify(b"Jefe", b"what do ya want for something?", &tag));
+        assert!(!hmac_sha256_verify(b"Jefe", b"what do ya want for nothing?", &tag[..16]));
+    }
+
+    #[test]
+    fn test_hmac_key() {
+        assert!(matches!(HmacKey::new(&[7; 15]), Err(SystemError::Validation { .. })));
+
+        let key = HmacKey::new(&[7; 16]).unwrap();
+        let tag = key.sign(b"message");
+        assert_eq!(tag, hmac_sha256(&[7; 16], b"message"));
+        assert!(key.verify(b"message", &tag));
+        assert!(!key.verify(b"other", &tag));
+        assert!(!format!("{key:?}").contains('7'));
+    }
+
+    #[test]
+    #[allow(clippy::assert_is_empty)]
     fn test_encryption_decryption() {
         let mut key = EncryptionKey::generate().unwrap();
         let plaintext = b"secret message";
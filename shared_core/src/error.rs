@@ -4,6 +4,7 @@
 //! All system-specific errors should wrap `SystemError` for consistency.
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::fmt;
 use thiserror::Error;
 
@@ -138,6 +139,15 @@ pub enum SystemError {
         expected_state: Option<String>,
     },
 
+    /// Rate limit or quota exceeded
+    #[error("Rate limit exceeded for {resource}, retry after {retry_after_ms}ms")]
+    RateLimited {
+        /// What was rate limited, e.g. the identity or operation
+        resource: String,
+        /// How long until the request may succeed, in milliseconds
+        retry_after_ms: u64,
+    },
+
     /// System-specific error
     #[error("System-specific error: {system} - {message}")]
     SystemSpecific {
@@ -155,6 +165,7 @@ pub enum SystemError {
         /// Error message
         message: String,
         /// Source location (file:line)
+        #[allow(clippy::doc_markdown)]
         location: Option<String>,
     },
 }
@@ -162,13 +173,40 @@ pub enum SystemError {
 /// Result type alias used throughout the codebase
 pub type Result<T> = std::result::Result<T, SystemError>;
 
+/// Span field names [`SystemError::record_in_current_span`] records, for
+/// spans to declare as [`tracing::field::Empty`]
+pub const SPAN_FIELDS: &[&str] = &[
+    "error.kind",
+    "error.message",
+    "error.context",
+    "error.key",
+    "error.operation",
+    "error.details",
+    "error.thread_id",
+    "error.field",
+    "error.reason",
+    "error.value",
+    "error.retry_attempt",
+    "error.format",
+    "error.duration_ms",
+    "error.resource_type",
+    "error.identifier",
+    "error.required_permission",
+    "error.current_state",
+    "error.expected_state",
+    "error.resource",
+    "error.retry_after_ms",
+    "error.system",
+    "error.location",
+];
+
 impl SystemError {
     /// Create an I/O error with context
     pub fn io(source: impl fmt::Display, context: impl Into<String>) -> Self {
         Self::Io {
             message: source.to_string(),
             context: context.into(),
-        }
+        }.recorded()
     }
 
     /// Create a configuration error
@@ -176,7 +214,7 @@ impl SystemError {
         Self::Config {
             message: message.into(),
             key,
-        }
+        }.recorded()
     }
 
     /// Create a cryptographic error
@@ -184,7 +222,7 @@ impl SystemError {
         Self::Crypto {
             operation: operation.into(),
             details: details.into(),
-        }
+        }.recorded()
     }
 
     /// Create a validation error
@@ -197,7 +235,7 @@ impl SystemError {
             field: field.into(),
             reason: reason.into(),
             value,
-        }
+        }.recorded()
     }
 
     /// Create a network error
@@ -210,7 +248,7 @@ impl SystemError {
             operation: operation.into(),
             message: message.into(),
             retry_attempt,
-        }
+        }.recorded()
     }
 
     /// Create a timeout error
@@ -218,7 +256,7 @@ impl SystemError {
         Self::Timeout {
             operation: operation.into(),
             duration_ms,
-        }
+        }.recorded()
     }
 
     /// Create a not found error
@@ -226,7 +264,15 @@ impl SystemError {
         Self::NotFound {
             resource_type: resource_type.into(),
             identifier: identifier.into(),
-        }
+        }.recorded()
+    }
+
+    /// Create a rate limited error
+    pub fn rate_limited(resource: impl Into<String>, retry_after: std::time::Duration) -> Self {
+        Self::RateLimited {
+            resource: resource.into(),
+            retry_after_ms: u64::try_from(retry_after.as_millis()).unwrap_or(u64::MAX),
+        }.recorded()
     }
 
     /// Create an internal error
@@ -234,8 +280,447 @@ impl SystemError {
         Self::Internal {
             message: message.into(),
             location,
+        }.recorded()
+    }
+
+    /// Record the error in the current span, as every constructor above does
+    fn recorded(self) -> Self {
+        self.record_in_current_span();
+        self
+    }
+
+    /// Record the error's kind and variant fields on the current span
+    ///
+    /// Fields are recorded as `error.kind` and `error.<field>`, e.g.
+    /// `error.field`, `error.reason` and `error.value` for `Validation`;
+    /// absent optional fields are left unset. A span only keeps the fields
+    /// it declared when it was created, so spans that should carry errors
+    /// across `.await` points preallocate them:
+    /// `#[instrument(fields(error.kind = tracing::field::Empty, ...))]`,
+    /// with the names in [`SPAN_FIELDS`]. Errors built with the helper
+    /// constructors are recorded when they are created.
+    pub fn record_in_current_span(&self) {
+        let span = tracing::Span::current();
+        if span.is_disabled() {
+            return;
+        }
+        span.record("error.kind", self.kind());
+        match self {
+            Self::Io { message, context } => {
+                span.record("error.message", message.as_str());
+                span.record("error.context", context.as_str());
+            },
+            Self::Config { message, key } => {
+                span.record("error.message", message.as_str());
+                span.record("error.key", key.as_deref());
+            },
+            Self::Crypto { operation, details } => {
+                span.record("error.operation", operation.as_str());
+                span.record("error.details", details.as_str());
+            },
+            Self::Concurrency { message, thread_id } => {
+                span.record("error.message", message.as_str());
+                span.record("error.thread_id", thread_id.as_deref());
+            },
+            Self::Validation { field, reason, value } => {
+                span.record("error.field", field.as_str());
+                span.record("error.reason", reason.as_str());
+                span.record("error.value", value.as_deref());
+            },
+            Self::Network {
+                operation,
+                message,
+                retry_attempt,
+            } => {
+                span.record("error.operation", operation.as_str());
+                span.record("error.message", message.as_str());
+                span.record("error.retry_attempt", retry_attempt);
+            },
+            Self::Database { operation, message } => {
+                span.record("error.operation", operation.as_str());
+                span.record("error.message", message.as_str());
+            },
+            Self::Serialization { message, format } => {
+                span.record("error.message", message.as_str());
+                span.record("error.format", format.as_str());
+            },
+            Self::Timeout {
+                operation,
+                duration_ms,
+            } => {
+                span.record("error.operation", operation.as_str());
+                span.record("error.duration_ms", duration_ms);
+            },
+            Self::NotFound {
+                resource_type,
+                identifier,
+            }
+            | Self::AlreadyExists {
+                resource_type,
+                identifier,
+            } => {
+                span.record("error.resource_type", resource_type.as_str());
+                span.record("error.identifier", identifier.as_str());
+            },
+            Self::PermissionDenied {
+                operation,
+                required_permission,
+            } => {
+                span.record("error.operation", operation.as_str());
+                span.record("error.required_permission", required_permission.as_deref());
+            },
+            Self::InvalidState {
+                message,
+                current_state,
+                expected_state,
+            } => {
+                span.record("error.message", message.as_str());
+                span.record("error.current_state", current_state.as_deref());
+                span.record("error.expected_state", expected_state.as_deref());
+            },
+            Self::RateLimited {
+                resource,
+                retry_after_ms,
+            } => {
+                span.record("error.resource", resource.as_str());
+                span.record("error.retry_after_ms", retry_after_ms);
+            },
+            Self::SystemSpecific {
+                system,
+                message,
+                context,
+            } => {
+                span.record("error.system", system.as_str());
+                span.record("error.message", message.as_str());
+                span.record("error.context", context.as_deref());
+            },
+            Self::Internal { message, location } => {
+                span.record("error.message", message.as_str());
+                span.record("error.location", location.as_deref());
+            },
         }
     }
+
+    /// Whether retrying the failed operation may succeed
+    #[must_use]
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            Self::Network { .. }
+                | Self::Timeout { .. }
+                | Self::Concurrency { .. }
+                | Self::RateLimited { .. }
+        )
+    }
+
+    /// How long the caller should wait before retrying, if the error says
+    #[must_use]
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::RateLimited { retry_after_ms, .. } => {
+                Some(std::time::Duration::from_millis(*retry_after_ms))
+            },
+            _ => None,
+        }
+    }
+
+    /// Name of the error variant, e.g. `"NotFound"`
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Io { .. } => "Io",
+            Self::Config { .. } => "Config",
+            Self::Crypto { .. } => "Crypto",
+            Self::Concurrency { .. } => "Concurrency",
+            Self::Validation { .. } => "Validation",
+            Self::Network { .. } => "Network",
+            Self::Database { .. } => "Database",
+            Self::Serialization { .. } => "Serialization",
+            Self::Timeout { .. } => "Timeout",
+            Self::NotFound { .. } => "NotFound",
+            Self::PermissionDenied { .. } => "PermissionDenied",
+            Self::AlreadyExists { .. } => "AlreadyExists",
+            Self::InvalidState { .. } => "InvalidState",
+            Self::RateLimited { .. } => "RateLimited",
+            Self::SystemSpecific { .. } => "SystemSpecific",
+            Self::Internal { .. } => "Internal",
+        }
+    }
+
+    /// HTTP status code this error maps to at an API boundary
+    #[must_use]
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Self::Validation { .. } | Self::Serialization { .. } | Self::Crypto { .. } => 400,
+            Self::PermissionDenied { .. } => 403,
+            Self::NotFound { .. } => 404,
+            Self::AlreadyExists { .. } | Self::InvalidState { .. } => 409,
+            Self::RateLimited { .. } => 429,
+            Self::Concurrency { .. } | Self::Database { .. } => 503,
+            Self::Network { .. } => 502,
+            Self::Timeout { .. } => 504,
+            Self::Io { .. }
+            | Self::Config { .. }
+            | Self::SystemSpecific { .. }
+            | Self::Internal { .. } => 500,
+        }
+    }
+
+    /// Severity to log this error at
+    ///
+    /// Caller mistakes log at `info`, transient failures at `warn`, and
+    /// everything else at `error`.
+    fn log_level(&self) -> &'static str {
+        match self {
+            Self::Validation { .. }
+            | Self::NotFound { .. }
+            | Self::PermissionDenied { .. }
+            | Self::AlreadyExists { .. }
+            | Self::RateLimited { .. } => "info",
+            _ if self.is_retriable() => "warn",
+            _ => "error",
+        }
+    }
+
+    /// Structured representation for production logging
+    ///
+    /// Every variant field becomes a named key alongside `kind`, `display`,
+    /// `level` and `retriable`, e.g.
+    /// `tracing::error!(error = ?err.to_log_value(), "request failed")`.
+    #[must_use]
+    pub fn to_log_value(&self) -> serde_json::Value {
+        let fields = match self {
+            Self::Io { message, context } => json!({ "message": message, "context": context }),
+            Self::Config { message, key } => json!({ "message": message, "key": key }),
+            Self::Crypto { operation, details } => {
+                json!({ "operation": operation, "details": details })
+            },
+            Self::Concurrency { message, thread_id } => {
+                json!({ "message": message, "thread_id": thread_id })
+            },
+            Self::Validation { field, reason, value } => {
+                json!({ "field": field, "reason": reason, "value": value })
+            },
+            Self::Network {
+                operation,
+                message,
+                retry_attempt,
+            } => json!({
+                "operation": operation,
+                "message": message,
+                "retry_attempt": retry_attempt,
+            }),
+            Self::Database { operation, message } => {
+                json!({ "operation": operation, "message": message })
+            },
+            Self::Serialization { message, format } => {
+                json!({ "message": message, "format": format })
+            },
+            Self::Timeout {
+                operation,
+                duration_ms,
+            } => json!({ "operation": operation, "duration_ms": duration_ms }),
+            Self::NotFound {
+                resource_type,
+                identifier,
+            }
+            | Self::AlreadyExists {
+                resource_type,
+                identifier,
+            } => json!({ "resource_type": resource_type, "identifier": identifier }),
+            Self::PermissionDenied {
+                operation,
+                required_permission,
+            } => json!({ "operation": operation, "required_permission": required_permission }),
+            Self::InvalidState {
+                message,
+                current_state,
+                expected_state,
+            } => json!({
+                "message": message,
+                "current_state": current_state,
+                "expected_state": expected_state,
+            }),
+            Self::RateLimited {
+                resource,
+                retry_after_ms,
+            } => json!({ "resource": resource, "retry_after_ms": retry_after_ms }),
+            Self::SystemSpecific {
+                system,
+                message,
+                context,
+            } => json!({ "system": system, "message": message, "context": context }),
+            Self::Internal { message, location } => {
+                json!({ "message": message, "location": location })
+            },
+        };
+
+        let mut value = json!({
+            "kind": self.kind(),
+            "display": self.to_string(),
+            "level": self.log_level(),
+            "retriable": self.is_retriable(),
+        });
+        if let (Some(object), serde_json::Value::Object(fields)) = (value.as_object_mut(), fields) {
+            object.extend(fields);
+        }
+        value
+    }
+}
+
+/// JSON error body returned by HTTP APIs
+///
+/// Server-side failures (status 500) carry a generic message so internal
+/// details stay in the logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// Error variant name, see [`SystemError::kind`]
+    pub kind: String,
+    /// Human-readable description
+    pub message: String,
+    /// Whether the client may retry the request
+    pub retriable: bool,
+    /// How long the client should wait before retrying, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+impl From<&SystemError> for ErrorResponse {
+    fn from(err: &SystemError) -> Self {
+        let status = err.http_status();
+        let message = if status == 500 {
+            "internal server error".to_string()
+        } else {
+            err.to_string()
+        };
+        Self {
+            status,
+            kind: err.kind().to_string(),
+            message,
+            retriable: err.is_retriable(),
+            retry_after_ms: match err {
+                SystemError::RateLimited { retry_after_ms, .. } => Some(*retry_after_ms),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Errors gathered by a pass that reports every problem instead of the first
+///
+/// Converts into a single `Validation` error listing every entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorCollection<E = SystemError> {
+    errors: Vec<E>,
+}
+
+impl<E> Default for ErrorCollection<E> {
+    fn default() -> Self {
+        Self { errors: Vec::new() }
+    }
+}
+
+impl<E> ErrorCollection<E> {
+    /// Create an empty collection
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an error
+    pub fn push(&mut self, error: E) {
+        self.errors.push(error);
+    }
+
+    /// Whether no error was recorded
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Number of recorded errors
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Recorded errors, in order
+    pub fn iter(&self) -> std::slice::Iter<'_, E> {
+        self.errors.iter()
+    }
+
+    /// Recorded errors, in order
+    #[must_use]
+    pub fn into_vec(self) -> Vec<E> {
+        self.errors
+    }
+
+    /// `Ok(value)` if no error was recorded, the collection otherwise
+    pub fn into_result<T>(self, value: T) -> std::result::Result<T, Self> {
+        if self.is_empty() {
+            Ok(value)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<E> Extend<E> for ErrorCollection<E> {
+    fn extend<I: IntoIterator<Item = E>>(&mut self, iter: I) {
+        self.errors.extend(iter);
+    }
+}
+
+impl<E> FromIterator<E> for ErrorCollection<E> {
+    fn from_iter<I: IntoIterator<Item = E>>(iter: I) -> Self {
+        Self {
+            errors: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<E> IntoIterator for ErrorCollection<E> {
+    type Item = E;
+    type IntoIter = std::vec::IntoIter<E>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl<'a, E> IntoIterator for &'a ErrorCollection<E> {
+    type Item = &'a E;
+    type IntoIter = std::slice::Iter<'a, E>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ErrorCollection<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for ErrorCollection<E> {}
+
+impl<E: fmt::Display> From<ErrorCollection<E>> for SystemError {
+    fn from(errors: ErrorCollection<E>) -> Self {
+        Self::validation(
+            "errors",
+            format!("{} error(s):\n{errors}", errors.len()),
+            None,
+        )
+    }
 }
 
 // Implement From for common error types
@@ -290,4 +775,124 @@ mod tests {
         let deserialized: SystemError = serde_json::from_str(&json).unwrap();
         assert!(matches!(deserialized, SystemError::Config { .. }));
     }
+
+    #[test]
+    fn test_log_value_fields() {
+        let err = SystemError::validation("email", "Invalid format", None);
+        let value = err.to_log_value();
+        assert_eq!(value["kind"], "Validation");
+        assert_eq!(value["level"], "info");
+        assert_eq!(value["retriable"], false);
+        assert_eq!(value["field"], "email");
+        assert_eq!(value["reason"], "Invalid format");
+        assert!(value["value"].is_null());
+        assert_eq!(value["display"], err.to_string());
+
+        let value = SystemError::timeout("fetch", 250).to_log_value();
+        assert_eq!(value["level"], "warn");
+        assert_eq!(value["retriable"], true);
+        assert_eq!(value["duration_ms"], 250);
+
+        let value = SystemError::internal("bug", Some("lib.rs:1".to_string())).to_log_value();
+        assert_eq!(value["level"], "error");
+        assert_eq!(value["message"], "bug");
+        assert_eq!(value["location"], "lib.rs:1");
+    }
+
+    /// Fields recorded on spans, as `name=value` pairs
+    #[derive(Clone, Default)]
+    struct Recorded(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Recorded {
+        fn on_record(
+            &self,
+            _: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            values.record(&mut |field: &tracing::field::Field, value: &dyn fmt::Debug| {
+                self.0.lock().unwrap().push(format!("{}={value:?}", field.name()));
+            });
+        }
+    }
+
+    #[test]
+    fn test_record_in_current_span() {
+        use tracing::field::Empty;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorded = Recorded::default();
+        let subscriber = tracing_subscriber::registry().with(recorded.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "request",
+                error.kind = Empty,
+                error.field = Empty,
+                error.reason = Empty,
+                error.value = Empty,
+                error.retry_attempt = Empty,
+            );
+            let _entered = span.enter();
+            let err = SystemError::validation("email", "Invalid format", None);
+            // Variants built directly are only recorded when asked to
+            let network = SystemError::Network {
+                operation: "connect".to_string(),
+                message: "refused".to_string(),
+                retry_attempt: Some(2),
+            };
+            network.record_in_current_span();
+            drop(err);
+        });
+        let recorded = recorded.0.lock().unwrap().clone();
+        assert_eq!(
+            recorded,
+            [
+                "error.kind=\"Validation\"",
+                "error.field=\"email\"",
+                "error.reason=\"Invalid format\"",
+                "error.kind=\"Network\"",
+                "error.retry_attempt=2",
+            ]
+        );
+        assert!(SPAN_FIELDS.contains(&"error.retry_attempt"));
+    }
+
+    #[test]
+    fn test_error_response() {
+        let response = ErrorResponse::from(&SystemError::not_found("attestation", "a-1"));
+        assert_eq!(response.status, 404);
+        assert_eq!(response.kind, "NotFound");
+        assert!(response.message.contains("a-1"));
+        assert!(!response.retriable);
+
+        let response = ErrorResponse::from(&SystemError::internal("secret detail", None));
+        assert_eq!(response.status, 500);
+        assert!(!response.message.contains("secret"));
+        assert_eq!(SystemError::timeout("op", 5).http_status(), 504);
+
+        let err = SystemError::rate_limited("svc-a", std::time::Duration::from_millis(1500));
+        let response = ErrorResponse::from(&err);
+        assert_eq!(response.status, 429);
+        assert_eq!(response.kind, "RateLimited");
+        assert!(response.retriable);
+        assert_eq!(response.retry_after_ms, Some(1500));
+        assert_eq!(err.to_log_value()["level"], "info");
+    }
+
+    #[test]
+    fn test_error_collection() {
+        let empty: ErrorCollection = ErrorCollection::new();
+        assert_eq!(empty.into_result(7).unwrap(), 7);
+
+        let mut errors: ErrorCollection<String> = ErrorCollection::new();
+        errors.push("1:1: first".to_string());
+        errors.extend(["2:1: second".to_string()]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors.to_string(), "1:1: first\n2:1: second");
+
+        let err = SystemError::from(errors.into_result(()).unwrap_err());
+        assert!(matches!(err, SystemError::Validation { .. }));
+        assert!(err.to_string().contains("2 error(s)"));
+        assert!(err.to_string().contains("2:1: second"));
+    }
 }
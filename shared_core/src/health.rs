@@ -0,0 +1,167 @@
+//! Health checks
+//!
+//! Services register named [`HealthCheck`]s with a [`HealthRegistry`] and
+//! expose the combined [`HealthReport`] from their health endpoint.
+
+use crate::Result;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A single component probe
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Succeed if the component is usable
+    async fn check(&self) -> Result<()>;
+}
+
+/// Result of one health check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    /// Whether the check passed
+    pub healthy: bool,
+    /// Failure description, if any
+    pub error: Option<String>,
+}
+
+/// Combined result of every registered check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Whether every check passed
+    pub healthy: bool,
+    /// Per-check results keyed by name
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+/// Named set of health checks
+///
+/// Each check is given `timeout` to complete; a check that runs over is
+/// reported unhealthy.
+#[derive(Clone)]
+pub struct HealthRegistry {
+    checks: Arc<RwLock<BTreeMap<String, Arc<dyn HealthCheck>>>>,
+    timeout: Duration,
+}
+
+impl HealthRegistry {
+    /// Create an empty registry
+    #[must_use]
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            checks: Arc::new(RwLock::new(BTreeMap::new())),
+            timeout,
+        }
+    }
+
+    /// Register a check, replacing any existing check with the same name
+    pub fn register(&self, name: impl Into<String>, check: Arc<dyn HealthCheck>) {
+        self.checks.write().insert(name.into(), check);
+    }
+
+    /// Remove a check
+    #[must_use]
+    pub fn unregister(&self, name: &str) -> bool {
+        self.checks.write().remove(name).is_some()
+    }
+
+    /// Run every check concurrently
+    pub async fn report(&self) -> HealthReport {
+        let checks: Vec<_> = self
+            .checks
+            .read()
+            .iter()
+            .map(|(name, check)| (name.clone(), Arc::clone(check)))
+            .collect();
+
+        let results = futures::future::join_all(checks.into_iter().map(|(name, check)| {
+            let timeout = self.timeout;
+            async move {
+                let error = match tokio::time::timeout(timeout, check.check()).await {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e.to_string()),
+                    Err(_) => Some(format!("timed out after {}ms", timeout.as_millis())),
+                };
+                let result = CheckResult {
+                    healthy: error.is_none(),
+                    error,
+                };
+                (name, result)
+            }
+        }))
+        .await;
+
+        let checks: BTreeMap<_, _> = results.into_iter().collect();
+        HealthReport {
+            healthy: checks.values().all(|c| c.healthy),
+            checks,
+        }
+    }
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl std::fmt::Debug for HealthRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HealthRegistry")
+            .field("checks", &self.checks.read().keys().collect::<Vec<_>>())
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SystemError;
+
+    struct Fixed(Option<&'static str>);
+
+    #[async_trait]
+    impl HealthCheck for Fixed {
+        async fn check(&self) -> Result<()> {
+            match self.0 {
+                None => Ok(()),
+                Some(reason) => Err(SystemError::internal(reason, None)),
+            }
+        }
+    }
+
+    struct Hangs;
+
+    #[async_trait]
+    impl HealthCheck for Hangs {
+        async fn check(&self) -> Result<()> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let registry = HealthRegistry::new(Duration::from_millis(50));
+        assert!(registry.report().await.healthy);
+
+        registry.register("db", Arc::new(Fixed(None)));
+        assert!(registry.report().await.healthy);
+
+        registry.register("cache", Arc::new(Fixed(Some("down"))));
+        registry.register("upstream", Arc::new(Hangs));
+        let report = registry.report().await;
+        assert!(!report.healthy);
+        assert!(report.checks["db"].healthy);
+        assert!(report.checks["cache"].error.as_deref().unwrap().contains("down"));
+        assert!(report.checks["upstream"].error.as_deref().unwrap().contains("timed out"));
+
+        assert!(registry.unregister("cache"));
+        assert!(registry.unregister("upstream"));
+        assert!(registry.report().await.healthy);
+    }
+}
@@ -9,6 +9,7 @@
 //! - `telemetry`: OpenTelemetry integration for distributed tracing and metrics
 //! - `crypto`: Cryptographic primitives and utilities
 //! - `config`: Configuration management and parsing
+//! - `health`: Health check registry for service endpoints
 //! - `types`: Common types and traits used across systems
 //! - `resource_governor`: Resource management and throttling (CPU, RAM, I/O)
 //! - `plugin`: Plugin system architecture for extending functionality
@@ -22,6 +23,7 @@
 pub mod config;
 pub mod crypto;
 pub mod error;
+pub mod health;
 pub mod logging;
 pub mod plugin;
 pub mod resource_governor;
@@ -29,9 +31,17 @@ pub mod telemetry;
 pub mod types;
 
 // Re-export commonly used items
-pub use error::{Result, SystemError};
-pub use plugin::{Plugin, PluginInput, PluginMetadata, PluginOutput, PluginRegistry, PluginState};
+pub use error::{ErrorCollection, ErrorResponse, Result, SystemError};
+pub use health::{HealthCheck, HealthRegistry, HealthReport};
+pub use logging::{CorrelatedError, LogIfErr};
+pub use plugin::{
+    DirectoryWatch, InterceptorGuard, MergeStrategy, OutputInterceptor, Plugin, PluginInput,
+    PluginMetadata, PluginOutput, PluginRegistry, PluginState, RegistrySnapshot, TimeoutOverride,
+};
 pub use resource_governor::{
-    GovernorStatistics, OperationPermit, ResourceGovernor, ResourceGovernorConfig,
+    CircuitBreaker, CircuitBreakerConfig, CircuitState, GovernorStatistics, LabelStatistics,
+    OperationPermit, RateLimiter, RateLimiterStats, ResourceGovernor, ResourceGovernorConfig,
+    RetryPolicy,
 };
+pub use telemetry::{TraceContext, TraceContextLayer};
 pub use types::*;
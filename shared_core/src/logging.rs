@@ -3,13 +3,15 @@
 //! This module provides a unified logging setup for all systems using the `tracing` crate.
 
 use crate::error::{Result, SystemError};
-use tracing::{Level, Subscriber};
+use crate::telemetry::{self, TraceContext, TraceContextLayer};
+use crate::types::Timestamp;
+use std::fmt;
+use tracing::Level;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
-    fmt::{self, format::FmtSpan},
+    fmt::format::FmtSpan,
     layer::SubscriberExt,
-    util::SubscriberInitExt,
-    EnvFilter, Layer, Registry,
+    EnvFilter,
 };
 
 /// Log output format
@@ -37,6 +39,7 @@ pub struct LogConfig {
     /// Whether to log to file
     pub log_to_file: bool,
     /// Log file path (if log_to_file is true)
+    #[allow(clippy::doc_markdown)]
     pub log_file_path: Option<String>,
     /// Span events to log
     pub span_events: FmtSpan,
@@ -58,6 +61,7 @@ impl Default for LogConfig {
 
 impl LogConfig {
     /// Create a production configuration
+    #[allow(clippy::must_use_candidate)]
     pub fn production() -> Self {
         Self {
             level: Level::INFO,
@@ -71,6 +75,7 @@ impl LogConfig {
     }
 
     /// Create a development configuration
+    #[allow(clippy::must_use_candidate)]
     pub fn development() -> Self {
         Self {
             level: Level::DEBUG,
@@ -84,6 +89,7 @@ impl LogConfig {
     }
 
     /// Create a test configuration
+    #[allow(clippy::must_use_candidate)]
     pub fn test() -> Self {
         Self {
             level: Level::TRACE,
@@ -101,6 +107,8 @@ impl LogConfig {
 ///
 /// Returns a `WorkerGuard` that must be kept alive for the duration of the program
 /// to ensure all logs are flushed. If logging to file is disabled, returns `None`.
+/// Spans are given trace context by a [`TraceContextLayer`].
+#[allow(clippy::needless_pass_by_value, clippy::uninlined_format_args)]
 pub fn init_logging(config: LogConfig) -> Result<Option<WorkerGuard>> {
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(config.level.to_string()));
@@ -119,7 +127,8 @@ pub fn init_logging(config: LogConfig) -> Result<Option<WorkerGuard>> {
                 .with_writer(non_blocking)
                 .with_env_filter(env_filter)
                 .json()
-                .finish();
+                .finish()
+                .with(TraceContextLayer);
 
             tracing::subscriber::set_global_default(subscriber)
                 .map_err(|e| SystemError::Concurrency {
@@ -140,6 +149,7 @@ pub fn init_logging(config: LogConfig) -> Result<Option<WorkerGuard>> {
     Ok(guard)
 }
 
+#[allow(clippy::needless_pass_by_value, clippy::uninlined_format_args)]
 fn init_simple(config: LogConfig) -> Result<()> {
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(config.level.to_string()));
@@ -148,7 +158,8 @@ fn init_simple(config: LogConfig) -> Result<()> {
     let subscriber = tracing_subscriber::fmt()
         .with_env_filter(env_filter)
         .json()
-        .finish();
+        .finish()
+        .with(TraceContextLayer);
 
     tracing::subscriber::set_global_default(subscriber)
         .map_err(|e| SystemError::Concurrency {
@@ -159,6 +170,110 @@ fn init_simple(config: LogConfig) -> Result<()> {
     Ok(())
 }
 
+/// A [`SystemError`] with the trace context it was raised in
+///
+/// By the time an error propagates up to where it is logged, the span it
+/// was raised in has often been exited; converting it with `into()` where
+/// it is raised keeps that span's trace and span IDs with it.
+#[derive(Debug)]
+pub struct CorrelatedError {
+    /// The error
+    pub error: SystemError,
+    /// Trace ID of the span the error was raised in, as lowercase hex
+    pub trace_id: Option<String>,
+    /// ID of the span the error was raised in, as lowercase hex
+    pub span_id: Option<String>,
+    /// When the error was raised
+    pub timestamp: Timestamp,
+}
+
+impl CorrelatedError {
+    /// Emit an `error` event with the error's fields, see
+    /// [`SystemError::to_log_value`], and its trace context
+    pub fn log_error(&self) {
+        log_correlated(
+            &self.error,
+            self.trace_id.as_deref(),
+            self.span_id.as_deref(),
+            self.timestamp,
+        );
+    }
+}
+
+/// Captures the trace context of the current span, see
+/// [`TraceContext::from_current_span`]
+impl From<SystemError> for CorrelatedError {
+    fn from(error: SystemError) -> Self {
+        let (trace_id, span_id) = current_trace_ids();
+        Self {
+            error,
+            trace_id,
+            span_id,
+            timestamp: Timestamp::now(),
+        }
+    }
+}
+
+impl fmt::Display for CorrelatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.trace_id {
+            Some(trace_id) => write!(f, "{} (trace {trace_id})", self.error),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CorrelatedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Logging of failed [`Result`]s where they fail
+pub trait LogIfErr {
+    /// Log the error, if any, as [`CorrelatedError::log_error`] does with
+    /// the current span's trace context, and pass the result on
+    #[must_use]
+    fn log_if_err(self) -> Self;
+}
+
+impl<T> LogIfErr for Result<T> {
+    fn log_if_err(self) -> Self {
+        if let Err(error) = &self {
+            let (trace_id, span_id) = current_trace_ids();
+            log_correlated(error, trace_id.as_deref(), span_id.as_deref(), Timestamp::now());
+        }
+        self
+    }
+}
+
+/// Trace and span IDs of the current span, as lowercase hex
+fn current_trace_ids() -> (Option<String>, Option<String>) {
+    TraceContext::from_current_span().map_or((None, None), |context| {
+        (
+            Some(telemetry::hex(&context.trace_id)),
+            Some(telemetry::hex(&context.span_id)),
+        )
+    })
+}
+
+fn log_correlated(
+    error: &SystemError,
+    trace_id: Option<&str>,
+    span_id: Option<&str>,
+    timestamp: Timestamp,
+) {
+    tracing::error!(
+        error.kind = error.kind(),
+        error.fields = %error.to_log_value(),
+        trace_id,
+        span_id,
+        timestamp = timestamp.as_millis(),
+        "{}",
+        error
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,4 +287,62 @@ mod tests {
         info!("Test info log");
         warn!("Test warning log");
     }
+
+    /// Events emitted, as `name=value` pairs of their fields
+    #[derive(Clone, Default)]
+    struct Events(std::sync::Arc<std::sync::Mutex<Vec<Vec<String>>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Events {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = Vec::new();
+            event.record(&mut |field: &tracing::field::Field, value: &dyn fmt::Debug| {
+                fields.push(format!("{}={value:?}", field.name()));
+            });
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    #[test]
+    fn test_correlated_error() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let events = Events::default();
+        let subscriber =
+            tracing_subscriber::registry().with(events.clone()).with(TraceContextLayer);
+        let (correlated, context) = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request");
+            let _entered = span.enter();
+            let correlated = CorrelatedError::from(SystemError::not_found("attestation", "a-1"));
+            let result: Result<()> = Err(SystemError::timeout("fetch", 250));
+            assert!(result.log_if_err().is_err());
+            assert_eq!(Ok::<_, SystemError>(7).log_if_err().unwrap(), 7);
+            (correlated, TraceContext::from_current_span().unwrap())
+        });
+
+        // Logged after the span was exited, with the context it was raised in
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(events.clone()), || {
+            correlated.log_error();
+        });
+        let trace_id = telemetry::hex(&context.trace_id);
+        assert_eq!(correlated.trace_id.as_deref(), Some(trace_id.as_str()));
+        assert_eq!(correlated.span_id, Some(telemetry::hex(&context.span_id)));
+        assert!(correlated.to_string().contains(&trace_id));
+
+        let events = events.0.lock().unwrap().clone();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains(&"error.kind=\"Timeout\"".to_string()));
+        assert!(events[0].contains(&format!("trace_id=\"{trace_id}\"")));
+        assert!(events[1].contains(&"error.kind=\"NotFound\"".to_string()));
+        assert!(events[1].contains(&format!("span_id=\"{}\"", telemetry::hex(&context.span_id))));
+        assert!(events[1]
+            .iter()
+            .any(|field| field.starts_with("error.fields=") && field.contains("a-1")));
+
+        let uncorrelated = CorrelatedError::from(SystemError::internal("bug", None));
+        assert_eq!((uncorrelated.trace_id, uncorrelated.span_id), (None, None));
+    }
 }
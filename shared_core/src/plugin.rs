@@ -3,13 +3,26 @@
 //! Provides a flexible plugin architecture for extending system functionality.
 //! All systems can load and execute plugins dynamically.
 
+use crate::resource_governor::{
+    CircuitBreaker, CircuitBreakerConfig, CircuitState, RateLimiter, RateLimiterStats,
+};
+use crate::types::{Version, VersionRange};
 use crate::{Result, SystemError};
 use async_trait::async_trait;
+use notify::event::{AccessKind, AccessMode, EventKind, ModifyKind, RenameMode};
+use notify::{RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// File extensions of the plugin artifacts picked up from a directory
+const PLUGIN_EXTENSIONS: [&str; 3] = ["so", "dll", "dylib"];
 
 /// Plugin metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +47,10 @@ pub struct PluginMetadata {
 
     /// Minimum system version required
     pub min_system_version: String,
+
+    /// Maximum system version supported, inclusive
+    #[serde(default)]
+    pub max_system_version: Option<String>,
 }
 
 impl PluginMetadata {
@@ -51,22 +68,52 @@ impl PluginMetadata {
             description: String::new(),
             capabilities: Vec::new(),
             min_system_version: "0.1.0".into(),
+            max_system_version: None,
         }
     }
 
+    /// Range of system versions the plugin runs on, from
+    /// `min_system_version` up to and including `max_system_version`
+    pub fn system_version_range(&self) -> Result<VersionRange> {
+        let parse = |field: &str, version: &str| {
+            Version::parse(version).ok_or_else(|| {
+                SystemError::validation(field, "not a version", Some(version.to_string()))
+            })
+        };
+        Ok(VersionRange {
+            min: Some(parse("min_system_version", &self.min_system_version)?),
+            max: self
+                .max_system_version
+                .as_deref()
+                .map(|max| parse("max_system_version", max))
+                .transpose()?,
+            include_max: true,
+        })
+    }
+
     /// Add a capability
+    #[must_use]
     pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
         self.capabilities.push(capability.into());
         self
     }
 
     /// Set description
+    #[must_use]
     pub fn with_description(mut self, description: impl Into<String>) -> Self {
         self.description = description.into();
         self
     }
 
+    /// Set the maximum system version supported
+    #[must_use]
+    pub fn with_max_system_version(mut self, version: impl Into<String>) -> Self {
+        self.max_system_version = Some(version.into());
+        self
+    }
+
     /// Set author
+    #[must_use]
     pub fn with_author(mut self, author: impl Into<String>) -> Self {
         self.author = author.into();
         self
@@ -96,6 +143,7 @@ pub enum PluginState {
 }
 
 /// Plugin trait that all plugins must implement
+#[allow(clippy::double_must_use)]
 #[async_trait]
 pub trait Plugin: Send + Sync {
     /// Get plugin metadata
@@ -137,6 +185,20 @@ pub trait Plugin: Send + Sync {
         Ok(())
     }
 
+    /// JSON Schema of the data the plugin accepts
+    ///
+    /// Defaults to [`PluginInput::json_schema`].
+    fn input_schema(&self) -> serde_json::Value {
+        PluginInput::json_schema()
+    }
+
+    /// JSON Schema of the data the plugin produces
+    ///
+    /// Defaults to [`PluginOutput::json_schema`].
+    fn output_schema(&self) -> serde_json::Value {
+        PluginOutput::json_schema()
+    }
+
     /// Get plugin as Any for downcasting
     fn as_any(&self) -> &dyn Any;
 
@@ -156,6 +218,7 @@ pub struct PluginInput {
 
 impl PluginInput {
     /// Create new plugin input
+    #[allow(clippy::must_use_candidate)]
     pub fn new() -> Self {
         Self {
             data: HashMap::new(),
@@ -164,26 +227,117 @@ impl PluginInput {
     }
 
     /// Add data field
+    #[allow(clippy::return_self_not_must_use)]
     pub fn with_data(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
         self.data.insert(key.into(), value);
         self
     }
 
     /// Add context field
+    #[allow(clippy::return_self_not_must_use)]
     pub fn with_context(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.context.insert(key.into(), value.into());
         self
     }
 
     /// Get data field
+    #[allow(clippy::must_use_candidate)]
     pub fn get_data(&self, key: &str) -> Option<&serde_json::Value> {
         self.data.get(key)
     }
 
     /// Get context field
+    #[allow(clippy::must_use_candidate)]
     pub fn get_context(&self, key: &str) -> Option<&String> {
         self.context.get(key)
     }
+
+    /// JSON Schema describing any plugin input
+    #[must_use]
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "data": { "type": "object" },
+                "context": { "type": "object" }
+            },
+            "required": ["data", "context"]
+        })
+    }
+
+    /// Union of the `data` and `context` maps of `inputs`, a later input's
+    /// value replacing an earlier one on key collision
+    pub fn merge(inputs: impl IntoIterator<Item = PluginInput>) -> Self {
+        inputs.into_iter().fold(Self::new(), |mut merged, input| {
+            merged.data.extend(input.data);
+            merged.context.extend(input.context);
+            merged
+        })
+    }
+
+    /// Union of the `data` and `context` maps of `inputs`, resolving key
+    /// collisions with `strategy`
+    ///
+    /// With [`MergeStrategy::ErrorOnConflict`], a key holding different
+    /// values in two inputs is a `Validation` error; equal values merge.
+    pub fn merge_with(
+        inputs: impl IntoIterator<Item = PluginInput>,
+        strategy: MergeStrategy,
+    ) -> Result<Self> {
+        let mut merged = Self::new();
+        for input in inputs {
+            merge_map(&mut merged.data, input.data, strategy, "data")?;
+            merge_map(&mut merged.context, input.context, strategy, "context")?;
+        }
+        Ok(merged)
+    }
+
+    /// Merge of `a` and `b`, `b` winning on key collision
+    #[must_use]
+    pub fn merge_two(a: PluginInput, b: PluginInput) -> Self {
+        Self::merge([a, b])
+    }
+}
+
+/// How [`PluginInput::merge_with`] resolves a key present in several inputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    /// Keep the value of the last input holding the key
+    #[default]
+    LastWins,
+    /// Keep the value of the first input holding the key
+    FirstWins,
+    /// Fail if the inputs hold different values for the key
+    ErrorOnConflict,
+}
+
+fn merge_map<V: PartialEq>(
+    merged: &mut HashMap<String, V>,
+    from: HashMap<String, V>,
+    strategy: MergeStrategy,
+    field: &str,
+) -> Result<()> {
+    for (key, value) in from {
+        match merged.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            },
+            Entry::Occupied(mut entry) => match strategy {
+                MergeStrategy::LastWins => {
+                    entry.insert(value);
+                },
+                MergeStrategy::ErrorOnConflict if *entry.get() != value => {
+                    return Err(SystemError::validation(
+                        field,
+                        format!("conflicting values for key `{}`", entry.key()),
+                        Some(entry.key().clone()),
+                    ));
+                },
+                MergeStrategy::FirstWins | MergeStrategy::ErrorOnConflict => {},
+            },
+        }
+    }
+    Ok(())
 }
 
 impl Default for PluginInput {
@@ -210,6 +364,7 @@ pub struct PluginOutput {
 
 impl PluginOutput {
     /// Create successful output
+    #[allow(clippy::must_use_candidate)]
     pub fn success() -> Self {
         Self {
             success: true,
@@ -230,36 +385,109 @@ impl PluginOutput {
     }
 
     /// Add data field
+    #[allow(clippy::return_self_not_must_use)]
     pub fn with_data(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
         self.data.insert(key.into(), value);
         self
     }
 
     /// Add metric
+    #[allow(clippy::return_self_not_must_use)]
     pub fn with_metric(mut self, key: impl Into<String>, value: f64) -> Self {
         self.metrics.insert(key.into(), value);
         self
     }
+
+    /// JSON Schema describing any plugin output
+    #[must_use]
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "success": { "type": "boolean" },
+                "data": { "type": "object" },
+                "error": { "type": "string" },
+                "metrics": { "type": "object" }
+            },
+            "required": ["success", "data", "metrics"]
+        })
+    }
+}
+
+/// Middleware that sees, and may rewrite, every plugin output
+///
+/// Interceptors are added with [`PluginRegistry::add_interceptor`] and run in
+/// the order they were added on the outputs returned by
+/// [`PluginRegistry::execute`].
+pub trait OutputInterceptor: Send + Sync {
+    /// Output to hand to the caller in place of `output`, which `plugin_id`
+    /// returned when executed by `registry`
+    fn intercept(
+        &self,
+        registry: &PluginRegistry,
+        plugin_id: &str,
+        output: PluginOutput,
+    ) -> PluginOutput;
 }
 
+/// Interceptors installed on a registry, in the order they run
+type Interceptors = Arc<parking_lot::RwLock<Vec<Arc<dyn OutputInterceptor>>>>;
+
 /// Plugin registry for managing loaded plugins
 pub struct PluginRegistry {
     plugins: Arc<RwLock<HashMap<String, Box<dyn Plugin>>>>,
     states: Arc<RwLock<HashMap<String, PluginState>>>,
+    timeout_overrides: Arc<parking_lot::RwLock<HashMap<String, Duration>>>,
+    rate_limits: Arc<parking_lot::RwLock<HashMap<String, Arc<RateLimiter>>>>,
+    circuit_breakers: Arc<parking_lot::RwLock<HashMap<String, Arc<CircuitBreaker>>>>,
+    interceptors: Interceptors,
+    system_version: Version,
 }
 
 impl PluginRegistry {
     /// Create a new plugin registry
+    #[allow(clippy::must_use_candidate)]
     pub fn new() -> Self {
         Self {
             plugins: Arc::new(RwLock::new(HashMap::new())),
             states: Arc::new(RwLock::new(HashMap::new())),
+            timeout_overrides: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+            rate_limits: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+            circuit_breakers: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+            interceptors: Arc::new(parking_lot::RwLock::new(Vec::new())),
+            system_version: Version::parse(env!("CARGO_PKG_VERSION"))
+                .unwrap_or_else(|| Version::new(0, 1, 0)),
         }
     }
 
+    /// Check plugins against `version` instead of this crate's version
+    #[must_use]
+    pub fn with_system_version(mut self, version: Version) -> Self {
+        self.system_version = version;
+        self
+    }
+
+    /// System version plugins are checked against when registered
+    #[must_use]
+    pub fn system_version(&self) -> &Version {
+        &self.system_version
+    }
+
     /// Register a plugin
+    ///
+    /// Fails with a `Validation` error if the system version is outside the
+    /// plugin's [`system_version_range`](PluginMetadata::system_version_range).
+    #[allow(clippy::uninlined_format_args)]
     pub async fn register(&self, plugin: Box<dyn Plugin>) -> Result<()> {
         let id = plugin.metadata().id.clone();
+        let range = plugin.metadata().system_version_range()?;
+        if !self.system_version.satisfies_range(&range) {
+            return Err(SystemError::validation(
+                "system_version",
+                format!("plugin '{id}' requires system version {range}"),
+                Some(self.system_version.to_string()),
+            ));
+        }
 
         let mut plugins = self.plugins.write().await;
         let mut states = self.states.write().await;
@@ -279,6 +507,7 @@ impl PluginRegistry {
     }
 
     /// Unregister a plugin
+    #[allow(clippy::uninlined_format_args)]
     pub async fn unregister(&self, plugin_id: &str) -> Result<()> {
         let mut plugins = self.plugins.write().await;
         let mut states = self.states.write().await;
@@ -290,11 +519,14 @@ impl PluginRegistry {
         })?;
 
         states.insert(plugin_id.to_string(), PluginState::Unloaded);
+        self.rate_limits.write().remove(plugin_id);
+        self.circuit_breakers.write().remove(plugin_id);
 
         Ok(())
     }
 
     /// Get a plugin by ID
+    #[allow(clippy::uninlined_format_args)]
     pub async fn get(&self, plugin_id: &str) -> Result<String> {
         let plugins = self.plugins.read().await;
 
@@ -309,6 +541,7 @@ impl PluginRegistry {
     }
 
     /// Initialize a plugin
+    #[allow(clippy::uninlined_format_args)]
     pub async fn initialize(&self, plugin_id: &str) -> Result<()> {
         let mut plugins = self.plugins.write().await;
         let mut states = self.states.write().await;
@@ -326,6 +559,7 @@ impl PluginRegistry {
     }
 
     /// Start a plugin
+    #[allow(clippy::uninlined_format_args)]
     pub async fn start(&self, plugin_id: &str) -> Result<()> {
         let mut plugins = self.plugins.write().await;
         let mut states = self.states.write().await;
@@ -343,6 +577,7 @@ impl PluginRegistry {
     }
 
     /// Stop a plugin
+    #[allow(clippy::uninlined_format_args)]
     pub async fn stop(&self, plugin_id: &str) -> Result<()> {
         let mut plugins = self.plugins.write().await;
         let mut states = self.states.write().await;
@@ -360,6 +595,12 @@ impl PluginRegistry {
     }
 
     /// Execute a plugin
+    ///
+    /// Fails with a `Timeout` error, without running the plugin, if its
+    /// rate limit is exhausted. The error's duration is the time until the
+    /// limit allows another execution. Fails with an `InvalidState` error if
+    /// the plugin's circuit breaker is open.
+    #[allow(clippy::uninlined_format_args)]
     pub async fn execute(&self, plugin_id: &str, input: PluginInput) -> Result<PluginOutput> {
         let mut plugins = self.plugins.write().await;
 
@@ -369,7 +610,186 @@ impl PluginRegistry {
             value: Some(plugin_id.to_string()),
         })?;
 
-        plugin.execute(input).await
+        let breaker = self.circuit_breakers.read().get(plugin_id).cloned();
+        if let Some(wait) = breaker.as_ref().and_then(|breaker| breaker.time_until_half_open()) {
+            return Err(SystemError::InvalidState {
+                message: format!(
+                    "circuit breaker of plugin '{plugin_id}' is open, half-open in {}ms",
+                    wait.as_millis()
+                ),
+                current_state: Some("open".into()),
+                expected_state: Some("closed".into()),
+            });
+        }
+
+        let limit = self.rate_limits.read().get(plugin_id).cloned();
+        if let Some(limit) = limit {
+            if !limit.try_acquire(1) {
+                let retry_ms = limit.time_until_available(1).map_or(u64::MAX, |wait| {
+                    u64::try_from(wait.as_millis()).unwrap_or(u64::MAX)
+                });
+                return Err(SystemError::timeout("plugin_rate_limit", retry_ms));
+            }
+        }
+
+        let result = plugin.execute(input).await;
+        drop(plugins);
+        if let Some(breaker) = breaker {
+            let failed = result.as_ref().map_or(true, |output| !output.success);
+            if !failed {
+                breaker.record_success();
+            } else if breaker.record_failure() {
+                tracing::warn!(
+                    "Circuit breaker of plugin {} opened after {} consecutive failures",
+                    plugin_id,
+                    breaker.consecutive_failures()
+                );
+            }
+        }
+        let output = result?;
+
+        // Snapshot so interceptors can add or remove interceptors themselves
+        let interceptors = self.interceptors.read().clone();
+        Ok(interceptors
+            .iter()
+            .fold(output, |output, interceptor| interceptor.intercept(self, plugin_id, output)))
+    }
+
+    /// Execute a plugin only if `guard` accepts its current state
+    ///
+    /// Returns `None`, without running the plugin, if the guard rejects the
+    /// state, e.g. to skip optional plugins while they are `Paused` rather
+    /// than fail. Unknown plugins are executed, and fail as with
+    /// [`execute`](Self::execute).
+    pub async fn execute_conditional<P>(
+        &self,
+        plugin_id: &str,
+        input: PluginInput,
+        guard: P,
+    ) -> Option<Result<PluginOutput>>
+    where
+        P: Fn(&PluginState) -> bool,
+    {
+        if let Some(state) = self.get_state(plugin_id).await {
+            if !guard(&state) {
+                tracing::debug!("Skipping plugin {} in state {:?}", plugin_id, state);
+                return None;
+            }
+        }
+        Some(self.execute(plugin_id, input).await)
+    }
+
+    /// Run `interceptor` on the output of every successful execution
+    ///
+    /// The interceptor stays installed until the returned guard is dropped.
+    pub fn add_interceptor(&self, interceptor: Arc<dyn OutputInterceptor>) -> InterceptorGuard {
+        self.interceptors.write().push(Arc::clone(&interceptor));
+        InterceptorGuard {
+            interceptor,
+            interceptors: Arc::clone(&self.interceptors),
+        }
+    }
+
+    /// Throttle executions of a plugin with `limit`, replacing any
+    /// previous limit
+    ///
+    /// Plugins have no rate limit by default. The limit is removed when the
+    /// plugin is unregistered.
+    pub async fn set_rate_limit(&self, plugin_id: &str, limit: RateLimiter) -> Result<()> {
+        if !self.plugins.read().await.contains_key(plugin_id) {
+            return Err(SystemError::not_found("plugin", plugin_id));
+        }
+        self.rate_limits
+            .write()
+            .insert(plugin_id.to_string(), Arc::new(limit));
+        Ok(())
+    }
+
+    /// Stop executing a plugin after repeated failures, as `config`
+    /// describes, replacing any previous breaker
+    ///
+    /// An execution fails if it returns an error or an unsuccessful output.
+    /// While the breaker is open, [`execute`](Self::execute) refuses to run
+    /// the plugin. The breaker is removed when the plugin is unregistered.
+    pub async fn enable_circuit_breaker(
+        &self,
+        plugin_id: &str,
+        config: CircuitBreakerConfig,
+    ) -> Result<()> {
+        if !self.plugins.read().await.contains_key(plugin_id) {
+            return Err(SystemError::not_found("plugin", plugin_id));
+        }
+        let breaker = CircuitBreaker::new(config)?;
+        self.circuit_breakers
+            .write()
+            .insert(plugin_id.to_string(), Arc::new(breaker));
+        Ok(())
+    }
+
+    /// State of a plugin's circuit breaker, if it has one
+    #[must_use]
+    pub fn circuit_breaker_state(&self, plugin_id: &str) -> Option<CircuitState> {
+        self.circuit_breakers.read().get(plugin_id).map(|breaker| breaker.state())
+    }
+
+    /// Statistics of a plugin's rate limit, if it has one
+    #[must_use]
+    pub fn rate_limit_stats(&self, plugin_id: &str) -> Option<RateLimiterStats> {
+        self.rate_limits.read().get(plugin_id).map(|limit| limit.stats())
+    }
+
+    /// Execute a plugin, giving up once `timeout` has passed
+    ///
+    /// A timeout set through [`PluginRegistry::override_timeout`] takes
+    /// precedence over `timeout`. On expiry the execution is cancelled and a
+    /// plugin recorded as `Active` is reset to `Ready` so it does not stay
+    /// stuck mid-execution.
+    pub async fn execute_with_timeout(
+        &self,
+        plugin_id: &str,
+        input: PluginInput,
+        timeout: Duration,
+    ) -> Result<PluginOutput> {
+        let timeout = self.timeout_override(plugin_id).unwrap_or(timeout);
+        let started = Instant::now();
+
+        if let Ok(result) = tokio::time::timeout(timeout, self.execute(plugin_id, input)).await {
+            return result;
+        }
+
+        let mut states = self.states.write().await;
+        if states.get(plugin_id) == Some(&PluginState::Active) {
+            states.insert(plugin_id.to_string(), PluginState::Ready);
+        }
+
+        let elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        Err(SystemError::timeout("plugin_execution", elapsed_ms))
+    }
+
+    /// Force `execute_with_timeout` to use `timeout` for a plugin
+    ///
+    /// The override lasts until the returned guard is dropped. Used by fault
+    /// injection to simulate slow plugins.
+    pub fn override_timeout(
+        &self,
+        plugin_id: impl Into<String>,
+        timeout: Duration,
+    ) -> TimeoutOverride {
+        let plugin_id = plugin_id.into();
+        self.timeout_overrides
+            .write()
+            .insert(plugin_id.clone(), timeout);
+
+        TimeoutOverride {
+            plugin_id,
+            overrides: Arc::clone(&self.timeout_overrides),
+        }
+    }
+
+    /// Get the timeout override for a plugin, if any
+    #[must_use]
+    pub fn timeout_override(&self, plugin_id: &str) -> Option<Duration> {
+        self.timeout_overrides.read().get(plugin_id).copied()
     }
 
     /// List all registered plugins
@@ -378,6 +798,50 @@ impl PluginRegistry {
         plugins.values().map(|p| p.metadata().clone()).collect()
     }
 
+    /// GraphQL schema exposing every registered plugin
+    ///
+    /// `Query` has one field `execute{PluginId}(input: {PluginId}Input): {PluginId}Output`
+    /// per plugin, with the identifier in `PascalCase`, and the argument and result types
+    /// are generated from [`Plugin::input_schema`] and [`Plugin::output_schema`]. String,
+    /// integer, number and boolean schemas map to the built-in scalars, arrays to lists
+    /// and objects with properties to object types named after their path; anything else
+    /// is a `JSON` scalar. Required properties are non-null.
+    ///
+    /// Identifiers and properties that map to a name already taken, such as `my-plugin`
+    /// after `my_plugin`, get the first free numeric suffix, `MyPlugin2`. With no plugins
+    /// registered, `Query` has a single `_empty: Boolean` field, as an object type needs
+    /// at least one.
+    pub async fn to_graphql_schema(&self) -> String {
+        let plugins = self.plugins.read().await;
+        let mut ids: Vec<&String> = plugins.keys().collect();
+        ids.sort();
+
+        let mut types = GraphqlTypes::new();
+        let mut plugin_names = HashSet::new();
+        let mut fields = String::new();
+        for id in ids {
+            let plugin = &plugins[id];
+            let name = unique_name(&mut plugin_names, graphql_type_name(id));
+            let input = types.root(&format!("{name}Input"), &plugin.input_schema(), true);
+            let output = types.root(&format!("{name}Output"), &plugin.output_schema(), false);
+            let _ = writeln!(fields, "  execute{name}(input: {input}): {output}");
+        }
+
+        let mut schema = String::new();
+        if types.uses_json {
+            schema.push_str("scalar JSON\n\n");
+        }
+        for definition in &types.definitions {
+            schema.push_str(definition);
+            schema.push('\n');
+        }
+        if fields.is_empty() {
+            fields.push_str("  _empty: Boolean\n");
+        }
+        let _ = writeln!(schema, "type Query {{\n{fields}}}");
+        schema
+    }
+
     /// Get plugin state
     pub async fn get_state(&self, plugin_id: &str) -> Option<PluginState> {
         let states = self.states.read().await;
@@ -396,6 +860,429 @@ impl PluginRegistry {
 
         results
     }
+
+    /// Capture the metadata and lifecycle state of every registered plugin
+    pub async fn serialize_state(&self) -> Result<RegistrySnapshot> {
+        let plugins = self.plugins.read().await;
+        let states = self.states.read().await;
+
+        let mut entries: Vec<(PluginMetadata, PluginState)> = plugins
+            .iter()
+            .map(|(id, plugin)| {
+                let state = states.get(id).copied().unwrap_or(PluginState::Loaded);
+                (plugin.metadata().clone(), state)
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+
+        Ok(RegistrySnapshot { plugins: entries })
+    }
+
+    /// Re-register plugins from a snapshot and bring them back to their
+    /// recorded lifecycle state
+    ///
+    /// `plugin_factory` constructs a fresh plugin instance for each entry.
+    /// Plugins recorded as `Ready` are initialized, `Active` plugins are
+    /// initialized and started, and `Paused` plugins are started then paused.
+    pub async fn restore_state(
+        &self,
+        snapshot: RegistrySnapshot,
+        plugin_factory: impl Fn(&PluginMetadata) -> Result<Box<dyn Plugin>>,
+    ) -> Result<()> {
+        for (metadata, state) in snapshot.plugins {
+            let plugin = plugin_factory(&metadata)?;
+            self.register(plugin).await?;
+
+            match state {
+                PluginState::Loaded | PluginState::Unloaded => {},
+                PluginState::Ready => self.initialize(&metadata.id).await?,
+                PluginState::Active => {
+                    self.initialize(&metadata.id).await?;
+                    self.start(&metadata.id).await?;
+                },
+                PluginState::Paused => {
+                    self.initialize(&metadata.id).await?;
+                    self.start(&metadata.id).await?;
+
+                    let mut plugins = self.plugins.write().await;
+                    let mut states = self.states.write().await;
+                    if let Some(plugin) = plugins.get_mut(&metadata.id) {
+                        plugin.pause().await?;
+                    }
+                    states.insert(metadata.id.clone(), PluginState::Paused);
+                },
+                PluginState::Error => {
+                    self.states
+                        .write()
+                        .await
+                        .insert(metadata.id.clone(), PluginState::Error);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Register a plugin for every `.so`, `.dll` and `.dylib` file in `dir`
+    ///
+    /// `plugin_factory` builds the plugin for an artifact path. Artifacts
+    /// whose factory or registration fails are logged and skipped. Returns
+    /// the IDs of the registered plugins, in file name order.
+    pub async fn load_from_directory(
+        &self,
+        dir: &Path,
+        plugin_factory: impl Fn(&Path) -> Result<Box<dyn Plugin>>,
+    ) -> Result<Vec<String>> {
+        let context = || format!("reading plugin directory {}", dir.display());
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(|e| SystemError::io(e, context()))? {
+            let path = entry.map_err(|e| SystemError::io(e, context()))?.path();
+            if is_plugin_artifact(&path) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut loaded = Vec::new();
+        for path in paths {
+            if let Some(id) = self.load_artifact(&path, &plugin_factory).await {
+                loaded.push(id);
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Register plugins for artifacts added to `dir` from now on
+    ///
+    /// Artifacts are tried when they are created, finish being written or
+    /// are moved into `dir`, until one attempt registers them; failures are
+    /// logged as in [`PluginRegistry::load_from_directory`]. Needs a Tokio
+    /// runtime. Watching stops when the returned guard is dropped.
+    pub fn watch_directory<F>(&self, dir: &Path, plugin_factory: F) -> Result<DirectoryWatch>
+    where
+        F: Fn(&Path) -> Result<Box<dyn Plugin>> + Send + Sync + 'static,
+    {
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+            SystemError::InvalidState {
+                message: "watching a plugin directory needs a Tokio runtime".to_string(),
+                current_state: None,
+                expected_state: Some("inside a Tokio runtime".to_string()),
+            }
+        })?;
+
+        let (paths_tx, mut paths_rx) = tokio::sync::mpsc::unbounded_channel();
+        let context = format!("watching plugin directory {}", dir.display());
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                Ok(event) if adds_file(event.kind) => {
+                    for path in event.paths {
+                        // The receiver only goes away when the watch is dropped
+                        let _ = paths_tx.send(path);
+                    }
+                },
+                Ok(_) => {},
+                Err(err) => tracing::warn!("Plugin directory watch failed: {}", err),
+            }
+        })
+        .map_err(|e| SystemError::io(e, context.clone()))?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| SystemError::io(e, context))?;
+
+        let registry = self.clone();
+        let task = runtime.spawn(async move {
+            let mut loaded = HashSet::new();
+            while let Some(path) = paths_rx.recv().await {
+                if loaded.contains(&path) || !is_plugin_artifact(&path) {
+                    continue;
+                }
+                if registry.load_artifact(&path, &plugin_factory).await.is_some() {
+                    loaded.insert(path);
+                }
+            }
+        });
+
+        Ok(DirectoryWatch {
+            _watcher: watcher,
+            task,
+        })
+    }
+
+    /// Build and register the plugin for one artifact, logging failures
+    async fn load_artifact(
+        &self,
+        path: &Path,
+        plugin_factory: &impl Fn(&Path) -> Result<Box<dyn Plugin>>,
+    ) -> Option<String> {
+        let plugin = match plugin_factory(path) {
+            Ok(plugin) => plugin,
+            Err(err) => {
+                tracing::warn!("Skipping plugin artifact {}: {}", path.display(), err);
+                return None;
+            },
+        };
+        let id = plugin.metadata().id.clone();
+        if let Err(err) = self.register(plugin).await {
+            tracing::warn!("Skipping plugin artifact {}: {}", path.display(), err);
+            return None;
+        }
+
+        tracing::info!("Loaded plugin {} from {}", id, path.display());
+        Some(id)
+    }
+}
+
+fn is_plugin_artifact(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| PLUGIN_EXTENSIONS.contains(&ext))
+}
+
+/// GraphQL type definitions generated from plugin JSON Schemas
+struct GraphqlTypes {
+    definitions: Vec<String>,
+    names: HashSet<String>,
+    uses_json: bool,
+}
+
+impl GraphqlTypes {
+    fn new() -> Self {
+        Self {
+            definitions: Vec::new(),
+            names: GRAPHQL_RESERVED_TYPES.into_iter().map(String::from).collect(),
+            uses_json: false,
+        }
+    }
+
+    /// Define the type `name` for a plugin's input or output schema, as an object type
+    /// or, when the schema has no properties, a scalar
+    fn root(&mut self, name: &str, schema: &serde_json::Value, input: bool) -> String {
+        if has_properties(schema) {
+            self.object(name, schema, input)
+        } else {
+            let name = self.define(name);
+            self.definitions.push(format!("scalar {name}\n"));
+            name
+        }
+    }
+
+    /// Claim a type name, suffixed if `name` or a built-in type already has it
+    fn define(&mut self, name: &str) -> String {
+        unique_name(&mut self.names, name.to_string())
+    }
+
+    /// GraphQL type of `schema`, defining the object types it needs with names
+    /// starting with `name`
+    fn type_of(&mut self, name: &str, schema: &serde_json::Value, input: bool) -> String {
+        match schema.get("type").and_then(serde_json::Value::as_str) {
+            Some("string") => "String".to_string(),
+            Some("integer") => "Int".to_string(),
+            Some("number") => "Float".to_string(),
+            Some("boolean") => "Boolean".to_string(),
+            Some("array") => {
+                let items = schema.get("items").unwrap_or(&serde_json::Value::Null);
+                format!("[{}]", self.type_of(&format!("{name}Item"), items, input))
+            },
+            Some("object") if has_properties(schema) => self.object(name, schema, input),
+            _ => {
+                self.uses_json = true;
+                "JSON".to_string()
+            },
+        }
+    }
+
+    /// Define an input or output object type `name` with a field per property
+    ///
+    /// Properties that already are valid field names keep them; the others get the
+    /// first free suffix if their name is taken.
+    fn object(&mut self, name: &str, schema: &serde_json::Value, input: bool) -> String {
+        let name = self.define(name);
+        let required: HashSet<&str> = schema
+            .get("required")
+            .and_then(serde_json::Value::as_array)
+            .map(|required| required.iter().filter_map(serde_json::Value::as_str).collect())
+            .unwrap_or_default();
+        let empty = serde_json::Map::new();
+        let properties = schema
+            .get("properties")
+            .and_then(serde_json::Value::as_object)
+            .unwrap_or(&empty);
+
+        let mut field_names: HashSet<String> = properties
+            .keys()
+            .filter(|property| graphql_field_name(property) == **property)
+            .cloned()
+            .collect();
+        let mut fields = String::new();
+        for (property, property_schema) in properties {
+            let field_type = self.type_of(
+                &format!("{name}{}", graphql_type_name(property)),
+                property_schema,
+                input,
+            );
+            let non_null = if required.contains(property.as_str()) { "!" } else { "" };
+            let field = graphql_field_name(property);
+            let field = if field == *property {
+                field
+            } else {
+                unique_name(&mut field_names, field)
+            };
+            let _ = writeln!(fields, "  {field}: {field_type}{non_null}");
+        }
+        let keyword = if input { "input" } else { "type" };
+        self.definitions.push(format!("{keyword} {name} {{\n{fields}}}\n"));
+        name
+    }
+}
+
+/// Type names the generated schema defines or uses besides the plugins' own
+const GRAPHQL_RESERVED_TYPES: [&str; 7] =
+    ["Query", "JSON", "String", "Int", "Float", "Boolean", "ID"];
+
+/// `name`, or `name` with the first numeric suffix from 2 that is not in `taken`,
+/// which it is added to
+fn unique_name(taken: &mut HashSet<String>, name: String) -> String {
+    let name = if taken.contains(&name) {
+        // Of these `taken.len() + 1` candidates at least one is free
+        (2..=taken.len() + 2)
+            .map(|n| format!("{name}{n}"))
+            .find(|candidate| !taken.contains(candidate))
+            .unwrap_or(name)
+    } else {
+        name
+    };
+    taken.insert(name.clone());
+    name
+}
+
+/// Whether an object schema declares any property
+fn has_properties(schema: &serde_json::Value) -> bool {
+    schema
+        .get("properties")
+        .and_then(serde_json::Value::as_object)
+        .is_some_and(|properties| !properties.is_empty())
+}
+
+/// `PascalCase` GraphQL type name for an identifier such as `my-plugin`
+fn graphql_type_name(id: &str) -> String {
+    let mut name: String = id
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// GraphQL field name for a JSON property, with invalid characters replaced
+fn graphql_field_name(property: &str) -> String {
+    let mut name: String = property
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Whether a watch event may have put a new file in place
+fn adds_file(kind: EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both))
+    )
+}
+
+/// Guard returned by [`PluginRegistry::watch_directory`]
+///
+/// Dropping it stops watching the directory.
+#[must_use = "the directory is no longer watched once the guard is dropped"]
+pub struct DirectoryWatch {
+    _watcher: notify::RecommendedWatcher,
+    task: JoinHandle<()>,
+}
+
+impl Drop for DirectoryWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Guard returned by [`PluginRegistry::override_timeout`]
+///
+/// Dropping it removes the override.
+#[must_use = "the override is removed when the guard is dropped"]
+pub struct TimeoutOverride {
+    plugin_id: String,
+    overrides: Arc<parking_lot::RwLock<HashMap<String, Duration>>>,
+}
+
+impl TimeoutOverride {
+    /// Plugin whose timeout is overridden
+    #[must_use]
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+}
+
+impl Drop for TimeoutOverride {
+    fn drop(&mut self) {
+        self.overrides.write().remove(&self.plugin_id);
+    }
+}
+
+/// Guard returned by [`PluginRegistry::add_interceptor`]
+///
+/// Dropping it removes the interceptor.
+#[must_use = "the interceptor is removed when the guard is dropped"]
+pub struct InterceptorGuard {
+    interceptor: Arc<dyn OutputInterceptor>,
+    interceptors: Interceptors,
+}
+
+impl Drop for InterceptorGuard {
+    fn drop(&mut self) {
+        let mut interceptors = self.interceptors.write();
+        // Compare data pointers only; vtable pointers are not unique
+        let installed = Arc::as_ptr(&self.interceptor).cast::<()>();
+        if let Some(index) = interceptors
+            .iter()
+            .position(|interceptor| Arc::as_ptr(interceptor).cast::<()>() == installed)
+        {
+            interceptors.remove(index);
+        }
+    }
+}
+
+/// Serializable snapshot of a plugin registry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    /// Registered plugins with their lifecycle state, ordered by plugin ID
+    pub plugins: Vec<(PluginMetadata, PluginState)>,
+}
+
+impl RegistrySnapshot {
+    /// Serialize the snapshot to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Deserialize a snapshot from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
 }
 
 impl Default for PluginRegistry {
@@ -409,6 +1296,11 @@ impl Clone for PluginRegistry {
         Self {
             plugins: Arc::clone(&self.plugins),
             states: Arc::clone(&self.states),
+            timeout_overrides: Arc::clone(&self.timeout_overrides),
+            rate_limits: Arc::clone(&self.rate_limits),
+            circuit_breakers: Arc::clone(&self.circuit_breakers),
+            interceptors: Arc::clone(&self.interceptors),
+            system_version: self.system_version.clone(),
         }
     }
 }
@@ -420,17 +1312,38 @@ mod tests {
     struct TestPlugin {
         metadata: PluginMetadata,
         state: PluginState,
+        schemas: Option<(serde_json::Value, serde_json::Value)>,
     }
 
     impl TestPlugin {
         fn new() -> Self {
+            Self::with_id("test-plugin")
+        }
+
+        fn with_id(id: &str) -> Self {
             Self {
-                metadata: PluginMetadata::new("test-plugin", "Test Plugin", "1.0.0")
+                metadata: PluginMetadata::new(id, "Test Plugin", "1.0.0")
                     .with_capability("testing")
                     .with_description("A test plugin"),
                 state: PluginState::Loaded,
+                schemas: None,
             }
         }
+
+        fn with_schemas(mut self, input: serde_json::Value, output: serde_json::Value) -> Self {
+            self.schemas = Some((input, output));
+            self
+        }
+    }
+
+    /// Builds a plugin named after the artifact's file stem, failing for
+    /// `broken.*`
+    fn artifact_factory(path: &Path) -> Result<Box<dyn Plugin>> {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        if stem == "broken" {
+            return Err(SystemError::validation("artifact", "corrupt", Some(stem.to_string())));
+        }
+        Ok(Box::new(TestPlugin::with_id(stem)))
     }
 
     #[async_trait]
@@ -454,7 +1367,13 @@ mod tests {
             Ok(())
         }
 
-        async fn execute(&mut self, _input: PluginInput) -> Result<PluginOutput> {
+        async fn execute(&mut self, input: PluginInput) -> Result<PluginOutput> {
+            if input.get_data("fail").is_some() {
+                return Err(SystemError::internal("requested failure", None));
+            }
+            if let Some(sleep_ms) = input.get_data("sleep_ms").and_then(serde_json::Value::as_u64) {
+                tokio::time::sleep(Duration::from_millis(sleep_ms)).await;
+            }
             Ok(PluginOutput::success().with_data("result", serde_json::json!("test")))
         }
 
@@ -462,6 +1381,20 @@ mod tests {
             self.state
         }
 
+        fn input_schema(&self) -> serde_json::Value {
+            match &self.schemas {
+                Some((input, _)) => input.clone(),
+                None => PluginInput::json_schema(),
+            }
+        }
+
+        fn output_schema(&self) -> serde_json::Value {
+            match &self.schemas {
+                Some((_, output)) => output.clone(),
+                None => PluginOutput::json_schema(),
+            }
+        }
+
         fn as_any(&self) -> &dyn Any {
             self
         }
@@ -486,6 +1419,58 @@ mod tests {
         assert_eq!(metadata.author, "Test Author");
     }
 
+    #[test]
+    fn test_merge_inputs() {
+        let http = PluginInput::new()
+            .with_data("user", serde_json::json!("alice"))
+            .with_data("amount", serde_json::json!(5))
+            .with_context("trace", "http");
+        let event = PluginInput::new()
+            .with_data("amount", serde_json::json!(7))
+            .with_data("topic", serde_json::json!("payments"))
+            .with_context("trace", "bus");
+
+        let merged = PluginInput::merge_two(http.clone(), event.clone());
+        assert_eq!(merged.data.len(), 3);
+        assert_eq!(merged.get_data("amount"), Some(&serde_json::json!(7)));
+        assert_eq!(merged.get_context("trace").map(String::as_str), Some("bus"));
+        assert_eq!(PluginInput::merge([]).data.len(), 0);
+
+        let inputs = || [http.clone(), event.clone()];
+        let first = PluginInput::merge_with(inputs(), MergeStrategy::FirstWins).unwrap();
+        assert_eq!(first.get_data("amount"), Some(&serde_json::json!(5)));
+        assert_eq!(first.get_context("trace").map(String::as_str), Some("http"));
+        assert_eq!(first.get_data("topic"), Some(&serde_json::json!("payments")));
+
+        let err = PluginInput::merge_with(inputs(), MergeStrategy::ErrorOnConflict).unwrap_err();
+        assert!(matches!(err, SystemError::Validation { ref field, .. } if field == "data"));
+        // Equal values do not conflict
+        let strict =
+            PluginInput::merge_with([http.clone(), http], MergeStrategy::ErrorOnConflict).unwrap();
+        assert_eq!(strict.data.len(), 2);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_last_wins_commutes_on_disjoint_keys(
+            a in proptest::collection::hash_map("[a-z]{1,4}", 0..100i64, 0..8),
+            b in proptest::collection::hash_map("[a-z]{1,4}", 0..100i64, 0..8),
+        ) {
+            let input = |prefix: &str, values: &HashMap<String, i64>| {
+                values.iter().fold(PluginInput::new(), |input, (key, value)| {
+                    input
+                        .with_data(format!("{prefix}{key}"), serde_json::json!(value))
+                        .with_context(format!("{prefix}{key}"), value.to_string())
+                })
+            };
+            let (a, b) = (input("a.", &a), input("b.", &b));
+            let ab = PluginInput::merge_two(a.clone(), b.clone());
+            let ba = PluginInput::merge_two(b, a);
+            proptest::prop_assert_eq!(ab.data, ba.data);
+            proptest::prop_assert_eq!(ab.context, ba.context);
+        }
+    }
+
     #[tokio::test]
     async fn test_plugin_input_output() {
         let input = PluginInput::new()
@@ -538,6 +1523,153 @@ mod tests {
         assert_eq!(registry.get_state("test-plugin").await, Some(PluginState::Unloaded));
     }
 
+    #[tokio::test]
+    async fn test_execute_with_timeout() {
+        let registry = PluginRegistry::new();
+        registry.register(Box::new(TestPlugin::new())).await.unwrap();
+        registry.initialize("test-plugin").await.unwrap();
+        registry.start("test-plugin").await.unwrap();
+
+        let slow = || PluginInput::new().with_data("sleep_ms", serde_json::json!(200));
+        let limit = Duration::from_millis(20);
+
+        let result = registry.execute_with_timeout("test-plugin", slow(), limit).await;
+        assert!(matches!(
+            result,
+            Err(SystemError::Timeout { ref operation, duration_ms })
+                if operation == "plugin_execution" && duration_ms >= 20
+        ));
+        assert_eq!(registry.get_state("test-plugin").await, Some(PluginState::Ready));
+
+        // The registry lock is released, so the plugin can run again
+        let fast = registry
+            .execute_with_timeout("test-plugin", PluginInput::new(), limit)
+            .await
+            .unwrap();
+        assert!(fast.success);
+
+        assert!(registry
+            .execute_with_timeout("missing", PluginInput::new(), limit)
+            .await
+            .is_err());
+    }
+
+    struct Tag(&'static str);
+
+    impl OutputInterceptor for Tag {
+        fn intercept(
+            &self,
+            _registry: &PluginRegistry,
+            plugin_id: &str,
+            output: PluginOutput,
+        ) -> PluginOutput {
+            let tags = output.data.get("tags").and_then(serde_json::Value::as_str);
+            let tags = format!("{}+{}", tags.unwrap_or(plugin_id), self.0);
+            output.with_data("tags", serde_json::json!(tags))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_interceptors() {
+        let registry = PluginRegistry::new();
+        registry.register(Box::new(TestPlugin::new())).await.unwrap();
+        let tags = || async {
+            let output = registry.execute("test-plugin", PluginInput::new()).await.unwrap();
+            assert_eq!(output.data["result"], "test");
+            output.data.get("tags").cloned()
+        };
+
+        let first = registry.add_interceptor(Arc::new(Tag("a")));
+        let second = registry.add_interceptor(Arc::new(Tag("b")));
+        assert_eq!(tags().await, Some(serde_json::json!("test-plugin+a+b")));
+
+        drop(second);
+        assert_eq!(tags().await, Some(serde_json::json!("test-plugin+a")));
+        drop(first);
+        assert_eq!(tags().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_override() {
+        let registry = PluginRegistry::new();
+        registry.register(Box::new(TestPlugin::new())).await.unwrap();
+
+        let input = || PluginInput::new().with_data("sleep_ms", serde_json::json!(50));
+        let generous = Duration::from_secs(5);
+
+        let guard = registry.override_timeout("test-plugin", Duration::from_millis(5));
+        assert_eq!(guard.plugin_id(), "test-plugin");
+        assert_eq!(registry.timeout_override("test-plugin"), Some(Duration::from_millis(5)));
+        assert!(registry
+            .execute_with_timeout("test-plugin", input(), generous)
+            .await
+            .is_err());
+
+        drop(guard);
+        assert_eq!(registry.timeout_override("test-plugin"), None);
+        assert!(registry
+            .execute_with_timeout("test-plugin", input(), generous)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let registry = PluginRegistry::new();
+        registry.register(Box::new(TestPlugin::new())).await.unwrap();
+        assert_eq!(registry.rate_limit_stats("test-plugin"), None);
+        let limit = || RateLimiter::new(2, 0.0).unwrap();
+        assert!(registry.set_rate_limit("missing", limit()).await.is_err());
+        registry.set_rate_limit("test-plugin", limit()).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(registry.execute("test-plugin", PluginInput::new()).await.is_ok());
+        }
+        let throttled = registry.execute("test-plugin", PluginInput::new()).await;
+        assert!(matches!(
+            throttled,
+            Err(SystemError::Timeout { ref operation, duration_ms: u64::MAX })
+                if operation == "plugin_rate_limit"
+        ));
+        let stats = registry.rate_limit_stats("test-plugin").unwrap();
+        assert_eq!(stats.tokens_available, 0.0);
+        assert_eq!(stats.total_throttled, 1);
+
+        registry.unregister("test-plugin").await.unwrap();
+        assert_eq!(registry.rate_limit_stats("test-plugin"), None);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let registry = PluginRegistry::new();
+        registry.register(Box::new(TestPlugin::new())).await.unwrap();
+        let config = CircuitBreakerConfig {
+            failure_threshold: 2,
+            open_duration: Duration::from_millis(50),
+        };
+        assert!(registry.enable_circuit_breaker("missing", config).await.is_err());
+        registry.enable_circuit_breaker("test-plugin", config).await.unwrap();
+        assert_eq!(registry.circuit_breaker_state("test-plugin"), Some(CircuitState::Closed));
+
+        let failing = || PluginInput::new().with_data("fail", serde_json::json!(true));
+        for _ in 0..2 {
+            let err = registry.execute("test-plugin", failing()).await.unwrap_err();
+            assert!(matches!(err, SystemError::Internal { .. }));
+        }
+        assert_eq!(registry.circuit_breaker_state("test-plugin"), Some(CircuitState::Open));
+        let refused = registry.execute("test-plugin", PluginInput::new()).await.unwrap_err();
+        assert!(matches!(refused, SystemError::InvalidState { ref message, .. }
+            if message.contains("test-plugin") && message.contains("half-open in")));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(registry.circuit_breaker_state("test-plugin"), Some(CircuitState::HalfOpen));
+        assert!(registry.execute("test-plugin", PluginInput::new()).await.is_ok());
+        assert_eq!(registry.circuit_breaker_state("test-plugin"), Some(CircuitState::Closed));
+
+        registry.unregister("test-plugin").await.unwrap();
+        assert_eq!(registry.circuit_breaker_state("test-plugin"), None);
+    }
+
     #[tokio::test]
     async fn test_plugin_list() {
         let registry = PluginRegistry::new();
@@ -550,6 +1682,101 @@ mod tests {
         assert_eq!(list[0].id, "test-plugin");
     }
 
+    #[tokio::test]
+    async fn test_serialize_and_restore_state() {
+        let registry = PluginRegistry::new();
+        registry.register(Box::new(TestPlugin::new())).await.unwrap();
+        registry.initialize("test-plugin").await.unwrap();
+        registry.start("test-plugin").await.unwrap();
+
+        let snapshot = registry.serialize_state().await.unwrap();
+        let json = snapshot.to_json().unwrap();
+        let snapshot = RegistrySnapshot::from_json(&json).unwrap();
+        assert_eq!(snapshot.plugins.len(), 1);
+        assert_eq!(snapshot.plugins[0].1, PluginState::Active);
+
+        let restored = PluginRegistry::new();
+        restored
+            .restore_state(snapshot, |metadata| {
+                assert_eq!(metadata.id, "test-plugin");
+                Ok(Box::new(TestPlugin::new()))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(restored.get_state("test-plugin").await, Some(PluginState::Active));
+        let output = restored.execute("test-plugin", PluginInput::new()).await.unwrap();
+        assert!(output.success);
+    }
+
+    #[tokio::test]
+    async fn test_execute_conditional_skips_rejected_states() {
+        let snapshot = RegistrySnapshot {
+            plugins: vec![
+                (PluginMetadata::new("active", "Active", "1.0.0"), PluginState::Active),
+                (PluginMetadata::new("paused", "Paused", "1.0.0"), PluginState::Paused),
+            ],
+        };
+        let registry = PluginRegistry::new();
+        registry
+            .restore_state(snapshot, |metadata| Ok(Box::new(TestPlugin::with_id(&metadata.id))))
+            .await
+            .unwrap();
+        let active = |state: &PluginState| *state == PluginState::Active;
+
+        let output = registry.execute_conditional("active", PluginInput::new(), active).await;
+        assert!(output.unwrap().unwrap().success);
+        let skipped = registry.execute_conditional("paused", PluginInput::new(), active).await;
+        assert!(skipped.is_none());
+        let unknown = registry.execute_conditional("ghost", PluginInput::new(), active).await;
+        assert!(unknown.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restore_state_propagates_factory_error() {
+        let snapshot = RegistrySnapshot {
+            plugins: vec![(
+                PluginMetadata::new("missing", "Missing", "1.0.0"),
+                PluginState::Ready,
+            )],
+        };
+
+        let registry = PluginRegistry::new();
+        let result = registry
+            .restore_state(snapshot, |metadata| {
+                Err(SystemError::not_found("plugin", metadata.id.clone()))
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(registry.list().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_system_version_range() {
+        let registry = PluginRegistry::new().with_system_version(Version::new(1, 4, 0));
+        let plugin = |min: &str, max: Option<&str>| {
+            let mut plugin = TestPlugin::with_id(&format!("{min}-{max:?}"));
+            plugin.metadata.min_system_version = min.to_string();
+            plugin.metadata.max_system_version = max.map(str::to_string);
+            Box::new(plugin)
+        };
+        registry.register(plugin("1.0.0", None)).await.unwrap();
+        registry.register(plugin("1.0.0", Some("1.4.0"))).await.unwrap();
+        for (min, max) in [("1.5.0", None), ("1.0.0", Some("1.3.9")), ("one", None)] {
+            let err = registry.register(plugin(min, max)).await.unwrap_err();
+            assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+        }
+        let err = registry.register(plugin("2.0.0", None)).await.unwrap_err();
+        assert!(err.to_string().contains("requires system version >=2.0.0"), "{err}");
+
+        let metadata = PluginMetadata::new("p", "P", "1.0.0").with_max_system_version("1.9.9");
+        assert_eq!(
+            metadata.system_version_range().unwrap(),
+            VersionRange::parse(">=0.1.0 <=1.9.9").unwrap()
+        );
+        assert_eq!(PluginRegistry::new().system_version(), &Version::new(0, 1, 0));
+    }
+
     #[tokio::test]
     async fn test_duplicate_registration() {
         let registry = PluginRegistry::new();
@@ -560,4 +1787,155 @@ mod tests {
         let result = registry.register(plugin2).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_load_from_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.dll", "a.so", "b.dylib", "broken.so", "notes.txt"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("c.so")).unwrap();
+
+        let registry = PluginRegistry::new();
+        let loaded = registry.load_from_directory(dir.path(), artifact_factory).await.unwrap();
+
+        // `a.so` duplicates the ID of `a.dll` and is skipped like `broken.so`
+        assert_eq!(loaded, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(registry.list().await.len(), 2);
+
+        let missing = dir.path().join("missing");
+        let result = registry.load_from_directory(&missing, artifact_factory).await;
+        assert!(matches!(result, Err(SystemError::Io { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_watch_directory_loads_new_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = PluginRegistry::new();
+        let watch = registry.watch_directory(dir.path(), artifact_factory).unwrap();
+
+        std::fs::write(dir.path().join("broken.so"), b"").unwrap();
+        std::fs::write(dir.path().join("ignored.txt"), b"").unwrap();
+        std::fs::write(dir.path().join("late.so"), b"").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while registry.get_state("late").await.is_none() {
+            assert!(Instant::now() < deadline, "watched artifact was not loaded");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(registry.list().await.len(), 1);
+
+        drop(watch);
+        std::fs::write(dir.path().join("after.so"), b"").unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(registry.get_state("after").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_to_graphql_schema() {
+        let registry = PluginRegistry::new();
+        let empty = registry.to_graphql_schema().await;
+        assert_eq!(empty, "type Query {\n  _empty: Boolean\n}\n");
+        graphql_parser::parse_schema::<String>(&empty).unwrap();
+
+        registry.register(Box::new(TestPlugin::with_id("echo"))).await.unwrap();
+        let input = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "limit": { "type": "integer" },
+                "filter": {
+                    "type": "object",
+                    "properties": { "min-score": { "type": "number" } }
+                }
+            },
+            "required": ["query"]
+        });
+        let output = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "hits": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": { "id": { "type": "string" }, "extra": {} },
+                        "required": ["id"]
+                    }
+                },
+                "exact": { "type": "boolean" }
+            }
+        });
+        let search = TestPlugin::with_id("doc-search").with_schemas(input, output);
+        registry.register(Box::new(search)).await.unwrap();
+
+        let schema = registry.to_graphql_schema().await;
+        let document = graphql_parser::parse_schema::<String>(&schema).unwrap();
+        assert_eq!(document.definitions.len(), 8);
+
+        assert!(schema.contains(
+            "type Query {\n  \
+             executeDocSearch(input: DocSearchInput): DocSearchOutput\n  \
+             executeEcho(input: EchoInput): EchoOutput\n}"
+        ));
+        assert!(schema.contains("input DocSearchInput {\n  filter: DocSearchInputFilter\n"));
+        assert!(schema.contains("  query: String!\n"));
+        assert!(schema.contains("  limit: Int\n"));
+        assert!(schema.contains("input DocSearchInputFilter {\n  min_score: Float\n}"));
+        assert!(schema.contains("  hits: [DocSearchOutputHitsItem]\n"));
+        assert!(schema.contains("type DocSearchOutputHitsItem {\n  extra: JSON\n  id: String!\n}"));
+        assert!(schema.contains("type EchoOutput {\n  data: JSON!\n  error: String\n"));
+        assert!(schema.starts_with("scalar JSON\n"));
+    }
+
+    #[tokio::test]
+    async fn test_graphql_schema_name_collisions() {
+        use graphql_parser::schema::{Definition, TypeDefinition};
+
+        let registry = PluginRegistry::new();
+        let input = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "min-score": { "type": "number" },
+                "min_score": { "type": "integer" },
+                "min_score2": { "type": "boolean" }
+            }
+        });
+        for id in ["my-plugin", "my_plugin", "my-plugin2"] {
+            let plugin = TestPlugin::with_id(id).with_schemas(input.clone(), serde_json::json!({}));
+            registry.register(Box::new(plugin)).await.unwrap();
+        }
+
+        let schema = registry.to_graphql_schema().await;
+        let document = graphql_parser::parse_schema::<String>(&schema).unwrap();
+        let mut names = HashSet::new();
+        for definition in &document.definitions {
+            let Definition::TypeDefinition(definition) = definition else {
+                continue;
+            };
+            let (name, fields) = match definition {
+                TypeDefinition::Object(object) => {
+                    (&object.name, object.fields.iter().map(|f| &f.name).collect())
+                },
+                TypeDefinition::InputObject(input) => {
+                    (&input.name, input.fields.iter().map(|f| &f.name).collect())
+                },
+                TypeDefinition::Scalar(scalar) => (&scalar.name, Vec::new()),
+                _ => continue,
+            };
+            assert!(names.insert(name.clone()), "{name} is defined twice");
+            let unique: HashSet<_> = fields.iter().collect();
+            assert_eq!(unique.len(), fields.len(), "{name} has duplicate fields");
+        }
+
+        assert!(schema.contains(
+            "type Query {\n  \
+             executeMyPlugin(input: MyPluginInput): MyPluginOutput\n  \
+             executeMyPlugin2(input: MyPlugin2Input): MyPlugin2Output\n  \
+             executeMyPlugin3(input: MyPlugin3Input): MyPlugin3Output\n}"
+        ));
+        // Properties with valid names keep them
+        assert!(schema.contains(
+            "input MyPluginInput {\n  min_score3: Float\n  min_score: Int\n  min_score2: Boolean\n}"
+        ));
+    }
 }
@@ -6,12 +6,29 @@
 use crate::{Result, SystemError};
 use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{Semaphore, RwLock};
 use tokio::time::sleep;
 
+/// Values of [`ResourceGovernorConfig::preset_label`] the preset constructors set
+const PRESET_LABELS: [&str; 2] = ["testing", "production"];
+
+/// Gains of the CPU throttle's proportional-integral controller
+const THROTTLE_KP: f64 = 1.0;
+const THROTTLE_KI: f64 = 0.1;
+
+/// Bounds of the sleep the CPU throttle adds to an operation, in ms
+const MIN_THROTTLE_SLEEP_MS: f64 = 1.0;
+const MAX_THROTTLE_SLEEP_MS: f64 = 500.0;
+
+/// `&'static str` under a name `serde` does not borrow, so configs still
+/// deserialize from owned data
+type StaticStr = &'static str;
+
 /// Resource governor configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceGovernorConfig {
@@ -32,6 +49,11 @@ pub struct ResourceGovernorConfig {
 
     /// Maximum concurrent operations
     pub max_concurrent_operations: usize,
+
+    /// Preset the configuration was created from, as in `"testing"` for
+    /// [`testing`](Self::testing); kept if fields are changed afterwards
+    #[serde(default, deserialize_with = "deserialize_preset_label")]
+    pub preset_label: Option<StaticStr>,
 }
 
 impl Default for ResourceGovernorConfig {
@@ -43,12 +65,14 @@ impl Default for ResourceGovernorConfig {
             deterministic_mode: false,
             sandbox_mode: false,
             max_concurrent_operations: 1000,
+            preset_label: None,
         }
     }
 }
 
 impl ResourceGovernorConfig {
     /// Create a configuration for testing with strict limits
+    #[allow(clippy::must_use_candidate)]
     pub fn testing() -> Self {
         Self {
             cpu_cap_percent: Some(50),
@@ -57,10 +81,12 @@ impl ResourceGovernorConfig {
             deterministic_mode: true,
             sandbox_mode: true,
             max_concurrent_operations: 10,
+            preset_label: Some(PRESET_LABELS[0]),
         }
     }
 
     /// Create a configuration for production with moderate limits
+    #[allow(clippy::must_use_candidate)]
     pub fn production() -> Self {
         Self {
             cpu_cap_percent: Some(80),
@@ -69,6 +95,7 @@ impl ResourceGovernorConfig {
             deterministic_mode: false,
             sandbox_mode: false,
             max_concurrent_operations: 1000,
+            preset_label: Some(PRESET_LABELS[1]),
         }
     }
 
@@ -92,6 +119,73 @@ impl ResourceGovernorConfig {
 
         Ok(())
     }
+
+    /// Read the limits of the cgroup v2 directory `path`
+    ///
+    /// `memory.max` sets the RAM cap and `cpu.max` the CPU cap, as the
+    /// quota's share of its period capped at 100%. The I/O rate is the
+    /// lowest `riops` or `wiops` limit in `io.max`. Missing files and `max`
+    /// values leave a limit unset; the other fields keep their defaults.
+    pub fn from_cgroups(path: &Path) -> Result<Self> {
+        let read = |file: &str| -> Result<Option<String>> {
+            match std::fs::read_to_string(path.join(file)) {
+                Ok(contents) => Ok(Some(contents)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(SystemError::io(e, format!("reading {}", path.join(file).display()))),
+            }
+        };
+        let parse = |file: &str, value: &str| -> Result<Option<u64>> {
+            if value == "max" {
+                return Ok(None);
+            }
+            value.parse().map(Some).map_err(|_| {
+                SystemError::config(format!("invalid value `{value}` in {file}"), Some(file.into()))
+            })
+        };
+
+        let mut config = Self::default();
+        if let Some(contents) = read("memory.max")? {
+            config.ram_cap_bytes = parse("memory.max", contents.trim())?;
+        }
+        if let Some(contents) = read("cpu.max")? {
+            let mut fields = contents.split_whitespace();
+            let quota = parse("cpu.max", fields.next().unwrap_or("max"))?;
+            let period = parse("cpu.max", fields.next().unwrap_or("100000"))?;
+            if let (Some(quota), Some(period)) = (quota, period.filter(|&p| p > 0)) {
+                let percent = (quota.saturating_mul(100) / period).clamp(1, 100);
+                config.cpu_cap_percent = u8::try_from(percent).ok();
+            }
+        }
+        if let Some(contents) = read("io.max")? {
+            for setting in contents.split_whitespace() {
+                let Some(("riops" | "wiops", value)) = setting.split_once('=') else {
+                    continue;
+                };
+                if let Some(limit) = parse("io.max", value)? {
+                    let current = config.io_ops_per_second.unwrap_or(u64::MAX);
+                    config.io_ops_per_second = Some(current.min(limit));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Accept only the labels of known presets, which are `'static`
+fn deserialize_preset_label<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<StaticStr>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(label) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    PRESET_LABELS
+        .into_iter()
+        .find(|&preset| preset == label)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown preset `{label}`")))
 }
 
 /// Resource governor for managing and throttling system resources
@@ -101,6 +195,10 @@ pub struct ResourceGovernor {
     // CPU tracking
     cpu_usage_percent: Arc<AtomicU64>,
     last_cpu_check: Arc<RwLock<Instant>>,
+    // Sleep of the last throttled operation, 0 when not throttling
+    last_sleep_ms: Arc<AtomicU64>,
+    // Bits of the controller's `f64` integral error
+    integral_error: Arc<AtomicU64>,
 
     // RAM tracking
     ram_usage_bytes: Arc<AtomicU64>,
@@ -116,6 +214,10 @@ pub struct ResourceGovernor {
     is_paused: Arc<AtomicBool>,
     total_operations: Arc<AtomicU64>,
     throttled_operations: Arc<AtomicU64>,
+    labels: Arc<parking_lot::Mutex<BTreeMap<String, LabelStatistics>>>,
+
+    // Hierarchy
+    parent: Option<Arc<ResourceGovernor>>,
 }
 
 impl ResourceGovernor {
@@ -128,16 +230,80 @@ impl ResourceGovernor {
             config,
             cpu_usage_percent: Arc::new(AtomicU64::new(0)),
             last_cpu_check: Arc::new(RwLock::new(Instant::now())),
+            last_sleep_ms: Arc::new(AtomicU64::new(0)),
+            integral_error: Arc::new(AtomicU64::new(0.0_f64.to_bits())),
             ram_usage_bytes: Arc::new(AtomicU64::new(0)),
             io_ops_count: Arc::new(AtomicU64::new(0)),
             io_window_start: Arc::new(RwLock::new(Instant::now())),
             is_paused: Arc::new(AtomicBool::new(false)),
             total_operations: Arc::new(AtomicU64::new(0)),
             throttled_operations: Arc::new(AtomicU64::new(0)),
+            labels: Arc::new(parking_lot::Mutex::new(BTreeMap::new())),
+            parent: None,
         })
     }
 
+    /// Create a child governor bounded by this governor's limits
+    ///
+    /// Limits left unset in `config` are inherited; limits looser than the
+    /// parent's are rejected. Every child permit also holds a permit from the
+    /// parent, so children share the parent's concurrency pool and are
+    /// subject to its CPU and RAM checks. RAM tracked on the child is
+    /// mirrored to the parent.
+    pub fn child(&self, mut config: ResourceGovernorConfig) -> Result<ResourceGovernor> {
+        config.validate()?;
+
+        config.cpu_cap_percent = tighter_limit(
+            config.cpu_cap_percent,
+            self.config.cpu_cap_percent,
+            "cpu_cap_percent",
+        )?;
+        config.ram_cap_bytes =
+            tighter_limit(config.ram_cap_bytes, self.config.ram_cap_bytes, "ram_cap_bytes")?;
+        config.io_ops_per_second = tighter_limit(
+            config.io_ops_per_second,
+            self.config.io_ops_per_second,
+            "io_ops_per_second",
+        )?;
+        if config.max_concurrent_operations > self.config.max_concurrent_operations {
+            return Err(SystemError::Config {
+                message: format!(
+                    "max_concurrent_operations must be <= the parent's {}",
+                    self.config.max_concurrent_operations
+                ),
+                key: Some("max_concurrent_operations".into()),
+            });
+        }
+
+        let mut child = Self::new(config)?;
+        child.parent = Some(Arc::new(self.clone()));
+        Ok(child)
+    }
+
+    /// Get the parent governor, if this is a child governor
+    #[must_use]
+    pub fn parent(&self) -> Option<&ResourceGovernor> {
+        self.parent.as_deref()
+    }
+
+    /// Get the configuration the governor enforces
+    ///
+    /// For a child governor this includes the limits inherited from its
+    /// parent.
+    #[must_use]
+    pub fn config(&self) -> &ResourceGovernorConfig {
+        &self.config
+    }
+
+    /// Get the configuration as JSON, including its preset label
+    #[must_use]
+    pub fn config_json(&self) -> serde_json::Value {
+        // Serializing plain fields cannot fail
+        serde_json::to_value(&self.config).unwrap_or_default()
+    }
+
     /// Acquire a permit to execute an operation
+    #[allow(clippy::uninlined_format_args)]
     pub async fn acquire_permit(&self) -> Result<OperationPermit> {
         self.total_operations.fetch_add(1, Ordering::Relaxed);
 
@@ -158,13 +324,9 @@ impl ResourceGovernor {
             })?;
 
         // Check CPU throttling
-        if let Some(cpu_cap) = self.config.cpu_cap_percent {
-            let current_cpu = self.cpu_usage_percent.load(Ordering::Relaxed);
-            if current_cpu > u64::from(cpu_cap) {
-                self.throttled_operations.fetch_add(1, Ordering::Relaxed);
-                let sleep_duration = Duration::from_millis(10);
-                sleep(sleep_duration).await;
-            }
+        if let Some(sleep_duration) = self.adaptive_cpu_throttling() {
+            self.throttled_operations.fetch_add(1, Ordering::Relaxed);
+            sleep(sleep_duration).await;
         }
 
         // Check RAM limit
@@ -182,14 +344,108 @@ impl ResourceGovernor {
             }
         }
 
+        // Children also need room in every ancestor
+        let parent_permit = match &self.parent {
+            Some(parent) => Some(Box::new(Box::pin(parent.acquire_permit()).await?)),
+            None => None,
+        };
+
         Ok(OperationPermit {
             _permit: permit,
+            _parent_permit: parent_permit,
             governor: self.clone(),
             start_time: Instant::now(),
+            label: None,
         })
     }
 
+    /// Acquire a permit counted under `label` in [`GovernorStatistics::labels`]
+    pub async fn acquire_permit_labeled(&self, label: &str) -> Result<OperationPermit> {
+        let mut permit = self.acquire_permit().await?;
+
+        let mut labels = self.labels.lock();
+        let stats = labels.entry(label.to_string()).or_default();
+        stats.total_operations += 1;
+        stats.active_operations += 1;
+        permit.label = Some(label.to_string());
+        Ok(permit)
+    }
+
+    /// Work out how long the next operation sleeps to keep CPU usage under
+    /// the cap, or `None` if it does not
+    ///
+    /// Throttling starts once usage goes over the cap and goes on, each call
+    /// tuning the sleep with [`Self::compute_throttle_sleep`], until usage is
+    /// back under the cap with the sleep at its minimum, which also clears
+    /// the integral.
+    #[must_use]
+    pub fn adaptive_cpu_throttling(&self) -> Option<Duration> {
+        let cap = self.config.cpu_cap_percent?;
+        let current = self.cpu_usage_percent.load(Ordering::Relaxed);
+        let last_sleep_ms = self.last_sleep_ms.load(Ordering::Relaxed);
+        let over_cap = current > u64::from(cap);
+        if !over_cap && last_sleep_ms == 0 {
+            return None;
+        }
+
+        let wait = self.compute_throttle_sleep(current, cap, last_sleep_ms);
+        // Back under the cap with the sleep at its minimum
+        if !over_cap && wait.as_secs_f64() * 1000.0 <= MIN_THROTTLE_SLEEP_MS {
+            self.integral_error.store(0.0_f64.to_bits(), Ordering::Relaxed);
+            self.last_sleep_ms.store(0, Ordering::Relaxed);
+            return None;
+        }
+
+        let wait_ms = u64::try_from(wait.as_millis()).unwrap_or(u64::MAX);
+        self.last_sleep_ms.store(wait_ms, Ordering::Relaxed);
+        Some(wait)
+    }
+
+    /// Tune the CPU throttle's sleep to how far usage is over the cap
+    ///
+    /// A proportional-integral controller: the sleep is
+    /// `kp * error + ki * integral_error` milliseconds, `error` being
+    /// `current_percent - cap` in percentage points, with `kp = 1.0` and
+    /// `ki = 0.1`, clamped to 1-500 ms. Each call adds `error` to the
+    /// governor's integral, exactly once however many calls race, except
+    /// while `last_sleep_ms` is at a bound the error pushes towards, so the
+    /// integral does not wind up while the sleep is clamped.
+    #[must_use]
+    pub fn compute_throttle_sleep(
+        &self,
+        current_percent: u64,
+        cap: u8,
+        last_sleep_ms: u64,
+    ) -> Duration {
+        let current = f64::from(u8::try_from(current_percent.min(100)).unwrap_or(100));
+        let error = current - f64::from(cap);
+        let last = f64::from(u32::try_from(last_sleep_ms).unwrap_or(u32::MAX));
+        let saturated = (last >= MAX_THROTTLE_SLEEP_MS && error > 0.0)
+            || (last_sleep_ms != 0 && last <= MIN_THROTTLE_SLEEP_MS && error < 0.0);
+
+        let mut integral_bits = self.integral_error.load(Ordering::Relaxed);
+        let integral_error = loop {
+            let integral_error = f64::from_bits(integral_bits);
+            if saturated {
+                break integral_error;
+            }
+            match self.integral_error.compare_exchange_weak(
+                integral_bits,
+                (integral_error + error).to_bits(),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break integral_error + error,
+                Err(actual) => integral_bits = actual,
+            }
+        };
+        let sleep_ms = (THROTTLE_KP * error + THROTTLE_KI * integral_error)
+            .clamp(MIN_THROTTLE_SLEEP_MS, MAX_THROTTLE_SLEEP_MS);
+        Duration::from_secs_f64(sleep_ms / 1000.0)
+    }
+
     /// Throttle I/O operation if needed
+    #[allow(clippy::unchecked_time_subtraction)]
     pub async fn throttle_io(&self) -> Result<()> {
         if let Some(ops_limit) = self.config.io_ops_per_second {
             let mut window_start = self.io_window_start.write().await;
@@ -224,22 +480,30 @@ impl ResourceGovernor {
             .store(u64::from(percent.min(100)), Ordering::Relaxed);
     }
 
-    /// Track RAM allocation
+    /// Track RAM allocation (mirrored to the parent governor)
     pub fn track_ram_allocation(&self, bytes: u64) {
         self.ram_usage_bytes.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.track_ram_allocation(bytes);
+        }
     }
 
-    /// Track RAM deallocation
+    /// Track RAM deallocation (mirrored to the parent governor)
     pub fn track_ram_deallocation(&self, bytes: u64) {
         self.ram_usage_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.track_ram_deallocation(bytes);
+        }
     }
 
     /// Get current RAM usage
+    #[allow(clippy::must_use_candidate)]
     pub fn current_ram_usage(&self) -> u64 {
         self.ram_usage_bytes.load(Ordering::Relaxed)
     }
 
     /// Get current CPU usage
+    #[allow(clippy::must_use_candidate)]
     pub fn current_cpu_usage(&self) -> u64 {
         self.cpu_usage_percent.load(Ordering::Relaxed)
     }
@@ -254,17 +518,26 @@ impl ResourceGovernor {
         self.is_paused.store(false, Ordering::Relaxed);
     }
 
+    /// Check if operations are paused
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::Relaxed)
+    }
+
     /// Check if in deterministic mode
+    #[allow(clippy::must_use_candidate)]
     pub fn is_deterministic(&self) -> bool {
         self.config.deterministic_mode
     }
 
     /// Check if in sandbox mode
+    #[allow(clippy::must_use_candidate)]
     pub fn is_sandboxed(&self) -> bool {
         self.config.sandbox_mode
     }
 
     /// Get statistics
+    #[allow(clippy::must_use_candidate)]
     pub fn statistics(&self) -> GovernorStatistics {
         GovernorStatistics {
             total_operations: self.total_operations.load(Ordering::Relaxed),
@@ -272,16 +545,21 @@ impl ResourceGovernor {
             current_cpu_usage: self.cpu_usage_percent.load(Ordering::Relaxed),
             current_ram_usage: self.ram_usage_bytes.load(Ordering::Relaxed),
             is_paused: self.is_paused.load(Ordering::Relaxed),
+            labels: self.labels.lock().clone(),
         }
     }
 
-    /// Reset statistics
+    /// Reset statistics; active labeled operations keep being counted
     pub fn reset_statistics(&self) {
         self.total_operations.store(0, Ordering::Relaxed);
         self.throttled_operations.store(0, Ordering::Relaxed);
+        for stats in self.labels.lock().values_mut() {
+            stats.total_operations = 0;
+        }
     }
 
     /// Get random number generator (deterministic if in deterministic mode)
+    #[allow(clippy::must_use_candidate)]
     pub fn get_rng(&self) -> Box<dyn rand::RngCore> {
         if self.config.deterministic_mode {
             // Use seeded RNG for deterministic execution
@@ -299,6 +577,8 @@ impl Clone for ResourceGovernor {
             config: self.config.clone(),
             cpu_usage_percent: Arc::clone(&self.cpu_usage_percent),
             last_cpu_check: Arc::clone(&self.last_cpu_check),
+            last_sleep_ms: Arc::clone(&self.last_sleep_ms),
+            integral_error: Arc::clone(&self.integral_error),
             ram_usage_bytes: Arc::clone(&self.ram_usage_bytes),
             io_ops_count: Arc::clone(&self.io_ops_count),
             io_window_start: Arc::clone(&self.io_window_start),
@@ -306,27 +586,65 @@ impl Clone for ResourceGovernor {
             is_paused: Arc::clone(&self.is_paused),
             total_operations: Arc::clone(&self.total_operations),
             throttled_operations: Arc::clone(&self.throttled_operations),
+            labels: Arc::clone(&self.labels),
+            parent: self.parent.clone(),
         }
     }
 }
 
+/// Resolve a child limit against its parent's: unset inherits, looser fails
+fn tighter_limit<T>(child: Option<T>, parent: Option<T>, key: &str) -> Result<Option<T>>
+where
+    T: Copy + PartialOrd + std::fmt::Display,
+{
+    match (child, parent) {
+        (Some(child), Some(parent)) if child > parent => Err(SystemError::Config {
+            message: format!("{key} must be <= the parent's {parent}"),
+            key: Some(key.into()),
+        }),
+        (None, parent) => Ok(parent),
+        (child, _) => Ok(child),
+    }
+}
+
 /// Permit for executing an operation under resource governance
 pub struct OperationPermit {
     _permit: tokio::sync::OwnedSemaphorePermit,
+    _parent_permit: Option<Box<OperationPermit>>,
     governor: ResourceGovernor,
     start_time: Instant,
+    /// Set by [`ResourceGovernor::acquire_permit_labeled`]
+    label: Option<String>,
 }
 
 impl OperationPermit {
     /// Get the resource governor
+    #[allow(clippy::must_use_candidate)]
     pub fn governor(&self) -> &ResourceGovernor {
         &self.governor
     }
 
     /// Get operation duration
+    #[allow(clippy::must_use_candidate)]
     pub fn duration(&self) -> Duration {
         self.start_time.elapsed()
     }
+
+    /// Label the permit was acquired under
+    #[must_use]
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+impl Drop for OperationPermit {
+    fn drop(&mut self) {
+        if let Some(label) = &self.label {
+            if let Some(stats) = self.governor.labels.lock().get_mut(label) {
+                stats.active_operations = stats.active_operations.saturating_sub(1);
+            }
+        }
+    }
 }
 
 /// Resource governor statistics
@@ -346,9 +664,333 @@ pub struct GovernorStatistics {
 
     /// Whether governor is paused
     pub is_paused: bool,
+
+    /// Operations acquired with [`ResourceGovernor::acquire_permit_labeled`],
+    /// by label
+    #[serde(default)]
+    pub labels: BTreeMap<String, LabelStatistics>,
+}
+
+/// Statistics of the operations acquired under one label
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelStatistics {
+    /// Permits acquired since the statistics were last reset
+    pub total_operations: u64,
+
+    /// Permits currently held
+    pub active_operations: u64,
+}
+
+/// Token bucket limiting how often an operation may run
+///
+/// The bucket starts full and refills continuously up to its capacity.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_second: f64,
+    bucket: parking_lot::Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    total_throttled: u64,
+}
+
+impl RateLimiter {
+    /// Create a limiter holding up to `capacity` tokens and regaining
+    /// `refill_per_second` of them per second
+    ///
+    /// A refill rate of zero gives a fixed budget of `capacity` tokens.
+    pub fn new(capacity: u32, refill_per_second: f64) -> Result<Self> {
+        if capacity == 0 {
+            return Err(SystemError::validation(
+                "capacity",
+                "must be positive",
+                Some(capacity.to_string()),
+            ));
+        }
+        if !refill_per_second.is_finite() || refill_per_second < 0.0 {
+            return Err(SystemError::validation(
+                "refill_per_second",
+                "must be a finite, non-negative rate",
+                Some(refill_per_second.to_string()),
+            ));
+        }
+        let capacity = f64::from(capacity);
+        Ok(Self {
+            capacity,
+            refill_per_second,
+            bucket: parking_lot::Mutex::new(Bucket {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+                total_throttled: 0,
+            }),
+        })
+    }
+
+    /// Take `tokens` from the bucket, returning `false` and counting the
+    /// attempt as throttled if there are not enough
+    #[must_use]
+    pub fn try_acquire(&self, tokens: u32) -> bool {
+        let mut bucket = self.refilled();
+        let tokens = f64::from(tokens);
+        if bucket.tokens >= tokens {
+            bucket.tokens -= tokens;
+            true
+        } else {
+            bucket.total_throttled += 1;
+            false
+        }
+    }
+
+    /// Time until `tokens` can be acquired, or `None` if never
+    #[must_use]
+    pub fn time_until_available(&self, tokens: u32) -> Option<Duration> {
+        let tokens = f64::from(tokens);
+        let missing = tokens - self.refilled().tokens;
+        if missing <= 0.0 {
+            return Some(Duration::ZERO);
+        }
+        if tokens > self.capacity || self.refill_per_second == 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(missing / self.refill_per_second))
+    }
+
+    /// Current tokens and throttled attempts so far
+    #[must_use]
+    pub fn stats(&self) -> RateLimiterStats {
+        let bucket = self.refilled();
+        RateLimiterStats {
+            tokens_available: bucket.tokens,
+            total_throttled: bucket.total_throttled,
+        }
+    }
+
+    fn refilled(&self) -> parking_lot::MutexGuard<'_, Bucket> {
+        let mut bucket = self.bucket.lock();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        bucket.refilled_at = now;
+        bucket
+    }
+}
+
+/// Statistics of a [`RateLimiter`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimiterStats {
+    /// Tokens that can be acquired right now, possibly fractional
+    pub tokens_available: f64,
+
+    /// Acquisitions refused for lack of tokens
+    pub total_throttled: u64,
+}
+
+/// When a [`CircuitBreaker`] opens and how long it stays open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the breaker
+    pub failure_threshold: u32,
+
+    /// How long the breaker stays open before letting a trial call through
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+
+    /// Calls are refused until the open duration has passed
+    Open,
+
+    /// Calls go through; the next outcome closes or reopens the breaker
+    HalfOpen,
+}
+
+/// Breaker refusing calls to an operation that keeps failing
+///
+/// The breaker opens after `failure_threshold` consecutive failures and
+/// refuses calls for `open_duration`. It is then half-open: the next
+/// success closes it, the next failure opens it again.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuit: parking_lot::Mutex<Circuit>,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker
+    pub fn new(config: CircuitBreakerConfig) -> Result<Self> {
+        if config.failure_threshold == 0 {
+            return Err(SystemError::validation(
+                "failure_threshold",
+                "must be positive",
+                Some(config.failure_threshold.to_string()),
+            ));
+        }
+        Ok(Self {
+            config,
+            circuit: parking_lot::Mutex::new(Circuit {
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+        })
+    }
+
+    /// Current state
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        match self.circuit.lock().opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.config.open_duration => {
+                CircuitState::Open
+            },
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Time until an open breaker becomes half-open, `None` unless open
+    #[must_use]
+    pub fn time_until_half_open(&self) -> Option<Duration> {
+        let opened_at = self.circuit.lock().opened_at?;
+        let wait = self.config.open_duration.saturating_sub(opened_at.elapsed());
+        (!wait.is_zero()).then_some(wait)
+    }
+
+    /// Record a successful call, closing the breaker
+    pub fn record_success(&self) {
+        let mut circuit = self.circuit.lock();
+        circuit.consecutive_failures = 0;
+        circuit.opened_at = None;
+    }
+
+    /// Record a failed call, returning `true` if it opened the breaker
+    pub fn record_failure(&self) -> bool {
+        let mut circuit = self.circuit.lock();
+        circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+        let half_open = circuit
+            .opened_at
+            .is_some_and(|opened_at| opened_at.elapsed() >= self.config.open_duration);
+        let tripped = circuit.opened_at.is_none()
+            && circuit.consecutive_failures >= self.config.failure_threshold;
+        if half_open || tripped {
+            circuit.opened_at = Some(Instant::now());
+        }
+        half_open || tripped
+    }
+
+    /// Consecutive failures recorded since the last success
+    #[must_use]
+    pub fn consecutive_failures(&self) -> u32 {
+        self.circuit.lock().consecutive_failures
+    }
+}
+
+/// How often, and how long apart, a failing operation is retried
+///
+/// Only retriable errors are retried. After failed attempt `n` the
+/// operation waits `initial_backoff * multiplier^(n - 1)`, at most
+/// `max_backoff`; with `jitter` the wait is drawn from the upper half of
+/// that instead. A rate-limited error's retry-after is waited at least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first included; 1 never retries
+    pub max_attempts: u32,
+
+    /// Wait after the first failed attempt
+    pub initial_backoff: Duration,
+
+    /// Longest wait between attempts
+    pub max_backoff: Duration,
+
+    /// Factor each wait grows by
+    pub multiplier: u32,
+
+    /// Whether to randomize waits, so that operations failing together
+    /// do not retry together
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy running the operation once
+    #[must_use]
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Validate the policy
+    pub fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            return Err(SystemError::validation("max_attempts", "must be positive", None));
+        }
+        if self.multiplier == 0 {
+            return Err(SystemError::validation("multiplier", "must be positive", None));
+        }
+        Ok(())
+    }
+
+    /// Whether to retry after attempt `attempt`, counted from 1, failed
+    /// with `error`
+    #[must_use]
+    pub fn should_retry(&self, attempt: u32, error: &SystemError) -> bool {
+        attempt < self.max_attempts && error.is_retriable()
+    }
+
+    /// Wait before retrying after attempt `attempt`, counted from 1,
+    /// failed with `error`, drawing any jitter from `rng`
+    pub fn backoff<R: rand::Rng + ?Sized>(
+        &self,
+        attempt: u32,
+        error: &SystemError,
+        rng: &mut R,
+    ) -> Duration {
+        let factor = self.multiplier.saturating_pow(attempt.saturating_sub(1));
+        let mut wait = self.initial_backoff.saturating_mul(factor).min(self.max_backoff);
+        if self.jitter && !wait.is_zero() {
+            let half = wait / 2;
+            wait = rng.gen_range(half..=wait);
+        }
+        error.retry_after().map_or(wait, |after| wait.max(after))
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
 
@@ -365,6 +1007,59 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_from_cgroups() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ResourceGovernorConfig::from_cgroups(dir.path()).unwrap();
+        assert_eq!(config.ram_cap_bytes, None);
+        assert_eq!(config.cpu_cap_percent, None);
+
+        std::fs::write(dir.path().join("memory.max"), "1048576\n").unwrap();
+        std::fs::write(dir.path().join("cpu.max"), "50000 100000\n").unwrap();
+        std::fs::write(
+            dir.path().join("io.max"),
+            "8:0 rbps=max wbps=1024 riops=300 wiops=max\n8:16 riops=max wiops=200\n",
+        )
+        .unwrap();
+        let config = ResourceGovernorConfig::from_cgroups(dir.path()).unwrap();
+        assert_eq!(config.ram_cap_bytes, Some(1_048_576));
+        assert_eq!(config.cpu_cap_percent, Some(50));
+        assert_eq!(config.io_ops_per_second, Some(200));
+        assert!(config.validate().is_ok());
+
+        // More than one CPU is capped at 100%
+        std::fs::write(dir.path().join("cpu.max"), "400000 100000\n").unwrap();
+        let config = ResourceGovernorConfig::from_cgroups(dir.path()).unwrap();
+        assert_eq!(config.cpu_cap_percent, Some(100));
+
+        std::fs::write(dir.path().join("memory.max"), "lots\n").unwrap();
+        assert!(ResourceGovernorConfig::from_cgroups(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_config_json_round_trip() {
+        let governor = ResourceGovernor::new(ResourceGovernorConfig::production()).unwrap();
+        assert_eq!(governor.config().max_concurrent_operations, 1000);
+        let json = governor.config_json();
+        assert_eq!(json["preset_label"], "production");
+
+        let config: ResourceGovernorConfig = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(config.preset_label, Some("production"));
+        assert_eq!(config.ram_cap_bytes, Some(4 * 1024 * 1024 * 1024));
+        assert_eq!(serde_json::to_value(&config).unwrap(), json);
+
+        // Configs without a label, or written before labels existed, have none
+        let governor = ResourceGovernor::new(ResourceGovernorConfig::default()).unwrap();
+        let mut json = governor.config_json();
+        assert!(json["preset_label"].is_null());
+        json.as_object_mut().unwrap().remove("preset_label");
+        let config: ResourceGovernorConfig = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(config.preset_label, None);
+
+        json["preset_label"] = "custom".into();
+        assert!(serde_json::from_value::<ResourceGovernorConfig>(json).is_err());
+    }
+
     #[test]
     fn test_governor_creation() {
         let config = ResourceGovernorConfig::default();
@@ -390,6 +1085,64 @@ mod tests {
         assert_eq!(governor.current_cpu_usage(), 50);
     }
 
+    #[test]
+    fn test_adaptive_cpu_throttling() {
+        // Operations of `work_ms` keep 100% of the CPU busy unthrottled, and
+        // each usage reading moves 30% of the way to the load since the last
+        let usage = |work_ms: u128, wait: Duration, last: u64| {
+            let period = work_ms * 1000 + wait.as_micros();
+            let load = u64::try_from((100_000 * work_ms + period / 2) / period).unwrap();
+            u8::try_from((7 * last + 3 * load + 5) / 10).unwrap()
+        };
+        for work_ms in [5, 10] {
+            let config = ResourceGovernorConfig {
+                cpu_cap_percent: Some(50),
+                ..Default::default()
+            };
+            let governor = ResourceGovernor::new(config).unwrap();
+            assert_eq!(governor.adaptive_cpu_throttling(), None);
+
+            governor.update_cpu_usage(100);
+            for _ in 0..10 {
+                let wait = governor.adaptive_cpu_throttling().unwrap();
+                governor.update_cpu_usage(usage(work_ms, wait, governor.current_cpu_usage()));
+            }
+            let current = governor.current_cpu_usage();
+            assert!(current.abs_diff(50) <= 3, "{work_ms} ms operations at {current}%");
+
+            // Throttling stops once the load goes away
+            governor.update_cpu_usage(10);
+            let waits: Vec<_> = (0..10).map(|_| governor.adaptive_cpu_throttling()).collect();
+            assert_eq!(waits.last(), Some(&None));
+            assert_eq!(governor.adaptive_cpu_throttling(), None);
+        }
+
+        // kp * error + ki * integral_error, clamped
+        let config = ResourceGovernorConfig::default();
+        let governor = ResourceGovernor::new(config).unwrap();
+        let integral = |governor: &ResourceGovernor| {
+            f64::from_bits(governor.integral_error.load(Ordering::Relaxed))
+        };
+        assert_eq!(governor.compute_throttle_sleep(60, 50, 0), Duration::from_millis(11));
+        assert_eq!(integral(&governor), 10.0);
+        assert_eq!(governor.compute_throttle_sleep(55, 50, 11), Duration::from_micros(6500));
+        assert_eq!(integral(&governor), 15.0);
+        governor.integral_error.store(5000.0_f64.to_bits(), Ordering::Relaxed);
+        assert_eq!(governor.compute_throttle_sleep(100, 50, 0), Duration::from_millis(500));
+        governor.integral_error.store(0.0_f64.to_bits(), Ordering::Relaxed);
+        assert_eq!(governor.compute_throttle_sleep(0, 50, 0), Duration::from_millis(1));
+
+        // The integral holds while the sleep is clamped
+        governor.integral_error.store(5000.0_f64.to_bits(), Ordering::Relaxed);
+        let _ = governor.compute_throttle_sleep(100, 50, 500);
+        assert_eq!(integral(&governor), 5000.0);
+        let _ = governor.compute_throttle_sleep(40, 50, 500);
+        assert_eq!(integral(&governor), 4990.0);
+        governor.integral_error.store(5.0_f64.to_bits(), Ordering::Relaxed);
+        let _ = governor.compute_throttle_sleep(40, 50, 1);
+        assert_eq!(integral(&governor), 5.0);
+    }
+
     #[tokio::test]
     async fn test_ram_tracking() {
         let config = ResourceGovernorConfig::default();
@@ -410,6 +1163,7 @@ mod tests {
         governor.pause();
         let stats = governor.statistics();
         assert!(stats.is_paused);
+        assert!(governor.is_paused());
 
         governor.resume();
         let stats = governor.statistics();
@@ -463,6 +1217,185 @@ mod tests {
         assert_eq!(stats.total_operations, 0);
     }
 
+    #[tokio::test]
+    async fn test_labeled_permits() {
+        let governor = ResourceGovernor::new(ResourceGovernorConfig::default()).unwrap();
+
+        let signing = governor.acquire_permit_labeled("sign").await.unwrap();
+        assert_eq!(signing.label(), Some("sign"));
+        drop(governor.acquire_permit_labeled("sign").await.unwrap());
+        let _unlabeled = governor.acquire_permit().await.unwrap();
+
+        let stats = governor.statistics();
+        assert_eq!(stats.total_operations, 3);
+        let expected = LabelStatistics {
+            total_operations: 2,
+            active_operations: 1,
+        };
+        assert_eq!(stats.labels["sign"], expected);
+
+        drop(signing);
+        governor.reset_statistics();
+        assert_eq!(governor.statistics().labels["sign"], LabelStatistics::default());
+    }
+
+    #[tokio::test]
+    async fn test_child_limits() {
+        let parent = ResourceGovernor::new(ResourceGovernorConfig {
+            cpu_cap_percent: Some(80),
+            ram_cap_bytes: Some(1024),
+            max_concurrent_operations: 2,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let child = parent
+            .child(ResourceGovernorConfig {
+                ram_cap_bytes: Some(512),
+                max_concurrent_operations: 2,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(child.config.cpu_cap_percent, Some(80));
+        assert!(child.parent().is_some());
+
+        let looser = ResourceGovernorConfig {
+            ram_cap_bytes: Some(2048),
+            max_concurrent_operations: 2,
+            ..Default::default()
+        };
+        assert!(matches!(
+            parent.child(looser),
+            Err(SystemError::Config { key: Some(ref key), .. }) if key == "ram_cap_bytes"
+        ));
+        assert!(parent.child(ResourceGovernorConfig::default()).is_err());
+
+        // Child allocations count towards the parent
+        child.track_ram_allocation(600);
+        assert_eq!(parent.statistics().current_ram_usage, 600);
+        assert!(child.acquire_permit().await.is_err());
+        child.track_ram_deallocation(300);
+        assert_eq!(parent.current_ram_usage(), 300);
+
+        // Parent usage alone can block the child
+        parent.track_ram_allocation(800);
+        assert_eq!(child.current_ram_usage(), 300);
+        assert!(child.acquire_permit().await.is_err());
+        parent.track_ram_deallocation(800);
+
+        // Children share the parent's concurrency pool
+        let sibling = parent
+            .child(ResourceGovernorConfig {
+                max_concurrent_operations: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        let _first = child.acquire_permit().await.unwrap();
+        let _second = sibling.acquire_permit().await.unwrap();
+        let blocked = tokio::time::timeout(Duration::from_millis(50), child.acquire_permit()).await;
+        assert!(blocked.is_err());
+        assert_eq!(parent.statistics().total_operations, 4);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        assert!(RateLimiter::new(0, 1.0).is_err());
+        assert!(RateLimiter::new(1, f64::NAN).is_err());
+
+        let limiter = RateLimiter::new(3, 0.0).unwrap();
+        assert!(limiter.try_acquire(2));
+        assert!(!limiter.try_acquire(2));
+        assert!(limiter.try_acquire(1));
+        assert_eq!(limiter.time_until_available(1), None);
+        let stats = limiter.stats();
+        assert_eq!(stats.tokens_available, 0.0);
+        assert_eq!(stats.total_throttled, 1);
+
+        // Refills over time, but never beyond capacity
+        let limiter = RateLimiter::new(1, 1000.0).unwrap();
+        assert!(limiter.try_acquire(1));
+        assert!(limiter.time_until_available(1).unwrap() <= Duration::from_millis(1));
+        assert_eq!(limiter.time_until_available(2), None);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(limiter.stats().tokens_available, 1.0);
+        assert!(limiter.try_acquire(1));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let config = |open_duration| CircuitBreakerConfig {
+            failure_threshold: 2,
+            open_duration,
+        };
+        assert!(CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 0,
+            ..CircuitBreakerConfig::default()
+        })
+        .is_err());
+
+        let breaker = CircuitBreaker::new(config(Duration::from_secs(60))).unwrap();
+        assert!(!breaker.record_failure());
+        breaker.record_success();
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.time_until_half_open().unwrap() > Duration::from_secs(59));
+        // Failures while open do not restart the open duration
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.consecutive_failures(), 3);
+
+        let breaker = CircuitBreaker::new(config(Duration::from_millis(10))).unwrap();
+        breaker.record_failure();
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(breaker.time_until_half_open(), None);
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        std::thread::sleep(Duration::from_millis(20));
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.consecutive_failures(), 0);
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(30),
+            multiplier: 2,
+            jitter: false,
+        };
+        assert!(policy.validate().is_ok());
+        let timeout = SystemError::timeout("call", 5);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let waits: Vec<_> = (1..=3).map(|n| policy.backoff(n, &timeout, &mut rng)).collect();
+        assert_eq!(waits, [10, 20, 30].map(Duration::from_millis));
+        let limited = SystemError::rate_limited("api", Duration::from_secs(1));
+        assert_eq!(policy.backoff(1, &limited, &mut rng), Duration::from_secs(1));
+
+        assert!(policy.should_retry(3, &timeout));
+        assert!(!policy.should_retry(4, &timeout));
+        assert!(!policy.should_retry(1, &SystemError::internal("bug", None)));
+        assert!(!RetryPolicy::never().should_retry(1, &timeout));
+
+        let jittered = RetryPolicy {
+            jitter: true,
+            ..policy
+        };
+        for _ in 0..100 {
+            let wait = jittered.backoff(2, &timeout, &mut rng);
+            assert!((Duration::from_millis(10)..=Duration::from_millis(20)).contains(&wait));
+        }
+        let zero = RetryPolicy {
+            max_attempts: 0,
+            ..policy
+        };
+        assert!(zero.validate().is_err());
+    }
+
     #[test]
     fn test_preset_configs() {
         let testing = ResourceGovernorConfig::testing();
@@ -4,7 +4,19 @@
 
 use crate::error::{Result, SystemError};
 use metrics_exporter_prometheus::PrometheusBuilder;
+use std::collections::HashMap;
+use std::fmt::Write;
 use std::net::SocketAddr;
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Header carrying a [`TraceContext`]
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// `trace-flags` bit marking a trace as sampled
+pub const TRACE_FLAG_SAMPLED: u8 = 0x01;
 
 /// Telemetry configuration
 #[derive(Debug, Clone)]
@@ -41,6 +53,7 @@ impl Default for TelemetryConfig {
 
 impl TelemetryConfig {
     /// Create a production telemetry configuration
+    #[allow(clippy::missing_panics_doc, clippy::must_use_candidate)]
     pub fn production(service_name: String) -> Self {
         Self {
             service_name,
@@ -54,6 +67,7 @@ impl TelemetryConfig {
     }
 
     /// Create a development telemetry configuration
+    #[allow(clippy::missing_panics_doc, clippy::must_use_candidate)]
     pub fn development(service_name: String) -> Self {
         Self {
             service_name,
@@ -68,6 +82,7 @@ impl TelemetryConfig {
 }
 
 /// Initialize telemetry based on configuration
+#[allow(clippy::uninlined_format_args)]
 pub fn init_telemetry(config: &TelemetryConfig) -> Result<()> {
     if config.enable_metrics {
         if let Some(addr) = config.metrics_endpoint {
@@ -89,11 +104,199 @@ pub fn init_telemetry(config: &TelemetryConfig) -> Result<()> {
     Ok(())
 }
 
+/// W3C trace context, as carried by the `traceparent` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceContext {
+    /// Identifier shared by every span of the trace
+    pub trace_id: [u8; 16],
+    /// Identifier of the parent span
+    pub span_id: [u8; 8],
+    /// Trace flags, see [`TRACE_FLAG_SAMPLED`]
+    pub trace_flags: u8,
+}
+
+impl TraceContext {
+    /// Format as a version `00` `traceparent` header value
+    #[must_use]
+    pub fn to_traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            hex(&self.trace_id),
+            hex(&self.span_id),
+            self.trace_flags
+        )
+    }
+
+    /// Parse a `traceparent` header value
+    ///
+    /// Fields beyond the fourth are accepted for versions above `00`, as the
+    /// specification requires. All-zero trace or span IDs are rejected.
+    pub fn from_traceparent(header: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            SystemError::validation(TRACEPARENT_HEADER, reason, Some(header.to_string()))
+        };
+
+        let mut fields = header.trim().split('-');
+        let version = fields.next().unwrap_or_default();
+        let [version] = decode_hex::<1>(version).ok_or_else(|| invalid("malformed version"))?;
+        if version == 0xff {
+            return Err(invalid("version ff is not allowed"));
+        }
+
+        let trace_id = fields
+            .next()
+            .and_then(decode_hex::<16>)
+            .ok_or_else(|| invalid("malformed trace-id"))?;
+        let span_id = fields
+            .next()
+            .and_then(decode_hex::<8>)
+            .ok_or_else(|| invalid("malformed parent-id"))?;
+        let [trace_flags] = fields
+            .next()
+            .and_then(decode_hex::<1>)
+            .ok_or_else(|| invalid("malformed trace-flags"))?;
+        if version == 0 && fields.next().is_some() {
+            return Err(invalid("unexpected fields after trace-flags"));
+        }
+
+        if trace_id == [0; 16] {
+            return Err(invalid("trace-id is all zeros"));
+        }
+        if span_id == [0; 8] {
+            return Err(invalid("parent-id is all zeros"));
+        }
+
+        Ok(Self {
+            trace_id,
+            span_id,
+            trace_flags,
+        })
+    }
+
+    /// Set the `traceparent` header
+    pub fn inject_into_headers(&self, headers: &mut HashMap<String, String>) {
+        headers.insert(TRACEPARENT_HEADER.to_string(), self.to_traceparent());
+    }
+
+    /// Context of the span the caller is in, as given to it by
+    /// [`TraceContextLayer`]
+    ///
+    /// Returns `None` outside a span, or when the span's subscriber is not
+    /// built on `tracing_subscriber::Registry` with a [`TraceContextLayer`].
+    #[must_use]
+    pub fn from_current_span() -> Option<Self> {
+        tracing::Span::current()
+            .with_subscriber(|(id, dispatch)| {
+                let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+                let span = registry.span(id)?;
+                let extensions = span.extensions();
+                extensions.get::<SpanContext>().map(|context| context.0)
+            })
+            .flatten()
+    }
+
+    /// Continue this trace in `span`, typically with a context read from
+    /// an incoming request's `traceparent` header
+    ///
+    /// `span` takes this trace ID and flags, and so do the spans opened
+    /// under it from then on; its own span ID is kept. Returns `false`,
+    /// changing nothing, when `span` has no context from a
+    /// [`TraceContextLayer`].
+    #[must_use]
+    pub fn continue_in(&self, span: &tracing::Span) -> bool {
+        span.with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+            let span = registry.span(id)?;
+            let mut extensions = span.extensions_mut();
+            let context = &mut extensions.get_mut::<SpanContext>()?.0;
+            context.trace_id = self.trace_id;
+            context.trace_flags = self.trace_flags;
+            Some(())
+        })
+        .flatten()
+        .is_some()
+    }
+}
+
+/// Gives each span a [`TraceContext`] when it is opened
+///
+/// A span opened under another takes its trace ID and flags; any other span
+/// starts a sampled trace with a random trace ID. Every span gets a random
+/// span ID. Unlike `tracing` span IDs, which are reused once a span closes,
+/// these stay unique, so they can be sent to other services.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceContextLayer;
+
+/// Extension holding the [`TraceContext`] of a span
+struct SpanContext(TraceContext);
+
+impl<S> Layer<S> for TraceContextLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanContext>().map(|context| context.0));
+        let context = TraceContext {
+            trace_id: parent.map_or_else(random_id, |parent| parent.trace_id),
+            span_id: random_id(),
+            trace_flags: parent.map_or(TRACE_FLAG_SAMPLED, |parent| parent.trace_flags),
+        };
+        span.extensions_mut().insert(SpanContext(context));
+    }
+}
+
+/// Maps the span ID; an all-zero span ID, which W3C forbids, maps to 1
+impl From<TraceContext> for tracing::span::Id {
+    fn from(context: TraceContext) -> Self {
+        Self::from_u64(u64::from_be_bytes(context.span_id).max(1))
+    }
+}
+
+/// Random ID that is not all zeros, which W3C forbids
+fn random_id<const N: usize>() -> [u8; N] {
+    use rand::RngCore;
+    let mut id = [0; N];
+    while id == [0; N] {
+        rand::thread_rng().fill_bytes(&mut id);
+    }
+    id
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+/// Decode exactly `N` bytes of lowercase hex
+fn decode_hex<const N: usize>(field: &str) -> Option<[u8; N]> {
+    let digits = field.as_bytes();
+    if digits.len() != N * 2 {
+        return None;
+    }
+    let nibble = |digit: u8| match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        _ => None,
+    };
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = nibble(pair[0])? << 4 | nibble(pair[1])?;
+    }
+    Some(bytes)
+}
+
 /// Record a counter metric
 #[macro_export]
 macro_rules! count {
     ($name:expr, $value:expr $(, $key:expr => $val:expr)*) => {
-        metrics::counter!($name $(, $key => $val)*).increment($value);
+        metrics::counter!($name, $value $(, $key => $val)*);
     };
 }
 
@@ -101,7 +304,7 @@ macro_rules! count {
 #[macro_export]
 macro_rules! gauge {
     ($name:expr, $value:expr $(, $key:expr => $val:expr)*) => {
-        metrics::gauge!($name $(, $key => $val)*).set($value);
+        metrics::gauge!($name, $value $(, $key => $val)*);
     };
 }
 
@@ -109,7 +312,7 @@ macro_rules! gauge {
 #[macro_export]
 macro_rules! histogram {
     ($name:expr, $value:expr $(, $key:expr => $val:expr)*) => {
-        metrics::histogram!($name $(, $key => $val)*).record($value);
+        metrics::histogram!($name, $value $(, $key => $val)*);
     };
 }
 
@@ -123,4 +326,114 @@ mod tests {
         assert_eq!(config.service_name, "semantic_notary");
         assert!(!config.enable_tracing);
     }
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::from_traceparent(header).unwrap();
+        assert_eq!(context.trace_id[0], 0x4b);
+        assert_eq!(context.span_id[7], 0xb7);
+        assert_eq!(context.trace_flags, TRACE_FLAG_SAMPLED);
+        assert_eq!(context.to_traceparent(), header);
+
+        let mut headers = HashMap::new();
+        context.inject_into_headers(&mut headers);
+        assert_eq!(headers[TRACEPARENT_HEADER], header);
+        assert_eq!(tracing::span::Id::from(context).into_u64(), 0x00f0_67aa_0ba9_02b7);
+
+        // Later versions may append fields
+        assert!(TraceContext::from_traceparent(&format!("01{}-extra", &header[2..])).is_ok());
+
+        for bad in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        ] {
+            assert!(TraceContext::from_traceparent(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_from_current_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        assert_eq!(TraceContext::from_current_span(), None);
+
+        // Without the layer, spans have no context
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            let _span = tracing::info_span!("untracked").entered();
+            assert_eq!(TraceContext::from_current_span(), None);
+        });
+
+        let subscriber = tracing_subscriber::registry().with(TraceContextLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(TraceContext::from_current_span(), None);
+
+            let root = tracing::info_span!("root");
+            let _root = root.enter();
+            let outer = TraceContext::from_current_span().unwrap();
+            assert_eq!(outer.trace_flags, TRACE_FLAG_SAMPLED);
+
+            let child = tracing::info_span!("child");
+            let _child = child.enter();
+            let inner = TraceContext::from_current_span().unwrap();
+            assert_eq!(inner.trace_id, outer.trace_id);
+            assert_ne!(inner.span_id, outer.span_id);
+        });
+    }
+
+    #[test]
+    fn test_consecutive_root_spans_get_distinct_ids() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(TraceContextLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            // The second span may reuse the `tracing` ID of the first, closed one
+            let first = tracing::info_span!("first");
+            let first_context = first.in_scope(|| TraceContext::from_current_span().unwrap());
+            drop(first);
+
+            let second = tracing::info_span!("second");
+            let second_context = second.in_scope(|| TraceContext::from_current_span().unwrap());
+            assert_ne!(second_context.trace_id, first_context.trace_id);
+            assert_ne!(second_context.span_id, first_context.span_id);
+        });
+    }
+
+    #[test]
+    fn test_continue_remote_trace() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let remote = TraceContext::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00",
+        )
+        .unwrap();
+
+        let untracked = tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            remote.continue_in(&tracing::info_span!("untracked"))
+        });
+        assert!(!untracked);
+
+        let subscriber = tracing_subscriber::registry().with(TraceContextLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request");
+            assert!(remote.continue_in(&request));
+            let _request = request.enter();
+            let context = TraceContext::from_current_span().unwrap();
+            assert_eq!(context.trace_id, remote.trace_id);
+            assert_eq!(context.trace_flags, remote.trace_flags);
+            assert_ne!(context.span_id, remote.span_id);
+
+            let _child = tracing::info_span!("child").entered();
+            let child = TraceContext::from_current_span().unwrap();
+            assert_eq!(child.trace_id, remote.trace_id);
+            assert_eq!(child.trace_flags, remote.trace_flags);
+            assert_ne!(child.span_id, context.span_id);
+        });
+    }
 }
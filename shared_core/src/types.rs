@@ -2,8 +2,11 @@
 //!
 //! This module defines common types and traits used across all systems.
 
+use crate::error::{Result, SystemError};
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 
 /// Unique identifier type
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -16,6 +19,7 @@ impl Id {
     }
 
     /// Generate a new random ID
+    #[allow(clippy::must_use_candidate, clippy::uninlined_format_args)]
     pub fn generate() -> Self {
         use rand::Rng;
         let random: u128 = rand::thread_rng().gen();
@@ -23,11 +27,75 @@ impl Id {
     }
 
     /// Get the ID as a string slice
+    #[allow(clippy::must_use_candidate)]
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Encode a generated (32-digit hex) ID in base 62, at most 22 characters
+    pub fn to_base62(&self) -> Result<String> {
+        let mut value = self.hex_value().ok_or_else(|| {
+            SystemError::validation("id", "not a 128-bit hex ID", Some(self.0.clone()))
+        })?;
+        if value == 0 {
+            return Ok("0".to_string());
+        }
+        let mut digits = Vec::with_capacity(22);
+        while value > 0 {
+            // The remainder is below 62, so its low byte is the whole value
+            let index = (value % 62).to_le_bytes()[0];
+            digits.push(BASE62_ALPHABET[usize::from(index)]);
+            value /= 62;
+        }
+        digits.reverse();
+        Ok(digits.into_iter().map(char::from).collect())
+    }
+
+    /// Decode an ID from [`Id::to_base62`] output or its 32-digit hex form
+    ///
+    /// Exactly 32 lowercase hex digits are read as hex, anything else as
+    /// base 62. The result is always the hex form of the ID.
+    pub fn from_base62(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| SystemError::validation("id", reason, Some(s.to_string()));
+        let hex = Self(s.to_string());
+        if hex.hex_value().is_some() {
+            return Ok(hex);
+        }
+        if s.is_empty() {
+            return Err(invalid("empty base-62 ID"));
+        }
+
+        let mut value: u128 = 0;
+        for byte in s.bytes() {
+            let digit = match byte {
+                b'0'..=b'9' => byte - b'0',
+                b'A'..=b'Z' => byte - b'A' + 10,
+                b'a'..=b'z' => byte - b'a' + 36,
+                _ => return Err(invalid("invalid base-62 character")),
+            };
+            value = value
+                .checked_mul(62)
+                .and_then(|v| v.checked_add(u128::from(digit)))
+                .ok_or_else(|| invalid("base-62 ID exceeds 128 bits"))?;
+        }
+        Ok(Self(format!("{value:032x}")))
+    }
+
+    /// Value of an ID in the format produced by [`Id::generate`]
+    fn hex_value(&self) -> Option<u128> {
+        let is_hex = self.0.len() == 32
+            && self
+                .0
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+        is_hex.then(|| u128::from_str_radix(&self.0, 16).ok()).flatten()
+    }
 }
 
+/// Digits of [`Id::to_base62`], in value order
+const BASE62_ALPHABET: &[u8; 62] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -47,38 +115,127 @@ impl From<&str> for Id {
 }
 
 /// Timestamp in milliseconds since Unix epoch
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub struct Timestamp(u64);
+///
+/// Timestamps from [`Timestamp::monotonic`] instead count milliseconds since
+/// program start. Only the millisecond value is serialized, so deserialized
+/// timestamps are always treated as system-clock timestamps. Likewise only
+/// the millisecond value is compared, so that a timestamp equals itself
+/// after a round trip.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub struct Timestamp {
+    millis: u64,
+    monotonic: bool,
+}
+
+/// Reference point for monotonic timestamps, fixed on first use
+static PROGRAM_START: LazyLock<Instant> = LazyLock::new(Instant::now);
 
 impl Timestamp {
     /// Create a timestamp from milliseconds
+    #[must_use]
     pub fn from_millis(millis: u64) -> Self {
-        Self(millis)
+        Self {
+            millis,
+            monotonic: false,
+        }
     }
 
     /// Get the current timestamp
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system clock is set before the Unix epoch.
+    #[must_use]
     pub fn now() -> Self {
         use std::time::{SystemTime, UNIX_EPOCH};
         let duration = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards");
-        Self(duration.as_millis() as u64)
+        Self::from_millis(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    /// Get a timestamp from the monotonic clock
+    ///
+    /// Unlike [`Timestamp::now`], this never goes backwards when the system
+    /// clock is adjusted. The value is only meaningful within this process.
+    #[must_use]
+    pub fn monotonic() -> Self {
+        let elapsed = PROGRAM_START.elapsed().as_millis();
+        Self {
+            millis: u64::try_from(elapsed).unwrap_or(u64::MAX),
+            monotonic: true,
+        }
+    }
+
+    /// Whether this timestamp came from [`Timestamp::monotonic`]
+    #[must_use]
+    pub fn is_monotonic(&self) -> bool {
+        self.monotonic
+    }
+
+    /// Time elapsed between `earlier` and this timestamp
+    ///
+    /// Returns `None` if `earlier` is later than `self`, or if one timestamp
+    /// is monotonic and the other is not.
+    #[must_use]
+    pub fn elapsed_since(&self, earlier: Timestamp) -> Option<Duration> {
+        if self.monotonic != earlier.monotonic {
+            return None;
+        }
+        self.millis
+            .checked_sub(earlier.millis)
+            .map(Duration::from_millis)
     }
 
     /// Get the timestamp as milliseconds
+    #[must_use]
     pub fn as_millis(&self) -> u64 {
-        self.0
+        self.millis
     }
 
     /// Get the timestamp as seconds
+    #[must_use]
     pub fn as_secs(&self) -> u64 {
-        self.0 / 1000
+        self.millis / 1000
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.millis == other.millis
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.millis.cmp(&other.millis)
+    }
+}
+
+impl From<u64> for Timestamp {
+    fn from(millis: u64) -> Self {
+        Self::from_millis(millis)
+    }
+}
+
+impl From<Timestamp> for u64 {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.millis
     }
 }
 
 impl fmt::Display for Timestamp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.millis)
     }
 }
 
@@ -95,11 +252,24 @@ pub struct Version {
 
 impl Version {
     /// Create a new version
+    #[must_use]
     pub fn new(major: u32, minor: u32, patch: u32) -> Self {
         Self { major, minor, patch }
     }
 
+    /// Whether the version is within `range`
+    #[must_use]
+    pub fn satisfies_range(&self, range: &VersionRange) -> bool {
+        range.satisfies(self)
+    }
+
+    /// The next patch version
+    fn next_patch(&self) -> Self {
+        Self::new(self.major, self.minor, self.patch.saturating_add(1))
+    }
+
     /// Parse a version from a string (e.g., "1.2.3")
+    #[must_use]
     pub fn parse(s: &str) -> Option<Self> {
         let parts: Vec<&str> = s.split('.').collect();
         if parts.len() != 3 {
@@ -120,6 +290,113 @@ impl fmt::Display for Version {
     }
 }
 
+/// Range of versions, from an inclusive minimum to a maximum
+///
+/// Either bound may be absent. Parsed from npm-style ranges, see
+/// [`VersionRange::parse`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionRange {
+    /// Lowest version in the range
+    pub min: Option<Version>,
+    /// Bound above every version in the range
+    pub max: Option<Version>,
+    /// Whether `max` itself is in the range
+    pub include_max: bool,
+}
+
+impl VersionRange {
+    /// Parse a range such as `>=1.0.0 <2.0.0`, `^1.2.3` or `~1.2.3`
+    ///
+    /// A range is whitespace-separated comparators, all of which a version
+    /// must satisfy: `>=`, `>`, `<=`, `<` or `=` followed by a version, a
+    /// bare version for that version only, `^v` for versions compatible
+    /// with `v` (below the next major version, or the next minor one for
+    /// `0.x`), `~v` for `v` up to the next minor version, and `*` for any
+    /// version. An empty range contains every version.
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| SystemError::validation("version_range", reason, Some(s.to_string()));
+        let mut range = Self::default();
+        for comparator in s.split_whitespace() {
+            if comparator == "*" {
+                continue;
+            }
+            let (operator, version) = comparator
+                .find(|c: char| c.is_ascii_digit())
+                .map(|at| comparator.split_at(at))
+                .ok_or_else(|| invalid("expected a version"))?;
+            let version = Version::parse(version).ok_or_else(|| invalid("bad version"))?;
+            let (min, max) = match operator {
+                ">=" => (Some(version), None),
+                ">" => (Some(version.next_patch()), None),
+                "<=" => (None, Some((version, true))),
+                "<" => (None, Some((version, false))),
+                "" | "=" => (Some(version.clone()), Some((version, true))),
+                "^" => {
+                    let below = match (version.major, version.minor) {
+                        (0, 0) => version.next_patch(),
+                        (0, minor) => Version::new(0, minor.saturating_add(1), 0),
+                        (major, _) => Version::new(major.saturating_add(1), 0, 0),
+                    };
+                    (Some(version), Some((below, false)))
+                },
+                "~" => {
+                    let below = Version::new(version.major, version.minor.saturating_add(1), 0);
+                    (Some(version), Some((below, false)))
+                },
+                _ => return Err(invalid("unknown operator")),
+            };
+            range.narrow(min, max);
+        }
+        Ok(range)
+    }
+
+    /// Whether `version` is in the range
+    #[must_use]
+    pub fn satisfies(&self, version: &Version) -> bool {
+        let above_min = self.min.as_ref().map_or(true, |min| version >= min);
+        let below_max = self.max.as_ref().map_or(true, |max| {
+            version < max || (self.include_max && version == max)
+        });
+        above_min && below_max
+    }
+
+    /// Intersect the range with the one between `min` and `max`
+    fn narrow(&mut self, min: Option<Version>, max: Option<(Version, bool)>) {
+        if let Some(min) = min {
+            if self.min.as_ref().map_or(true, |current| min > *current) {
+                self.min = Some(min);
+            }
+        }
+        if let Some((max, include_max)) = max {
+            let narrower = match &self.max {
+                None => true,
+                Some(current) => max < *current || (max == *current && !include_max),
+            };
+            if narrower {
+                self.max = Some(max);
+                self.include_max = include_max;
+            }
+        }
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.min, &self.max) {
+            (None, None) => f.write_str("*"),
+            (Some(min), None) => write!(f, ">={min}"),
+            (min, Some(max)) => {
+                if let Some(min) = min {
+                    write!(f, ">={min} ")?;
+                }
+                let operator = if self.include_max { "<=" } else { "<" };
+                write!(f, "{operator}{max}")
+            },
+        }
+    }
+}
+
 /// Lifecycle state for stateful systems
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LifecycleState {
@@ -159,12 +436,19 @@ pub enum HealthStatus {
     /// System is healthy
     Healthy,
     /// System is degraded but operational
-    Degraded { reason: String },
+    Degraded {
+        /// Why the system is degraded
+        reason: String,
+    },
     /// System is unhealthy
-    Unhealthy { reason: String },
+    Unhealthy {
+        /// Why the system is unhealthy
+        reason: String,
+    },
 }
 
 impl fmt::Display for HealthStatus {
+    #[allow(clippy::uninlined_format_args)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Healthy => write!(f, "healthy"),
@@ -187,6 +471,31 @@ mod tests {
         assert_eq!(id1.as_str().len(), 32);
     }
 
+    #[test]
+    fn test_id_base62() {
+        let max = Id::new("f".repeat(32));
+        assert_eq!(max.to_base62().unwrap(), "7n42DGM5Tflk9n8mt7Fhc7");
+        assert_eq!(Id::new("0".repeat(32)).to_base62().unwrap(), "0");
+        assert_eq!(Id::from_base62("7n42DGM5Tflk9n8mt7Fhc7").unwrap(), max);
+        assert_eq!(Id::from_base62(max.as_str()).unwrap(), max);
+
+        assert!(Id::new("user-1").to_base62().is_err());
+        assert!(Id::from_base62("").is_err());
+        assert!(Id::from_base62("abc-def").is_err());
+        assert!(Id::from_base62("7n42DGM5Tflk9n8mt7Fhc8").is_err());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_base62_round_trip(value: u128) {
+            let id = Id::new(format!("{value:032x}"));
+            let encoded = id.to_base62().unwrap();
+            proptest::prop_assert!(encoded.len() <= 22);
+            proptest::prop_assert!(encoded.bytes().all(|b| BASE62_ALPHABET.contains(&b)));
+            proptest::prop_assert_eq!(Id::from_base62(&encoded).unwrap(), id);
+        }
+    }
+
     #[test]
     fn test_timestamp() {
         let ts1 = Timestamp::now();
@@ -196,6 +505,31 @@ mod tests {
         assert!(ts2 > ts1);
     }
 
+    #[test]
+    fn test_monotonic_timestamp() {
+        let t1 = Timestamp::monotonic();
+        std::thread::sleep(Duration::from_millis(10));
+        let t2 = Timestamp::monotonic();
+
+        assert!(t1.is_monotonic());
+        assert!(!Timestamp::now().is_monotonic());
+        assert!(t2.elapsed_since(t1).unwrap() >= Duration::from_millis(10));
+        assert_eq!(t1.elapsed_since(t2), None);
+        assert_eq!(t2.elapsed_since(Timestamp::now()), None);
+    }
+
+    #[test]
+    fn test_timestamp_serializes_as_millis() {
+        let monotonic = Timestamp::monotonic();
+        let json = serde_json::to_string(&monotonic).unwrap();
+        let restored: Timestamp = serde_json::from_str(&json).unwrap();
+        assert!(!restored.is_monotonic());
+        assert_eq!(restored, monotonic);
+        assert_eq!(restored.cmp(&monotonic), std::cmp::Ordering::Equal);
+
+        assert_eq!(serde_json::to_string(&Timestamp::from_millis(42)).unwrap(), "42");
+    }
+
     #[test]
     fn test_version_parsing() {
         let version = Version::parse("1.2.3").unwrap();
@@ -205,6 +539,45 @@ mod tests {
         assert_eq!(version.to_string(), "1.2.3");
     }
 
+    #[test]
+    fn test_version_ranges() {
+        let v = |s| Version::parse(s).unwrap();
+        let range = VersionRange::parse(">=1.0.0 <2.0.0").unwrap();
+        assert!(v("1.0.0").satisfies_range(&range));
+        assert!(v("1.9.9").satisfies_range(&range));
+        assert!(!v("2.0.0").satisfies_range(&range));
+        assert!(!v("0.9.0").satisfies_range(&range));
+        assert_eq!(range.to_string(), ">=1.0.0 <2.0.0");
+
+        let caret = VersionRange::parse("^1.2.3").unwrap();
+        assert_eq!(caret, range_of("1.2.3", "2.0.0"));
+        assert_eq!(VersionRange::parse("^0.2.3").unwrap(), range_of("0.2.3", "0.3.0"));
+        assert_eq!(VersionRange::parse("^0.0.3").unwrap(), range_of("0.0.3", "0.0.4"));
+        assert_eq!(VersionRange::parse("~1.2.3").unwrap(), range_of("1.2.3", "1.3.0"));
+
+        // Comparators intersect
+        let range = VersionRange::parse("^1.2.0 <=1.4.0 >1.3.0").unwrap();
+        assert_eq!(range.to_string(), ">=1.3.1 <=1.4.0");
+        assert!(range.satisfies(&v("1.4.0")));
+        assert!(!range.satisfies(&v("1.3.0")));
+        let exact = VersionRange::parse("=1.2.3").unwrap();
+        assert!(exact.satisfies(&v("1.2.3")) && !exact.satisfies(&v("1.2.4")));
+        assert_eq!(VersionRange::parse("1.2.3").unwrap(), exact);
+        assert_eq!(VersionRange::parse(" * ").unwrap().to_string(), "*");
+
+        for bad in ["=>1.0.0", ">=1.0", "^", "1.x.0"] {
+            assert!(VersionRange::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    fn range_of(min: &str, below: &str) -> VersionRange {
+        VersionRange {
+            min: Version::parse(min),
+            max: Version::parse(below),
+            include_max: false,
+        }
+    }
+
     #[test]
     fn test_version_comparison() {
         let v1 = Version::new(1, 0, 0);
@@ -5,8 +5,6 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
-
 pub mod api;
 pub mod config;
 pub mod core;
@@ -14,16 +12,29 @@ pub mod optimization;
 pub mod reduction;
 pub mod symbolic;
 
+pub use reduction::{Reducer, ReductionStep, ReductionTrace, RewriteRule};
+pub use symbolic::Term;
+
 /// Modeler configuration
 #[derive(Debug, Clone)]
 pub struct ModelerConfig {
     /// Enable optimizations
     pub optimize: bool,
+    /// Record the rules applied by [`Reducer::reduce_traced`]
+    ///
+    /// Off by default, so that reductions allocate no trace.
+    pub enable_trace_collection: bool,
+    /// Most rewrite steps a reduction may take to reach a normal form
+    pub max_reduction_steps: usize,
 }
 
 impl Default for ModelerConfig {
     fn default() -> Self {
-        Self { optimize: true }
+        Self {
+            optimize: true,
+            enable_trace_collection: false,
+            max_reduction_steps: 10_000,
+        }
     }
 }
 
@@ -1 +1,316 @@
 //! Reduction module
+//!
+//! A [`Reducer`] rewrites a [`Term`] with its [`RewriteRule`]s until none
+//! applies. [`Reducer::reduce_traced`] also returns the
+//! [`ReductionTrace`] of the rules applied, for debugging rule sets.
+
+use std::fmt::Write;
+
+use shared_core::{Result, SystemError};
+
+use crate::symbolic::Term;
+use crate::ModelerConfig;
+
+/// What each variable of a rule's left side matched
+type Bindings = Vec<(String, Term)>;
+
+/// Rewrites terms matching `lhs` into `rhs`
+///
+/// Variables of `lhs` match any term, the same term wherever a variable
+/// occurs more than once, and are replaced by what they matched in `rhs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewriteRule {
+    name: String,
+    lhs: Term,
+    rhs: Term,
+}
+
+impl RewriteRule {
+    /// Create a rule rewriting `lhs` into `rhs`
+    ///
+    /// Fails with a `Validation` error if `rhs` has a variable `lhs` does
+    /// not bind.
+    pub fn new(name: impl Into<String>, lhs: Term, rhs: Term) -> Result<Self> {
+        let name = name.into();
+        let bound = lhs.variables();
+        if let Some(unbound) = rhs.variables().into_iter().find(|var| !bound.contains(var)) {
+            return Err(SystemError::validation(
+                "rhs",
+                format!("variable {unbound} of rule {name} is not bound by its left side"),
+                Some(rhs.to_string()),
+            ));
+        }
+        Ok(Self { name, lhs, rhs })
+    }
+
+    /// Name of the rule
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What each variable of the left side matched, if `term` matches it
+    pub fn matches(&self, term: &Term) -> Option<Vec<(String, Term)>> {
+        let mut bindings = Vec::new();
+        bind(&self.lhs, term, &mut bindings).then_some(bindings)
+    }
+}
+
+fn bind(pattern: &Term, term: &Term, bindings: &mut Bindings) -> bool {
+    match (pattern, term) {
+        (Term::Var(name), _) => match bindings.iter().find(|(bound, _)| bound == name) {
+            Some((_, bound)) => bound == term,
+            None => {
+                bindings.push((name.clone(), term.clone()));
+                true
+            },
+        },
+        (Term::Num(a), Term::Num(b)) => a == b,
+        (Term::App(op, args), Term::App(term_op, term_args)) => {
+            op == term_op
+                && args.len() == term_args.len()
+                && args.iter().zip(term_args).all(|(arg, term)| bind(arg, term, bindings))
+        },
+        _ => false,
+    }
+}
+
+/// One rule application of a reduction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReductionStep {
+    /// Rule applied
+    pub rule_name: String,
+    /// Whole term before the step
+    pub before: Term,
+    /// Whole term after the step
+    pub after: Term,
+    /// What each variable of the rule's left side matched
+    pub matches: Vec<(String, Term)>,
+}
+
+/// Rules applied by a reduction, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReductionTrace {
+    /// Steps of the reduction
+    pub steps: Vec<ReductionStep>,
+}
+
+impl ReductionTrace {
+    /// Render the trace as a Graphviz DOT graph
+    ///
+    /// Each node is a term, from the initial term to the normal form, and
+    /// each edge is labelled with the rule rewriting one into the next.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph reduction {\n    node [shape=box];\n");
+        let terms = self.steps.first().map(|step| &step.before).into_iter();
+        let terms = terms.chain(self.steps.iter().map(|step| &step.after));
+        for (i, term) in terms.enumerate() {
+            let _ = writeln!(dot, "    t{i} [label={}];", quote(&term.to_string()));
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            let _ = writeln!(dot, "    t{i} -> t{} [label={}];", i + 1, quote(&step.rule_name));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// `text` as a quoted DOT string
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Rewrites terms to normal form
+///
+/// At each step the first rule matching the outermost, leftmost subterm is
+/// applied, until no rule matches anywhere in the term.
+#[derive(Debug, Clone, Default)]
+pub struct Reducer {
+    config: ModelerConfig,
+    rules: Vec<RewriteRule>,
+}
+
+impl Reducer {
+    /// Create a reducer with no rules
+    pub fn new(config: ModelerConfig) -> Self {
+        Self {
+            config,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a rule, tried after those already added
+    pub fn with_rule(mut self, rule: RewriteRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Rules of the reducer, in the order they are tried
+    pub fn rules(&self) -> &[RewriteRule] {
+        &self.rules
+    }
+
+    /// Reduce `term` to normal form
+    ///
+    /// Fails if no normal form is reached within
+    /// [`ModelerConfig::max_reduction_steps`] steps.
+    pub fn reduce(&self, term: Term) -> Result<Term> {
+        self.run(term, None)
+    }
+
+    /// Reduce `term` to normal form as [`reduce`](Self::reduce) does,
+    /// returning the rules applied along the way
+    ///
+    /// The trace is left empty unless
+    /// [`ModelerConfig::enable_trace_collection`] is set.
+    pub fn reduce_traced(&self, term: Term) -> Result<(Term, ReductionTrace)> {
+        let mut trace = ReductionTrace::default();
+        let collect = self.config.enable_trace_collection.then_some(&mut trace);
+        let term = self.run(term, collect)?;
+        Ok((term, trace))
+    }
+
+    fn run(&self, mut term: Term, mut trace: Option<&mut ReductionTrace>) -> Result<Term> {
+        for _ in 0..self.config.max_reduction_steps {
+            let Some((rule, matches, after)) = self.rewrite(&term) else {
+                return Ok(term);
+            };
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.steps.push(ReductionStep {
+                    rule_name: rule.name.clone(),
+                    before: term,
+                    after: after.clone(),
+                    matches,
+                });
+            }
+            term = after;
+        }
+        if self.rewrite(&term).is_none() {
+            return Ok(term);
+        }
+        Err(SystemError::SystemSpecific {
+            system: "symbolic_reduction_modeler".to_string(),
+            message: format!(
+                "no normal form within {} reduction steps",
+                self.config.max_reduction_steps
+            ),
+            context: Some(term.to_string()),
+        })
+    }
+
+    /// Rule applied to the outermost, leftmost redex of `term`, what it
+    /// matched, and the rewritten term
+    fn rewrite(&self, term: &Term) -> Option<(&RewriteRule, Bindings, Term)> {
+        for rule in &self.rules {
+            if let Some(matches) = rule.matches(term) {
+                let rewritten = rule.rhs.substitute(&matches);
+                return Some((rule, matches, rewritten));
+            }
+        }
+        let Term::App(op, args) = term else {
+            return None;
+        };
+        args.iter().enumerate().find_map(|(i, arg)| {
+            let (rule, matches, rewritten) = self.rewrite(arg)?;
+            let mut args = args.clone();
+            args[i] = rewritten;
+            Some((rule, matches, Term::App(op.clone(), args)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn x() -> Term {
+        Term::var("x")
+    }
+
+    fn rule(name: &str, op: &str, operand: i64, rhs: Term) -> RewriteRule {
+        RewriteRule::new(name, Term::app(op, [x(), operand.into()]), rhs).unwrap()
+    }
+
+    fn arithmetic(config: ModelerConfig) -> Reducer {
+        Reducer::new(config)
+            .with_rule(rule("add-zero", "add", 0, x()))
+            .with_rule(rule("mul-one", "mul", 1, x()))
+            .with_rule(rule("mul-zero", "mul", 0, 0.into()))
+    }
+
+    fn tracing() -> ModelerConfig {
+        ModelerConfig {
+            enable_trace_collection: true,
+            ..ModelerConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_reduce_to_normal_form() {
+        let term = Term::app("add", [Term::app("mul", [Term::var("y"), 1.into()]), 0.into()]);
+        assert_eq!(arithmetic(ModelerConfig::default()).reduce(term).unwrap(), Term::var("y"));
+
+        let stuck = Term::app("sub", [Term::var("y"), 0.into()]);
+        assert_eq!(arithmetic(ModelerConfig::default()).reduce(stuck.clone()).unwrap(), stuck);
+    }
+
+    #[test]
+    fn test_trace_records_each_rule_application() {
+        let inner = Term::app("mul", [Term::var("y"), 1.into()]);
+        let term = Term::app("add", [inner.clone(), 0.into()]);
+        let (normal, trace) = arithmetic(tracing()).reduce_traced(term.clone()).unwrap();
+        assert_eq!(normal, Term::var("y"));
+
+        let rules: Vec<&str> = trace.steps.iter().map(|step| step.rule_name.as_str()).collect();
+        assert_eq!(rules, ["add-zero", "mul-one"]);
+        assert_eq!(trace.steps[0].before, term);
+        assert_eq!(trace.steps[0].after, inner);
+        assert_eq!(trace.steps[0].matches, [("x".to_string(), inner.clone())]);
+        assert_eq!(trace.steps[1].before, inner);
+        assert_eq!(trace.steps[1].after, normal);
+
+        let dot = trace.to_dot();
+        assert!(dot.starts_with("digraph reduction {"));
+        assert!(dot.contains("t0 [label=\"add(mul(y, 1), 0)\"];"), "{dot}");
+        assert!(dot.contains("t2 [label=\"y\"];"), "{dot}");
+        assert!(dot.contains("t0 -> t1 [label=\"add-zero\"];"), "{dot}");
+        assert!(dot.contains("t1 -> t2 [label=\"mul-one\"];"), "{dot}");
+    }
+
+    #[test]
+    fn test_trace_collection_is_opt_in() {
+        let term = Term::app("add", [x(), 0.into()]);
+        let (normal, trace) =
+            arithmetic(ModelerConfig::default()).reduce_traced(term).unwrap();
+        assert_eq!(normal, x());
+        assert!(trace.steps.is_empty());
+        assert_eq!(trace.to_dot(), "digraph reduction {\n    node [shape=box];\n}\n");
+    }
+
+    #[test]
+    fn test_nonlinear_patterns_and_unbound_variables() {
+        let sub_self =
+            RewriteRule::new("sub-self", Term::app("sub", [x(), x()]), 0.into()).unwrap();
+        assert!(sub_self.matches(&Term::app("sub", [Term::var("y"), Term::var("y")])).is_some());
+        assert!(sub_self.matches(&Term::app("sub", [Term::var("y"), Term::var("z")])).is_none());
+
+        let unbound = RewriteRule::new("bad", Term::app("neg", [x()]), Term::var("y"));
+        assert!(matches!(unbound, Err(SystemError::Validation { .. })));
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let commute = RewriteRule::new(
+            "commute",
+            Term::app("add", [x(), Term::var("y")]),
+            Term::app("add", [Term::var("y"), x()]),
+        )
+        .unwrap();
+        let config = ModelerConfig {
+            max_reduction_steps: 10,
+            ..tracing()
+        };
+        let looping = Reducer::new(config).with_rule(commute);
+        let term = Term::app("add", [Term::var("a"), Term::var("b")]);
+        assert!(matches!(looping.reduce_traced(term), Err(SystemError::SystemSpecific { .. })));
+    }
+}
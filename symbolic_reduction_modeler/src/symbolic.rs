@@ -1 +1,86 @@
 //! Symbolic module
+//!
+//! [`Term`]s are the expressions the modeler reduces: variables, integer
+//! constants and operators applied to arguments, as in `add(x, 0)`.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Symbolic expression
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Term {
+    /// Variable, which in the left side of a rule matches any term
+    Var(String),
+    /// Integer constant
+    Num(i64),
+    /// Operator applied to arguments
+    App(String, Vec<Term>),
+}
+
+impl Term {
+    /// Variable named `name`
+    pub fn var(name: impl Into<String>) -> Self {
+        Self::Var(name.into())
+    }
+
+    /// Operator `op` applied to `args`
+    pub fn app(op: impl Into<String>, args: impl IntoIterator<Item = Term>) -> Self {
+        Self::App(op.into(), args.into_iter().collect())
+    }
+
+    /// Names of the variables of the term, in order of first occurrence
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Self::Var(name) if !names.contains(&name.as_str()) => names.push(name),
+            Self::App(_, args) => args.iter().for_each(|arg| arg.collect_variables(names)),
+            _ => {},
+        }
+    }
+
+    /// The term with each variable bound in `bindings` replaced by its
+    /// binding
+    pub fn substitute(&self, bindings: &[(String, Term)]) -> Term {
+        match self {
+            Self::Var(name) => bindings
+                .iter()
+                .find(|(bound, _)| bound == name)
+                .map_or_else(|| self.clone(), |(_, term)| term.clone()),
+            Self::Num(_) => self.clone(),
+            Self::App(op, args) => {
+                Self::App(op.clone(), args.iter().map(|arg| arg.substitute(bindings)).collect())
+            },
+        }
+    }
+}
+
+impl From<i64> for Term {
+    fn from(value: i64) -> Self {
+        Self::Num(value)
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Var(name) => write!(f, "{name}"),
+            Self::Num(value) => write!(f, "{value}"),
+            Self::App(op, args) => {
+                write!(f, "{op}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            },
+        }
+    }
+}
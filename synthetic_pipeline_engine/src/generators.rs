@@ -1 +1,132 @@
 //! Generators module
+//!
+//! Stages that add synthetic values to records.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use shared_core::{Result, Timestamp};
+
+use crate::pipeline::{PipelineData, Stage};
+
+/// Field [`TimestampSequenceGenerator`] sets
+pub const TIMESTAMP_FIELD: &str = "timestamp";
+
+/// Stage setting the `"timestamp"` field of each record to the next of a
+/// jittered sequence, as milliseconds since the Unix epoch
+///
+/// Record `n` is stamped `start + n * interval`, moved by a random jitter in
+/// `[-jitter_ms, +jitter_ms]`. Timestamps never decrease: one that would
+/// fall before the previous timestamp, or before `start` for the first
+/// record, is clamped to it. The jitter is drawn from a generator seeded
+/// like the deterministic one of a
+/// [`ResourceGovernor`](shared_core::ResourceGovernor), so a seed always
+/// gives the same sequence.
+pub struct TimestampSequenceGenerator {
+    start: Timestamp,
+    interval: Duration,
+    jitter_ms: u64,
+    sequence: Mutex<Sequence>,
+}
+
+/// Position in the sequence
+struct Sequence {
+    rng: StdRng,
+    /// Index of the next record
+    next: u64,
+    /// Last timestamp handed out
+    last: Option<u64>,
+}
+
+impl TimestampSequenceGenerator {
+    /// Create a generator whose jitter is drawn from a generator seeded with
+    /// `seed`
+    pub fn new(start: Timestamp, interval: Duration, jitter_ms: u64, seed: u64) -> Self {
+        Self {
+            start,
+            interval,
+            jitter_ms,
+            sequence: Mutex::new(Sequence {
+                rng: StdRng::seed_from_u64(seed),
+                next: 0,
+                last: None,
+            }),
+        }
+    }
+
+    /// Records holding only the next `count` timestamps of the sequence
+    pub fn generate_series(&self, count: usize) -> Vec<PipelineData> {
+        (0..count)
+            .map(|_| PipelineData::new().with(TIMESTAMP_FIELD, self.next_timestamp().as_millis()))
+            .collect()
+    }
+
+    fn next_timestamp(&self) -> Timestamp {
+        let mut sequence = self.sequence.lock();
+        let interval = u64::try_from(self.interval.as_millis()).unwrap_or(u64::MAX);
+        let nominal = self.start.as_millis().saturating_add(interval.saturating_mul(sequence.next));
+        let jitter = i128::from(self.jitter_ms);
+        let jitter = sequence.rng.gen_range(-jitter..=jitter);
+        let jittered = (i128::from(nominal) + jitter).clamp(0, i128::from(u64::MAX));
+        let floor = sequence.last.unwrap_or(self.start.as_millis());
+        let millis = u64::try_from(jittered).unwrap_or(u64::MAX).max(floor);
+        sequence.next += 1;
+        sequence.last = Some(millis);
+        Timestamp::from_millis(millis)
+    }
+}
+
+#[async_trait]
+impl Stage for TimestampSequenceGenerator {
+    fn name(&self) -> &str {
+        "timestamp_sequence"
+    }
+
+    async fn process(&self, data: PipelineData) -> Result<PipelineData> {
+        Ok(data.with(TIMESTAMP_FIELD, self.next_timestamp().as_millis()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(series: &[PipelineData]) -> Vec<u64> {
+        series.iter().map(|data| data.get(TIMESTAMP_FIELD).unwrap().as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_series_is_monotonic_and_jittered() {
+        let start = Timestamp::from_millis(1_000_000);
+        // Jitter wider than the interval would reorder records unclamped
+        let generator = TimestampSequenceGenerator::new(start, Duration::from_millis(10), 25, 7);
+        let series = millis(&generator.generate_series(500));
+        assert!(series[0] >= start.as_millis());
+        assert!(series.windows(2).all(|pair| pair[0] <= pair[1]));
+        for (n, &millis) in (0u64..).zip(&series) {
+            let nominal = start.as_millis() + n * 10;
+            // Clamping only ever moves a timestamp later
+            assert!(millis + 25 >= nominal, "record {n} at {millis}");
+        }
+        assert!(series.iter().zip(0u64..).any(|(&millis, n)| millis != 1_000_000 + n * 10));
+
+        let same = TimestampSequenceGenerator::new(start, Duration::from_millis(10), 25, 7);
+        assert_eq!(millis(&same.generate_series(500)), series);
+        let exact = TimestampSequenceGenerator::new(start, Duration::from_secs(1), 0, 7);
+        assert_eq!(millis(&exact.generate_series(3)), [1_000_000, 1_001_000, 1_002_000]);
+    }
+
+    #[tokio::test]
+    async fn test_process_continues_the_sequence() {
+        let start = Timestamp::from_millis(0);
+        let generator = TimestampSequenceGenerator::new(start, Duration::from_millis(5), 0, 1);
+        let data = generator.process(PipelineData::new().with("id", 1)).await.unwrap();
+        assert_eq!(data.get("id"), Some(&1.into()));
+        assert_eq!(data.get(TIMESTAMP_FIELD), Some(&0.into()));
+        assert_eq!(millis(&generator.generate_series(2)), [5, 10]);
+        assert_eq!(generator.name(), "timestamp_sequence");
+    }
+}
@@ -1 +1,695 @@
 //! Pipeline module
+//!
+//! A [`Pipeline`] is a sequence of [`Stage`]s applied to each
+//! [`PipelineData`] record. [`Pipeline::split`] fans records out to parallel
+//! branches whose outputs are merged back by [`JoinHandle::join`].
+//!
+//! [`Pipeline::run`] streams records through the pipeline with one task per
+//! stage and per branch, connected by bounded `mpsc` channels.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::future::{join_all, select_ok};
+use futures::FutureExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+use tokio::sync::mpsc;
+
+/// Capacity of the channels between stages
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A record flowing through a pipeline
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineData {
+    /// Named field values
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl PipelineData {
+    /// Create an empty record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a field
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.fields.insert(name.into(), value.into());
+        self
+    }
+
+    /// Get a field
+    pub fn get(&self, name: &str) -> Option<&serde_json::Value> {
+        self.fields.get(name)
+    }
+}
+
+/// One processing step
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait Stage: Send + Sync {
+    /// Stage name, used in logs
+    fn name(&self) -> &str;
+
+    /// Transform one record
+    async fn process(&self, data: PipelineData) -> Result<PipelineData>;
+}
+
+/// Stage wrapping a synchronous function
+struct FnStage<F> {
+    name: String,
+    f: F,
+}
+
+#[async_trait]
+impl<F> Stage for FnStage<F>
+where
+    F: Fn(PipelineData) -> Result<PipelineData> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn process(&self, data: PipelineData) -> Result<PipelineData> {
+        (self.f)(data)
+    }
+}
+
+fn fn_stage<F>(name: impl Into<String>, f: F) -> Arc<dyn Stage>
+where
+    F: Fn(PipelineData) -> Result<PipelineData> + Send + Sync + 'static,
+{
+    Arc::new(FnStage {
+        name: name.into(),
+        f,
+    })
+}
+
+/// How branch outputs for a record are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinStrategy {
+    /// Wait for every branch and union their fields; on conflicts the branch
+    /// with the higher index wins
+    Merge,
+    /// Take the output of the first branch to finish and drop the others
+    First,
+    /// Wait for every branch and nest each branch's fields under
+    /// `branch_<index>`
+    Zip,
+}
+
+impl JoinStrategy {
+    /// Combine complete branch outputs, in branch order
+    fn combine(self, outputs: Vec<PipelineData>) -> PipelineData {
+        let mut joined = PipelineData::new();
+        match self {
+            Self::Merge | Self::First => {
+                for output in outputs {
+                    joined.fields.extend(output.fields);
+                }
+            },
+            Self::Zip => {
+                for (index, output) in outputs.into_iter().enumerate() {
+                    joined.fields.insert(format!("branch_{index}"), output.fields.into());
+                }
+            },
+        }
+        joined
+    }
+}
+
+/// Per-branch processing time, for spotting imbalanced branches
+#[derive(Debug)]
+pub struct BranchTimings {
+    busy_ns: Vec<AtomicU64>,
+    records: Vec<AtomicU64>,
+    join_wait_ns: AtomicU64,
+}
+
+impl BranchTimings {
+    fn new(branches: usize) -> Self {
+        Self {
+            busy_ns: (0..branches).map(|_| AtomicU64::new(0)).collect(),
+            records: (0..branches).map(|_| AtomicU64::new(0)).collect(),
+            join_wait_ns: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, branch: usize, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.busy_ns[branch].fetch_add(nanos, Ordering::Relaxed);
+        self.records[branch].fetch_add(1, Ordering::Relaxed);
+        shared_core::histogram!(
+            "pipeline_branch_duration_seconds",
+            elapsed.as_secs_f64(),
+            "branch" => branch.to_string()
+        );
+    }
+
+    fn record_join_wait(&self, wait: Duration) {
+        let nanos = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        self.join_wait_ns.fetch_add(nanos, Ordering::Relaxed);
+        shared_core::histogram!("pipeline_branch_join_wait_seconds", wait.as_secs_f64());
+    }
+
+    /// Total time each branch spent in its stages
+    pub fn busy(&self) -> Vec<Duration> {
+        self.busy_ns
+            .iter()
+            .map(|nanos| Duration::from_nanos(nanos.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Records each branch has processed
+    pub fn records(&self) -> Vec<u64> {
+        self.records.iter().map(|count| count.load(Ordering::Relaxed)).collect()
+    }
+
+    /// Total time records waited at the join between the first and the last
+    /// branch output; always zero with [`JoinStrategy::First`]
+    pub fn join_wait(&self) -> Duration {
+        Duration::from_nanos(self.join_wait_ns.load(Ordering::Relaxed))
+    }
+
+    /// Busy time of the slowest branch over that of the fastest
+    ///
+    /// 1.0 means perfectly balanced. Branches that have not run yet count as
+    /// balanced.
+    pub fn imbalance_ratio(&self) -> f64 {
+        let busy: Vec<u64> = self.busy_ns.iter().map(|n| n.load(Ordering::Relaxed)).collect();
+        match (busy.iter().min(), busy.iter().max()) {
+            (Some(&min), Some(&max)) if min > 0 => max as f64 / min as f64,
+            _ => 1.0,
+        }
+    }
+}
+
+type Stages = Arc<Mutex<Vec<Arc<dyn Stage>>>>;
+
+/// Parallel branches and how their outputs are joined
+#[derive(Clone)]
+struct SplitJoin {
+    branches: Vec<Vec<Arc<dyn Stage>>>,
+    strategy: JoinStrategy,
+    timings: Arc<BranchTimings>,
+}
+
+#[derive(Clone)]
+enum Segment {
+    Stage(Arc<dyn Stage>),
+    SplitJoin(SplitJoin),
+}
+
+/// Linear sequence of stages, possibly containing split/join sections
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    segments: Vec<Segment>,
+}
+
+/// One branch of a split pipeline, built up before [`JoinHandle::join`]
+pub struct BranchPipeline {
+    index: usize,
+    stages: Stages,
+}
+
+impl BranchPipeline {
+    /// Position of the branch, as used by [`JoinStrategy::Zip`]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Append a stage to the branch
+    pub fn add_stage(&mut self, stage: impl Stage + 'static) -> &mut Self {
+        self.stages.lock().push(Arc::new(stage));
+        self
+    }
+
+    /// Append a synchronous stage to the branch
+    pub fn map<F>(&mut self, name: impl Into<String>, f: F) -> &mut Self
+    where
+        F: Fn(PipelineData) -> Result<PipelineData> + Send + Sync + 'static,
+    {
+        self.stages.lock().push(fn_stage(name, f));
+        self
+    }
+}
+
+/// Pending join of the branches returned by [`Pipeline::split`]
+pub struct JoinHandle {
+    upstream: Pipeline,
+    branches: Vec<Stages>,
+    timings: Arc<BranchTimings>,
+}
+
+impl JoinHandle {
+    /// Timing metrics of the branches, shared with the joined pipeline
+    pub fn timings(&self) -> Arc<BranchTimings> {
+        Arc::clone(&self.timings)
+    }
+
+    /// Close the split, merging branch outputs with `strategy`
+    ///
+    /// Stages added to the branches after this call are ignored.
+    pub fn join(self, strategy: JoinStrategy) -> Pipeline {
+        let branches = self.branches.iter().map(|stages| stages.lock().clone()).collect();
+        let mut pipeline = self.upstream;
+        pipeline.segments.push(Segment::SplitJoin(SplitJoin {
+            branches,
+            strategy,
+            timings: self.timings,
+        }));
+        pipeline
+    }
+}
+
+/// Record tagged with its input position, so branch outputs can be matched
+type Item = (u64, Result<PipelineData>);
+
+enum JoinInput {
+    /// Error from before the split, passed straight to the join
+    Bypass(u64, SystemError),
+    /// Output of one branch
+    Branch(usize, u64, Result<PipelineData>),
+}
+
+impl Pipeline {
+    /// Create an empty pipeline
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a stage
+    pub fn add_stage(mut self, stage: impl Stage + 'static) -> Self {
+        self.segments.push(Segment::Stage(Arc::new(stage)));
+        self
+    }
+
+    /// Append a synchronous stage
+    pub fn map<F>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(PipelineData) -> Result<PipelineData> + Send + Sync + 'static,
+    {
+        self.segments.push(Segment::Stage(fn_stage(name, f)));
+        self
+    }
+
+    /// Fan every record out to `branches` parallel sub-pipelines
+    ///
+    /// Add stages to the returned branches, then call [`JoinHandle::join`]
+    /// to continue the pipeline.
+    pub fn split(self, branches: usize) -> (Vec<BranchPipeline>, JoinHandle) {
+        let stages: Vec<Stages> = (0..branches).map(|_| Stages::default()).collect();
+        let branch_pipelines = stages
+            .iter()
+            .enumerate()
+            .map(|(index, stages)| BranchPipeline {
+                index,
+                stages: Arc::clone(stages),
+            })
+            .collect();
+        let handle = JoinHandle {
+            upstream: self,
+            branches: stages,
+            timings: Arc::new(BranchTimings::new(branches)),
+        };
+        (branch_pipelines, handle)
+    }
+
+    /// Process a single record
+    pub async fn process(&self, mut data: PipelineData) -> Result<PipelineData> {
+        for segment in &self.segments {
+            data = match segment {
+                Segment::Stage(stage) => stage.process(data).await?,
+                Segment::SplitJoin(split) => split.process(data).await?,
+            };
+        }
+        Ok(data)
+    }
+
+    /// Stream records from `input` through the pipeline
+    ///
+    /// Each stage and each branch runs on its own task. Each record yields
+    /// one result. Results keep the input order unless
+    /// the pipeline joins with [`JoinStrategy::First`]. A split with no
+    /// branches fails every record with `InvalidState`. Must be called within
+    /// a Tokio runtime.
+    pub fn run(
+        &self,
+        mut input: mpsc::Receiver<PipelineData>,
+    ) -> mpsc::Receiver<Result<PipelineData>> {
+        let (tx, mut rx) = mpsc::channel::<Item>(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            let mut seq = 0;
+            while let Some(data) = input.recv().await {
+                if tx.send((seq, Ok(data))).await.is_err() {
+                    break;
+                }
+                seq += 1;
+            }
+        });
+
+        for segment in &self.segments {
+            rx = match segment {
+                Segment::Stage(stage) => spawn_stage(Arc::clone(stage), rx),
+                Segment::SplitJoin(split) => split.spawn(rx),
+            };
+        }
+
+        let (out_tx, out_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            while let Some((_, result)) = rx.recv().await {
+                if out_tx.send(result).await.is_err() {
+                    break;
+                }
+            }
+        });
+        out_rx
+    }
+}
+
+impl std::fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Stage(stage) => stage.name().to_string(),
+                Segment::SplitJoin(split) => {
+                    format!("split({}, {:?})", split.branches.len(), split.strategy)
+                },
+            })
+            .collect();
+        f.debug_struct("Pipeline").field("segments", &segments).finish()
+    }
+}
+
+fn no_branches() -> SystemError {
+    SystemError::InvalidState {
+        message: "split has no branches".to_string(),
+        current_state: Some("0 branches".to_string()),
+        expected_state: Some("at least one branch".to_string()),
+    }
+}
+
+async fn run_branch(
+    stages: &[Arc<dyn Stage>],
+    mut data: PipelineData,
+    timings: &BranchTimings,
+    branch: usize,
+) -> Result<PipelineData> {
+    let started = Instant::now();
+    for stage in stages {
+        data = stage.process(data).await?;
+    }
+    timings.record(branch, started.elapsed());
+    Ok(data)
+}
+
+impl SplitJoin {
+    async fn process(&self, data: PipelineData) -> Result<PipelineData> {
+        if self.branches.is_empty() {
+            return Err(no_branches());
+        }
+        let timings = &self.timings;
+        let branches = self.branches.iter().enumerate().map(|(index, stages)| {
+            run_branch(stages, data.clone(), timings, index).boxed()
+        });
+
+        if self.strategy == JoinStrategy::First {
+            let (output, _) = select_ok(branches).await?;
+            return Ok(output);
+        }
+
+        let started = Instant::now();
+        let outputs = join_all(branches.map(|branch| async move {
+            let output = branch.await;
+            (output, started.elapsed())
+        }))
+        .await;
+        let (first, last) = outputs
+            .iter()
+            .fold((Duration::MAX, Duration::ZERO), |(lo, hi), (_, at)| (lo.min(*at), hi.max(*at)));
+        self.timings.record_join_wait(last.saturating_sub(first));
+
+        let outputs = outputs.into_iter().map(|(output, _)| output).collect::<Result<Vec<_>>>()?;
+        Ok(self.strategy.combine(outputs))
+    }
+
+    fn spawn(&self, mut input: mpsc::Receiver<Item>) -> mpsc::Receiver<Item> {
+        let (join_tx, join_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let mut branch_txs = Vec::with_capacity(self.branches.len());
+
+        for (index, stages) in self.branches.iter().enumerate() {
+            let (tx, mut rx) = mpsc::channel::<Item>(CHANNEL_CAPACITY);
+            branch_txs.push(tx);
+
+            let stages = stages.clone();
+            let timings = Arc::clone(&self.timings);
+            let join_tx = join_tx.clone();
+            tokio::spawn(async move {
+                while let Some((seq, result)) = rx.recv().await {
+                    let result = match result {
+                        Ok(data) => run_branch(&stages, data, &timings, index).await,
+                        Err(err) => Err(err),
+                    };
+                    if join_tx.send(JoinInput::Branch(index, seq, result)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Fan out
+        let bypass = join_tx;
+        tokio::spawn(async move {
+            while let Some((seq, result)) = input.recv().await {
+                let data = match result {
+                    Ok(data) if !branch_txs.is_empty() => data,
+                    Ok(_) => {
+                        let _ = bypass.send(JoinInput::Bypass(seq, no_branches())).await;
+                        continue;
+                    },
+                    Err(err) => {
+                        if bypass.send(JoinInput::Bypass(seq, err)).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    },
+                };
+                for tx in &branch_txs {
+                    if tx.send((seq, Ok(data.clone()))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let (out_tx, out_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let joiner = Joiner {
+            branches: self.branches.len(),
+            strategy: self.strategy,
+            timings: Arc::clone(&self.timings),
+            pending: HashMap::new(),
+        };
+        tokio::spawn(joiner.run(join_rx, out_tx));
+        out_rx
+    }
+}
+
+/// Branch outputs received so far for one record
+struct Pending {
+    outputs: Vec<Option<Result<PipelineData>>>,
+    arrived: usize,
+    emitted: bool,
+    first_arrival: Instant,
+}
+
+/// Matches branch outputs by record and applies the join strategy
+struct Joiner {
+    branches: usize,
+    strategy: JoinStrategy,
+    timings: Arc<BranchTimings>,
+    pending: HashMap<u64, Pending>,
+}
+
+impl Joiner {
+    async fn run(mut self, mut input: mpsc::Receiver<JoinInput>, output: mpsc::Sender<Item>) {
+        while let Some(message) = input.recv().await {
+            let ready = match message {
+                JoinInput::Bypass(seq, err) => Some((seq, Err(err))),
+                JoinInput::Branch(branch, seq, result) => self.accept(branch, seq, result),
+            };
+            if let Some(item) = ready {
+                if output.send(item).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Store one branch output, returning the joined record once it is ready
+    fn accept(&mut self, branch: usize, seq: u64, result: Result<PipelineData>) -> Option<Item> {
+        let branches = self.branches;
+        let pending = self.pending.entry(seq).or_insert_with(|| Pending {
+            outputs: (0..branches).map(|_| None).collect(),
+            arrived: 0,
+            emitted: false,
+            first_arrival: Instant::now(),
+        });
+        pending.outputs[branch] = Some(result);
+        pending.arrived += 1;
+        let complete = pending.arrived == branches;
+
+        let mut ready = None;
+        if self.strategy == JoinStrategy::First && !pending.emitted {
+            // The first success wins; an error only if every branch failed
+            if let Some(Ok(_)) = &pending.outputs[branch] {
+                pending.emitted = true;
+                ready = pending.outputs[branch].take();
+            } else if complete {
+                pending.emitted = true;
+                ready = pending.outputs.iter_mut().find_map(Option::take);
+            }
+        } else if self.strategy != JoinStrategy::First && complete {
+            self.timings.record_join_wait(pending.first_arrival.elapsed());
+            let outputs = pending.outputs.iter_mut().filter_map(Option::take);
+            ready = Some(outputs.collect::<Result<Vec<_>>>().map(|all| self.strategy.combine(all)));
+        }
+
+        if complete {
+            self.pending.remove(&seq);
+        }
+        ready.map(|result| (seq, result))
+    }
+}
+
+/// Run `stage` over every item of `input` on its own task
+fn spawn_stage(stage: Arc<dyn Stage>, mut input: mpsc::Receiver<Item>) -> mpsc::Receiver<Item> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        while let Some((seq, result)) = input.recv().await {
+            let result = match result {
+                Ok(data) => stage.process(data).await,
+                Err(err) => Err(err),
+            };
+            if tx.send((seq, result)).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sleep(Duration, &'static str);
+
+    #[async_trait]
+    impl Stage for Sleep {
+        fn name(&self) -> &str {
+            self.1
+        }
+
+        async fn process(&self, data: PipelineData) -> Result<PipelineData> {
+            tokio::time::sleep(self.0).await;
+            Ok(data.with(self.1, true))
+        }
+    }
+
+    fn tag(value: &'static str) -> impl Fn(PipelineData) -> Result<PipelineData> {
+        move |data| Ok(data.with("tag", value).with(value, true))
+    }
+
+    fn split_pipeline(strategy: JoinStrategy) -> (Pipeline, Arc<BranchTimings>) {
+        let (mut branches, join) = Pipeline::new().map("seed", tag("seed")).split(2);
+        branches[0].map("left", tag("left"));
+        branches[1].add_stage(Sleep(Duration::from_millis(2), "slow")).map("right", tag("right"));
+        let timings = join.timings();
+        (join.join(strategy).map("after", tag("after")), timings)
+    }
+
+    #[tokio::test]
+    async fn test_join_strategies() {
+        let (merge, _) = split_pipeline(JoinStrategy::Merge);
+        let merged = merge.process(PipelineData::new()).await.unwrap();
+        for field in ["seed", "left", "slow", "right", "after"] {
+            assert_eq!(merged.get(field), Some(&true.into()), "{field}");
+        }
+
+        let (zip, _) = split_pipeline(JoinStrategy::Zip);
+        let zipped = zip.process(PipelineData::new()).await.unwrap();
+        assert_eq!(zipped.get("branch_0").unwrap()["tag"], "left");
+        assert_eq!(zipped.get("branch_1").unwrap()["tag"], "right");
+        assert_eq!(zipped.get("after"), Some(&true.into()));
+
+        let (first, _) = split_pipeline(JoinStrategy::First);
+        let winner = first.process(PipelineData::new()).await.unwrap();
+        assert_eq!(winner.get("left"), Some(&true.into()));
+        assert_eq!(winner.get("right"), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_streams_through_branches() {
+        let (pipeline, timings) = split_pipeline(JoinStrategy::Merge);
+        let (tx, rx) = mpsc::channel(8);
+        let mut output = pipeline.run(rx);
+
+        tokio::spawn(async move {
+            for i in 0..20 {
+                tx.send(PipelineData::new().with("i", i)).await.unwrap();
+            }
+        });
+
+        for i in 0..20 {
+            let record = output.recv().await.unwrap().unwrap();
+            assert_eq!(record.get("i"), Some(&i.into()));
+            assert_eq!(record.get("tag"), Some(&"after".into()));
+            assert_eq!(record.get("slow"), Some(&true.into()));
+        }
+        assert!(output.recv().await.is_none());
+
+        assert_eq!(timings.records(), vec![20, 20]);
+        assert!(timings.busy()[1] >= Duration::from_millis(40));
+        assert!(timings.imbalance_ratio() > 1.0);
+        assert!(timings.join_wait() > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_branch_errors() {
+        let failing = |strategy| {
+            let (mut branches, join) = Pipeline::new().split(2);
+            branches[0].map("fail", |_| Err(SystemError::internal("branch failed", None)));
+            branches[1].add_stage(Sleep(Duration::from_millis(1), "ok"));
+            join.join(strategy)
+        };
+
+        // First falls back to the branch that succeeded
+        let record = failing(JoinStrategy::First).process(PipelineData::new()).await.unwrap();
+        assert_eq!(record.get("ok"), Some(&true.into()));
+
+        for strategy in [JoinStrategy::Merge, JoinStrategy::First] {
+            let (tx, rx) = mpsc::channel(1);
+            let mut output = failing(strategy).run(rx);
+            tx.send(PipelineData::new()).await.unwrap();
+            drop(tx);
+
+            let result = output.recv().await.unwrap();
+            match strategy {
+                JoinStrategy::First => assert!(result.unwrap().get("ok").is_some()),
+                _ => assert!(matches!(result, Err(SystemError::Internal { .. }))),
+            }
+            assert!(output.recv().await.is_none());
+        }
+
+        let (_, join) = Pipeline::new().split(0);
+        let err = join.join(JoinStrategy::Zip).process(PipelineData::new()).await.unwrap_err();
+        assert!(matches!(err, SystemError::InvalidState { .. }));
+    }
+}
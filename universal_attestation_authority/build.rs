@@ -0,0 +1,17 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+/// Generate the gRPC server and client from `proto/`, using a vendored
+/// `protoc` unless `PROTOC` is set
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc is available");
+        std::env::set_var("PROTOC", protoc);
+    }
+    tonic_build::configure()
+        .compile(&["proto/attestation.proto"], &["proto"])
+        .expect("failed to compile protobuf definitions");
+}
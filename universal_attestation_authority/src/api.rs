@@ -1 +1,589 @@
-//! Api module
+//! HTTP API
+//!
+//! Serves the authority over HTTP:
+//!
+//! - `POST /attestations` issues an attestation; include `challenge` when the
+//!   authority requires challenge-response issuance
+//! - `POST /attestations/challenge` starts a challenge for an identity
+//! - `POST /attestations/verify` verifies an attestation
+//! - `POST /attestations/{id}/revoke` revokes an attestation; callers need a
+//!   bearer token or an allow-listed client certificate
+//! - `GET /attestations?identity=&page=&page_size=` lists an identity's
+//!   attestations
+//! - `GET /.well-known/jwks.json` publishes the verification keys
+//! - `GET /healthz` reports the [`HealthRegistry`]
+//!
+//! Failures are returned as [`ErrorResponse`] bodies.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        MatchedPath, Path, Query, Request, State,
+    },
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use shared_core::{
+    ErrorResponse, HealthCheck, HealthRegistry, HealthReport, Result, SystemError,
+};
+use tokio::net::TcpListener;
+use tracing::Instrument;
+
+use crate::{
+    audit, Attestation, AttestationAuthority, AttestationRequest, Challenge, ChallengeResponse,
+    Jwks, Page, VerificationResult,
+};
+
+/// HTTP API configuration
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    /// Bearer tokens allowed to revoke attestations
+    pub revoke_tokens: Vec<String>,
+    /// Client certificate fingerprints allowed to revoke attestations
+    pub client_cert_allow_list: Vec<String>,
+    /// Largest `page_size` accepted when listing attestations
+    pub max_page_size: usize,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            revoke_tokens: Vec::new(),
+            client_cert_allow_list: Vec::new(),
+            max_page_size: 1000,
+        }
+    }
+}
+
+/// Client certificate presented on an mTLS connection
+///
+/// The TLS acceptor in front of [`router`] inserts this into the request
+/// extensions once the handshake has verified the certificate chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// Hex-encoded SHA-256 fingerprint of the leaf certificate
+    pub fingerprint: String,
+}
+
+/// Shared state of the HTTP handlers
+#[derive(Clone)]
+pub struct ApiState {
+    pub(crate) authority: Arc<AttestationAuthority>,
+    pub(crate) config: Arc<ApiConfig>,
+    health: HealthRegistry,
+}
+
+impl ApiState {
+
+    /// Create handler state with a `store` health check registered
+    pub fn new(authority: Arc<AttestationAuthority>, config: ApiConfig) -> Self {
+        let health = HealthRegistry::default();
+        health.register("store", Arc::new(StoreCheck(Arc::clone(&authority))));
+        Self {
+            authority,
+            config: Arc::new(config),
+            health,
+        }
+    }
+
+    /// Registry reported by `GET /healthz`
+    pub fn health(&self) -> &HealthRegistry {
+        &self.health
+    }
+}
+
+/// Build the API router
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/attestations", post(issue).get(list))
+        .route("/attestations/challenge", post(challenge))
+        .route("/attestations/verify", post(verify))
+        .route("/attestations/:id/revoke", post(revoke))
+        .route("/.well-known/jwks.json", get(jwks))
+        .route("/healthz", get(healthz))
+        .route_layer(middleware::from_fn(observe))
+        .with_state(state)
+}
+
+/// Serve the API on `listener` until the server fails
+pub async fn serve(listener: TcpListener, state: ApiState) -> Result<()> {
+    axum::serve(listener, router(state))
+        .await
+        .map_err(|e| SystemError::io(e, "serving attestation API"))
+}
+
+/// Probes the attestation store with a lookup
+struct StoreCheck(Arc<AttestationAuthority>);
+
+#[async_trait]
+impl HealthCheck for StoreCheck {
+    async fn check(&self) -> Result<()> {
+        self.0.get("healthz").await.map(|_| ())
+    }
+}
+
+/// [`SystemError`] rendered as an [`ErrorResponse`]
+struct ApiError(SystemError);
+
+impl From<SystemError> for ApiError {
+    fn from(err: SystemError) -> Self {
+        Self(err)
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self(SystemError::validation("body", rejection.body_text(), None))
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self(SystemError::validation("query", rejection.body_text(), None))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorResponse::from(&self.0);
+        if body.status >= 500 {
+            tracing::error!(error = ?self.0.to_log_value(), "request failed");
+        } else {
+            tracing::debug!(error = ?self.0.to_log_value(), "request rejected");
+        }
+        let status = StatusCode::from_u16(body.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(body)).into_response();
+        if let Some(retry_after) = self.0.retry_after() {
+            // Retry-After has second precision; round up so retries succeed
+            let seconds = retry_after.as_millis().div_ceil(1000).to_string();
+            if let Ok(value) = HeaderValue::from_str(&seconds) {
+                response.headers_mut().insert(RETRY_AFTER, value);
+            }
+        }
+        response
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Record request metrics and run the handler inside a span
+async fn observe(path: MatchedPath, request: Request, next: Next) -> Response {
+    let route = path.as_str().to_string();
+    let method = request.method().to_string();
+    let span = tracing::info_span!(
+        "http_request",
+        method = %method,
+        route = %route,
+        status = tracing::field::Empty,
+    );
+
+    let start = Instant::now();
+    let response = audit::with_actor("http", next.run(request))
+        .instrument(span.clone())
+        .await;
+    let status = response.status().as_u16();
+    span.record("status", status);
+
+    shared_core::count!(
+        "uaa_http_requests_total", 1,
+        "method" => method.clone(), "route" => route.clone(), "status" => status.to_string()
+    );
+    shared_core::histogram!(
+        "uaa_http_request_duration_seconds", start.elapsed().as_secs_f64(),
+        "method" => method, "route" => route
+    );
+    response
+}
+
+#[derive(Deserialize)]
+struct IssueBody {
+    #[serde(flatten)]
+    request: AttestationRequest,
+    #[serde(default)]
+    challenge: Option<ChallengeResponse>,
+}
+
+async fn issue(
+    State(state): State<ApiState>,
+    body: std::result::Result<Json<IssueBody>, JsonRejection>,
+) -> ApiResult<(StatusCode, Json<Attestation>)> {
+    let Json(body) = body?;
+    let attestation = match &body.challenge {
+        Some(response) => state.authority.issue_with_challenge(body.request, response).await?,
+        None => state.authority.issue(body.request).await?,
+    };
+    Ok((StatusCode::CREATED, Json(attestation)))
+}
+
+#[derive(Deserialize)]
+struct ChallengeBody {
+    identity: String,
+}
+
+async fn challenge(
+    State(state): State<ApiState>,
+    body: std::result::Result<Json<ChallengeBody>, JsonRejection>,
+) -> ApiResult<Json<Challenge>> {
+    let Json(body) = body?;
+    Ok(Json(state.authority.begin_challenge(&body.identity)?))
+}
+
+async fn verify(
+    State(state): State<ApiState>,
+    body: std::result::Result<Json<Attestation>, JsonRejection>,
+) -> ApiResult<Json<VerificationResult>> {
+    let Json(attestation) = body?;
+    Ok(Json(state.authority.verify(&attestation).await?))
+}
+
+#[derive(Deserialize)]
+struct RevokeBody {
+    reason: String,
+}
+
+async fn revoke(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    certificate: Option<Extension<ClientCertificate>>,
+    body: std::result::Result<Json<RevokeBody>, JsonRejection>,
+) -> ApiResult<StatusCode> {
+    let bearer = headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok());
+    let certificate = certificate.as_ref().map(|c| &c.0);
+    let actor = authorize_revoke(&state.config, "http", bearer, certificate)?;
+    let Json(body) = body?;
+    audit::with_actor(actor, state.authority.revoke(&id, body.reason)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Accept an allow-listed client certificate or a configured bearer token
+///
+/// `authorization` is the raw `Authorization` header or metadata value.
+/// Returns the actor to audit the revocation under, prefixed with
+/// `transport`.
+pub(crate) fn authorize_revoke(
+    config: &ApiConfig,
+    transport: &str,
+    authorization: Option<&str>,
+    certificate: Option<&ClientCertificate>,
+) -> Result<String> {
+    let certificate_allowed = certificate.is_some_and(|cert| {
+        config
+            .client_cert_allow_list
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(&cert.fingerprint))
+    });
+    let token_allowed = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| {
+            config
+                .revoke_tokens
+                .iter()
+                .any(|allowed| constant_time_eq(allowed.as_bytes(), token.as_bytes()))
+        });
+
+    match certificate {
+        Some(cert) if certificate_allowed => {
+            Ok(format!("{transport}:client-cert:{}", cert.fingerprint))
+        },
+        _ if token_allowed => Ok(format!("{transport}:bearer-token")),
+        _ => Err(SystemError::PermissionDenied {
+            operation: "revoke".to_string(),
+            required_permission: Some(
+                "bearer token or allow-listed client certificate".to_string(),
+            ),
+        }),
+    }
+}
+
+/// Compare secrets without exiting early on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    identity: String,
+    #[serde(default)]
+    page: usize,
+    page_size: Option<usize>,
+}
+
+async fn list(
+    State(state): State<ApiState>,
+    query: std::result::Result<Query<ListQuery>, QueryRejection>,
+) -> ApiResult<Json<Vec<Attestation>>> {
+    let Query(query) = query?;
+    let page_size = query.page_size.unwrap_or(Page::default().limit);
+    if page_size == 0 || page_size > state.config.max_page_size {
+        return Err(SystemError::validation(
+            "page_size",
+            format!("must be between 1 and {}", state.config.max_page_size),
+            Some(page_size.to_string()),
+        )
+        .into());
+    }
+    let offset = query.page.checked_mul(page_size).ok_or_else(|| {
+        SystemError::validation("page", "out of range", Some(query.page.to_string()))
+    })?;
+
+    let page = Page::new(offset, page_size);
+    Ok(Json(state.authority.list_by_identity(&query.identity, page).await?))
+}
+
+async fn jwks(State(state): State<ApiState>) -> Json<Jwks> {
+    Json(state.authority.jwks())
+}
+
+async fn healthz(State(state): State<ApiState>) -> (StatusCode, Json<HealthReport>) {
+    let report = state.health.report().await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttestationConfig, VerificationOutcome, VerifiedBy};
+    use serde_json::json;
+    use shared_core::crypto::KeyPair;
+    use std::net::SocketAddr;
+
+    const TOKEN: &str = "revoke-secret";
+
+    async fn spawn(authority: AttestationAuthority, app: impl FnOnce(Router) -> Router) -> String {
+        let config = ApiConfig {
+            revoke_tokens: vec![TOKEN.to_string()],
+            client_cert_allow_list: vec!["AB12".to_string()],
+            ..ApiConfig::default()
+        };
+        let state = ApiState::new(Arc::new(authority), config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let app = app(router(state));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn issue_body(identity: &str) -> serde_json::Value {
+        json!({ "identity": identity, "claims": { "role": "node" }, "validity_seconds": 3600 })
+    }
+
+    #[tokio::test]
+    async fn test_issue_verify_revoke() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let base = spawn(authority, |app| app).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{base}/attestations"))
+            .json(&issue_body("node-1"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+        let attestation: Attestation = response.json().await.unwrap();
+
+        let result: VerificationResult = client
+            .post(format!("{base}/attestations/verify"))
+            .json(&attestation)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.verified_by, VerifiedBy::Local);
+
+        let listed: Vec<Attestation> = client
+            .get(format!("{base}/attestations?identity=node-1&page=0"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, attestation.id);
+
+        let response = client
+            .post(format!("{base}/attestations/{}/revoke", attestation.id))
+            .bearer_auth(TOKEN)
+            .json(&json!({ "reason": "compromised" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+
+        let result: VerificationResult = client
+            .post(format!("{base}/attestations/verify"))
+            .json(&attestation)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(matches!(
+            result.outcome,
+            VerificationOutcome::Revoked { ref reason, .. } if reason == "compromised"
+        ));
+
+        let jwks: Jwks = client
+            .get(format!("{base}/.well-known/jwks.json"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(jwks.keys.len(), 1);
+
+        let response = client.get(format!("{base}/healthz")).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let report: HealthReport = response.json().await.unwrap();
+        assert!(report.checks["store"].healthy);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_requires_credentials() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let attestation = authority
+            .issue(serde_json::from_value(issue_body("node-1")).unwrap())
+            .await
+            .unwrap();
+        let base = spawn(authority, |app| app).await;
+        let client = reqwest::Client::new();
+        let url = format!("{base}/attestations/{}/revoke", attestation.id);
+
+        for request in [
+            client.post(&url),
+            client.post(&url).bearer_auth("wrong"),
+            client.post(&url).header("authorization", TOKEN),
+        ] {
+            let response = request.json(&json!({ "reason": "x" })).send().await.unwrap();
+            assert_eq!(response.status(), 403);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.kind, "PermissionDenied");
+        }
+
+        let result: VerificationResult = client
+            .post(format!("{base}/attestations/verify"))
+            .json(&attestation)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.verified_by, VerifiedBy::Local);
+
+        let response = client
+            .post(format!("{base}/attestations/missing/revoke"))
+            .bearer_auth(TOKEN)
+            .json(&json!({ "reason": "x" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_with_client_certificate() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let attestation = authority
+            .issue(serde_json::from_value(issue_body("node-1")).unwrap())
+            .await
+            .unwrap();
+        let certificate = ClientCertificate {
+            fingerprint: "ab12".to_string(),
+        };
+        let base = spawn(authority, |app| app.layer(Extension(certificate))).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{base}/attestations/{}/revoke", attestation.id))
+            .json(&json!({ "reason": "decommissioned" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_flow_and_errors() {
+        let config = AttestationConfig {
+            require_challenge: true,
+            ..AttestationConfig::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+        let identity_key = KeyPair::generate();
+        authority.register_identity("node-1", identity_key.public_key());
+        let base = spawn(authority, |app| app).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{base}/attestations"))
+            .json(&issue_body("node-1"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+
+        let challenge: Challenge = client
+            .post(format!("{base}/attestations/challenge"))
+            .json(&json!({ "identity": "node-1" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let mut body = issue_body("node-1");
+        body["challenge"] = json!({
+            "nonce": challenge.nonce,
+            "signature": identity_key.sign(challenge.nonce.as_bytes()),
+        });
+        let response = client
+            .post(format!("{base}/attestations"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+
+        let response = client
+            .post(format!("{base}/attestations"))
+            .header("content-type", "application/json")
+            .body("{not json")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let error: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(error.kind, "Validation");
+
+        let response = client
+            .get(format!("{base}/attestations?identity=node-1&page_size=0"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+}
@@ -1 +1,99 @@
 //! Attestation module
+//!
+//! Canonical encoding of attestations and revocation lists for signing.
+
+use serde::{Deserialize, Serialize};
+use shared_core::{crypto::PublicKey, Result, Timestamp};
+
+use crate::{claims::canonicalize_map, storage::RevocationEntry, Attestation};
+
+/// Fields covered by the authority signature, in signing order
+#[derive(Serialize)]
+struct SigningPayload<'a> {
+    id: &'a str,
+    identity: &'a str,
+    claims: serde_json::Map<String, serde_json::Value>,
+    issued_at: Timestamp,
+    not_before: Timestamp,
+    expires_at: Timestamp,
+    key_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    renews: Option<&'a str>,
+}
+
+impl Attestation {
+    /// Canonical bytes signed by the issuing authority
+    ///
+    /// Claim keys are sorted at every nesting level, so the signature does
+    /// not depend on claim insertion order.
+    pub fn signing_payload(&self) -> Result<Vec<u8>> {
+        let payload = SigningPayload {
+            id: &self.id,
+            identity: &self.identity,
+            claims: canonicalize_map(&self.claims),
+            issued_at: self.issued_at,
+            not_before: self.not_before,
+            expires_at: self.expires_at,
+            key_id: &self.key_id,
+            renews: self.renews.as_deref(),
+        };
+        Ok(serde_json::to_vec(&payload)?)
+    }
+
+    /// Bytes the identity key signs to renew this attestation
+    pub fn renewal_nonce(&self) -> Vec<u8> {
+        format!("uaa-renew:{}", self.id).into_bytes()
+    }
+}
+
+/// Signed snapshot of the revocation set
+///
+/// Offline verifiers can cache the list and use `sequence` to tell whether a
+/// newer list has been published.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevocationList {
+    /// Monotonically increasing sequence number
+    pub sequence: u64,
+    /// When this list was generated
+    pub issued_at: Timestamp,
+    /// Revoked attestations
+    pub entries: Vec<RevocationEntry>,
+    /// ID of the authority key that signed the list
+    #[serde(default)]
+    pub key_id: String,
+    /// Authority signature over the list
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct RevocationListPayload<'a> {
+    sequence: u64,
+    issued_at: Timestamp,
+    entries: &'a [RevocationEntry],
+    key_id: &'a str,
+}
+
+impl RevocationList {
+    /// Canonical bytes signed by the issuing authority
+    pub fn signing_payload(&self) -> Result<Vec<u8>> {
+        let payload = RevocationListPayload {
+            sequence: self.sequence,
+            issued_at: self.issued_at,
+            entries: &self.entries,
+            key_id: &self.key_id,
+        };
+        Ok(serde_json::to_vec(&payload)?)
+    }
+
+    /// Verify the list signature against the authority public key
+    pub fn verify(&self, public_key: &PublicKey) -> Result<()> {
+        public_key.verify(&self.signing_payload()?, &self.signature)
+    }
+
+    /// Check whether an attestation is on this list
+    pub fn is_revoked(&self, attestation_id: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.attestation_id == attestation_id)
+    }
+}
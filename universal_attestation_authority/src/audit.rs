@@ -0,0 +1,284 @@
+//! Operation audit log
+//!
+//! Every issuance, verification failure, revocation and key change is
+//! appended to the store as an [`AuditRecord`]. Each record carries the
+//! BLAKE3 hash of its predecessor, so editing, removing or reordering stored
+//! records breaks the chain from that point on.
+//!
+//! The chain head is kept in memory, so one authority at a time should
+//! append to a given store.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use shared_core::{crypto, Result, Timestamp};
+
+use crate::storage::{AttestationStore, Page};
+
+/// Actor recorded when none is set with [`with_actor`]
+pub const DEFAULT_ACTOR: &str = "local";
+
+/// Records fetched per store round trip by [`AuditLog::verify_integrity`]
+const VERIFY_PAGE_SIZE: usize = 1000;
+
+tokio::task_local! {
+    static ACTOR: String;
+}
+
+/// Run `future` with `actor` recorded on the operations it audits
+pub async fn with_actor<F: Future>(actor: impl Into<String>, future: F) -> F::Output {
+    ACTOR.scope(actor.into(), future).await
+}
+
+fn current_actor() -> String {
+    ACTOR
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| DEFAULT_ACTOR.to_string())
+}
+
+/// Kind of audited operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// Attestation issuance, successful or rejected
+    Issue,
+    /// Verification that did not return `Valid`
+    VerifyFailure,
+    /// Attestation revocation
+    Revoke,
+    /// Signing key rotation
+    RotateKey,
+    /// Early retirement of a verification key
+    RetireKey,
+}
+
+/// Entry of the operation audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position in the log, starting at zero
+    pub index: u64,
+    /// What was done
+    pub operation: AuditOperation,
+    /// Who did it, see [`with_actor`]
+    pub actor: String,
+    /// Identity the operation concerned
+    pub identity: Option<String>,
+    /// Attestation the operation concerned
+    pub attestation_id: Option<String>,
+    /// Result of the operation
+    pub outcome: String,
+    /// When the record was appended
+    pub timestamp: Timestamp,
+    /// Hash of the previous record, zero for the first
+    pub prev_hash: [u8; 32],
+    /// Hash of this record's contents and `prev_hash`
+    pub hash: [u8; 32],
+}
+
+#[derive(Serialize)]
+struct RecordPayload<'a> {
+    index: u64,
+    operation: AuditOperation,
+    actor: &'a str,
+    identity: &'a Option<String>,
+    attestation_id: &'a Option<String>,
+    outcome: &'a str,
+    timestamp: Timestamp,
+    prev_hash: &'a [u8; 32],
+}
+
+impl AuditRecord {
+    /// Hash of the record contents, excluding `hash` itself
+    pub fn compute_hash(&self) -> Result<[u8; 32]> {
+        let payload = RecordPayload {
+            index: self.index,
+            operation: self.operation,
+            actor: &self.actor,
+            identity: &self.identity,
+            attestation_id: &self.attestation_id,
+            outcome: &self.outcome,
+            timestamp: self.timestamp,
+            prev_hash: &self.prev_hash,
+        };
+        Ok(crypto::hash_blake3(&serde_json::to_vec(&payload)?))
+    }
+}
+
+/// Result of [`AuditLog::verify_integrity`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Number of records checked
+    pub records: u64,
+    /// Index of the first record that is missing, altered or out of chain
+    pub first_invalid: Option<u64>,
+}
+
+impl IntegrityReport {
+    /// Whether the whole chain checked out
+    pub fn is_intact(&self) -> bool {
+        self.first_invalid.is_none()
+    }
+}
+
+/// Index and hash of the last appended record
+struct Head {
+    next_index: u64,
+    hash: [u8; 32],
+}
+
+/// Hash-chained log of authority operations
+pub struct AuditLog {
+    store: Arc<dyn AttestationStore>,
+    /// Loaded from the store on first append; the lock also serializes appends
+    head: tokio::sync::Mutex<Option<Head>>,
+}
+
+impl AuditLog {
+    /// Create a log on top of `store`, continuing any chain already in it
+    pub fn new(store: Arc<dyn AttestationStore>) -> Self {
+        Self {
+            store,
+            head: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Append a record for the current actor
+    pub async fn append(
+        &self,
+        operation: AuditOperation,
+        identity: Option<&str>,
+        attestation_id: Option<&str>,
+        outcome: impl Into<String>,
+    ) -> Result<AuditRecord> {
+        let mut head = self.head.lock().await;
+        let (next_index, prev_hash) = match &*head {
+            Some(head) => (head.next_index, head.hash),
+            None => match self.store.last_audit_record().await? {
+                Some(last) => (last.index + 1, last.hash),
+                None => (0, [0; 32]),
+            },
+        };
+
+        let mut record = AuditRecord {
+            index: next_index,
+            operation,
+            actor: current_actor(),
+            identity: identity.map(str::to_string),
+            attestation_id: attestation_id.map(str::to_string),
+            outcome: outcome.into(),
+            timestamp: Timestamp::now(),
+            prev_hash,
+            hash: [0; 32],
+        };
+        record.hash = record.compute_hash()?;
+        self.store.put_audit_record(&record).await?;
+
+        *head = Some(Head {
+            next_index: next_index + 1,
+            hash: record.hash,
+        });
+        Ok(record)
+    }
+
+    /// Read records in index order
+    pub async fn export(&self, page: Page) -> Result<Vec<AuditRecord>> {
+        self.store.list_audit_records(page).await
+    }
+
+    /// Walk the whole chain and report the first record that does not fit
+    pub async fn verify_integrity(&self) -> Result<IntegrityReport> {
+        let mut expected_index = 0u64;
+        let mut prev_hash = [0u8; 32];
+        let mut first_invalid = None;
+
+        loop {
+            let page = Page::new(expected_index as usize, VERIFY_PAGE_SIZE);
+            let records = self.store.list_audit_records(page).await?;
+            let fetched = records.len();
+
+            for record in records {
+                if first_invalid.is_none()
+                    && (record.index != expected_index
+                        || record.prev_hash != prev_hash
+                        || record.hash != record.compute_hash()?)
+                {
+                    first_invalid = Some(expected_index);
+                }
+                prev_hash = record.hash;
+                expected_index += 1;
+            }
+
+            if fetched < VERIFY_PAGE_SIZE {
+                break;
+            }
+        }
+
+        if first_invalid.is_some() {
+            tracing::warn!("Audit log integrity check failed at {:?}", first_invalid);
+        }
+        Ok(IntegrityReport {
+            records: expected_index,
+            first_invalid,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    async fn filled_log(store: &Arc<dyn AttestationStore>, records: u64) -> AuditLog {
+        let log = AuditLog::new(Arc::clone(store));
+        for i in 0..records {
+            let id = format!("att_{i}");
+            log.append(AuditOperation::Issue, Some("svc"), Some(&id), "issued")
+                .await
+                .unwrap();
+        }
+        log
+    }
+
+    #[tokio::test]
+    async fn test_detects_corrupted_record() {
+        let store: Arc<dyn AttestationStore> = Arc::new(MemoryStore::new());
+        let log = filled_log(&store, 100).await;
+
+        let report = log.verify_integrity().await.unwrap();
+        assert_eq!(report.records, 100);
+        assert!(report.is_intact());
+
+        let mut record = log.export(Page::new(42, 1)).await.unwrap().remove(0);
+        record.outcome = "rejected".to_string();
+        store.put_audit_record(&record).await.unwrap();
+        assert_eq!(log.verify_integrity().await.unwrap().first_invalid, Some(42));
+
+        // Re-hashing the edited record moves the break to its successor
+        record.hash = record.compute_hash().unwrap();
+        store.put_audit_record(&record).await.unwrap();
+        assert_eq!(log.verify_integrity().await.unwrap().first_invalid, Some(43));
+    }
+
+    #[tokio::test]
+    async fn test_export_and_resume() {
+        let store: Arc<dyn AttestationStore> = Arc::new(MemoryStore::new());
+        drop(filled_log(&store, 5).await);
+
+        // A new log continues the stored chain
+        let log = AuditLog::new(Arc::clone(&store));
+        let record = with_actor("operator", async {
+            log.append(AuditOperation::RotateKey, None, None, "rotated").await
+        })
+        .await
+        .unwrap();
+        assert_eq!(record.index, 5);
+        assert_eq!(record.actor, "operator");
+
+        let page = log.export(Page::new(3, 10)).await.unwrap();
+        let indices: Vec<u64> = page.iter().map(|r| r.index).collect();
+        assert_eq!(indices, vec![3, 4, 5]);
+        assert_eq!(page[0].actor, DEFAULT_ACTOR);
+        assert!(log.verify_integrity().await.unwrap().is_intact());
+    }
+}
@@ -0,0 +1,284 @@
+//! Verification cache
+//!
+//! Remembers `Valid` outcomes so repeated verification of the same
+//! attestation skips signature and store checks. Entries are keyed by
+//! attestation ID and a digest of the signed content, never outlive the
+//! attestation or its signing key, and are re-checked against the validity
+//! window on every read.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+use shared_core::{crypto, Timestamp};
+
+use crate::{verification, Attestation};
+
+/// Hit and miss counts since the cache was created
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Entry {
+    digest: [u8; 32],
+    deadline: Timestamp,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, Entry>,
+    /// Attestation IDs by last use, least recent first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    /// Bumped on every invalidation so in-flight verifications that started
+    /// before it cannot cache their result
+    generation: u64,
+}
+
+impl Lru {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(entry) = self.entries.remove(id) {
+            self.recency.remove(&entry.tick);
+        }
+    }
+}
+
+/// Bounded LRU of `Valid` verification outcomes
+pub(crate) struct VerificationCache {
+    capacity: usize,
+    ttl_ms: u64,
+    skew_ms: u64,
+    lru: Mutex<Lru>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl VerificationCache {
+    /// Create a cache; a `capacity` of zero disables it
+    pub(crate) fn new(capacity: usize, ttl_ms: u64, skew_ms: u64) -> Self {
+        Self {
+            capacity,
+            ttl_ms,
+            skew_ms,
+            lru: Mutex::new(Lru::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Digest identifying the signed content of an attestation
+    pub(crate) fn digest(payload: &[u8], signature: &[u8]) -> [u8; 32] {
+        crypto::hash_blake3(&[payload, signature].concat())
+    }
+
+    /// Generation to pass to [`VerificationCache::insert`]
+    pub(crate) fn generation(&self) -> u64 {
+        self.lru.lock().generation
+    }
+
+    /// Whether `attestation` is cached as valid at `now`
+    pub(crate) fn get(&self, attestation: &Attestation, digest: &[u8; 32], now: Timestamp) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+
+        let mut lru = self.lru.lock();
+        let hit = match lru.entries.get(&attestation.id) {
+            Some(entry) if entry.digest == *digest => {
+                let fresh = now < entry.deadline
+                    && verification::check_validity_window(attestation, now, self.skew_ms)
+                        .is_valid();
+                if !fresh && now >= entry.deadline {
+                    lru.remove(&attestation.id);
+                }
+                fresh
+            },
+            _ => false,
+        };
+
+        if hit {
+            let tick = lru.next_tick();
+            if let Some(entry) = lru.entries.get_mut(&attestation.id) {
+                let previous = std::mem::replace(&mut entry.tick, tick);
+                lru.recency.remove(&previous);
+                lru.recency.insert(tick, attestation.id.clone());
+            }
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            shared_core::count!("uaa_verification_cache_hits_total", 1);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            shared_core::count!("uaa_verification_cache_misses_total", 1);
+        }
+        hit
+    }
+
+    /// Cache a `Valid` outcome observed at `now`
+    ///
+    /// Ignored if the cache was invalidated since `generation` was read. The
+    /// entry expires after the TTL, when the attestation expires, or when its
+    /// signing key retires, whichever comes first.
+    pub(crate) fn insert(
+        &self,
+        attestation: &Attestation,
+        digest: [u8; 32],
+        now: Timestamp,
+        key_retires_at: Option<Timestamp>,
+        generation: u64,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut deadline = Timestamp::from_millis(
+            now.as_millis()
+                .saturating_add(self.ttl_ms)
+                .min(attestation.expires_at.as_millis().saturating_add(self.skew_ms)),
+        );
+        if let Some(retires_at) = key_retires_at {
+            deadline = deadline.min(retires_at);
+        }
+        if deadline <= now {
+            return;
+        }
+
+        let mut lru = self.lru.lock();
+        if lru.generation != generation {
+            return;
+        }
+        lru.remove(&attestation.id);
+        while lru.entries.len() >= self.capacity {
+            let Some((_, oldest)) = lru.recency.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+        }
+        let tick = lru.next_tick();
+        lru.recency.insert(tick, attestation.id.clone());
+        lru.entries.insert(
+            attestation.id.clone(),
+            Entry {
+                digest,
+                deadline,
+                tick,
+            },
+        );
+    }
+
+    /// Drop the entry for an attestation
+    pub(crate) fn invalidate(&self, id: &str) {
+        let mut lru = self.lru.lock();
+        lru.generation += 1;
+        lru.remove(id);
+    }
+
+    /// Drop every entry
+    pub(crate) fn clear(&self) {
+        let mut lru = self.lru.lock();
+        lru.generation += 1;
+        lru.entries.clear();
+        lru.recency.clear();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.lru.lock().entries.len()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attestation(id: &str, expires_at: u64) -> Attestation {
+        Attestation {
+            id: id.to_string(),
+            identity: "svc".to_string(),
+            claims: serde_json::Map::new(),
+            issued_at: Timestamp::from_millis(0),
+            not_before: Timestamp::from_millis(0),
+            expires_at: Timestamp::from_millis(expires_at),
+            key_id: "k1".to_string(),
+            renews: None,
+            signature: id.as_bytes().to_vec(),
+        }
+    }
+
+    fn digest(attestation: &Attestation) -> [u8; 32] {
+        VerificationCache::digest(&attestation.signing_payload().unwrap(), &attestation.signature)
+    }
+
+    fn at(ms: u64) -> Timestamp {
+        Timestamp::from_millis(ms)
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = VerificationCache::new(2, 60_000, 0);
+        let [a, b, c] = ["a", "b", "c"].map(|id| attestation(id, 100_000));
+
+        cache.insert(&a, digest(&a), at(0), None, cache.generation());
+        cache.insert(&b, digest(&b), at(0), None, cache.generation());
+        assert!(cache.get(&a, &digest(&a), at(1)));
+
+        // `b` is now least recently used
+        cache.insert(&c, digest(&c), at(2), None, cache.generation());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&a, &digest(&a), at(3)));
+        assert!(!cache.get(&b, &digest(&b), at(3)));
+        assert!(cache.get(&c, &digest(&c), at(3)));
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 1 });
+    }
+
+    #[test]
+    fn test_expiry_on_read() {
+        let cache = VerificationCache::new(8, 60_000, 0);
+        let short = attestation("short", 1_000);
+
+        cache.insert(&short, digest(&short), at(0), None, cache.generation());
+        assert!(cache.get(&short, &digest(&short), at(999)));
+        assert!(!cache.get(&short, &digest(&short), at(1_000)));
+        assert_eq!(cache.len(), 0);
+
+        let long = attestation("long", 100_000);
+        cache.insert(&long, digest(&long), at(0), Some(at(500)), cache.generation());
+        assert!(!cache.get(&long, &digest(&long), at(500)));
+    }
+
+    #[test]
+    fn test_digest_and_generation() {
+        let cache = VerificationCache::new(8, 60_000, 0);
+        let original = attestation("a", 100_000);
+        let mut tampered = original.clone();
+        tampered.identity = "impostor".to_string();
+
+        cache.insert(&original, digest(&original), at(0), None, cache.generation());
+        assert!(!cache.get(&tampered, &digest(&tampered), at(1)));
+
+        let stale = cache.generation();
+        cache.invalidate("a");
+        cache.insert(&original, digest(&original), at(2), None, stale);
+        assert!(!cache.get(&original, &digest(&original), at(3)));
+
+        let disabled = VerificationCache::new(0, 60_000, 0);
+        disabled.insert(&original, digest(&original), at(0), None, disabled.generation());
+        assert!(!disabled.get(&original, &digest(&original), at(1)));
+        assert_eq!(disabled.stats(), CacheStats::default());
+    }
+}
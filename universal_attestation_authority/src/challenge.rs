@@ -0,0 +1,288 @@
+//! Challenge module
+//!
+//! Challenge-response issuance. A requester asks for a nonce, signs it with
+//! the key registered for its identity, and presents the signature together
+//! with the attestation request. Each nonce is single use and short lived, so
+//! a captured request cannot be replayed.
+//!
+//! Identities with hardware evidence skip the signature: the decoded nonce is
+//! bound into the evidence instead, see [`crate::evidence`].
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use shared_core::{crypto, crypto::PublicKey, Result, SystemError, Timestamp};
+
+/// Server-issued nonce awaiting a signed response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    /// Hex-encoded random nonce
+    pub nonce: String,
+    /// The nonce cannot be redeemed at or after this time
+    pub expires_at: Timestamp,
+}
+
+/// Requester's answer to a [`Challenge`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    /// Nonce from the challenge
+    pub nonce: String,
+    /// Signature over the UTF-8 bytes of the nonce
+    pub signature: Vec<u8>,
+}
+
+struct PendingChallenge {
+    identity: String,
+    expires_at: Timestamp,
+    ttl_ms: u64,
+    used: bool,
+}
+
+/// Registered identity keys and outstanding nonces
+#[derive(Default)]
+pub(crate) struct ChallengeRegistry {
+    identities: DashMap<String, PublicKey>,
+    pending: DashMap<String, PendingChallenge>,
+}
+
+impl ChallengeRegistry {
+    pub(crate) fn register(&self, identity: String, public_key: PublicKey) {
+        self.identities.insert(identity, public_key);
+    }
+
+    pub(crate) fn is_registered(&self, identity: &str) -> bool {
+        self.identities.contains_key(identity)
+    }
+
+    pub(crate) fn public_key(&self, identity: &str) -> Option<PublicKey> {
+        self.identities.get(identity).map(|key| key.value().clone())
+    }
+
+    /// Issue a nonce for `identity`; the caller checks that it is known
+    pub(crate) fn begin(&self, identity: &str, ttl_ms: u64) -> Result<Challenge> {
+        let nonce: String = crypto::random_bytes(32)?
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(self.insert(identity, nonce, ttl_ms))
+    }
+
+    fn insert(&self, identity: &str, nonce: String, ttl_ms: u64) -> Challenge {
+        let now = Timestamp::now();
+        // Spent and expired nonces are kept for one extra TTL so late replays
+        // are reported as such rather than as unknown nonces
+        self.pending.retain(|_, pending| {
+            pending.expires_at.as_millis().saturating_add(ttl_ms) > now.as_millis()
+        });
+
+        let expires_at = Timestamp::from_millis(now.as_millis().saturating_add(ttl_ms));
+        self.pending.insert(
+            nonce.clone(),
+            PendingChallenge {
+                identity: identity.to_string(),
+                expires_at,
+                ttl_ms,
+                used: false,
+            },
+        );
+
+        Challenge { nonce, expires_at }
+    }
+
+    /// Issue a caller-chosen nonce, for fixtures bound to a known nonce
+    #[cfg(test)]
+    pub(crate) fn begin_with_nonce(&self, identity: &str, nonce: &[u8], ttl_ms: u64) -> Challenge {
+        self.insert(
+            identity,
+            nonce.iter().map(|b| format!("{b:02x}")).collect(),
+            ttl_ms,
+        )
+    }
+
+    /// Consume a nonce on behalf of `identity`
+    ///
+    /// The nonce is spent by the first attempt, even if that attempt fails.
+    pub(crate) fn redeem(&self, identity: &str, response: &ChallengeResponse) -> Result<()> {
+        let public_key = self
+            .public_key(identity)
+            .ok_or_else(|| SystemError::not_found("identity", identity))?;
+
+        self.spend(identity, &response.nonce, "issue_with_challenge")?;
+        if public_key
+            .verify(response.nonce.as_bytes(), &response.signature)
+            .is_err()
+        {
+            return Err(SystemError::PermissionDenied {
+                operation: "issue_with_challenge".to_string(),
+                required_permission: Some(format!(
+                    "signature by the key registered for {identity}"
+                )),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Consume a nonce issued to `identity`, without checking a signature
+    ///
+    /// The nonce is spent by the first attempt, even if that attempt fails.
+    pub(crate) fn spend(&self, identity: &str, nonce: &str, operation: &str) -> Result<()> {
+        let mut pending = self
+            .pending
+            .get_mut(nonce)
+            .ok_or_else(|| SystemError::not_found("challenge", nonce))?;
+
+        if pending.used {
+            return Err(SystemError::InvalidState {
+                message: "challenge nonce has already been used".to_string(),
+                current_state: Some("used".to_string()),
+                expected_state: Some("pending".to_string()),
+            });
+        }
+        pending.used = true;
+
+        if Timestamp::now() >= pending.expires_at {
+            return Err(SystemError::timeout("challenge response", pending.ttl_ms));
+        }
+
+        if pending.identity != identity {
+            return Err(SystemError::PermissionDenied {
+                operation: operation.to_string(),
+                required_permission: Some(format!("challenge issued to {identity}")),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Decode a hex nonce into the bytes bound into hardware evidence
+pub(crate) fn nonce_bytes(nonce: &str) -> Result<Vec<u8>> {
+    let invalid = || SystemError::validation("nonce", "not a hex string", Some(nonce.to_string()));
+    if nonce.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    (0..nonce.len())
+        .step_by(2)
+        .map(|i| {
+            nonce
+                .get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use shared_core::crypto::KeyPair;
+
+    use crate::{AttestationAuthority, AttestationConfig, AttestationRequest, ChallengeResponse};
+
+    use super::*;
+
+    fn request() -> AttestationRequest {
+        AttestationRequest {
+            identity: "edge-node".to_string(),
+            claims: serde_json::Map::new(),
+            validity_seconds: 3600,
+            not_before: None,
+        }
+    }
+
+    fn respond(key: &KeyPair, challenge: &Challenge) -> ChallengeResponse {
+        ChallengeResponse {
+            nonce: challenge.nonce.clone(),
+            signature: key.sign(challenge.nonce.as_bytes()),
+        }
+    }
+
+    fn authority(challenge_ttl_ms: u64) -> (AttestationAuthority, KeyPair) {
+        let config = AttestationConfig {
+            require_challenge: true,
+            challenge_ttl_ms,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+        let key = KeyPair::generate();
+        authority.register_identity("edge-node", key.public_key());
+        (authority, key)
+    }
+
+    #[tokio::test]
+    async fn test_challenge_happy_path() {
+        let (authority, key) = authority(60_000);
+
+        // Single-step issuance is disabled when challenges are required
+        assert!(matches!(
+            authority.issue(request()).await,
+            Err(SystemError::PermissionDenied { .. })
+        ));
+
+        let challenge = authority.begin_challenge("edge-node").unwrap();
+        let attestation = authority
+            .issue_with_challenge(request(), &respond(&key, &challenge))
+            .await
+            .unwrap();
+        assert!(authority.is_valid(&attestation).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_challenge_replay_rejected() {
+        let (authority, key) = authority(60_000);
+
+        let challenge = authority.begin_challenge("edge-node").unwrap();
+        let response = respond(&key, &challenge);
+        authority
+            .issue_with_challenge(request(), &response)
+            .await
+            .unwrap();
+
+        let replay = authority.issue_with_challenge(request(), &response).await;
+        assert!(matches!(replay, Err(SystemError::InvalidState { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_challenge_wrong_signature_rejected() {
+        let (authority, _) = authority(60_000);
+
+        let challenge = authority.begin_challenge("edge-node").unwrap();
+        let forged = respond(&KeyPair::generate(), &challenge);
+        let result = authority.issue_with_challenge(request(), &forged).await;
+        assert!(matches!(result, Err(SystemError::PermissionDenied { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_challenge_expired_nonce_rejected() {
+        let (authority, key) = authority(1);
+
+        let challenge = authority.begin_challenge("edge-node").unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+        let result = authority
+            .issue_with_challenge(request(), &respond(&key, &challenge))
+            .await;
+        assert!(matches!(result, Err(SystemError::Timeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_challenge_unknown_identity_rejected() {
+        let (authority, key) = authority(60_000);
+
+        assert!(matches!(
+            authority.begin_challenge("stranger"),
+            Err(SystemError::NotFound { .. })
+        ));
+
+        let challenge = authority.begin_challenge("edge-node").unwrap();
+        let result = authority
+            .issue_with_challenge(
+                AttestationRequest {
+                    identity: "stranger".to_string(),
+                    ..request()
+                },
+                &respond(&key, &challenge),
+            )
+            .await;
+        assert!(matches!(result, Err(SystemError::NotFound { .. })));
+    }
+}
@@ -0,0 +1,430 @@
+//! Claims module
+//!
+//! Schema validation for attestation claims, claims validators, typed claim
+//! accessors, and the canonical claim encoding used for signing.
+//!
+//! Schemas are written in a subset of JSON Schema: `type`, `enum`, `const`,
+//! `properties`, `required`, `additionalProperties`, `items`, `minimum`,
+//! `maximum`, `minLength`, `maxLength`, and `pattern`. Other keywords are
+//! ignored.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use shared_core::{Result, SystemError};
+
+use crate::Attestation;
+
+/// JSON Schema that attestation claims must satisfy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimsSchema {
+    schema: Value,
+}
+
+impl ClaimsSchema {
+    /// Create a schema from its JSON representation
+    pub fn new(schema: Value) -> Result<Self> {
+        if !schema.is_object() {
+            return Err(SystemError::config(
+                "claims schema must be a JSON object",
+                Some("claims_schemas".to_string()),
+            ));
+        }
+        Ok(Self { schema })
+    }
+
+    /// Get the raw schema
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// Validate a claims map
+    ///
+    /// Errors are `Validation` errors whose field is the JSON pointer of the
+    /// offending claim (the empty pointer refers to the claims object itself).
+    pub fn validate(&self, claims: &Map<String, Value>) -> Result<()> {
+        // Cloning keeps the validator working on plain `Value`s
+        validate_value(&self.schema, &Value::Object(claims.clone()), "")
+    }
+}
+
+/// Hook deciding whether claims may be attested, see
+/// [`AttestationConfig::claims_validator`](crate::AttestationConfig::claims_validator)
+///
+/// Validators compose as closures:
+/// `Arc::new(move |claims| size.validate(claims).and(required.validate(claims)))`.
+pub type ClaimsValidator = Arc<dyn Fn(&Map<String, Value>) -> Result<()> + Send + Sync>;
+
+/// Rejects claims whose JSON encoding is longer than the given number of bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxClaimsSizeValidator(pub usize);
+
+impl MaxClaimsSizeValidator {
+    /// Check the serialized size of `claims`
+    pub fn validate(&self, claims: &Map<String, Value>) -> Result<()> {
+        let size = serde_json::to_vec(claims)?.len();
+        if size > self.0 {
+            return Err(SystemError::validation(
+                "",
+                format!("serialized claims exceed {} bytes", self.0),
+                Some(size.to_string()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<MaxClaimsSizeValidator> for ClaimsValidator {
+    fn from(validator: MaxClaimsSizeValidator) -> Self {
+        Arc::new(move |claims| validator.validate(claims))
+    }
+}
+
+/// Rejects claims missing any of the given keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredClaimsValidator(pub Vec<String>);
+
+impl RequiredClaimsValidator {
+    /// Check that every required key is present in `claims`
+    pub fn validate(&self, claims: &Map<String, Value>) -> Result<()> {
+        match self.0.iter().find(|key| !claims.contains_key(key.as_str())) {
+            Some(key) => Err(SystemError::validation(
+                format!("/{}", escape_pointer(key)),
+                "required claim is missing",
+                None,
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl From<RequiredClaimsValidator> for ClaimsValidator {
+    fn from(validator: RequiredClaimsValidator) -> Self {
+        Arc::new(move |claims| validator.validate(claims))
+    }
+}
+
+/// Check whether `identity` matches a pattern where `*` matches any sequence
+pub fn identity_matches(pattern: &str, identity: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let identity: Vec<char> = identity.chars().collect();
+
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < identity.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, i));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == identity[i] {
+            p += 1;
+            i += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            i = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn invalid(pointer: &str, reason: impl Into<String>, value: &Value) -> SystemError {
+    SystemError::validation(pointer, reason, Some(value.to_string()))
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn validate_value(schema: &Value, value: &Value, pointer: &str) -> Result<()> {
+    let Some(schema) = schema.as_object() else {
+        return Ok(());
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(t, value)) {
+            return Err(invalid(
+                pointer,
+                format!("expected type {}", allowed.join(" or ")),
+                value,
+            ));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            return Err(invalid(pointer, "value is not one of the allowed values", value));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(invalid(pointer, format!("expected {expected}"), value));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                return Err(invalid(pointer, format!("must be >= {minimum}"), value));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                return Err(invalid(pointer, format!("must be <= {maximum}"), value));
+            }
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if length < min {
+                return Err(invalid(pointer, format!("length must be >= {min}"), value));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if length > max {
+                return Err(invalid(pointer, format!("length must be <= {max}"), value));
+            }
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            let regex = regex::Regex::new(pattern).map_err(|e| {
+                SystemError::config(
+                    format!("invalid pattern '{pattern}' in claims schema: {e}"),
+                    Some("claims_schemas".to_string()),
+                )
+            })?;
+            if !regex.is_match(text) {
+                return Err(invalid(pointer, format!("must match pattern '{pattern}'"), value));
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    return Err(SystemError::validation(
+                        format!("{pointer}/{}", escape_pointer(key)),
+                        "required claim is missing",
+                        None,
+                    ));
+                }
+            }
+        }
+
+        for (key, child) in object {
+            let child_pointer = format!("{pointer}/{}", escape_pointer(key));
+            match properties.and_then(|p| p.get(key)) {
+                Some(child_schema) => validate_value(child_schema, child, &child_pointer)?,
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        return Err(invalid(&child_pointer, "unexpected claim", child));
+                    },
+                    Some(extra_schema @ Value::Object(_)) => {
+                        validate_value(extra_schema, child, &child_pointer)?;
+                    },
+                    _ => {},
+                },
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            validate_value(items, item, &format!("{pointer}/{index}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rebuild a JSON value with object keys inserted in sorted order
+///
+/// This keeps the signing bytes stable even if `serde_json` is built with
+/// `preserve_order`, where maps keep insertion order.
+pub(crate) fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(canonicalize_map(map)),
+        Value::Array(items) => Value::Array(items.iter().map(canonicalize).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Sorted-key copy of a claims map, see [`canonicalize`]
+pub(crate) fn canonicalize_map(map: &Map<String, Value>) -> Map<String, Value> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .map(|key| (key.clone(), canonicalize(&map[key])))
+        .collect()
+}
+
+impl Attestation {
+    /// Get a string claim
+    pub fn claim_str(&self, key: &str) -> Option<&str> {
+        self.claims.get(key).and_then(Value::as_str)
+    }
+
+    /// Get an unsigned integer claim
+    pub fn claim_u64(&self, key: &str) -> Option<u64> {
+        self.claims.get(key).and_then(Value::as_u64)
+    }
+
+    /// Get all claims whose key starts with `prefix`, sorted by key
+    pub fn claims_matching(&self, prefix: &str) -> Vec<(&str, &Value)> {
+        let mut matching: Vec<(&str, &Value)> = self
+            .claims
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        matching.sort_by(|a, b| a.0.cmp(b.0));
+        matching
+    }
+
+    /// Require that every `(key, value)` pair is present with exactly that value
+    pub fn require_claims(&self, required: &[(&str, &Value)]) -> Result<()> {
+        for (key, expected) in required {
+            match self.claims.get(*key) {
+                Some(actual) if actual == *expected => {},
+                Some(actual) => {
+                    return Err(SystemError::validation(
+                        format!("/{}", escape_pointer(key)),
+                        format!("expected {expected}"),
+                        Some(actual.to_string()),
+                    ));
+                },
+                None => {
+                    return Err(SystemError::validation(
+                        format!("/{}", escape_pointer(key)),
+                        "required claim is missing",
+                        None,
+                    ));
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn claims(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    fn schema() -> ClaimsSchema {
+        ClaimsSchema::new(json!({
+            "type": "object",
+            "required": ["environment"],
+            "additionalProperties": false,
+            "properties": {
+                "environment": { "enum": ["production", "staging"] },
+                "replicas": { "type": "integer", "minimum": 1 },
+                "permissions": { "type": "array", "items": { "type": "string" } }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn field(err: SystemError) -> String {
+        match err {
+            SystemError::Validation { field, .. } => field,
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_schema_accepts_valid_claims() {
+        let valid = claims(json!({
+            "environment": "production",
+            "replicas": 3,
+            "permissions": ["read:users"]
+        }));
+        assert!(schema().validate(&valid).is_ok());
+    }
+
+    #[test]
+    fn test_schema_rejection_names_pointer() {
+        let typo = claims(json!({ "environment": "production", "enviroment": "staging" }));
+        assert_eq!(field(schema().validate(&typo).unwrap_err()), "/enviroment");
+
+        let missing = claims(json!({ "replicas": 2 }));
+        assert_eq!(field(schema().validate(&missing).unwrap_err()), "/environment");
+
+        let bad_item = claims(json!({ "environment": "staging", "permissions": ["ok", 7] }));
+        assert_eq!(field(schema().validate(&bad_item).unwrap_err()), "/permissions/1");
+
+        let too_small = claims(json!({ "environment": "staging", "replicas": 0 }));
+        assert_eq!(field(schema().validate(&too_small).unwrap_err()), "/replicas");
+    }
+
+    #[test]
+    fn test_builtin_validators_compose() {
+        let size = MaxClaimsSizeValidator(40);
+        let required = RequiredClaimsValidator(vec!["environment".to_string()]);
+        let validator: ClaimsValidator =
+            Arc::new(move |claims| size.validate(claims).and(required.validate(claims)));
+
+        assert!(validator(&claims(json!({ "environment": "staging" }))).is_ok());
+
+        let missing = claims(json!({ "replicas": 2 }));
+        assert_eq!(field(validator(&missing).unwrap_err()), "/environment");
+
+        let large = claims(json!({ "environment": "staging", "notes": "x".repeat(64) }));
+        assert_eq!(field(validator(&large).unwrap_err()), "");
+    }
+
+    #[test]
+    fn test_identity_patterns() {
+        assert!(identity_matches("*", "anything"));
+        assert!(identity_matches("service-*.prod", "service-a.prod"));
+        assert!(identity_matches("*.prod", "a.b.prod"));
+        assert!(!identity_matches("service-*.prod", "service-a.staging"));
+        assert!(identity_matches("exact", "exact"));
+        assert!(!identity_matches("exact", "exactly"));
+    }
+
+    #[test]
+    fn test_canonicalize_sorts_nested_keys() {
+        let mut inner = Map::new();
+        inner.insert("z".to_string(), json!(1));
+        inner.insert("a".to_string(), json!(2));
+        let mut outer = Map::new();
+        outer.insert("b".to_string(), Value::Object(inner));
+        outer.insert("a".to_string(), json!([{"y": 1, "x": 2}]));
+
+        let encoded = serde_json::to_string(&canonicalize_map(&outer)).unwrap();
+        assert_eq!(encoded, r#"{"a":[{"x":2,"y":1}],"b":{"a":2,"z":1}}"#);
+    }
+}
@@ -1 +1,40 @@
 //! Config module
+//!
+//! Storage backend selection for the attestation authority.
+
+use std::sync::Arc;
+
+use shared_core::Result;
+
+use crate::storage::{AttestationStore, MemoryStore, SledStore};
+
+/// Storage backend used to retain attestations and revocations
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// Volatile in-memory storage
+    #[default]
+    Memory,
+    /// Embedded sled database at the given directory
+    Sled {
+        /// Database directory
+        path: String,
+    },
+    /// SQLite database reached through sqlx
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        /// Connection URL, e.g. `sqlite://attestations.db?mode=rwc`
+        url: String,
+    },
+}
+
+impl StorageBackend {
+    /// Open the configured store
+    pub fn open(&self) -> Result<Arc<dyn AttestationStore>> {
+        Ok(match self {
+            Self::Memory => Arc::new(MemoryStore::new()),
+            Self::Sled { path } => Arc::new(SledStore::open(path)?),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite { url } => Arc::new(crate::storage::SqliteStore::new(url)?),
+        })
+    }
+}
@@ -1 +1,499 @@
 //! Core module
+//!
+//! Issuance policy: which identities may be issued which claims, for how
+//! long, and how often.
+//!
+//! A policy is a list of rules keyed by identity pattern (`*` matches any
+//! sequence). Only the most specific matching rule applies, where specificity
+//! is the number of literal characters in the pattern; ties go to the rule
+//! declared first. Policies implement [`Config`] so they can be loaded from
+//! TOML files and swapped at runtime.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use shared_core::{config::Config, Result, SystemError, Timestamp};
+
+use crate::{claims::identity_matches, AttestationRequest};
+
+/// Window over which `max_issuances_per_hour` is counted
+const RATE_WINDOW_MS: u64 = 60 * 60 * 1000;
+
+/// Source of the current time for policy decisions
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Current time
+    fn now(&self) -> Timestamp;
+}
+
+/// Wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+/// Rule applied to identities matching `identity_pattern`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    /// Rule name, reported in decisions and errors
+    pub name: String,
+    /// Identity pattern (`*` matches any sequence)
+    pub identity_pattern: String,
+    /// Claims that must be present
+    #[serde(default)]
+    pub required_claims: Vec<String>,
+    /// Claims that must not be present
+    #[serde(default)]
+    pub forbidden_claims: Vec<String>,
+    /// Maximum validity period, in seconds
+    #[serde(default)]
+    pub max_validity_seconds: Option<u64>,
+    /// Maximum attestations issued to one identity in any hour
+    #[serde(default)]
+    pub max_issuances_per_hour: Option<u32>,
+}
+
+impl PolicyRule {
+    fn specificity(&self) -> usize {
+        self.identity_pattern.chars().filter(|&c| c != '*').count()
+    }
+}
+
+/// Set of issuance rules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssuancePolicy {
+    /// Rules, see the module documentation for precedence
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+    /// Allow requests for identities that match no rule
+    #[serde(default = "default_allow_unmatched")]
+    pub allow_unmatched: bool,
+}
+
+fn default_allow_unmatched() -> bool {
+    true
+}
+
+impl Default for IssuancePolicy {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            allow_unmatched: true,
+        }
+    }
+}
+
+impl Config for IssuancePolicy {
+    fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for rule in &self.rules {
+            let key = |field: &str| Some(format!("rules.{}.{field}", rule.name));
+
+            if rule.name.is_empty() {
+                return Err(SystemError::config("rule name cannot be empty", Some("rules".into())));
+            }
+            if !names.insert(rule.name.as_str()) {
+                return Err(SystemError::config(
+                    format!("duplicate rule name '{}'", rule.name),
+                    key("name"),
+                ));
+            }
+            if rule.identity_pattern.is_empty() {
+                return Err(SystemError::config(
+                    "identity pattern cannot be empty",
+                    key("identity_pattern"),
+                ));
+            }
+            if rule.max_validity_seconds == Some(0) {
+                return Err(SystemError::config(
+                    "max_validity_seconds must be > 0",
+                    key("max_validity_seconds"),
+                ));
+            }
+            if rule.max_issuances_per_hour == Some(0) {
+                return Err(SystemError::config(
+                    "max_issuances_per_hour must be > 0",
+                    key("max_issuances_per_hour"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IssuancePolicy {
+    /// Find the rule that applies to an identity
+    pub fn rule_for(&self, identity: &str) -> Option<&PolicyRule> {
+        self.rules
+            .iter()
+            .filter(|rule| identity_matches(&rule.identity_pattern, identity))
+            // `max_by_key` keeps the last maximum, so reverse to prefer earlier rules
+            .rev()
+            .max_by_key(|rule| rule.specificity())
+    }
+}
+
+/// Outcome of evaluating a request against the issuance policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyDecision {
+    /// Whether issuance is allowed
+    pub allowed: bool,
+    /// Name of the rule that applied, if any
+    pub rule: Option<String>,
+    /// Why the request was denied
+    pub reason: Option<String>,
+    /// Requested identity
+    pub identity: String,
+    /// When the decision was made
+    pub timestamp: Timestamp,
+}
+
+impl PolicyDecision {
+    /// Convert a denial into a `PermissionDenied` error naming the rule
+    pub fn into_result(self) -> Result<()> {
+        if self.allowed {
+            return Ok(());
+        }
+        let rule = self.rule.as_deref().unwrap_or("default");
+        Err(SystemError::PermissionDenied {
+            operation: "issue".to_string(),
+            required_permission: Some(format!(
+                "policy rule '{rule}': {}",
+                self.reason.as_deref().unwrap_or("denied")
+            )),
+        })
+    }
+}
+
+/// Evaluates requests against the current policy and tracks issuance rates
+pub(crate) struct PolicyEngine {
+    policy: RwLock<IssuancePolicy>,
+    clock: Arc<dyn Clock>,
+    /// Recent issuance times per identity, oldest first
+    issuances: DashMap<String, VecDeque<Timestamp>>,
+}
+
+impl PolicyEngine {
+    pub(crate) fn new(policy: IssuancePolicy, clock: Arc<dyn Clock>) -> Result<Self> {
+        policy.validate()?;
+        Ok(Self {
+            policy: RwLock::new(policy),
+            clock,
+            issuances: DashMap::new(),
+        })
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Replace the policy; issuance history is kept
+    pub(crate) fn reload(&self, policy: IssuancePolicy) -> Result<()> {
+        policy.validate()?;
+        *self.policy.write() = policy;
+        Ok(())
+    }
+
+    /// Evaluate without recording an issuance
+    pub(crate) fn evaluate(&self, request: &AttestationRequest) -> PolicyDecision {
+        let now = self.clock.now();
+        let recent = self
+            .issuances
+            .get(&request.identity)
+            .map_or(0, |times| count_since(&times, now));
+        self.decide(request, now, recent)
+    }
+
+    /// Evaluate and, if allowed, count the issuance against the rate limit
+    pub(crate) fn admit(&self, request: &AttestationRequest) -> PolicyDecision {
+        let now = self.clock.now();
+        // The entry lock makes check-and-record atomic per identity
+        let mut times = self.issuances.entry(request.identity.clone()).or_default();
+        let window_start = now.as_millis().saturating_sub(RATE_WINDOW_MS);
+        while times.front().is_some_and(|t| t.as_millis() <= window_start) {
+            times.pop_front();
+        }
+
+        let decision = self.decide(request, now, times.len());
+        if decision.allowed {
+            times.push_back(now);
+        }
+        decision
+    }
+
+    fn decide(
+        &self,
+        request: &AttestationRequest,
+        now: Timestamp,
+        recent: usize,
+    ) -> PolicyDecision {
+        let policy = self.policy.read();
+        let mut decision = PolicyDecision {
+            allowed: false,
+            rule: None,
+            reason: None,
+            identity: request.identity.clone(),
+            timestamp: now,
+        };
+
+        let Some(rule) = policy.rule_for(&request.identity) else {
+            decision.allowed = policy.allow_unmatched;
+            if !decision.allowed {
+                decision.reason = Some("no policy rule matches the identity".to_string());
+            }
+            return decision;
+        };
+        decision.rule = Some(rule.name.clone());
+        decision.reason = violation(rule, request, recent);
+        decision.allowed = decision.reason.is_none();
+        decision
+    }
+}
+
+fn count_since(times: &VecDeque<Timestamp>, now: Timestamp) -> usize {
+    let window_start = now.as_millis().saturating_sub(RATE_WINDOW_MS);
+    times.iter().filter(|t| t.as_millis() > window_start).count()
+}
+
+/// First way in which `request` breaks `rule`
+fn violation(rule: &PolicyRule, request: &AttestationRequest, recent: usize) -> Option<String> {
+    if let Some(max) = rule.max_validity_seconds {
+        if request.validity_seconds > max {
+            return Some(format!("validity exceeds {max} seconds"));
+        }
+    }
+
+    if let Some(missing) = rule
+        .required_claims
+        .iter()
+        .find(|claim| !request.claims.contains_key(*claim))
+    {
+        return Some(format!("required claim '{missing}' is missing"));
+    }
+
+    if let Some(forbidden) = rule
+        .forbidden_claims
+        .iter()
+        .find(|claim| request.claims.contains_key(*claim))
+    {
+        return Some(format!("claim '{forbidden}' is forbidden"));
+    }
+
+    if let Some(limit) = rule.max_issuances_per_hour {
+        if recent >= limit as usize {
+            return Some(format!("rate limit of {limit} issuances per hour reached"));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{AttestationAuthority, AttestationConfig, Page};
+
+    #[derive(Debug)]
+    struct ManualClock(AtomicU64);
+
+    impl ManualClock {
+        fn advance(&self, millis: u64) {
+            self.0.fetch_add(millis, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Timestamp {
+            Timestamp::from_millis(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    fn rule(name: &str, identity_pattern: &str) -> PolicyRule {
+        PolicyRule {
+            name: name.to_string(),
+            identity_pattern: identity_pattern.to_string(),
+            required_claims: Vec::new(),
+            forbidden_claims: Vec::new(),
+            max_validity_seconds: None,
+            max_issuances_per_hour: None,
+        }
+    }
+
+    fn request(identity: &str, claims: serde_json::Value) -> AttestationRequest {
+        AttestationRequest {
+            identity: identity.to_string(),
+            claims: claims.as_object().unwrap().clone(),
+            validity_seconds: 3600,
+            not_before: None,
+        }
+    }
+
+    fn authority(policy: IssuancePolicy) -> AttestationAuthority {
+        let config = AttestationConfig {
+            issuance_policy: policy,
+            ..Default::default()
+        };
+        AttestationAuthority::new(config).unwrap()
+    }
+
+    #[test]
+    fn test_most_specific_rule_wins() {
+        let policy = IssuancePolicy {
+            rules: vec![
+                rule("catch-all", "*"),
+                rule("payments", "payments-*"),
+                rule("payments-api", "payments-api"),
+                rule("payments-dup", "payments-*"),
+            ],
+            allow_unmatched: false,
+        };
+
+        let name = |identity| policy.rule_for(identity).map(|r| r.name.as_str());
+        assert_eq!(name("payments-api"), Some("payments-api"));
+        assert_eq!(name("payments-worker"), Some("payments"));
+        assert_eq!(name("search"), Some("catch-all"));
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_and_required_claims() {
+        let authority = authority(IssuancePolicy {
+            rules: vec![PolicyRule {
+                required_claims: vec!["team".to_string()],
+                forbidden_claims: vec!["admin".to_string()],
+                ..rule("frontend", "frontend-*")
+            }],
+            allow_unmatched: false,
+        });
+
+        let result = authority
+            .issue(request("frontend-web", json!({ "team": "web", "admin": true })))
+            .await;
+        assert!(matches!(
+            result,
+            Err(SystemError::PermissionDenied { required_permission: Some(ref p), .. })
+                if p.contains("'frontend'") && p.contains("'admin'")
+        ));
+
+        let missing = authority.evaluate_policy(&request("frontend-web", json!({})));
+        assert!(!missing.allowed);
+        assert_eq!(missing.rule.as_deref(), Some("frontend"));
+
+        assert!(authority
+            .issue(request("frontend-web", json!({ "team": "web" })))
+            .await
+            .is_ok());
+
+        let unmatched = authority.evaluate_policy(&request("backend", json!({})));
+        assert!(!unmatched.allowed);
+        assert_eq!(unmatched.rule, None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_with_manual_clock() {
+        let clock = Arc::new(ManualClock(AtomicU64::new(1_000_000)));
+        let authority = authority(IssuancePolicy {
+            rules: vec![PolicyRule {
+                max_issuances_per_hour: Some(2),
+                ..rule("limited", "batch-*")
+            }],
+            allow_unmatched: true,
+        })
+        .with_clock(clock.clone());
+
+        let job = || request("batch-job", json!({}));
+        authority.issue(job()).await.unwrap();
+        clock.advance(10 * 60 * 1000);
+        authority.issue(job()).await.unwrap();
+
+        // Dry runs do not consume the budget
+        assert!(!authority.evaluate_policy(&job()).allowed);
+        assert!(authority.issue(job()).await.is_err());
+        assert!(authority.issue(request("batch-other", json!({}))).await.is_ok());
+
+        // The first issuance leaves the window after an hour
+        clock.advance(50 * 60 * 1000);
+        assert!(authority.evaluate_policy(&job()).allowed);
+        authority.issue(job()).await.unwrap();
+        assert!(authority.issue(job()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_audit_trail() {
+        let clock = Arc::new(ManualClock(AtomicU64::new(5_000)));
+        let authority = authority(IssuancePolicy {
+            rules: vec![PolicyRule {
+                max_validity_seconds: Some(60),
+                ..rule("short-lived", "*")
+            }],
+            allow_unmatched: true,
+        })
+        .with_clock(clock.clone());
+
+        authority
+            .issue(AttestationRequest {
+                validity_seconds: 60,
+                ..request("svc-a", json!({}))
+            })
+            .await
+            .unwrap();
+        clock.advance(1);
+        assert!(authority.issue(request("svc-b", json!({}))).await.is_err());
+        authority.evaluate_policy(&request("svc-c", json!({})));
+
+        let audit = authority.audit_log(Page::default()).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert!(audit[0].allowed);
+        assert_eq!(audit[0].identity, "svc-a");
+        assert_eq!(audit[0].timestamp, Timestamp::from_millis(5_000));
+        assert!(!audit[1].allowed);
+        assert_eq!(audit[1].identity, "svc-b");
+        assert_eq!(audit[1].rule.as_deref(), Some("short-lived"));
+        assert_eq!(audit[1].timestamp, Timestamp::from_millis(5_001));
+    }
+
+    #[tokio::test]
+    async fn test_policy_hot_reload_from_file() {
+        let authority = authority(IssuancePolicy::default());
+        assert!(authority.evaluate_policy(&request("svc", json!({}))).allowed);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.toml");
+        std::fs::write(
+            &path,
+            r#"
+            allow_unmatched = false
+
+            [[rules]]
+            name = "internal"
+            identity_pattern = "internal-*"
+            forbidden_claims = ["root"]
+            "#,
+        )
+        .unwrap();
+        authority.reload_policy_from_file(&path).unwrap();
+
+        assert!(!authority.evaluate_policy(&request("svc", json!({}))).allowed);
+        assert!(authority.evaluate_policy(&request("internal-db", json!({}))).allowed);
+
+        let invalid = IssuancePolicy {
+            rules: vec![rule("dup", "a"), rule("dup", "b")],
+            allow_unmatched: true,
+        };
+        assert!(matches!(
+            authority.reload_policy(invalid),
+            Err(SystemError::Config { .. })
+        ));
+        assert!(authority.evaluate_policy(&request("internal-db", json!({}))).allowed);
+    }
+}
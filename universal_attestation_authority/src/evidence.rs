@@ -0,0 +1,614 @@
+//! Evidence module
+//!
+//! Hardware evidence presented with an attestation request. An
+//! [`EvidenceVerifier`] checks the evidence against the challenge nonce and
+//! turns it into claims that are added to the issued attestation.
+//!
+//! [`TpmQuoteVerifier`] handles TPM 2.0 quotes: a `TPMS_ATTEST` structure
+//! signed by an attestation key (AK), together with the PCR values it covers.
+//! The quote's `extraData` must hold the decoded challenge nonce, its PCR
+//! digest must match the presented values, and those values must match the
+//! [`PcrPolicy`] configured for the identity.
+
+use std::collections::BTreeMap;
+
+use dashmap::DashMap;
+use ring::{digest, signature};
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+
+use crate::{claims, AttestationConfig};
+
+const TPM_GENERATED_VALUE: u32 = 0xFF54_4347;
+const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+const TPM_ALG_SHA256: u16 = 0x000B;
+const TPM_ALG_RSASSA: u16 = 0x0014;
+const TPM_ALG_ECDSA: u16 = 0x0018;
+
+/// Largest PCR selection bitmap accepted, in bytes
+const MAX_PCR_SELECT: usize = 32;
+/// Largest number of PCR selections accepted in one quote
+const MAX_PCR_SELECTIONS: u32 = 16;
+
+/// Evidence accompanying an attestation request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub enum Evidence {
+    /// TPM 2.0 quote over SHA-256 PCRs
+    TpmQuote {
+        /// Marshalled `TPMS_ATTEST` structure
+        quote: Vec<u8>,
+        /// Marshalled `TPMT_SIGNATURE` over `quote`
+        signature: Vec<u8>,
+        /// Values of the quoted PCRs, keyed by PCR index
+        pcrs: BTreeMap<u32, Vec<u8>>,
+    },
+}
+
+/// Checks evidence and derives claims from it
+pub trait EvidenceVerifier: Send + Sync {
+    /// Whether `identity` can present evidence to this verifier
+    fn is_enrolled(&self, identity: &str) -> bool;
+
+    /// Verify evidence bound to `nonce` and return the claims it supports
+    ///
+    /// Malformed evidence fails with `Validation`, evidence that is well
+    /// formed but not trusted with `PermissionDenied`.
+    fn verify(
+        &self,
+        identity: &str,
+        evidence: &Evidence,
+        nonce: &[u8],
+    ) -> Result<serde_json::Map<String, serde_json::Value>>;
+}
+
+/// Public part of a TPM attestation key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AkPublicKey {
+    /// ECDSA key on P-256, as an uncompressed SEC1 point
+    EcdsaP256 {
+        /// `0x04 || X || Y`
+        point: Vec<u8>,
+    },
+    /// RSA key for RSASSA-PKCS1-v1_5 signatures
+    Rsa {
+        /// Big-endian modulus
+        modulus: Vec<u8>,
+        /// Big-endian public exponent
+        exponent: Vec<u8>,
+    },
+}
+
+/// Expected SHA-256 PCR values for an identity
+///
+/// Every PCR listed must be quoted with exactly this value. Quoted PCRs that
+/// are not listed are accepted and still reported as claims.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PcrPolicy {
+    /// Expected value per PCR index
+    pub expected: BTreeMap<u32, Vec<u8>>,
+}
+
+impl PcrPolicy {
+    /// Create an empty policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require PCR `index` to hold `value`
+    pub fn expect(mut self, index: u32, value: impl Into<Vec<u8>>) -> Self {
+        self.expected.insert(index, value.into());
+        self
+    }
+}
+
+/// Fields of a quote that verification uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Quote {
+    extra_data: Vec<u8>,
+    firmware_version: u64,
+    /// Selected SHA-256 PCR indices, in the order they are digested
+    pcrs: Vec<u32>,
+    pcr_digest: Vec<u8>,
+}
+
+/// Bounds-checked big-endian reader over a marshalled TPM structure
+struct Reader<'a> {
+    structure: &'static str,
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(structure: &'static str, bytes: &'a [u8]) -> Self {
+        Self { structure, bytes }
+    }
+
+    fn malformed(&self, reason: impl Into<String>) -> SystemError {
+        SystemError::validation(self.structure, reason, None)
+    }
+
+    fn take(&mut self, len: usize, field: &str) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(self.malformed(format!("truncated at {field}")));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self, field: &str) -> Result<u8> {
+        Ok(self.take(1, field)?[0])
+    }
+
+    fn u16(&mut self, field: &str) -> Result<u16> {
+        let bytes = self.take(2, field)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self, field: &str) -> Result<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4, field)?);
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn u64(&mut self, field: &str) -> Result<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8, field)?);
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    /// A `TPM2B_*` sized buffer
+    fn sized(&mut self, field: &str) -> Result<&'a [u8]> {
+        let len = self.u16(field)?;
+        self.take(usize::from(len), field)
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(self.malformed(format!("{} trailing bytes", self.bytes.len())))
+        }
+    }
+}
+
+impl Quote {
+    /// Parse a `TPMS_ATTEST` holding a `TPMS_QUOTE_INFO`
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new("quote", bytes);
+
+        if reader.u32("magic")? != TPM_GENERATED_VALUE {
+            return Err(reader.malformed("not generated by a TPM"));
+        }
+        let kind = reader.u16("type")?;
+        if kind != TPM_ST_ATTEST_QUOTE {
+            return Err(reader.malformed(format!("attestation type {kind:#06x} is not a quote")));
+        }
+        reader.sized("qualifiedSigner")?;
+        let extra_data = reader.sized("extraData")?.to_vec();
+        // clock (8), resetCount (4), restartCount (4), safe (1)
+        reader.take(17, "clockInfo")?;
+        let firmware_version = reader.u64("firmwareVersion")?;
+
+        let selections = reader.u32("pcrSelect.count")?;
+        if selections > MAX_PCR_SELECTIONS {
+            return Err(reader.malformed(format!("{selections} PCR selections")));
+        }
+        let mut pcrs = Vec::new();
+        for _ in 0..selections {
+            let hash = reader.u16("pcrSelect.hash")?;
+            if hash != TPM_ALG_SHA256 {
+                return Err(reader.malformed(format!("PCR bank {hash:#06x} is not SHA-256")));
+            }
+            let size = usize::from(reader.u8("pcrSelect.sizeofSelect")?);
+            if size > MAX_PCR_SELECT {
+                return Err(reader.malformed(format!("{size} byte PCR selection")));
+            }
+            let bitmap = reader.take(size, "pcrSelect.pcrSelect")?;
+            for (byte, bits) in (0u32..).zip(bitmap) {
+                pcrs.extend(
+                    (0..8)
+                        .filter(|bit| bits & (1 << bit) != 0)
+                        .map(|bit| byte * 8 + bit),
+                );
+            }
+        }
+        let pcr_digest = reader.sized("pcrDigest")?.to_vec();
+        reader.finish()?;
+
+        Ok(Self {
+            extra_data,
+            firmware_version,
+            pcrs,
+            pcr_digest,
+        })
+    }
+}
+
+/// Signature algorithm and bytes of a `TPMT_SIGNATURE`
+#[derive(Debug, PartialEq, Eq)]
+enum QuoteSignature {
+    /// `r || s`, each left-padded to 32 bytes
+    Ecdsa(Vec<u8>),
+    Rsassa(Vec<u8>),
+}
+
+impl QuoteSignature {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new("signature", bytes);
+        let algorithm = reader.u16("sigAlg")?;
+        let hash = reader.u16("hash")?;
+        if hash != TPM_ALG_SHA256 {
+            return Err(reader.malformed(format!("hash {hash:#06x} is not SHA-256")));
+        }
+
+        let signature = match algorithm {
+            TPM_ALG_ECDSA => {
+                let mut fixed = vec![0; 64];
+                for (half, field) in fixed.chunks_mut(32).zip(["signatureR", "signatureS"]) {
+                    let scalar = reader.sized(field)?;
+                    if scalar.len() > 32 {
+                        return Err(reader.malformed(format!("{field} longer than 32 bytes")));
+                    }
+                    half[32 - scalar.len()..].copy_from_slice(scalar);
+                }
+                Self::Ecdsa(fixed)
+            },
+            TPM_ALG_RSASSA => Self::Rsassa(reader.sized("sig")?.to_vec()),
+            other => {
+                return Err(reader.malformed(format!("signature scheme {other:#06x}")));
+            },
+        };
+        reader.finish()?;
+        Ok(signature)
+    }
+
+    fn verify(&self, key: &AkPublicKey, message: &[u8]) -> bool {
+        match (self, key) {
+            (Self::Ecdsa(sig), AkPublicKey::EcdsaP256 { point }) => {
+                signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                    .verify(message, sig)
+                    .is_ok()
+            },
+            (Self::Rsassa(sig), AkPublicKey::Rsa { modulus, exponent }) => {
+                signature::RsaPublicKeyComponents {
+                    n: modulus,
+                    e: exponent,
+                }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, sig)
+                .is_ok()
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Verifies TPM 2.0 quotes against enrolled attestation keys
+///
+/// Derived claims are `pcr<N>` (hex-encoded value) for every quoted PCR and
+/// `firmware_version`, formatted as the two 32-bit halves of the TPM's
+/// `firmwareVersion` joined by a dot.
+#[derive(Default)]
+pub struct TpmQuoteVerifier {
+    keys: DashMap<String, AkPublicKey>,
+    policies: Vec<(String, PcrPolicy)>,
+}
+
+impl TpmQuoteVerifier {
+    /// Create a verifier with PCR policies keyed by identity pattern
+    ///
+    /// Patterns work as for claims schemas; the first match wins. Identities
+    /// matching no pattern are rejected.
+    pub fn new(policies: Vec<(String, PcrPolicy)>) -> Self {
+        Self {
+            keys: DashMap::new(),
+            policies,
+        }
+    }
+
+    /// Create a verifier with the PCR policies of `config`
+    pub fn from_config(config: &AttestationConfig) -> Self {
+        Self::new(config.pcr_policies.clone())
+    }
+
+    /// Enroll the attestation key `identity` signs quotes with
+    pub fn register_key(&self, identity: impl Into<String>, key: AkPublicKey) {
+        self.keys.insert(identity.into(), key);
+    }
+
+    fn denied(requirement: String) -> SystemError {
+        SystemError::PermissionDenied {
+            operation: "issue_with_evidence".to_string(),
+            required_permission: Some(requirement),
+        }
+    }
+}
+
+impl EvidenceVerifier for TpmQuoteVerifier {
+    fn is_enrolled(&self, identity: &str) -> bool {
+        self.keys.contains_key(identity)
+    }
+
+    fn verify(
+        &self,
+        identity: &str,
+        evidence: &Evidence,
+        nonce: &[u8],
+    ) -> Result<serde_json::Map<String, serde_json::Value>> {
+        let Evidence::TpmQuote {
+            quote: quote_bytes,
+            signature,
+            pcrs,
+        } = evidence;
+
+        let key = self
+            .keys
+            .get(identity)
+            .map(|key| key.value().clone())
+            .ok_or_else(|| SystemError::not_found("attestation key", identity))?;
+        let policy = self
+            .policies
+            .iter()
+            .find(|(pattern, _)| claims::identity_matches(pattern, identity))
+            .map(|(_, policy)| policy)
+            .ok_or_else(|| SystemError::not_found("pcr policy", identity))?;
+
+        let quote = Quote::parse(quote_bytes)?;
+        if !QuoteSignature::parse(signature)?.verify(&key, quote_bytes) {
+            return Err(Self::denied(format!(
+                "quote signed by the AK enrolled for {identity}"
+            )));
+        }
+        if quote.extra_data != nonce {
+            return Err(Self::denied(
+                "quote bound to the challenge nonce".to_string(),
+            ));
+        }
+
+        let mut selected = Vec::with_capacity(quote.pcrs.len() * digest::SHA256_OUTPUT_LEN);
+        for index in &quote.pcrs {
+            let value = pcrs.get(index).ok_or_else(|| {
+                SystemError::validation("pcrs", format!("missing quoted PCR {index}"), None)
+            })?;
+            if value.len() != digest::SHA256_OUTPUT_LEN {
+                return Err(SystemError::validation(
+                    "pcrs",
+                    format!("PCR {index} is not a SHA-256 value"),
+                    Some(value.len().to_string()),
+                ));
+            }
+            selected.extend_from_slice(value);
+        }
+        if pcrs.len() != quote.pcrs.len() {
+            return Err(SystemError::validation(
+                "pcrs",
+                "values for PCRs that were not quoted",
+                None,
+            ));
+        }
+        if digest::digest(&digest::SHA256, &selected).as_ref() != quote.pcr_digest.as_slice() {
+            return Err(Self::denied(
+                "PCR values matching the quoted digest".to_string(),
+            ));
+        }
+
+        for (index, expected) in &policy.expected {
+            if pcrs.get(index) != Some(expected) {
+                return Err(Self::denied(format!(
+                    "PCR {index} matching the policy for {identity}"
+                )));
+            }
+        }
+
+        let mut claims = serde_json::Map::new();
+        for (index, value) in pcrs {
+            claims.insert(format!("pcr{index}"), hex(value).into());
+        }
+        let version = quote.firmware_version;
+        claims.insert(
+            "firmware_version".to_string(),
+            format!("{}.{}", version >> 32, version & 0xFFFF_FFFF).into(),
+        );
+        Ok(claims)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PCRS: [u32; 3] = [0, 2, 7];
+
+    fn evidence(case: &str) -> Evidence {
+        let read = |ext: &str| std::fs::read(format!("tests/data/tpm/{case}.{ext}")).unwrap();
+        Evidence::TpmQuote {
+            quote: read("quote"),
+            signature: read("sig"),
+            pcrs: PCRS
+                .into_iter()
+                .zip(read("pcrs").chunks(32).map(<[u8]>::to_vec))
+                .collect(),
+        }
+    }
+
+    fn nonce() -> Vec<u8> {
+        digest::digest(&digest::SHA256, b"uaa fixture nonce")
+            .as_ref()
+            .to_vec()
+    }
+
+    fn verifier() -> TpmQuoteVerifier {
+        let Evidence::TpmQuote { pcrs, .. } = evidence("valid");
+        let policy = PcrPolicy::new()
+            .expect(0, pcrs[&0].clone())
+            .expect(7, pcrs[&7].clone());
+        let verifier = TpmQuoteVerifier::new(vec![("edge-*".to_string(), policy)]);
+        verifier.register_key(
+            "edge-node",
+            AkPublicKey::EcdsaP256 {
+                point: std::fs::read("tests/data/tpm/ak_p256.pub").unwrap(),
+            },
+        );
+        verifier.register_key(
+            "edge-rsa",
+            AkPublicKey::Rsa {
+                modulus: std::fs::read("tests/data/tpm/ak_rsa.pub").unwrap(),
+                exponent: vec![0x01, 0x00, 0x01],
+            },
+        );
+        verifier
+    }
+
+    #[test]
+    fn test_valid_quotes() {
+        let verifier = verifier();
+
+        let claims = verifier
+            .verify("edge-node", &evidence("valid"), &nonce())
+            .unwrap();
+        let Evidence::TpmQuote { pcrs, .. } = evidence("valid");
+        assert_eq!(claims["pcr7"], hex(&pcrs[&7]));
+        assert!(claims.contains_key("pcr0") && claims.contains_key("pcr2"));
+        assert_eq!(claims["firmware_version"], "7.85");
+
+        let rsa = verifier
+            .verify("edge-rsa", &evidence("valid_rsa"), &nonce())
+            .unwrap();
+        assert_eq!(rsa, claims);
+
+        // A quote signed by another identity's key
+        let err = verifier
+            .verify("edge-rsa", &evidence("valid"), &nonce())
+            .unwrap_err();
+        assert!(matches!(err, SystemError::PermissionDenied { .. }));
+    }
+
+    #[test]
+    fn test_untrusted_quotes_rejected() {
+        let verifier = verifier();
+
+        for case in ["wrong_nonce", "wrong_pcrs"] {
+            let err = verifier
+                .verify("edge-node", &evidence(case), &nonce())
+                .unwrap_err();
+            assert!(
+                matches!(err, SystemError::PermissionDenied { .. }),
+                "{case}: {err}"
+            );
+        }
+
+        // PCR values that do not match the quoted digest
+        let Evidence::TpmQuote {
+            quote,
+            signature,
+            mut pcrs,
+        } = evidence("valid");
+        pcrs.insert(2, vec![0; 32]);
+        let err = verifier
+            .verify(
+                "edge-node",
+                &Evidence::TpmQuote {
+                    quote,
+                    signature,
+                    pcrs,
+                },
+                &nonce(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, SystemError::PermissionDenied { .. }));
+
+        let err = verifier
+            .verify("stranger", &evidence("valid"), &nonce())
+            .unwrap_err();
+        assert!(matches!(err, SystemError::NotFound { .. }));
+    }
+
+    #[test]
+    fn test_malformed_evidence_is_validation_error() {
+        let verifier = verifier();
+        let Evidence::TpmQuote {
+            quote,
+            signature,
+            pcrs,
+        } = evidence("valid");
+
+        let mut cases = Vec::new();
+        for len in 0..quote.len() {
+            cases.push((quote[..len].to_vec(), signature.clone(), pcrs.clone()));
+        }
+        for len in 0..signature.len() {
+            cases.push((quote.clone(), signature[..len].to_vec(), pcrs.clone()));
+        }
+        let mut trailing = quote.clone();
+        trailing.push(0);
+        cases.push((trailing, signature.clone(), pcrs.clone()));
+        let mut short_pcr = pcrs.clone();
+        short_pcr.insert(0, vec![0; 20]);
+        cases.push((quote.clone(), signature.clone(), short_pcr));
+        let mut missing_pcr = pcrs;
+        missing_pcr.remove(&7);
+        cases.push((quote, signature, missing_pcr));
+
+        for (quote, signature, pcrs) in cases {
+            let evidence = Evidence::TpmQuote {
+                quote,
+                signature,
+                pcrs,
+            };
+            let err = verifier
+                .verify("edge-node", &evidence, &nonce())
+                .unwrap_err();
+            assert!(matches!(err, SystemError::Validation { .. }), "{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_issue_with_evidence() {
+        let authority = crate::AttestationAuthority::new(AttestationConfig {
+            require_challenge: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let request = || crate::AttestationRequest {
+            identity: "edge-node".to_string(),
+            claims: serde_json::Map::new(),
+            validity_seconds: 3600,
+            not_before: None,
+        };
+
+        let err = authority
+            .issue_with_evidence(request(), "00", &evidence("valid"))
+            .await;
+        assert!(matches!(err, Err(SystemError::Config { .. })));
+
+        let authority = authority.with_evidence_verifier(std::sync::Arc::new(verifier()));
+        assert!(authority.begin_challenge("edge-node").is_ok());
+
+        let challenge = authority
+            .challenges
+            .begin_with_nonce("edge-node", &nonce(), 60_000);
+        let attestation = authority
+            .issue_with_evidence(request(), &challenge.nonce, &evidence("valid"))
+            .await
+            .unwrap();
+        assert_eq!(attestation.claims["firmware_version"], "7.85");
+        assert!(authority.is_valid(&attestation).await.unwrap());
+
+        // The nonce is single use
+        let replay = authority
+            .issue_with_evidence(request(), &challenge.nonce, &evidence("valid"))
+            .await;
+        assert!(matches!(replay, Err(SystemError::InvalidState { .. })));
+
+        let fresh = authority.begin_challenge("edge-node").unwrap();
+        let stale = authority
+            .issue_with_evidence(request(), &fresh.nonce, &evidence("valid"))
+            .await;
+        assert!(matches!(stale, Err(SystemError::PermissionDenied { .. })));
+    }
+}
@@ -0,0 +1,494 @@
+//! gRPC API
+//!
+//! Serves the authority over gRPC when built with the `grpc` feature. The
+//! service is defined in `proto/attestation.proto`:
+//!
+//! - `Issue` issues an attestation; include `challenge` when the authority
+//!   requires challenge-response issuance
+//! - `Verify` verifies an attestation
+//! - `Revoke` revokes an attestation; callers need `authorization: Bearer`
+//!   metadata or an allow-listed client certificate, as with the HTTP API
+//! - `GetJwks` publishes the verification keys
+//! - `WatchRevocations` streams revocations as they happen
+//!
+//! Claims travel as a `google.protobuf.Struct`. Its numbers are doubles, so
+//! integral values are turned back into JSON integers on the way in.
+//! Failures map to the gRPC status matching their HTTP status; rate limited
+//! calls fail with `RESOURCE_EXHAUSTED` and [`RETRY_AFTER_MS`] metadata.
+
+// The generated service trait fixes the error type to `tonic::Status`
+#![allow(clippy::result_large_err)]
+
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::time::Instant;
+
+use futures::{stream, Stream, StreamExt};
+use prost_types::value::Kind;
+use shared_core::{ErrorResponse, Result, SystemError, Timestamp};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Code, Request, Response, Status};
+use tracing::Instrument;
+
+use crate::api::{authorize_revoke, ApiState, ClientCertificate};
+use crate::{audit, AttestationRequest, ChallengeResponse, VerificationOutcome};
+
+/// Types and stubs generated from `proto/attestation.proto`
+#[allow(missing_docs, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("uaa.v1");
+}
+
+use proto::attestation_authority_server::{AttestationAuthority, AttestationAuthorityServer};
+
+/// Build the gRPC service
+pub fn service(state: ApiState) -> AttestationAuthorityServer<GrpcService> {
+    AttestationAuthorityServer::new(GrpcService { state })
+}
+
+/// Serve the gRPC API on `listener` until the server fails
+pub async fn serve(listener: TcpListener, state: ApiState) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(service(state))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .map_err(|e| SystemError::internal(format!("serving gRPC API: {e}"), None))
+}
+
+/// Implementation of the generated `AttestationAuthority` service
+#[derive(Clone)]
+pub struct GrpcService {
+    state: ApiState,
+}
+
+/// Metadata key carrying how long to wait before retrying, in milliseconds
+pub const RETRY_AFTER_MS: &str = "retry-after-ms";
+
+type RpcResult<T> = std::result::Result<Response<T>, Status>;
+
+type RevocationStream =
+    Pin<Box<dyn Stream<Item = std::result::Result<proto::RevocationEntry, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl AttestationAuthority for GrpcService {
+    async fn issue(&self, request: Request<proto::IssueRequest>) -> RpcResult<proto::Attestation> {
+        observe("Issue", "grpc".to_string(), async {
+            let body = request.into_inner();
+            let challenge = body.challenge.clone().map(|c| ChallengeResponse {
+                nonce: c.nonce,
+                signature: c.signature,
+            });
+            let request = AttestationRequest {
+                identity: body.identity,
+                claims: struct_to_claims(body.claims)?,
+                validity_seconds: body.validity_seconds,
+                not_before: body.not_before_ms.map(Timestamp::from_millis),
+            };
+            let authority = &self.state.authority;
+            let attestation = match &challenge {
+                Some(response) => authority.issue_with_challenge(request, response).await?,
+                None => authority.issue(request).await?,
+            };
+            Ok(attestation.into())
+        })
+        .await
+    }
+
+    async fn verify(
+        &self,
+        request: Request<proto::VerifyRequest>,
+    ) -> RpcResult<proto::VerifyResponse> {
+        observe("Verify", "grpc".to_string(), async {
+            let attestation = request
+                .into_inner()
+                .attestation
+                .ok_or_else(|| SystemError::validation("attestation", "is required", None))?;
+            let result = self.state.authority.verify(&attestation.try_into()?).await?;
+            Ok(result.outcome.into())
+        })
+        .await
+    }
+
+    async fn revoke(
+        &self,
+        request: Request<proto::RevokeRequest>,
+    ) -> RpcResult<proto::RevokeResponse> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        let certificate = request.extensions().get::<ClientCertificate>();
+        let actor = authorize_revoke(&self.state.config, "grpc", authorization, certificate)
+            .map_err(into_status)?;
+
+        observe("Revoke", actor, async {
+            let body = request.into_inner();
+            self.state.authority.revoke(&body.id, body.reason).await?;
+            Ok(proto::RevokeResponse {})
+        })
+        .await
+    }
+
+    async fn get_jwks(&self, _request: Request<proto::GetJwksRequest>) -> RpcResult<proto::Jwks> {
+        observe("GetJwks", "grpc".to_string(), async {
+            let keys = self.state.authority.jwks().keys.into_iter().map(|jwk| proto::Jwk {
+                kty: jwk.kty,
+                crv: jwk.crv,
+                x: jwk.x,
+                kid: jwk.kid,
+                alg: jwk.alg,
+                r#use: jwk.use_,
+            });
+            Ok(proto::Jwks { keys: keys.collect() })
+        })
+        .await
+    }
+
+    type WatchRevocationsStream = RevocationStream;
+
+    async fn watch_revocations(
+        &self,
+        request: Request<proto::WatchRevocationsRequest>,
+    ) -> RpcResult<RevocationStream> {
+        observe("WatchRevocations", "grpc".to_string(), async {
+            // Subscribe before listing so nothing falls between the two
+            let receiver = self.state.authority.subscribe_revocations();
+            let existing = if request.into_inner().include_existing {
+                self.state.authority.revocations().await?
+            } else {
+                Vec::new()
+            };
+            let seen: Vec<String> = existing.iter().map(|e| e.attestation_id.clone()).collect();
+
+            let live = stream::unfold(Some(receiver), |receiver| async move {
+                let mut receiver = receiver?;
+                match receiver.recv().await {
+                    Ok(entry) => Some((Ok(entry), Some(receiver))),
+                    Err(RecvError::Lagged(missed)) => {
+                        let message = format!("subscriber lagged behind by {missed} revocations");
+                        Some((Err(Status::aborted(message)), None))
+                    },
+                    Err(RecvError::Closed) => None,
+                }
+            })
+            .filter(move |item| {
+                let duplicate = matches!(item, Ok(entry) if seen.contains(&entry.attestation_id));
+                futures::future::ready(!duplicate)
+            });
+
+            let entries = stream::iter(existing.into_iter().map(Ok)).chain(live);
+            Ok(Box::pin(entries.map(|item| item.map(Into::into))) as RevocationStream)
+        })
+        .await
+    }
+}
+
+/// Run an RPC inside a span with `actor` recorded on audited operations
+async fn observe<T>(
+    method: &'static str,
+    actor: String,
+    rpc: impl std::future::Future<Output = Result<T>>,
+) -> RpcResult<T> {
+    let span = tracing::info_span!("grpc_request", method, code = tracing::field::Empty);
+
+    let start = Instant::now();
+    let result = audit::with_actor(actor, rpc).instrument(span.clone()).await;
+    let result = result.map(Response::new).map_err(into_status);
+    let code = result.as_ref().map_or_else(Status::code, |_| Code::Ok);
+    span.record("code", tracing::field::debug(code));
+
+    shared_core::count!(
+        "uaa_grpc_requests_total", 1,
+        "method" => method, "code" => format!("{code:?}")
+    );
+    shared_core::histogram!(
+        "uaa_grpc_request_duration_seconds", start.elapsed().as_secs_f64(),
+        "method" => method
+    );
+    result
+}
+
+/// Map an error to the gRPC status matching its HTTP status
+fn into_status(err: SystemError) -> Status {
+    let body = ErrorResponse::from(&err);
+    if body.status >= 500 {
+        tracing::error!(error = ?err.to_log_value(), "RPC failed");
+    } else {
+        tracing::debug!(error = ?err.to_log_value(), "RPC rejected");
+    }
+    let code = match body.status {
+        400 => Code::InvalidArgument,
+        403 => Code::PermissionDenied,
+        404 => Code::NotFound,
+        429 => Code::ResourceExhausted,
+        409 if matches!(err, SystemError::AlreadyExists { .. }) => Code::AlreadyExists,
+        409 => Code::FailedPrecondition,
+        502 | 503 => Code::Unavailable,
+        504 => Code::DeadlineExceeded,
+        _ => Code::Internal,
+    };
+    let mut status = Status::new(code, body.message);
+    if let Some(retry_after) = err.retry_after() {
+        let millis = retry_after.as_millis().to_string();
+        if let Ok(value) = millis.parse() {
+            status.metadata_mut().insert(RETRY_AFTER_MS, value);
+        }
+    }
+    status
+}
+
+type Claims = serde_json::Map<String, serde_json::Value>;
+
+fn struct_to_claims(claims: Option<prost_types::Struct>) -> Result<Claims> {
+    claims
+        .unwrap_or_default()
+        .fields
+        .into_iter()
+        .map(|(name, value)| Ok((name, value_to_json(value)?)))
+        .collect()
+}
+
+fn claims_to_struct(claims: Claims) -> prost_types::Struct {
+    let fields: BTreeMap<_, _> =
+        claims.into_iter().map(|(name, value)| (name, json_to_value(value))).collect();
+    prost_types::Struct { fields }
+}
+
+fn value_to_json(value: prost_types::Value) -> Result<serde_json::Value> {
+    Ok(match value.kind {
+        None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+        Some(Kind::BoolValue(b)) => b.into(),
+        Some(Kind::NumberValue(n)) => number_to_json(n)?,
+        Some(Kind::StringValue(s)) => s.into(),
+        Some(Kind::ListValue(list)) => serde_json::Value::Array(
+            list.values.into_iter().map(value_to_json).collect::<Result<_>>()?,
+        ),
+        Some(Kind::StructValue(s)) => struct_to_claims(Some(s))?.into(),
+    })
+}
+
+/// Largest magnitude at which every integer is exactly representable in an f64
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+fn number_to_json(n: f64) -> Result<serde_json::Value> {
+    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+        return Ok((n as i64).into());
+    }
+    serde_json::Number::from_f64(n)
+        .map(serde_json::Value::Number)
+        .ok_or_else(|| SystemError::validation("claims", "numbers must be finite", None))
+}
+
+fn json_to_value(value: serde_json::Value) -> prost_types::Value {
+    let kind = match value {
+        serde_json::Value::Null => Kind::NullValue(0),
+        serde_json::Value::Bool(b) => Kind::BoolValue(b),
+        serde_json::Value::Number(n) => Kind::NumberValue(n.as_f64().unwrap_or_default()),
+        serde_json::Value::String(s) => Kind::StringValue(s),
+        serde_json::Value::Array(values) => Kind::ListValue(prost_types::ListValue {
+            values: values.into_iter().map(json_to_value).collect(),
+        }),
+        serde_json::Value::Object(map) => Kind::StructValue(claims_to_struct(map)),
+    };
+    prost_types::Value { kind: Some(kind) }
+}
+
+impl From<crate::Attestation> for proto::Attestation {
+    fn from(attestation: crate::Attestation) -> Self {
+        Self {
+            id: attestation.id,
+            identity: attestation.identity,
+            claims: Some(claims_to_struct(attestation.claims)),
+            issued_at_ms: attestation.issued_at.as_millis(),
+            not_before_ms: attestation.not_before.as_millis(),
+            expires_at_ms: attestation.expires_at.as_millis(),
+            key_id: attestation.key_id,
+            renews: attestation.renews,
+            signature: attestation.signature,
+        }
+    }
+}
+
+impl TryFrom<proto::Attestation> for crate::Attestation {
+    type Error = SystemError;
+
+    fn try_from(attestation: proto::Attestation) -> Result<Self> {
+        Ok(Self {
+            id: attestation.id,
+            identity: attestation.identity,
+            claims: struct_to_claims(attestation.claims)?,
+            issued_at: Timestamp::from_millis(attestation.issued_at_ms),
+            not_before: Timestamp::from_millis(attestation.not_before_ms),
+            expires_at: Timestamp::from_millis(attestation.expires_at_ms),
+            key_id: attestation.key_id,
+            renews: attestation.renews,
+            signature: attestation.signature,
+        })
+    }
+}
+
+impl From<VerificationOutcome> for proto::VerifyResponse {
+    fn from(outcome: VerificationOutcome) -> Self {
+        let mut response = Self {
+            status: outcome.status().to_string(),
+            ..Self::default()
+        };
+        match outcome {
+            VerificationOutcome::Expired { at } | VerificationOutcome::NotYetValid { at } => {
+                response.at_ms = Some(at.as_millis());
+            },
+            VerificationOutcome::Revoked { reason, at } => {
+                response.at_ms = Some(at.as_millis());
+                response.reason = Some(reason);
+            },
+            VerificationOutcome::KeyRetired { key_id, at } => {
+                response.at_ms = Some(at.as_millis());
+                response.key_id = Some(key_id);
+            },
+            VerificationOutcome::Valid
+            | VerificationOutcome::BadSignature
+            | VerificationOutcome::Unknown => {},
+        }
+        response
+    }
+}
+
+impl From<crate::RevocationEntry> for proto::RevocationEntry {
+    fn from(entry: crate::RevocationEntry) -> Self {
+        Self {
+            attestation_id: entry.attestation_id,
+            reason: entry.reason,
+            revoked_at_ms: entry.revoked_at.as_millis(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiConfig;
+    use crate::{AttestationAuthority as Authority, AttestationConfig};
+    use proto::attestation_authority_client::AttestationAuthorityClient;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tonic::transport::Channel;
+
+    const TOKEN: &str = "revoke-secret";
+
+    async fn spawn() -> AttestationAuthorityClient<Channel> {
+        let authority = Authority::new(AttestationConfig::default()).unwrap();
+        let config = ApiConfig {
+            revoke_tokens: vec![TOKEN.to_string()],
+            ..ApiConfig::default()
+        };
+        let state = ApiState::new(Arc::new(authority), config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state));
+        AttestationAuthorityClient::connect(format!("http://{addr}")).await.unwrap()
+    }
+
+    fn revoke_request(id: &str, token: Option<&str>) -> Request<proto::RevokeRequest> {
+        let mut request = Request::new(proto::RevokeRequest {
+            id: id.to_string(),
+            reason: "key_compromise".to_string(),
+        });
+        if let Some(token) = token {
+            let value = format!("Bearer {token}").parse().unwrap();
+            request.metadata_mut().insert("authorization", value);
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_issue_verify_revoke() {
+        let mut client = spawn().await;
+        let claims = json!({ "role": "node", "cores": 8, "load": 0.5, "tags": ["a", null] });
+        let claims = claims_to_struct(claims.as_object().unwrap().clone());
+
+        let attestation = client
+            .issue(proto::IssueRequest {
+                identity: "svc-a".to_string(),
+                claims: Some(claims),
+                validity_seconds: 3600,
+                ..proto::IssueRequest::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let decoded = crate::Attestation::try_from(attestation.clone()).unwrap();
+        assert_eq!(decoded.claims["cores"], json!(8));
+        assert_eq!(decoded.claims["load"], json!(0.5));
+        assert_eq!(decoded.claims["tags"], json!(["a", null]));
+
+        let verify = || proto::VerifyRequest {
+            attestation: Some(attestation.clone()),
+        };
+        let outcome = client.verify(verify()).await.unwrap().into_inner();
+        assert_eq!(outcome.status, "valid");
+
+        let jwks = client.get_jwks(proto::GetJwksRequest {}).await.unwrap().into_inner();
+        assert_eq!(jwks.keys.len(), 1);
+        assert_eq!(jwks.keys[0].kid, attestation.key_id);
+
+        let mut revocations = client
+            .watch_revocations(proto::WatchRevocationsRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+
+        let status = client.revoke(revoke_request(&attestation.id, None)).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = client.revoke(revoke_request(&attestation.id, Some("nope"))).await;
+        assert_eq!(status.unwrap_err().code(), Code::PermissionDenied);
+        client.revoke(revoke_request(&attestation.id, Some(TOKEN))).await.unwrap();
+
+        let entry = tokio::time::timeout(Duration::from_secs(5), revocations.message())
+            .await
+            .expect("revocation not delivered")
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.attestation_id, attestation.id);
+        assert_eq!(entry.reason, "key_compromise");
+
+        let outcome = client.verify(verify()).await.unwrap().into_inner();
+        assert_eq!(outcome.status, "revoked");
+        assert_eq!(outcome.reason.as_deref(), Some("key_compromise"));
+        assert_eq!(outcome.at_ms, Some(entry.revoked_at_ms));
+
+        // A new watcher can catch up on existing revocations
+        let mut revocations = client
+            .watch_revocations(proto::WatchRevocationsRequest {
+                include_existing: true,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let replayed = revocations.message().await.unwrap().unwrap();
+        assert_eq!(replayed, entry);
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_status_codes() {
+        let mut client = spawn().await;
+
+        let status = client.verify(proto::VerifyRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let status = client.revoke(revoke_request("missing", Some(TOKEN))).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let status = client
+            .issue(proto::IssueRequest {
+                identity: "svc-a".to_string(),
+                validity_seconds: 365 * 24 * 60 * 60,
+                ..proto::IssueRequest::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+}
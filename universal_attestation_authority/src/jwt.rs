@@ -0,0 +1,310 @@
+//! JWT module
+//!
+//! Compact JWS (EdDSA) encoding of attestations for consumers such as Envoy
+//! or OPA, and the JWKS document they validate against.
+//!
+//! Registered claims carry the attestation metadata (`sub`, `jti`, `iat`,
+//! `nbf`, `exp`); the attestation claims live under [`CLAIMS_KEY`]. JWT times
+//! have second precision, so `nbf` is rounded up and `exp` rounded down.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use shared_core::{
+    crypto::{KeyPair, PublicKey},
+    Result, SystemError, Timestamp,
+};
+
+use crate::{keys::key_id, Attestation, AttestationAuthority};
+
+/// Payload key holding the attestation claims
+pub const CLAIMS_KEY: &str = "urn:uaa:claims";
+
+/// The only accepted JWS algorithm
+const ALGORITHM: &str = "EdDSA";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Payload {
+    sub: String,
+    jti: String,
+    iat: u64,
+    nbf: u64,
+    exp: u64,
+    #[serde(rename = "urn:uaa:claims", default)]
+    claims: serde_json::Map<String, serde_json::Value>,
+    #[serde(rename = "urn:uaa:renews", default, skip_serializing_if = "Option::is_none")]
+    renews: Option<String>,
+}
+
+/// JSON Web Key Set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwks {
+    /// Published keys
+    pub keys: Vec<Jwk>,
+}
+
+/// Ed25519 public key in JWK form (RFC 8037)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    /// Key type, always `OKP`
+    pub kty: String,
+    /// Curve, always `Ed25519`
+    pub crv: String,
+    /// Base64url-encoded public key
+    pub x: String,
+    /// Key ID, matching the `kid` header of issued tokens
+    pub kid: String,
+    /// Algorithm, always `EdDSA`
+    pub alg: String,
+    /// Intended use, always `sig`
+    #[serde(rename = "use")]
+    pub use_: String,
+}
+
+impl Jwk {
+    /// Build the JWK for an authority public key
+    pub fn from_public_key(public_key: &PublicKey) -> Self {
+        Self {
+            kty: "OKP".to_string(),
+            crv: "Ed25519".to_string(),
+            x: URL_SAFE_NO_PAD.encode(public_key.to_bytes()),
+            kid: key_id(public_key),
+            alg: ALGORITHM.to_string(),
+            use_: "sig".to_string(),
+        }
+    }
+}
+
+fn invalid_token(reason: impl Into<String>) -> SystemError {
+    SystemError::validation("token", reason, None)
+}
+
+fn decode_part<T: for<'de> Deserialize<'de>>(part: &str, name: &str) -> Result<T> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|e| invalid_token(format!("{name} is not base64url: {e}")))?;
+    serde_json::from_slice(&bytes).map_err(|e| invalid_token(format!("{name} is not valid JSON: {e}")))
+}
+
+impl Attestation {
+    /// Encode as a compact JWS signed with the current authority key
+    ///
+    /// Fails if the attestation was not issued by `authority` under a key it
+    /// still trusts.
+    pub fn to_jwt(&self, authority: &AttestationAuthority) -> Result<String> {
+        let payload = self.signing_payload()?;
+        if authority
+            .check_signature(&self.key_id, &payload, &self.signature, Timestamp::now())
+            .is_some()
+        {
+            return Err(SystemError::crypto(
+                "to_jwt",
+                "attestation was not signed by a trusted key of this authority",
+            ));
+        }
+
+        authority.with_signing_key(|key, signing_key| {
+            self.encode_jwt(&key.key_id, signing_key)
+        })
+    }
+
+    fn encode_jwt(&self, kid: &str, signing_key: &KeyPair) -> Result<String> {
+        let header = Header {
+            alg: ALGORITHM.to_string(),
+            typ: Some("JWT".to_string()),
+            kid: Some(kid.to_string()),
+        };
+        let payload = Payload {
+            sub: self.identity.clone(),
+            jti: self.id.clone(),
+            iat: self.issued_at.as_secs(),
+            nbf: self.not_before.as_millis().div_ceil(1000),
+            exp: self.expires_at.as_secs(),
+            claims: self.claims.clone(),
+            renews: self.renews.clone(),
+        };
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload)?)
+        );
+        let signature = signing_key.sign(signing_input.as_bytes());
+
+        Ok(format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+}
+
+/// A parsed token whose signature has not been checked yet
+pub(crate) struct DecodedJwt {
+    pub(crate) signing_input: String,
+    pub(crate) signature: Vec<u8>,
+    pub(crate) attestation: Attestation,
+}
+
+/// Parse a compact JWS, enforcing the EdDSA algorithm
+pub(crate) fn decode(token: &str) -> Result<DecodedJwt> {
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid_token("expected three dot-separated parts"));
+    };
+
+    let parsed_header: Header = decode_part(header, "header")?;
+    if parsed_header.alg != ALGORITHM {
+        return Err(SystemError::validation(
+            "alg",
+            format!("only {ALGORITHM} is accepted"),
+            Some(parsed_header.alg),
+        ));
+    }
+
+    let payload_claims: Payload = decode_part(payload, "payload")?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|e| invalid_token(format!("signature is not base64url: {e}")))?;
+
+    let seconds = |secs: u64| Timestamp::from_millis(secs.saturating_mul(1000));
+    let attestation = Attestation {
+        id: payload_claims.jti,
+        identity: payload_claims.sub,
+        claims: payload_claims.claims,
+        issued_at: seconds(payload_claims.iat),
+        not_before: seconds(payload_claims.nbf),
+        expires_at: seconds(payload_claims.exp),
+        // Tokens are verified against the key that signed the JWS
+        key_id: parsed_header.kid.unwrap_or_default(),
+        renews: payload_claims.renews,
+        signature: signature.clone(),
+    };
+
+    Ok(DecodedJwt {
+        signing_input: format!("{header}.{payload}"),
+        signature,
+        attestation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{AttestationConfig, AttestationRequest, VerificationOutcome};
+
+    async fn issued() -> (AttestationAuthority, Attestation) {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let attestation = authority
+            .issue(AttestationRequest {
+                identity: "envoy-sidecar".to_string(),
+                claims: json!({ "role": "frontend" }).as_object().unwrap().clone(),
+                validity_seconds: 3600,
+                not_before: None,
+            })
+            .await
+            .unwrap();
+        (authority, attestation)
+    }
+
+    fn encode_json(value: &serde_json::Value) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(value).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_jwt_round_trip() {
+        let (authority, attestation) = issued().await;
+
+        let token = attestation.to_jwt(&authority).unwrap();
+        assert_eq!(token.split('.').count(), 3);
+        assert_eq!(
+            authority.verify_jwt(&token).await.unwrap(),
+            VerificationOutcome::Valid
+        );
+
+        let decoded = decode(&token).unwrap().attestation;
+        assert_eq!(decoded.id, attestation.id);
+        assert_eq!(decoded.identity, "envoy-sidecar");
+        assert_eq!(decoded.claim_str("role"), Some("frontend"));
+        assert_eq!(decoded.expires_at.as_secs(), attestation.expires_at.as_secs());
+    }
+
+    #[tokio::test]
+    async fn test_jwt_rejects_alg_confusion() {
+        let (authority, attestation) = issued().await;
+        let token = attestation.to_jwt(&authority).unwrap();
+        let rest = token.split_once('.').unwrap().1;
+
+        for alg in ["none", "HS256", "RS256"] {
+            let forged = format!("{}.{rest}", encode_json(&json!({ "alg": alg, "typ": "JWT" })));
+            assert!(matches!(
+                authority.verify_jwt(&forged).await,
+                Err(SystemError::Validation { ref field, .. }) if field == "alg"
+            ));
+        }
+
+        assert!(authority.verify_jwt("not-a-token").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jwt_rejects_tampered_payload() {
+        let (authority, attestation) = issued().await;
+        let token = attestation.to_jwt(&authority).unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+
+        let mut payload: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        payload[CLAIMS_KEY]["role"] = json!("admin");
+        let tampered = format!("{}.{}.{}", parts[0], encode_json(&payload), parts[2]);
+
+        assert_eq!(
+            authority.verify_jwt(&tampered).await.unwrap(),
+            VerificationOutcome::BadSignature
+        );
+
+        // Tokens from another authority do not verify either
+        let (other, _) = issued().await;
+        assert_eq!(
+            other.verify_jwt(&token).await.unwrap(),
+            VerificationOutcome::BadSignature
+        );
+    }
+
+    #[tokio::test]
+    async fn test_to_jwt_requires_authority_signature() {
+        let (authority, mut attestation) = issued().await;
+        attestation.identity = "impostor".to_string();
+
+        assert!(matches!(
+            attestation.to_jwt(&authority),
+            Err(SystemError::Crypto { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_jwks_structure() {
+        let (authority, attestation) = issued().await;
+
+        let jwks = serde_json::to_value(authority.jwks()).unwrap();
+        let key = &jwks["keys"][0];
+        assert_eq!(key["kty"], "OKP");
+        assert_eq!(key["crv"], "Ed25519");
+        assert_eq!(key["alg"], "EdDSA");
+        assert_eq!(key["use"], "sig");
+
+        let x = URL_SAFE_NO_PAD.decode(key["x"].as_str().unwrap()).unwrap();
+        assert_eq!(x, authority.public_key().to_bytes());
+
+        let token = attestation.to_jwt(&authority).unwrap();
+        let header: Header = decode_part(token.split('.').next().unwrap(), "header").unwrap();
+        assert_eq!(header.kid.as_deref(), key["kid"].as_str());
+    }
+}
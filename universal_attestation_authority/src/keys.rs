@@ -0,0 +1,363 @@
+//! Keys module
+//!
+//! Versioned authority signing keys. Every attestation names the key that
+//! signed it, and rotating the signing key keeps earlier public keys trusted
+//! until their retirement time so outstanding attestations stay verifiable.
+//!
+//! The key history can be persisted through the attestation store, sealed
+//! with AES-256-GCM under a key derived from the configured [`MasterKey`].
+
+use std::fmt;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use shared_core::{
+    crypto::{self, EncryptionKey, KeyPair, PublicKey},
+    Result, SystemError, Timestamp,
+};
+
+/// Associated data binding sealed key histories to their purpose
+const SEALED_AAD: &[u8] = b"uaa-key-history-v1";
+
+/// Length of the random salt prefixed to a sealed key history
+const SALT_LEN: usize = 32;
+
+/// Stable key ID derived from the public key
+pub fn key_id(public_key: &PublicKey) -> String {
+    let digest = blake3::hash(&public_key.to_bytes());
+    URL_SAFE_NO_PAD.encode(&digest.as_bytes()[..8])
+}
+
+/// Secret used to encrypt the persisted key history
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey([u8; 32]);
+
+impl MasterKey {
+    /// Create a master key from raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+/// Authority public key trusted for verification, with its validity window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationKey {
+    /// Key ID carried by attestations signed with this key
+    pub key_id: String,
+    /// Public key
+    pub public_key: PublicKey,
+    /// When the key became the signing key
+    pub valid_from: Timestamp,
+    /// When the key stops being trusted, once it has been rotated out
+    pub retires_at: Option<Timestamp>,
+}
+
+impl VerificationKey {
+    fn new(public_key: PublicKey) -> Self {
+        Self {
+            key_id: key_id(&public_key),
+            public_key,
+            valid_from: Timestamp::now(),
+            retires_at: None,
+        }
+    }
+
+    /// Whether the key is no longer trusted at `now`
+    pub fn is_retired_at(&self, now: Timestamp) -> bool {
+        self.retires_at.is_some_and(|at| now >= at)
+    }
+}
+
+/// Current signing key and every key it replaced
+#[derive(Clone)]
+pub(crate) struct KeyRing {
+    signing_key: KeyPair,
+    /// Oldest first; the last entry belongs to `signing_key`
+    keys: Vec<VerificationKey>,
+}
+
+/// Plaintext of a sealed key history
+///
+/// Only the current signing key is kept in full; rotated-out keys are only
+/// needed for verification.
+#[derive(Serialize, Deserialize)]
+struct KeyHistory {
+    signing_seed: [u8; 32],
+    keys: Vec<VerificationKey>,
+}
+
+impl KeyRing {
+    pub(crate) fn generate() -> Self {
+        let signing_key = KeyPair::generate();
+        let keys = vec![VerificationKey::new(signing_key.public_key())];
+        Self { signing_key, keys }
+    }
+
+    pub(crate) fn signing_key(&self) -> &KeyPair {
+        &self.signing_key
+    }
+
+    pub(crate) fn current(&self) -> &VerificationKey {
+        self.keys.last().expect("key ring always holds the signing key")
+    }
+
+    pub(crate) fn get(&self, key_id: &str) -> Option<&VerificationKey> {
+        self.keys.iter().find(|key| key.key_id == key_id)
+    }
+
+    /// Every key, including retired ones, oldest first
+    pub(crate) fn all(&self) -> &[VerificationKey] {
+        &self.keys
+    }
+
+    /// Keys that are not retired at `now`, oldest first
+    pub(crate) fn trusted(&self, now: Timestamp) -> Vec<VerificationKey> {
+        self.keys
+            .iter()
+            .filter(|key| !key.is_retired_at(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Switch to a fresh signing key, retiring the current one at `retires_at`
+    pub(crate) fn rotate(&mut self, retires_at: Timestamp) -> VerificationKey {
+        if let Some(previous) = self.keys.last_mut() {
+            previous.retires_at = Some(retires_at);
+        }
+
+        self.signing_key = KeyPair::generate();
+        let key = VerificationKey::new(self.signing_key.public_key());
+        self.keys.push(key.clone());
+        key
+    }
+
+    /// Retire a rotated-out key at `now`, or keep its earlier retirement time
+    pub(crate) fn retire(&mut self, key_id: &str, now: Timestamp) -> Result<()> {
+        if self.current().key_id == key_id {
+            return Err(SystemError::InvalidState {
+                message: format!("key {key_id} is the current signing key"),
+                current_state: Some("signing".to_string()),
+                expected_state: Some("rotated".to_string()),
+            });
+        }
+
+        let key = self
+            .keys
+            .iter_mut()
+            .find(|key| key.key_id == key_id)
+            .ok_or_else(|| SystemError::not_found("authority key", key_id))?;
+        key.retires_at = Some(key.retires_at.map_or(now, |at| at.min(now)));
+        Ok(())
+    }
+
+    /// Encrypt the key history for storage
+    ///
+    /// Each call derives a fresh key from the master key and a random salt,
+    /// since [`EncryptionKey`] nonces restart at zero for every new key.
+    pub(crate) fn seal(&self, master_key: &MasterKey) -> Result<Vec<u8>> {
+        let history = KeyHistory {
+            signing_seed: self.signing_key.to_bytes(),
+            keys: self.keys.clone(),
+        };
+        let plaintext = serde_json::to_vec(&history)?;
+
+        let salt = crypto::random_bytes(SALT_LEN)?;
+        let mut cipher =
+            EncryptionKey::from_bytes(&crypto::hash_blake3_keyed(&master_key.0, &salt))?;
+        let ciphertext = cipher.encrypt(&plaintext, SEALED_AAD)?;
+
+        Ok([salt, ciphertext].concat())
+    }
+
+    /// Decrypt a key history produced by [`KeyRing::seal`]
+    pub(crate) fn unseal(master_key: &MasterKey, sealed: &[u8]) -> Result<Self> {
+        if sealed.len() <= SALT_LEN {
+            return Err(SystemError::crypto("unseal_keys", "sealed key history is truncated"));
+        }
+        let (salt, ciphertext) = sealed.split_at(SALT_LEN);
+
+        let mut cipher =
+            EncryptionKey::from_bytes(&crypto::hash_blake3_keyed(&master_key.0, salt))?;
+        let plaintext = cipher.decrypt(ciphertext, SEALED_AAD).map_err(|_| {
+            SystemError::crypto("unseal_keys", "wrong master key or corrupted key history")
+        })?;
+        let history: KeyHistory = serde_json::from_slice(&plaintext)?;
+
+        let signing_key = KeyPair::from_seed(&history.signing_seed);
+        match history.keys.last() {
+            Some(current) if current.public_key == signing_key.public_key() => {},
+            _ => {
+                return Err(SystemError::crypto(
+                    "unseal_keys",
+                    "key history does not end with the signing key",
+                ))
+            },
+        }
+
+        Ok(Self {
+            signing_key,
+            keys: history.keys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AttestationAuthority, AttestationConfig, AttestationRequest, StorageBackend,
+        VerificationOutcome,
+    };
+
+    use super::*;
+
+    fn request() -> AttestationRequest {
+        AttestationRequest {
+            identity: "billing-service".to_string(),
+            claims: serde_json::Map::new(),
+            validity_seconds: 3600,
+            not_before: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_old_attestations_verifiable() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let under_a = authority.issue(request()).await.unwrap();
+        let key_a = under_a.key_id.clone();
+        assert_eq!(key_a, key_id(&authority.public_key()));
+
+        let key_b = authority.rotate_key().await.unwrap();
+        let under_b = authority.issue(request()).await.unwrap();
+        assert_eq!(under_b.key_id, key_b.key_id);
+        assert_ne!(key_a, key_b.key_id);
+
+        assert_eq!(authority.verify(&under_a).await.unwrap().outcome, VerificationOutcome::Valid);
+        assert_eq!(authority.verify(&under_b).await.unwrap().outcome, VerificationOutcome::Valid);
+        assert_eq!(
+            authority.verify_batch(&[under_a.clone(), under_b.clone()]).await,
+            vec![VerificationOutcome::Valid, VerificationOutcome::Valid]
+        );
+
+        let trusted = authority.verification_keys();
+        assert_eq!(trusted.len(), 2);
+        assert_eq!(trusted[0].key_id, key_a);
+        assert!(trusted[0].retires_at.is_some());
+        assert_eq!(trusted[1].retires_at, None);
+        assert_eq!(authority.jwks().keys.len(), 2);
+
+        authority.retire_key(&key_a).await.unwrap();
+        assert!(matches!(
+            authority.verify(&under_a).await.unwrap().outcome,
+            VerificationOutcome::KeyRetired { ref key_id, .. } if *key_id == key_a
+        ));
+        assert!(matches!(
+            authority.verify_batch(std::slice::from_ref(&under_a)).await[0],
+            VerificationOutcome::KeyRetired { .. }
+        ));
+        assert_eq!(authority.verify(&under_b).await.unwrap().outcome, VerificationOutcome::Valid);
+        assert_eq!(authority.verification_keys().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rotated_key_retires_after_grace_period() {
+        let config = AttestationConfig {
+            key_retirement_seconds: 60,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+
+        let attestation = authority.issue(request()).await.unwrap();
+        authority.rotate_key().await.unwrap();
+
+        let soon = Timestamp::from_millis(Timestamp::now().as_millis() + 59_000);
+        let later = Timestamp::from_millis(Timestamp::now().as_millis() + 61_000);
+        assert_eq!(
+            authority.verify_at(&attestation, soon).await.unwrap().outcome,
+            VerificationOutcome::Valid
+        );
+        assert!(matches!(
+            authority.verify_at(&attestation, later).await.unwrap().outcome,
+            VerificationOutcome::KeyRetired { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retire_key_errors() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let current = key_id(&authority.public_key());
+
+        assert!(matches!(
+            authority.retire_key(&current).await,
+            Err(SystemError::InvalidState { .. })
+        ));
+        assert!(matches!(
+            authority.retire_key("unknown").await,
+            Err(SystemError::NotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_key_history_persisted_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AttestationConfig {
+            storage: StorageBackend::Sled {
+                path: dir.path().display().to_string(),
+            },
+            master_key: Some(MasterKey::from_bytes([42; 32])),
+            ..Default::default()
+        };
+
+        let authority = AttestationAuthority::open(config.clone()).await.unwrap();
+        let under_a = authority.issue(request()).await.unwrap();
+        authority.rotate_key().await.unwrap();
+        let under_b = authority.issue(request()).await.unwrap();
+        let public_key = authority.public_key();
+        drop(authority);
+
+        let reopened = AttestationAuthority::open(config.clone()).await.unwrap();
+        assert_eq!(reopened.public_key(), public_key);
+        assert!(reopened.is_valid(&under_a).await.unwrap());
+        assert!(reopened.is_valid(&under_b).await.unwrap());
+        drop(reopened);
+
+        let wrong_master = AttestationConfig {
+            master_key: Some(MasterKey::from_bytes([7; 32])),
+            ..config.clone()
+        };
+        assert!(matches!(
+            AttestationAuthority::open(wrong_master).await,
+            Err(SystemError::Crypto { .. })
+        ));
+
+        let no_master = AttestationConfig {
+            master_key: None,
+            ..config
+        };
+        assert!(matches!(
+            AttestationAuthority::open(no_master).await,
+            Err(SystemError::Config { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sealed_history_hides_signing_key() {
+        let ring = KeyRing::generate();
+        let master_key = MasterKey::from_bytes([1; 32]);
+
+        let sealed = ring.seal(&master_key).unwrap();
+        let seed = ring.signing_key().to_bytes();
+        assert!(!sealed.windows(seed.len()).any(|window| window == seed));
+        assert_ne!(sealed, ring.seal(&master_key).unwrap());
+
+        let unsealed = KeyRing::unseal(&master_key, &sealed).unwrap();
+        assert_eq!(unsealed.current(), ring.current());
+        assert_eq!(format!("{master_key:?}"), "MasterKey(..)");
+    }
+}
@@ -5,16 +5,58 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-use shared_core::{Result, SystemError};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
 use serde::{Deserialize, Serialize};
+use cache::VerificationCache;
+use challenge::ChallengeRegistry;
+use futures::future::join_all;
+use crate::core::PolicyEngine;
+use keys::KeyRing;
+use quota::IssuanceQuotas;
+use parking_lot::RwLock;
+use shared_core::{
+    config::Config, crypto::KeyPair, crypto::PublicKey, Id, ResourceGovernor,
+    ResourceGovernorConfig, Result, SystemError, Timestamp,
+};
 
 pub mod api;
 pub mod attestation;
+pub mod audit;
+mod cache;
+pub mod challenge;
+pub mod claims;
 pub mod config;
 pub mod core;
+pub mod evidence;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod jwt;
+pub mod keys;
+pub mod offline;
+pub mod quota;
 pub mod storage;
 pub mod verification;
 
+pub use crate::core::{Clock, IssuancePolicy, PolicyDecision, PolicyRule, SystemClock};
+pub use attestation::RevocationList;
+pub use audit::{AuditLog, AuditOperation, AuditRecord, IntegrityReport};
+pub use challenge::{Challenge, ChallengeResponse};
+pub use claims::{
+    ClaimsSchema, ClaimsValidator, MaxClaimsSizeValidator, RequiredClaimsValidator,
+};
+pub use config::StorageBackend;
+pub use evidence::{AkPublicKey, Evidence, EvidenceVerifier, PcrPolicy, TpmQuoteVerifier};
+pub use jwt::{Jwk, Jwks};
+pub use keys::{MasterKey, VerificationKey};
+pub use offline::{BundlePolicy, OfflineVerifier, TrustBundle};
+pub use quota::{IssuanceQuota, QuotaStatus};
+pub use storage::{AttestationStore, MemoryStore, Page, RevocationEntry, SledStore};
+pub use verification::{VerificationOutcome, VerificationResult, VerifiedBy};
+
 /// Attestation request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttestationRequest {
@@ -24,9 +66,12 @@ pub struct AttestationRequest {
     pub claims: serde_json::Map<String, serde_json::Value>,
     /// Validity period in seconds
     pub validity_seconds: u64,
+    /// Start of the validity window (defaults to the issue time)
+    #[serde(default)]
+    pub not_before: Option<Timestamp>,
 }
 
-/// Attestation (placeholder)
+/// Signed attestation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attestation {
     /// Attestation ID
@@ -35,49 +80,1052 @@ pub struct Attestation {
     pub identity: String,
     /// Claims
     pub claims: serde_json::Map<String, serde_json::Value>,
+    /// When the attestation was issued
+    pub issued_at: Timestamp,
+    /// Attestation is not valid before this time
+    pub not_before: Timestamp,
+    /// Attestation is not valid at or after this time
+    pub expires_at: Timestamp,
+    /// ID of the authority key that signed the attestation
+    #[serde(default)]
+    pub key_id: String,
+    /// ID of the attestation this one renews, see [`AttestationAuthority::renew`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renews: Option<String>,
     /// Signature
     pub signature: Vec<u8>,
 }
 
-/// Attestation authority (placeholder)
+/// Attestation authority
 pub struct AttestationAuthority {
-    _config: AttestationConfig,
+    config: AttestationConfig,
+    keys: RwLock<KeyRing>,
+    /// Serializes key rotation and retirement, including persistence
+    key_updates: tokio::sync::Mutex<()>,
+    store: Arc<dyn AttestationStore>,
+    challenges: ChallengeRegistry,
+    governor: ResourceGovernor,
+    policy: PolicyEngine,
+    quotas: IssuanceQuotas,
+    verification_cache: VerificationCache,
+    operation_log: AuditLog,
+    evidence_verifier: Option<Arc<dyn EvidenceVerifier>>,
+    /// Keys of foreign authorities whose attestations [`AttestationAuthority::verify`] accepts
+    trusted_authorities: RwLock<BTreeMap<String, PublicKey>>,
+    /// Publishes new revocations to [`AttestationAuthority::subscribe_revocations`]
+    revocation_events: tokio::sync::broadcast::Sender<RevocationEntry>,
 }
 
+/// Governor label of the permit held while an attestation is signed and stored
+const SIGN_PERMIT_LABEL: &str = "attestation_sign";
+
+/// Revocations buffered per subscriber before it starts lagging
+const REVOCATION_EVENT_CAPACITY: usize = 1024;
+
 /// Authority configuration
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AttestationConfig {
     /// Key path
     pub key_path: Option<String>,
+    /// Maximum validity period accepted on a request, in seconds
+    pub max_validity_seconds: u64,
+    /// Clock skew tolerated when checking validity windows, in milliseconds
+    pub clock_skew_tolerance_ms: u64,
+    /// Backend used to retain attestations and revocations
+    pub storage: StorageBackend,
+    /// Report attestations missing from the store as `Unknown` on verification
+    pub require_known_attestation: bool,
+    /// Claims schemas keyed by identity pattern (`*` matches any sequence);
+    /// the first matching pattern wins
+    pub claims_schemas: Vec<(String, ClaimsSchema)>,
+    /// Claims schema for identities that match no pattern
+    pub default_claims_schema: Option<ClaimsSchema>,
+    /// Called with the claims of every request before it is signed, after
+    /// any claims schema; an error rejects the request
+    pub claims_validator: Option<ClaimsValidator>,
+    /// Only issue through [`AttestationAuthority::issue_with_challenge`]
+    pub require_challenge: bool,
+    /// How long a challenge nonce can be redeemed, in milliseconds
+    pub challenge_ttl_ms: u64,
+    /// Maximum number of items processed at once by batch operations, and
+    /// of attestations signed at once
+    pub batch_concurrency: usize,
+    /// Per-identity issuance rate limit; `None` disables it
+    pub issuance_quota: Option<IssuanceQuota>,
+    /// How long a rotated-out key keeps verifying attestations, in seconds
+    pub key_retirement_seconds: u64,
+    /// Key used to encrypt the persisted key history; without it the key
+    /// history is kept in memory only
+    pub master_key: Option<MasterKey>,
+    /// Rules deciding which identities may be issued what
+    pub issuance_policy: IssuancePolicy,
+    /// Number of `Valid` verification outcomes to cache; zero disables the
+    /// cache
+    pub verification_cache_capacity: usize,
+    /// How long a cached verification outcome is reused, in milliseconds;
+    /// revocations and deletions made directly on a shared store are only
+    /// seen once this elapses
+    pub verification_cache_ttl_ms: u64,
+    /// Expected PCR values keyed by identity pattern, used by
+    /// [`TpmQuoteVerifier::from_config`]; the first matching pattern wins
+    pub pcr_policies: Vec<(String, PcrPolicy)>,
+    /// How far past the first attestation's `not_before` a chain of renewals
+    /// may extend, in seconds
+    pub max_renewal_chain_seconds: u64,
 }
 
 impl Default for AttestationConfig {
     fn default() -> Self {
-        Self { key_path: None }
+        Self {
+            key_path: None,
+            max_validity_seconds: 30 * 24 * 60 * 60, // 30 days
+            clock_skew_tolerance_ms: 5 * 60 * 1000,  // 5 minutes
+            storage: StorageBackend::Memory,
+            require_known_attestation: false,
+            claims_schemas: Vec::new(),
+            default_claims_schema: None,
+            claims_validator: None,
+            require_challenge: false,
+            challenge_ttl_ms: 60 * 1000, // 1 minute
+            batch_concurrency: 64,
+            issuance_quota: None,
+            key_retirement_seconds: 30 * 24 * 60 * 60, // 30 days
+            master_key: None,
+            issuance_policy: IssuancePolicy::default(),
+            verification_cache_capacity: 10_000,
+            verification_cache_ttl_ms: 60 * 1000, // 1 minute
+            pcr_policies: Vec::new(),
+            max_renewal_chain_seconds: 7 * 24 * 60 * 60, // 7 days
+        }
+    }
+}
+
+impl std::fmt::Debug for AttestationConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttestationConfig")
+            .field("key_path", &self.key_path)
+            .field("max_validity_seconds", &self.max_validity_seconds)
+            .field("clock_skew_tolerance_ms", &self.clock_skew_tolerance_ms)
+            .field("storage", &self.storage)
+            .field("require_known_attestation", &self.require_known_attestation)
+            .field("claims_schemas", &self.claims_schemas)
+            .field("default_claims_schema", &self.default_claims_schema)
+            .field("claims_validator", &self.claims_validator.as_ref().map(|_| ".."))
+            .field("require_challenge", &self.require_challenge)
+            .field("challenge_ttl_ms", &self.challenge_ttl_ms)
+            .field("batch_concurrency", &self.batch_concurrency)
+            .field("issuance_quota", &self.issuance_quota)
+            .field("key_retirement_seconds", &self.key_retirement_seconds)
+            .field("master_key", &self.master_key)
+            .field("issuance_policy", &self.issuance_policy)
+            .field("verification_cache_capacity", &self.verification_cache_capacity)
+            .field("verification_cache_ttl_ms", &self.verification_cache_ttl_ms)
+            .field("pcr_policies", &self.pcr_policies)
+            .field("max_renewal_chain_seconds", &self.max_renewal_chain_seconds)
+            .finish()
     }
 }
 
 impl AttestationAuthority {
     /// Create new authority
     pub fn new(config: AttestationConfig) -> Result<Self> {
-        Ok(Self { _config: config })
+        let store = config.storage.open()?;
+        Self::with_store(config, store)
+    }
+
+    /// Open an authority, restoring its key history from the configured store
+    ///
+    /// See [`AttestationAuthority::open_with_store`].
+    pub async fn open(config: AttestationConfig) -> Result<Self> {
+        let store = config.storage.open()?;
+        Self::open_with_store(config, store).await
+    }
+
+    /// Open an authority on top of an existing store, restoring its key history
+    ///
+    /// With a master key configured, the sealed key history is loaded from
+    /// the store, or the freshly generated key is persisted if there is none.
+    /// A stored history without a master key is a `Config` error.
+    pub async fn open_with_store(
+        config: AttestationConfig,
+        store: Arc<dyn AttestationStore>,
+    ) -> Result<Self> {
+        let authority = Self::with_store(config, store)?;
+        let sealed = authority.store.get_key_history().await?;
+
+        match (&authority.config.master_key, sealed) {
+            (Some(master_key), Some(sealed)) => {
+                *authority.keys.write() = KeyRing::unseal(master_key, &sealed)?;
+            },
+            (Some(_), None) => {
+                let keys = authority.keys.read().clone();
+                authority.persist_keys(&keys).await?;
+            },
+            (None, Some(_)) => {
+                return Err(SystemError::config(
+                    "a master key is required to restore the stored key history",
+                    Some("master_key".to_string()),
+                ));
+            },
+            (None, None) => {},
+        }
+
+        Ok(authority)
+    }
+
+    /// Create new authority on top of an existing store
+    ///
+    /// `config.storage` is ignored, and any stored key history is neither
+    /// loaded nor overwritten until the key is rotated.
+    pub fn with_store(config: AttestationConfig, store: Arc<dyn AttestationStore>) -> Result<Self> {
+        let governor = ResourceGovernor::new(ResourceGovernorConfig {
+            max_concurrent_operations: config.batch_concurrency,
+            ..Default::default()
+        })?;
+
+        let policy = PolicyEngine::new(config.issuance_policy.clone(), Arc::new(SystemClock))?;
+        let quotas = IssuanceQuotas::new(config.issuance_quota, Arc::new(SystemClock))?;
+        let verification_cache = VerificationCache::new(
+            config.verification_cache_capacity,
+            config.verification_cache_ttl_ms,
+            config.clock_skew_tolerance_ms,
+        );
+
+        Ok(Self {
+            operation_log: AuditLog::new(Arc::clone(&store)),
+            config,
+            keys: RwLock::new(KeyRing::generate()),
+            key_updates: tokio::sync::Mutex::new(()),
+            store,
+            challenges: ChallengeRegistry::default(),
+            governor,
+            policy,
+            quotas,
+            verification_cache,
+            evidence_verifier: None,
+            trusted_authorities: RwLock::default(),
+            revocation_events: tokio::sync::broadcast::channel(REVOCATION_EVENT_CAPACITY).0,
+        })
+    }
+
+    /// Accept hardware evidence checked by `verifier` in
+    /// [`AttestationAuthority::issue_with_evidence`]
+    pub fn with_evidence_verifier(mut self, verifier: Arc<dyn EvidenceVerifier>) -> Self {
+        self.evidence_verifier = Some(verifier);
+        self
+    }
+
+    /// Use `clock` for policy decisions and issuance rate limits
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.quotas.set_clock(Arc::clone(&clock));
+        self.policy.set_clock(clock);
+        self
+    }
+
+    /// Issuance quota state of `identity`, or `None` without a configured
+    /// [`AttestationConfig::issuance_quota`]
+    pub fn quota_status(&self, identity: &str) -> Option<QuotaStatus> {
+        self.quotas.status(identity)
+    }
+
+    /// Governor bounding concurrent signing, see its `attestation_sign`
+    /// label statistics
+    pub fn governor(&self) -> &ResourceGovernor {
+        &self.governor
+    }
+
+    /// Replace the issuance policy without restarting
+    ///
+    /// An invalid policy is rejected and the current one stays in force.
+    /// Issuance counts for rate limits carry over.
+    pub fn reload_policy(&self, policy: IssuancePolicy) -> Result<()> {
+        self.policy.reload(policy)?;
+        tracing::info!("Reloaded issuance policy");
+        Ok(())
+    }
+
+    /// Replace the issuance policy with one loaded from a TOML file
+    pub fn reload_policy_from_file(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        self.reload_policy(IssuancePolicy::from_file(path)?)
+    }
+
+    /// Evaluate a request against the issuance policy without issuing
+    ///
+    /// Dry runs are neither audited nor counted towards rate limits.
+    pub fn evaluate_policy(&self, request: &AttestationRequest) -> PolicyDecision {
+        self.policy.evaluate(request)
+    }
+
+    /// List issuance policy decisions, oldest first
+    pub async fn audit_log(&self, page: Page) -> Result<Vec<PolicyDecision>> {
+        self.store.list_audit(page).await
+    }
+
+    /// Find the claims schema that applies to an identity
+    fn claims_schema_for(&self, identity: &str) -> Option<&ClaimsSchema> {
+        self.config
+            .claims_schemas
+            .iter()
+            .find(|(pattern, _)| claims::identity_matches(pattern, identity))
+            .map(|(_, schema)| schema)
+            .or(self.config.default_claims_schema.as_ref())
+    }
+
+    /// Get the public key of the current signing key
+    pub fn public_key(&self) -> PublicKey {
+        self.keys.read().current().public_key.clone()
+    }
+
+    /// Get every key trusted for verification right now, oldest first
+    pub fn verification_keys(&self) -> Vec<VerificationKey> {
+        self.keys.read().trusted(Timestamp::now())
+    }
+
+    /// Switch to a freshly generated signing key
+    ///
+    /// The previous key keeps verifying for `key_retirement_seconds`. With a
+    /// master key configured, the key history is persisted before the new
+    /// key is used.
+    pub async fn rotate_key(&self) -> Result<VerificationKey> {
+        let _update = self.key_updates.lock().await;
+
+        let retires_at = Timestamp::from_millis(
+            Timestamp::now()
+                .as_millis()
+                .saturating_add(self.config.key_retirement_seconds.saturating_mul(1000)),
+        );
+        let mut keys = self.keys.read().clone();
+        let key = keys.rotate(retires_at);
+        self.persist_keys(&keys).await?;
+        *self.keys.write() = keys;
+        self.verification_cache.clear();
+
+        tracing::info!("Rotated authority signing key to {}", key.key_id);
+        let outcome = format!("rotated to {}", key.key_id);
+        self.operation_log
+            .append(AuditOperation::RotateKey, None, None, outcome)
+            .await?;
+        Ok(key)
+    }
+
+    /// Stop trusting a rotated-out key immediately
+    ///
+    /// Fails with `NotFound` for unknown keys and `InvalidState` for the
+    /// current signing key.
+    pub async fn retire_key(&self, key_id: &str) -> Result<()> {
+        let _update = self.key_updates.lock().await;
+
+        let mut keys = self.keys.read().clone();
+        keys.retire(key_id, Timestamp::now())?;
+        self.persist_keys(&keys).await?;
+        *self.keys.write() = keys;
+        self.verification_cache.clear();
+
+        tracing::info!("Retired authority key {}", key_id);
+        let outcome = format!("retired {key_id}");
+        self.operation_log
+            .append(AuditOperation::RetireKey, None, None, outcome)
+            .await?;
+        Ok(())
+    }
+
+    async fn persist_keys(&self, keys: &KeyRing) -> Result<()> {
+        match &self.config.master_key {
+            Some(master_key) => self.store.put_key_history(&keys.seal(master_key)?).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Accept attestations signed by `verifying_key` of the foreign authority
+    /// `authority_id` in [`AttestationAuthority::verify`]
+    ///
+    /// Trusting an authority again replaces its key. An empty ID is a
+    /// `Validation` error.
+    pub fn trust_authority(&self, authority_id: &str, verifying_key: PublicKey) -> Result<()> {
+        if authority_id.is_empty() {
+            return Err(SystemError::validation("authority_id", "must not be empty", None));
+        }
+        self.trusted_authorities.write().insert(authority_id.to_string(), verifying_key);
+        tracing::info!("Trusting attestations of authority {}", authority_id);
+        Ok(())
+    }
+
+    /// Stop accepting attestations of a foreign authority, for example after
+    /// its key was compromised
+    ///
+    /// Fails with `NotFound` for authorities that are not trusted.
+    pub fn distrust_authority(&self, authority_id: &str) -> Result<()> {
+        if self.trusted_authorities.write().remove(authority_id).is_none() {
+            return Err(SystemError::not_found("trusted authority", authority_id));
+        }
+        tracing::info!("Distrusted authority {}", authority_id);
+        Ok(())
+    }
+
+    /// Register the key an identity signs challenge nonces with
+    pub fn register_identity(&self, identity: impl Into<String>, public_key: PublicKey) {
+        self.challenges.register(identity.into(), public_key);
+    }
+
+    /// Start a challenge-response issuance for a registered identity
+    ///
+    /// Identities enrolled with the evidence verifier count as registered.
+    pub fn begin_challenge(&self, identity: &str) -> Result<Challenge> {
+        let enrolled = self
+            .evidence_verifier
+            .as_ref()
+            .is_some_and(|verifier| verifier.is_enrolled(identity));
+        if !enrolled && !self.challenges.is_registered(identity) {
+            return Err(SystemError::not_found("identity", identity));
+        }
+        self.challenges.begin(identity, self.config.challenge_ttl_ms)
+    }
+
+    /// Issue attestation against a signed challenge nonce
+    ///
+    /// Unknown identities or nonces fail with `NotFound`, reused nonces with
+    /// `InvalidState`, expired nonces with `Timeout`, and bad signatures with
+    /// `PermissionDenied`.
+    pub async fn issue_with_challenge(
+        &self,
+        request: AttestationRequest,
+        response: &ChallengeResponse,
+    ) -> Result<Attestation> {
+        self.challenges.redeem(&request.identity, response)?;
+        self.issue_unchecked(request, None).await
+    }
+
+    /// Issue attestation against hardware evidence bound to a challenge nonce
+    ///
+    /// The nonce is spent as in [`AttestationAuthority::issue_with_challenge`].
+    /// Claims derived from the evidence replace requested claims of the same
+    /// name before the claims schema is checked. Fails with `Config` when no
+    /// evidence verifier is set.
+    pub async fn issue_with_evidence(
+        &self,
+        mut request: AttestationRequest,
+        nonce: &str,
+        evidence: &Evidence,
+    ) -> Result<Attestation> {
+        let verifier = self
+            .evidence_verifier
+            .as_ref()
+            .ok_or_else(|| SystemError::config("no evidence verifier is configured", None))?;
+
+        self.challenges.spend(&request.identity, nonce, "issue_with_evidence")?;
+        let derived =
+            verifier.verify(&request.identity, evidence, &challenge::nonce_bytes(nonce)?)?;
+        request.claims.extend(derived);
+        self.issue_unchecked(request, None).await
+    }
+
+    /// Re-issue a currently valid attestation with a fresh validity window
+    ///
+    /// `proof_of_possession` is a signature over [`Attestation::renewal_nonce`]
+    /// by the key registered for the identity. The new attestation keeps the
+    /// claims and validity period of `old` and links to it through `renews`.
+    /// Invalid attestations fail with `InvalidState`, unknown identities with
+    /// `NotFound`, and bad signatures with `PermissionDenied`. So does a
+    /// renewal that would take the chain more than `max_renewal_chain_seconds`
+    /// past the first attestation's `not_before`, which then needs a full
+    /// issuance; every earlier link must still be in the store.
+    pub async fn renew(
+        &self,
+        old: &Attestation,
+        proof_of_possession: &[u8],
+    ) -> Result<Attestation> {
+        let result = self.verify(old).await?;
+        if !result.is_valid || result.verified_by != VerifiedBy::Local {
+            return Err(SystemError::InvalidState {
+                message: format!("attestation {} cannot be renewed", old.id),
+                current_state: Some(match result.verified_by {
+                    VerifiedBy::Local => result.outcome.status().to_string(),
+                    VerifiedBy::TrustedAuthority(id) => format!("issued by {id}"),
+                }),
+                expected_state: Some("valid".to_string()),
+            });
+        }
+
+        let public_key = self
+            .challenges
+            .public_key(&old.identity)
+            .ok_or_else(|| SystemError::not_found("identity", &old.identity))?;
+        if public_key.verify(&old.renewal_nonce(), proof_of_possession).is_err() {
+            return Err(SystemError::PermissionDenied {
+                operation: "renew".to_string(),
+                required_permission: Some(format!(
+                    "signature by the key registered for {}",
+                    old.identity
+                )),
+            });
+        }
+
+        let mut chain_start = old.not_before;
+        let mut link = old.renews.clone();
+        let mut seen = std::collections::HashSet::from([old.id.clone()]);
+        while let Some(id) = link {
+            if !seen.insert(id.clone()) {
+                return Err(SystemError::internal(format!("renewal chain of {id} loops"), None));
+            }
+            let previous = self
+                .store
+                .get(&id)
+                .await?
+                .ok_or_else(|| SystemError::not_found("attestation", &id))?;
+            chain_start = previous.not_before;
+            link = previous.renews;
+        }
+
+        let validity_ms = old.expires_at.as_millis().saturating_sub(old.not_before.as_millis());
+        let chain_end = Timestamp::now().as_millis().saturating_add(validity_ms);
+        let max_chain_ms = self.config.max_renewal_chain_seconds.saturating_mul(1000);
+        if chain_end.saturating_sub(chain_start.as_millis()) > max_chain_ms {
+            return Err(SystemError::PermissionDenied {
+                operation: "renew".to_string(),
+                required_permission: Some(format!(
+                    "full issuance once a renewal chain spans {} seconds",
+                    self.config.max_renewal_chain_seconds
+                )),
+            });
+        }
+
+        let request = AttestationRequest {
+            identity: old.identity.clone(),
+            claims: old.claims.clone(),
+            validity_seconds: validity_ms.div_ceil(1000),
+            not_before: None,
+        };
+        self.issue_unchecked(request, Some(old.id.clone())).await
     }
 
     /// Issue attestation
+    ///
+    /// Fails with `PermissionDenied` when `require_challenge` is set.
     pub async fn issue(&self, request: AttestationRequest) -> Result<Attestation> {
+        if self.config.require_challenge {
+            return Err(SystemError::PermissionDenied {
+                operation: "issue".to_string(),
+                required_permission: Some("challenge response".to_string()),
+            });
+        }
+        self.issue_unchecked(request, None).await
+    }
+
+    /// Issue without the challenge check, auditing the result
+    async fn issue_unchecked(
+        &self,
+        request: AttestationRequest,
+        renews: Option<String>,
+    ) -> Result<Attestation> {
+        let identity = request.identity.clone();
+        let result = self.sign_and_store(request, renews).await;
+
+        match &result {
+            Ok(attestation) => {
+                let outcome = match &attestation.renews {
+                    Some(old) => format!("renewed {old}"),
+                    None => "issued".to_string(),
+                };
+                self.operation_log
+                    .append(AuditOperation::Issue, Some(&identity), Some(&attestation.id), outcome)
+                    .await?;
+            },
+            Err(err) => {
+                let outcome = format!("rejected: {}", err.kind());
+                if let Err(audit_err) = self
+                    .operation_log
+                    .append(AuditOperation::Issue, Some(&identity), None, outcome)
+                    .await
+                {
+                    tracing::warn!("Failed to audit rejected issuance: {}", audit_err);
+                }
+            },
+        }
+        result
+    }
+
+    async fn sign_and_store(
+        &self,
+        request: AttestationRequest,
+        renews: Option<String>,
+    ) -> Result<Attestation> {
         tracing::info!("Issuing attestation for identity: {}", request.identity);
-        Ok(Attestation {
-            id: "att_placeholder".to_string(),
+
+        if request.validity_seconds == 0 {
+            return Err(SystemError::validation(
+                "validity_seconds",
+                "must be > 0",
+                Some("0".to_string()),
+            ));
+        }
+
+        if request.validity_seconds > self.config.max_validity_seconds {
+            return Err(SystemError::validation(
+                "validity_seconds",
+                format!(
+                    "exceeds maximum validity of {} seconds",
+                    self.config.max_validity_seconds
+                ),
+                Some(request.validity_seconds.to_string()),
+            ));
+        }
+
+        if let Some(schema) = self.claims_schema_for(&request.identity) {
+            schema.validate(&request.claims)?;
+        }
+        if let Some(validator) = &self.config.claims_validator {
+            validator(&request.claims)?;
+        }
+        self.quotas.take(&request.identity)?;
+
+        let _permit = self.governor.acquire_permit_labeled(SIGN_PERMIT_LABEL).await?;
+
+        let decision = self.policy.admit(&request);
+        self.store.append_audit(&decision).await?;
+        decision.into_result()?;
+
+        let issued_at = Timestamp::now();
+        let not_before = request.not_before.unwrap_or(issued_at);
+        let expires_at = Timestamp::from_millis(
+            not_before
+                .as_millis()
+                .saturating_add(request.validity_seconds.saturating_mul(1000)),
+        );
+
+        let mut attestation = Attestation {
+            id: format!("att_{}", Id::generate()),
             identity: request.identity,
             claims: request.claims,
-            signature: vec![0; 64],
-        })
+            issued_at,
+            not_before,
+            expires_at,
+            key_id: String::new(),
+            renews,
+            signature: Vec::new(),
+        };
+        self.with_signing_key(|key, signing_key| {
+            attestation.key_id = key.key_id.clone();
+            attestation.signature = signing_key.sign(&attestation.signing_payload()?);
+            Ok::<_, SystemError>(())
+        })?;
+        self.store.put(&attestation).await?;
+
+        Ok(attestation)
+    }
+
+    /// Issue many attestations concurrently
+    ///
+    /// Like every issuance, each request holds an `attestation_sign`
+    /// governor permit while it is admitted, signed and stored, so at most
+    /// `batch_concurrency` are signed at once. Results keep the input order
+    /// and a failed request does not affect the others.
+    pub async fn issue_batch(
+        &self,
+        requests: Vec<AttestationRequest>,
+    ) -> Result<Vec<Result<Attestation>>> {
+        if self.config.require_challenge {
+            return Err(SystemError::PermissionDenied {
+                operation: "issue_batch".to_string(),
+                required_permission: Some("challenge response".to_string()),
+            });
+        }
+
+        let results = join_all(
+            requests
+                .into_iter()
+                .map(|request| self.issue_unchecked(request, None)),
+        )
+        .await;
+
+        Ok(results)
+    }
+
+    /// Verify many attestations
+    ///
+    /// Signatures are checked with one batch verification per signing key.
+    /// Each item then goes through the same checks as
+    /// [`AttestationAuthority::verify`] under a governor permit, including
+    /// the fallback to trusted authorities' keys for signatures the batch
+    /// rejected. Outcomes keep the input order. An item whose status lookup
+    /// fails is reported as `Unknown`.
+    pub async fn verify_batch(&self, attestations: &[Attestation]) -> Vec<VerificationOutcome> {
+        let now = Timestamp::now();
+        let payloads: Vec<Option<Vec<u8>>> = attestations
+            .iter()
+            .map(|attestation| attestation.signing_payload().ok())
+            .collect();
+        let keys: Vec<Option<VerificationKey>> = {
+            let ring = self.keys.read();
+            attestations
+                .iter()
+                .map(|attestation| ring.get(&attestation.key_id).cloned())
+                .collect()
+        };
+
+        // Group items by signing key so each key runs one batch verification
+        let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, (key, payload)) in keys.iter().zip(&payloads).enumerate() {
+            if let (Some(key), Some(_)) = (key, payload) {
+                groups.entry(key.key_id.as_str()).or_default().push(index);
+            }
+        }
+
+        let mut signature_ok = vec![false; attestations.len()];
+        for indices in groups.into_values() {
+            let Some(key) = &keys[indices[0]] else {
+                continue;
+            };
+            let items: Vec<(&[u8], &[u8])> = indices
+                .iter()
+                .filter_map(|&i| {
+                    Some((payloads[i].as_deref()?, attestations[i].signature.as_slice()))
+                })
+                .collect();
+            for (index, ok) in indices.into_iter().zip(key.public_key.verify_batch(&items)) {
+                signature_ok[index] = ok;
+            }
+        }
+
+        let checks = attestations.iter().zip(&payloads).zip(keys).zip(signature_ok).map(
+            |(((attestation, payload), key), good_signature)| async move {
+                let Some(payload) = payload else {
+                    return VerificationOutcome::BadSignature;
+                };
+                let signature_check = if good_signature {
+                    key.and_then(|key| verification::check_key_retired(&key, now))
+                } else {
+                    Some(VerificationOutcome::BadSignature)
+                };
+                let verified = async {
+                    let _permit = self.governor.acquire_permit().await?;
+                    let signature = &attestation.signature;
+                    self.verify_signed(attestation, payload, signature, signature_check, now).await
+                };
+                verified.await.map_or_else(
+                    |e| {
+                        tracing::warn!("Status check failed for {}: {}", attestation.id, e);
+                        VerificationOutcome::Unknown
+                    },
+                    |result| result.outcome,
+                )
+            },
+        );
+
+        let outcomes = join_all(checks.collect::<Vec<_>>()).await;
+        for (attestation, outcome) in attestations.iter().zip(&outcomes) {
+            self.audit_verification(attestation, outcome).await;
+        }
+        outcomes
     }
 
     /// Verify attestation
-    pub async fn verify(&self, attestation: &Attestation) -> Result<bool> {
+    pub async fn verify(&self, attestation: &Attestation) -> Result<VerificationResult> {
+        self.verify_at(attestation, Timestamp::now()).await
+    }
+
+    /// Verify attestation against the given point in time
+    ///
+    /// The signature is checked against the authority's own keys first, then
+    /// against each trusted authority's key (see
+    /// [`AttestationAuthority::trust_authority`]). Attestations of a trusted
+    /// authority are only checked for their validity window, since their
+    /// revocations are not known here.
+    ///
+    /// Locally `Valid` outcomes are cached (see
+    /// `verification_cache_capacity`); a cached outcome is dropped when the
+    /// attestation is revoked or the key set changes, and is never returned
+    /// outside the validity window.
+    pub async fn verify_at(
+        &self,
+        attestation: &Attestation,
+        now: Timestamp,
+    ) -> Result<VerificationResult> {
         tracing::info!("Verifying attestation: {}", attestation.id);
-        Ok(true)
+
+        let payload = attestation.signing_payload()?;
+        let digest = VerificationCache::digest(&payload, &attestation.signature);
+        if self.verification_cache.get(attestation, &digest, now) {
+            return Ok(VerificationResult::local(VerificationOutcome::Valid));
+        }
+        let generation = self.verification_cache.generation();
+
+        let signature_check =
+            self.check_signature(&attestation.key_id, &payload, &attestation.signature, now);
+        let result = self
+            .verify_signed(attestation, &payload, &attestation.signature, signature_check, now)
+            .await?;
+        self.audit_verification(attestation, &result.outcome).await;
+        if result.is_valid && result.verified_by == VerifiedBy::Local {
+            let retires_at = self.keys.read().get(&attestation.key_id).and_then(|k| k.retires_at);
+            self.verification_cache.insert(attestation, digest, now, retires_at, generation);
+        }
+        Ok(result)
+    }
+
+    /// Finish verifying `attestation` from the check of its `signature` over
+    /// `payload` against the authority's own keys, as returned by
+    /// `check_signature`
+    ///
+    /// A `BadSignature` check falls back to the trusted authorities' keys,
+    /// and a trusted authority's attestation is then only checked for its
+    /// validity window. A good signature goes on to the status checks.
+    async fn verify_signed(
+        &self,
+        attestation: &Attestation,
+        payload: &[u8],
+        signature: &[u8],
+        signature_check: Option<VerificationOutcome>,
+        now: Timestamp,
+    ) -> Result<VerificationResult> {
+        Ok(match signature_check {
+            Some(VerificationOutcome::BadSignature) => {
+                match self.trusted_signer(payload, signature) {
+                    Some(authority_id) => VerificationResult::new(
+                        verification::check_validity_window(
+                            attestation,
+                            now,
+                            self.config.clock_skew_tolerance_ms,
+                        ),
+                        VerifiedBy::TrustedAuthority(authority_id),
+                    ),
+                    None => VerificationResult::local(VerificationOutcome::BadSignature),
+                }
+            },
+            Some(outcome) => VerificationResult::local(outcome),
+            None => VerificationResult::local(self.check_status(attestation, now).await?),
+        })
+    }
+
+    /// ID of the first trusted authority whose key made `signature`
+    fn trusted_signer(&self, message: &[u8], signature: &[u8]) -> Option<String> {
+        self.trusted_authorities
+            .read()
+            .iter()
+            .find(|(_, key)| key.verify(message, signature).is_ok())
+            .map(|(authority_id, _)| authority_id.clone())
+    }
+
+    /// Verify a compact JWS produced by [`Attestation::to_jwt`]
+    ///
+    /// Tokens of trusted authorities are accepted as by
+    /// [`AttestationAuthority::verify`]. Malformed tokens and any algorithm
+    /// other than `EdDSA` are rejected with a `Validation` error.
+    pub async fn verify_jwt(&self, token: &str) -> Result<VerificationOutcome> {
+        let decoded = jwt::decode(token)?;
+        tracing::info!("Verifying attestation JWT: {}", decoded.attestation.id);
+
+        let now = Timestamp::now();
+        let payload = decoded.signing_input.as_bytes();
+        let signature_check =
+            self.check_signature(&decoded.attestation.key_id, payload, &decoded.signature, now);
+        let outcome = self
+            .verify_signed(&decoded.attestation, payload, &decoded.signature, signature_check, now)
+            .await?
+            .outcome;
+        self.audit_verification(&decoded.attestation, &outcome).await;
+        Ok(outcome)
+    }
+
+    /// Record a failed verification in the operation log
+    ///
+    /// Verification results are returned even if the record cannot be
+    /// written.
+    async fn audit_verification(&self, attestation: &Attestation, outcome: &VerificationOutcome) {
+        if outcome.is_valid() {
+            return;
+        }
+        let appended = self
+            .operation_log
+            .append(
+                AuditOperation::VerifyFailure,
+                Some(&attestation.identity),
+                Some(&attestation.id),
+                outcome.status(),
+            )
+            .await;
+        if let Err(err) = appended {
+            tracing::warn!("Failed to audit verification of {}: {}", attestation.id, err);
+        }
+    }
+
+    /// Hash-chained log of issuances, verification failures, revocations and
+    /// key changes
+    pub fn operation_log(&self) -> &AuditLog {
+        &self.operation_log
+    }
+
+    /// Get the trusted verification keys as a JWKS document
+    pub fn jwks(&self) -> Jwks {
+        Jwks {
+            keys: self
+                .verification_keys()
+                .iter()
+                .map(|key| Jwk::from_public_key(&key.public_key))
+                .collect(),
+        }
+    }
+
+    /// Run `sign` with the current signing key and its verification key
+    pub(crate) fn with_signing_key<T>(
+        &self,
+        sign: impl FnOnce(&VerificationKey, &KeyPair) -> T,
+    ) -> T {
+        let keys = self.keys.read();
+        sign(keys.current(), keys.signing_key())
+    }
+
+    /// Check a signature made with the authority key `key_id`
+    ///
+    /// Returns the failing outcome, or `None` if the signature is good and
+    /// the key is not retired at `now`. Unknown keys give `BadSignature`.
+    pub(crate) fn check_signature(
+        &self,
+        key_id: &str,
+        message: &[u8],
+        signature: &[u8],
+        now: Timestamp,
+    ) -> Option<VerificationOutcome> {
+        let Some(key) = self.keys.read().get(key_id).cloned() else {
+            return Some(VerificationOutcome::BadSignature);
+        };
+        if key.public_key.verify(message, signature).is_err() {
+            return Some(VerificationOutcome::BadSignature);
+        }
+        verification::check_key_retired(&key, now)
+    }
+
+    /// Checks that apply once the signature is known to be good
+    async fn check_status(
+        &self,
+        attestation: &Attestation,
+        now: Timestamp,
+    ) -> Result<VerificationOutcome> {
+        if self.config.require_known_attestation
+            && self.store.get(&attestation.id).await?.is_none()
+        {
+            return Ok(VerificationOutcome::Unknown);
+        }
+
+        if let Some(entry) = self.store.get_revocation(&attestation.id).await? {
+            return Ok(VerificationOutcome::Revoked {
+                reason: entry.reason,
+                at: entry.revoked_at,
+            });
+        }
+
+        Ok(verification::check_validity_window(
+            attestation,
+            now,
+            self.config.clock_skew_tolerance_ms,
+        ))
+    }
+
+    /// Verify attestation, returning `true` only if it is currently valid
+    pub async fn is_valid(&self, attestation: &Attestation) -> Result<bool> {
+        Ok(self.verify(attestation).await?.is_valid)
+    }
+
+    /// Revoke a previously issued attestation
+    ///
+    /// Revoking an attestation twice is a no-op; the original reason is kept.
+    pub async fn revoke(&self, id: &str, reason: impl Into<String>) -> Result<()> {
+        if self.store.get_revocation(id).await?.is_some() {
+            return Ok(());
+        }
+
+        let Some(attestation) = self.store.get(id).await? else {
+            return Err(SystemError::not_found("attestation", id));
+        };
+
+        let entry = RevocationEntry {
+            attestation_id: id.to_string(),
+            reason: reason.into(),
+            revoked_at: Timestamp::now(),
+        };
+        self.store.put_revocation(&entry).await?;
+        self.verification_cache.invalidate(id);
+        tracing::info!("Revoked attestation {}: {}", id, entry.reason);
+        let outcome = format!("revoked: {}", entry.reason);
+        self.operation_log
+            .append(AuditOperation::Revoke, Some(&attestation.identity), Some(id), outcome)
+            .await?;
+        // No subscribers is not an error
+        let _ = self.revocation_events.send(entry);
+
+        Ok(())
+    }
+
+    /// Receive revocations made through this authority from now on
+    ///
+    /// A subscriber that falls more than 1024 revocations behind gets
+    /// `RecvError::Lagged` and should resynchronize from
+    /// [`AttestationAuthority::revocations`].
+    pub fn subscribe_revocations(&self) -> tokio::sync::broadcast::Receiver<RevocationEntry> {
+        self.revocation_events.subscribe()
+    }
+
+    /// List all revoked attestations
+    pub async fn revocations(&self) -> Result<Vec<RevocationEntry>> {
+        self.store.list_revocations().await
+    }
+
+    /// Export a signed snapshot of the revocation set
+    ///
+    /// Revocations are never removed, so the number of entries doubles as the
+    /// list sequence number.
+    pub async fn export_revocation_list(&self) -> Result<RevocationList> {
+        let entries = self.store.list_revocations().await?;
+
+        let mut list = RevocationList {
+            sequence: entries.len() as u64,
+            issued_at: Timestamp::now(),
+            entries,
+            key_id: String::new(),
+            signature: Vec::new(),
+        };
+        self.with_signing_key(|key, signing_key| {
+            list.key_id = key.key_id.clone();
+            list.signature = signing_key.sign(&list.signing_payload()?);
+            Ok::<_, SystemError>(())
+        })?;
+
+        Ok(list)
+    }
+
+    /// Export a signed bundle for [`OfflineVerifier`]
+    ///
+    /// The bundle holds every authority key, including retired ones so that
+    /// offline verification reports `KeyRetired` like the authority does.
+    pub async fn export_trust_bundle(&self) -> Result<TrustBundle> {
+        let revocations = self.export_revocation_list().await?;
+
+        let mut bundle = TrustBundle {
+            issued_at: Timestamp::now(),
+            keys: self.keys.read().all().to_vec(),
+            revocations,
+            policy: BundlePolicy {
+                clock_skew_tolerance_ms: self.config.clock_skew_tolerance_ms,
+            },
+            key_id: String::new(),
+            signature: Vec::new(),
+        };
+        self.with_signing_key(|key, signing_key| {
+            bundle.key_id = key.key_id.clone();
+            bundle.signature = signing_key.sign(&bundle.signing_payload()?);
+            Ok::<_, SystemError>(())
+        })?;
+
+        Ok(bundle)
+    }
+
+    /// Look up an issued attestation
+    pub async fn get(&self, id: &str) -> Result<Option<Attestation>> {
+        self.store.get(id).await
+    }
+
+    /// List attestations issued to an identity, oldest first
+    pub async fn list_by_identity(&self, identity: &str, page: Page) -> Result<Vec<Attestation>> {
+        self.store.list_by_identity(identity, page).await
     }
 }
 
@@ -85,19 +1133,26 @@ impl AttestationAuthority {
 mod tests {
     use super::*;
 
+    fn request(validity_seconds: u64) -> AttestationRequest {
+        AttestationRequest {
+            identity: "test-service".to_string(),
+            claims: serde_json::Map::new(),
+            validity_seconds,
+            not_before: None,
+        }
+    }
+
     #[tokio::test]
     async fn test_attestation_issuance() {
         let config = AttestationConfig::default();
         let authority = AttestationAuthority::new(config).unwrap();
 
-        let request = AttestationRequest {
-            identity: "test-service".to_string(),
-            claims: serde_json::Map::new(),
-            validity_seconds: 3600,
-        };
-
-        let result = authority.issue(request).await;
-        assert!(result.is_ok());
+        let attestation = authority.issue(request(3600)).await.unwrap();
+        assert_eq!(attestation.not_before, attestation.issued_at);
+        assert_eq!(
+            attestation.expires_at.as_millis() - attestation.not_before.as_millis(),
+            3_600_000
+        );
     }
 
     #[tokio::test]
@@ -105,14 +1160,696 @@ mod tests {
         let config = AttestationConfig::default();
         let authority = AttestationAuthority::new(config).unwrap();
 
-        let attestation = Attestation {
-            id: "test".to_string(),
-            identity: "test".to_string(),
-            claims: serde_json::Map::new(),
-            signature: vec![0; 64],
+        let attestation = authority.issue(request(3600)).await.unwrap();
+
+        let outcome = authority.verify(&attestation).await.unwrap().outcome;
+        assert_eq!(outcome, VerificationOutcome::Valid);
+        assert!(authority.is_valid(&attestation).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tampered_attestation_has_bad_signature() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let mut attestation = authority.issue(request(3600)).await.unwrap();
+        attestation.identity = "impostor".to_string();
+
+        let outcome = authority.verify(&attestation).await.unwrap().outcome;
+        assert_eq!(outcome, VerificationOutcome::BadSignature);
+    }
+
+    #[tokio::test]
+    async fn test_attestation_expires() {
+        let config = AttestationConfig {
+            clock_skew_tolerance_ms: 0,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+
+        let attestation = authority.issue(request(1)).await.unwrap();
+        assert!(authority.is_valid(&attestation).await.unwrap());
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        let outcome = authority.verify(&attestation).await.unwrap().outcome;
+        assert_eq!(
+            outcome,
+            VerificationOutcome::Expired {
+                at: attestation.expires_at
+            }
+        );
+        assert!(!authority.is_valid(&attestation).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_revocation() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let attestation = authority.issue(request(3600)).await.unwrap();
+        authority.revoke(&attestation.id, "key_compromise").await.unwrap();
+
+        let outcome = authority.verify(&attestation).await.unwrap().outcome;
+        assert!(matches!(
+            outcome,
+            VerificationOutcome::Revoked { ref reason, .. } if reason == "key_compromise"
+        ));
+
+        // Double revocation is idempotent
+        authority.revoke(&attestation.id, "other").await.unwrap();
+        let revocations = authority.revocations().await.unwrap();
+        assert_eq!(revocations.len(), 1);
+        assert_eq!(revocations[0].reason, "key_compromise");
+
+        let result = authority.revoke("att_unknown", "reason").await;
+        assert!(matches!(result, Err(SystemError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_operation_audit_log() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let attestation = audit::with_actor("deployer", authority.issue(request(3600)))
+            .await
+            .unwrap();
+        assert!(authority.issue(request(0)).await.is_err());
+        assert!(authority.verify(&attestation).await.unwrap().is_valid);
+        let mut tampered = attestation.clone();
+        tampered.identity = "impostor".to_string();
+        authority.verify(&tampered).await.unwrap();
+        authority.revoke(&attestation.id, "key_compromise").await.unwrap();
+        let key = authority.rotate_key().await.unwrap();
+
+        let log = authority.operation_log();
+        let records = log.export(Page::default()).await.unwrap();
+        let summary: Vec<(AuditOperation, &str)> = records
+            .iter()
+            .map(|r| (r.operation, r.outcome.as_str()))
+            .collect();
+        let rotated = format!("rotated to {}", key.key_id);
+        assert_eq!(
+            summary,
+            vec![
+                (AuditOperation::Issue, "issued"),
+                (AuditOperation::Issue, "rejected: Validation"),
+                (AuditOperation::VerifyFailure, "bad_signature"),
+                (AuditOperation::Revoke, "revoked: key_compromise"),
+                (AuditOperation::RotateKey, rotated.as_str()),
+            ]
+        );
+        assert_eq!(records[0].actor, "deployer");
+        assert_eq!(records[0].attestation_id.as_deref(), Some(attestation.id.as_str()));
+        assert_eq!(records[2].identity.as_deref(), Some("impostor"));
+        assert_eq!(records[3].actor, audit::DEFAULT_ACTOR);
+        assert!(log.verify_integrity().await.unwrap().is_intact());
+    }
+
+    #[tokio::test]
+    async fn test_verification_cache() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let attestation = authority.issue(request(3600)).await.unwrap();
+
+        for _ in 0..3 {
+            assert!(authority.verify(&attestation).await.unwrap().is_valid);
+        }
+        assert_eq!(authority.verification_cache.stats().hits, 2);
+        assert_eq!(authority.verification_cache.stats().misses, 1);
+
+        let mut tampered = attestation.clone();
+        tampered.identity = "impostor".to_string();
+        let outcome = authority.verify(&tampered).await.unwrap().outcome;
+        assert_eq!(outcome, VerificationOutcome::BadSignature);
+
+        authority.revoke(&attestation.id, "key_compromise").await.unwrap();
+        let outcome = authority.verify(&attestation).await.unwrap().outcome;
+        assert!(matches!(outcome, VerificationOutcome::Revoked { .. }));
+
+        let expires = attestation.expires_at.as_millis();
+        let fresh = authority.issue(request(3600)).await.unwrap();
+        assert!(authority.verify(&fresh).await.unwrap().is_valid);
+        let later = fresh.expires_at.as_millis().max(expires) + 3_600_000;
+        let later = Timestamp::from_millis(later);
+        let outcome = authority.verify_at(&fresh, later).await.unwrap().outcome;
+        assert!(matches!(outcome, VerificationOutcome::Expired { .. }));
+
+        assert!(authority.verify(&fresh).await.unwrap().is_valid);
+        assert_eq!(authority.verification_cache.len(), 1);
+        authority.rotate_key().await.unwrap();
+        assert_eq!(authority.verification_cache.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_verification_cache_disabled() {
+        let config = AttestationConfig {
+            verification_cache_capacity: 0,
+            ..AttestationConfig::default()
         };
+        let authority = AttestationAuthority::new(config).unwrap();
+        let attestation = authority.issue(request(3600)).await.unwrap();
+
+        assert!(authority.verify(&attestation).await.unwrap().is_valid);
+        assert!(authority.verify(&attestation).await.unwrap().is_valid);
+        assert_eq!(authority.verification_cache.len(), 0);
+        assert_eq!(authority.verification_cache.stats().hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_revocation_list_export() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let first = authority.issue(request(3600)).await.unwrap();
+        let second = authority.issue(request(3600)).await.unwrap();
+        authority.revoke(&first.id, "decommissioned").await.unwrap();
+        authority.revoke(&second.id, "decommissioned").await.unwrap();
+        authority.revoke(&second.id, "decommissioned").await.unwrap();
+
+        let list = authority.export_revocation_list().await.unwrap();
+        assert_eq!(list.sequence, 2);
+        assert!(list.is_revoked(&first.id));
+
+        let json = serde_json::to_string(&list).unwrap();
+        let mut decoded: RevocationList = serde_json::from_str(&json).unwrap();
+        assert!(decoded.verify(&authority.public_key()).is_ok());
+
+        decoded.entries.pop();
+        assert!(decoded.verify(&authority.public_key()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revocations_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AttestationConfig {
+            storage: StorageBackend::Sled {
+                path: dir.path().display().to_string(),
+            },
+            ..Default::default()
+        };
+
+        let authority = AttestationAuthority::new(config.clone()).unwrap();
+        let attestation = authority.issue(request(3600)).await.unwrap();
+        authority.revoke(&attestation.id, "key_compromise").await.unwrap();
+        drop(authority);
+
+        let reopened = AttestationAuthority::new(config).unwrap();
+        let revocations = reopened.revocations().await.unwrap();
+        assert_eq!(revocations.len(), 1);
+        assert_eq!(revocations[0].attestation_id, attestation.id);
+        assert!(reopened.get(&attestation.id).await.unwrap().is_some());
+        assert_eq!(reopened.export_revocation_list().await.unwrap().sequence, 1);
+    }
+
+    #[tokio::test]
+    async fn test_issued_attestations_are_stored() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let first = authority.issue(request(3600)).await.unwrap();
+        let second = authority.issue(request(3600)).await.unwrap();
+
+        assert_eq!(authority.get(&first.id).await.unwrap().unwrap().id, first.id);
+        let listed = authority
+            .list_by_identity("test-service", Page::default())
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed.iter().any(|a| a.id == second.id));
+    }
+
+    #[tokio::test]
+    async fn test_require_known_attestation() {
+        let store: Arc<dyn AttestationStore> = Arc::new(MemoryStore::new());
+        let config = AttestationConfig {
+            require_known_attestation: true,
+            // The store is modified behind the authority's back below
+            verification_cache_capacity: 0,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::with_store(config, Arc::clone(&store)).unwrap();
+
+        let attestation = authority.issue(request(3600)).await.unwrap();
+        assert_eq!(
+            authority.verify(&attestation).await.unwrap().outcome,
+            VerificationOutcome::Valid
+        );
+
+        store.delete(&attestation.id).await.unwrap();
+        assert_eq!(
+            authority.verify(&attestation).await.unwrap().outcome,
+            VerificationOutcome::Unknown
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trusted_authority_federation() {
+        let local = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let foreign = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let attestation = foreign.issue(request(3600)).await.unwrap();
+
+        let token = attestation.to_jwt(&foreign).unwrap();
+
+        let result = local.verify(&attestation).await.unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.verified_by, VerifiedBy::Local);
+        assert_eq!(result.outcome, VerificationOutcome::BadSignature);
+        let outcomes = local.verify_batch(std::slice::from_ref(&attestation)).await;
+        assert_eq!(outcomes, vec![VerificationOutcome::BadSignature]);
+        assert_eq!(local.verify_jwt(&token).await.unwrap(), VerificationOutcome::BadSignature);
+
+        assert!(local.trust_authority("", foreign.public_key()).is_err());
+        local.trust_authority("foreign", foreign.public_key()).unwrap();
+        let result = local.verify(&attestation).await.unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.verified_by, VerifiedBy::TrustedAuthority("foreign".to_string()));
+        assert_eq!(local.verify_jwt(&token).await.unwrap(), VerificationOutcome::Valid);
+        // Trusting other authorities leaves local attestations verified locally
+        let own = local.issue(request(3600)).await.unwrap();
+        assert_eq!(local.verify(&own).await.unwrap().verified_by, VerifiedBy::Local);
+        let outcomes = local.verify_batch(&[attestation.clone(), own]).await;
+        assert_eq!(outcomes, vec![VerificationOutcome::Valid; 2]);
+
+        let expired = foreign.issue(request(1)).await.unwrap();
+        let later = Timestamp::from_millis(expired.expires_at.as_millis() + 600_000);
+        let result = local.verify_at(&expired, later).await.unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.verified_by, VerifiedBy::TrustedAuthority("foreign".to_string()));
+        let tampered = Attestation {
+            identity: "mallory".to_string(),
+            ..attestation.clone()
+        };
+        let outcomes = local.verify_batch(&[tampered]).await;
+        assert_eq!(outcomes, vec![VerificationOutcome::BadSignature]);
+
+        local.distrust_authority("foreign").unwrap();
+        assert!(!local.is_valid(&attestation).await.unwrap());
+        let err = local.distrust_authority("foreign").unwrap_err();
+        assert!(matches!(err, SystemError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_max_validity_rejected() {
+        let config = AttestationConfig {
+            max_validity_seconds: 60,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+
+        assert!(authority.issue(request(60)).await.is_ok());
+
+        let result = authority.issue(request(61)).await;
+        assert!(matches!(
+            result,
+            Err(SystemError::Validation { ref field, .. }) if field == "validity_seconds"
+        ));
+
+        assert!(authority.issue(request(0)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clock_skew_tolerance_boundaries() {
+        let config = AttestationConfig {
+            clock_skew_tolerance_ms: 500,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+
+        let not_before = Timestamp::from_millis(Timestamp::now().as_millis() + 60_000);
+        let attestation = authority
+            .issue(AttestationRequest {
+                not_before: Some(not_before),
+                ..request(10)
+            })
+            .await
+            .unwrap();
+        let start = attestation.not_before.as_millis();
+        let end = attestation.expires_at.as_millis();
+
+        let at = |millis| authority.verify_at(&attestation, Timestamp::from_millis(millis));
+
+        assert_eq!(
+            at(start - 501).await.unwrap().outcome,
+            VerificationOutcome::NotYetValid {
+                at: attestation.not_before
+            }
+        );
+        assert_eq!(at(start - 500).await.unwrap().outcome, VerificationOutcome::Valid);
+        assert_eq!(at(end + 499).await.unwrap().outcome, VerificationOutcome::Valid);
+        assert_eq!(
+            at(end + 500).await.unwrap().outcome,
+            VerificationOutcome::Expired {
+                at: attestation.expires_at
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_claims_schema_enforced_on_issue() {
+        let strict = ClaimsSchema::new(serde_json::json!({
+            "required": ["environment"],
+            "additionalProperties": false,
+            "properties": { "environment": { "type": "string" } }
+        }))
+        .unwrap();
+        let config = AttestationConfig {
+            claims_schemas: vec![("prod-*".to_string(), strict)],
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+
+        let mut claims = serde_json::Map::new();
+        claims.insert("environment".to_string(), serde_json::json!("production"));
+        claims.insert("enviroment".to_string(), serde_json::json!("staging"));
+
+        let result = authority
+            .issue(AttestationRequest {
+                identity: "prod-api".to_string(),
+                claims: claims.clone(),
+                ..request(3600)
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(SystemError::Validation { ref field, .. }) if field == "/enviroment"
+        ));
+
+        // Identities matching no pattern fall back to the (absent) default schema
+        assert!(authority
+            .issue(AttestationRequest {
+                claims,
+                ..request(3600)
+            })
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_claims_validator_rejects_before_signing() {
+        let config = AttestationConfig {
+            claims_validator: Some(RequiredClaimsValidator(vec!["region".to_string()]).into()),
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+
+        let result = authority.issue(request(3600)).await;
+        assert!(matches!(
+            result,
+            Err(SystemError::Validation { ref field, .. }) if field == "/region"
+        ));
+        let page = Page::new(0, 10);
+        assert!(authority.list_by_identity("test-service", page).await.unwrap().is_empty());
+
+        let mut claims = serde_json::Map::new();
+        claims.insert("region".to_string(), serde_json::json!("eu-west-1"));
+        let request = AttestationRequest {
+            claims,
+            ..request(3600)
+        };
+        assert!(authority.issue(request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_typed_claim_accessors() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let claims = serde_json::json!({
+            "environment": "production",
+            "replicas": 3,
+            "perm:read": true,
+            "perm:write": false
+        });
+        let attestation = authority
+            .issue(AttestationRequest {
+                claims: claims.as_object().unwrap().clone(),
+                ..request(3600)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(attestation.claim_str("environment"), Some("production"));
+        assert_eq!(attestation.claim_str("replicas"), None);
+        assert_eq!(attestation.claim_u64("replicas"), Some(3));
+        assert_eq!(attestation.claim_u64("missing"), None);
+
+        let perms: Vec<&str> = attestation
+            .claims_matching("perm:")
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(perms, vec!["perm:read", "perm:write"]);
+
+        let production = serde_json::json!("production");
+        let staging = serde_json::json!("staging");
+        assert!(attestation
+            .require_claims(&[("environment", &production)])
+            .is_ok());
+        assert!(matches!(
+            attestation.require_claims(&[("environment", &staging)]),
+            Err(SystemError::Validation { ref field, .. }) if field == "/environment"
+        ));
+        assert!(attestation
+            .require_claims(&[("region", &production)])
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signature_independent_of_claim_order() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let mut claims = serde_json::Map::new();
+        claims.insert("b".to_string(), serde_json::json!({"y": 1, "x": 2}));
+        claims.insert("a".to_string(), serde_json::json!(1));
+        let mut attestation = authority
+            .issue(AttestationRequest {
+                claims,
+                ..request(3600)
+            })
+            .await
+            .unwrap();
+
+        let mut reordered = serde_json::Map::new();
+        reordered.insert("a".to_string(), serde_json::json!(1));
+        reordered.insert("b".to_string(), serde_json::json!({"x": 2, "y": 1}));
+        attestation.claims = reordered;
+
+        assert!(authority.is_valid(&attestation).await.unwrap());
+    }
+
+    /// Memory store that takes a while to persist each attestation
+    struct SlowStore {
+        inner: MemoryStore,
+        delay: std::time::Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl AttestationStore for SlowStore {
+        async fn put(&self, attestation: &Attestation) -> Result<()> {
+            tokio::time::sleep(self.delay).await;
+            self.inner.put(attestation).await
+        }
+
+        async fn get(&self, id: &str) -> Result<Option<Attestation>> {
+            self.inner.get(id).await
+        }
+
+        async fn list_by_identity(&self, identity: &str, page: Page) -> Result<Vec<Attestation>> {
+            self.inner.list_by_identity(identity, page).await
+        }
+
+        async fn delete(&self, id: &str) -> Result<bool> {
+            self.inner.delete(id).await
+        }
+
+        async fn put_revocation(&self, entry: &RevocationEntry) -> Result<()> {
+            self.inner.put_revocation(entry).await
+        }
+
+        async fn get_revocation(&self, attestation_id: &str) -> Result<Option<RevocationEntry>> {
+            self.inner.get_revocation(attestation_id).await
+        }
+
+        async fn list_revocations(&self) -> Result<Vec<RevocationEntry>> {
+            self.inner.list_revocations().await
+        }
+
+        async fn put_key_history(&self, sealed: &[u8]) -> Result<()> {
+            self.inner.put_key_history(sealed).await
+        }
+
+        async fn get_key_history(&self) -> Result<Option<Vec<u8>>> {
+            self.inner.get_key_history().await
+        }
+
+        async fn append_audit(&self, decision: &PolicyDecision) -> Result<()> {
+            self.inner.append_audit(decision).await
+        }
+
+        async fn list_audit(&self, page: Page) -> Result<Vec<PolicyDecision>> {
+            self.inner.list_audit(page).await
+        }
+
+        async fn put_audit_record(&self, record: &AuditRecord) -> Result<()> {
+            self.inner.put_audit_record(record).await
+        }
+
+        async fn list_audit_records(&self, page: Page) -> Result<Vec<AuditRecord>> {
+            self.inner.list_audit_records(page).await
+        }
+
+        async fn last_audit_record(&self) -> Result<Option<AuditRecord>> {
+            self.inner.last_audit_record().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_issue_batch_preserves_order_and_errors() {
+        let delay = std::time::Duration::from_millis(5);
+        let store = Arc::new(SlowStore {
+            inner: MemoryStore::new(),
+            delay,
+        });
+        let authority = AttestationAuthority::with_store(AttestationConfig::default(), store).unwrap();
+
+        let requests: Vec<AttestationRequest> = (0..500)
+            .map(|i| AttestationRequest {
+                identity: format!("workload-{i}"),
+                ..request(if i % 100 == 7 { 0 } else { 3600 })
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        let results = authority.issue_batch(requests).await.unwrap();
+        let elapsed = started.elapsed();
+
+        assert_eq!(results.len(), 500);
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(attestation) => assert_eq!(attestation.identity, format!("workload-{i}")),
+                Err(SystemError::Validation { field, .. }) => {
+                    assert_eq!(i % 100, 7);
+                    assert_eq!(field, "validity_seconds");
+                },
+                Err(other) => panic!("unexpected error at {i}: {other}"),
+            }
+        }
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 5);
+
+        // Serial issuance would take at least 495 * 5ms
+        assert!(elapsed < delay * 495 / 4, "batch took {elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn test_verify_batch() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+
+        let mut attestations: Vec<Attestation> = authority
+            .issue_batch((0..20).map(|_| request(3600)).collect())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect();
+        attestations[3].identity = "impostor".to_string();
+        attestations[9].signature.truncate(10);
+        authority.revoke(&attestations[12].id, "decommissioned").await.unwrap();
+
+        let outcomes = authority.verify_batch(&attestations).await;
+        assert_eq!(outcomes.len(), 20);
+        for (i, outcome) in outcomes.iter().enumerate() {
+            match i {
+                3 | 9 => assert_eq!(*outcome, VerificationOutcome::BadSignature),
+                12 => assert!(matches!(outcome, VerificationOutcome::Revoked { .. })),
+                _ => assert_eq!(*outcome, VerificationOutcome::Valid),
+            }
+        }
+    }
+
+    fn renewable_authority() -> (AttestationAuthority, KeyPair) {
+        let config = AttestationConfig {
+            max_renewal_chain_seconds: 2 * 3600,
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap();
+        let key = KeyPair::generate();
+        authority.register_identity("test-service", key.public_key());
+        (authority, key)
+    }
+
+    /// An attestation valid for `minutes` whose window opened `minutes_ago`
+    async fn issued(
+        authority: &AttestationAuthority,
+        minutes_ago: u64,
+        minutes: u64,
+    ) -> Attestation {
+        let now = Timestamp::now().as_millis();
+        let not_before = Timestamp::from_millis(now - minutes_ago * 60_000);
+        let mut request = request(minutes * 60);
+        request.claims.insert("tier".to_string(), serde_json::json!("gold"));
+        request.not_before = Some(not_before);
+        authority.issue(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_renewal() {
+        let (authority, key) = renewable_authority();
+        let original = issued(&authority, 50, 60).await;
+
+        let proof = key.sign(&original.renewal_nonce());
+        let renewed = authority.renew(&original, &proof).await.unwrap();
+        assert_eq!(renewed.renews.as_deref(), Some(original.id.as_str()));
+        assert_eq!(renewed.claims, original.claims);
+        assert_eq!(renewed.not_before, renewed.issued_at);
+        assert_eq!(renewed.expires_at.as_millis() - renewed.not_before.as_millis(), 3_600_000);
+        assert!(authority.is_valid(&renewed).await.unwrap());
+
+        // The link is signed and survives the JWT round trip
+        let mut unlinked = renewed.clone();
+        unlinked.renews = None;
+        assert!(!authority.is_valid(&unlinked).await.unwrap());
+        let token = renewed.to_jwt(&authority).unwrap();
+        assert_eq!(authority.verify_jwt(&token).await.unwrap(), VerificationOutcome::Valid);
+
+        for proof in [
+            KeyPair::generate().sign(&renewed.renewal_nonce()),
+            key.sign(&original.renewal_nonce()),
+        ] {
+            let result = authority.renew(&renewed, &proof).await;
+            assert!(matches!(result, Err(SystemError::PermissionDenied { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_renewal_chain_lifetime() {
+        let (authority, key) = renewable_authority();
+
+        // Renewing extends the chain to 1h50m, then 1h50m again
+        let original = issued(&authority, 50, 60).await;
+        let first = authority.renew(&original, &key.sign(&original.renewal_nonce())).await.unwrap();
+        let second = authority.renew(&first, &key.sign(&first.renewal_nonce())).await.unwrap();
+        assert_eq!(second.renews.as_deref(), Some(first.id.as_str()));
+
+        // 30m ago plus another 100m exceeds the two hour limit
+        let old = issued(&authority, 30, 100).await;
+        let result = authority.renew(&old, &key.sign(&old.renewal_nonce())).await;
+        assert!(matches!(result, Err(SystemError::PermissionDenied { .. })));
+
+        // The chain start is looked up through the stored links
+        authority.store.delete(&original.id).await.unwrap();
+        let result = authority.renew(&second, &key.sign(&second.renewal_nonce())).await;
+        assert!(matches!(result, Err(SystemError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_renewal_of_invalid_attestation_rejected() {
+        let (authority, key) = renewable_authority();
+
+        let attestation = issued(&authority, 0, 60).await;
+        let proof = key.sign(&attestation.renewal_nonce());
+        authority.revoke(&attestation.id, "key_compromise").await.unwrap();
+        let err = authority.renew(&attestation, &proof).await.unwrap_err();
+        assert!(matches!(
+            err,
+            SystemError::InvalidState { current_state: Some(ref state), .. } if state == "revoked"
+        ));
 
-        let result = authority.verify(&attestation).await;
-        assert!(result.is_ok());
+        let expired = issued(&authority, 120, 60).await;
+        let err = authority.renew(&expired, &key.sign(&expired.renewal_nonce())).await.unwrap_err();
+        assert!(matches!(err, SystemError::InvalidState { .. }));
     }
 }
@@ -0,0 +1,261 @@
+//! Offline verification
+//!
+//! A [`TrustBundle`] carries everything needed to verify attestations
+//! without contacting the authority: its public keys with their validity
+//! windows, a signed [`RevocationList`] and the verification policy. Edge
+//! services cache a bundle, check it against a pinned authority key with
+//! [`OfflineVerifier::new`] and fetch a fresh one once
+//! [`TrustBundle::is_stale`] says so.
+//!
+//! Offline verification never reports [`VerificationOutcome::Unknown`], since
+//! the bundle does not list issued attestations, and only knows the
+//! revocations made before the bundle was exported.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use shared_core::{crypto::PublicKey, Result, SystemError, Timestamp};
+
+use crate::{jwt, verification, Attestation, RevocationList, VerificationKey, VerificationOutcome};
+
+/// Verification policy shipped with a [`TrustBundle`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundlePolicy {
+    /// Clock skew tolerated when checking validity windows, in milliseconds
+    pub clock_skew_tolerance_ms: u64,
+}
+
+/// Signed snapshot of the authority state needed for offline verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustBundle {
+    /// When the bundle was exported
+    pub issued_at: Timestamp,
+    /// Every authority key, including rotated-out and retired ones
+    pub keys: Vec<VerificationKey>,
+    /// Revocations known at export time
+    pub revocations: RevocationList,
+    /// How attestations are to be verified
+    pub policy: BundlePolicy,
+    /// ID of the authority key that signed the bundle
+    pub key_id: String,
+    /// Authority signature over the bundle
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct TrustBundlePayload<'a> {
+    issued_at: Timestamp,
+    keys: &'a [VerificationKey],
+    revocations: &'a RevocationList,
+    policy: BundlePolicy,
+    key_id: &'a str,
+}
+
+impl TrustBundle {
+    /// Canonical bytes signed by the issuing authority
+    pub fn signing_payload(&self) -> Result<Vec<u8>> {
+        let payload = TrustBundlePayload {
+            issued_at: self.issued_at,
+            keys: &self.keys,
+            revocations: &self.revocations,
+            policy: self.policy,
+            key_id: &self.key_id,
+        };
+        Ok(serde_json::to_vec(&payload)?)
+    }
+
+    /// Verify the bundle signature against an authority public key
+    pub fn verify(&self, public_key: &PublicKey) -> Result<()> {
+        public_key.verify(&self.signing_payload()?, &self.signature)
+    }
+
+    /// Whether the bundle was exported more than `max_age` ago
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        Timestamp::now()
+            .elapsed_since(self.issued_at)
+            .is_some_and(|age| age > max_age)
+    }
+
+    fn key(&self, key_id: &str) -> Option<&VerificationKey> {
+        self.keys.iter().find(|key| key.key_id == key_id)
+    }
+}
+
+/// Verifies attestations against a [`TrustBundle`] without network access
+#[derive(Debug, Clone)]
+pub struct OfflineVerifier {
+    bundle: TrustBundle,
+}
+
+impl OfflineVerifier {
+    /// Accept `bundle` if one of the pinned `trusted` keys signed it
+    ///
+    /// The bundle's revocation list must also carry a valid signature by one
+    /// of the bundle keys. Failures are `Crypto` errors.
+    pub fn new(bundle: TrustBundle, trusted: &[PublicKey]) -> Result<Self> {
+        let signer = bundle
+            .key(&bundle.key_id)
+            .filter(|key| trusted.contains(&key.public_key))
+            .ok_or_else(|| {
+                SystemError::crypto("verify_trust_bundle", "bundle signer is not a trusted key")
+            })?;
+        bundle.verify(&signer.public_key)?;
+
+        let list_signer = bundle.key(&bundle.revocations.key_id).ok_or_else(|| {
+            SystemError::crypto("verify_trust_bundle", "revocation list signer is unknown")
+        })?;
+        bundle.revocations.verify(&list_signer.public_key)?;
+
+        Ok(Self { bundle })
+    }
+
+    /// The bundle attestations are verified against
+    pub fn bundle(&self) -> &TrustBundle {
+        &self.bundle
+    }
+
+    /// Verify an attestation at the current time
+    pub fn verify(&self, attestation: &Attestation) -> VerificationOutcome {
+        self.verify_at(attestation, Timestamp::now())
+    }
+
+    /// Verify an attestation as of `now`
+    pub fn verify_at(&self, attestation: &Attestation, now: Timestamp) -> VerificationOutcome {
+        let Ok(payload) = attestation.signing_payload() else {
+            return VerificationOutcome::BadSignature;
+        };
+        self.check_signature(&attestation.key_id, &payload, &attestation.signature, now)
+            .unwrap_or_else(|| self.check_status(attestation, now))
+    }
+
+    /// Verify a compact JWS produced by [`Attestation::to_jwt`]
+    ///
+    /// Malformed tokens and any algorithm other than `EdDSA` are rejected
+    /// with a `Validation` error.
+    pub fn verify_jwt(&self, token: &str) -> Result<VerificationOutcome> {
+        let decoded = jwt::decode(token)?;
+        let now = Timestamp::now();
+        Ok(self
+            .check_signature(
+                &decoded.attestation.key_id,
+                decoded.signing_input.as_bytes(),
+                &decoded.signature,
+                now,
+            )
+            .unwrap_or_else(|| self.check_status(&decoded.attestation, now)))
+    }
+
+    /// Same contract as the authority's signature check
+    fn check_signature(
+        &self,
+        key_id: &str,
+        message: &[u8],
+        signature: &[u8],
+        now: Timestamp,
+    ) -> Option<VerificationOutcome> {
+        let Some(key) = self.bundle.key(key_id) else {
+            return Some(VerificationOutcome::BadSignature);
+        };
+        if key.public_key.verify(message, signature).is_err() {
+            return Some(VerificationOutcome::BadSignature);
+        }
+        verification::check_key_retired(key, now)
+    }
+
+    fn check_status(&self, attestation: &Attestation, now: Timestamp) -> VerificationOutcome {
+        let revoked = self
+            .bundle
+            .revocations
+            .entries
+            .iter()
+            .find(|entry| entry.attestation_id == attestation.id);
+        if let Some(entry) = revoked {
+            return VerificationOutcome::Revoked {
+                reason: entry.reason.clone(),
+                at: entry.revoked_at,
+            };
+        }
+
+        verification::check_validity_window(
+            attestation,
+            now,
+            self.bundle.policy.clock_skew_tolerance_ms,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttestationAuthority, AttestationConfig, AttestationRequest};
+
+    fn request(identity: &str) -> AttestationRequest {
+        AttestationRequest {
+            identity: identity.to_string(),
+            claims: serde_json::Map::new(),
+            validity_seconds: 3600,
+            not_before: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_offline_with_revocations() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let kept = authority.issue(request("svc-a")).await.unwrap();
+        let revoked = authority.issue(request("svc-b")).await.unwrap();
+        authority.revoke(&revoked.id, "key_compromise").await.unwrap();
+
+        let bundle = authority.export_trust_bundle().await.unwrap();
+        assert!(!bundle.is_stale(Duration::from_secs(60)));
+        let verifier = OfflineVerifier::new(bundle, &[authority.public_key()]).unwrap();
+
+        assert_eq!(verifier.verify(&kept), VerificationOutcome::Valid);
+        assert!(matches!(
+            verifier.verify(&revoked),
+            VerificationOutcome::Revoked { ref reason, .. } if reason == "key_compromise"
+        ));
+
+        let mut forged = kept.clone();
+        forged.claims.insert("role".to_string(), "admin".into());
+        assert_eq!(verifier.verify(&forged), VerificationOutcome::BadSignature);
+
+        let token = kept.to_jwt(&authority).unwrap();
+        assert_eq!(verifier.verify_jwt(&token).unwrap(), VerificationOutcome::Valid);
+        let token = revoked.to_jwt(&authority).unwrap();
+        assert!(matches!(
+            verifier.verify_jwt(&token).unwrap(),
+            VerificationOutcome::Revoked { .. }
+        ));
+
+        let later = Timestamp::from_millis(kept.expires_at.as_millis() + 10 * 60 * 1000);
+        assert!(matches!(verifier.verify_at(&kept, later), VerificationOutcome::Expired { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_tampered_bundle_rejected() {
+        let authority = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let revoked = authority.issue(request("svc-a")).await.unwrap();
+        authority.revoke(&revoked.id, "key_compromise").await.unwrap();
+        let bundle = authority.export_trust_bundle().await.unwrap();
+        let trusted = [authority.public_key()];
+
+        // Dropping a revocation breaks the bundle signature
+        let mut tampered = bundle.clone();
+        tampered.revocations.entries.clear();
+        let err = OfflineVerifier::new(tampered, &trusted).unwrap_err();
+        assert!(matches!(err, SystemError::Crypto { .. }));
+
+        let mut tampered = bundle.clone();
+        tampered.policy.clock_skew_tolerance_ms = u64::MAX;
+        assert!(OfflineVerifier::new(tampered, &trusted).is_err());
+
+        // A bundle signed by another authority is not trusted
+        let other = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        let foreign = other.export_trust_bundle().await.unwrap();
+        assert!(OfflineVerifier::new(foreign, &trusted).is_err());
+
+        let mut stale = bundle;
+        stale.issued_at = Timestamp::from_millis(stale.issued_at.as_millis() - 2 * 60 * 60 * 1000);
+        assert!(stale.is_stale(Duration::from_secs(3600)));
+    }
+}
@@ -0,0 +1,253 @@
+//! Issuance quotas
+//!
+//! Per-identity token buckets bounding how fast one identity can be issued
+//! attestations. A bucket holds up to `burst` tokens and refills at
+//! `requests_per_minute`; every issuance that passes request validation
+//! takes a token, whether or not it is then admitted by the policy.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, SystemError};
+
+use crate::core::Clock;
+
+/// Bucket units per token; a quota refills `requests_per_minute` units per
+/// millisecond, so refills are exact in integers
+const UNITS_PER_TOKEN: u64 = 60_000;
+
+/// Per-identity issuance rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssuanceQuota {
+    /// Sustained issuances per minute
+    pub requests_per_minute: u32,
+    /// Issuances allowed in a burst, and the bucket capacity
+    pub burst: u32,
+}
+
+impl IssuanceQuota {
+    /// Reject quotas that would never admit a request
+    pub fn validate(&self) -> Result<()> {
+        if self.requests_per_minute == 0 || self.burst == 0 {
+            return Err(SystemError::config(
+                "requests_per_minute and burst must be > 0",
+                Some("issuance_quota".to_string()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> u64 {
+        u64::from(self.burst) * UNITS_PER_TOKEN
+    }
+}
+
+/// Quota state of one identity, see
+/// [`AttestationAuthority::quota_status`](crate::AttestationAuthority::quota_status)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaStatus {
+    /// Identity the status belongs to
+    pub identity: String,
+    /// Quota applied to the identity
+    pub quota: IssuanceQuota,
+    /// Issuances available right now
+    pub remaining: u32,
+    /// How long until the next issuance is available, when none is
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    units: u64,
+    updated_ms: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, quota: &IssuanceQuota, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.updated_ms);
+        let refill = elapsed.saturating_mul(u64::from(quota.requests_per_minute));
+        self.units = self.units.saturating_add(refill).min(quota.capacity());
+        self.updated_ms = self.updated_ms.max(now_ms);
+    }
+
+    fn remaining(&self) -> u32 {
+        u32::try_from(self.units / UNITS_PER_TOKEN).unwrap_or(u32::MAX)
+    }
+
+    fn retry_after(&self, quota: &IssuanceQuota) -> Option<Duration> {
+        let missing = UNITS_PER_TOKEN.checked_sub(self.units).filter(|&m| m > 0)?;
+        Some(Duration::from_millis(
+            missing.div_ceil(u64::from(quota.requests_per_minute)),
+        ))
+    }
+}
+
+/// Token buckets of every identity seen so far
+pub(crate) struct IssuanceQuotas {
+    quota: Option<IssuanceQuota>,
+    clock: Arc<dyn Clock>,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl IssuanceQuotas {
+    pub(crate) fn new(quota: Option<IssuanceQuota>, clock: Arc<dyn Clock>) -> Result<Self> {
+        if let Some(quota) = &quota {
+            quota.validate()?;
+        }
+        Ok(Self {
+            quota,
+            clock,
+            buckets: DashMap::new(),
+        })
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Take a token for `identity`, or fail with `RateLimited`
+    pub(crate) fn take(&self, identity: &str) -> Result<()> {
+        let Some(quota) = &self.quota else {
+            return Ok(());
+        };
+        let now_ms = self.clock.now().as_millis();
+        // The entry lock makes refill-and-take atomic per identity
+        let mut bucket = self.buckets.entry(identity.to_string()).or_insert(Bucket {
+            units: quota.capacity(),
+            updated_ms: now_ms,
+        });
+        bucket.refill(quota, now_ms);
+
+        let result = match bucket.retry_after(quota) {
+            Some(retry_after) => {
+                shared_core::count!(
+                    "uaa_issuance_rate_limited_total", 1, "identity" => identity.to_string()
+                );
+                Err(SystemError::rate_limited(identity, retry_after))
+            },
+            None => {
+                bucket.units -= UNITS_PER_TOKEN;
+                Ok(())
+            },
+        };
+        shared_core::gauge!(
+            "uaa_issuance_quota_remaining", f64::from(bucket.remaining()),
+            "identity" => identity.to_string()
+        );
+        result
+    }
+
+    /// Current state of the bucket of `identity`, without taking a token
+    pub(crate) fn status(&self, identity: &str) -> Option<QuotaStatus> {
+        let quota = self.quota?;
+        let now_ms = self.clock.now().as_millis();
+        let mut bucket = self.buckets.get(identity).map_or(
+            Bucket {
+                units: quota.capacity(),
+                updated_ms: now_ms,
+            },
+            |bucket| *bucket,
+        );
+        bucket.refill(&quota, now_ms);
+
+        Some(QuotaStatus {
+            identity: identity.to_string(),
+            quota,
+            remaining: bucket.remaining(),
+            retry_after: bucket.retry_after(&quota),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use shared_core::{ErrorResponse, Timestamp};
+
+    use super::*;
+    use crate::{AttestationAuthority, AttestationConfig, AttestationRequest};
+
+    #[derive(Debug)]
+    struct ManualClock(AtomicU64);
+
+    impl ManualClock {
+        fn advance(&self, millis: u64) {
+            self.0.fetch_add(millis, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Timestamp {
+            Timestamp::from_millis(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    fn request(identity: &str) -> AttestationRequest {
+        AttestationRequest {
+            identity: identity.to_string(),
+            claims: serde_json::Map::new(),
+            validity_seconds: 3600,
+            not_before: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quota_limits_one_identity() {
+        let clock = Arc::new(ManualClock(AtomicU64::new(1_000_000)));
+        let config = AttestationConfig {
+            issuance_quota: Some(IssuanceQuota {
+                requests_per_minute: 60,
+                burst: 3,
+            }),
+            ..Default::default()
+        };
+        let authority = AttestationAuthority::new(config).unwrap().with_clock(clock.clone());
+
+        for _ in 0..3 {
+            authority.issue(request("svc-a")).await.unwrap();
+        }
+        let err = authority.issue(request("svc-a")).await.unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(1)));
+        assert_eq!(ErrorResponse::from(&err).status, 429);
+
+        clock.advance(400);
+        let err = authority.issue(request("svc-a")).await.unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_millis(600)));
+        let status = authority.quota_status("svc-a").unwrap();
+        assert_eq!(status.remaining, 0);
+        assert_eq!(status.retry_after, Some(Duration::from_millis(600)));
+
+        // Other identities have their own bucket
+        authority.issue(request("svc-b")).await.unwrap();
+        assert_eq!(authority.quota_status("svc-b").unwrap().remaining, 2);
+        assert_eq!(authority.quota_status("svc-c").unwrap().remaining, 3);
+
+        clock.advance(600);
+        authority.issue(request("svc-a")).await.unwrap();
+
+        // Rejected requests never reach the signer
+        let signed = authority.governor().statistics().labels[crate::SIGN_PERMIT_LABEL];
+        assert_eq!(signed.total_operations, 5);
+        assert_eq!(signed.active_operations, 0);
+    }
+
+    #[test]
+    fn test_quota_validation() {
+        let quota = IssuanceQuota {
+            requests_per_minute: 0,
+            burst: 1,
+        };
+        assert!(matches!(quota.validate(), Err(SystemError::Config { .. })));
+
+        let config = AttestationConfig {
+            issuance_quota: Some(quota),
+            ..Default::default()
+        };
+        assert!(AttestationAuthority::new(config).is_err());
+        let unlimited = AttestationAuthority::new(AttestationConfig::default()).unwrap();
+        assert!(unlimited.quota_status("svc-a").is_none());
+    }
+}
@@ -1 +1,432 @@
 //! Storage module
+//!
+//! Pluggable persistence for issued attestations and revocations. Backends
+//! implement [`AttestationStore`]; the authority picks one through
+//! [`StorageBackend`](crate::config::StorageBackend).
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared_core::{Result, Timestamp};
+
+use crate::{audit::AuditRecord, Attestation, PolicyDecision};
+
+mod sled_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
+
+pub use sled_store::SledStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite_store::SqliteStore;
+
+/// A single revoked attestation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationEntry {
+    /// ID of the revoked attestation
+    pub attestation_id: String,
+    /// Why the attestation was revoked
+    pub reason: String,
+    /// When the attestation was revoked
+    pub revoked_at: Timestamp,
+}
+
+/// Offset/limit pagination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    /// Number of items to skip
+    pub offset: usize,
+    /// Maximum number of items to return
+    pub limit: usize,
+}
+
+impl Page {
+    /// Create a page
+    pub fn new(offset: usize, limit: usize) -> Self {
+        Self { offset, limit }
+    }
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: 100,
+        }
+    }
+}
+
+/// Persistence backend for attestations and revocations
+#[allow(clippy::double_must_use)]
+#[async_trait]
+pub trait AttestationStore: Send + Sync {
+    /// Insert or replace an attestation
+    async fn put(&self, attestation: &Attestation) -> Result<()>;
+
+    /// Get an attestation by ID
+    async fn get(&self, id: &str) -> Result<Option<Attestation>>;
+
+    /// List attestations for an identity, ordered by `issued_at` then ID
+    async fn list_by_identity(&self, identity: &str, page: Page) -> Result<Vec<Attestation>>;
+
+    /// Delete an attestation, returning whether it existed
+    async fn delete(&self, id: &str) -> Result<bool>;
+
+    /// Insert or replace a revocation entry
+    async fn put_revocation(&self, entry: &RevocationEntry) -> Result<()>;
+
+    /// Get the revocation entry for an attestation
+    async fn get_revocation(&self, attestation_id: &str) -> Result<Option<RevocationEntry>>;
+
+    /// List all revocations, ordered by `revoked_at` then attestation ID
+    async fn list_revocations(&self) -> Result<Vec<RevocationEntry>>;
+
+    /// Replace the encrypted authority key history
+    async fn put_key_history(&self, sealed: &[u8]) -> Result<()>;
+
+    /// Get the encrypted authority key history
+    async fn get_key_history(&self) -> Result<Option<Vec<u8>>>;
+
+    /// Append an issuance policy decision to the audit log
+    async fn append_audit(&self, decision: &PolicyDecision) -> Result<()>;
+
+    /// List audit log entries, oldest first
+    async fn list_audit(&self, page: Page) -> Result<Vec<PolicyDecision>>;
+
+    /// Insert or replace the operation audit record at `record.index`
+    async fn put_audit_record(&self, record: &AuditRecord) -> Result<()>;
+
+    /// List operation audit records by index
+    async fn list_audit_records(&self, page: Page) -> Result<Vec<AuditRecord>>;
+
+    /// Get the operation audit record with the highest index
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>>;
+}
+
+/// Volatile in-memory store
+#[derive(Default)]
+pub struct MemoryStore {
+    attestations: DashMap<String, Attestation>,
+    revocations: DashMap<String, RevocationEntry>,
+    key_history: Mutex<Option<Vec<u8>>>,
+    audit: Mutex<Vec<PolicyDecision>>,
+    audit_records: Mutex<BTreeMap<u64, AuditRecord>>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AttestationStore for MemoryStore {
+    async fn put(&self, attestation: &Attestation) -> Result<()> {
+        self.attestations
+            .insert(attestation.id.clone(), attestation.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Attestation>> {
+        Ok(self.attestations.get(id).map(|entry| entry.value().clone()))
+    }
+
+    async fn list_by_identity(&self, identity: &str, page: Page) -> Result<Vec<Attestation>> {
+        let mut matching: Vec<Attestation> = self
+            .attestations
+            .iter()
+            .filter(|entry| entry.identity == identity)
+            .map(|entry| entry.value().clone())
+            .collect();
+        sort_by_issued_at(&mut matching);
+
+        Ok(matching
+            .into_iter()
+            .skip(page.offset)
+            .take(page.limit)
+            .collect())
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self.attestations.remove(id).is_some())
+    }
+
+    async fn put_revocation(&self, entry: &RevocationEntry) -> Result<()> {
+        self.revocations
+            .insert(entry.attestation_id.clone(), entry.clone());
+        Ok(())
+    }
+
+    async fn get_revocation(&self, attestation_id: &str) -> Result<Option<RevocationEntry>> {
+        Ok(self
+            .revocations
+            .get(attestation_id)
+            .map(|entry| entry.value().clone()))
+    }
+
+    async fn list_revocations(&self) -> Result<Vec<RevocationEntry>> {
+        let mut entries: Vec<RevocationEntry> = self
+            .revocations
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        sort_by_revoked_at(&mut entries);
+        Ok(entries)
+    }
+
+    async fn put_key_history(&self, sealed: &[u8]) -> Result<()> {
+        *self.key_history.lock() = Some(sealed.to_vec());
+        Ok(())
+    }
+
+    async fn get_key_history(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self.key_history.lock().clone())
+    }
+
+    async fn append_audit(&self, decision: &PolicyDecision) -> Result<()> {
+        self.audit.lock().push(decision.clone());
+        Ok(())
+    }
+
+    async fn list_audit(&self, page: Page) -> Result<Vec<PolicyDecision>> {
+        Ok(self
+            .audit
+            .lock()
+            .iter()
+            .skip(page.offset)
+            .take(page.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn put_audit_record(&self, record: &AuditRecord) -> Result<()> {
+        self.audit_records.lock().insert(record.index, record.clone());
+        Ok(())
+    }
+
+    async fn list_audit_records(&self, page: Page) -> Result<Vec<AuditRecord>> {
+        Ok(self
+            .audit_records
+            .lock()
+            .values()
+            .skip(page.offset)
+            .take(page.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>> {
+        Ok(self.audit_records.lock().values().next_back().cloned())
+    }
+}
+
+fn sort_by_issued_at(attestations: &mut [Attestation]) {
+    attestations.sort_by(|a, b| a.issued_at.cmp(&b.issued_at).then_with(|| a.id.cmp(&b.id)));
+}
+
+fn sort_by_revoked_at(entries: &mut [RevocationEntry]) {
+    entries.sort_by(|a, b| {
+        a.revoked_at
+            .cmp(&b.revoked_at)
+            .then_with(|| a.attestation_id.cmp(&b.attestation_id))
+    });
+}
+
+/// Conformance suite shared by every backend
+#[cfg(test)]
+pub(crate) mod conformance {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::audit::AuditOperation;
+
+    pub(crate) fn attestation(id: &str, identity: &str, issued_at: u64) -> Attestation {
+        Attestation {
+            id: id.to_string(),
+            identity: identity.to_string(),
+            claims: serde_json::Map::new(),
+            issued_at: Timestamp::from_millis(issued_at),
+            not_before: Timestamp::from_millis(issued_at),
+            expires_at: Timestamp::from_millis(issued_at + 60_000),
+            key_id: "test-key".to_string(),
+            renews: None,
+            signature: vec![7; 64],
+        }
+    }
+
+    /// Run every conformance check against a fresh store
+    pub(crate) async fn run(store: Arc<dyn AttestationStore>) {
+        crud(store.as_ref()).await;
+        pagination(store.as_ref()).await;
+        revocations(store.as_ref()).await;
+        key_history(store.as_ref()).await;
+        audit_log(store.as_ref()).await;
+        audit_records(store.as_ref()).await;
+        concurrent_writes(store).await;
+    }
+
+    async fn crud(store: &dyn AttestationStore) {
+        let att = attestation("att_crud", "crud-service", 1_000);
+
+        assert!(store.get("att_crud").await.unwrap().is_none());
+        store.put(&att).await.unwrap();
+
+        let loaded = store.get("att_crud").await.unwrap().unwrap();
+        assert_eq!(loaded.identity, "crud-service");
+        assert_eq!(loaded.signature, att.signature);
+
+        assert!(store.delete("att_crud").await.unwrap());
+        assert!(!store.delete("att_crud").await.unwrap());
+        assert!(store.get("att_crud").await.unwrap().is_none());
+        assert!(store
+            .list_by_identity("crud-service", Page::default())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    async fn pagination(store: &dyn AttestationStore) {
+        // Inserted out of order to check sorting by issued_at
+        for (id, issued_at) in [("att_p3", 3_000), ("att_p1", 1_000), ("att_p2", 2_000)] {
+            store
+                .put(&attestation(id, "paged-service", issued_at))
+                .await
+                .unwrap();
+        }
+        store
+            .put(&attestation("att_other", "other-service", 500))
+            .await
+            .unwrap();
+
+        let ids = |page: Vec<Attestation>| page.into_iter().map(|a| a.id).collect::<Vec<_>>();
+
+        let first = store
+            .list_by_identity("paged-service", Page::new(0, 2))
+            .await
+            .unwrap();
+        assert_eq!(ids(first), vec!["att_p1", "att_p2"]);
+
+        let second = store
+            .list_by_identity("paged-service", Page::new(2, 2))
+            .await
+            .unwrap();
+        assert_eq!(ids(second), vec!["att_p3"]);
+
+        let past_end = store
+            .list_by_identity("paged-service", Page::new(3, 2))
+            .await
+            .unwrap();
+        assert!(past_end.is_empty());
+    }
+
+    async fn revocations(store: &dyn AttestationStore) {
+        let later = RevocationEntry {
+            attestation_id: "att_r2".to_string(),
+            reason: "decommissioned".to_string(),
+            revoked_at: Timestamp::from_millis(2_000),
+        };
+        let earlier = RevocationEntry {
+            attestation_id: "att_r1".to_string(),
+            reason: "key_compromise".to_string(),
+            revoked_at: Timestamp::from_millis(1_000),
+        };
+        store.put_revocation(&later).await.unwrap();
+        store.put_revocation(&earlier).await.unwrap();
+
+        assert_eq!(store.get_revocation("att_r1").await.unwrap(), Some(earlier.clone()));
+        assert!(store.get_revocation("att_none").await.unwrap().is_none());
+        assert_eq!(store.list_revocations().await.unwrap(), vec![earlier, later]);
+    }
+
+    async fn key_history(store: &dyn AttestationStore) {
+        assert!(store.get_key_history().await.unwrap().is_none());
+
+        store.put_key_history(b"first").await.unwrap();
+        store.put_key_history(b"second").await.unwrap();
+        assert_eq!(store.get_key_history().await.unwrap(), Some(b"second".to_vec()));
+    }
+
+    async fn audit_log(store: &dyn AttestationStore) {
+        for (i, identity) in ["svc-b", "svc-a", "svc-c"].into_iter().enumerate() {
+            let decision = PolicyDecision {
+                allowed: i != 1,
+                rule: Some("rule".to_string()),
+                reason: None,
+                identity: identity.to_string(),
+                timestamp: Timestamp::from_millis(1_000),
+            };
+            store.append_audit(&decision).await.unwrap();
+        }
+
+        let identities = |page: Vec<PolicyDecision>| {
+            page.into_iter().map(|d| d.identity).collect::<Vec<_>>()
+        };
+        let all = store.list_audit(Page::default()).await.unwrap();
+        assert!(!all[1].allowed);
+        assert_eq!(identities(all), vec!["svc-b", "svc-a", "svc-c"]);
+        let tail = store.list_audit(Page::new(1, 5)).await.unwrap();
+        assert_eq!(identities(tail), vec!["svc-a", "svc-c"]);
+    }
+
+    async fn audit_records(store: &dyn AttestationStore) {
+        assert!(store.last_audit_record().await.unwrap().is_none());
+
+        let record = |index: u64, outcome: &str| AuditRecord {
+            index,
+            operation: AuditOperation::Issue,
+            actor: "tester".to_string(),
+            identity: Some("svc".to_string()),
+            attestation_id: None,
+            outcome: outcome.to_string(),
+            timestamp: Timestamp::from_millis(1_000),
+            prev_hash: [index as u8; 32],
+            hash: [0; 32],
+        };
+        // Out of order, including an index above 255 to check key ordering
+        for index in [1, 300, 0, 2] {
+            store.put_audit_record(&record(index, "issued")).await.unwrap();
+        }
+        store.put_audit_record(&record(2, "rejected")).await.unwrap();
+
+        let indices =
+            |page: Vec<AuditRecord>| page.into_iter().map(|r| r.index).collect::<Vec<_>>();
+        let all = store.list_audit_records(Page::default()).await.unwrap();
+        assert_eq!(all[2], record(2, "rejected"));
+        assert_eq!(indices(all), vec![0, 1, 2, 300]);
+        let tail = store.list_audit_records(Page::new(1, 2)).await.unwrap();
+        assert_eq!(indices(tail), vec![1, 2]);
+        assert_eq!(store.last_audit_record().await.unwrap().unwrap().index, 300);
+    }
+
+    async fn concurrent_writes(store: Arc<dyn AttestationStore>) {
+        let handles: Vec<_> = (0..32u64)
+            .map(|i| {
+                let store = Arc::clone(&store);
+                tokio::spawn(async move {
+                    store
+                        .put(&attestation(&format!("att_c{i:02}"), "busy-service", i))
+                        .await
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        let all = store
+            .list_by_identity("busy-service", Page::new(0, 100))
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 32);
+        assert!(all.windows(2).all(|w| w[0].issued_at <= w[1].issued_at));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_conformance() {
+        run(Arc::new(MemoryStore::new())).await;
+    }
+}
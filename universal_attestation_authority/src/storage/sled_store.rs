@@ -0,0 +1,288 @@
+//! Embedded sled backend
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use shared_core::{Result, SystemError};
+use sled::Transactional;
+
+use super::{sort_by_revoked_at, AttestationStore, Page, RevocationEntry};
+use crate::{audit::AuditRecord, Attestation, PolicyDecision};
+
+/// How long [`SledStore::open`] waits for a lock held by a closing database
+const LOCK_WAIT: Duration = Duration::from_secs(2);
+
+/// Store backed by an embedded sled database
+///
+/// Attestations are indexed by `identity \0 issued_at \0 id` so identity
+/// listings come back in issue order without a full scan.
+pub struct SledStore {
+    db: sled::Db,
+    attestations: sled::Tree,
+    identity_index: sled::Tree,
+    revocations: sled::Tree,
+    keys: sled::Tree,
+    audit: sled::Tree,
+    audit_records: sled::Tree,
+}
+
+impl SledStore {
+    /// Open (or create) a database in the given directory
+    ///
+    /// sled releases its file lock from background threads, so a database
+    /// closed moments ago in this process may still be locked; opening waits
+    /// up to [`LOCK_WAIT`] for it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let deadline = Instant::now() + LOCK_WAIT;
+        let db = loop {
+            match sled::open(path.as_ref()) {
+                Err(sled::Error::Io(e))
+                    if e.to_string().starts_with("could not acquire lock")
+                        && Instant::now() < deadline =>
+                {
+                    std::thread::sleep(Duration::from_millis(10));
+                },
+                result => break result.map_err(|e| db_error("open", e))?,
+            }
+        };
+        Self::from_db(&db)
+    }
+
+    /// Open a throwaway database that is removed on drop
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .map_err(|e| db_error("open", e))?;
+        Self::from_db(&db)
+    }
+
+    fn from_db(db: &sled::Db) -> Result<Self> {
+        let tree = |name: &str| db.open_tree(name).map_err(|e| db_error("open_tree", e));
+        Ok(Self {
+            db: db.clone(),
+            attestations: tree("attestations")?,
+            identity_index: tree("attestations_by_identity")?,
+            revocations: tree("revocations")?,
+            keys: tree("keys")?,
+            audit: tree("audit")?,
+            audit_records: tree("audit_records")?,
+        })
+    }
+}
+
+const KEY_HISTORY: &[u8] = b"history";
+
+fn db_error(operation: &str, err: impl std::fmt::Display) -> SystemError {
+    SystemError::Database {
+        operation: format!("sled {operation}"),
+        message: err.to_string(),
+    }
+}
+
+fn identity_prefix(identity: &str) -> Vec<u8> {
+    let mut key = identity.as_bytes().to_vec();
+    key.push(0);
+    key
+}
+
+fn index_key(attestation: &Attestation) -> Vec<u8> {
+    let mut key = identity_prefix(&attestation.identity);
+    key.extend_from_slice(&attestation.issued_at.as_millis().to_be_bytes());
+    key.push(0);
+    key.extend_from_slice(attestation.id.as_bytes());
+    key
+}
+
+#[async_trait]
+impl AttestationStore for SledStore {
+    async fn put(&self, attestation: &Attestation) -> Result<()> {
+        let value = serde_json::to_vec(attestation)?;
+        let new_index = index_key(attestation);
+
+        (&self.attestations, &self.identity_index)
+            .transaction(|(attestations, index)| {
+                if let Some(previous) = attestations.insert(attestation.id.as_bytes(), value.as_slice())? {
+                    if let Ok(previous) = serde_json::from_slice::<Attestation>(&previous) {
+                        index.remove(index_key(&previous))?;
+                    }
+                }
+                index.insert(new_index.as_slice(), attestation.id.as_bytes())?;
+                Ok(())
+            })
+            .map_err(|e: sled::transaction::TransactionError| db_error("put", e))
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Attestation>> {
+        self.attestations
+            .get(id.as_bytes())
+            .map_err(|e| db_error("get", e))?
+            .map(|bytes| Ok(serde_json::from_slice(&bytes)?))
+            .transpose()
+    }
+
+    async fn list_by_identity(&self, identity: &str, page: Page) -> Result<Vec<Attestation>> {
+        let mut results = Vec::new();
+
+        for entry in self
+            .identity_index
+            .scan_prefix(identity_prefix(identity))
+            .skip(page.offset)
+            .take(page.limit)
+        {
+            let (_, id) = entry.map_err(|e| db_error("scan", e))?;
+            if let Some(bytes) = self
+                .attestations
+                .get(&id)
+                .map_err(|e| db_error("get", e))?
+            {
+                results.push(serde_json::from_slice(&bytes)?);
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        (&self.attestations, &self.identity_index)
+            .transaction(|(attestations, index)| {
+                let Some(previous) = attestations.remove(id.as_bytes())? else {
+                    return Ok(false);
+                };
+                if let Ok(previous) = serde_json::from_slice::<Attestation>(&previous) {
+                    index.remove(index_key(&previous))?;
+                }
+                Ok(true)
+            })
+            .map_err(|e: sled::transaction::TransactionError| db_error("delete", e))
+    }
+
+    async fn put_revocation(&self, entry: &RevocationEntry) -> Result<()> {
+        let value = serde_json::to_vec(entry)?;
+        self.revocations
+            .insert(entry.attestation_id.as_bytes(), value)
+            .map_err(|e| db_error("put_revocation", e))?;
+        Ok(())
+    }
+
+    async fn get_revocation(&self, attestation_id: &str) -> Result<Option<RevocationEntry>> {
+        self.revocations
+            .get(attestation_id.as_bytes())
+            .map_err(|e| db_error("get_revocation", e))?
+            .map(|bytes| Ok(serde_json::from_slice(&bytes)?))
+            .transpose()
+    }
+
+    async fn list_revocations(&self) -> Result<Vec<RevocationEntry>> {
+        let mut entries = self
+            .revocations
+            .iter()
+            .values()
+            .map(|bytes| {
+                let bytes = bytes.map_err(|e| db_error("scan", e))?;
+                Ok(serde_json::from_slice(&bytes)?)
+            })
+            .collect::<Result<Vec<RevocationEntry>>>()?;
+        sort_by_revoked_at(&mut entries);
+        Ok(entries)
+    }
+
+    async fn put_key_history(&self, sealed: &[u8]) -> Result<()> {
+        self.keys
+            .insert(KEY_HISTORY, sealed)
+            .map_err(|e| db_error("put_key_history", e))?;
+        self.keys
+            .flush_async()
+            .await
+            .map_err(|e| db_error("flush", e))?;
+        Ok(())
+    }
+
+    async fn get_key_history(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .keys
+            .get(KEY_HISTORY)
+            .map_err(|e| db_error("get_key_history", e))?
+            .map(|bytes| bytes.to_vec()))
+    }
+
+    async fn append_audit(&self, decision: &PolicyDecision) -> Result<()> {
+        // Generated IDs are monotonic, so big-endian keys keep append order
+        let seq = self.db.generate_id().map_err(|e| db_error("generate_id", e))?;
+        self.audit
+            .insert(seq.to_be_bytes(), serde_json::to_vec(decision)?)
+            .map_err(|e| db_error("append_audit", e))?;
+        Ok(())
+    }
+
+    async fn list_audit(&self, page: Page) -> Result<Vec<PolicyDecision>> {
+        self.audit
+            .iter()
+            .values()
+            .skip(page.offset)
+            .take(page.limit)
+            .map(|bytes| {
+                let bytes = bytes.map_err(|e| db_error("scan", e))?;
+                Ok(serde_json::from_slice(&bytes)?)
+            })
+            .collect()
+    }
+
+    async fn put_audit_record(&self, record: &AuditRecord) -> Result<()> {
+        // Big-endian keys keep records in index order
+        self.audit_records
+            .insert(record.index.to_be_bytes(), serde_json::to_vec(record)?)
+            .map_err(|e| db_error("put_audit_record", e))?;
+        Ok(())
+    }
+
+    async fn list_audit_records(&self, page: Page) -> Result<Vec<AuditRecord>> {
+        self.audit_records
+            .iter()
+            .values()
+            .skip(page.offset)
+            .take(page.limit)
+            .map(|bytes| {
+                let bytes = bytes.map_err(|e| db_error("scan", e))?;
+                Ok(serde_json::from_slice(&bytes)?)
+            })
+            .collect()
+    }
+
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>> {
+        self.audit_records
+            .last()
+            .map_err(|e| db_error("last_audit_record", e))?
+            .map(|(_, bytes)| Ok(serde_json::from_slice(&bytes)?))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::storage::conformance;
+
+    #[tokio::test]
+    async fn test_sled_store_conformance() {
+        conformance::run(Arc::new(SledStore::temporary().unwrap())).await;
+    }
+
+    #[tokio::test]
+    async fn test_sled_store_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .put(&conformance::attestation("att_1", "svc", 1_000))
+            .await
+            .unwrap();
+        drop(store);
+
+        let reopened = SledStore::open(dir.path()).unwrap();
+        assert!(reopened.get("att_1").await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,274 @@
+//! SQLite backend (requires the `sqlite` feature)
+
+use async_trait::async_trait;
+use shared_core::{Result, SystemError};
+use sqlx::{sqlite::SqlitePoolOptions, Row, SqlitePool};
+use tokio::sync::OnceCell;
+
+use super::{AttestationStore, Page, RevocationEntry};
+use crate::{audit::AuditRecord, Attestation, PolicyDecision};
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS attestations (
+        id TEXT PRIMARY KEY,
+        identity TEXT NOT NULL,
+        issued_at INTEGER NOT NULL,
+        body TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS attestations_by_identity
+        ON attestations (identity, issued_at, id)",
+    "CREATE TABLE IF NOT EXISTS revocations (
+        attestation_id TEXT PRIMARY KEY,
+        revoked_at INTEGER NOT NULL,
+        body TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS key_history (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        sealed BLOB NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS audit_log (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        body TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS audit_records (
+        idx INTEGER PRIMARY KEY,
+        body TEXT NOT NULL
+    )",
+];
+
+/// Store backed by a SQLite database
+///
+/// The connection pool is created lazily and the schema is applied on first use.
+pub struct SqliteStore {
+    pool: SqlitePool,
+    schema: OnceCell<()>,
+}
+
+impl SqliteStore {
+    /// Create a store for the given connection URL
+    pub fn new(url: &str) -> Result<Self> {
+        // Every connection to `:memory:` opens a separate database
+        let max_connections = if url.contains(":memory:") { 1 } else { 8 };
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_lazy(url)
+            .map_err(|e| db_error("connect", e))?;
+
+        Ok(Self {
+            pool,
+            schema: OnceCell::new(),
+        })
+    }
+
+    async fn pool(&self) -> Result<&SqlitePool> {
+        self.schema
+            .get_or_try_init(|| async {
+                for statement in SCHEMA {
+                    sqlx::query(statement)
+                        .execute(&self.pool)
+                        .await
+                        .map_err(|e| db_error("migrate", e))?;
+                }
+                Ok::<_, SystemError>(())
+            })
+            .await?;
+        Ok(&self.pool)
+    }
+}
+
+fn db_error(operation: &str, err: impl std::fmt::Display) -> SystemError {
+    SystemError::Database {
+        operation: format!("sqlite {operation}"),
+        message: err.to_string(),
+    }
+}
+
+fn to_i64<T>(value: T, field: &str) -> Result<i64>
+where
+    T: Copy + std::fmt::Display + TryInto<i64>,
+{
+    value.try_into().map_err(|_| {
+        SystemError::validation(field, "out of range for SQLite", Some(value.to_string()))
+    })
+}
+
+#[async_trait]
+impl AttestationStore for SqliteStore {
+    async fn put(&self, attestation: &Attestation) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO attestations (id, identity, issued_at, body)
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(&attestation.id)
+        .bind(&attestation.identity)
+        .bind(to_i64(attestation.issued_at.as_millis(), "issued_at")?)
+        .bind(serde_json::to_string(attestation)?)
+        .execute(self.pool().await?)
+        .await
+        .map_err(|e| db_error("put", e))?;
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Attestation>> {
+        let row = sqlx::query("SELECT body FROM attestations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.pool().await?)
+            .await
+            .map_err(|e| db_error("get", e))?;
+
+        row.map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .transpose()
+    }
+
+    async fn list_by_identity(&self, identity: &str, page: Page) -> Result<Vec<Attestation>> {
+        let rows = sqlx::query(
+            "SELECT body FROM attestations WHERE identity = ?
+             ORDER BY issued_at, id LIMIT ? OFFSET ?",
+        )
+        .bind(identity)
+        .bind(to_i64(page.limit, "limit")?)
+        .bind(to_i64(page.offset, "offset")?)
+        .fetch_all(self.pool().await?)
+        .await
+        .map_err(|e| db_error("list_by_identity", e))?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .collect()
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM attestations WHERE id = ?")
+            .bind(id)
+            .execute(self.pool().await?)
+            .await
+            .map_err(|e| db_error("delete", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn put_revocation(&self, entry: &RevocationEntry) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO revocations (attestation_id, revoked_at, body)
+             VALUES (?, ?, ?)",
+        )
+        .bind(&entry.attestation_id)
+        .bind(to_i64(entry.revoked_at.as_millis(), "revoked_at")?)
+        .bind(serde_json::to_string(entry)?)
+        .execute(self.pool().await?)
+        .await
+        .map_err(|e| db_error("put_revocation", e))?;
+        Ok(())
+    }
+
+    async fn get_revocation(&self, attestation_id: &str) -> Result<Option<RevocationEntry>> {
+        let row = sqlx::query("SELECT body FROM revocations WHERE attestation_id = ?")
+            .bind(attestation_id)
+            .fetch_optional(self.pool().await?)
+            .await
+            .map_err(|e| db_error("get_revocation", e))?;
+
+        row.map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .transpose()
+    }
+
+    async fn list_revocations(&self) -> Result<Vec<RevocationEntry>> {
+        let rows = sqlx::query("SELECT body FROM revocations ORDER BY revoked_at, attestation_id")
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(|e| db_error("list_revocations", e))?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .collect()
+    }
+
+    async fn put_key_history(&self, sealed: &[u8]) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO key_history (id, sealed) VALUES (0, ?)")
+            .bind(sealed)
+            .execute(self.pool().await?)
+            .await
+            .map_err(|e| db_error("put_key_history", e))?;
+        Ok(())
+    }
+
+    async fn get_key_history(&self) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query("SELECT sealed FROM key_history WHERE id = 0")
+            .fetch_optional(self.pool().await?)
+            .await
+            .map_err(|e| db_error("get_key_history", e))?;
+
+        Ok(row.map(|row| row.get::<Vec<u8>, _>("sealed")))
+    }
+
+    async fn append_audit(&self, decision: &PolicyDecision) -> Result<()> {
+        sqlx::query("INSERT INTO audit_log (body) VALUES (?)")
+            .bind(serde_json::to_string(decision)?)
+            .execute(self.pool().await?)
+            .await
+            .map_err(|e| db_error("append_audit", e))?;
+        Ok(())
+    }
+
+    async fn list_audit(&self, page: Page) -> Result<Vec<PolicyDecision>> {
+        let rows = sqlx::query("SELECT body FROM audit_log ORDER BY seq LIMIT ? OFFSET ?")
+            .bind(to_i64(page.limit, "limit")?)
+            .bind(to_i64(page.offset, "offset")?)
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(|e| db_error("list_audit", e))?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .collect()
+    }
+
+    async fn put_audit_record(&self, record: &AuditRecord) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO audit_records (idx, body) VALUES (?, ?)")
+            .bind(to_i64(record.index, "index")?)
+            .bind(serde_json::to_string(record)?)
+            .execute(self.pool().await?)
+            .await
+            .map_err(|e| db_error("put_audit_record", e))?;
+        Ok(())
+    }
+
+    async fn list_audit_records(&self, page: Page) -> Result<Vec<AuditRecord>> {
+        let rows = sqlx::query("SELECT body FROM audit_records ORDER BY idx LIMIT ? OFFSET ?")
+            .bind(to_i64(page.limit, "limit")?)
+            .bind(to_i64(page.offset, "offset")?)
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(|e| db_error("list_audit_records", e))?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .collect()
+    }
+
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>> {
+        let row = sqlx::query("SELECT body FROM audit_records ORDER BY idx DESC LIMIT 1")
+            .fetch_optional(self.pool().await?)
+            .await
+            .map_err(|e| db_error("last_audit_record", e))?;
+
+        row.map(|row| Ok(serde_json::from_str(row.get::<&str, _>("body"))?))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::storage::conformance;
+
+    #[tokio::test]
+    async fn test_sqlite_store_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}?mode=rwc", dir.path().join("store.db").display());
+
+        conformance::run(Arc::new(SqliteStore::new(&url).unwrap())).await;
+    }
+}
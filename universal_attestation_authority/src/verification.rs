@@ -1 +1,143 @@
 //! Verification module
+//!
+//! Structured verification outcomes and validity window checks.
+
+use serde::{Deserialize, Serialize};
+use shared_core::Timestamp;
+
+use crate::{Attestation, VerificationKey};
+
+/// Result of verifying an attestation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VerificationOutcome {
+    /// Signature is valid and the attestation is within its validity window
+    Valid,
+    /// Attestation expired
+    Expired {
+        /// When the attestation expired
+        at: Timestamp,
+    },
+    /// Attestation is not valid yet
+    NotYetValid {
+        /// When the attestation becomes valid
+        at: Timestamp,
+    },
+    /// Signature does not match the attestation contents
+    BadSignature,
+    /// Attestation is not known to the authority's store
+    Unknown,
+    /// Attestation was revoked by the authority
+    Revoked {
+        /// Why the attestation was revoked
+        reason: String,
+        /// When the attestation was revoked
+        at: Timestamp,
+    },
+    /// Attestation was signed with an authority key that has been retired
+    KeyRetired {
+        /// ID of the retired key
+        key_id: String,
+        /// When the key was retired
+        at: Timestamp,
+    },
+}
+
+impl VerificationOutcome {
+    /// Whether the attestation verified successfully
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid)
+    }
+
+    /// Name of the outcome, as serialized in the `status` field
+    pub fn status(&self) -> &'static str {
+        match self {
+            Self::Valid => "valid",
+            Self::Expired { .. } => "expired",
+            Self::NotYetValid { .. } => "not_yet_valid",
+            Self::BadSignature => "bad_signature",
+            Self::Unknown => "unknown",
+            Self::Revoked { .. } => "revoked",
+            Self::KeyRetired { .. } => "key_retired",
+        }
+    }
+}
+
+/// Whose key verified an attestation, see [`VerificationResult`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifiedBy {
+    /// One of the authority's own keys
+    #[serde(rename = "self")]
+    Local,
+    /// The key of an authority registered with
+    /// [`AttestationAuthority::trust_authority`](crate::AttestationAuthority::trust_authority)
+    TrustedAuthority(String),
+}
+
+/// Result of [`AttestationAuthority::verify`](crate::AttestationAuthority::verify)
+///
+/// `verified_by` names the authority whose key the signature matched, or
+/// [`VerifiedBy::Local`] if none did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationResult {
+    /// Whether the attestation verified successfully
+    pub is_valid: bool,
+    /// Whose key the attestation was verified with
+    pub verified_by: VerifiedBy,
+    /// Detailed outcome
+    #[serde(flatten)]
+    pub outcome: VerificationOutcome,
+}
+
+impl VerificationResult {
+    /// Result of a check against the authority's own keys
+    pub(crate) fn local(outcome: VerificationOutcome) -> Self {
+        Self::new(outcome, VerifiedBy::Local)
+    }
+
+    pub(crate) fn new(outcome: VerificationOutcome, verified_by: VerifiedBy) -> Self {
+        Self {
+            is_valid: outcome.is_valid(),
+            verified_by,
+            outcome,
+        }
+    }
+}
+
+/// Check the validity window of an attestation at `now`, allowing for
+/// `skew_ms` milliseconds of clock skew on either side.
+pub(crate) fn check_validity_window(
+    attestation: &Attestation,
+    now: Timestamp,
+    skew_ms: u64,
+) -> VerificationOutcome {
+    let now = now.as_millis();
+
+    if now.saturating_add(skew_ms) < attestation.not_before.as_millis() {
+        return VerificationOutcome::NotYetValid {
+            at: attestation.not_before,
+        };
+    }
+
+    if now >= attestation.expires_at.as_millis().saturating_add(skew_ms) {
+        return VerificationOutcome::Expired {
+            at: attestation.expires_at,
+        };
+    }
+
+    VerificationOutcome::Valid
+}
+
+/// Check whether the signing key is retired at `now`
+pub(crate) fn check_key_retired(
+    key: &VerificationKey,
+    now: Timestamp,
+) -> Option<VerificationOutcome> {
+    key.retires_at
+        .filter(|_| key.is_retired_at(now))
+        .map(|at| VerificationOutcome::KeyRetired {
+            key_id: key.key_id.clone(),
+            at,
+        })
+}
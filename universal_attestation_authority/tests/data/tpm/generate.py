@@ -0,0 +1,98 @@
+#!/usr/bin/env python3
+"""Regenerate the TPM quote fixtures used by src/evidence.rs.
+
+Each case is a TPMS_ATTEST quote (<case>.quote), its TPMT_SIGNATURE
+(<case>.sig) and the raw values of PCRs 0, 2 and 7 (<case>.pcrs). The
+attestation keys are freshly generated, so every file changes on each run.
+"""
+
+import hashlib
+import os
+import struct
+
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, padding, rsa
+from cryptography.hazmat.primitives.asymmetric.utils import decode_dss_signature
+
+TPM_GENERATED_VALUE = 0xFF544347
+TPM_ST_ATTEST_QUOTE = 0x8018
+TPM_ALG_SHA256 = 0x000B
+TPM_ALG_RSASSA = 0x0014
+TPM_ALG_ECDSA = 0x0018
+
+PCRS = (0, 2, 7)
+NONCE = hashlib.sha256(b"uaa fixture nonce").digest()
+FIRMWARE_VERSION = (7 << 32) | 85
+OUT = os.path.dirname(os.path.abspath(__file__))
+
+
+def tpm2b(data):
+    return struct.pack(">H", len(data)) + data
+
+
+def pcr_values(boot_state):
+    return [hashlib.sha256(f"pcr{i} {boot_state}".encode()).digest() for i in PCRS]
+
+
+def quote(nonce, values):
+    select = bytearray(3)
+    for i in PCRS:
+        select[i // 8] |= 1 << (i % 8)
+    signer = struct.pack(">H", TPM_ALG_SHA256) + hashlib.sha256(b"ak").digest()
+    return (
+        struct.pack(">IH", TPM_GENERATED_VALUE, TPM_ST_ATTEST_QUOTE)
+        + tpm2b(signer)
+        + tpm2b(nonce)
+        + struct.pack(">QIIB", 123456, 2, 0, 1)
+        + struct.pack(">Q", FIRMWARE_VERSION)
+        + struct.pack(">IHB", 1, TPM_ALG_SHA256, len(select))
+        + bytes(select)
+        + tpm2b(hashlib.sha256(b"".join(values)).digest())
+    )
+
+
+def sign_ecdsa(key, attest):
+    r, s = decode_dss_signature(key.sign(attest, ec.ECDSA(hashes.SHA256())))
+    return struct.pack(">HH", TPM_ALG_ECDSA, TPM_ALG_SHA256) + tpm2b(
+        r.to_bytes(32, "big")
+    ) + tpm2b(s.to_bytes(32, "big"))
+
+
+def sign_rsa(key, attest):
+    signature = key.sign(attest, padding.PKCS1v15(), hashes.SHA256())
+    return struct.pack(">HH", TPM_ALG_RSASSA, TPM_ALG_SHA256) + tpm2b(signature)
+
+
+def write(name, data):
+    with open(os.path.join(OUT, name), "wb") as f:
+        f.write(data)
+
+
+def main():
+    ecdsa_key = ec.generate_private_key(ec.SECP256R1())
+    rsa_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+
+    write(
+        "ak_p256.pub",
+        ecdsa_key.public_key().public_bytes(
+            serialization.Encoding.X962, serialization.PublicFormat.UncompressedPoint
+        ),
+    )
+    write("ak_rsa.pub", rsa_key.public_key().public_numbers().n.to_bytes(256, "big"))
+
+    good = pcr_values("measured")
+    cases = {
+        "valid": (ecdsa_key, sign_ecdsa, NONCE, good),
+        "valid_rsa": (rsa_key, sign_rsa, NONCE, good),
+        "wrong_nonce": (ecdsa_key, sign_ecdsa, hashlib.sha256(b"stale").digest(), good),
+        "wrong_pcrs": (ecdsa_key, sign_ecdsa, NONCE, pcr_values("tampered")),
+    }
+    for case, (key, sign, nonce, values) in cases.items():
+        attest = quote(nonce, values)
+        write(f"{case}.quote", attest)
+        write(f"{case}.sig", sign(key, attest))
+        write(f"{case}.pcrs", b"".join(values))
+
+
+if __name__ == "__main__":
+    main()
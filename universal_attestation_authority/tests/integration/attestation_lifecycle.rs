@@ -11,6 +11,7 @@ async fn test_attestation_issue_and_verify() {
         identity: "test-service".to_string(),
         claims: serde_json::Map::new(),
         validity_seconds: 3600,
+        not_before: None,
     };
 
     // Issue attestation
@@ -21,7 +22,7 @@ async fn test_attestation_issue_and_verify() {
 
     // Verify attestation
     let is_valid = authority
-        .verify(&attestation)
+        .is_valid(&attestation)
         .await
         .expect("Failed to verify attestation");
 
@@ -40,6 +41,7 @@ async fn test_multiple_attestations() {
             identity: format!("service-{}", i),
             claims: serde_json::Map::new(),
             validity_seconds: 3600,
+            not_before: None,
         };
 
         let attestation = authority.issue(request).await.expect("Failed to issue");
@@ -48,7 +50,7 @@ async fn test_multiple_attestations() {
 
     // Verify all
     for attestation in &attestations {
-        let is_valid = authority.verify(attestation).await.expect("Failed to verify");
+        let is_valid = authority.is_valid(attestation).await.expect("Failed to verify");
         assert!(is_valid);
     }
 }